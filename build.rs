@@ -1,3 +1,89 @@
+//! Embeds the git commit and build date for `trinitychain::build_info`.
+//!
+//! Both come from the source tree rather than the clock so the same commit
+//! always builds the same binary: the date is `SOURCE_DATE_EPOCH` when set,
+//! otherwise the commit time. Builds from a tarball can pass the commit in
+//! `TRINITY_GIT_COMMIT`.
+
+use std::path::Path;
+use std::process::Command;
+
 fn main() {
-    // Build script - no warnings needed
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=TRINITY_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    watch_git_head();
+
+    let commit = std::env::var("TRINITY_GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let date = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .or_else(|| git(&["log", "-1", "--format=%ct"]))
+        .and_then(|secs| secs.trim().parse::<i64>().ok())
+        .map(format_utc)
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=TRINITY_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=TRINITY_BUILD_DATE={}", date);
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (!out.is_empty()).then_some(out)
+}
+
+/// Rebuild when HEAD moves. Only existing files are watched, since cargo
+/// reruns the script on every build for a missing one.
+fn watch_git_head() {
+    let head = Path::new(".git/HEAD");
+    if !head.exists() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Ok(contents) = std::fs::read_to_string(head) {
+        if let Some(reference) = contents.trim().strip_prefix("ref: ") {
+            let path = Path::new(".git").join(reference);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+    if Path::new(".git/packed-refs").exists() {
+        println!("cargo:rerun-if-changed=.git/packed-refs");
+    }
+}
+
+/// Format seconds since the epoch as an RFC 3339 UTC timestamp
+fn format_utc(secs: i64) -> String {
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
 }
@@ -101,9 +101,10 @@ class TrinityChainAPI {
   // WALLET ENDPOINTS
   // ============================================================================
 
-  async createWallet() {
+  async createWallet(passphrase, name) {
     return this.request('/api/wallet/create', {
       method: 'POST',
+      body: JSON.stringify({ passphrase, name }),
     });
   }
 
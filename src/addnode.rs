@@ -0,0 +1,126 @@
+//! Persistent "addnode" list
+//!
+//! Peers added by an operator at runtime are remembered in `addnodes.json`
+//! in the data directory and reconnected on every start, alongside the
+//! bootstrap peers from the config file.
+
+use crate::error::ChainError;
+use crate::network::Node;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the addnode list inside the data directory
+pub const ADDNODE_FILE: &str = "addnodes.json";
+
+/// Operator-managed peers, optionally backed by a file rewritten on change
+#[derive(Debug, Default)]
+pub struct AddNodeList {
+    path: Option<PathBuf>,
+    nodes: Vec<Node>,
+}
+
+impl AddNodeList {
+    /// Create an in-memory list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the list stored at `path`; a missing file yields an empty list
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let nodes = if path.exists() {
+            let content = fs::read_to_string(path)?;
+            serde_json::from_str(&content).map_err(|e| {
+                ChainError::DatabaseError(format!("Invalid addnode list {}: {}", path.display(), e))
+            })?
+        } else {
+            Vec::new()
+        };
+        Ok(AddNodeList {
+            path: Some(path.to_path_buf()),
+            nodes,
+        })
+    }
+
+    /// Load `addnodes.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(ADDNODE_FILE))
+    }
+
+    fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let json = serde_json::to_string_pretty(&self.nodes).map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to serialize addnode list: {}", e))
+        })?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Remember `node`. Returns `false` if it was already listed.
+    pub fn add(&mut self, node: Node) -> Result<bool, ChainError> {
+        if self.nodes.contains(&node) {
+            return Ok(false);
+        }
+        self.nodes.push(node);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Forget `node`. Returns `false` if it was not listed.
+    pub fn remove(&mut self, node: &Node) -> Result<bool, ChainError> {
+        let before = self.nodes.len();
+        self.nodes.retain(|n| n != node);
+        if self.nodes.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+}
+
+/// Parse `host:port`, `[v6]:port` or `<onion>.onion:port` into a peer address
+pub fn parse_peer_address(input: &str) -> Result<Node, ChainError> {
+    input.parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addnodes_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut list = AddNodeList::load_from_data_dir(dir.path()).unwrap();
+        let node = parse_peer_address("seed.example.org:8333").unwrap();
+        assert!(list.add(node.clone()).unwrap());
+        assert!(!list.add(node.clone()).unwrap());
+
+        let mut reloaded = AddNodeList::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.nodes().len(), 1);
+        assert_eq!(reloaded.nodes()[0], node);
+        assert!(reloaded.remove(&node).unwrap());
+        assert!(AddNodeList::load_from_data_dir(dir.path()).unwrap().nodes().is_empty());
+    }
+
+    #[test]
+    fn test_parse_peer_address() {
+        assert_eq!(
+            parse_peer_address("[::1]:8333").unwrap(),
+            Node::new("::1".to_string(), 8333)
+        );
+        assert!(parse_peer_address("localhost").is_err());
+        assert!(parse_peer_address("localhost:http").is_err());
+    }
+}
@@ -540,11 +540,10 @@ fn validate_notes(notes: &str) -> Result<(), ChainError> {
 
 // Helper functions for default paths
 
-/// Get the default address book path
+/// Get the default address book path, in the data directory
 pub fn get_addressbook_path() -> PathBuf {
-    dirs::home_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join(".trinitychain")
+    crate::config::prepare_data_dir()
+        .unwrap_or_else(|_| crate::config::data_dir())
         .join("addressbook.json")
 }
 
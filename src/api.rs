@@ -4,46 +4,74 @@
 //! mining control, network management, and wallet operations.
 
 use axum::{
-    extract::{Path, Query, Request, State},
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, Request, State},
     http::{self, StatusCode},
     middleware::{self, Next},
-    response::{IntoResponse, Response},
-    routing::{get, post},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{delete, get, post},
     Json, Router,
 };
 use hex::decode_to_slice;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::services::ServeDir;
+use tower_http::timeout::TimeoutLayer;
 
-use crate::blockchain::{Block, Blockchain, Sha256Hash};
-use crate::crypto::KeyPair;
+use crate::audit::{self, AuditQuery, ExportFormat, TransactionAuditLog};
+use crate::txtrace::{self, HopReport};
+use crate::blockchain::{Block, BlockHeader, Blockchain, Sha256Hash, SpentArchive, TimeRange, TipStatus, UtxoTree, SIZE_BUCKET_BOUNDS};
+use crate::build_info;
+use crate::crypto::{self, KeyPair};
 use crate::error::ChainError;
-use crate::geometry::Coord;
-use crate::miner;
+use crate::geometry::{Coord, Triangle};
+use crate::mempool::{MempoolSnapshot, RestoreReport};
+use crate::miner::{self, HashRateStats, LocalPriority, MinerThrottle, TemplateRefresh};
 use crate::network::NetworkNode;
+use crate::peerstats::{self, SAMPLE_INTERVAL};
+use crate::persistence::Persistence;
+use crate::qr::{self, PaymentRequest};
+use crate::spending::{self, Approval, SpendingLedger, SpendingPolicy};
+use crate::telemetry::Telemetry;
 use crate::transaction::{CoinbaseTx, Transaction};
+use crate::units::{self, Unit};
+use crate::wallet::{EncryptedWallet, Wallet};
+use crate::watchlist::{Notifier, Subscriber, Watch};
 
 // API Configuration
 const DEFAULT_API_PORT: u16 = 3000;
 
-// Reserved for future use
-#[allow(dead_code)]
+/// Default for [`Node::with_request_limits`]
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
-#[allow(dead_code)]
+/// Default for [`Node::with_request_limits`]
 const MAX_REQUEST_BODY_SIZE: usize = 10 * 1024 * 1024; // 10 MB
-#[allow(dead_code)]
+
+/// Requests a client may make to a rate-limited endpoint per window
 const RATE_LIMIT_REQUESTS: u32 = 100;
-#[allow(dead_code)]
 const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
 
+/// Default lifetime of an unlocked wallet session, unless configured
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(300);
+/// Upper bound on the lifetime a client may request for a wallet session,
+/// unless configured
+const MAX_SESSION_TTL: Duration = Duration::from_secs(3600);
+/// How long `/api/health` waits for the chain lock before reporting the
+/// node hung
+const LIVENESS_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+/// Minimum passphrase length accepted when creating a server-side keystore
+const MIN_PASSPHRASE_LENGTH: usize = 8;
+
 /// Node state with mining capabilities
 #[derive(Clone)]
 pub struct Node {
@@ -54,7 +82,68 @@ pub struct Node {
     is_mining: Arc<AtomicBool>,
     blocks_mined: Arc<AtomicU64>,
     mining_task: Arc<RwLock<Option<JoinHandle<()>>>>,
+    /// Hashing statistics of the built-in miner, kept across runs
+    hash_stats: Arc<std::sync::Mutex<HashRateStats>>,
+    /// When the built-in miner rebuilds the block it is working on
+    template_refresh: TemplateRefresh,
+    /// CPU share and pause schedule of the built-in miner
+    throttle: Arc<MinerThrottle>,
+    /// Senders the built-in miner includes regardless of fee
+    local_priority: Arc<LocalPriority>,
     api_stats: Arc<RwLock<ApiStats>>,
+    /// Encrypted keystore entries indexed by hex address
+    keystore: Arc<RwLock<HashMap<String, EncryptedWallet>>>,
+    /// Directory where keystore entries are persisted (in-memory only when unset)
+    keystore_dir: Option<PathBuf>,
+    /// Unlocked wallet sessions indexed by session token
+    wallet_sessions: Arc<RwLock<HashMap<String, WalletSession>>>,
+    /// Lifetime of a session that asks for none, and the longest allowed
+    session_ttl: (Duration, Duration),
+    /// Bearer token for operator endpoints; they are disabled when unset
+    admin_token: Option<String>,
+    /// Telemetry settings, shown by `/api/telemetry/preview`
+    telemetry: Telemetry,
+    /// Largest accepted request body
+    max_body_bytes: usize,
+    /// Time a request may take before it is answered with `408`
+    request_timeout: Duration,
+    /// Limits applied when a wallet session signs a payment
+    spending_policy: SpendingPolicy,
+    spending_ledger: Arc<RwLock<SpendingLedger>>,
+    /// Address watchlists for webhooks and `/api/watchlist/events`
+    notifier: Arc<Notifier>,
+    /// The node's database, for stored series like block metrics; the
+    /// chain's own persistence is used when unset
+    persistence: Option<Arc<Box<dyn Persistence>>>,
+    /// Every transaction accepted by `POST /api/transaction`
+    audit_log: Arc<TransactionAuditLog>,
+    /// UTXO tree of the last tip a balance proof was asked for
+    utxo_tree: Arc<tokio::sync::Mutex<Option<Arc<TipUtxoTree>>>>,
+    /// Balance proof requests per client
+    proof_requests: Arc<std::sync::Mutex<RateLimiter>>,
+}
+
+/// UTXO tree of one tip, shared by balance proofs until the tip moves
+struct TipUtxoTree {
+    height: u64,
+    block_hash: Sha256Hash,
+    tree: UtxoTree,
+}
+
+/// An unlocked wallet held in memory for a limited time.
+///
+/// The decrypted keypair never leaves the server; clients only hold the
+/// opaque session token.
+struct WalletSession {
+    address: String,
+    keypair: KeyPair,
+    expires_at: Instant,
+}
+
+impl WalletSession {
+    fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
 }
 
 /// API statistics and monitoring
@@ -87,14 +176,12 @@ impl ApiStats {
     }
 }
 
-/// Rate limiter for API endpoints (reserved for future implementation)
-#[allow(dead_code)]
+/// Per-client request counts of an expensive public endpoint
 #[derive(Debug)]
 struct RateLimiter {
     requests: HashMap<String, (u32, Instant)>,
 }
 
-#[allow(dead_code)]
 impl RateLimiter {
     fn new() -> Self {
         RateLimiter {
@@ -142,7 +229,26 @@ impl Node {
             is_mining: Arc::new(AtomicBool::new(false)),
             blocks_mined: Arc::new(AtomicU64::new(0)),
             mining_task: Arc::new(RwLock::new(None)),
+            hash_stats: Arc::new(std::sync::Mutex::new(HashRateStats::default())),
+            template_refresh: TemplateRefresh::default(),
+            throttle: Arc::new(MinerThrottle::default()),
+            local_priority: Arc::new(LocalPriority::default()),
             api_stats: Arc::new(RwLock::new(ApiStats::new())),
+            keystore: Arc::new(RwLock::new(HashMap::new())),
+            keystore_dir: None,
+            wallet_sessions: Arc::new(RwLock::new(HashMap::new())),
+            session_ttl: (DEFAULT_SESSION_TTL, MAX_SESSION_TTL),
+            admin_token: None,
+            telemetry: Telemetry::default(),
+            max_body_bytes: MAX_REQUEST_BODY_SIZE,
+            request_timeout: REQUEST_TIMEOUT,
+            spending_policy: SpendingPolicy::default(),
+            spending_ledger: Arc::new(RwLock::new(SpendingLedger::new())),
+            notifier: Arc::new(Notifier::default()),
+            persistence: None,
+            audit_log: Arc::new(TransactionAuditLog::new()),
+            utxo_tree: Arc::new(tokio::sync::Mutex::new(None)),
+            proof_requests: Arc::new(std::sync::Mutex::new(RateLimiter::new())),
         }
     }
 
@@ -162,10 +268,110 @@ impl Node {
             is_mining: Arc::new(AtomicBool::new(false)),
             blocks_mined: Arc::new(AtomicU64::new(0)),
             mining_task: Arc::new(RwLock::new(None)),
+            hash_stats: Arc::new(std::sync::Mutex::new(HashRateStats::default())),
+            template_refresh: TemplateRefresh::default(),
+            throttle: Arc::new(MinerThrottle::default()),
+            local_priority: Arc::new(LocalPriority::default()),
             api_stats: Arc::new(RwLock::new(ApiStats::new())),
+            keystore: Arc::new(RwLock::new(HashMap::new())),
+            keystore_dir: None,
+            wallet_sessions: Arc::new(RwLock::new(HashMap::new())),
+            session_ttl: (DEFAULT_SESSION_TTL, MAX_SESSION_TTL),
+            admin_token: None,
+            telemetry: Telemetry::default(),
+            max_body_bytes: MAX_REQUEST_BODY_SIZE,
+            request_timeout: REQUEST_TIMEOUT,
+            spending_policy: SpendingPolicy::default(),
+            spending_ledger: Arc::new(RwLock::new(SpendingLedger::new())),
+            notifier: Arc::new(Notifier::default()),
+            persistence: None,
+            audit_log: Arc::new(TransactionAuditLog::new()),
+            utxo_tree: Arc::new(tokio::sync::Mutex::new(None)),
+            proof_requests: Arc::new(std::sync::Mutex::new(RateLimiter::new())),
         }
     }
 
+    /// Require `token` as a bearer token on operator endpoints. Without a
+    /// token those endpoints answer `403`.
+    pub fn with_admin_token(mut self, token: Option<String>) -> Self {
+        self.admin_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
+    /// Refuse request bodies over `max_body_bytes` with `413` and answer
+    /// requests that run longer than `timeout` with `408`
+    pub fn with_request_limits(mut self, max_body_bytes: usize, timeout: Duration) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Open wallet sessions for `default` unless the client asks for
+    /// another lifetime, and for at most `max`
+    pub fn with_session_ttl(mut self, default: Duration, max: Duration) -> Self {
+        self.session_ttl = (default.min(max), max);
+        self
+    }
+
+    /// Telemetry settings of the node, for the preview endpoint
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    /// Rebuild the block being mined according to `refresh`
+    pub fn with_template_refresh(mut self, refresh: TemplateRefresh) -> Self {
+        self.template_refresh = refresh;
+        self
+    }
+
+    /// Throttle the built-in miner with `throttle`, typically shared with
+    /// the node's embedded miner so both follow the same settings
+    pub fn with_miner_throttle(mut self, throttle: Arc<MinerThrottle>) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Include transactions from these senders in every block the built-in
+    /// miner assembles, sharing the counters with the embedded miner
+    pub fn with_local_priority(mut self, priority: Arc<LocalPriority>) -> Self {
+        self.local_priority = priority;
+        self
+    }
+
+    /// Persist keystore entries created through the API in `dir`.
+    pub fn with_keystore_dir(mut self, dir: PathBuf) -> Self {
+        self.keystore_dir = Some(dir);
+        self
+    }
+
+    /// Enforce `policy` when wallet sessions sign payments, counting them in
+    /// `ledger` (typically the same disk-backed one `trinity-send` uses)
+    pub fn with_spending_limits(mut self, policy: SpendingPolicy, ledger: SpendingLedger) -> Self {
+        self.spending_policy = policy;
+        self.spending_ledger = Arc::new(RwLock::new(ledger));
+        self
+    }
+
+    /// Serve watchlists from `notifier`, which the caller keeps polling
+    pub fn with_notifier(mut self, notifier: Arc<Notifier>) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Read stored series such as block metrics from `persistence`
+    pub fn with_persistence(mut self, persistence: Arc<Box<dyn Persistence>>) -> Self {
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Record submitted transactions in `log`, typically the file-backed
+    /// one the wallet tools append to as well
+    pub fn with_audit_log(mut self, log: TransactionAuditLog) -> Self {
+        self.audit_log = Arc::new(log);
+        self
+    }
+
     /// Check if currently mining
     pub fn is_mining(&self) -> bool {
         self.is_mining.load(Ordering::Relaxed)
@@ -176,6 +382,11 @@ impl Node {
         self.blocks_mined.load(Ordering::Relaxed)
     }
 
+    /// Hashing statistics of the built-in miner
+    pub fn hash_stats(&self) -> HashRateStats {
+        self.hash_stats.lock().map(|stats| stats.clone()).unwrap_or_default()
+    }
+
     /// Start mining with proper validation and error handling
     pub async fn start_mining(&self, miner_address: String) -> Result<(), ApiError> {
         // Validate address format
@@ -206,82 +417,65 @@ impl Node {
 
         let node_clone = self.clone();
         let task = tokio::spawn(async move {
-            println!("Mining started for address: {}", miner_address);
+            tracing::info!("Mining started for address: {}", miner_address);
 
             loop {
                 if !node_clone.is_mining.load(Ordering::Relaxed) {
                     break;
                 }
 
-                let new_block = {
+                let template = {
                     let bc = node_clone.blockchain.read().await;
-
-                    if bc.blocks.is_empty() {
-                        eprintln!("Cannot mine without a genesis block.");
-                        break;
-                    }
-
-                    let last_block = match bc.blocks.last() {
-                        Some(b) => b.clone(),
-                        None => {
-                            eprintln!("Cannot determine last block: chain appears empty");
+                    match mining_template(&bc, address, &node_clone.local_priority) {
+                        Ok(block) => block,
+                        Err(e) => {
+                            tracing::warn!("Cannot build block template: {}", e);
                             break;
                         }
-                    };
-                    let transactions = bc.mempool.get_all_transactions();
-                    let height = bc.blocks.len() as u64;
-                    let reward = Blockchain::calculate_block_reward(height);
-
-                    let mut address = [0u8; 32];
-                    if let Err(e) = hex::decode_to_slice(&miner_address, &mut address) {
-                        eprintln!("Invalid miner address while mining: {}", e);
-                        break;
                     }
-
-                    let coinbase_tx = Transaction::Coinbase(CoinbaseTx {
-                        reward_area: Coord::from_num(reward),
-                        beneficiary_address: address,
-                        nonce: 0,
-                    });
-
-                    let mut all_txs = vec![coinbase_tx];
-                    all_txs.extend(transactions);
-
-                    Some(Block::new(
-                        height,
-                        last_block.hash(),
-                        bc.difficulty,
-                        all_txs,
-                    ))
                 };
 
-                if let Some(block) = new_block {
-                    match miner::mine_block(block) {
-                        Ok(mined_block) => {
-                            let mut bc = node_clone.blockchain.write().await;
-                            match bc.apply_block(mined_block.clone()) {
-                                Ok(_) => {
-                                    node_clone.blocks_mined.fetch_add(1, Ordering::SeqCst);
-                                    node_clone.network.broadcast_block(&mined_block).await;
-                                    println!(
-                                        "✅ Successfully mined block at height {}",
-                                        mined_block.header.height
-                                    );
-                                }
-                                Err(e) => {
-                                    eprintln!("❌ Mined block was invalid: {}", e);
-                                    // Continue mining despite this error
-                                }
-                            }
+                let refresh = node_clone.template_refresh;
+                let pow = node_clone.blockchain.read().await.consensus.pow;
+                let mined = miner::mine_with_refresh(template, pow, &node_clone.hash_stats, &node_clone.throttle, |template, age| {
+                    let blockchain = node_clone.blockchain.clone();
+                    let priority = node_clone.local_priority.clone();
+                    let previous_hash = template.header.previous_hash;
+                    let template_txs = template.transactions.len().saturating_sub(1);
+                    async move {
+                        let bc = blockchain.read().await;
+                        let tip_moved = bc.blocks.last().map(|b| b.hash()) != Some(previous_hash);
+                        if !tip_moved && !refresh.is_due(age, template_txs, bc.mempool.len()) {
+                            return None;
                         }
-                        Err(e) => {
-                            eprintln!("Mining error: {}", e);
-                            // Small delay before retrying
-                            tokio::time::sleep(Duration::from_secs(1)).await;
+                        mining_template(&bc, address, &priority).ok()
+                    }
+                })
+                .await;
+
+                match mined {
+                    Ok(mined_block) => {
+                        let mut bc = node_clone.blockchain.write().await;
+                        match bc.apply_block(mined_block.clone()) {
+                            Ok(_) => {
+                                node_clone.blocks_mined.fetch_add(1, Ordering::SeqCst);
+                                node_clone.network.broadcast_block(&mined_block).await;
+                                tracing::info!(
+                                    "✅ Successfully mined block at height {}",
+                                    mined_block.header.height
+                                );
+                            }
+                            Err(e) => {
+                                tracing::error!("❌ Mined block was invalid: {}", e);
+                                // Continue mining despite this error
+                            }
                         }
                     }
-                } else {
-                    break;
+                    Err(e) => {
+                        tracing::warn!("Mining error: {}", e);
+                        // Small delay before retrying
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
                 }
 
                 // Small delay between mining attempts
@@ -289,7 +483,7 @@ impl Node {
             }
 
             node_clone.is_mining.store(false, Ordering::SeqCst);
-            println!("Mining has stopped.");
+            tracing::info!("Mining has stopped.");
         });
 
         *self.mining_task.write().await = Some(task);
@@ -312,7 +506,7 @@ impl Node {
             stats.mining_stops += 1;
         }
 
-        println!("Stopping mining...");
+        tracing::info!("Stopping mining...");
 
         if let Some(task) = self.mining_task.write().await.take() {
             // Give the task a moment to stop gracefully
@@ -320,7 +514,7 @@ impl Node {
 
             if !task.is_finished() {
                 task.abort();
-                println!("Mining task aborted.");
+                tracing::info!("Mining task aborted.");
             }
         }
 
@@ -344,6 +538,289 @@ impl Node {
             is_mining: self.is_mining(),
         }
     }
+
+    /// Create a new wallet whose secret key is only ever stored encrypted
+    /// under `passphrase`. The passphrase itself is never retained.
+    pub async fn create_keystore_wallet(
+        &self,
+        name: Option<String>,
+        passphrase: &str,
+    ) -> Result<EncryptedWallet, ApiError> {
+        if passphrase.len() < MIN_PASSPHRASE_LENGTH {
+            return Err(ApiError::InvalidInput(format!(
+                "Passphrase must be at least {} characters",
+                MIN_PASSPHRASE_LENGTH
+            )));
+        }
+
+        let wallet = Wallet::new(name)?;
+        let encrypted = EncryptedWallet::from_wallet(&wallet, passphrase)?;
+
+        if let Some(dir) = &self.keystore_dir {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ApiError::InternalError(format!("Failed to create keystore directory: {}", e))
+            })?;
+            encrypted.save(&dir.join(format!("{}.json", encrypted.address)))?;
+        }
+
+        self.keystore
+            .write()
+            .await
+            .insert(encrypted.address.clone(), encrypted.clone());
+
+        Ok(encrypted)
+    }
+
+    /// UTXO tree of the tip, built once per tip: building it sorts and
+    /// hashes the whole set under the chain's read lock
+    async fn tip_utxo_tree(&self) -> Result<Arc<TipUtxoTree>, ApiError> {
+        let mut cached = self.utxo_tree.lock().await;
+        let blockchain = self.blockchain.read().await;
+        let tip = blockchain
+            .blocks
+            .last()
+            .ok_or_else(|| ApiError::InternalError("Chain has no blocks".to_string()))?;
+        let block_hash = tip.hash();
+        if let Some(tree) = cached.as_ref().filter(|tree| tree.block_hash == block_hash) {
+            return Ok(tree.clone());
+        }
+        let tree = Arc::new(TipUtxoTree {
+            height: tip.header.height,
+            block_hash,
+            tree: UtxoTree::build(&blockchain.state),
+        });
+        *cached = Some(tree.clone());
+        Ok(tree)
+    }
+
+    /// Look up a keystore entry, falling back to the on-disk keystore.
+    /// `address` must be a hex address, so it never names a file outside the
+    /// keystore directory.
+    async fn find_keystore_entry(&self, address: &str) -> Result<Option<EncryptedWallet>, ApiError> {
+        let address = crate::crypto::address_from_hex(address)
+            .map(hex::encode)
+            .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+        if let Some(entry) = self.keystore.read().await.get(&address) {
+            return Ok(Some(entry.clone()));
+        }
+
+        let Some(dir) = self.keystore_dir.as_ref() else {
+            return Ok(None);
+        };
+        let Ok(entry) = EncryptedWallet::load(&dir.join(format!("{}.json", address))) else {
+            return Ok(None);
+        };
+        self.keystore.write().await.insert(address, entry.clone());
+        Ok(Some(entry))
+    }
+
+    /// Decrypt a keystore entry and open a time-limited signing session.
+    /// Returns the session token and its lifetime in seconds.
+    pub async fn unlock_wallet(
+        &self,
+        encrypted: &EncryptedWallet,
+        passphrase: &str,
+        ttl: Option<Duration>,
+    ) -> Result<(String, u64), ApiError> {
+        let wallet = encrypted.decrypt(passphrase).map_err(|_| {
+            ApiError::Unauthorized("Invalid passphrase for wallet".to_string())
+        })?;
+        let keypair = wallet.get_keypair()?;
+        if hex::encode(keypair.address()) != wallet.address {
+            return Err(ApiError::InvalidInput(
+                "Keystore address does not match its secret key".to_string(),
+            ));
+        }
+
+        let (default_ttl, max_ttl) = self.session_ttl;
+        let ttl = ttl.unwrap_or(default_ttl).min(max_ttl);
+        let mut token_bytes = [0u8; 32];
+        rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut token_bytes);
+        let token = hex::encode(token_bytes);
+
+        let mut sessions = self.wallet_sessions.write().await;
+        sessions.retain(|_, s| !s.is_expired());
+        sessions.insert(
+            token.clone(),
+            WalletSession {
+                address: wallet.address,
+                keypair,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+
+        Ok((token, ttl.as_secs()))
+    }
+
+    /// Close a signing session. Returns whether the session existed.
+    pub async fn lock_wallet(&self, token: &str) -> bool {
+        self.wallet_sessions.write().await.remove(token).is_some()
+    }
+
+    /// Open sessions, soonest to expire first, without their tokens
+    pub async fn wallet_sessions(&self) -> Vec<WalletSessionInfo> {
+        let mut sessions = self.wallet_sessions.write().await;
+        sessions.retain(|_, s| !s.is_expired());
+        let mut info: Vec<_> = sessions
+            .values()
+            .map(|session| WalletSessionInfo {
+                address: session.address.clone(),
+                expires_in_secs: session.expires_at.saturating_duration_since(Instant::now()).as_secs(),
+            })
+            .collect();
+        info.sort_by_key(|session| session.expires_in_secs);
+        info
+    }
+
+    /// Close every session, or those of `address`. Returns how many were
+    /// open.
+    pub async fn revoke_wallet_sessions(&self, address: Option<&str>) -> usize {
+        let mut sessions = self.wallet_sessions.write().await;
+        sessions.retain(|_, s| !s.is_expired());
+        let open = sessions.len();
+        sessions.retain(|_, s| address.is_some_and(|a| !s.address.eq_ignore_ascii_case(a)));
+        open - sessions.len()
+    }
+
+    /// Run `f` against a live session, dropping it if it has expired.
+    async fn with_session<T>(
+        &self,
+        token: &str,
+        f: impl FnOnce(&WalletSession) -> Result<T, ApiError>,
+    ) -> Result<T, ApiError> {
+        let mut sessions = self.wallet_sessions.write().await;
+        match sessions.get(token) {
+            Some(s) if !s.is_expired() => f(s),
+            Some(_) => {
+                sessions.remove(token);
+                Err(ApiError::Unauthorized(
+                    "Wallet session has expired".to_string(),
+                ))
+            }
+            None => Err(ApiError::Unauthorized(
+                "Unknown wallet session".to_string(),
+            )),
+        }
+    }
+
+    /// Sign `tx` with the keypair held by an unlocked session, within the
+    /// spending limits. `confirmed` acknowledges a payment above the
+    /// confirmation threshold.
+    pub async fn sign_with_session(
+        &self,
+        token: &str,
+        tx: Transaction,
+        confirmed: bool,
+    ) -> Result<Transaction, ApiError> {
+        let spent = spending::amount_spent(std::slice::from_ref(&tx));
+        self.sign_counting(token, tx, confirmed, spent).await
+    }
+
+    /// [`sign_with_session`](Self::sign_with_session), counting `spent`
+    /// against the spending limits instead of everything `tx` pays
+    async fn sign_counting(
+        &self,
+        token: &str,
+        tx: Transaction,
+        confirmed: bool,
+        spent: Coord,
+    ) -> Result<Transaction, ApiError> {
+        let address = self
+            .with_session(token, |session| Ok(session.address.clone()))
+            .await?;
+        let mut ledger = self.spending_ledger.write().await;
+        let approval = ledger.check(&self.spending_policy, &address, spent)?;
+        if approval == Approval::NeedsConfirmation && !confirmed {
+            return Err(ApiError::Forbidden(format!(
+                "Paying {} needs confirmation; resend with \"confirm\": true",
+                units::format_amount(spent, Unit::Trc)
+            )));
+        }
+
+        let chain_id = self.blockchain.read().await.chain_id();
+        let signed = self
+            .with_session(token, |session| Self::sign_transaction_as(session, tx, &chain_id))
+            .await?;
+        if spent > Coord::from_num(0) {
+            ledger.record(&address, spent, crate::security::current_timestamp())?;
+        }
+        Ok(signed)
+    }
+
+    /// Sign an off-chain message proving control of the session's address.
+    /// Returns the address and the signature.
+    pub async fn sign_message_with_session(
+        &self,
+        token: &str,
+        message: &str,
+    ) -> Result<(String, String), ApiError> {
+        self.with_session(token, |session| {
+            Ok((session.address.clone(), session.keypair.sign_message(message)?))
+        })
+        .await
+    }
+
+    fn sign_transaction_as(
+        session: &WalletSession,
+        tx: Transaction,
+        chain_id: &Sha256Hash,
+    ) -> Result<Transaction, ApiError> {
+        let signer = session.keypair.address();
+        let public_key = session.keypair.public_key_bytes().to_vec();
+
+        match tx {
+            Transaction::Transfer(mut transfer) => {
+                if transfer.sender != signer {
+                    return Err(ApiError::InvalidInput(format!(
+                        "Transaction sender does not match unlocked wallet {}",
+                        session.address
+                    )));
+                }
+                let signature = session.keypair.sign(&transfer.signable_message(chain_id))?;
+                transfer.sign(signature.to_vec(), public_key);
+                Ok(Transaction::Transfer(transfer))
+            }
+            Transaction::Subdivision(mut subdivision) => {
+                if subdivision.owner_address != signer {
+                    return Err(ApiError::InvalidInput(format!(
+                        "Transaction owner does not match unlocked wallet {}",
+                        session.address
+                    )));
+                }
+                let signature = session.keypair.sign(&subdivision.signable_message(chain_id))?;
+                subdivision.sign(signature.to_vec(), public_key);
+                Ok(Transaction::Subdivision(subdivision))
+            }
+            Transaction::Register(mut register) => {
+                if register.owner != signer {
+                    return Err(ApiError::InvalidInput(format!(
+                        "Name owner does not match unlocked wallet {}",
+                        session.address
+                    )));
+                }
+                let signature = session.keypair.sign(&register.signable_message(chain_id))?;
+                register.sign(signature.to_vec(), public_key);
+                Ok(Transaction::Register(register))
+            }
+            Transaction::Data(mut data) => {
+                if data.owner != signer {
+                    return Err(ApiError::InvalidInput(format!(
+                        "Data transaction owner does not match unlocked wallet {}",
+                        session.address
+                    )));
+                }
+                let signature = session.keypair.sign(&data.signable_message(chain_id))?;
+                data.sign(signature.to_vec(), public_key);
+                Ok(Transaction::Data(data))
+            }
+            Transaction::Coinbase(_) => Err(ApiError::InvalidInput(
+                "Coinbase transactions cannot be signed".to_string(),
+            )),
+            Transaction::Announce(_) => Err(ApiError::InvalidInput(
+                "Key announcements need no signature".to_string(),
+            )),
+        }
+    }
 }
 
 // ============================================================================
@@ -358,13 +835,64 @@ pub enum ApiError {
     MiningAlreadyRunning,
     MiningNotRunning,
     RateLimitExceeded,
+    Unauthorized(String),
+    Forbidden(String),
+    PayloadTooLarge(usize),
+    RequestTimeout,
     InternalError(String),
 }
 
+impl ApiError {
+    /// Machine-readable code sent as `code` in the error body; chain errors
+    /// use [`ChainError::code`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BlockchainError(e) => e.code(),
+            ApiError::InvalidInput(_) => "invalid_input",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::MiningAlreadyRunning => "mining_already_running",
+            ApiError::MiningNotRunning => "mining_not_running",
+            ApiError::RateLimitExceeded => "rate_limited",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::RequestTimeout => "request_timeout",
+            ApiError::InternalError(_) => "internal_error",
+        }
+    }
+}
+
+/// HTTP status for a chain error: missing inputs are `404`, conflicts with
+/// the mempool or chain `409`, unaffordable payments `422`, storage and
+/// peer failures `5xx`, and anything else the client sent `400`
+fn chain_error_status(err: &ChainError) -> StatusCode {
+    match err.root() {
+        ChainError::SpendingLimit(_) => StatusCode::FORBIDDEN,
+        ChainError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
+        ChainError::UnknownUtxo(_) | ChainError::TriangleNotFound(_) | ChainError::ForkNotFound => {
+            StatusCode::NOT_FOUND
+        }
+        ChainError::AlreadySpent { .. }
+        | ChainError::DuplicateTransaction
+        | ChainError::DoubleSpendDetected(_)
+        | ChainError::BlockAlreadyExists => StatusCode::CONFLICT,
+        ChainError::InsufficientValue { .. } | ChainError::InsufficientFunds(_) => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        ChainError::MempoolFull => StatusCode::SERVICE_UNAVAILABLE,
+        ChainError::NetworkError(_) => StatusCode::BAD_GATEWAY,
+        ChainError::DatabaseError(_) | ChainError::IoError(_) | ChainError::BincodeError(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let code = self.code();
         let (status, message) = match self {
-            ApiError::BlockchainError(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+            ApiError::BlockchainError(e) => (chain_error_status(&e), e.to_string()),
             ApiError::InvalidInput(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             ApiError::MiningAlreadyRunning => (
@@ -378,10 +906,20 @@ impl IntoResponse for ApiError {
                 StatusCode::TOO_MANY_REQUESTS,
                 "Rate limit exceeded".to_string(),
             ),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::PayloadTooLarge(limit) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Request body exceeds the limit of {} bytes", limit),
+            ),
+            ApiError::RequestTimeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                "Request timed out".to_string(),
+            ),
             ApiError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
         };
 
-        (status, Json(ErrorResponse { error: message })).into_response()
+        (status, Json(ErrorResponse { error: message, code })).into_response()
     }
 }
 
@@ -394,6 +932,7 @@ impl From<ChainError> for ApiError {
 #[derive(Serialize)]
 struct ErrorResponse {
     error: String,
+    code: &'static str,
 }
 
 // ============================================================================
@@ -404,12 +943,51 @@ struct ErrorResponse {
 pub struct BalanceResponse {
     pub balance: String, // Changed to String to preserve floating-point precision of Coord
     pub address: String,
+    /// Paid to the address by pending transactions
+    pub pending_in: String,
+    /// Taken from the address by pending transactions, fees included
+    pub pending_out: String,
+    /// Confirmed balance not spent by a pending transaction
+    pub available: String,
+}
+
+#[derive(Serialize)]
+pub struct AddressSummaryResponse {
+    pub address: String,
+    /// Paid to the address by confirmed transactions
+    pub total_received: String,
+    /// Paid by the address in confirmed transactions, fees included
+    pub total_sent: String,
+    pub tx_count: u64,
+    /// `None` until a block involves the address
+    pub first_seen_height: Option<u64>,
+    pub last_active_height: Option<u64>,
+}
+
+/// A transaction as returned by the API: its usual JSON with the size of its
+/// canonical encoding and the fee paid per byte alongside
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionView {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    pub size: usize,
+    pub fee_per_byte: f64,
+}
+
+impl From<Transaction> for TransactionView {
+    fn from(transaction: Transaction) -> Self {
+        TransactionView {
+            size: transaction.size(),
+            fee_per_byte: transaction.fee_per_byte(),
+            transaction,
+        }
+    }
 }
 
 // Struct to hold a transaction and its containing block height
 #[derive(Serialize)]
 pub struct TransactionHistoryEntry {
-    pub transaction: Transaction,
+    pub transaction: TransactionView,
     pub block_height: u64,
 }
 
@@ -419,6 +997,58 @@ pub struct StatsResponse {
     pub difficulty: u32,
     pub mempool_size: usize,
     pub total_blocks: u64,
+    /// Cumulative work of the chain as 32 hex digits
+    pub chain_work: String,
+    pub checkpoints: CheckpointStatus,
+}
+
+/// Signed checkpoint settings and the checkpoint currently followed
+#[derive(Serialize)]
+pub struct CheckpointStatus {
+    pub enabled: bool,
+    pub threshold: usize,
+    pub authorities: usize,
+    /// `None` until a checkpoint has been adopted
+    pub latest: Option<crate::checkpoint::Checkpoint>,
+}
+
+/// Next block for the built-in miner: the mempool's transactions by fee,
+/// `priority`'s senders first, after a coinbase paying `address` the
+/// subsidy plus their fees
+fn mining_template(bc: &Blockchain, address: [u8; 32], priority: &LocalPriority) -> Result<Block, ChainError> {
+    assemble_block(bc, address, priority.select(&bc.mempool, usize::MAX))
+}
+
+/// Unmined block on the tip holding `transactions` after a coinbase paying
+/// `address` the subsidy plus their fees
+fn assemble_block(bc: &Blockchain, address: [u8; 32], transactions: Vec<Transaction>) -> Result<Block, ChainError> {
+    let last_block = bc
+        .blocks
+        .last()
+        .ok_or_else(|| ChainError::InvalidBlock("Cannot mine without a genesis block".to_string()))?;
+    let height = bc.blocks.len() as u64;
+    let subsidy = bc.mining_subsidy(height, Coord::from_num(Blockchain::calculate_block_reward(height)));
+    let fees = bc.claimable_fees(&transactions)?;
+    let reward = crate::geometry::coord_checked_add(subsidy, fees)?;
+
+    let coinbase_tx = Transaction::Coinbase(CoinbaseTx {
+        reward_area: reward,
+        beneficiary_address: address,
+        nonce: 0,
+        height,
+        fees,
+        extra_nonce: Vec::new(),
+        outputs: bc.reward_outputs(reward)?,
+    });
+
+    let mut all_txs = vec![coinbase_tx];
+    all_txs.extend(transactions);
+
+    let mut block = Block::new_with_clock(height, last_block.hash(), 0, all_txs, bc.clock.as_ref())
+        .with_version(bc.next_block_version());
+    block.header.timestamp = bc.next_timestamp();
+    block.header.difficulty = bc.required_difficulty(block.header.timestamp);
+    Ok(block)
 }
 
 #[derive(Serialize)]
@@ -439,11 +1069,170 @@ pub struct StartMiningRequest {
     pub miner_address: String,
 }
 
+/// Changes to the miner throttle; fields left out keep their value, and a
+/// `null` `pause_hours` removes the schedule
+#[derive(Deserialize)]
+pub struct ThrottleRequest {
+    #[serde(default)]
+    pub cpu_percent: Option<u8>,
+    #[serde(default, deserialize_with = "present")]
+    pub pause_hours: Option<Option<String>>,
+    #[serde(default)]
+    pub pause_on_battery: Option<bool>,
+}
+
+/// Tell a field sent as `null` (`Some(None)`) from one left out (`None`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+#[derive(Deserialize)]
+pub struct CreateWalletRequest {
+    pub passphrase: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Response for wallet creation. Only the address and the encrypted backup
+/// are returned; the secret key never leaves the server in plaintext.
 #[derive(Serialize)]
 struct WalletResponse {
     address: String,
-    public_key: String,
-    private_key: String,
+    encrypted_backup: EncryptedWallet,
+}
+
+#[derive(Deserialize)]
+pub struct UnlockWalletRequest {
+    /// Address of a keystore entry held by this server
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Encrypted backup blob, for wallets not held by this server
+    #[serde(default)]
+    pub encrypted_backup: Option<EncryptedWallet>,
+    pub passphrase: String,
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct UnlockWalletResponse {
+    address: String,
+    session_token: String,
+    expires_in_secs: u64,
+}
+
+#[derive(Deserialize)]
+pub struct LockWalletRequest {
+    pub session_token: String,
+}
+
+/// An open wallet session as shown to the operator
+#[derive(Debug, Serialize)]
+pub struct WalletSessionInfo {
+    pub address: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Deserialize)]
+pub struct RevokeSessionsQuery {
+    /// Only the sessions of this address
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SignTransactionRequest {
+    pub session_token: String,
+    pub transaction: Transaction,
+    /// Submit the signed transaction to the mempool and broadcast it
+    #[serde(default)]
+    pub submit: bool,
+    /// Acknowledge a payment above the wallet's `confirm_above` threshold
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Deserialize)]
+pub struct BumpFeeRequest {
+    pub session_token: String,
+    /// Pending transfer of the session's wallet to replace
+    pub txid: String,
+    /// New fee in TRC; defaults to the lowest the replacement rules accept
+    #[serde(default)]
+    pub fee: Option<String>,
+    /// Acknowledge a fee increase above the wallet's `confirm_above`
+    /// threshold
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Serialize)]
+struct BumpFeeResponse {
+    hash: String,
+    replaced: String,
+    fee: String,
+    /// Every transaction evicted: the replaced one and its descendants
+    evicted: Vec<String>,
+    relayed_to: usize,
+}
+
+#[derive(Deserialize)]
+pub struct SignMessageRequest {
+    pub session_token: String,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyMessageRequest {
+    pub address: String,
+    pub message: String,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+struct SignedMessageResponse {
+    address: String,
+    message: String,
+    signature: String,
+}
+
+#[derive(Serialize)]
+struct VerifyMessageResponse {
+    valid: bool,
+}
+
+#[derive(Serialize)]
+struct SignTransactionResponse {
+    transaction: Transaction,
+    hash: String,
+    submitted: bool,
+    /// Peers the submitted transaction was announced to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relayed_to: Option<usize>,
+}
+
+/// Result of submitting a transaction. With no peer to take it,
+/// `relayed_to` is 0 and the node announces it once a peer connects.
+#[derive(Serialize)]
+struct SubmitTransactionResponse {
+    message: String,
+    hash: String,
+    relayed_to: usize,
+    queued_for_relay: bool,
+    /// Id to look the propagation trace up by, for `?trace=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SubmitTransactionQuery {
+    /// Tag the transaction for propagation tracing (`[trace] enabled` only)
+    #[serde(default)]
+    trace: bool,
 }
 
 #[derive(Serialize)]
@@ -451,6 +1240,64 @@ struct SuccessResponse {
     message: String,
 }
 
+/// A block header without its transactions
+#[derive(Serialize)]
+pub struct HeaderResponse {
+    pub hash: String,
+    #[serde(flatten)]
+    pub header: BlockHeader,
+    pub transaction_count: usize,
+}
+
+/// A chain tip, as listed by `/blockchain/tips`
+#[derive(Serialize)]
+pub struct TipResponse {
+    pub height: u64,
+    pub hash: String,
+    pub branch_len: u64,
+    pub status: TipStatus,
+}
+
+/// Canonical serialized bytes of a block or transaction
+#[derive(Serialize)]
+pub struct RawResponse {
+    pub hash: String,
+    pub hex: String,
+}
+
+#[derive(Deserialize)]
+pub struct SubmitRawBlockRequest {
+    pub hex: String,
+}
+
+#[derive(Serialize)]
+pub struct SubmitBlockResponse {
+    pub hash: String,
+    pub height: u64,
+}
+
+/// Optional `from`/`to` bounds on block time, see [`TimeRange::parse`]
+#[derive(Deserialize, Default)]
+struct TimeRangeQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+impl TimeRangeQuery {
+    fn range(&self) -> Result<TimeRange, ApiError> {
+        TimeRange::parse(self.from.as_deref(), self.to.as_deref()).map_err(ApiError::InvalidInput)
+    }
+}
+
+/// Blocks in the time range, all of them when it is unbounded
+fn blocks_in_range(blockchain: &Blockchain, range: TimeRange) -> Vec<&Block> {
+    if range.is_all() {
+        blockchain.blocks.iter().collect()
+    } else {
+        blockchain.blocks_in(range).collect()
+    }
+}
+
 #[derive(Deserialize)]
 struct PaginationQuery {
     #[serde(default = "default_page")]
@@ -498,6 +1345,48 @@ async fn stats_middleware(State(node): State<Arc<Node>>, req: Request, next: Nex
     response
 }
 
+/// Gate operator endpoints behind `Authorization: Bearer <api.admin_token>`
+async fn admin_auth_middleware(
+    State(node): State<Arc<Node>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(expected) = node.admin_token.as_deref() else {
+        return Err(ApiError::Forbidden(
+            "Admin API is disabled; set api.admin_token in config.toml".to_string(),
+        ));
+    };
+    let provided = req
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if !constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        return Err(ApiError::Unauthorized("Invalid admin token".to_string()));
+    }
+    Ok(next.run(req).await)
+}
+
+/// Give the bare `413` and `408` answers of the body limit and timeout
+/// layers the same JSON error body as every other failure
+async fn limit_errors_middleware(State(node): State<Arc<Node>>, req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+    let is_json = response
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    match response.status() {
+        StatusCode::PAYLOAD_TOO_LARGE if !is_json => ApiError::PayloadTooLarge(node.max_body_bytes).into_response(),
+        StatusCode::REQUEST_TIMEOUT if !is_json => ApiError::RequestTimeout.into_response(),
+        _ => response,
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 /// Detailed request logging middleware. Logs method, path, status, duration
 /// and current `NodeState` (when available).
 async fn logging_middleware(
@@ -536,101 +1425,158 @@ async fn logging_middleware(
 // API Server
 // ============================================================================
 
+/// Which endpoints a listener serves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiProfile {
+    /// Chain queries and transaction/block submission only; safe to expose
+    /// for explorers
+    Public,
+    /// Everything, including mining control, wallets and peer management
+    #[default]
+    Admin,
+}
+
+impl ApiProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiProfile::Public => "public",
+            ApiProfile::Admin => "admin",
+        }
+    }
+}
+
 /// Build the API router with all endpoints (for testing)
 pub fn build_api_router(node: Arc<Node>) -> Router {
+    build_api_router_for(node, ApiProfile::Admin)
+}
+
+/// Build the API router serving the endpoints of `profile`
+pub fn build_api_router_for(node: Arc<Node>, profile: ApiProfile) -> Router {
     // CORS configuration - allow all origins with credentials
     let cors = CorsLayer::new()
         .allow_origin(AllowOrigin::mirror_request()) // Reflect the request's origin
         .allow_methods(vec![
             http::Method::GET,
             http::Method::POST,
+            http::Method::DELETE,
             http::Method::OPTIONS,
         ]) // Explicitly allow methods
-        .allow_headers(vec![http::header::CONTENT_TYPE]) // Explicitly allow headers
+        .allow_headers(vec![http::header::CONTENT_TYPE, http::header::AUTHORIZATION]) // Explicitly allow headers
         .allow_credentials(true);
 
-    // API routes
-    let api_routes = Router::new()
+    // Read-only queries plus self-authenticating submissions
+    let public_routes = Router::new()
         // Blockchain endpoints
         .route("/blockchain/height", get(get_blockchain_height))
         .route("/blockchain/blocks", get(get_blocks))
         .route("/blockchain/block/:height", get(get_block_by_height))
+        .route("/blockchain/block/hash/:hash", get(get_block_by_hash))
+        .route("/blockchain/header/:height_or_hash", get(get_block_header))
         .route("/blockchain/stats", get(get_blockchain_stats))
+        .route("/blockchain/tips", get(get_chain_tips))
+        .route("/blockchain/deployments", get(get_deployments))
+        .route("/analytics/difficulty", get(get_difficulty_history))
+        .route("/analytics/utxo", get(get_utxo_analytics))
+        .route("/blockchain/checkpoint", post(submit_checkpoint))
+        .route("/block/raw", post(submit_raw_block))
+        .route("/block/:hash/raw", get(get_raw_block))
         // Transaction endpoints
         .route("/transaction", post(submit_transaction))
         .route("/transaction/:hash", get(get_transaction))
+        .route("/transaction/:hash/raw", get(get_raw_transaction))
+        .route("/data/:hash", get(get_data_payload))
         .route("/mempool", get(get_mempool))
-        // Mining endpoints
-        .route("/mining/start", post(start_mining))
-        .route("/mining/stop", post(stop_mining))
+        .route("/mempool/by-fee", get(get_mempool_by_fee))
+        // Triangle endpoints
+        .route("/triangle/*id", get(get_triangle_by_id))
+        // Archive endpoints (archive mode only)
+        .route("/archive/utxo/:hash", get(get_archived_utxo))
+        .route("/archive/utxo/:hash/provenance", get(get_utxo_provenance))
+        .route("/archive/triangle/*id", get(get_triangle_owners_at))
+        // Mining and network status
         .route("/mining/status", get(get_mining_status))
-        // Network endpoints
+        .route("/mining/template/preview", get(get_template_preview))
         .route("/network/peers", get(get_peers))
         .route("/network/info", get(get_network_info))
+        .route("/network/stats/history", get(get_network_stats_history))
+        .route("/sync/status", get(get_sync_status))
+        .route("/trace/report", post(report_trace_hop))
+        .route("/trace/:id", get(get_trace))
+        .route("/watchlist/events", get(watch_events))
         // Address endpoints
         .route("/address/:addr/balance", get(get_address_balance))
+        .route("/address/:addr/summary", get(get_address_summary))
         .route("/address/:addr/transactions", get(get_address_transactions))
-        // Wallet endpoints
-        .route("/wallet/create", post(create_wallet))
+        .route("/address/:addr/utxos", get(get_address_utxos))
+        .route("/address/:addr/pubkey", get(get_address_pubkey))
+        .route("/address/:addr/proof", get(get_address_proof))
+        .route("/address/:addr/qr.svg", get(get_address_qr))
+        .route("/name/:name", get(get_name))
+        .route("/wallet/verify-message", post(verify_message))
         // System endpoints
         .route("/health", get(health_check))
-        .route("/stats", get(get_api_stats))
-        // logging before stats so we always record timing and node-state
-        .layer(middleware::from_fn_with_state(node.clone(), logging_middleware))
-        .layer(middleware::from_fn_with_state(
-            node.clone(),
-            stats_middleware,
-        ))
-        .with_state(node)
-        .layer(cors.clone());
-
-    // Serve static dashboard files
-    let serve_dir = ServeDir::new("dashboard/dist");
-    Router::new()
-        .nest("/api", api_routes)
-        .fallback_service(serve_dir)
-        .layer(cors)
-}
-
-/// Run the API server with production-grade configuration
-pub async fn run_api_server(node: Arc<Node>) -> Result<(), Box<dyn std::error::Error>> {
-    // CORS configuration - allow all origins with credentials
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::mirror_request()) // Reflect the request's origin
-        .allow_methods(vec![
-            http::Method::GET,
-            http::Method::POST,
-            http::Method::OPTIONS,
-        ]) // Explicitly allow methods
-        .allow_headers(vec![http::header::CONTENT_TYPE]) // Explicitly allow headers
-        .allow_credentials(true);
+        .route("/ready", get(readiness_check))
+        .route("/version", get(get_version))
+        .route("/stats", get(get_api_stats));
 
-    // API routes
-    let api_routes = Router::new()
-        // Blockchain endpoints
-        .route("/blockchain/height", get(get_blockchain_height))
-        .route("/blockchain/blocks", get(get_blocks))
-        .route("/blockchain/block/:height", get(get_block_by_height))
-        .route("/blockchain/stats", get(get_blockchain_stats))
-        // Transaction endpoints
-        .route("/transaction", post(submit_transaction))
-        .route("/transaction/:hash", get(get_transaction))
-        .route("/mempool", get(get_mempool))
+    // Endpoints that act on the node or its wallets
+    let private_routes = Router::new()
         // Mining endpoints
         .route("/mining/start", post(start_mining))
         .route("/mining/stop", post(stop_mining))
-        .route("/mining/status", get(get_mining_status))
-        // Network endpoints
-        .route("/network/peers", get(get_peers))
-        .route("/network/info", get(get_network_info))
-        // Address endpoints
-        .route("/address/:addr/balance", get(get_address_balance))
-        .route("/address/:addr/transactions", get(get_address_transactions))
+        .route("/mining/throttle", post(set_mining_throttle))
         // Wallet endpoints
         .route("/wallet/create", post(create_wallet))
-        // System endpoints
-        .route("/health", get(health_check))
-        .route("/stats", get(get_api_stats))
+        .route("/wallet/unlock", post(unlock_wallet))
+        .route("/wallet/lock", post(lock_wallet))
+        .route("/wallet/sign", post(sign_transaction))
+        .route("/wallet/bump", post(bump_fee))
+        .route("/wallet/sign-message", post(sign_message))
+        .route("/telemetry/preview", get(get_telemetry_preview));
+
+    // Operator endpoints, gated by the admin token
+    let admin_routes = Router::new()
+        .route("/network/peers", post(add_peer))
+        .route("/network/peers/:addr", delete(remove_peer))
+        .route("/network/addnodes", get(get_addnodes))
+        .route("/network/bans", get(get_bans))
+        .route("/network/ban", post(ban_peer))
+        .route("/network/unban", post(unban_peer))
+        .route("/debug/rejected-blocks", get(get_rejected_blocks))
+        .route("/debug/state", get(get_debug_state))
+        .route("/debug/state/utxos", get(get_debug_utxos))
+        .route("/debug/state/balances", get(get_debug_balances))
+        .route("/debug/state/mempool", get(get_debug_mempool))
+        .route("/debug/state/blocks", get(get_debug_blocks))
+        .route("/debug/memory", get(get_debug_memory))
+        .route("/watchlist", get(get_watchlist))
+        .route("/watchlist/watch", post(add_webhook_watch))
+        .route("/watchlist/unwatch", post(remove_webhook_watch))
+        .route(
+            "/mempool/:hash/prioritize",
+            post(prioritize_transaction).delete(deprioritize_transaction),
+        )
+        .route("/mempool/:hash", delete(remove_mempool_transaction))
+        .route("/mempool/snapshot", get(get_mempool_snapshot).post(restore_mempool_snapshot))
+        .route("/audit/transactions", get(get_transaction_audit))
+        .route("/audit/transactions/export", get(export_transaction_audit))
+        .route("/wallet/sessions", get(get_wallet_sessions).delete(revoke_wallet_sessions))
+        .route_layer(middleware::from_fn_with_state(
+            node.clone(),
+            admin_auth_middleware,
+        ));
+
+    let api_routes = match profile {
+        ApiProfile::Public => public_routes,
+        ApiProfile::Admin => public_routes.merge(private_routes).merge(admin_routes),
+    };
+
+    // API routes
+    let api_routes = api_routes
+        .layer(DefaultBodyLimit::max(node.max_body_bytes))
+        .layer(RequestBodyLimitLayer::new(node.max_body_bytes))
+        .layer(TimeoutLayer::new(node.request_timeout))
+        .layer(middleware::from_fn_with_state(node.clone(), limit_errors_middleware))
         // logging before stats so we always record timing and node-state
         .layer(middleware::from_fn_with_state(node.clone(), logging_middleware))
         .layer(middleware::from_fn_with_state(
@@ -642,27 +1588,52 @@ pub async fn run_api_server(node: Arc<Node>) -> Result<(), Box<dyn std::error::E
 
     // Serve static dashboard files
     let serve_dir = ServeDir::new("dashboard/dist");
-    let app = Router::new()
+    Router::new()
         .nest("/api", api_routes)
         .fallback_service(serve_dir)
-        .layer(cors);
+        .layer(cors)
+}
 
+/// Run the API server with production-grade configuration
+pub async fn run_api_server(node: Arc<Node>) -> Result<(), Box<dyn std::error::Error>> {
     // Get port from environment or use default
     let port = std::env::var("PORT")
         .ok()
         .and_then(|p| p.parse::<u16>().ok())
         .unwrap_or(DEFAULT_API_PORT);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    serve_api(node, ApiProfile::Admin, SocketAddr::from(([0, 0, 0, 0], port))).await
+}
+
+/// Serve the endpoints of `profile` on `addr`
+pub async fn serve_api(
+    node: Arc<Node>,
+    profile: ApiProfile,
+    addr: SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    serve_api_until(node, profile, addr, std::future::pending()).await
+}
+
+/// Serve the endpoints of `profile` on `addr` until `shutdown` resolves,
+/// then stop accepting connections and return once open requests finish
+pub async fn serve_api_until(
+    node: Arc<Node>,
+    profile: ApiProfile,
+    addr: SocketAddr,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let app = build_api_router_for(node, profile);
 
     // Binds the TCP listener to the address, defining the 'listener' variable
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
-    println!("🚀 API server listening on http://{}", addr);
-    println!("📊 Dashboard available at http://{}", addr);
-    println!("🔗 API documentation at http://{}/api", addr);
+    tracing::info!("🚀 API server ({}) listening on http://{}", profile.as_str(), addr);
+    tracing::info!("📊 Dashboard available at http://{}", addr);
+    tracing::info!("🔗 API documentation at http://{}/api", addr);
 
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown)
+        .await?;
 
     Ok(())
 }
@@ -671,41 +1642,47 @@ pub async fn run_api_server(node: Arc<Node>) -> Result<(), Box<dyn std::error::E
 // Route Handlers
 // ============================================================================
 
+async fn get_version() -> Json<build_info::BuildInfo> {
+    Json(build_info::build_info())
+}
+
+/// Liveness: the process answers and the chain is not stuck behind its
+/// lock. Syncing and degraded nodes are alive; whether they should get
+/// traffic is for [`readiness_check`] to say.
 async fn health_check(State(node): State<Arc<Node>>) -> impl IntoResponse {
-    // If the orchestrator provided a `NodeState`, use it to determine health.
-    if let Some(s) = &node.state {
-        let state = s.read().await.clone();
-        match state {
-            crate::node::NodeState::Ready => (
-                StatusCode::OK,
-                Json(serde_json::json!({
-                    "status": "healthy",
-                    "node_state": format!("{:?}", state),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                })),
-            )
-                .into_response(),
-            _ => (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(serde_json::json!({
-                    "status": "unhealthy",
-                    "node_state": format!("{:?}", state),
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                })),
-            )
-                .into_response(),
-        }
-    } else {
-        // No orchestrator state available — assume healthy
-        (
-            StatusCode::OK,
-            Json(serde_json::json!({
-                "status": "healthy",
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })),
-        )
-            .into_response()
-    }
+    let node_state = match &node.state {
+        Some(state) => Some(format!("{:?}", *state.read().await)),
+        None => None,
+    };
+    let alive = tokio::time::timeout(LIVENESS_LOCK_TIMEOUT, node.blockchain.read()).await.is_ok();
+    let status = if alive { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(serde_json::json!({
+            "status": if alive { "healthy" } else { "unhealthy" },
+            "node_state": node_state,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    )
+}
+
+/// Readiness: started up, not degraded and not shutting down. Without an
+/// orchestrator state (standalone API) the node is always ready.
+async fn readiness_check(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let state = match &node.state {
+        Some(state) => *state.read().await,
+        None => crate::node::NodeState::Ready,
+    };
+    let ready = state == crate::node::NodeState::Ready;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(serde_json::json!({
+            "ready": ready,
+            "node_state": format!("{:?}", state),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    )
 }
 
 async fn get_blockchain_height(State(node): State<Arc<Node>>) -> impl IntoResponse {
@@ -720,24 +1697,26 @@ fn hash_to_hex(hash: &Sha256Hash) -> String {
 async fn get_blocks(
     State(node): State<Arc<Node>>,
     Query(params): Query<PaginationQuery>,
-) -> impl IntoResponse {
+    Query(time): Query<TimeRangeQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let range = time.range()?;
     let blockchain = node.blockchain.read().await;
-    let total = blockchain.blocks.len();
+    let blocks = blocks_in_range(&blockchain, range);
+    let total = blocks.len();
 
     let limit = params.limit.min(100); // Max 100 blocks per request
     let offset = params.page * limit;
 
     if offset >= total as u64 {
-        return Json(serde_json::json!({
+        return Ok(Json(serde_json::json!({
             "blocks": [],
             "total": total,
             "page": params.page,
             "limit": limit
-        }));
+        })));
     }
 
-    let blocks_json: Vec<_> = blockchain
-        .blocks
+    let blocks_json: Vec<_> = blocks
         .iter()
         .rev()
         .skip(offset as usize)
@@ -767,12 +1746,12 @@ async fn get_blocks(
         })
         .collect();
 
-    Json(serde_json::json!({
+    Ok(Json(serde_json::json!({
         "blocks": blocks_json,
         "total": total,
         "page": params.page,
         "limit": limit
-    }))
+    })))
 }
 
 async fn get_block_by_height(
@@ -789,33 +1768,316 @@ async fn get_block_by_height(
         .map(Json)
 }
 
+async fn get_block_by_hash(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<Block>, ApiError> {
+    let target_hash = parse_hash(&hash_str)?;
+    let blockchain = node.blockchain.read().await;
+    blockchain
+        .block_by_hash(&target_hash)
+        .cloned()
+        .ok_or_else(|| ApiError::NotFound(format!("Block {} not found", hash_str)))
+        .map(Json)
+}
+
+/// Header of the block at a height, or with a hash when given 64 hex digits
+async fn get_block_header(
+    State(node): State<Arc<Node>>,
+    Path(height_or_hash): Path<String>,
+) -> Result<Json<HeaderResponse>, ApiError> {
+    let blockchain = node.blockchain.read().await;
+    let block = if height_or_hash.len() == 64 {
+        blockchain.block_by_hash(&parse_hash(&height_or_hash)?)
+    } else {
+        let height = height_or_hash.parse::<u64>().map_err(|_| {
+            ApiError::InvalidInput("Expected a block height or a 64-character hex hash".to_string())
+        })?;
+        blockchain.blocks.get(height as usize)
+    };
+    let block = block.ok_or_else(|| ApiError::NotFound(format!("Block {} not found", height_or_hash)))?;
+    Ok(Json(HeaderResponse {
+        hash: hex::encode(block.hash()),
+        header: block.header.clone(),
+        transaction_count: block.transactions.len(),
+    }))
+}
+
+/// Difficulty and solve time of each block stamped in the time range, from
+/// the stored block metrics rather than the block headers
+async fn get_difficulty_history(
+    State(node): State<Arc<Node>>,
+    Query(time): Query<TimeRangeQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let range = time.range()?;
+    let metrics = match &node.persistence {
+        Some(persistence) => persistence.load_block_metrics(range)?,
+        None => node.blockchain.read().await.persistence.load_block_metrics(range)?,
+    };
+    let solve_times: Vec<i64> = metrics
+        .iter()
+        .filter(|metric| metric.height > 0)
+        .map(|metric| metric.solve_time_ms)
+        .collect();
+    let average_solve_time_ms = match solve_times.len() {
+        0 => None,
+        count => Some(solve_times.iter().sum::<i64>() / count as i64),
+    };
+    Ok(Json(serde_json::json!({
+        "count": metrics.len(),
+        "average_solve_time_ms": average_solve_time_ms,
+        "points": metrics,
+    })))
+}
+
+/// Composition of the current UTXO set, and its history over the blocks
+/// stamped in the requested range
+async fn get_utxo_analytics(
+    State(node): State<Arc<Node>>,
+    Query(time): Query<TimeRangeQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let range = time.range()?;
+    let blockchain = node.blockchain.read().await;
+    let height = blockchain.blocks.last().map_or(0, |tip| tip.header.height);
+    let timestamp = blockchain.blocks.last().map_or(0, |tip| tip.header.timestamp);
+    let current = blockchain.utxo_stats.snapshot(height, timestamp);
+    let points = match &node.persistence {
+        Some(persistence) => persistence.load_utxo_stats(range)?,
+        None => blockchain.persistence.load_utxo_stats(range)?,
+    };
+    drop(blockchain);
+
+    let average_value = match current.count {
+        0 => Coord::from_num(0),
+        count => current.total_value / Coord::from_num(count),
+    };
+    // The last bucket has no upper bound
+    let distribution: Vec<_> = current
+        .size_buckets
+        .iter()
+        .enumerate()
+        .map(|(i, count)| serde_json::json!({ "below": SIZE_BUCKET_BOUNDS.get(i), "count": count }))
+        .collect();
+    Ok(Json(serde_json::json!({
+        "height": current.height,
+        "count": current.count,
+        "total_value": units::format_number(current.total_value, Unit::Trc),
+        "average_value": units::format_number(average_value, Unit::Trc),
+        "dust_count": current.dust_count,
+        "dust_threshold": units::format_number(current.dust_threshold, Unit::Trc),
+        "distribution": distribution,
+        "points": points,
+    })))
+}
+
+/// The active tip and the competing branches this node has seen
+async fn get_chain_tips(State(node): State<Arc<Node>>) -> Json<Vec<TipResponse>> {
+    let blockchain = node.blockchain.read().await;
+    let tips = blockchain
+        .chain_tips()
+        .into_iter()
+        .map(|tip| TipResponse {
+            height: tip.height,
+            hash: hex::encode(tip.hash),
+            branch_len: tip.branch_len,
+            status: tip.status,
+        })
+        .collect();
+    Json(tips)
+}
+
 async fn get_blockchain_stats(State(node): State<Arc<Node>>) -> impl IntoResponse {
     let blockchain = node.blockchain.read().await;
+    let authority = node.network.checkpoint_authority();
     let stats = StatsResponse {
         height: blockchain.blocks.len() as u64,
         difficulty: blockchain.difficulty,
         mempool_size: blockchain.mempool.len(),
         total_blocks: blockchain.blocks.len() as u64,
+        chain_work: format!("{:032x}", blockchain.total_work()),
+        checkpoints: CheckpointStatus {
+            enabled: authority.is_some(),
+            threshold: authority.map_or(0, |a| a.threshold()),
+            authorities: authority.map_or(0, |a| a.authority_count()),
+            latest: blockchain.checkpoint.clone(),
+        },
     };
     Json(stats)
 }
 
+/// Adopt and relay a checkpoint signed by the configured authorities
+async fn submit_checkpoint(
+    State(node): State<Arc<Node>>,
+    Json(signed): Json<crate::checkpoint::SignedCheckpoint>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    if node.network.checkpoint_authority().is_none() {
+        return Err(ApiError::Forbidden("Checkpoints are not enabled on this node".to_string()));
+    }
+    let height = signed.checkpoint.height;
+    let adopted = node.network.submit_checkpoint(signed).await.map_err(|e| match e {
+        ChainError::CryptoError(msg) => ApiError::InvalidInput(msg),
+        other => ApiError::BlockchainError(other),
+    })?;
+    let message = if adopted {
+        format!("Checkpoint at height {} adopted", height)
+    } else {
+        format!("Checkpoint at height {} is not newer than the current one", height)
+    };
+    Ok(Json(SuccessResponse { message }))
+}
+
+/// Version-bits soft-fork deployments and their state for the next block
+async fn get_deployments(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let next_height = blockchain.blocks.len() as u64;
+    let period = blockchain.consensus.signaling_period.max(1);
+    let period_start = next_height - next_height % period;
+    let deployments: Vec<_> = blockchain
+        .deployment_states()
+        .into_iter()
+        .map(|(deployment, state)| {
+            serde_json::json!({
+                "name": deployment.name,
+                "bit": deployment.bit,
+                "start_height": deployment.start_height,
+                "timeout_height": deployment.timeout_height,
+                "threshold": deployment.threshold,
+                "state": state,
+                "signals_this_period": crate::blockchain::count_signals(
+                    &blockchain.blocks,
+                    deployment,
+                    period_start,
+                    next_height
+                ),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "height": next_height,
+        "signaling_period": period,
+        "next_block_version": blockchain.next_block_version(),
+        "deployments": deployments
+    }))
+}
+
 async fn get_mempool(State(node): State<Arc<Node>>) -> impl IntoResponse {
     let blockchain = node.blockchain.read().await;
-    let transactions = blockchain.mempool.get_all_transactions();
+    let transactions: Vec<TransactionView> = blockchain
+        .mempool
+        .get_all_transactions()
+        .into_iter()
+        .map(TransactionView::from)
+        .collect();
     Json(serde_json::json!({
         "count": transactions.len(),
         "transactions": transactions
     }))
 }
 
-async fn submit_transaction(
+/// Mempool entries in block-assembly order, with fee density per entry
+async fn get_mempool_by_fee(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let entries = blockchain.mempool.fee_density_report();
+    Json(serde_json::json!({
+        "count": entries.len(),
+        "entries": entries
+    }))
+}
+
+async fn prioritize_transaction(
     State(node): State<Arc<Node>>,
-    Json(tx): Json<Transaction>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let hash = parse_hash(&hash_str)?;
+    let mut blockchain = node.blockchain.write().await;
+    if blockchain.mempool.get_transaction(&hash).is_none() {
+        return Err(ApiError::NotFound(format!("{} is not in the mempool", hash_str)));
+    }
+    blockchain.mempool.prioritize(&hash)?;
+    Ok(Json(SuccessResponse {
+        message: format!("Prioritized {}", hash_str),
+    }))
+}
+
+async fn deprioritize_transaction(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let hash = parse_hash(&hash_str)?;
+    if !node.blockchain.write().await.mempool.deprioritize(&hash) {
+        return Err(ApiError::NotFound(format!("{} is not prioritized", hash_str)));
+    }
+    Ok(Json(SuccessResponse {
+        message: format!("Removed priority from {}", hash_str),
+    }))
+}
+
+async fn remove_mempool_transaction(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
 ) -> Result<Json<SuccessResponse>, ApiError> {
+    let hash = parse_hash(&hash_str)?;
     let mut blockchain = node.blockchain.write().await;
+    if blockchain.mempool.get_transaction(&hash).is_none() {
+        return Err(ApiError::NotFound(format!("{} is not in the mempool", hash_str)));
+    }
+    let removed = blockchain.mempool.remove_with_descendants(&hash);
+    Ok(Json(SuccessResponse {
+        message: format!("Removed {} and {} dependent transaction(s) from the mempool", hash_str, removed - 1),
+    }))
+}
+
+/// Every pending transaction, for loading into another node
+async fn get_mempool_snapshot(State(node): State<Arc<Node>>) -> Result<Json<MempoolSnapshot>, ApiError> {
+    let blockchain = node.blockchain.read().await;
+    let (height, hash) = blockchain
+        .blocks
+        .last()
+        .map_or((0, [0u8; 32]), |tip| (tip.header.height, tip.hash()));
+    Ok(Json(blockchain.mempool.snapshot(height, &hash)?))
+}
+
+/// Load a snapshot taken with [`get_mempool_snapshot`], revalidating every
+/// entry, and relay the transactions that were added
+async fn restore_mempool_snapshot(
+    State(node): State<Arc<Node>>,
+    Json(snapshot): Json<MempoolSnapshot>,
+) -> Result<Json<RestoreReport>, ApiError> {
+    let report = node.blockchain.write().await.restore_mempool(&snapshot)?;
+    let restored: Vec<Transaction> = {
+        let blockchain = node.blockchain.read().await;
+        report
+            .restored
+            .iter()
+            .filter_map(|txid| parse_hash(txid).ok())
+            .filter_map(|hash| blockchain.mempool.get_transaction(&hash).cloned())
+            .collect()
+    };
+    for tx in &restored {
+        node.network.broadcast_transaction(tx).await;
+    }
+    Ok(Json(report))
+}
 
-    blockchain.mempool.add_transaction(tx.clone())?;
+async fn submit_transaction(
+    State(node): State<Arc<Node>>,
+    client: Option<ConnectInfo<SocketAddr>>,
+    headers: http::HeaderMap,
+    Query(query): Query<SubmitTransactionQuery>,
+    Json(tx): Json<Transaction>,
+) -> Result<Json<SubmitTransactionResponse>, ApiError> {
+    if query.trace && !node.network.tracing_enabled() {
+        return Err(ApiError::InvalidInput("Transaction tracing is disabled on this node".to_string()));
+    }
+    let mut chain = node.blockchain.write().await;
+    chain.mempool.add_transaction(tx.clone())?;
+    // No audit entry, no relay: take the transaction back out
+    let source = submission_source(&node, client.map(|ConnectInfo(addr)| addr), &headers);
+    if let Err(e) = node.audit_log.record(&source, &tx) {
+        chain.mempool.remove_transaction(&tx.hash());
+        return Err(ApiError::InternalError(format!("Failed to write the transaction audit log: {}", e)));
+    }
+    drop(chain);
 
     // Update stats
     {
@@ -824,32 +2086,57 @@ async fn submit_transaction(
     }
 
     // Broadcast to network
-    node.network.broadcast_transaction(&tx).await;
+    let (relayed_to, trace_id) = if query.trace {
+        let (relayed_to, trace_id) = node.network.broadcast_traced_transaction(&tx).await?;
+        (relayed_to, Some(txtrace::trace_id_hex(trace_id)))
+    } else {
+        (node.network.broadcast_transaction(&tx).await, None)
+    };
 
-    Ok(Json(SuccessResponse {
+    Ok(Json(SubmitTransactionResponse {
         message: "Transaction submitted successfully".to_string(),
+        hash: tx.hash_str(),
+        relayed_to,
+        queued_for_relay: relayed_to == 0,
+        trace_id,
     }))
 }
 
-async fn get_transaction(
-    State(node): State<Arc<Node>>,
-    Path(hash_str): Path<String>,
-) -> Result<Json<Transaction>, ApiError> {
-    let target_hash = parse_hash(&hash_str)?;
+/// Who submitted a transaction, for the audit log: `api:admin` for callers
+/// presenting the admin token, otherwise `api:` and the client's IP
+fn submission_source(node: &Node, client: Option<SocketAddr>, headers: &http::HeaderMap) -> String {
+    let bearer = headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let (Some(expected), Some(provided)) = (node.admin_token.as_deref(), bearer) {
+        if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+            return "api:admin".to_string();
+        }
+    }
+    match client {
+        Some(addr) => format!("api:{}", addr.ip()),
+        None => "api:unknown".to_string(),
+    }
+}
+
+/// A transaction by txid, confirmed or pending
+async fn find_transaction(node: &Node, hash_str: &str) -> Result<Transaction, ApiError> {
+    let target_hash = parse_hash(hash_str)?;
     let blockchain = node.blockchain.read().await;
 
     // 1. Search in blocks (on-chain)
     for block in &blockchain.blocks {
         for tx in &block.transactions {
             if tx.hash() == target_hash {
-                return Ok(Json(tx.clone()));
+                return Ok(tx.clone());
             }
         }
     }
 
     // 2. Search in mempool (unconfirmed)
     if let Some(tx) = blockchain.mempool.get_transaction(&target_hash) {
-        return Ok(Json(tx.clone()));
+        return Ok(tx.clone());
     }
 
     Err(ApiError::NotFound(format!(
@@ -858,6 +2145,92 @@ async fn get_transaction(
     )))
 }
 
+async fn get_transaction(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<TransactionView>, ApiError> {
+    find_transaction(&node, &hash_str).await.map(|tx| Json(tx.into()))
+}
+
+/// The bincode encoding used on the wire between peers
+fn encode_raw<T: Serialize>(value: &T) -> Result<String, ApiError> {
+    bincode::serialize(value)
+        .map(hex::encode)
+        .map_err(|e| ApiError::InternalError(format!("Serialization failed: {}", e)))
+}
+
+/// Inverse of [`encode_raw`]. Length prefixes inside the data cannot make
+/// it allocate more than the input size, and trailing bytes are refused.
+fn decode_raw<T: serde::de::DeserializeOwned>(hex_str: &str) -> Result<T, ApiError> {
+    use bincode::Options;
+
+    let bytes = hex::decode(hex_str.trim())
+        .map_err(|e| ApiError::InvalidInput(format!("Invalid hex: {}", e)))?;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(bytes.len() as u64)
+        .deserialize(&bytes)
+        .map_err(|e| ApiError::InvalidInput(format!("Malformed raw data: {}", e)))
+}
+
+async fn get_raw_transaction(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<RawResponse>, ApiError> {
+    let tx = find_transaction(&node, &hash_str).await?;
+    Ok(Json(RawResponse {
+        hash: tx.hash_str(),
+        hex: encode_raw(&tx)?,
+    }))
+}
+
+async fn get_raw_block(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<RawResponse>, ApiError> {
+    let target_hash = parse_hash(&hash_str)?;
+    let blockchain = node.blockchain.read().await;
+    let block = blockchain
+        .block_by_hash(&target_hash)
+        .ok_or_else(|| ApiError::NotFound(format!("Block {} not found", hash_str)))?;
+    Ok(Json(RawResponse {
+        hash: hash_str.to_lowercase(),
+        hex: encode_raw(block)?,
+    }))
+}
+
+/// Accept an externally mined block that extends the tip, and relay it
+async fn submit_raw_block(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<SubmitRawBlockRequest>,
+) -> Result<Json<SubmitBlockResponse>, ApiError> {
+    let block: Block = decode_raw(&req.hex)?;
+    let hash = block.hash();
+    let height = block.header.height;
+    {
+        let mut blockchain = node.blockchain.write().await;
+        let tip = blockchain.blocks.last().map(|tip| tip.hash());
+        if tip != Some(block.header.previous_hash) {
+            return Err(ApiError::InvalidInput(
+                "Block does not extend the current tip".to_string(),
+            ));
+        }
+        if let Err(e) = blockchain.apply_block(block.clone()) {
+            drop(blockchain);
+            node.network
+                .record_rejected_block(hash, height, None, &e.to_string())
+                .await;
+            return Err(e.into());
+        }
+    }
+    node.network.broadcast_block(&block).await;
+
+    Ok(Json(SubmitBlockResponse {
+        hash: hex::encode(hash),
+        height,
+    }))
+}
+
 async fn start_mining(
     State(node): State<Arc<Node>>,
     Json(req): Json<StartMiningRequest>,
@@ -878,78 +2251,1179 @@ async fn stop_mining(State(node): State<Arc<Node>>) -> Result<Json<SuccessRespon
 }
 
 async fn get_mining_status(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let stats = node.hash_stats();
     Json(serde_json::json!({
         "is_mining": node.is_mining(),
-        "blocks_mined": node.blocks_mined()
+        "blocks_mined": node.blocks_mined(),
+        "hash_rate": stats.hash_rate(),
+        "template_refreshes": stats.refreshes,
+        "throttle": node.throttle.settings(),
+        "paused": node.throttle.current_pause(),
+        "local_priority": node.local_priority.is_enabled().then(|| node.local_priority.stats()),
+    }))
+}
+
+#[derive(Deserialize)]
+struct TemplatePreviewQuery {
+    /// Coinbase beneficiary; the zero address if left out
+    address: Option<String>,
+}
+
+/// A mempool transaction the next template would include
+#[derive(Serialize)]
+struct TemplateEntry {
+    #[serde(flatten)]
+    info: crate::mempool::MempoolEntryInfo,
+    /// Sent by an address given local priority
+    local: bool,
+}
+
+#[derive(Serialize)]
+struct TemplatePreviewResponse {
+    height: u64,
+    previous_hash: String,
+    difficulty: u32,
+    version: u32,
+    /// Serialized size of the block, coinbase included
+    size: usize,
+    transactions: Vec<TemplateEntry>,
+    /// Mempool transactions left out
+    excluded: usize,
+    total_fees: String,
+    /// Part of the fees burned instead of paid to the miner
+    burned_fees: String,
+    subsidy: String,
+    reward: String,
+    reward_outputs: Vec<serde_json::Value>,
+}
+
+/// The block the built-in miner would assemble right now, without mining it
+/// or counting it in the local priority statistics
+async fn get_template_preview(
+    State(node): State<Arc<Node>>,
+    Query(query): Query<TemplatePreviewQuery>,
+) -> Result<Json<TemplatePreviewResponse>, ApiError> {
+    let address = match &query.address {
+        Some(address) => {
+            crate::crypto::address_from_hex(address).map_err(|e| ApiError::InvalidInput(e.to_string()))?
+        }
+        None => [0u8; 32],
+    };
+    let bc = node.blockchain.read().await;
+    let transactions = node.local_priority.preview(&bc.mempool, usize::MAX);
+    let total_fees = crate::geometry::coord_checked_sum(transactions.iter().map(Transaction::fee_area))?;
+    let block = assemble_block(&bc, address, transactions)?;
+
+    let local = bc.mempool.sent_by(&node.local_priority.senders());
+    let mut infos: HashMap<String, crate::mempool::MempoolEntryInfo> =
+        bc.mempool.fee_density_report().into_iter().map(|info| (info.hash.clone(), info)).collect();
+    let entries: Vec<TemplateEntry> = block.transactions[1..]
+        .iter()
+        .filter_map(|tx| {
+            let info = infos.remove(&tx.hash_str())?;
+            Some(TemplateEntry { info, local: node.local_priority.is_enabled() && local.contains(&tx.hash()) })
+        })
+        .collect();
+    let Transaction::Coinbase(coinbase) = &block.transactions[0] else {
+        return Err(ApiError::InternalError("Template without a coinbase".to_string()));
+    };
+    let height = block.header.height;
+    let subsidy = bc.mining_subsidy(height, Coord::from_num(Blockchain::calculate_block_reward(height)));
+
+    Ok(Json(TemplatePreviewResponse {
+        height: block.header.height,
+        previous_hash: hex::encode(block.header.previous_hash),
+        difficulty: block.header.difficulty,
+        version: block.header.version,
+        size: bincode::serialize(&block).map_err(ChainError::from)?.len(),
+        excluded: infos.len(),
+        transactions: entries,
+        total_fees: units::format_number(total_fees, Unit::Trc),
+        burned_fees: units::format_number(total_fees.saturating_sub(coinbase.fees), Unit::Trc),
+        subsidy: units::format_number(subsidy, Unit::Trc),
+        reward: units::format_number(coinbase.reward_area, Unit::Trc),
+        reward_outputs: coinbase
+            .outputs
+            .iter()
+            .map(|output| {
+                serde_json::json!({
+                    "address": hex::encode(output.address),
+                    "amount": units::format_number(output.amount, Unit::Trc),
+                })
+            })
+            .collect(),
     }))
 }
 
+async fn set_mining_throttle(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<ThrottleRequest>,
+) -> Result<Json<miner::ThrottleSettings>, ApiError> {
+    let mut settings = node.throttle.settings();
+    if let Some(cpu_percent) = req.cpu_percent {
+        settings.cpu_percent = cpu_percent;
+    }
+    if let Some(pause_hours) = req.pause_hours {
+        settings.pause_hours = pause_hours.as_deref().map(str::parse).transpose()?;
+    }
+    if let Some(pause_on_battery) = req.pause_on_battery {
+        settings.pause_on_battery = pause_on_battery;
+    }
+    node.throttle.set(settings.clone())?;
+    Ok(Json(settings))
+}
+
 async fn get_peers(State(node): State<Arc<Node>>) -> impl IntoResponse {
-    let peers = node.network.list_peers().await;
+    let peers = node.network.peer_details().await;
+    let (inbound, outbound) = node.network.connection_counts().await;
+    let limits = node.network.peer_limits();
     Json(serde_json::json!({
         "count": peers.len(),
+        "inbound": inbound,
+        "outbound": outbound,
+        "max_inbound": limits.max_inbound,
+        "max_outbound": limits.max_outbound,
         "peers": peers
     }))
 }
 
 async fn get_network_info(State(node): State<Arc<Node>>) -> impl IntoResponse {
     let peers = node.network.list_peers().await;
+    let chain_id = node.blockchain.read().await.chain_id();
     Json(serde_json::json!({
+        "chain_id": hex::encode(chain_id),
         "peer_count": peers.len(),
         "peers": peers,
-        "protocol_version": "1.0"
+        "protocol_version": crate::network::PROTOCOL_VERSION,
+        "min_peer_protocol_version": crate::network::MIN_PEER_PROTOCOL_VERSION,
+        "features": crate::network::features::names(crate::network::features::LOCAL)
     }))
 }
 
-async fn get_address_balance(
+#[derive(Deserialize)]
+struct PeerStatsQuery {
+    #[serde(flatten)]
+    time: TimeRangeQuery,
+    /// Only this peer's samples
+    peer: Option<String>,
+}
+
+async fn get_network_stats_history(
     State(node): State<Arc<Node>>,
-    Path(addr_str): Path<String>,
-) -> impl IntoResponse {
-    let mut addr = [0u8; 32];
-    if hex::decode_to_slice(&addr_str, &mut addr).is_err() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid address format".to_string(),
-            }),
-        )
-            .into_response();
+    Query(query): Query<PeerStatsQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let range = query.time.range()?;
+    let mut samples = match &node.persistence {
+        Some(persistence) => persistence.load_peer_stats(range)?,
+        None => node.blockchain.read().await.persistence.load_peer_stats(range)?,
+    };
+    if let Some(peer) = &query.peer {
+        samples.retain(|sample| &sample.peer == peer);
     }
+    let (points, peers) = peerstats::summarize(&samples);
+    Ok(Json(serde_json::json!({
+        "interval_secs": SAMPLE_INTERVAL.as_secs(),
+        "points": points,
+        "peers": peers
+    })))
+}
 
-    let blockchain = node.blockchain.read().await;
-    // Format the balance (Coord) as a String to preserve floating-point precision
-    let balance = format!("{}", blockchain.state.get_balance(&addr));
-
-    Json(BalanceResponse {
-        balance, // Now a String
-        address: addr_str,
+/// Store a hop report POSTed by a tracing node. Only nodes with tracing on
+/// act as aggregators.
+async fn report_trace_hop(
+    State(node): State<Arc<Node>>,
+    Json(report): Json<HopReport>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    if !node.network.tracing_enabled() {
+        return Err(ApiError::NotFound("Transaction tracing is disabled on this node".to_string()));
+    }
+    if !node.network.traces.write().await.record(report) {
+        return Err(ApiError::InvalidInput(format!(
+            "Trace already holds {} hops",
+            txtrace::MAX_HOPS_PER_TRACE
+        )));
+    }
+    Ok(Json(SuccessResponse {
+        message: "Hop recorded".to_string(),
+    }))
+}
+
+/// Hops of a traced transaction seen here or reported to this node
+async fn get_trace(State(node): State<Arc<Node>>, Path(id): Path<String>) -> Result<Json<serde_json::Value>, ApiError> {
+    let id = id.to_lowercase();
+    let hops = node
+        .network
+        .traces
+        .read()
+        .await
+        .hops(&id)
+        .ok_or_else(|| ApiError::NotFound(format!("Trace {} not found", id)))?;
+    Ok(Json(serde_json::json!({
+        "trace_id": id,
+        "hops": hops
+    })))
+}
+
+async fn get_telemetry_preview(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let report = node.telemetry.report(&node.network).await;
+    Json(serde_json::json!({
+        "enabled": node.telemetry.is_active(),
+        "endpoint": node.telemetry.config.endpoint,
+        "interval_secs": node.telemetry.interval().as_secs(),
+        "payload": report
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddPeerRequest {
+    /// Peer to connect to, as `host:port`
+    pub address: String,
+    /// Remember the peer and reconnect on startup (default `true`)
+    #[serde(default = "default_true")]
+    pub persist: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+async fn add_peer(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<AddPeerRequest>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let peer = crate::addnode::parse_peer_address(&req.address)
+        .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    node.network
+        .clone()
+        .connect_manual_peer(peer.host.clone(), peer.port)
+        .await?;
+    if req.persist {
+        node.network.addnodes.write().await.add(peer.clone())?;
+    }
+    Ok(Json(SuccessResponse {
+        message: format!("Connected to {}", peer.addr()),
+    }))
+}
+
+async fn remove_peer(
+    State(node): State<Arc<Node>>,
+    Path(addr): Path<String>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let peer = crate::addnode::parse_peer_address(&addr)
+        .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    let disconnected = node.network.disconnect_peer(&peer).await;
+    let forgotten = node.network.addnodes.write().await.remove(&peer)?;
+    if !disconnected && !forgotten {
+        return Err(ApiError::NotFound(format!("Not connected to {}", peer.addr())));
+    }
+    Ok(Json(SuccessResponse {
+        message: format!("Removed {}", peer.addr()),
+    }))
+}
+
+async fn get_addnodes(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let nodes = node.network.addnodes.read().await.nodes().to_vec();
+    Json(serde_json::json!({
+        "count": nodes.len(),
+        "nodes": nodes
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanPeerRequest {
+    /// Peer host, optionally with a port
+    pub address: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Ban length; omitted for a permanent ban
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnbanPeerRequest {
+    pub address: String,
+}
+
+async fn get_bans(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let bans = node.network.banlist.read().await.list();
+    Json(serde_json::json!({
+        "count": bans.len(),
+        "bans": bans
+    }))
+}
+
+async fn get_rejected_blocks(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blocks = node.network.rejected_blocks.read().await.list();
+    Json(serde_json::json!({
+        "count": blocks.len(),
+        "max_entries": crate::rejected::MAX_REJECTED_BLOCKS,
+        "blocks": blocks
+    }))
+}
+
+/// Fingerprint of the chain state, for comparing nodes that disagree
+/// Filters of the audit endpoints: `from`/`to` as for block queries,
+/// `source` (exact, or a kind such as `api:`) and `txid`
+#[derive(Deserialize, Default)]
+struct AuditParams {
+    from: Option<String>,
+    to: Option<String>,
+    source: Option<String>,
+    txid: Option<String>,
+    format: Option<String>,
+}
+
+impl AuditParams {
+    fn query(&self) -> Result<AuditQuery, ApiError> {
+        let range = TimeRange::parse(self.from.as_deref(), self.to.as_deref()).map_err(ApiError::InvalidInput)?;
+        Ok(AuditQuery {
+            range: (!range.is_all()).then_some(range),
+            source: self.source.clone(),
+            txid: self.txid.clone(),
+        })
+    }
+}
+
+async fn get_transaction_audit(
+    State(node): State<Arc<Node>>,
+    Query(params): Query<PaginationQuery>,
+    Query(filter): Query<AuditParams>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let entries = node.audit_log.entries(&filter.query()?)?;
+    let total = entries.len();
+    let limit = params.limit.min(500);
+    // Newest first, as for blocks
+    let page: Vec<_> = entries
+        .into_iter()
+        .rev()
+        .skip((params.page * limit) as usize)
+        .take(limit as usize)
+        .collect();
+    Ok(Json(serde_json::json!({
+        "entries": page,
+        "total": total,
+        "page": params.page,
+        "limit": limit
+    })))
+}
+
+/// The whole filtered log as a download, CSV unless `format=jsonl`
+async fn export_transaction_audit(
+    State(node): State<Arc<Node>>,
+    Query(filter): Query<AuditParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let format = match &filter.format {
+        Some(format) => format.parse::<ExportFormat>().map_err(ApiError::InvalidInput)?,
+        None => ExportFormat::default(),
+    };
+    let entries = node.audit_log.entries(&filter.query()?)?;
+    let disposition = format!("attachment; filename=\"tx_audit.{}\"", format.extension());
+    Ok((
+        [
+            (http::header::CONTENT_TYPE, format.content_type().to_string()),
+            (http::header::CONTENT_DISPOSITION, disposition),
+        ],
+        audit::export(&entries, format),
+    ))
+}
+
+async fn get_debug_state(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let tip = blockchain.blocks.last();
+    let last_event = blockchain.journal.recent(1).next().map(journal_entry_json);
+    Json(serde_json::json!({
+        "tip_height": tip.map(|b| b.header.height),
+        "tip_hash": tip.map(|b| hash_to_hex(&b.hash())),
+        "utxo_count": blockchain.state.utxo_set.len(),
+        "address_count": blockchain.state.address_balances.len(),
+        "balance_mismatches": blockchain.state.balance_mismatches().len(),
+        "mempool_size": blockchain.mempool.len(),
+        "mempool_conflicts": blockchain.mempool.conflicts(&blockchain.state).len(),
+        "last_block_event": last_event,
+    }))
+}
+
+/// Page through the UTXO set in hash order
+async fn get_debug_utxos(
+    State(node): State<Arc<Node>>,
+    Query(params): Query<PaginationQuery>,
+) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let mut utxos: Vec<_> = blockchain.state.utxo_set.iter().collect();
+    utxos.sort_by_key(|(hash, _)| **hash);
+    let limit = params.limit.min(1000);
+    let page: Vec<_> = utxos
+        .iter()
+        .skip((params.page * limit) as usize)
+        .take(limit as usize)
+        .map(|(hash, triangle)| {
+            serde_json::json!({
+                "hash": hash_to_hex(hash),
+                "owner": hex::encode(triangle.owner),
+                "value": units::format_number(triangle.effective_value(), Unit::Trc),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "total": utxos.len(),
+        "page": params.page,
+        "limit": limit,
+        "utxos": page,
+    }))
+}
+
+/// Compare the cached address balances with the UTXO set
+async fn get_debug_balances(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let mismatches: Vec<_> = blockchain
+        .state
+        .balance_mismatches()
+        .into_iter()
+        .map(|m| {
+            serde_json::json!({
+                "address": hex::encode(m.address),
+                "stored": units::format_number(m.stored, Unit::Trc),
+                "computed": units::format_number(m.computed, Unit::Trc),
+            })
+        })
+        .collect();
+    Json(serde_json::json!({
+        "addresses": blockchain.state.address_balances.len(),
+        "consistent": mismatches.is_empty(),
+        "mismatches": mismatches,
+    }))
+}
+
+async fn get_debug_mempool(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let conflicts = blockchain.mempool.conflicts(&blockchain.state);
+    Json(serde_json::json!({
+        "size": blockchain.mempool.len(),
+        "conflicts": conflicts,
+    }))
+}
+
+/// Fill of the bounded in-memory structures against their caps
+async fn get_debug_memory(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let mempool = node.blockchain.read().await.mempool.usage();
+    let network = &node.network;
+    Json(serde_json::json!({
+        "mempool": mempool,
+        "sync_buffer": network.synchronizer.buffer_usage().await,
+        "pending_relay": {
+            "count": network.pending_relay_count().await,
+            "max": crate::network::MAX_PENDING_RELAY,
+        },
+        "known_invalid": {
+            "count": network.known_invalid.lock().await.len(),
+            "max": crate::knowninvalid::MAX_KNOWN_INVALID,
+        },
+        "propagation": {
+            "count": network.propagation.read().await.tracked_items(),
+            "max": crate::propagation::MAX_TRACKED_ITEMS,
+        },
+        "traces": {
+            "count": network.traces.read().await.len(),
+            "max": txtrace::MAX_TRACES,
+        },
+    }))
+}
+
+/// Blocks recently applied to and reverted from the tip, newest first
+async fn get_debug_blocks(
+    State(node): State<Arc<Node>>,
+    Query(params): Query<PaginationQuery>,
+) -> impl IntoResponse {
+    let blockchain = node.blockchain.read().await;
+    let limit = params.limit.min(crate::blockchain::MAX_JOURNAL_ENTRIES as u64) as usize;
+    let events: Vec<_> = blockchain
+        .journal
+        .recent(usize::MAX)
+        .skip(params.page as usize * limit)
+        .take(limit)
+        .map(journal_entry_json)
+        .collect();
+    Json(serde_json::json!({
+        "total": blockchain.journal.len(),
+        "events": events,
+    }))
+}
+
+fn journal_entry_json(entry: &crate::blockchain::JournalEntry) -> serde_json::Value {
+    serde_json::json!({
+        "event": entry.event,
+        "height": entry.height,
+        "hash": hash_to_hex(&entry.hash),
+    })
+}
+
+async fn ban_peer(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<BanPeerRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let reason = req.reason.unwrap_or_else(|| "manual ban".to_string());
+    let entry = node
+        .network
+        .ban_peer(&req.address, &reason, req.duration_secs.map(Duration::from_secs))
+        .await
+        .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    Ok(Json(entry))
+}
+
+async fn unban_peer(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<UnbanPeerRequest>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let removed = node
+        .network
+        .unban_peer(&req.address)
+        .await
+        .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    if !removed {
+        return Err(ApiError::NotFound(format!("{} is not banned", req.address)));
+    }
+    Ok(Json(SuccessResponse {
+        message: format!("Unbanned {}", req.address),
+    }))
+}
+
+/// Most addresses one SSE connection may watch
+const MAX_SSE_ADDRESSES: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookWatchRequest {
+    pub address: String,
+    /// `http://` URL notifications are POSTed to
+    pub webhook: String,
+    /// Confirmation depths to report; defaults to 6
+    #[serde(default)]
+    pub depths: Vec<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchEventsQuery {
+    /// Comma-separated hex addresses
+    pub addresses: String,
+    /// Comma-separated confirmation depths
+    #[serde(default)]
+    pub depths: Option<String>,
+}
+
+async fn get_watchlist(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let watches: Vec<Watch> = node
+        .notifier
+        .watches()
+        .into_iter()
+        .filter(|w| w.subscriber.is_persistent())
+        .collect();
+    Json(serde_json::json!({
+        "count": watches.len(),
+        "watches": watches
+    }))
+}
+
+async fn add_webhook_watch(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<WebhookWatchRequest>,
+) -> Result<Json<Watch>, ApiError> {
+    let subscriber = Subscriber::webhook(&req.webhook).map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    let watch =
+        Watch::new(subscriber, &req.address, req.depths).map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    node.notifier.subscribe(watch.clone())?;
+    Ok(Json(watch))
+}
+
+async fn remove_webhook_watch(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<WebhookWatchRequest>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    let subscriber = Subscriber::Webhook { url: req.webhook.clone() };
+    if !node.notifier.unsubscribe(&subscriber, &req.address)? {
+        return Err(ApiError::NotFound(format!("{} is not watching {}", req.webhook, req.address)));
+    }
+    Ok(Json(SuccessResponse {
+        message: format!("{} no longer watches {}", req.webhook, req.address),
+    }))
+}
+
+/// Removes an SSE client's watches when its stream is dropped
+struct SseClient {
+    notifier: Arc<Notifier>,
+    subscriber: Subscriber,
+}
+
+impl Drop for SseClient {
+    fn drop(&mut self) {
+        let _ = self.notifier.remove_subscriber(&self.subscriber);
+    }
+}
+
+/// Stream notifications for the given addresses as server-sent events
+async fn watch_events(
+    State(node): State<Arc<Node>>,
+    Query(query): Query<WatchEventsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let depths = match &query.depths {
+        Some(depths) => depths
+            .split(',')
+            .map(|d| d.trim().parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ApiError::InvalidInput(format!("Invalid depths: {}", e)))?,
+        None => Vec::new(),
+    };
+    let addresses: Vec<&str> = query.addresses.split(',').map(str::trim).filter(|a| !a.is_empty()).collect();
+    if addresses.is_empty() || addresses.len() > MAX_SSE_ADDRESSES {
+        return Err(ApiError::InvalidInput(format!(
+            "Watch between 1 and {} addresses",
+            MAX_SSE_ADDRESSES
+        )));
+    }
+
+    let subscriber = node.notifier.next_sse_client();
+    let watches = addresses
+        .into_iter()
+        .map(|address| Watch::new(subscriber.clone(), address, depths.clone()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| ApiError::InvalidInput(e.to_string()))?;
+    let events = node.notifier.events();
+    for watch in watches {
+        node.notifier.subscribe(watch)?;
+    }
+
+    let client = SseClient {
+        notifier: node.notifier.clone(),
+        subscriber,
+    };
+    let stream = futures_util::stream::unfold((events, client), |(mut events, client)| async move {
+        loop {
+            match events.recv().await {
+                Ok(notification) if notification.subscriber == client.subscriber => {
+                    let event = Event::default()
+                        .event(notification.event.name())
+                        .json_data(&notification);
+                    return Some((event, (events, client)));
+                }
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn get_sync_status(State(node): State<Arc<Node>>) -> impl IntoResponse {
+    let local_height = {
+        let chain = node.blockchain.read().await;
+        chain.blocks.last().map_or(0, |b| b.header.height)
+    };
+    Json(node.network.synchronizer.progress(local_height).await)
+}
+
+/// Unspent triangles at a structural address (`<root hex>/<child indices>`),
+/// or with `<hash>/tree` the subdivision tree of an output
+async fn get_triangle_by_id(
+    State(node): State<Arc<Node>>,
+    Path(id): Path<String>,
+    Query(query): Query<TriangleTreeQuery>,
+) -> Result<Response, ApiError> {
+    // Shares the wildcard route: the router cannot tell `:hash/tree` apart
+    // from a structural address
+    if let Some(hash) = id.strip_suffix("/tree") {
+        return get_triangle_tree(&node, hash, query).await.map(IntoResponse::into_response);
+    }
+    let id: crate::geometry::TriangleId = id
+        .parse()
+        .map_err(|e: ChainError| ApiError::InvalidInput(e.to_string()))?;
+    let blockchain = node.blockchain.read().await;
+    let mut outputs: Vec<_> = blockchain
+        .state
+        .utxo_set
+        .iter()
+        .filter(|(_, triangle)| triangle.id.as_ref() == Some(&id))
+        .map(|(utxo, triangle)| {
+            serde_json::json!({
+                "utxo": hex::encode(utxo),
+                "triangle": triangle,
+            })
+        })
+        .collect();
+    if outputs.is_empty() {
+        return Err(ApiError::NotFound(format!("No unspent triangle at {}", id)));
+    }
+    outputs.sort_by(|a, b| a["utxo"].as_str().cmp(&b["utxo"].as_str()));
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "depth": id.depth(),
+        "outputs": outputs,
+    }))
+    .into_response())
+}
+
+/// Levels below the root returned when no depth is asked for
+const DEFAULT_TREE_DEPTH: u32 = 3;
+
+/// Deepest tree served; each level triples the number of nodes
+const MAX_TREE_DEPTH: u32 = 6;
+
+#[derive(Deserialize)]
+struct TriangleTreeQuery {
+    depth: Option<u32>,
+}
+
+/// One output of a subdivision tree and, down to the depth limit, its
+/// descendants. `subdivided` tells whether it has children, returned or not.
+fn tree_node(blockchain: &Blockchain, hash: &Sha256Hash, triangle: Option<&Triangle>, depth: u32) -> serde_json::Value {
+    let children = blockchain.subdivisions.children(hash);
+    let descendants: Vec<_> = if depth == 0 {
+        Vec::new()
+    } else {
+        children
+            .iter()
+            .map(|(child, triangle)| tree_node(blockchain, child, Some(triangle), depth - 1))
+            .collect()
+    };
+    serde_json::json!({
+        "hash": hex::encode(hash),
+        "id": triangle.and_then(|t| t.id.as_ref()),
+        "owner": triangle.map(|t| hex::encode(t.owner)),
+        "value": triangle.map(|t| units::format_number(t.effective_value(), Unit::Trc)),
+        "unspent": blockchain.state.utxo_set.contains_key(hash),
+        "subdivided": !children.is_empty(),
+        "children": descendants,
+    })
+}
+
+/// Subdivision tree rooted at an output: its children, their children and
+/// so on, with owners and values, down to `depth` levels
+async fn get_triangle_tree(node: &Node, hash_str: &str, query: TriangleTreeQuery) -> Result<impl IntoResponse, ApiError> {
+    let hash = parse_hash(hash_str)?;
+    let depth = query.depth.unwrap_or(DEFAULT_TREE_DEPTH);
+    if depth > MAX_TREE_DEPTH {
+        return Err(ApiError::InvalidInput(format!("Depth must be at most {}", MAX_TREE_DEPTH)));
+    }
+    let blockchain = node.blockchain.read().await;
+    // A spent root is known from the index if a subdivision created it, or
+    // from the archive; otherwise only its descendants are
+    let triangle = blockchain
+        .state
+        .utxo_set
+        .get(&hash)
+        .or_else(|| blockchain.subdivisions.triangle(&hash))
+        .or_else(|| blockchain.archive.as_ref()?.spent(&hash).map(|spent| &spent.triangle));
+    if triangle.is_none() && blockchain.subdivisions.children(&hash).is_empty() {
+        return Err(ApiError::NotFound(format!("Triangle {} not found", hash_str)));
+    }
+    Ok(Json(serde_json::json!({
+        "depth": depth,
+        "parent": blockchain.subdivisions.parent(&hash).map(hex::encode),
+        "root": tree_node(&blockchain, &hash, triangle, depth),
+    })))
+}
+
+#[derive(Deserialize)]
+struct ArchiveHeightQuery {
+    /// Defaults to the chain tip
+    height: Option<u64>,
+}
+
+fn spent_archive(blockchain: &Blockchain) -> Result<&SpentArchive, ApiError> {
+    blockchain
+        .archive
+        .as_ref()
+        .ok_or_else(|| ApiError::Forbidden("Archive mode is not enabled on this node".to_string()))
+}
+
+/// One output as the archive knows it: its triangle, where it came from and,
+/// once spent, what spent it
+fn archived_output(blockchain: &Blockchain, utxo: &Sha256Hash) -> Option<serde_json::Value> {
+    let archive = blockchain.archive.as_ref()?;
+    let origin = archive.origin(utxo)?;
+    let spent = archive.spent(utxo);
+    let triangle = match spent {
+        Some(spent) => &spent.triangle,
+        None => blockchain.state.utxo_set.get(utxo)?,
+    };
+    Some(serde_json::json!({
+        "utxo": hex::encode(utxo),
+        "triangle": triangle,
+        "created_height": origin.height,
+        "created_by": hex::encode(origin.tx),
+        "parent": origin.parent.map(hex::encode),
+        "spent_height": spent.map(|s| s.spent_height),
+        "spending_tx": spent.map(|s| hex::encode(s.spending_tx)),
+    }))
+}
+
+/// A spent or unspent output, with its creation and spend
+async fn get_archived_utxo(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let utxo = parse_hash(&hash_str)?;
+    let blockchain = node.blockchain.read().await;
+    spent_archive(&blockchain)?;
+    archived_output(&blockchain, &utxo)
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Output {} not found", hash_str)))
+}
+
+/// Provenance graph of an output: every output it descends from back to the
+/// coinbase that minted the value, and every output that descends from it
+async fn get_utxo_provenance(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let utxo = parse_hash(&hash_str)?;
+    let blockchain = node.blockchain.read().await;
+    let archive = spent_archive(&blockchain)?;
+    let output = archived_output(&blockchain, &utxo)
+        .ok_or_else(|| ApiError::NotFound(format!("Output {} not found", hash_str)))?;
+
+    let mut ancestors = Vec::new();
+    let mut parent = archive.origin(&utxo).and_then(|origin| origin.parent);
+    while let Some(hash) = parent {
+        ancestors.extend(archived_output(&blockchain, &hash));
+        parent = archive.origin(&hash).and_then(|origin| origin.parent);
+    }
+
+    let mut descendants = Vec::new();
+    let mut queue: std::collections::VecDeque<Sha256Hash> = archive.children(&utxo).iter().copied().collect();
+    while let Some(hash) = queue.pop_front() {
+        descendants.extend(archived_output(&blockchain, &hash));
+        queue.extend(archive.children(&hash));
+    }
+
+    Ok(Json(serde_json::json!({
+        "output": output,
+        "ancestors": ancestors,
+        "descendants": descendants,
+    })))
+}
+
+/// Outputs holding the triangle at a structural address at a given height
+async fn get_triangle_owners_at(
+    State(node): State<Arc<Node>>,
+    Path(id): Path<String>,
+    Query(query): Query<ArchiveHeightQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id: crate::geometry::TriangleId = id
+        .parse()
+        .map_err(|e: ChainError| ApiError::InvalidInput(e.to_string()))?;
+    let blockchain = node.blockchain.read().await;
+    let archive = spent_archive(&blockchain)?;
+    let tip = blockchain.blocks.last().map_or(0, |b| b.header.height);
+    let height = query.height.unwrap_or(tip);
+    if height > tip {
+        return Err(ApiError::InvalidInput(format!("Height {} is above the tip at {}", height, tip)));
+    }
+    let outputs: Vec<_> = archive
+        .outputs_at(&id, height, &blockchain.state)
+        .into_iter()
+        .map(|(utxo, triangle)| {
+            serde_json::json!({
+                "utxo": hex::encode(utxo),
+                "owner": hex::encode(triangle.owner),
+                "triangle": triangle,
+            })
+        })
+        .collect();
+    if outputs.is_empty() {
+        return Err(ApiError::NotFound(format!("Triangle {} had no owner at height {}", id, height)));
+    }
+    Ok(Json(serde_json::json!({
+        "id": id,
+        "height": height,
+        "outputs": outputs,
+    })))
+}
+
+async fn get_address_balance(
+    State(node): State<Arc<Node>>,
+    Path(addr_str): Path<String>,
+) -> impl IntoResponse {
+    let mut addr = [0u8; 32];
+    if hex::decode_to_slice(&addr_str, &mut addr).is_err() {
+        return ApiError::InvalidInput("Invalid address format".to_string()).into_response();
+    }
+
+    let blockchain = node.blockchain.read().await;
+    let confirmed = blockchain.state.get_balance(&addr);
+    let pending = match blockchain.mempool.pending_balance(&addr, &blockchain.state) {
+        Ok(pending) => pending,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    // Exact TRC strings; an f64 would lose precision
+    let available = confirmed.saturating_sub(pending.locked).max(Coord::from_num(0));
+
+    Json(BalanceResponse {
+        balance: units::format_number(confirmed, Unit::Trc),
+        address: addr_str,
+        pending_in: units::format_number(pending.incoming, Unit::Trc),
+        pending_out: units::format_number(pending.outgoing, Unit::Trc),
+        available: units::format_number(available, Unit::Trc),
     })
     .into_response()
 }
 
+/// Confirmed totals of an address from the address index, without paging
+/// through its history
+async fn get_address_summary(
+    State(node): State<Arc<Node>>,
+    Path(addr_str): Path<String>,
+) -> Result<Json<AddressSummaryResponse>, ApiError> {
+    let mut addr = [0u8; 32];
+    hex::decode_to_slice(&addr_str, &mut addr)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    let summary = node.blockchain.read().await.address_index.get(&addr);
+    let zero = Coord::from_num(0);
+    Ok(Json(AddressSummaryResponse {
+        address: addr_str,
+        total_received: units::format_number(summary.map_or(zero, |s| s.received), Unit::Trc),
+        total_sent: units::format_number(summary.map_or(zero, |s| s.sent), Unit::Trc),
+        tx_count: summary.map_or(0, |s| s.tx_count),
+        first_seen_height: summary.map(|s| s.first_seen_height),
+        last_active_height: summary.map(|s| s.last_active_height),
+    }))
+}
+
+/// Public key of an address, once it has signed a transaction or announced
+/// its key on chain
+async fn get_address_pubkey(
+    State(node): State<Arc<Node>>,
+    Path(addr_str): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut addr = [0u8; 32];
+    hex::decode_to_slice(&addr_str, &mut addr)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    let public_key = node
+        .blockchain
+        .read()
+        .await
+        .public_key_of(&addr)
+        .ok_or_else(|| ApiError::NotFound(format!("Public key of {} is not known", addr_str)))?;
+    Ok(Json(serde_json::json!({
+        "address": addr_str,
+        "public_key": hex::encode(public_key),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+struct AddressProofQuery {
+    /// Must be the tip height when given
+    height: Option<u64>,
+}
+
+/// Proof against the UTXO commitment of the tip that an address owns its
+/// outputs and nothing else; check it with `BalanceProof::verify`. Only the
+/// tip's UTXO set is kept, so `?height=` may only name the tip. Limited per
+/// client since a new tip makes the next request rebuild the tree.
+async fn get_address_proof(
+    State(node): State<Arc<Node>>,
+    client: Option<ConnectInfo<SocketAddr>>,
+    Path(addr_str): Path<String>,
+    Query(query): Query<AddressProofQuery>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let client = client.map_or_else(|| "unknown".to_string(), |ConnectInfo(addr)| addr.ip().to_string());
+    node.proof_requests
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .check_rate_limit(&client)?;
+
+    let mut addr = [0u8; 32];
+    hex::decode_to_slice(&addr_str, &mut addr)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    let tip = node.tip_utxo_tree().await?;
+    match query.height {
+        Some(height) if height > tip.height => {
+            return Err(ApiError::NotFound(format!("No block at height {}; the tip is at {}", height, tip.height)));
+        }
+        Some(height) if height < tip.height => {
+            return Err(ApiError::InvalidInput(format!(
+                "Proofs are only served against the tip at height {}, not height {}",
+                tip.height, height
+            )));
+        }
+        _ => {}
+    }
+    let proof = tip.tree.balance_proof(addr, tip.height, tip.block_hash);
+    let balance = proof.verify(&proof.commitment)?;
+    Ok(Json(serde_json::json!({
+        "address": addr_str,
+        "height": proof.height,
+        "block_hash": hash_to_hex(&proof.block_hash),
+        "commitment": hash_to_hex(&proof.commitment),
+        "balance": units::format_number(balance, Unit::Trc),
+        "outputs": proof.outputs.len(),
+        "proof": proof,
+    })))
+}
+
+/// Owner and registration heights of an on-chain name, with or without its
+/// leading `@`
+async fn get_name(
+    State(node): State<Arc<Node>>,
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let name = name.strip_prefix('@').unwrap_or(&name);
+    let blockchain = node.blockchain.read().await;
+    let record = blockchain
+        .names
+        .get(name)
+        .ok_or_else(|| ApiError::NotFound(format!("Name '{}' is not registered", name)))?;
+    let height = blockchain.blocks.last().map_or(0, |b| b.header.height);
+    Ok(Json(serde_json::json!({
+        "name": name,
+        "owner": hex::encode(record.owner),
+        "registered_height": record.registered_height,
+        "expires_height": record.expires_height,
+        "expired": !record.is_active(height),
+    })))
+}
+
+/// Payload of a data transaction, mined or pending. `payload` is `null`
+/// once the block's payloads have been pruned; the committed hash and length
+/// remain.
+async fn get_data_payload(
+    State(node): State<Arc<Node>>,
+    Path(hash_str): Path<String>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let target_hash = parse_hash(&hash_str)?;
+    let blockchain = node.blockchain.read().await;
+    let mined = blockchain.blocks.iter().find_map(|block| {
+        block
+            .transactions
+            .iter()
+            .find(|tx| tx.hash() == target_hash)
+            .map(|tx| (tx, Some(block.header.height)))
+    });
+    let (tx, height) = mined
+        .or_else(|| blockchain.mempool.get_transaction(&target_hash).map(|tx| (tx, None)))
+        .ok_or_else(|| ApiError::NotFound(format!("Transaction {} not found", hash_str)))?;
+    let Transaction::Data(data) = tx else {
+        return Err(ApiError::InvalidInput(format!("Transaction {} is not a data transaction", hash_str)));
+    };
+    Ok(Json(serde_json::json!({
+        "txid": hex::encode(target_hash),
+        "owner": hex::encode(data.owner),
+        "payload": data.payload.as_ref().map(hex::encode),
+        "payload_hash": hex::encode(data.payload_hash),
+        "payload_len": data.payload_len,
+        "fee": units::format_number(data.fee_area, Unit::Trc),
+        "pruned": data.is_pruned(),
+        "height": height,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct PaymentRequestQuery {
+    /// Requested amount, e.g. "2.5" or "2500mTRC"
+    pub amount: Option<String>,
+    pub memo: Option<String>,
+}
+
+/// QR code of a `trinity:` payment request to an address, as SVG
+async fn get_address_qr(
+    Path(addr_str): Path<String>,
+    Query(params): Query<PaymentRequestQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut addr = [0u8; 32];
+    hex::decode_to_slice(&addr_str, &mut addr)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    let mut request = PaymentRequest::new(addr);
+    if let Some(amount) = &params.amount {
+        request = request.with_amount(units::parse_amount(amount, Unit::Trc)?);
+    }
+    if let Some(memo) = params.memo {
+        request = request.with_memo(memo);
+    }
+    let svg = qr::render_svg(&request.qr_code()?);
+    Ok(([(http::header::CONTENT_TYPE, "image/svg+xml")], svg))
+}
+
+/// Unspent triangles owned by an address, newest first, paginated
+async fn get_address_utxos(
+    State(node): State<Arc<Node>>,
+    Path(addr_str): Path<String>,
+    Query(params): Query<PaginationQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut addr = [0u8; 32];
+    hex::decode_to_slice(&addr_str, &mut addr)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    let blockchain = node.blockchain.read().await;
+    let tip_height = blockchain.blocks.last().map_or(0, |b| b.header.height);
+    let heights = blockchain.utxo_creation_heights(&addr);
+
+    let mut utxos: Vec<(&Sha256Hash, &crate::geometry::Triangle)> = blockchain
+        .state
+        .utxo_set
+        .iter()
+        .filter(|(_, triangle)| triangle.owner == addr)
+        .collect();
+    utxos.sort_by(|(a_hash, _), (b_hash, _)| {
+        heights
+            .get(*b_hash)
+            .cmp(&heights.get(*a_hash))
+            .then(a_hash.cmp(b_hash))
+    });
+
+    let total = utxos.len();
+    let limit = params.limit.min(100);
+    let page: Vec<_> = utxos
+        .into_iter()
+        .skip((params.page * limit) as usize)
+        .take(limit as usize)
+        .map(|(hash, triangle)| {
+            let height = heights.get(hash).copied();
+            serde_json::json!({
+                "hash": hash_to_hex(hash),
+                "id": triangle.id,
+                "vertices": [triangle.a, triangle.b, triangle.c],
+                "value": units::format_number(triangle.effective_value(), Unit::Trc),
+                "parent_hash": triangle.parent_hash.as_ref().map(hash_to_hex),
+                "height": height,
+                "confirmations": height.map_or(0, |h| tip_height - h + 1),
+            })
+        })
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "address": addr_str,
+        "utxos": page,
+        "total": total,
+        "page": params.page,
+        "limit": limit
+    })))
+}
+
 async fn get_address_transactions(
     State(node): State<Arc<Node>>,
     Path(addr_str): Path<String>,
+    Query(time): Query<TimeRangeQuery>,
 ) -> impl IntoResponse {
     let mut target_addr = [0u8; 32];
     if hex::decode_to_slice(&addr_str, &mut target_addr).is_err() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Invalid address format".to_string(),
-            }),
-        )
-            .into_response();
+        return ApiError::InvalidInput("Invalid address format".to_string()).into_response();
     }
+    let range = match time.range() {
+        Ok(range) => range,
+        Err(e) => return e.into_response(),
+    };
 
     let blockchain = node.blockchain.read().await;
 
     // We will collect transactions found on the blockchain and transactions found in the mempool.
     let mut transactions: Vec<TransactionHistoryEntry> = Vec::new();
 
-    // 1. Search confirmed transactions in the blockchain
+    // 1. Search confirmed transactions in the blockchain, only in blocks
+    // stamped within the requested time range
     // We iterate backwards from the latest block for common chronological display in wallets.
-    for block in blockchain.blocks.iter().rev() {
+    for block in blocks_in_range(&blockchain, range).into_iter().rev() {
         let block_height = block.header.height;
         for tx in &block.transactions {
             let matches = match tx {
@@ -961,24 +3435,36 @@ async fn get_address_transactions(
                 Transaction::Subdivision(subdivision_tx) => {
                     subdivision_tx.owner_address == target_addr
                 }
-                // Coinbase transactions only have a beneficiary (miner)
+                // Coinbase transactions pay the miner and any reward shares
                 Transaction::Coinbase(coinbase_tx) => {
                     coinbase_tx.beneficiary_address == target_addr
+                        || coinbase_tx.outputs.iter().any(|output| output.address == target_addr)
                 }
+                // Key announcements publish the target's own key
+                Transaction::Announce(announce_tx) => announce_tx.address() == target_addr,
+                Transaction::Register(register_tx) => register_tx.owner == target_addr,
+                Transaction::Data(data_tx) => data_tx.owner == target_addr,
             };
 
             if matches {
                 transactions.push(TransactionHistoryEntry {
-                    transaction: tx.clone(),
+                    transaction: tx.clone().into(),
                     block_height,
                 });
             }
         }
     }
 
-    // 2. Search unconfirmed transactions in the mempool
+    // 2. Search unconfirmed transactions in the mempool, which belong to
+    // the present, when the range covers it
     // These entries will have a block_height of 0 (unconfirmed)
-    for tx in blockchain.mempool.get_all_transactions() {
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let pending = if range.contains(now) {
+        blockchain.mempool.get_all_transactions()
+    } else {
+        Vec::new()
+    };
+    for tx in pending {
         let matches = match &tx {
             // Transfer transaction: involves sender (input) or new_owner (output)
             Transaction::Transfer(transfer_tx) => {
@@ -988,12 +3474,15 @@ async fn get_address_transactions(
             Transaction::Subdivision(subdivision_tx) => subdivision_tx.owner_address == target_addr,
             // Coinbase transactions are never in the mempool
             Transaction::Coinbase(_) => false,
+            Transaction::Announce(announce_tx) => announce_tx.address() == target_addr,
+            Transaction::Register(register_tx) => register_tx.owner == target_addr,
+            Transaction::Data(data_tx) => data_tx.owner == target_addr,
         };
 
         if matches {
             // Unconfirmed transactions are assigned height 0
             transactions.push(TransactionHistoryEntry {
-                transaction: tx.clone(),
+                transaction: tx.into(),
                 block_height: 0,
             });
         }
@@ -1007,14 +3496,163 @@ async fn get_address_transactions(
     .into_response()
 }
 
-async fn create_wallet() -> Result<Json<WalletResponse>, ApiError> {
-    let keypair = KeyPair::generate()
-        .map_err(|e| ApiError::InternalError(format!("Failed to generate keypair: {}", e)))?;
+async fn create_wallet(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<CreateWalletRequest>,
+) -> Result<Json<WalletResponse>, ApiError> {
+    let encrypted = node.create_keystore_wallet(req.name, &req.passphrase).await?;
 
     Ok(Json(WalletResponse {
-        address: hex::encode(keypair.address()),
-        public_key: hex::encode(keypair.public_key.serialize()),
-        private_key: hex::encode(keypair.secret_key.as_ref()),
+        address: encrypted.address.clone(),
+        encrypted_backup: encrypted,
+    }))
+}
+
+async fn unlock_wallet(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<UnlockWalletRequest>,
+) -> Result<Json<UnlockWalletResponse>, ApiError> {
+    let encrypted = match (req.encrypted_backup, req.address) {
+        (Some(backup), _) => backup,
+        (None, Some(address)) => node
+            .find_keystore_entry(&address)
+            .await?
+            .ok_or_else(|| ApiError::NotFound(format!("No keystore entry for {}", address)))?,
+        (None, None) => {
+            return Err(ApiError::InvalidInput(
+                "Either address or encrypted_backup must be provided".to_string(),
+            ))
+        }
+    };
+
+    let ttl = req.ttl_secs.map(Duration::from_secs);
+    let (session_token, expires_in_secs) =
+        node.unlock_wallet(&encrypted, &req.passphrase, ttl).await?;
+
+    Ok(Json(UnlockWalletResponse {
+        address: encrypted.address,
+        session_token,
+        expires_in_secs,
+    }))
+}
+
+async fn lock_wallet(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<LockWalletRequest>,
+) -> Result<Json<SuccessResponse>, ApiError> {
+    if !node.lock_wallet(&req.session_token).await {
+        return Err(ApiError::NotFound("Unknown wallet session".to_string()));
+    }
+
+    Ok(Json(SuccessResponse {
+        message: "Wallet locked".to_string(),
+    }))
+}
+
+async fn get_wallet_sessions(State(node): State<Arc<Node>>) -> Json<Vec<WalletSessionInfo>> {
+    Json(node.wallet_sessions().await)
+}
+
+/// Close all wallet sessions at once, or all of one address, e.g. when a
+/// token may have leaked
+async fn revoke_wallet_sessions(
+    State(node): State<Arc<Node>>,
+    Query(query): Query<RevokeSessionsQuery>,
+) -> Json<serde_json::Value> {
+    let revoked = node.revoke_wallet_sessions(query.address.as_deref()).await;
+    Json(serde_json::json!({ "revoked": revoked }))
+}
+
+async fn sign_transaction(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<SignTransactionRequest>,
+) -> Result<Json<SignTransactionResponse>, ApiError> {
+    let signed = node
+        .sign_with_session(&req.session_token, req.transaction, req.confirm)
+        .await?;
+
+    let mut relayed_to = None;
+    if req.submit {
+        node.blockchain
+            .write()
+            .await
+            .mempool
+            .add_transaction(signed.clone())?;
+        node.api_stats.write().await.transactions_submitted += 1;
+        relayed_to = Some(node.network.broadcast_transaction(&signed).await);
+    }
+
+    Ok(Json(SignTransactionResponse {
+        hash: signed.hash_str(),
+        transaction: signed,
+        submitted: req.submit,
+        relayed_to,
+    }))
+}
+
+/// Replace a stuck pending transfer of the session's wallet with a copy
+/// paying a higher fee, signed by the session, and relay it. Only the fee
+/// increase counts against the spending limits.
+async fn bump_fee(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<BumpFeeRequest>,
+) -> Result<Json<BumpFeeResponse>, ApiError> {
+    let txid = parse_hash(&req.txid)?;
+    let fee = req.fee.as_deref().map(|fee| units::parse_amount(fee, Unit::Trc)).transpose()?;
+    let (bumped, increase) = {
+        let blockchain = node.blockchain.read().await;
+        let mempool = &blockchain.mempool;
+        let current = mempool
+            .get_transaction(&txid)
+            .ok_or_else(|| ApiError::NotFound(format!("{} is not in the mempool", req.txid)))?
+            .fee_area();
+        let fee = match fee {
+            Some(fee) => fee,
+            None => mempool.min_replacement_fee(&txid).unwrap_or(current),
+        };
+        (mempool.fee_bump(&txid, fee)?, fee - current)
+    };
+    let fee = bumped.fee_area;
+    let signed = node
+        .sign_counting(&req.session_token, Transaction::Transfer(bumped), req.confirm, increase)
+        .await?;
+
+    let evicted = node.blockchain.write().await.replace_transaction(signed.clone())?;
+    node.api_stats.write().await.transactions_submitted += 1;
+    let relayed_to = node.network.broadcast_transaction(&signed).await;
+    Ok(Json(BumpFeeResponse {
+        hash: signed.hash_str(),
+        replaced: req.txid,
+        fee: units::format_number(fee, Unit::Trc),
+        evicted: evicted.iter().map(hex::encode).collect(),
+        relayed_to,
+    }))
+}
+
+async fn sign_message(
+    State(node): State<Arc<Node>>,
+    Json(req): Json<SignMessageRequest>,
+) -> Result<Json<SignedMessageResponse>, ApiError> {
+    let (address, signature) = node
+        .sign_message_with_session(&req.session_token, &req.message)
+        .await?;
+
+    Ok(Json(SignedMessageResponse {
+        address,
+        message: req.message,
+        signature,
+    }))
+}
+
+async fn verify_message(
+    Json(req): Json<VerifyMessageRequest>,
+) -> Result<Json<VerifyMessageResponse>, ApiError> {
+    let mut address = [0u8; 32];
+    hex::decode_to_slice(&req.address, &mut address)
+        .map_err(|_| ApiError::InvalidInput("Invalid address format".to_string()))?;
+
+    Ok(Json(VerifyMessageResponse {
+        valid: crypto::verify_message(&address, &req.message, &req.signature).is_ok(),
     }))
 }
 
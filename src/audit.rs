@@ -0,0 +1,286 @@
+//! Audit log of submitted transactions
+//!
+//! Every transaction the node's API or the wallet tools put into the
+//! mempool is recorded with when it was submitted, who submitted it and its
+//! raw encoding, so operators have a trail of the funds that left through
+//! this node. The log is a JSON-lines file next to the wallets that is only
+//! ever appended to; nothing in the node rewrites or prunes it.
+
+use crate::blockchain::TimeRange;
+use crate::error::{ChainError, ResultExt};
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File name of the log inside the wallet directory
+pub const TX_AUDIT_FILE: &str = "tx_audit.jsonl";
+
+/// One submitted transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix milliseconds
+    pub submitted_at: u64,
+    /// Who submitted it, e.g. `api:admin`, `api:203.0.113.7` or
+    /// `wallet:savings`
+    pub source: String,
+    pub txid: String,
+    /// Hex of the bincode encoding, as served by `/api/transaction/:hash/raw`
+    pub raw: String,
+}
+
+/// Filter for [`TransactionAuditLog::entries`]
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub range: Option<TimeRange>,
+    /// Exact source, or a prefix ending in `:` such as `api:`
+    pub source: Option<String>,
+    pub txid: Option<String>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.range.is_none_or(|range| range.contains(entry.submitted_at))
+            && self.source.as_deref().is_none_or(|source| match source.strip_suffix(':') {
+                Some(prefix) => entry.source.split_once(':').is_some_and(|(kind, _)| kind == prefix),
+                None => entry.source == source,
+            })
+            && self.txid.as_deref().is_none_or(|txid| entry.txid.eq_ignore_ascii_case(txid))
+    }
+}
+
+/// The log, either backed by a file or kept in memory for tests and
+/// API nodes without a wallet directory
+#[derive(Debug, Default)]
+pub struct TransactionAuditLog {
+    path: Option<PathBuf>,
+    memory: Mutex<Vec<AuditEntry>>,
+}
+
+impl TransactionAuditLog {
+    /// Create an in-memory log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Log appending to `path`, which is created on the first submission
+    pub fn open(path: &Path) -> Self {
+        TransactionAuditLog {
+            path: Some(path.to_path_buf()),
+            memory: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Log at `tx_audit.jsonl` in a wallet directory
+    pub fn open_in_dir(dir: &Path) -> Self {
+        Self::open(&dir.join(TX_AUDIT_FILE))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Record the submission of `tx` by `source`. The entry is on disk
+    /// when this returns.
+    pub fn record(&self, source: &str, tx: &Transaction) -> Result<AuditEntry, ChainError> {
+        let raw = bincode::serialize(tx)
+            .map_err(|e| ChainError::InvalidTransaction(format!("Serialization failed: {}", e)))?;
+        let entry = AuditEntry {
+            submitted_at: chrono::Utc::now().timestamp_millis() as u64,
+            source: source.to_string(),
+            txid: tx.hash_str(),
+            raw: hex::encode(raw),
+        };
+        let Some(path) = &self.path else {
+            if let Ok(mut memory) = self.memory.lock() {
+                memory.push(entry.clone());
+            }
+            return Ok(entry);
+        };
+
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize audit entry: {}", e)))?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(|| format!("Opening {}", path.display()))?;
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .context(|| format!("Appending to {}", path.display()))?;
+        Ok(entry)
+    }
+
+    /// Entries matching `query`, oldest first
+    pub fn entries(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, ChainError> {
+        let Some(path) = &self.path else {
+            let memory = self.memory.lock().map(|memory| memory.clone()).unwrap_or_default();
+            return Ok(memory.into_iter().filter(|entry| query.matches(entry)).collect());
+        };
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(path).context(|| format!("Reading {}", path.display()))?;
+        let mut entries = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: AuditEntry = serde_json::from_str(line).map_err(|e| {
+                ChainError::WalletError(format!("Invalid audit entry at {}:{}: {}", path.display(), number + 1, e))
+            })?;
+            if query.matches(&entry) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Export format of [`export`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" | "ndjson" => Ok(ExportFormat::Jsonl),
+            _ => Err(format!("Unknown export format '{}' (expected csv or jsonl)", s)),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// `entries` as a CSV document with an RFC 3339 time column, or as JSON lines
+pub fn export(entries: &[AuditEntry], format: ExportFormat) -> String {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            out.push_str("submitted_at,time,source,txid,raw\n");
+            for entry in entries {
+                let time = chrono::DateTime::from_timestamp_millis(entry.submitted_at as i64)
+                    .map(|time| time.to_rfc3339())
+                    .unwrap_or_default();
+                out.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    entry.submitted_at,
+                    time,
+                    csv_field(&entry.source),
+                    entry.txid,
+                    entry.raw
+                ));
+            }
+        }
+        ExportFormat::Jsonl => {
+            for entry in entries {
+                if let Ok(line) = serde_json::to_string(entry) {
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Quote a field holding a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Coord;
+    use crate::transaction::TransferTx;
+
+    fn transfer(nonce: u64) -> Transaction {
+        Transaction::Transfer(TransferTx::new([9u8; 32], [2u8; 32], [1u8; 32], Coord::from_num(1), Coord::from_num(0), nonce))
+    }
+
+    #[test]
+    fn test_log_survives_reopen_and_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = TransactionAuditLog::open_in_dir(dir.path());
+        let first = log.record("wallet:savings", &transfer(1)).unwrap();
+        log.record("api:admin", &transfer(2)).unwrap();
+        log.record("api:203.0.113.7", &transfer(3)).unwrap();
+
+        let reopened = TransactionAuditLog::open_in_dir(dir.path());
+        let all = reopened.entries(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0], first);
+        let raw: Transaction = bincode::deserialize(&hex::decode(&all[0].raw).unwrap()).unwrap();
+        assert_eq!(raw.hash_str(), first.txid);
+
+        let api = AuditQuery { source: Some("api:".to_string()), ..Default::default() };
+        assert_eq!(reopened.entries(&api).unwrap().len(), 2);
+        let admin = AuditQuery { source: Some("api:admin".to_string()), ..Default::default() };
+        assert_eq!(reopened.entries(&admin).unwrap().len(), 1);
+        let by_txid = AuditQuery { txid: Some(first.txid.to_uppercase()), ..Default::default() };
+        assert_eq!(reopened.entries(&by_txid).unwrap(), vec![first.clone()]);
+        let before = AuditQuery {
+            range: Some(TimeRange { from: 0, to: first.submitted_at - 1 }),
+            ..Default::default()
+        };
+        assert!(reopened.entries(&before).unwrap().is_empty());
+
+        // Later submissions are appended, never rewriting what is there
+        let before_append = fs::read_to_string(reopened.path().unwrap()).unwrap();
+        reopened.record("wallet:savings", &transfer(4)).unwrap();
+        assert!(fs::read_to_string(reopened.path().unwrap()).unwrap().starts_with(&before_append));
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let log = TransactionAuditLog::new();
+        log.record("wallet:a,b", &transfer(1)).unwrap();
+        let entries = log.entries(&AuditQuery::default()).unwrap();
+
+        let csv = export(&entries, ExportFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("submitted_at,time,source,txid,raw"));
+        assert!(lines.next().unwrap().contains(&format!(",\"wallet:a,b\",{},", entries[0].txid)));
+
+        let jsonl = export(&entries, ExportFormat::Jsonl);
+        let parsed: AuditEntry = serde_json::from_str(jsonl.trim_end()).unwrap();
+        assert_eq!(parsed, entries[0]);
+        assert_eq!("NDJSON".parse::<ExportFormat>().unwrap(), ExportFormat::Jsonl);
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
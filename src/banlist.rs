@@ -0,0 +1,204 @@
+//! Persistent peer ban list
+//!
+//! Bans are keyed by peer host (IP address or hostname) so that they cover
+//! every port a misbehaving peer connects from. The list is stored as JSON in
+//! the node's data directory and survives restarts; expired bans are dropped
+//! on load.
+
+use crate::error::ChainError;
+use crate::security::current_timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File name of the ban list inside the data directory
+pub const BANLIST_FILE: &str = "banlist.json";
+
+/// Ban applied automatically when a peer's sync failures mark it unreliable
+pub const DEFAULT_MISBEHAVIOR_BAN: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A single banned peer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BanEntry {
+    /// Banned host (IP address or hostname)
+    pub host: String,
+    pub reason: String,
+    /// Unix timestamp (seconds) when the ban was created
+    pub banned_at: u64,
+    /// Unix timestamp (seconds) when the ban lifts; `None` bans permanently
+    pub expires_at: Option<u64>,
+}
+
+impl BanEntry {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|expiry| now >= expiry)
+    }
+}
+
+/// Ban list, optionally backed by a file that is rewritten on every change
+#[derive(Debug, Default)]
+pub struct BanList {
+    path: Option<PathBuf>,
+    entries: HashMap<String, BanEntry>,
+}
+
+impl BanList {
+    /// Create an in-memory ban list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the ban list stored at `path`. A missing file yields an empty list
+    /// that will be created on the first change.
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut list = BanList {
+            path: Some(path.to_path_buf()),
+            entries: HashMap::new(),
+        };
+        if !path.exists() {
+            return Ok(list);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let entries: Vec<BanEntry> = serde_json::from_str(&content)
+            .map_err(|e| ChainError::DatabaseError(format!("Invalid ban list {}: {}", path.display(), e)))?;
+        let now = current_timestamp();
+        list.entries = entries
+            .into_iter()
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| (entry.host.clone(), entry))
+            .collect();
+        Ok(list)
+    }
+
+    /// Load `banlist.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(BANLIST_FILE))
+    }
+
+    /// Write the list to its backing file (no-op for in-memory lists)
+    pub fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&self.list())
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to serialize ban list: {}", e)))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Ban `host` for `duration` (or permanently) and persist the change.
+    /// Re-banning a host replaces its previous entry.
+    pub fn ban(
+        &mut self,
+        host: &str,
+        reason: &str,
+        duration: Option<Duration>,
+    ) -> Result<BanEntry, ChainError> {
+        let host = normalize_host(host)?;
+        let now = current_timestamp();
+        let entry = BanEntry {
+            host: host.clone(),
+            reason: reason.to_string(),
+            banned_at: now,
+            expires_at: duration.map(|d| now.saturating_add(d.as_secs())),
+        };
+        self.entries.insert(host, entry.clone());
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// Lift the ban on `host`. Returns `false` if it was not banned.
+    pub fn unban(&mut self, host: &str) -> Result<bool, ChainError> {
+        let host = normalize_host(host)?;
+        let removed = self.entries.remove(&host).is_some();
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Check whether `host` is currently banned
+    pub fn is_banned(&self, host: &str) -> bool {
+        let Ok(host) = normalize_host(host) else {
+            return false;
+        };
+        self.entries
+            .get(&host)
+            .is_some_and(|entry| !entry.is_expired(current_timestamp()))
+    }
+
+    /// Active bans, ordered by host
+    pub fn list(&self) -> Vec<BanEntry> {
+        let now = current_timestamp();
+        let mut entries: Vec<BanEntry> = self
+            .entries
+            .values()
+            .filter(|entry| !entry.is_expired(now))
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| a.host.cmp(&b.host));
+        entries
+    }
+}
+
+/// Reduce `host`, `host:port` or `ip:port` to the bare lower-case host
+fn normalize_host(input: &str) -> Result<String, ChainError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ChainError::NetworkError("Peer address must not be empty".to_string()));
+    }
+    if let Ok(addr) = input.parse::<SocketAddr>() {
+        return Ok(addr.ip().to_string());
+    }
+    let host = match input.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => host,
+        _ => input,
+    };
+    Ok(host.trim_matches(|c| c == '[' || c == ']').to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut list = BanList::load_from_data_dir(dir.path()).unwrap();
+        list.ban("10.0.0.1:8333", "sent invalid blocks", None).unwrap();
+        list.ban("Seed.Example.org", "spam", Some(Duration::from_secs(3600))).unwrap();
+
+        let reloaded = BanList::load_from_data_dir(dir.path()).unwrap();
+        assert!(reloaded.is_banned("10.0.0.1"));
+        assert!(reloaded.is_banned("10.0.0.1:4444"));
+        assert!(reloaded.is_banned("seed.example.org"));
+        assert_eq!(reloaded.list().len(), 2);
+        assert_eq!(reloaded.list()[0].reason, "sent invalid blocks");
+    }
+
+    #[test]
+    fn test_unban_and_expiry() {
+        let mut list = BanList::new();
+        list.ban("::1", "test", None).unwrap();
+        assert!(list.is_banned("[::1]:8333"));
+        assert!(list.unban("::1").unwrap());
+        assert!(!list.unban("::1").unwrap());
+        assert!(!list.is_banned("::1"));
+
+        list.ban("10.0.0.2", "short", Some(Duration::ZERO)).unwrap();
+        assert!(!list.is_banned("10.0.0.2"));
+        assert!(list.list().is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 #![forbid(unsafe_code)]
 use sha2::{Digest, Sha256};
+use trinitychain::cli;
+use trinitychain::config::load_config;
 use trinitychain::crypto::Address;
 use trinitychain::geometry::Coord;
 use trinitychain::persistence::Database;
@@ -10,15 +12,20 @@ fn address_from_string(s: &str) -> Address {
     hasher.finalize().into()
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    cli::args();
     // Address to check
     let addr = address_from_string("miner");
 
-    let db = Database::open("trinitychain.db")?;
+    let config = load_config()?;
+    let db = Database::open(&config.database.path)?;
     let chain = db.load_blockchain()?;
 
     let balance: Coord = chain.state.get_balance(&addr);
-    let balance_f: f64 = balance.to_num();
 
     println!("Address: miner");
     println!(
@@ -26,7 +33,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         chain.blocks.last().map(|b| b.header.height).unwrap_or(0)
     );
     println!("Balance (raw Coord): {:?}", balance);
-    println!("Balance (as f64): {:.6}", balance_f);
+    println!("Balance: {}", config.display.format(balance));
 
     Ok(())
 }
@@ -3,6 +3,7 @@
 use colored::*;
 
 fn main() {
+    trinitychain::cli::args();
     println!("{}", "TrinityChain CLI".bright_cyan().bold());
     println!("{}", "----------------".bright_cyan());
     println!();
@@ -20,6 +21,8 @@ fn main() {
     println!("  - {}", "trinity-mine-block".bright_white());
     println!("  - {}", "trinity-miner".bright_white());
     println!("  - {}", "trinity-send".bright_white());
+    println!("  - {}", "trinity-name".bright_white());
+    println!("  - {}", "trinity-data".bright_white());
     println!("  - {}", "trinity-history".bright_white());
     println!("  - {}", "trinity-balance".bright_white());
     println!("  - {}", "trinity-wallet".bright_white());
@@ -31,6 +34,11 @@ fn main() {
     println!("  - {}", "trinity-server".bright_white());
     println!("  - {}", "trinity-telegram-bot".bright_white());
     println!();
+    println!(
+        "{}",
+        "Every binary accepts --data-dir <dir> (or TRINITY_DATA_DIR) to choose where the chain and wallets live.".yellow()
+    );
+    println!();
     println!("{}", "Example:".bright_green().underline());
     println!("{}", "  cargo run --bin trinity-node".italic());
 }
@@ -3,12 +3,16 @@
 //!
 //! Command-line interface for managing TrinityChain address book
 
-use std::env;
 use trinitychain::addressbook::{self, AddressBook};
+use trinitychain::cli;
 use trinitychain::error::ChainError;
 
-fn main() -> Result<(), ChainError> {
-    let args: Vec<String> = env::args().collect();
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), ChainError> {
+    let args = cli::args();
 
     if args.len() < 2 {
         print_usage();
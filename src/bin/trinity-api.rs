@@ -6,18 +6,23 @@
 use std::sync::Arc;
 use trinitychain::api::{run_api_server, Node};
 use trinitychain::blockchain::Blockchain;
+use trinitychain::cli;
 use trinitychain::error::ChainError;
 
 #[tokio::main]
-async fn main() -> Result<(), ChainError> {
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), ChainError> {
+    cli::args();
     // Prevent accidental standalone servers; prefer `trinity-node` as authoritative orchestrator.
     if std::env::var("TRINITY_STANDALONE").unwrap_or_default() != "1" {
         eprintln!("This binary is deprecated for production; run `trinity-node` instead or set TRINITY_STANDALONE=1 to continue in standalone mode.");
         return Ok(());
     }
 
-    // Initialize logging (if you have env_logger or similar)
-    // env_logger::init();
+    tracing_subscriber::fmt::init();
 
     println!("🚀 Starting TrinityChain API Server (standalone)...");
 
@@ -5,8 +5,13 @@ use colored::*;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::Color as TableColor;
 use comfy_table::{Attribute, Cell, ContentArrangement, Table};
+use trinitychain::cli::{self, node_api_request};
+use trinitychain::config::{load_config, Config};
 use trinitychain::crypto::address_from_hex;
+use trinitychain::geometry::Coord;
 use trinitychain::persistence::Database;
+use trinitychain::units::{parse_amount, Unit};
+use trinitychain::wallet;
 
 const LOGO: &str = r#"
 ╔═══════════════════════════════════════════════════════════════╗
@@ -20,11 +25,36 @@ const LOGO: &str = r#"
 ╚═══════════════════════════════════════════════════════════════╝
 "#;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[tokio::main]
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+/// What the running node's mempool does to the balance
+struct Pending {
+    incoming: Coord,
+    outgoing: Coord,
+    available: Coord,
+}
+
+/// Pending amounts from the node running on this machine, `None` if none
+/// answers. The database holds confirmed blocks only.
+async fn pending_balance(config: &Config, address: &str) -> Option<Pending> {
+    let json = node_api_request(config, "GET", &format!("/address/{}/balance", address), None)
+        .await
+        .ok()?;
+    let amount = |key: &str| parse_amount(json[key].as_str()?, Unit::Trc).ok();
+    Some(Pending {
+        incoming: amount("pending_in")?,
+        outgoing: amount("pending_out")?,
+        available: amount("available")?,
+    })
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
     println!("{}", LOGO.bright_cyan());
 
-    let args: Vec<String> = std::env::args().collect();
-    let home = std::env::var("HOME")?;
+    let args = cli::args();
 
     // Check if address was provided as argument
     let my_address = if args.len() > 1 {
@@ -33,9 +63,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Otherwise, load from wallet file (support WALLET_NAME env var)
         let wallet_name = std::env::var("WALLET_NAME").unwrap_or_else(|_| String::new());
         let wallet_file = if wallet_name.is_empty() {
-            format!("{}/.trinitychain/wallet.json", home)
+            wallet::get_default_wallet_path()?
         } else {
-            format!("{}/.trinitychain/wallet_{}.json", home, wallet_name)
+            wallet::get_named_wallet_path(&wallet_name)?
         };
 
         let wallet_content = std::fs::read_to_string(&wallet_file).map_err(|e| {
@@ -50,7 +80,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "{}",
                 "💡 Run 'trinity-wallet-new <name>' to create a wallet".yellow()
             );
-            format!("No wallet found at {}: {}", wallet_file, e)
+            format!("No wallet found at {}: {}", wallet_file.display(), e)
         })?;
 
         let wallet_data: serde_json::Value = serde_json::from_str(&wallet_content)
@@ -64,11 +94,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let my_address_bytes = address_from_hex(&my_address)?;
 
-    let db =
-        Database::open("trinitychain.db").map_err(|e| format!("Failed to open database: {}", e))?;
+    let config = load_config()?;
+    let display = &config.display;
+    let db = Database::open(&config.database.path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
     let chain = db
         .load_blockchain()
         .map_err(|e| format!("Failed to load blockchain: {}", e))?;
+    let pending = pending_balance(&config, &my_address).await;
 
     println!(
         "{}",
@@ -146,6 +179,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "╚══════════════════════════════════════════════════════════╝".yellow()
         );
         println!();
+        if let Some(pending) = pending.as_ref().filter(|p| p.incoming > Coord::from_num(0)) {
+            println!(
+                "{}",
+                format!("⏳ Pending incoming: {}", display.format(pending.incoming)).yellow()
+            );
+        }
         println!("{}", "💡 Start mining: miner <your_address>".bright_blue());
         return Ok(());
     }
@@ -185,7 +224,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .add_attribute(Attribute::Bold),
         ]);
 
-    triangle_list.sort_by(|a, b| b.1.cmp(&a.1));
+    triangle_list.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     for (idx, (hash, area)) in triangle_list.iter().enumerate() {
         let hash_short = if hash.len() > 20 {
@@ -197,7 +236,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         table.add_row(vec![
             Cell::new(format!("{}", idx + 1)).fg(TableColor::Yellow),
             Cell::new(&hash_short).fg(TableColor::Green),
-            Cell::new(format!("{:.6}", area)).fg(TableColor::Magenta),
+            Cell::new(display.format(*area)).fg(TableColor::Magenta),
         ]);
     }
 
@@ -224,13 +263,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     println!(
         "{}",
-        format!("║  📐 Total Area: {:<39.6} ║", total_area).green()
+        format!("║  📐 Confirmed: {:<40} ║", display.format(total_area)).green()
     );
+    match &pending {
+        Some(pending) => {
+            println!(
+                "{}",
+                format!("║  ✅ Available: {:<40} ║", display.format(pending.available)).green()
+            );
+            println!(
+                "{}",
+                format!("║  ⏳ Pending In: {:<39} ║", format!("+{}", display.format(pending.incoming))).yellow()
+            );
+            println!(
+                "{}",
+                format!("║  ⏳ Pending Out: {:<38} ║", format!("-{}", display.format(pending.outgoing))).yellow()
+            );
+        }
+        None => println!(
+            "{}",
+            format!("║  ⏳ Pending: {:<42} ║", "unknown (no node API reachable)").dimmed()
+        ),
+    }
 
     let avg_area = total_area / trinitychain::geometry::Coord::from_num(my_triangles);
     println!(
         "{}",
-        format!("║  📊 Average Area: {:<37.6} ║", avg_area).green()
+        format!("║  📊 Average Area: {:<37} ║", display.format(avg_area)).green()
     );
     println!(
         "{}",
@@ -1,55 +1,62 @@
 #![forbid(unsafe_code)]
-use std::env;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use trinitychain::blockchain::Blockchain;
+use trinitychain::cli::{self, CliError, ErrorKind};
 use trinitychain::config::load_config;
 use trinitychain::network::NetworkNode;
 use trinitychain::persistence::Database;
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = env::args().collect();
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::args();
     if args.len() < 2 {
         print_usage();
-        return;
+        return Err(CliError::usage("missing command"));
     }
 
     match args[1].as_str() {
         "peer" => {
             if args.len() < 3 {
-                eprintln!("Usage: trinity-connect peer <ip:port>");
-                return;
+                return Err(CliError::usage("Usage: trinity-connect peer <ip:port>"));
             }
-            connect_peer(&args[2]).await;
+            connect_peer(&args[2]).await
+        }
+        "info" => {
+            show_info().await;
+            Ok(())
+        }
+        other => {
+            print_usage();
+            Err(CliError::usage(format!("unknown command '{}'", other)))
         }
-        "info" => show_info().await,
-        _ => print_usage(),
     }
 }
 
-async fn connect_peer(addr: &str) {
+async fn connect_peer(addr: &str) -> Result<(), CliError> {
     println!("🔗 Connecting to peer: {}", addr);
-    let config = load_config().expect("Failed to load config");
-    let db = Database::open(&config.database.path).expect("DB open failed");
+    let config = load_config()?;
+    let db = Database::open(&config.database.path)
+        .map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
     let blockchain = db
         .load_blockchain()
         .unwrap_or_else(|_| Blockchain::new([0; 32], 1).expect("Failed to create new blockchain"));
     let node = Arc::new(NetworkNode::new(Arc::new(RwLock::new(blockchain))));
 
-    let parts: Vec<&str> = addr.split(':').collect();
-    if parts.len() != 2 {
-        eprintln!("❌ Format: IP:PORT");
-        return;
-    }
-
-    let host = parts[0].to_string();
-    let port = parts[1].parse::<u16>().unwrap_or(8334);
+    let peer = trinitychain::addnode::parse_peer_address(addr).map_err(|e| {
+        CliError::usage(format!("{} (format: IP:PORT or [IPv6]:PORT)", e))
+    })?;
 
-    match node.clone().connect_peer(host, port).await {
-        Ok(_) => println!("✅ Connected! Syncing..."),
-        Err(e) => eprintln!("❌ Failed: {}", e),
-    }
+    node.clone()
+        .connect_peer(peer.host, peer.port)
+        .await
+        .map_err(|e| CliError::new(ErrorKind::NodeUnreachable, format!("Failed: {}", e)))?;
+    println!("✅ Connected! Syncing...");
+    Ok(())
 }
 
 async fn show_info() {
@@ -0,0 +1,226 @@
+#![forbid(unsafe_code)]
+//! Anchor data on chain and read it back
+
+use colored::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use trinitychain::audit::TransactionAuditLog;
+use trinitychain::cli::{self, load_blockchain_from_config, CliError, ErrorKind};
+use trinitychain::crypto::address_from_hex;
+use trinitychain::geometry::Coord;
+use trinitychain::network::NetworkNode;
+use trinitychain::security::current_timestamp;
+use trinitychain::transaction::{DataTx, Transaction, MAX_DATA_PAYLOAD};
+use trinitychain::units::{self, Unit};
+use trinitychain::wallet;
+
+#[tokio::main]
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::args();
+    match args.get(1).map(String::as_str) {
+        Some("anchor") => {
+            let mut payload = None;
+            let mut from = None;
+            let mut fee = None;
+            let mut rest = args[2..].iter();
+            while let Some(arg) = rest.next() {
+                let mut value = |flag: &str| {
+                    rest.next()
+                        .cloned()
+                        .ok_or_else(|| CliError::usage(format!("{} requires a value", flag)))
+                };
+                match arg.as_str() {
+                    "--hex" => {
+                        let bytes = hex::decode(value("--hex")?)
+                            .map_err(|_| CliError::usage("--hex expects hex-encoded bytes"))?;
+                        payload = Some(bytes);
+                    }
+                    "--from" => from = Some(value("--from")?),
+                    "--fee" => {
+                        fee = Some(
+                            units::parse_amount(&value("--fee")?, Unit::Trc).map_err(|e| CliError::usage(e.to_string()))?,
+                        )
+                    }
+                    other if other.starts_with("--") => {
+                        return Err(CliError::usage(format!("Unexpected argument '{}'", other)))
+                    }
+                    path if payload.is_none() => payload = Some(std::fs::read(path)?),
+                    other => return Err(CliError::usage(format!("Unexpected argument '{}'", other))),
+                }
+            }
+            let payload = payload.ok_or_else(|| CliError::usage("anchor requires a file or --hex <bytes>"))?;
+            anchor(payload, from, fee).await
+        }
+        Some("get") => match args.get(2) {
+            Some(txid) => get(txid),
+            None => {
+                print_usage();
+                Err(CliError::usage("get requires a transaction id"))
+            }
+        },
+        _ => {
+            print_usage();
+            Err(CliError::usage("missing command"))
+        }
+    }
+}
+
+/// Put `payload` on chain in a data transaction paid from the wallet
+async fn anchor(payload: Vec<u8>, from: Option<String>, fee: Option<Coord>) -> Result<(), CliError> {
+    if payload.is_empty() || payload.len() > MAX_DATA_PAYLOAD {
+        return Err(CliError::usage(format!(
+            "Payload is {} bytes; it must be 1 to {} bytes",
+            payload.len(),
+            MAX_DATA_PAYLOAD
+        )));
+    }
+    let fee = fee.unwrap_or_else(|| DataTx::min_fee(payload.len()));
+
+    let wallet = match &from {
+        Some(from) => wallet::load_named_wallet(from),
+        None => wallet::load_default_wallet(),
+    }
+    .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let owner = address_from_hex(&wallet.address)?;
+    let keypair = wallet
+        .get_keypair()
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+
+    let (_config, mut chain) =
+        load_blockchain_from_config().map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    if let Ok(mempool_data) = std::fs::read_to_string("mempool.json") {
+        if let Ok(txs) = serde_json::from_str::<Vec<Transaction>>(&mempool_data) {
+            for tx in txs {
+                let _ = chain.mempool.add_transaction(tx);
+            }
+        }
+    }
+
+    // Pay from the smallest triangle that covers the fee and is not
+    // already being spent by a pending transaction
+    let locked: HashSet<_> = chain
+        .mempool
+        .get_all_transactions()
+        .iter()
+        .flat_map(Transaction::inputs)
+        .collect();
+    let input_hash = chain
+        .state
+        .utxo_set
+        .iter()
+        .filter(|(hash, triangle)| triangle.owner == owner && !locked.contains(*hash))
+        .filter(|(_, triangle)| triangle.effective_value() >= fee)
+        .min_by_key(|(_, triangle)| triangle.effective_value())
+        .map(|(hash, _)| *hash)
+        .ok_or_else(|| {
+            CliError::new(
+                ErrorKind::InsufficientFunds,
+                format!("No unlocked triangle covers the {} TRC fee", units::format_number(fee, Unit::Trc)),
+            )
+        })?;
+
+    let payload_len = payload.len();
+    let mut tx = DataTx::new(input_hash, owner, payload, fee, current_timestamp());
+    let signature = keypair.sign(&tx.signable_message(&chain.chain_id()))?;
+    tx.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+    let tx = Transaction::Data(tx);
+
+    let state = chain.state.clone();
+    let domain = chain.signing_domain(chain.next_height());
+    chain.mempool.accept_transaction(tx.clone(), &state, &domain)?;
+    let audit_source = format!("wallet:{}", from.as_deref().unwrap_or("default"));
+    wallet::get_wallet_dir()
+        .map(|dir| TransactionAuditLog::open_in_dir(&dir))
+        .and_then(|log| log.record(&audit_source, &tx))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let all_txs = serde_json::to_string(&chain.mempool.get_all_transactions())
+        .map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    std::fs::write("mempool.json", all_txs)?;
+
+    let network_node = NetworkNode::new(Arc::new(RwLock::new(chain)));
+    network_node.broadcast_transaction(&tx).await;
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "txid": tx.hash_str(),
+                "owner": wallet.address,
+                "payload_len": payload_len,
+                "fee": units::format_number(fee, Unit::Trc),
+            })
+        );
+        return Ok(());
+    }
+    println!("{}", format!("⚓ Anchoring {} bytes", payload_len).bright_green());
+    println!("   Transaction {}", tx.hash_str());
+    println!("   Fee         {} TRC", units::format_number(fee, Unit::Trc));
+    Ok(())
+}
+
+/// Print the payload of a data transaction from the local chain or mempool
+fn get(txid: &str) -> Result<(), CliError> {
+    let hash: [u8; 32] = hex::decode(txid)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| CliError::usage("Transaction id must be 64 hex characters"))?;
+    let (_config, chain) =
+        load_blockchain_from_config().map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+
+    let confirmed = chain
+        .blocks
+        .iter()
+        .find_map(|block| block.transactions.iter().find(|tx| tx.hash() == hash).map(|tx| (tx.clone(), Some(block.header.height))));
+    let pending = || {
+        std::fs::read_to_string("mempool.json")
+            .ok()
+            .and_then(|data| serde_json::from_str::<Vec<Transaction>>(&data).ok())
+            .and_then(|txs| txs.into_iter().find(|tx| tx.hash() == hash))
+            .map(|tx| (tx, None))
+    };
+    let (tx, height) = confirmed
+        .or_else(pending)
+        .ok_or_else(|| CliError::new(ErrorKind::General, format!("Transaction {} not found", txid)))?;
+    let Transaction::Data(data) = tx else {
+        return Err(CliError::usage(format!("Transaction {} is not a data transaction", txid)));
+    };
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "txid": txid,
+                "owner": hex::encode(data.owner),
+                "payload": data.payload.as_ref().map(hex::encode),
+                "payload_hash": hex::encode(data.payload_hash),
+                "payload_len": data.payload_len,
+                "height": height,
+            })
+        );
+        return Ok(());
+    }
+    println!("⚓ Data transaction {}", txid.bright_cyan());
+    println!("   Owner:   {}", hex::encode(data.owner));
+    match height {
+        Some(height) => println!("   Block:   {}", height),
+        None => println!("   Block:   pending"),
+    }
+    println!("   Hash:    {} ({} bytes)", hex::encode(data.payload_hash), data.payload_len);
+    match &data.payload {
+        Some(payload) => println!("   Payload: {}", hex::encode(payload)),
+        None => println!("   {}", "Payload pruned by this node; ask an archive node".yellow()),
+    }
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Usage:");
+    println!("  trinity-data anchor <file> [--from <wallet_name>] [--fee <amount>]  - Put a file's bytes on chain");
+    println!("  trinity-data anchor --hex <bytes> [--from <wallet_name>]           - Put hex-encoded bytes on chain");
+    println!("  trinity-data get <txid>                                             - Show a data transaction's payload");
+}
@@ -2,19 +2,25 @@
 use clap::{Parser, Subcommand};
 use colored::*;
 use std::collections::HashSet;
-use trinitychain::cli::load_blockchain_from_config;
+use trinitychain::build_info;
+use trinitychain::cli::{self, load_blockchain_from_config};
+use trinitychain::config::set_data_dir;
 use trinitychain::crypto::{address_from_hex, address_from_string, address_to_hex};
 use trinitychain::geometry::Coord;
-use trinitychain::transaction::{Transaction, TransferTx};
+use trinitychain::transaction::{Transaction, TransactionBuilder};
 use trinitychain::wallet;
 
 const GUESTBOOK_ADDRESS: &str = "trinity-guestbook-address-00000000000000000";
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(name = "trinity-guestbook", author, version = build_info::long_version(), about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Data directory (defaults to $TRINITY_DATA_DIR, then
+    /// $XDG_DATA_HOME/trinitychain)
+    #[arg(long, global = true)]
+    data_dir: Option<std::path::PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -32,8 +38,15 @@ enum Commands {
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if let Some(dir) = &cli.data_dir {
+        set_data_dir(dir);
+    }
 
     match &cli.command {
         Commands::Sign { message, wallet } => {
@@ -91,22 +104,15 @@ async fn sign(message: &str, wallet_name: Option<&str>) -> Result<(), Box<dyn st
         })
         .ok_or("No UTXOs available to pay for the guestbook signing fee.")?;
 
-    let mut tx = TransferTx::new(
-        *input_hash,
-        address_from_string(GUESTBOOK_ADDRESS),
-        from_address_bytes,
-        Coord::from_num(0),      // No value transferred to the guestbook address
-        Coord::from_num(0.0001), // A small fee to get the transaction mined
-        chain.blocks.len() as u64,
-    )
-    .with_memo(message.to_string())?;
-
-    let message_to_sign = tx.signable_message();
-    let signature = keypair.sign(&message_to_sign)?;
-    let public_key = keypair.public_key.serialize().to_vec();
-    tx.sign(signature.to_vec(), public_key.to_vec());
-
-    let transaction = Transaction::Transfer(tx);
+    let transaction = TransactionBuilder::new()
+        .chain(chain.chain_id())
+        .input(*input_hash)
+        .to(address_from_string(GUESTBOOK_ADDRESS))
+        .amount(Coord::from_num(0)) // No value transferred to the guestbook address
+        .fee(Coord::from_num(0.0001)) // A small fee to get the transaction mined
+        .nonce(chain.blocks.len() as u64)
+        .memo(message)
+        .sign(&keypair)?;
     chain.mempool.add_transaction(transaction.clone())?;
 
     let all_txs = chain.mempool.get_all_transactions();
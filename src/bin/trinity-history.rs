@@ -5,9 +5,13 @@ use colored::*;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::Color as TableColor;
 use comfy_table::{Attribute, Cell, ContentArrangement, Table};
-use trinitychain::cli::load_blockchain_from_config;
-use trinitychain::crypto::{address_from_hex, address_to_hex};
+use trinitychain::blockchain::{Blockchain, TimeRange};
+use trinitychain::cli::{self, load_blockchain_from_config, CliError};
+use trinitychain::crypto::{address_from_hex, address_to_hex, Address, KeyPair};
+use trinitychain::geometry::Coord;
 use trinitychain::transaction::Transaction;
+use trinitychain::units::{self, Unit};
+use trinitychain::wallet;
 
 const LOGO: &str = r#"
 ╔═══════════════════════════════════════════════════════════════╗
@@ -21,11 +25,38 @@ const LOGO: &str = r#"
 ╚═══════════════════════════════════════════════════════════════╝
 "#;
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("{}", LOGO.bright_magenta());
+fn main() {
+    cli::exit_on_error(run());
+}
 
-    let home = std::env::var("HOME")?;
-    let wallet_file = format!("{}/.trinitychain/wallet.json", home);
+/// Command line options: `--from DATE`, `--to DATE` and `--csv`
+struct Options {
+    range: TimeRange,
+    csv: bool,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, CliError> {
+    let (mut from, mut to, mut csv) = (None, None, false);
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" => from = Some(args.next().ok_or_else(|| CliError::usage("--from requires a date"))?),
+            "--to" => to = Some(args.next().ok_or_else(|| CliError::usage("--to requires a date"))?),
+            "--csv" => csv = true,
+            other => return Err(CliError::usage(format!("Unexpected argument '{}'", other))),
+        }
+    }
+    let range = TimeRange::parse(from.map(String::as_str), to.map(String::as_str)).map_err(CliError::usage)?;
+    Ok(Options { range, csv })
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let options = parse_options(&cli::args())?;
+    if !options.csv {
+        println!("{}", LOGO.bright_magenta());
+    }
+
+    let wallet_file = wallet::get_default_wallet_path()?;
 
     let wallet_content = std::fs::read_to_string(&wallet_file).map_err(|e| {
         eprintln!("{}", "╔══════════════════════════════════════════╗".red());
@@ -36,7 +67,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("{}", "╚══════════════════════════════════════════╝".red());
         eprintln!();
         eprintln!("{}", "💡 Run 'wallet new' to create a wallet".yellow());
-        format!("No wallet found at {}: {}", wallet_file, e)
+        format!("No wallet found at {}: {}", wallet_file.display(), e)
     })?;
 
     let wallet_data: serde_json::Value = serde_json::from_str(&wallet_content)
@@ -47,8 +78,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .ok_or("Wallet address not found in wallet file")?;
 
     let my_address_bytes = address_from_hex(my_address)?;
+    // Used only to read memos encrypted to this wallet
+    let my_keypair = wallet_data["secret_key"]
+        .as_str()
+        .and_then(|secret| hex::decode(secret).ok())
+        .and_then(|secret| KeyPair::from_secret_bytes(&secret).ok());
 
-    let (_config, chain) = load_blockchain_from_config()?;
+    let (config, chain) = load_blockchain_from_config()?;
+    if options.csv {
+        return print_csv(&chain, my_address_bytes, my_keypair.as_ref(), options.range);
+    }
 
     let addr_display = if my_address.len() > 40 {
         format!(
@@ -94,8 +133,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut transactions: Vec<TxRecord> = Vec::new();
 
-    // Iterate through all blocks
-    for block in &chain.blocks {
+    // Iterate through the blocks in the requested time range
+    for block in chain.blocks_in(options.range) {
         for tx in &block.transactions {
             match tx {
                 Transaction::Transfer(transfer_tx) => {
@@ -147,8 +186,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         };
 
                         let memo_str = if let Some(memo) = &transfer_tx.memo {
-                            if memo.len() > 20 {
-                                format!(" | \"{}...\"", &memo[..17])
+                            let memo = trinitychain::memo::display(memo, my_keypair.as_ref());
+                            if memo.chars().count() > 20 {
+                                format!(" | \"{}...\"", memo.chars().take(17).collect::<String>())
                             } else {
                                 format!(" | \"{}\"", memo)
                             }
@@ -167,7 +207,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     }
                 }
                 Transaction::Coinbase(coinbase_tx) => {
-                    if coinbase_tx.beneficiary_address == my_address_bytes {
+                    let reward = coinbase_tx.paid_to(&my_address_bytes);
+                    if reward > Coord::from_num(0) {
                         tx_count += 1;
                         received_count += 1;
                         mining_count += 1;
@@ -176,7 +217,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                             block_height: block.header.height,
                             tx_type: "Mining".to_string(),
                             direction: "⛏️  Reward".to_string(),
-                            details: format!("Area: {}", coinbase_tx.reward_area),
+                            details: config.display.format(reward),
                             timestamp: block.header.timestamp as i64,
                             color: TableColor::Cyan,
                         });
@@ -203,6 +244,48 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         });
                     }
                 }
+                Transaction::Announce(announce_tx) => {
+                    if announce_tx.address() == my_address_bytes {
+                        tx_count += 1;
+
+                        transactions.push(TxRecord {
+                            block_height: block.header.height,
+                            tx_type: "Announce".to_string(),
+                            direction: "📢 Key".to_string(),
+                            details: "Public key published".to_string(),
+                            timestamp: block.header.timestamp as i64,
+                            color: TableColor::Blue,
+                        });
+                    }
+                }
+                Transaction::Register(register_tx) => {
+                    if register_tx.owner == my_address_bytes {
+                        tx_count += 1;
+
+                        transactions.push(TxRecord {
+                            block_height: block.header.height,
+                            tx_type: "Register".to_string(),
+                            direction: "🏷️  Name".to_string(),
+                            details: format!("@{}", register_tx.name),
+                            timestamp: block.header.timestamp as i64,
+                            color: TableColor::Blue,
+                        });
+                    }
+                }
+                Transaction::Data(data_tx) => {
+                    if data_tx.owner == my_address_bytes {
+                        tx_count += 1;
+
+                        transactions.push(TxRecord {
+                            block_height: block.header.height,
+                            tx_type: "Data".to_string(),
+                            direction: "📎 Anchor".to_string(),
+                            details: format!("{} bytes, fee {}", data_tx.payload_len, data_tx.fee_area),
+                            timestamp: block.header.timestamp as i64,
+                            color: TableColor::Blue,
+                        });
+                    }
+                }
             }
         }
     }
@@ -306,6 +389,92 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// One CSV row per transaction of `address` in blocks stamped within `range`
+fn print_csv(
+    chain: &Blockchain,
+    address: Address,
+    keypair: Option<&KeyPair>,
+    range: TimeRange,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("height,time,txid,type,direction,counterparty,amount,fee,memo");
+    for block in chain.blocks_in(range) {
+        let time = chrono::DateTime::from_timestamp_millis(block.header.timestamp as i64)
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_default();
+        for tx in &block.transactions {
+            let (kind, direction, counterparty, amount, fee, memo) = match tx {
+                Transaction::Transfer(t) if t.sender == address || t.new_owner == address => {
+                    let (direction, counterparty) = match (t.sender == address, t.new_owner == address) {
+                        (true, true) => ("self", address),
+                        (true, false) => ("sent", t.new_owner),
+                        _ => ("received", t.sender),
+                    };
+                    let fee = if t.sender == address { t.sender_fee() } else { Coord::from_num(0) };
+                    (
+                        "transfer",
+                        direction,
+                        address_to_hex(&counterparty),
+                        units::format_number(t.amount, Unit::Trc),
+                        units::format_number(fee, Unit::Trc),
+                        t.memo
+                            .as_ref()
+                            .map(|memo| trinitychain::memo::display(memo, keypair))
+                            .unwrap_or_default(),
+                    )
+                }
+                Transaction::Coinbase(c) if c.paid_to(&address) > Coord::from_num(0) => (
+                    "coinbase",
+                    "received",
+                    String::new(),
+                    units::format_number(c.paid_to(&address), Unit::Trc),
+                    String::new(),
+                    String::new(),
+                ),
+                Transaction::Subdivision(sub) if sub.owner_address == address => {
+                    ("subdivision", "self", String::new(), String::new(), String::new(), String::new())
+                }
+                Transaction::Announce(announce) if announce.address() == address => {
+                    ("announce", "self", String::new(), String::new(), String::new(), String::new())
+                }
+                Transaction::Register(register) if register.owner == address => {
+                    ("register", "self", String::new(), String::new(), String::new(), format!("@{}", register.name))
+                }
+                Transaction::Data(data) if data.owner == address => (
+                    "data",
+                    "self",
+                    String::new(),
+                    String::new(),
+                    units::format_number(data.fee_area, Unit::Trc),
+                    String::new(),
+                ),
+                _ => continue,
+            };
+            println!(
+                "{},{},{},{},{},{},{},{},{}",
+                block.header.height,
+                time,
+                tx.hash_str(),
+                kind,
+                direction,
+                counterparty,
+                amount,
+                fee,
+                csv_field(&memo)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Quote a free-text CSV field
+fn csv_field(text: &str) -> String {
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    }
+}
+
 fn format_timestamp_short(timestamp: i64) -> String {
     use chrono::DateTime;
 
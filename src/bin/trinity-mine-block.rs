@@ -1,14 +1,22 @@
 #![forbid(unsafe_code)]
-use std::env;
 use std::time::Instant;
 use trinitychain::blockchain::{Block, Blockchain};
+use trinitychain::cli;
+use trinitychain::config::load_config;
 use trinitychain::crypto::{address_from_hex, address_to_hex};
+use trinitychain::geometry::Coord;
+use trinitychain::lockfile::DataDirLock;
 use trinitychain::miner::mine_block;
 use trinitychain::persistence::Database;
 use trinitychain::transaction::{CoinbaseTx, Transaction};
+use trinitychain::units::{self, Unit};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::args();
     if args.len() < 2 {
         eprintln!("Usage: {} <address>", args[0]);
         return Ok(());
@@ -16,7 +24,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let address_hex = &args[1];
     let address = address_from_hex(address_hex)?;
 
-    let db = Database::open("trinitychain.db")?;
+    let config = load_config()?;
+    let display = &config.display;
+    let _lock = DataDirLock::acquire(&config.data_dir())?;
+    let db = Database::open(&config.database.path)?;
     let mut chain = db.load_blockchain().unwrap_or_else(|_| {
         println!("⛓️  No chain found – creating genesis block...\n");
         Blockchain::new(address, 1).unwrap()
@@ -25,10 +36,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let last_block = chain.blocks.last().cloned().unwrap();
     let new_height = last_block.header.height + 1;
 
+    let reward_area = Coord::from_num(1000);
     let coinbase_tx = Transaction::Coinbase(CoinbaseTx {
-        reward_area: trinitychain::geometry::Coord::from_num(1000),
+        reward_area,
         beneficiary_address: address,
         nonce: new_height,
+        height: new_height,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: chain.reward_outputs(reward_area)?,
     });
 
     let transactions = vec![coinbase_tx];
@@ -38,7 +54,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         last_block.hash(),
         chain.difficulty,
         transactions,
-    );
+    )
+    .with_version(chain.next_block_version());
 
     if new_block.header.timestamp <= last_block.header.timestamp {
         new_block.header.timestamp = last_block.header.timestamp + 1;
@@ -68,7 +85,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .state
         .utxo_set
         .values()
-        .find(|t| t.owner == address && t.effective_value().to_num::<f64>() >= 999.0)
+        .find(|t| t.owner == address && t.effective_value() >= reward_area)
         .cloned();
 
     // Print enhanced mining results
@@ -105,17 +122,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "│ Reward Type:         Coinbase Transaction                                             │"
     );
     println!(
-        "│ Amount:              1000.000000 TRC (geometric area units)                          │"
+        "│ Amount:              {:<65} │",
+        display.format(reward_area)
     );
     println!("│ Beneficiary:         {} │", beneficiary);
 
     if let Some(triangle) = reward_triangle {
         let tri_hash = hex::encode(triangle.hash());
-        let area = triangle.effective_value().to_num::<f64>();
         println!("│ Triangle Hash:       {} │", tri_hash);
         println!(
-            "│ Triangle Area:       {:.6}                                          │",
-            area
+            "│ Triangle Area:       {:<65} │",
+            units::format_rounded(triangle.effective_value(), Unit::Area, display.decimals)
         );
         println!(
             "│ Vertices:            A({}, {}), B({}, {}), C({}, {})  │",
@@ -146,8 +163,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     println!("│ Chain Difficulty:    {:<65} │", chain.difficulty);
     println!(
-        "│ Your Balance:        {} TRC (from {} triangles)                    │",
-        chain.state.get_balance(&address).to_num::<f64>(),
+        "│ Your Balance:        {} (from {} triangles)                    │",
+        display.format(chain.state.get_balance(&address)),
         chain
             .state
             .utxo_set
@@ -1,29 +1,30 @@
 #![forbid(unsafe_code)]
 //! Miner CLI for TrinityChain - Clean TUI edition!
 
-use crossterm::{
-    event::{self, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
 use ratatui::{
-    backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block as TuiBlock, Borders, Gauge, Paragraph, Sparkline},
-    Terminal,
 };
-use std::env;
-use std::io;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::sleep;
-use trinitychain::blockchain::{Block, Blockchain};
+use trinitychain::blockchain::{Block, Blockchain, PowAlgorithm};
+use trinitychain::cli;
+use trinitychain::config::{load_config, DisplayConfig};
+use trinitychain::geometry::Coord;
+use trinitychain::lockfile::DataDirLock;
+use trinitychain::miner::{HashRateStats, MinerThrottle, ThrottleSettings};
 use trinitychain::network::NetworkNode;
 use trinitychain::persistence::Database;
+use trinitychain::sync::SyncProgress;
 use trinitychain::transaction::{CoinbaseTx, Transaction};
+use trinitychain::tui::{
+    block_tree, enter_terminal, format_hash, format_number, quit_hint, quit_requested,
+    restore_terminal, sync_gauge, title_bar,
+};
 
 #[derive(Clone)]
 struct MiningStats {
@@ -32,7 +33,8 @@ struct MiningStats {
     uptime_secs: u64,
     avg_block_time: f64,
     difficulty: u64,
-    total_earned: f64,
+    total_earned: Coord,
+    block_reward: Coord,
     max_supply: u64,
     blocks_to_halving: u64,
     halving_era: u64,
@@ -43,6 +45,7 @@ struct MiningStats {
     last_block_time: f64,
     recent_blocks: Vec<(u64, String, String)>, // (height, hash, parent_hash)
     hashrate_history: Vec<u64>,                // Last 20 hashrate samples
+    sync: Option<SyncProgress>,
 }
 
 impl Default for MiningStats {
@@ -53,7 +56,8 @@ impl Default for MiningStats {
             uptime_secs: 0,
             avg_block_time: 0.0,
             difficulty: 1,
-            total_earned: 0.0,
+            total_earned: Coord::from_num(0),
+            block_reward: Coord::from_num(1000),
             max_supply: 420_000_000,
             blocks_to_halving: 210_000,
             halving_era: 0,
@@ -64,33 +68,17 @@ impl Default for MiningStats {
             last_block_time: 0.0,
             recent_blocks: Vec::new(),
             hashrate_history: vec![0; 20],
+            sync: None,
         }
     }
 }
 
-fn format_number(num: u64) -> String {
-    let num_str = num.to_string();
-    let mut result = String::new();
-    let chars: Vec<char> = num_str.chars().collect();
-
-    for (i, &ch) in chars.iter().enumerate() {
-        if i > 0 && (chars.len() - i).is_multiple_of(3) {
-            result.push(',');
-        }
-        result.push(ch);
-    }
-    result
-}
-
-fn format_hash(hash: &str) -> String {
-    if hash.len() > 20 {
-        format!("{}...{}", &hash[..10], &hash[hash.len() - 10..])
-    } else {
-        hash.to_string()
-    }
-}
-
-fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
+fn draw_ui(
+    f: &mut ratatui::Frame,
+    stats: &MiningStats,
+    beneficiary: &str,
+    display: &DisplayConfig,
+) {
     let size = f.size();
 
     // Create main layout
@@ -102,6 +90,7 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
             Constraint::Length(8),  // Mining Status (increased for network peers)
             Constraint::Length(10), // Stats
             Constraint::Length(6),  // Supply Progress
+            Constraint::Length(3),  // Sync Progress
             Constraint::Length(6),  // Hashrate Graph
             Constraint::Length(12), // Blockchain Tree
             Constraint::Min(0),     // Bottom padding
@@ -109,24 +98,7 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
         .split(size);
 
     // Title - Centered and bold
-    let title = Paragraph::new(vec![Line::from(vec![
-        Span::styled("⛏️   ", Style::default().fg(Color::Yellow)),
-        Span::styled(
-            "TRINITY CHAIN MINER",
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Span::styled("   ⛏️", Style::default().fg(Color::Yellow)),
-    ])])
-    .block(
-        TuiBlock::default()
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::Cyan)),
-    )
-    .style(Style::default().fg(Color::White))
-    .alignment(Alignment::Center);
-    f.render_widget(title, chunks[0]);
+    f.render_widget(title_bar("⛏️", "TRINITY CHAIN MINER"), chunks[0]);
 
     // Mining Status Box
     let status_text = vec![
@@ -203,7 +175,7 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
         Line::from(vec![
             Span::styled("     Total Earned: ", Style::default().fg(Color::Gray)),
             Span::styled(
-                format!(" {:.0} TRC ", stats.total_earned),
+                format!(" {} ", display.format(stats.total_earned)),
                 Style::default()
                     .fg(Color::Yellow)
                     .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
@@ -219,7 +191,7 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
             Span::styled("  │  ", Style::default().fg(Color::DarkGray)),
             Span::styled("Reward: ", Style::default().fg(Color::Gray)),
             Span::styled(
-                format!("{} TRC", 1000 / (1 << stats.halving_era)),
+                display.format(stats.block_reward),
                 Style::default()
                     .fg(Color::Green)
                     .add_modifier(Modifier::BOLD),
@@ -258,6 +230,9 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
         ));
     f.render_widget(gauge, chunks[3]);
 
+    // Sync Progress
+    f.render_widget(sync_gauge(stats.sync.as_ref()), chunks[4]);
+
     // Hashrate Graph
     let hashrate_sparkline = Sparkline::default()
         .block(
@@ -275,102 +250,32 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &MiningStats, beneficiary: &str) {
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
         );
-    f.render_widget(hashrate_sparkline, chunks[4]);
+    f.render_widget(hashrate_sparkline, chunks[5]);
 
     // Blockchain Tree - Real Parent-Child Relationships
-    let mut tree_lines = vec![Line::from("")];
-
-    if stats.recent_blocks.is_empty() {
-        tree_lines.push(Line::from(vec![Span::styled(
-            "   Waiting for blocks...",
-            Style::default()
-                .fg(Color::DarkGray)
-                .add_modifier(Modifier::ITALIC),
-        )]));
-    } else {
-        // Show last 5 blocks in tree format
-        let blocks_to_show = stats.recent_blocks.iter().rev().take(5).collect::<Vec<_>>();
-
-        for (i, (height, hash, parent_hash)) in blocks_to_show.iter().enumerate() {
-            let is_latest = i == 0;
-            let color = if is_latest {
-                Color::Green
-            } else if i == 1 {
-                Color::Cyan
-            } else {
-                Color::Gray
-            };
-
-            // Block node
-            tree_lines.push(Line::from(vec![
-                Span::styled("      ", Style::default()),
-                Span::styled("▲", Style::default().fg(color).add_modifier(Modifier::BOLD)),
-                Span::styled(
-                    format!(" #{}", height),
-                    Style::default().fg(color).add_modifier(Modifier::BOLD),
-                ),
-            ]));
-
-            // Hash
-            tree_lines.push(Line::from(vec![
-                Span::styled("     ", Style::default()),
-                Span::styled("╱ ╲", Style::default().fg(color)),
-                Span::styled(
-                    format!("  {}", format_hash(hash)),
-                    Style::default().fg(color),
-                ),
-            ]));
-
-            if i < blocks_to_show.len() - 1 {
-                // Connection to parent
-                tree_lines.push(Line::from(vec![Span::styled(
-                    "      │",
-                    Style::default().fg(Color::DarkGray),
-                )]));
-                tree_lines.push(Line::from(vec![
-                    Span::styled("      │", Style::default().fg(Color::DarkGray)),
-                    Span::styled(
-                        format!("  parent: {}", format_hash(parent_hash)),
-                        Style::default().fg(Color::DarkGray),
-                    ),
-                ]));
-            }
-        }
-    }
-
-    let tree = Paragraph::new(tree_lines).block(
-        TuiBlock::default()
-            .borders(Borders::ALL)
-            .title("🌳 Blockchain Tree (Parent → Child)")
-            .border_style(Style::default().fg(Color::Magenta)),
-    );
-    f.render_widget(tree, chunks[5]);
+    f.render_widget(block_tree(&stats.recent_blocks), chunks[6]);
 
     // Footer
     let footer_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(chunks[6]);
-
-    let help = Paragraph::new(vec![Line::from(vec![
-        Span::styled("Press ", Style::default().fg(Color::DarkGray)),
-        Span::styled(
-            "'q'",
-            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-        ),
-        Span::styled(" to quit", Style::default().fg(Color::DarkGray)),
-    ])]);
-    f.render_widget(help, footer_chunks[0]);
+        .split(chunks[7]);
+
+    f.render_widget(quit_hint(), footer_chunks[0]);
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Enforce that the authoritative node runs mining by default.
     if std::env::var("TRINITY_STANDALONE").unwrap_or_default() != "1" {
         eprintln!("This miner binary should be managed by `trinity-node`. To run standalone, set TRINITY_STANDALONE=1");
         return Ok(());
     }
-    let args: Vec<String> = env::args().collect();
+    let args = cli::args();
     if args.len() < 2 {
         println!("Usage: trinity-miner <beneficiary_address> [--threads <N>]");
         return Ok(());
@@ -392,22 +297,25 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    let config = load_config()?;
+    let _lock = DataDirLock::acquire(&config.data_dir())?;
+    let db_path = config.database.path;
+    let throttle = MinerThrottle::new(ThrottleSettings::from_config(&config.miner)?);
+    let pow = config.network.pow_algorithm()?;
+
     // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = enter_terminal()?;
 
     let stats = Arc::new(Mutex::new(MiningStats::default()));
     let stats_clone = Arc::clone(&stats);
     let beneficiary_clone = beneficiary_address.clone();
 
     // Create and start network node
-    let db_for_network = Database::open("trinitychain.db").expect("Failed to open database");
-    let chain_for_network = db_for_network
+    let db_for_network = Database::open(&db_path).expect("Failed to open database");
+    let mut chain_for_network = db_for_network
         .load_blockchain()
         .unwrap_or_else(|_| Blockchain::new([0; 32], 1).unwrap());
+    chain_for_network.consensus.pow = pow;
     let network = Arc::new(NetworkNode::new(Arc::new(RwLock::new(chain_for_network))));
     let network_clone = network.clone();
 
@@ -420,27 +328,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // Track sync progress for the TUI
+    let sync_network = network.clone();
+    let sync_stats = Arc::clone(&stats);
+    let sync_handle = tokio::spawn(async move {
+        loop {
+            let local_height = {
+                let chain = sync_network.blockchain.read().await;
+                chain.blocks.last().map_or(0, |b| b.header.height)
+            };
+            let progress = sync_network.synchronizer.progress(local_height).await;
+            {
+                let mut s = sync_stats.lock().await;
+                s.network_peers = progress.active_peers;
+                s.sync = Some(progress);
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    });
+
     // Spawn mining task
     let mining_handle = tokio::spawn(async move {
-        mining_loop(beneficiary_clone, threads, stats_clone, Some(network)).await;
+        mining_loop(db_path, beneficiary_clone, threads, throttle, pow, stats_clone, Some(network)).await;
     });
 
     // UI loop
     loop {
         // Check for quit key
-        if event::poll(Duration::from_millis(100)).unwrap_or(false) {
-            if let Event::Key(key) = event::read().unwrap() {
-                if key.code == KeyCode::Char('q') {
-                    break;
-                }
-            }
+        if quit_requested(Duration::from_millis(100)) {
+            break;
         }
 
         // Draw UI
         let stats_lock = stats.lock().await.clone();
         terminal
             .draw(|f| {
-                draw_ui(f, &stats_lock, &beneficiary_address);
+                draw_ui(f, &stats_lock, &beneficiary_address, &config.display);
             })
             .ok();
 
@@ -448,31 +371,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Cleanup
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
+    restore_terminal(&mut terminal)?;
 
     mining_handle.abort();
+    sync_handle.abort();
 
     Ok(())
 }
 
 async fn mining_loop(
+    db_path: String,
     beneficiary_address: String,
     _threads: usize,
+    throttle: MinerThrottle,
+    pow: &'static dyn PowAlgorithm,
     stats: Arc<Mutex<MiningStats>>,
     network: Option<Arc<NetworkNode>>,
 ) {
-    let db = Database::open("trinitychain.db").expect("Failed to open database");
+    let db = Database::open(&db_path).expect("Failed to open database");
     let mut chain = db
         .load_blockchain()
         .unwrap_or_else(|_| Blockchain::new([0; 32], 1).unwrap());
 
     let start_time = Instant::now();
     let mut blocks_mined = 0;
+    let hash_stats = std::sync::Mutex::new(HashRateStats::default());
 
     loop {
         chain = db.load_blockchain().unwrap_or_else(|_| chain.clone());
+        chain.consensus.pow = pow;
 
         let last_block = match chain.blocks.last() {
             Some(block) => block,
@@ -483,18 +410,25 @@ async fn mining_loop(
         };
 
         let new_height = last_block.header.height + 1;
-        let difficulty = chain.difficulty;
+        let difficulty = chain.required_difficulty(chrono::Utc::now().timestamp_millis() as u64);
 
         let mut address = [0u8; 32];
         hex::decode_to_slice(&beneficiary_address, &mut address).unwrap();
+        let reward_area = Coord::from_num(1000);
         let coinbase_tx = Transaction::Coinbase(CoinbaseTx {
-            reward_area: trinitychain::geometry::Coord::from_num(1000),
+            reward_area,
             beneficiary_address: address,
             nonce: 0,
+            height: new_height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: chain
+                .reward_outputs(reward_area)
+                .expect("reward shares leave the miner part of the reward"),
         });
 
-        let mut new_block =
-            Block::new(new_height, last_block.hash(), difficulty, vec![coinbase_tx]);
+        let mut new_block = Block::new(new_height, last_block.hash(), difficulty, vec![coinbase_tx])
+            .with_version(chain.next_block_version());
 
         if new_block.header.timestamp <= last_block.header.timestamp {
             new_block.header.timestamp = last_block.header.timestamp + 1;
@@ -503,12 +437,16 @@ async fn mining_loop(
         // Update status
         {
             let mut s = stats.lock().await;
-            s.mining_status = format!("Mining block #{}...", new_height);
+            s.mining_status = match throttle.current_pause() {
+                Some(reason) => format!("Paused ({:?}) before block #{}", reason, new_height),
+                None => format!("Mining block #{}...", new_height),
+            };
             s.difficulty = difficulty as u64;
         }
 
         let mine_start = Instant::now();
-        let new_block = match trinitychain::miner::mine_block(new_block) {
+        let mined = trinitychain::miner::mine_with_refresh(new_block, chain.consensus.pow, &hash_stats, &throttle, |_, _| async { None });
+        let new_block = match mined.await {
             Ok(b) => b,
             Err(_) => {
                 sleep(Duration::from_secs(1)).await;
@@ -549,7 +487,8 @@ async fn mining_loop(
             s.chain_height = current_height;
             s.uptime_secs = elapsed.as_secs();
             s.avg_block_time = elapsed.as_secs_f64() / blocks_mined as f64;
-            s.total_earned = blocks_mined as f64 * 1000.0;
+            s.total_earned += reward_area;
+            s.block_reward = reward_area;
             s.blocks_to_halving = blocks_to_halving;
             s.halving_era = halving_era;
             s.mining_status = format!("✓ Block #{} mined!", new_height);
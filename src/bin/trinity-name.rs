@@ -0,0 +1,142 @@
+#![forbid(unsafe_code)]
+//! Register and look up on-chain names
+
+use colored::*;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use trinitychain::audit::TransactionAuditLog;
+use trinitychain::cli::{self, load_blockchain_from_config, CliError, ErrorKind};
+use trinitychain::crypto::address_from_hex;
+use trinitychain::network::NetworkNode;
+use trinitychain::security::current_timestamp;
+use trinitychain::transaction::{validate_name, RegisterNameTx, Transaction};
+use trinitychain::wallet;
+
+#[tokio::main]
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::args();
+    let name = args.get(2).map(|name| name.strip_prefix('@').unwrap_or(name));
+
+    match (args.get(1).map(String::as_str), name) {
+        (Some("register"), Some(name)) => {
+            let from = match args.get(3).map(String::as_str) {
+                Some("--from") => Some(
+                    args.get(4)
+                        .ok_or_else(|| CliError::usage("--from requires a wallet name"))?
+                        .clone(),
+                ),
+                Some(other) => return Err(CliError::usage(format!("Unexpected argument '{}'", other))),
+                None => None,
+            };
+            register(name, from).await
+        }
+        (Some("resolve"), Some(name)) => resolve(name),
+        _ => {
+            print_usage();
+            Err(CliError::usage("missing command or name"))
+        }
+    }
+}
+
+/// Register `name` to the wallet's address, or renew it if the wallet
+/// already holds it
+async fn register(name: &str, from: Option<String>) -> Result<(), CliError> {
+    validate_name(name).map_err(|e| CliError::usage(e.to_string()))?;
+    let wallet = match &from {
+        Some(from) => wallet::load_named_wallet(from),
+        None => wallet::load_default_wallet(),
+    }
+    .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let owner = address_from_hex(&wallet.address)?;
+    let keypair = wallet
+        .get_keypair()
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+
+    let (_config, mut chain) =
+        load_blockchain_from_config().map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    if let Ok(mempool_data) = std::fs::read_to_string("mempool.json") {
+        if let Ok(txs) = serde_json::from_str::<Vec<Transaction>>(&mempool_data) {
+            for tx in txs {
+                let _ = chain.mempool.add_transaction(tx);
+            }
+        }
+    }
+
+    // The timestamp nonce gives every renewal its own txid
+    let mut tx = RegisterNameTx::new(name.to_string(), owner, current_timestamp());
+    let next_height = chain.blocks.last().map_or(0, |b| b.header.height + 1);
+    chain.names.check(&tx, next_height)?;
+    let signature = keypair.sign(&tx.signable_message(&chain.chain_id()))?;
+    tx.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+    let tx = Transaction::Register(tx);
+
+    let state = chain.state.clone();
+    let domain = chain.signing_domain(next_height);
+    chain.mempool.accept_transaction(tx.clone(), &state, &domain)?;
+    let audit_source = format!("wallet:{}", from.as_deref().unwrap_or("default"));
+    wallet::get_wallet_dir()
+        .map(|dir| TransactionAuditLog::open_in_dir(&dir))
+        .and_then(|log| log.record(&audit_source, &tx))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let all_txs = serde_json::to_string(&chain.mempool.get_all_transactions())
+        .map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    std::fs::write("mempool.json", all_txs)?;
+
+    let network_node = NetworkNode::new(Arc::new(RwLock::new(chain)));
+    network_node.broadcast_transaction(&tx).await;
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "name": name, "owner": wallet.address, "txid": tx.hash_str() })
+        );
+        return Ok(());
+    }
+    println!("{}", format!("🏷️  Registering @{} to {}", name, wallet.address).bright_green());
+    println!("   Transaction {}", tx.hash_str());
+    println!("   The name is yours once the transaction is mined");
+    Ok(())
+}
+
+fn resolve(name: &str) -> Result<(), CliError> {
+    let (_config, chain) =
+        load_blockchain_from_config().map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    let record = chain
+        .names
+        .get(name)
+        .ok_or_else(|| CliError::new(ErrorKind::InvalidAddress, format!("Name '{}' is not registered", name)))?;
+    let expired = chain.resolve_name(name).is_none();
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "name": name,
+                "owner": hex::encode(record.owner),
+                "registered_height": record.registered_height,
+                "expires_height": record.expires_height,
+                "expired": expired,
+            })
+        );
+        return Ok(());
+    }
+    println!("🏷️  @{}", name.bright_cyan());
+    println!("   Owner:      {}", hex::encode(record.owner));
+    println!("   Registered: block {}", record.registered_height);
+    if expired {
+        println!("   {}", format!("Expired at block {}", record.expires_height).yellow());
+    } else {
+        println!("   Expires:    block {}", record.expires_height);
+    }
+    Ok(())
+}
+
+fn print_usage() {
+    println!("Usage:");
+    println!("  trinity-name register <name> [--from <wallet_name>]  - Register or renew a name");
+    println!("  trinity-name resolve <name>                          - Show who a name points to");
+}
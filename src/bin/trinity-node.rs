@@ -1,13 +1,877 @@
 #![forbid(unsafe_code)]
 //! TrinityChain node launcher
 
-use trinitychain::node::Node;
+use clap::{Parser, Subcommand, ValueEnum};
+use colored::*;
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block as TuiBlock, Borders, Paragraph},
+};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use trinitychain::banlist::BanList;
+use trinitychain::bootstrap;
+use trinitychain::build_info;
+use trinitychain::chainspec::{ChainParams, ChainSpec};
+use trinitychain::cli::{self, node_api_request};
+use trinitychain::config::{
+    ignore_config_file, load_config, set_config_overrides, set_data_dir, set_profile, Config,
+};
+use trinitychain::doctor::{self, Status};
+use trinitychain::error::ChainError;
+use trinitychain::lockfile::DataDirLock;
+use trinitychain::manifest::{self, Manifest};
+use trinitychain::network;
+use trinitychain::node::{LogOutput, Node};
+use trinitychain::persistence::Database;
+use trinitychain::sync::SyncProgress;
+use trinitychain::tui::{
+    block_tree, enter_terminal, format_bytes, format_hash, format_number, quit_hint,
+    quit_requested, restore_terminal, sync_gauge, title_bar, ResourceUsage, Tui,
+};
+use trinitychain::wallet;
+
+#[derive(Parser)]
+#[command(name = "trinity-node", author, version = build_info::long_version(), about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    /// Show a live dashboard instead of log lines; logs go to node.log in
+    /// the data directory
+    #[arg(long)]
+    tui: bool,
+    /// `headless` suits containers: config from TRINITY_* variables and
+    /// --set only, JSON log lines on stdout. `auto` picks it when stdout is
+    /// not a terminal.
+    #[arg(long, value_enum, global = true, default_value_t = Runtime::Auto)]
+    runtime: Runtime,
+    /// Data directory (defaults to $TRINITY_DATA_DIR, then
+    /// $XDG_DATA_HOME/trinitychain)
+    #[arg(long, global = true)]
+    data_dir: Option<std::path::PathBuf>,
+    /// Config profile: dev, testnet, mainnet or a [profiles.<name>] table in
+    /// config.toml (defaults to $TRINITY_PROFILE)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Override a config setting, e.g. --set network.api_port=3001; wins over
+    /// config.toml and TRINITY_* variables
+    #[arg(long = "set", global = true, value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Runtime {
+    Auto,
+    /// Read config.toml and log plain text, or show the dashboard
+    Interactive,
+    Headless,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Manage the persistent peer ban list
+    Peers {
+        #[command(subcommand)]
+        action: PeersCommand,
+    },
+    /// Inspect and manage the mempool of the running node
+    Mempool {
+        #[command(subcommand)]
+        action: MempoolCommand,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Maintain the chain database; stop the node first
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+    /// Initialize an empty data directory from a chain snapshot downloaded
+    /// over HTTP(S), verified against checkpoints and replayed
+    Bootstrap {
+        /// Snapshot URL, as written by `trinity-node db snapshot`
+        #[arg(long)]
+        url: String,
+        /// Block hash the snapshot must contain, in addition to the
+        /// checkpoints built into this release
+        #[arg(long = "checkpoint", value_name = "HEIGHT:HASH")]
+        checkpoints: Vec<String>,
+        /// Public key or address of the operator whose signed manifest the
+        /// snapshot must match; replaces the need for a checkpoint
+        #[arg(long, value_name = "KEY")]
+        publisher: Option<String>,
+        /// Manifest URL, by default the snapshot URL + ".manifest.json"
+        #[arg(long, requires = "publisher")]
+        manifest_url: Option<String>,
+    },
+    /// Sign and verify manifests of files distributed through mirrors
+    Manifest {
+        #[command(subcommand)]
+        action: ManifestCommand,
+    },
+    /// Check config, data directory, database, ports, clock and wallets, and
+    /// say how to fix what is wrong
+    Doctor,
+    /// Mine the genesis block of a private network described by a spec file
+    /// and write the chain params file its nodes load
+    InitNetwork {
+        /// Spec with the network name, initial difficulty, reward schedule
+        /// and premine allocations
+        #[arg(long)]
+        spec: std::path::PathBuf,
+        /// Where to write the chain params
+        #[arg(long, default_value = "chain_params.toml")]
+        out: std::path::PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ManifestCommand {
+    /// List files with their size and SHA-256, signed with a wallet key
+    Sign {
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+        /// Wallet whose key signs; the default wallet when omitted
+        #[arg(long)]
+        wallet: Option<String>,
+        /// Where to write the manifest, by default next to the first file
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Check a manifest's signature and the files it lists, looked up in the
+    /// manifest's directory
+    Verify {
+        manifest: std::path::PathBuf,
+        /// Public key or address the manifest must be signed by
+        #[arg(long, value_name = "KEY")]
+        publisher: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Rebuild the database file to reclaim space left by deleted rows
+    Compact,
+    /// Check the database file and compare the stored UTXO set with a replay
+    /// of the stored chain
+    Check,
+    /// Write the stored chain to a snapshot file for `trinity-node bootstrap`
+    Snapshot {
+        path: std::path::PathBuf,
+        /// Also write a manifest signed with this wallet's key, for mirrors
+        #[arg(long, value_name = "WALLET")]
+        sign: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective config after profiles, environment variables and
+    /// --set overrides are applied
+    Show,
+    /// Print the JSON Schema of config.toml, for validating config files
+    /// before deployment
+    Schema,
+}
+
+#[derive(Subcommand)]
+enum PeersCommand {
+    /// List banned peers
+    List,
+    /// Ban a peer host (IP or hostname, port optional)
+    Ban {
+        address: String,
+        /// Why the peer is banned
+        #[arg(long, default_value = "manual ban")]
+        reason: String,
+        /// Ban length, e.g. "12h" or "7days"; permanent when omitted
+        #[arg(long)]
+        duration: Option<humantime::Duration>,
+    },
+    /// Lift a ban
+    Unban { address: String },
+}
+
+#[derive(Subcommand)]
+enum MempoolCommand {
+    /// List pending transactions ordered by fee density
+    List,
+    /// Pin a transaction so the next block template includes it first
+    Prioritize { hash: String },
+    /// Remove an operator pin
+    Unprioritize { hash: String },
+    /// Drop a transaction from the mempool
+    Remove { hash: String },
+    /// Write every pending transaction to a snapshot file
+    Dump { path: std::path::PathBuf },
+    /// Load a snapshot file written by `mempool dump`, possibly on another
+    /// node; every transaction is validated again
+    Load { path: std::path::PathBuf },
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize the authoritative node orchestrator and run it.
-    let node = Node::init().await?;
-    let node = std::sync::Arc::new(node);
-    node.start().await?;
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    if let Some(dir) = &cli.data_dir {
+        set_data_dir(dir);
+    }
+    if let Some(profile) = &cli.profile {
+        set_profile(profile);
+    }
+    set_config_overrides(cli.overrides);
+    if cli.runtime == Runtime::Headless {
+        ignore_config_file();
+    }
+
+    match cli.command {
+        Some(Commands::Peers { action }) => peers(action),
+        Some(Commands::Mempool { action }) => mempool(action).await,
+        Some(Commands::Config {
+            action: ConfigCommand::Show,
+        }) => {
+            print!("{}", load_config()?.to_toml()?);
+            Ok(())
+        }
+        Some(Commands::Config {
+            action: ConfigCommand::Schema,
+        }) => {
+            println!("{}", serde_json::to_string_pretty(&Config::schema())?);
+            Ok(())
+        }
+        Some(Commands::Db { action }) => db(action),
+        Some(Commands::Bootstrap {
+            url,
+            checkpoints,
+            publisher,
+            manifest_url,
+        }) => {
+            let manifest = publisher.as_deref().map(|publisher| bootstrap::ManifestCheck {
+                url: manifest_url.as_deref(),
+                publisher,
+            });
+            bootstrap(&url, &checkpoints, manifest).await
+        }
+        Some(Commands::Manifest { action }) => manifest_command(action),
+        Some(Commands::Doctor) => doctor(),
+        Some(Commands::InitNetwork { spec, out }) => init_network(&spec, &out),
+        None if cli.tui => {
+            if cli.runtime == Runtime::Headless {
+                return Err("--tui needs a terminal; drop it or use --runtime interactive".into());
+            }
+            let node = Arc::new(Node::init_with_logs(LogOutput::DataDirFile).await?);
+            run_with_dashboard(node).await
+        }
+        None => {
+            let headless = match cli.runtime {
+                Runtime::Auto => !std::io::stdout().is_terminal(),
+                Runtime::Interactive => false,
+                Runtime::Headless => true,
+            };
+            // Initialize the authoritative node orchestrator and run it.
+            let node = if headless {
+                ignore_config_file();
+                let node = Node::init_with_logs(LogOutput::Json).await?;
+                tracing::info!("Headless runtime: config.toml is not read, settings come from TRINITY_* variables");
+                node
+            } else {
+                Node::init().await?
+            };
+            let node = std::sync::Arc::new(node);
+            node.start().await?;
+            Ok(())
+        }
+    }
+}
+
+struct PeerRow {
+    addr: String,
+    inbound: bool,
+    protocol_version: Option<u32>,
+}
+
+#[derive(Default)]
+struct NodeStats {
+    state: String,
+    chain_height: u64,
+    difficulty: u32,
+    chain_work: u128,
+    tip_hash: String,
+    tip_age_secs: u64,
+    recent_blocks: Vec<(u64, String, String)>, // (height, hash, parent_hash)
+    sync: Option<SyncProgress>,
+    peers: Vec<PeerRow>,
+    mempool_size: usize,
+    mempool_bytes: usize,
+    mempool_fees: f64,
+    best_fee_density: f64,
+    uptime_secs: u64,
+    resources: ResourceUsage,
+    database_bytes: Option<u64>,
+}
+
+impl NodeStats {
+    async fn collect(node: &Node, started: Instant) -> Self {
+        let mut stats = NodeStats {
+            state: format!("{:?}", *node.state.read().await),
+            uptime_secs: started.elapsed().as_secs(),
+            resources: ResourceUsage::sample(),
+            database_bytes: std::fs::metadata(&node.config.database.path).map(|m| m.len()).ok(),
+            ..Default::default()
+        };
+        {
+            let chain = node.blockchain.read().await;
+            if let Some(tip) = chain.blocks.last() {
+                stats.chain_height = tip.header.height;
+                stats.tip_hash = hex::encode(tip.hash());
+                let now = chrono::Utc::now().timestamp_millis() as u64;
+                stats.tip_age_secs = now.saturating_sub(tip.header.timestamp) / 1000;
+            }
+            stats.difficulty = chain.difficulty;
+            stats.chain_work = chain.total_work();
+            let start = chain.blocks.len().saturating_sub(5);
+            stats.recent_blocks = chain.blocks[start..]
+                .iter()
+                .map(|b| (b.header.height, hex::encode(b.hash()), hex::encode(b.header.previous_hash)))
+                .collect();
+
+            let entries = chain.mempool.fee_density_report();
+            stats.mempool_size = entries.len();
+            stats.mempool_bytes = entries.iter().map(|e| e.size).sum();
+            stats.mempool_fees = entries.iter().map(|e| e.fee_area).sum();
+            stats.best_fee_density = entries.iter().map(|e| e.fee_density).fold(0.0, f64::max);
+        }
+        stats.sync = Some(node.network.synchronizer.progress(stats.chain_height).await);
+        stats.peers = node
+            .network
+            .peer_details()
+            .await
+            .into_iter()
+            .map(|peer| PeerRow {
+                addr: peer.node.addr(),
+                inbound: peer.direction == network::Direction::Inbound,
+                protocol_version: peer.capabilities.map(|c| c.protocol_version),
+            })
+            .collect();
+        stats
+    }
+}
+
+/// Run the node with the dashboard in the foreground until 'q' is pressed
+async fn run_with_dashboard(node: Arc<Node>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut terminal = enter_terminal()?;
+    let result = tokio::select! {
+        result = node.clone().start() => result,
+        result = dashboard(&node, &mut terminal) => result.map_err(Into::into),
+    };
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+async fn dashboard(node: &Node, terminal: &mut Tui) -> std::io::Result<()> {
+    let started = Instant::now();
+    loop {
+        if quit_requested(Duration::ZERO) {
+            return Ok(());
+        }
+        let stats = NodeStats::collect(node, started).await;
+        terminal.draw(|f| draw_ui(f, &stats))?;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+fn label(text: &str) -> Span<'static> {
+    Span::styled(text.to_string(), Style::default().fg(Color::Gray))
+}
+
+fn draw_ui(f: &mut ratatui::Frame, stats: &NodeStats) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([
+            Constraint::Length(3),  // Title
+            Constraint::Length(8),  // Chain + Resources
+            Constraint::Length(3),  // Sync Progress
+            Constraint::Length(9),  // Peers + Mempool
+            Constraint::Length(12), // Blockchain Tree
+            Constraint::Min(0),     // Footer
+        ])
+        .split(f.size());
+
+    f.render_widget(title_bar("🔺", "TRINITY CHAIN NODE"), chunks[0]);
+
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(chunks[1]);
+
+    let state_color = match stats.state.as_str() {
+        "Ready" => Color::Green,
+        "Degraded" => Color::Red,
+        _ => Color::Yellow,
+    };
+    let chain_text = vec![
+        Line::from(vec![
+            label("State: "),
+            Span::styled(
+                stats.state.clone(),
+                Style::default().fg(state_color).add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(vec![
+            label("Height: "),
+            Span::styled(
+                format_number(stats.chain_height),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ),
+            label("   Difficulty: "),
+            Span::styled(stats.difficulty.to_string(), Style::default().fg(Color::Red)),
+        ]),
+        Line::from(vec![
+            label("Chain Work: "),
+            Span::styled(format!("{:#x}", stats.chain_work), Style::default().fg(Color::Magenta)),
+        ]),
+        Line::from(vec![
+            label("Tip: "),
+            Span::styled(format_hash(&stats.tip_hash), Style::default().fg(Color::Green)),
+        ]),
+        Line::from(vec![
+            label("Tip Age: "),
+            Span::styled(format!("{}s", stats.tip_age_secs), Style::default().fg(Color::Blue)),
+        ]),
+    ];
+    let chain = Paragraph::new(chain_text).block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .title("⛓️ Chain")
+            .border_style(Style::default().fg(Color::Green)),
+    );
+    f.render_widget(chain, top[0]);
+
+    let unknown = || "n/a".to_string();
+    let resources_text = vec![
+        Line::from(vec![
+            label("Uptime: "),
+            Span::styled(format!("{}s", stats.uptime_secs), Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(vec![
+            label("Memory: "),
+            Span::styled(
+                stats.resources.resident_bytes.map(format_bytes).unwrap_or_else(unknown),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            label("Threads: "),
+            Span::styled(
+                stats.resources.threads.map(|t| t.to_string()).unwrap_or_else(unknown),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            label("Database: "),
+            Span::styled(
+                stats.database_bytes.map(format_bytes).unwrap_or_else(unknown),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+    ];
+    let resources = Paragraph::new(resources_text).block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .title("🖥️ Resources")
+            .border_style(Style::default().fg(Color::Blue)),
+    );
+    f.render_widget(resources, top[1]);
+
+    f.render_widget(sync_gauge(stats.sync.as_ref()), chunks[2]);
+
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(chunks[3]);
+
+    let mut peer_lines: Vec<Line> = stats
+        .peers
+        .iter()
+        .map(|peer| {
+            let (arrow, color) = if peer.inbound { ("⇠ in ", Color::Blue) } else { ("⇢ out", Color::Green) };
+            Line::from(vec![
+                Span::styled(format!("{} ", arrow), Style::default().fg(color)),
+                Span::styled(peer.addr.clone(), Style::default().fg(Color::White)),
+                Span::styled(
+                    peer.protocol_version.map(|v| format!("  v{}", v)).unwrap_or_default(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        })
+        .collect();
+    if peer_lines.is_empty() {
+        peer_lines.push(Line::from(Span::styled(
+            "No peers connected",
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        )));
+    }
+    let peers = Paragraph::new(peer_lines).block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .title(format!("🌐 Peers ({})", stats.peers.len()))
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+    f.render_widget(peers, middle[0]);
+
+    let mempool_text = vec![
+        Line::from(vec![
+            label("Transactions: "),
+            Span::styled(
+                stats.mempool_size.to_string(),
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(vec![
+            label("Size: "),
+            Span::styled(format_bytes(stats.mempool_bytes as u64), Style::default().fg(Color::Yellow)),
+        ]),
+        Line::from(vec![
+            label("Total Fees: "),
+            Span::styled(format!("{:.4}", stats.mempool_fees), Style::default().fg(Color::Green)),
+        ]),
+        Line::from(vec![
+            label("Best Fee Density: "),
+            Span::styled(format!("{:.4}", stats.best_fee_density), Style::default().fg(Color::Magenta)),
+        ]),
+    ];
+    let mempool = Paragraph::new(mempool_text).block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .title("📥 Mempool")
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(mempool, middle[1]);
+
+    f.render_widget(block_tree(&stats.recent_blocks), chunks[4]);
+    f.render_widget(quit_hint(), chunks[5]);
+}
+
+/// Edit the ban list on disk. A running node picks the changes up on its next
+/// start; use the `/api/network/ban` endpoints to change a live node.
+fn peers(action: PeersCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let mut banlist = BanList::load_from_data_dir(&config.data_dir())?;
+
+    match action {
+        PeersCommand::List => {
+            let bans = banlist.list();
+            if bans.is_empty() {
+                println!("{}", "No banned peers".green());
+            }
+            for ban in bans {
+                let expiry = match ban.expires_at {
+                    Some(ts) => chrono::DateTime::from_timestamp(ts as i64, 0)
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_else(|| ts.to_string()),
+                    None => "never".to_string(),
+                };
+                println!(
+                    "🚫 {}  {}  (expires: {})",
+                    ban.host.bright_red(),
+                    ban.reason,
+                    expiry.dimmed()
+                );
+            }
+        }
+        PeersCommand::Ban {
+            address,
+            reason,
+            duration,
+        } => {
+            let entry = banlist.ban(&address, &reason, duration.map(Into::into))?;
+            println!("{} {}", "✅ Banned".green(), entry.host.bright_cyan());
+        }
+        PeersCommand::Unban { address } => {
+            if banlist.unban(&address)? {
+                println!("{} {}", "✅ Unbanned".green(), address.bright_cyan());
+            } else {
+                println!("{} {}", "⚠️  Not banned:".yellow(), address);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Run a maintenance task on the configured database
+fn db(action: DbCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let _lock = DataDirLock::acquire(&config.data_dir())?;
+    let db = Database::open(&config.database.path)?;
+
+    match action {
+        DbCommand::Compact => {
+            let stats = db.compact()?;
+            println!(
+                "{} {} -> {} ({} reclaimed)",
+                "✅ Compacted".green(),
+                format_bytes(stats.bytes_before),
+                format_bytes(stats.bytes_after),
+                format_bytes(stats.reclaimed()).bright_cyan()
+            );
+        }
+        DbCommand::Check => {
+            let report = db.integrity_check()?;
+            if report.is_ok() {
+                println!("{} {}", "✅ Database is consistent:".green(), report.summary());
+                return Ok(());
+            }
+            let listed = [
+                ("missing", &report.missing_utxos),
+                ("unexpected", &report.unexpected_utxos),
+                ("mismatched", &report.mismatched_utxos),
+            ];
+            for (label, hashes) in listed {
+                for hash in hashes {
+                    println!("  {:<10} {}", label.yellow(), hex::encode(hash));
+                }
+            }
+            return Err(ChainError::DatabaseError(format!("Integrity check failed: {}", report.summary())).into());
+        }
+        DbCommand::Snapshot { path, sign } => {
+            let chain = db.load_blockchain()?;
+            bootstrap::write_chain_snapshot(&chain.blocks, &path, config.database.snapshot_compression_level)?;
+            println!(
+                "{} {} blocks to {} ({})",
+                "✅ Wrote".green(),
+                chain.blocks.len(),
+                path.display(),
+                format_bytes(std::fs::metadata(&path)?.len()).bright_cyan()
+            );
+            if let Some(wallet) = sign {
+                let manifest = Manifest::create(std::slice::from_ref(&path), &wallet_keypair(Some(&wallet))?)?;
+                let manifest_path = manifest::manifest_path(&path);
+                manifest.save(&manifest_path)?;
+                println!(
+                    "{} {}, signed by {}",
+                    "✅ Wrote".green(),
+                    manifest_path.display(),
+                    manifest.signer_address()
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Key of the named wallet, or of the default wallet
+fn wallet_keypair(name: Option<&str>) -> Result<trinitychain::crypto::KeyPair, Box<dyn std::error::Error>> {
+    let wallet = match name {
+        Some(name) => wallet::load_named_wallet(name)?,
+        None => wallet::load_default_wallet()?,
+    };
+    Ok(wallet.get_keypair()?)
+}
+
+fn manifest_command(action: ManifestCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        ManifestCommand::Sign { files, wallet, out } => {
+            let manifest = Manifest::create(&files, &wallet_keypair(wallet.as_deref())?)?;
+            let out = out.unwrap_or_else(|| manifest::manifest_path(&files[0]));
+            manifest.save(&out)?;
+            println!(
+                "{} {} listing {} file(s), signed by {}",
+                "✅ Wrote".green(),
+                out.display(),
+                manifest.files.len(),
+                manifest.signer_address()
+            );
+        }
+        ManifestCommand::Verify { manifest, publisher } => {
+            let dir = manifest.parent().unwrap_or(Path::new("."));
+            let loaded = Manifest::load(&manifest)?;
+            loaded.verify(&publisher)?;
+            loaded.verify_files(dir)?;
+            for entry in &loaded.files {
+                println!("  {} {} ({})", "✓".green(), entry.name, format_bytes(entry.size));
+            }
+            println!(
+                "{} signed by {} on {}",
+                "✅ Manifest verified,".green(),
+                loaded.signer_address(),
+                loaded.created
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run the startup self-test and print what to fix
+fn doctor() -> Result<(), Box<dyn std::error::Error>> {
+    let report = doctor::run();
+    for check in &report.checks {
+        let mark = match check.status {
+            Status::Pass => "✓".green(),
+            Status::Warn => "!".yellow(),
+            Status::Fail => "✗".red(),
+        };
+        println!("{} {:<15} {}", mark, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("  {:<15} {}", "", hint.dimmed());
+        }
+    }
+    if report.failures() > 0 {
+        return Err(format!("{} check(s) failed, {} warning(s)", report.failures(), report.warnings()).into());
+    }
+    println!("{} {} warning(s)", "✅ No problems found,".green(), report.warnings());
+    Ok(())
+}
+
+/// Generate the chain params of a new network and print how nodes join it
+fn init_network(spec: &Path, out: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if out.exists() {
+        return Err(format!("{} already exists; remove it or pass another --out", out.display()).into());
+    }
+    let spec = ChainSpec::load(spec)?;
+    println!("⛏️  Mining the genesis block of '{}' at difficulty {}", spec.name, spec.initial_difficulty);
+    let (params, genesis) = ChainParams::generate(spec)?;
+    params.save(out)?;
+
+    let premine: f64 = params.spec.premine.iter().map(|allocation| allocation.amount).sum();
+    println!("{} {}", "✅ Wrote".green(), out.display());
+    println!("  {:<13} {}", "Genesis hash", hex::encode(genesis.hash()).bright_cyan());
+    println!("  {:<13} {}", "Magic", params.magic);
+    println!("  {:<13} {} to {} address(es)", "Premine", premine, params.spec.premine.len());
+    let halving = match params.spec.reward.halving_interval {
+        0 => "never halving".to_string(),
+        interval => format!("halving every {} blocks", interval),
+    };
+    println!("  {:<13} {} {}", "Subsidy", params.spec.reward.initial, halving);
+    println!();
+    println!("Copy {} to every node and add to its config.toml:", out.display());
+    println!();
+    print!("{}", params.config_snippet(Path::new(out.file_name().unwrap_or(out.as_os_str()))));
+    println!();
+    println!("{}", "Relative chain_params paths are looked up in the node's data directory.".dimmed());
+    Ok(())
+}
+
+/// Download, verify and install a chain snapshot into the data directory
+async fn bootstrap(
+    url: &str,
+    checkpoints: &[String],
+    manifest: Option<bootstrap::ManifestCheck<'_>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+    let _lock = DataDirLock::acquire(&config.data_dir())?;
+    let checkpoints = checkpoints
+        .iter()
+        .map(|text| bootstrap::parse_checkpoint(text))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!("⬇️  Downloading snapshot from {}", url);
+    let chain = bootstrap::bootstrap(
+        url,
+        &checkpoints,
+        manifest,
+        &config.data_dir(),
+        Path::new(&config.database.path),
+    ).await?;
+    let tip = chain.blocks.last().expect("chain has a genesis block");
+    println!(
+        "{} at height {} ({}); start the node to sync the rest from peers",
+        "✅ Bootstrapped".green(),
+        format_number(tip.header.height).bright_cyan(),
+        format_hash(&hex::encode(tip.hash()))
+    );
+    Ok(())
+}
+
+/// Manage the mempool through the running node's admin API
+async fn mempool(action: MempoolCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config()?;
+
+    let response = match action {
+        MempoolCommand::List => {
+            let response = node_api_request(&config, "GET", "/mempool/by-fee", None).await?;
+            let entries = response["entries"].as_array().cloned().unwrap_or_default();
+            if entries.is_empty() {
+                println!("{}", "Mempool is empty".green());
+            }
+            for entry in entries {
+                let pin = if entry["prioritized"].as_bool().unwrap_or(false) {
+                    "📌"
+                } else {
+                    "  "
+                };
+                println!(
+                    "{} {}  fee {:.4}  {} B  density {:.4}",
+                    pin,
+                    entry["hash"].as_str().unwrap_or_default().bright_cyan(),
+                    entry["fee_area"].as_f64().unwrap_or_default(),
+                    entry["size"].as_u64().unwrap_or_default(),
+                    entry["fee_density"].as_f64().unwrap_or_default()
+                );
+            }
+            return Ok(());
+        }
+        MempoolCommand::Prioritize { hash } => {
+            node_api_request(&config, "POST", &format!("/mempool/{}/prioritize", hash), None).await?
+        }
+        MempoolCommand::Unprioritize { hash } => {
+            node_api_request(&config, "DELETE", &format!("/mempool/{}/prioritize", hash), None)
+                .await?
+        }
+        MempoolCommand::Remove { hash } => {
+            node_api_request(&config, "DELETE", &format!("/mempool/{}", hash), None).await?
+        }
+        MempoolCommand::Dump { path } => {
+            let snapshot = node_api_request(&config, "GET", "/mempool/snapshot", None).await?;
+            std::fs::write(&path, serde_json::to_string_pretty(&snapshot)?)?;
+            println!(
+                "{} Wrote {} pending transaction(s) at height {} to {}",
+                "✅".green(),
+                snapshot["entries"].as_array().map_or(0, Vec::len),
+                snapshot["tip_height"].as_u64().unwrap_or_default(),
+                path.display()
+            );
+            return Ok(());
+        }
+        MempoolCommand::Load { path } => {
+            let snapshot: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            let report = node_api_request(&config, "POST", "/mempool/snapshot", Some(snapshot)).await?;
+            let count = |key: &str| report[key].as_array().map_or(0, Vec::len);
+            println!(
+                "{} Restored {} transaction(s), {} already pending, {} rejected",
+                "✅".green(),
+                count("restored"),
+                report["already_pending"].as_u64().unwrap_or_default(),
+                count("rejected")
+            );
+            for rejected in report["rejected"].as_array().cloned().unwrap_or_default() {
+                println!(
+                    "   {} {}: {}",
+                    "✗".red(),
+                    rejected["txid"].as_str().unwrap_or_default(),
+                    rejected["reason"].as_str().unwrap_or_default()
+                );
+            }
+            return Ok(());
+        }
+    };
+
+    println!(
+        "{} {}",
+        "✅".green(),
+        response["message"].as_str().unwrap_or_default()
+    );
     Ok(())
 }
@@ -3,16 +3,21 @@
 
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
+use trinitychain::audit::TransactionAuditLog;
 use std::collections::HashSet;
-use std::env;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
-use trinitychain::cli::load_blockchain_from_config;
+use trinitychain::cli::{self, load_blockchain_from_config, CliError, ErrorKind};
+use trinitychain::config::load_config;
 use trinitychain::crypto::address_from_hex;
 use trinitychain::geometry::Coord;
 use trinitychain::network::NetworkNode;
-use trinitychain::transaction::{Transaction, TransferTx};
+use trinitychain::payment::{plan_payment, PaymentRequest};
+use trinitychain::security::current_timestamp;
+use trinitychain::spending::{self, Approval, SpendingLedger};
+use trinitychain::transaction::Transaction;
+use trinitychain::units::{self, Unit};
 use trinitychain::wallet;
 
 const LOGO: &str = r#"
@@ -28,10 +33,18 @@ const LOGO: &str = r#"
 "#;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::args();
+    let json = cli::json_output();
 
     if args.len() < 3 {
+        if json {
+            return Err(CliError::usage("Usage: send <to_address> <amount> [--from <wallet_name>] [memo]"));
+        }
         println!("{}", LOGO.bright_cyan());
         println!(
             "{}",
@@ -59,6 +72,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "{}",
             "║    send <to_address> <amount> [--from <wallet_name>] [memo] ║".white()
         );
+        println!(
+            "{}",
+            "║    send <to_address> --amount <value> [...]              ║".white()
+        );
+        println!(
+            "{}",
+            "║    <to_address> may be a registered @name                ║".white()
+        );
+        println!(
+            "{}",
+            "║    [--encrypt] [--recipient-key <hex>] before the memo   ║".white()
+        );
+        println!(
+            "{}",
+            "║    encrypt it so only the recipient can read it          ║".white()
+        );
+        println!(
+            "{}",
+            "║    --yes skips the [wallet] confirm_above prompt         ║".white()
+        );
         println!(
             "{}",
             "║                                                          ║".bright_yellow()
@@ -84,23 +117,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "╚══════════════════════════════════════════════════════════╝".bright_yellow()
         );
         println!();
-        std::process::exit(1);
+        return Err(CliError::usage("Missing recipient or amount"));
     }
 
-    println!("{}", LOGO.bright_cyan());
+    if !json {
+        println!("{}", LOGO.bright_cyan());
+    }
 
+    // `@name` recipients are resolved once the chain is loaded
     let to_address = &args[1];
-    let to_address_bytes = address_from_hex(to_address)?;
-    let amount: f64 = args[2].parse()?;
-    let amount_coord = Coord::from_num(amount);
+    let to_name = to_address.strip_prefix('@');
+    let parsed_address = match to_name {
+        Some(_) => None,
+        None => Some(
+            address_from_hex(to_address).map_err(|e| CliError::new(ErrorKind::InvalidAddress, e.to_string()))?,
+        ),
+    };
+    let (amount, mut i) = if args[2] == "--amount" {
+        (args.get(3).ok_or_else(|| CliError::usage("--amount requires a value"))?, 4)
+    } else {
+        (&args[2], 3)
+    };
+    // Bare amounts are in the configured display unit
+    let config = load_config().ok();
+    let display = config.as_ref().map(|c| c.display.clone()).unwrap_or_default();
+    let policy = config.as_ref().map(|c| c.wallet.spending_policy()).unwrap_or_default();
+    let amount_coord = units::parse_amount(amount, display.unit)
+        .map_err(|_| CliError::usage(format!("Invalid amount: {}", amount)))?;
 
     let mut wallet_name: Option<String> = None;
     let mut memo: Option<String> = None;
+    let mut encrypt_memo = false;
+    let mut recipient_key: Option<Vec<u8>> = None;
+    let mut confirmed = false;
 
-    let mut i = 3;
     while i < args.len() {
         if args[i] == "--from" {
-            wallet_name = Some(args[i + 1].clone());
+            let name = args.get(i + 1).ok_or_else(|| CliError::usage("--from requires a wallet name"))?;
+            wallet_name = Some(name.clone());
+            i += 2;
+        } else if args[i] == "--encrypt" {
+            encrypt_memo = true;
+            i += 1;
+        } else if args[i] == "--yes" {
+            confirmed = true;
+            i += 1;
+        } else if args[i] == "--recipient-key" {
+            let key = args.get(i + 1).ok_or_else(|| CliError::usage("--recipient-key requires a value"))?;
+            recipient_key = Some(hex::decode(key).map_err(|e| CliError::usage(format!("Invalid recipient key: {}", e)))?);
+            encrypt_memo = true;
             i += 2;
         } else {
             memo = Some(args[i..].join(" "));
@@ -108,46 +173,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    println!(
-        "{}",
-        "┌─────────────────────────────────────────────────────────────┐".bright_magenta()
-    );
-    println!(
-        "{}",
-        "│                  💸 INITIATING TRANSFER                     │"
-            .bright_magenta()
-            .bold()
-    );
-    println!(
-        "{}",
-        "└─────────────────────────────────────────────────────────────┘".bright_magenta()
-    );
-    println!();
-
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
-            .template("{spinner:.cyan} {msg}")
-            .unwrap(),
-    );
+    if !json {
+        println!(
+            "{}",
+            "┌─────────────────────────────────────────────────────────────┐".bright_magenta()
+        );
+        println!(
+            "{}",
+            "│                  💸 INITIATING TRANSFER                     │"
+                .bright_magenta()
+                .bold()
+        );
+        println!(
+            "{}",
+            "└─────────────────────────────────────────────────────────────┘".bright_magenta()
+        );
+        println!();
+    }
 
+    let pb = spinner(json, "{spinner:.cyan} {msg}");
     pb.set_message("Loading wallet...");
-    pb.enable_steady_tick(Duration::from_millis(100));
 
+    let audit_source = format!("wallet:{}", wallet_name.as_deref().unwrap_or("default"));
     let from_wallet = if let Some(name) = wallet_name {
-        wallet::load_named_wallet(&name)?
+        wallet::load_named_wallet(&name)
     } else {
-        wallet::load_default_wallet()?
-    };
+        wallet::load_default_wallet()
+    }
+    .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
 
     let from_address = from_wallet.address.clone();
     let from_address_bytes = address_from_hex(&from_address)?;
-    let keypair = from_wallet.get_keypair()?;
+    let keypair = from_wallet
+        .get_keypair()
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
 
     pb.set_message("Loading blockchain...");
 
-    let (_config, mut chain) = load_blockchain_from_config()?;
+    let (_config, mut chain) =
+        load_blockchain_from_config().map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    let to_address_bytes = match parsed_address {
+        Some(address) => address,
+        None => chain.resolve_name(&to_address[1..]).ok_or_else(|| {
+            CliError::new(ErrorKind::InvalidAddress, format!("Name {} is not registered", to_address))
+        })?,
+    };
 
     // Track locked triangles from pending transactions
     let mut locked_triangles = HashSet::new();
@@ -169,157 +239,234 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 ));
             }
 
-            // Collect locked UTXOs from pending transfers
+            // Collect locked UTXOs from pending transactions
             for tx in txs_clone {
-                if let Transaction::Transfer(transfer_tx) = tx {
-                    locked_triangles.insert(transfer_tx.input_hash);
+                match tx {
+                    Transaction::Transfer(transfer_tx) => {
+                        locked_triangles.insert(transfer_tx.input_hash);
+                    }
+                    Transaction::Subdivision(subdivision_tx) => {
+                        locked_triangles.insert(subdivision_tx.parent_hash);
+                    }
+                    Transaction::Data(data_tx) => {
+                        locked_triangles.insert(data_tx.input_hash);
+                    }
+                    Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => {}
                 }
             }
         }
     }
-    pb.set_message("Finding a suitable triangle...");
-
-    let (input_hash, _input_triangle) = chain
-        .state
-        .utxo_set
-        .iter()
-        .find(|(hash, triangle)| {
-            triangle.owner == from_address_bytes
-                && triangle.effective_value() >= amount_coord
-                && !locked_triangles.contains(*hash)
-        })
-        .ok_or("No single triangle with sufficient value found for the transfer")?;
+    // Addresses are key hashes: without an explicit key, use the one the
+    // recipient revealed by spending or announcing it
+    let memo_recipient_key = match (encrypt_memo && memo.is_some(), recipient_key) {
+        (false, _) => None,
+        (true, Some(key)) => Some(key),
+        (true, None) => Some(chain.public_key_of(&to_address_bytes).ok_or_else(|| {
+            CliError::usage("The recipient's public key is not on chain yet; pass it with --recipient-key")
+        })?),
+    };
 
-    pb.finish_and_clear();
+    pb.set_message("Planning payment...");
 
-    let from_display = if from_address.len() > 20 {
-        format!(
-            "{}...{}",
-            &from_address[..10],
-            &from_address[from_address.len() - 10..]
-        )
-    } else {
-        from_address.clone()
-    };
-    let to_display = if to_address.len() > 20 {
-        format!(
-            "{}...{}",
-            &to_address[..10],
-            &to_address[to_address.len() - 10..]
-        )
-    } else {
-        to_address.to_string()
+    let request = PaymentRequest {
+        recipient: to_address_bytes,
+        amount: amount_coord,
+        fee_per_transfer: Coord::from_num(0),
+        memo: memo.clone(),
+        memo_recipient_key: memo_recipient_key.clone(),
     };
+    let mut plan = plan_payment(
+        &chain.state,
+        from_address_bytes,
+        &request,
+        &locked_triangles,
+        chain.blocks.len() as u64,
+    )?;
 
-    println!(
-        "{}",
-        "╔══════════════════════════════════════════════════════════╗".bright_cyan()
-    );
-    println!(
-        "{}",
-        "║              🔍 TRANSACTION DETAILS                      ║"
-            .bright_cyan()
-            .bold()
-    );
-    println!(
-        "{}",
-        "╠══════════════════════════════════════════════════════════╣".bright_cyan()
-    );
-    println!("{}", format!("║  👤 From: {:<47} ║", from_display).cyan());
-    println!("{}", format!("║  🎯 To: {:<49} ║", to_display).cyan());
-    println!("{}", format!("║  💸 Amount: {:<45} ║", amount).cyan());
-    if let Some(ref m) = memo {
-        let memo_display = if m.len() > 45 {
-            format!("{}...", &m[..42])
-        } else {
-            m.clone()
-        };
-        println!("{}", format!("║  📝 Memo: {:<47} ║", memo_display).cyan());
-    }
-    println!(
-        "{}",
-        "╚══════════════════════════════════════════════════════════╝".bright_cyan()
-    );
-    println!();
+    // Spending limits count everything leaving the wallet, fees included
+    let spent = spending::amount_spent(&plan.transactions);
+    let mut ledger = wallet::get_wallet_dir()
+        .and_then(|dir| SpendingLedger::load_from_data_dir(&dir))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let approval = ledger.check(&policy, &from_address, spent)?;
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-    pb.enable_steady_tick(Duration::from_millis(100));
+    pb.finish_and_clear();
 
-    pb.set_message("Creating transaction...");
+    if !json {
+        let from_display = if from_address.len() > 20 {
+            format!(
+                "{}...{}",
+                &from_address[..10],
+                &from_address[from_address.len() - 10..]
+            )
+        } else {
+            from_address.clone()
+        };
+        let to_display = if to_name.is_some() {
+            to_address.to_string()
+        } else if to_address.len() > 20 {
+            format!(
+                "{}...{}",
+                &to_address[..10],
+                &to_address[to_address.len() - 10..]
+            )
+        } else {
+            to_address.to_string()
+        };
 
-    let fee = Coord::from_num(0);
-    let mut tx = TransferTx::new(
-        *input_hash,
-        to_address_bytes,
-        from_address_bytes,
-        amount_coord,
-        fee,
-        chain.blocks.len() as u64,
-    );
+        println!(
+            "{}",
+            "╔══════════════════════════════════════════════════════════╗".bright_cyan()
+        );
+        println!(
+            "{}",
+            "║              🔍 TRANSACTION DETAILS                      ║"
+                .bright_cyan()
+                .bold()
+        );
+        println!(
+            "{}",
+            "╠══════════════════════════════════════════════════════════╣".bright_cyan()
+        );
+        println!("{}", format!("║  👤 From: {:<47} ║", from_display).cyan());
+        println!("{}", format!("║  🎯 To: {:<49} ║", to_display).cyan());
+        println!("{}", format!("║  💸 Amount: {:<45} ║", display.format(amount_coord)).cyan());
+        let steps = format!(
+            "{} transfer(s), {} subdivision(s)",
+            plan.transactions.len() - plan.subdivisions(),
+            plan.subdivisions()
+        );
+        println!("{}", format!("║  🔺 Plan: {:<47} ║", steps).cyan());
+        if let Some(ref m) = memo {
+            let memo_display = if m.len() > 45 {
+                format!("{}...", &m[..42])
+            } else {
+                m.clone()
+            };
+            let lock = if memo_recipient_key.is_some() { "🔒" } else { "📝" };
+            println!("{}", format!("║  {} Memo: {:<47} ║", lock, memo_display).cyan());
+        }
+        println!(
+            "{}",
+            "╚══════════════════════════════════════════════════════════╝".bright_cyan()
+        );
+        println!();
+    }
 
-    if let Some(m) = memo {
-        tx = tx.with_memo(m)?;
+    // JSON mode never prompts, so scripts must pass --yes
+    let needs_confirmation = approval == Approval::NeedsConfirmation && !confirmed;
+    if needs_confirmation && (json || !confirm(&display.format(spent))?) {
+        return Err(CliError::new(
+            ErrorKind::SpendingLimit,
+            format!(
+                "Sending {} needs confirmation (above wallet.confirm_above); rerun with --yes",
+                display.format(spent)
+            ),
+        ));
     }
 
-    pb.set_message("Signing transaction...");
+    let pb = spinner(json, "{spinner:.green} {msg}");
 
-    let message = tx.signable_message();
-    let signature = keypair.sign(&message)?;
-    let public_key = keypair.public_key.serialize().to_vec();
-    tx.sign(signature.to_vec(), public_key.to_vec());
+    pb.set_message("Signing transactions...");
+    plan.sign(&keypair, &chain.chain_id())?;
 
-    let transaction = Transaction::Transfer(tx);
-    chain.mempool.add_transaction(transaction.clone())?;
+    // The plan's transactions depend on each other: accept all or none
+    let chain_state = chain.state.clone();
+    let domain = chain.signing_domain(chain.next_height());
+    chain
+        .mempool
+        .accept_package(plan.transactions.clone(), &chain_state, &domain)?;
+
+    // Audited before anything leaves this process
+    let audit_log = wallet::get_wallet_dir()
+        .map(|dir| TransactionAuditLog::open_in_dir(&dir))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    for transaction in &plan.transactions {
+        audit_log.record(&audit_source, transaction)?;
+    }
 
     pb.set_message("Saving mempool...");
     let all_txs = chain.mempool.get_all_transactions();
-    std::fs::write("mempool.json", serde_json::to_string(&all_txs)?)?;
+    let all_txs = serde_json::to_string(&all_txs).map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    std::fs::write("mempool.json", all_txs)?;
+    ledger.record(&from_address, spent, current_timestamp())?;
 
     pb.set_message("Broadcasting to network...");
 
     let network_node = NetworkNode::new(Arc::new(RwLock::new(chain)));
-    network_node.broadcast_transaction(&transaction).await;
+    for transaction in &plan.transactions {
+        network_node.broadcast_transaction(transaction).await;
+    }
 
     pb.finish_and_clear();
 
-    println!(
-        "{}",
-        "╔══════════════════════════════════════════════════════════╗".bright_green()
-    );
-    println!(
-        "{}",
-        "║              ✅ TRANSACTION SUCCESSFUL!                  ║"
-            .bright_green()
-            .bold()
-    );
-    println!(
-        "{}",
-        "╠══════════════════════════════════════════════════════════╣".bright_green()
-    );
-    println!(
-        "{}",
-        "║  Your transaction has been broadcasted to the network   ║".green()
-    );
-    println!(
-        "{}",
-        "║  and will be included in the next block!                ║".green()
-    );
-    println!(
-        "{}",
-        "╚══════════════════════════════════════════════════════════╝".bright_green()
-    );
-    println!();
-    println!(
-        "{}",
-        "🎉 Transfer complete! The triangle is on its way!".bright_blue()
-    );
-    println!();
+    if json {
+        let txids: Vec<String> = plan.transactions.iter().map(|tx| tx.hash_str()).collect();
+        println!(
+            "{}",
+            serde_json::json!({ "from": from_address, "to": hex::encode(to_address_bytes), "amount": units::format_number(amount_coord, Unit::Trc), "txids": txids })
+        );
+        return Ok(());
+    }
+
+        println!(
+            "{}",
+            "╔══════════════════════════════════════════════════════════╗".bright_green()
+        );
+        println!(
+            "{}",
+            "║              ✅ TRANSACTION SUCCESSFUL!                  ║"
+                .bright_green()
+                .bold()
+        );
+        println!(
+            "{}",
+            "╠══════════════════════════════════════════════════════════╣".bright_green()
+        );
+        println!(
+            "{}",
+            "║  Your transaction has been broadcasted to the network   ║".green()
+        );
+        println!(
+            "{}",
+            "║  and will be included in the next block!                ║".green()
+        );
+        println!(
+            "{}",
+            "╚══════════════════════════════════════════════════════════╝".bright_green()
+        );
+        println!();
+        println!(
+            "{}",
+            "🎉 Transfer complete! The triangle is on its way!".bright_blue()
+        );
+        println!();
 
     Ok(())
 }
+
+/// Ask on the terminal before sending `amount`; only "yes" goes ahead
+fn confirm(amount: &str) -> Result<bool, CliError> {
+    use std::io::Write;
+    print!("{}", format!("⚠️  Send {}? Type 'yes' to confirm: ", amount).bright_yellow());
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+/// Progress spinner, hidden in JSON mode so only the result reaches stdout
+fn spinner(json: bool, template: &str) -> ProgressBar {
+    if json {
+        return ProgressBar::hidden();
+    }
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
+            .template(template)
+            .unwrap(),
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
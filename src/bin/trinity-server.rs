@@ -24,6 +24,7 @@ use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock};
 use tower_http::cors::{Any, CorsLayer};
 use trinitychain::blockchain::Blockchain;
+use trinitychain::cli;
 use trinitychain::config::load_config;
 use trinitychain::persistence::Database;
 
@@ -249,7 +250,12 @@ fn draw_ui(f: &mut ratatui::Frame, stats: &ServerStats) {
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    cli::args();
     // Prefer `trinity-node` as authoritative orchestrator.
     if std::env::var("TRINITY_STANDALONE").unwrap_or_default() != "1" {
         eprintln!("This server binary is deprecated; run `trinity-node` instead or set TRINITY_STANDALONE=1 to run standalone.");
@@ -1,19 +1,40 @@
 #![forbid(unsafe_code)]
 use log::{info, warn};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
 use teloxide::{prelude::*, utils::command::BotCommands};
 use tokio::sync::{Mutex, RwLock};
 use trinitychain::blockchain::Blockchain;
-use trinitychain::cli::load_blockchain_from_config;
+use trinitychain::botpay::{self, KeypadOutcome, Keypress, PaymentDesk, PendingPayment};
+use trinitychain::cli::{self, load_blockchain_from_config};
+use trinitychain::crypto::address_from_hex;
+use trinitychain::error::ChainError;
+use trinitychain::geometry::Coord;
 use trinitychain::network::NetworkNode;
+use trinitychain::payment::{plan_payment, PaymentRequest};
+use trinitychain::security::current_timestamp;
+use trinitychain::spending;
+use trinitychain::transaction::Transaction;
+use trinitychain::units::{self, Unit};
+use trinitychain::wallet;
+use trinitychain::watchlist::{self, Direction, Notification, Notifier, Subscriber, Watch, WatchEvent, Watchlist};
 
 type RateLimiter = Arc<Mutex<HashMap<i64, std::time::Instant>>>;
+type Desk = Arc<Mutex<PaymentDesk>>;
+
+/// How often the bot reloads the chain the node writes to disk
+const CHAIN_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Most addresses one chat may watch
+const MAX_CHAT_WATCHES: usize = 20;
 
 #[derive(Clone)]
 struct BotState {
     chain: Arc<RwLock<Blockchain>>,
     network: Option<Arc<NetworkNode>>,
+    /// Watchlists of the chats, scanned against `chain`
+    notifier: Arc<Notifier>,
 }
 
 #[derive(BotCommands, Clone)]
@@ -54,6 +75,20 @@ enum Command {
     Status,
     #[command(description = "broadcast raw tx hex to peers")]
     Broadcast(String),
+    #[command(description = "link your wallet (address signature)")]
+    Link(String),
+    #[command(description = "unlink your wallet")]
+    Unlink,
+    #[command(description = "send from the bot's hot wallet (amount [address])")]
+    Pay(String),
+    #[command(description = "show your payment allowance")]
+    Allowance,
+    #[command(description = "get notified about an address (address [depths])")]
+    Watch(String),
+    #[command(description = "stop watching an address")]
+    Unwatch(String),
+    #[command(description = "list watched addresses")]
+    Watching,
 }
 
 async fn answer(
@@ -63,7 +98,9 @@ async fn answer(
     state: Arc<BotState>,
     admin_token: Option<String>,
     rate_limiter: RateLimiter,
+    desk: Desk,
 ) -> ResponseResult<()> {
+    let notifier = &state.notifier;
     match command {
         Command::Start => {
             let welcome_msg = "🔺 Welcome to TrinityChain Bot! 🔺\n\n\
@@ -258,6 +295,164 @@ async fn answer(
 
             info!("Handled /broadcast command for user: {:?}", message.from());
         }
+        Command::Link(args) => {
+            let Some(from) = message.from() else {
+                return Ok(());
+            };
+            let uid = from.id.0 as i64;
+            let args: Vec<&str> = args.split_whitespace().collect();
+            let [address, signature] = args[..] else {
+                let help = format!(
+                    "🔗 To link a wallet, sign this message with it:\n\n\
+                    trinity-wallet sign-message \"{}\"\n\n\
+                    then send /link <address> <signature>",
+                    botpay::link_message(uid)
+                );
+                bot.send_message(message.chat.id, help).await?;
+                return Ok(());
+            };
+            let response = match desk.lock().await.link(uid, address, signature, current_timestamp()) {
+                Ok(()) => format!("✅ Linked {}", address),
+                Err(e) => format!("❌ Could not link: {}", e),
+            };
+            bot.send_message(message.chat.id, response).await?;
+            info!("Handled /link command for user: {:?}", message.from());
+        }
+        Command::Unlink => {
+            let Some(from) = message.from() else {
+                return Ok(());
+            };
+            let response = match desk.lock().await.unlink(from.id.0 as i64, current_timestamp()) {
+                Ok(Some(address)) => format!("✅ Unlinked {}", address),
+                Ok(None) => "No wallet is linked.".to_string(),
+                Err(e) => format!("❌ Could not unlink: {}", e),
+            };
+            bot.send_message(message.chat.id, response).await?;
+        }
+        Command::Allowance => {
+            let Some(from) = message.from() else {
+                return Ok(());
+            };
+            let response = match desk.lock().await.allowance(from.id.0 as i64, current_timestamp()) {
+                Some((policy, spent)) => {
+                    let per_payment = policy
+                        .max_per_transaction
+                        .map_or("no limit".to_string(), |max| units::format_amount(max, Unit::Trc));
+                    let daily = policy.max_per_day.unwrap_or(Coord::from_num(0));
+                    format!(
+                        "💳 Allowance\n\nPer payment: {}\nLast 24 hours: {} of {}",
+                        per_payment,
+                        units::format_amount(spent, Unit::Trc),
+                        units::format_amount(daily, Unit::Trc)
+                    )
+                }
+                None => "💳 Payments are not enabled for you on this bot.".to_string(),
+            };
+            bot.send_message(message.chat.id, response).await?;
+        }
+        Command::Pay(args) => {
+            let Some(from) = message.from() else {
+                return Ok(());
+            };
+            let uid = from.id.0 as i64;
+            let mut desk = desk.lock().await;
+            if desk.hot_wallet().is_none() {
+                bot.send_message(message.chat.id, "💳 Payments are disabled on this bot.").await?;
+                return Ok(());
+            }
+
+            let mut args = args.split_whitespace();
+            let Some(amount) = args.next().and_then(|a| units::parse_amount(a, Unit::Trc).ok()) else {
+                bot.send_message(message.chat.id, "Usage: /pay <amount> [address]").await?;
+                return Ok(());
+            };
+            let recipient = match args.next().or(desk.linked_address(uid)) {
+                Some(address) => address_from_hex(address),
+                None => Err(ChainError::WalletError("Link a wallet first with /link".to_string())),
+            };
+            let payment = match recipient.and_then(|r| desk.request(uid, r, amount, current_timestamp())) {
+                Ok(payment) => payment,
+                Err(e) => {
+                    bot.send_message(message.chat.id, format!("❌ {}", e)).await?;
+                    return Ok(());
+                }
+            };
+
+            // The code goes to the private chat; the keypad stays here
+            let code_message = format!(
+                "🔐 Code to confirm sending {}: {}\nIt expires in {} minutes. Never share it.",
+                units::format_amount(payment.amount, Unit::Trc),
+                payment.code,
+                botpay::CONFIRMATION_TTL_SECS / 60
+            );
+            if bot.send_message(ChatId(uid), code_message).await.is_err() {
+                let _ = desk.press(uid, Keypress::Cancel, current_timestamp());
+                bot.send_message(
+                    message.chat.id,
+                    "❌ Start a private chat with this bot first so it can send you the confirmation code.",
+                )
+                .await?;
+                return Ok(());
+            }
+            bot.send_message(message.chat.id, payment_text(&payment, "Enter the code sent to you privately", 0))
+                .reply_markup(keypad_markup())
+                .await?;
+            info!("Handled /pay command for user: {:?}", message.from());
+        }
+        Command::Watch(args) => {
+            let subscriber = Subscriber::Telegram {
+                chat_id: message.chat.id.0,
+            };
+            let mut args = args.split_whitespace();
+            let Some(address) = args.next() else {
+                bot.send_message(message.chat.id, "Usage: /watch <address> [depths, e.g. 3 6]").await?;
+                return Ok(());
+            };
+            let depths: Vec<u64> = args.filter_map(|d| d.parse().ok()).collect();
+            let response = if notifier.watches_of(&subscriber).len() >= MAX_CHAT_WATCHES {
+                format!("❌ A chat can watch at most {} addresses", MAX_CHAT_WATCHES)
+            } else {
+                match Watch::new(subscriber, address, depths).and_then(|w| {
+                    let depths = w.depths.clone();
+                    notifier.subscribe(w).map(|_| depths)
+                }) {
+                    Ok(depths) => format!(
+                        "👀 Watching {}\nYou will hear about transfers and confirmations at {:?} blocks.",
+                        address, depths
+                    ),
+                    Err(e) => format!("❌ {}", e),
+                }
+            };
+            bot.send_message(message.chat.id, response).await?;
+            info!("Handled /watch command for user: {:?}", message.from());
+        }
+        Command::Unwatch(address) => {
+            let subscriber = Subscriber::Telegram {
+                chat_id: message.chat.id.0,
+            };
+            let response = match notifier.unsubscribe(&subscriber, address.trim()) {
+                Ok(true) => format!("✅ No longer watching {}", address.trim()),
+                Ok(false) => "This chat is not watching that address.".to_string(),
+                Err(e) => format!("❌ {}", e),
+            };
+            bot.send_message(message.chat.id, response).await?;
+        }
+        Command::Watching => {
+            let watches = notifier.watches_of(&Subscriber::Telegram {
+                chat_id: message.chat.id.0,
+            });
+            let response = if watches.is_empty() {
+                "👀 This chat is not watching any address. Use /watch <address>.".to_string()
+            } else {
+                let list = watches
+                    .iter()
+                    .map(|w| format!("• {} (depths {:?})", w.address, w.depths))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("👀 Watched addresses ({})\n\n{}", watches.len(), list)
+            };
+            bot.send_message(message.chat.id, response).await?;
+        }
         _ => {
             bot.send_message(message.chat.id, "Command not implemented yet.")
                 .await?;
@@ -266,35 +461,262 @@ async fn answer(
     Ok(())
 }
 
+/// Keep the bot's copy of the chain current and scan it for watched
+/// addresses. The mempool is kept, so pending bot payments survive.
+async fn follow_chain(state: Arc<BotState>) {
+    let mut ticker = tokio::time::interval(CHAIN_REFRESH_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let loaded = load_blockchain_from_config().map_err(|e| e.to_string());
+        match loaded {
+            Ok((_, mut fresh)) => {
+                let mut chain = state.chain.write().await;
+                std::mem::swap(&mut fresh.mempool, &mut chain.mempool);
+                *chain = fresh;
+                state.notifier.poll(&chain);
+            }
+            Err(e) => warn!("Failed to reload blockchain: {}", e),
+        }
+    }
+}
+
+/// Send watchlist notifications addressed to Telegram chats
+async fn deliver_notifications(bot: Bot, notifier: Arc<Notifier>) {
+    let mut events = notifier.events();
+    loop {
+        let notification = match events.recv().await {
+            Ok(notification) => notification,
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                warn!("Dropped {} watchlist notifications", missed);
+                continue;
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+        };
+        let Subscriber::Telegram { chat_id } = notification.subscriber else {
+            continue;
+        };
+        if let Err(e) = bot.send_message(ChatId(chat_id), describe(&notification)).await {
+            warn!("Failed to notify chat {}: {}", chat_id, e);
+        }
+    }
+}
+
+fn describe(notification: &Notification) -> String {
+    match &notification.event {
+        WatchEvent::Transfer {
+            direction,
+            txid,
+            amount,
+            counterparty,
+            height,
+        } => {
+            let (icon, verb, preposition) = match direction {
+                Direction::Incoming => ("📥", "received", "from"),
+                Direction::Outgoing => ("📤", "sent", "to"),
+            };
+            let counterparty = counterparty.as_deref().unwrap_or("mining reward");
+            format!(
+                "{} {} {} {} TRC\n{} {}\nTx {} in block {}",
+                icon,
+                notification.address,
+                verb,
+                amount,
+                preposition,
+                counterparty,
+                txid,
+                height
+            )
+        }
+        WatchEvent::Confirmed {
+            txid,
+            height,
+            confirmations,
+        } => format!(
+            "✅ Tx {} (block {}) for {} has {} confirmations",
+            txid, height, notification.address, confirmations
+        ),
+    }
+}
+
+fn keypad_markup() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(botpay::keypad().into_iter().map(|row| {
+        row.into_iter()
+            .map(|key| InlineKeyboardButton::callback(key.label(), key.callback_data()))
+            .collect::<Vec<_>>()
+    }))
+}
+
+fn payment_text(payment: &PendingPayment, status: &str, entered: usize) -> String {
+    format!(
+        "💸 Send {} to {}\n\n{}\n{}",
+        units::format_amount(payment.amount, Unit::Trc),
+        hex::encode(payment.recipient),
+        status,
+        code_dots(entered)
+    )
+}
+
+fn code_dots(entered: usize) -> String {
+    format!("{}{}", "●".repeat(entered), "○".repeat(botpay::CODE_LENGTH - entered))
+}
+
+/// The keypad message with its last line, the code dots, redrawn
+fn redraw_code(message: &Message, entered: usize) -> String {
+    let text = message.text().unwrap_or_default();
+    let head = text.rsplit_once('\n').map_or(text, |(head, _)| head);
+    format!("{}\n{}", head, code_dots(entered))
+}
+
+/// Handle a press on the confirmation keypad of a /pay message
+async fn keypad(bot: Bot, query: CallbackQuery, state: Arc<BotState>, desk: Desk) -> ResponseResult<()> {
+    let uid = query.from.id.0 as i64;
+    let (Some(key), Some(message)) = (query.data.as_deref().and_then(Keypress::parse), query.message.as_ref()) else {
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    };
+    let edit = |text: String| bot.edit_message_text(message.chat.id, message.id, text);
+
+    let mut desk = desk.lock().await;
+    let payment = match desk.press(uid, key, current_timestamp()) {
+        Ok(KeypadOutcome::Entered(entered)) => {
+            bot.answer_callback_query(query.id).await?;
+            edit(redraw_code(message, entered)).reply_markup(keypad_markup()).await?;
+            return Ok(());
+        }
+        Ok(KeypadOutcome::WrongCode { attempts_left }) => {
+            bot.answer_callback_query(query.id)
+                .text(format!("Wrong code, {} attempt(s) left", attempts_left))
+                .await?;
+            edit(redraw_code(message, 0)).reply_markup(keypad_markup()).await?;
+            return Ok(());
+        }
+        Ok(KeypadOutcome::Cancelled) => {
+            bot.answer_callback_query(query.id).await?;
+            edit("✖ Payment cancelled".to_string()).await?;
+            return Ok(());
+        }
+        Ok(KeypadOutcome::Confirmed(payment)) => payment,
+        Err(e) => {
+            bot.answer_callback_query(query.id).text(e.to_string()).show_alert(true).await?;
+            return Ok(());
+        }
+    };
+    bot.answer_callback_query(query.id).await?;
+
+    let hot_wallet = desk.hot_wallet().unwrap_or_default().to_string();
+    let now = current_timestamp();
+    let text = match send_payment(&state, &hot_wallet, &payment).await {
+        Ok((spent, txids)) => {
+            if let Err(e) = desk.record_sent(&payment, spent, &txids, now) {
+                warn!("Failed to record bot payment: {}", e);
+            }
+            payment_text(&payment, &format!("✅ Sent in {}", txids.join(", ")), botpay::CODE_LENGTH)
+        }
+        Err(e) => {
+            if let Err(e) = desk.record_failed(&payment, &e.to_string(), now) {
+                warn!("Failed to record bot payment: {}", e);
+            }
+            payment_text(&payment, &format!("❌ Payment failed: {}", e), botpay::CODE_LENGTH)
+        }
+    };
+    edit(text).await?;
+    info!("Handled confirmed payment for user: {:?}", query.from);
+    Ok(())
+}
+
+/// Pay a confirmed payment from the hot wallet, returning what left the
+/// wallet (fees included) and the transaction ids
+async fn send_payment(
+    state: &BotState,
+    hot_wallet: &str,
+    payment: &PendingPayment,
+) -> Result<(Coord, Vec<String>), ChainError> {
+    let keypair = wallet::load_named_wallet(hot_wallet)?.get_keypair()?;
+    let mut chain = state.chain.write().await;
+
+    // Triangles spent by pending transactions are not available
+    let locked: HashSet<_> = chain
+        .mempool
+        .get_all_transactions()
+        .into_iter()
+        .filter_map(|tx| match tx {
+            Transaction::Transfer(transfer) => Some(transfer.input_hash),
+            Transaction::Subdivision(subdivision) => Some(subdivision.parent_hash),
+            Transaction::Data(data) => Some(data.input_hash),
+            Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => None,
+        })
+        .collect();
+    let request = PaymentRequest {
+        recipient: payment.recipient,
+        amount: payment.amount,
+        fee_per_transfer: Coord::from_num(0),
+        memo: None,
+        memo_recipient_key: None,
+    };
+    let nonce = chain.blocks.len() as u64;
+    let mut plan = plan_payment(&chain.state, keypair.address(), &request, &locked, nonce)?;
+    plan.sign(&keypair, &chain.chain_id())?;
+    let chain_state = chain.state.clone();
+    let domain = chain.signing_domain(chain.next_height());
+    chain.mempool.accept_package(plan.transactions.clone(), &chain_state, &domain)?;
+    drop(chain);
+
+    if let Some(node) = state.network.as_ref() {
+        for tx in &plan.transactions {
+            node.broadcast_transaction(tx).await;
+        }
+    }
+    let txids = plan.transactions.iter().map(|tx| tx.hash_str()).collect();
+    Ok((spending::amount_spent(&plan.transactions), txids))
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
+    cli::args();
     info!("Starting TrinityChain Telegram Bot...");
 
     let bot = Bot::from_env();
     let admin_token = std::env::var("BOT_ADMIN_TOKEN").ok();
     let rate_limiter: RateLimiter = Arc::new(Mutex::new(HashMap::new()));
 
-    let (_config, chain) = load_blockchain_from_config().expect("Failed to load blockchain");
+    let (config, chain) = load_blockchain_from_config().expect("Failed to load blockchain");
     let chain = Arc::new(RwLock::new(chain));
+    let desk = PaymentDesk::open_in_data_dir(config.telegram.clone(), &config.data_dir())
+        .expect("Failed to load bot payment state");
+    if let Some(hot_wallet) = desk.hot_wallet() {
+        info!("Payments enabled from wallet '{}'", hot_wallet);
+    }
+    let desk: Desk = Arc::new(Mutex::new(desk));
+    let watchlist = Watchlist::load(&config.data_dir().join(watchlist::BOT_WATCHLIST_FILE))
+        .expect("Failed to load bot watchlist");
+    let notifier = Arc::new(Notifier::new(watchlist));
+    // Mark the current tip so only new blocks are reported
+    notifier.poll(&*chain.read().await);
 
     let network = Arc::new(NetworkNode::new(Arc::clone(&chain)));
     let state = Arc::new(BotState {
         chain,
         network: Some(network),
+        notifier: notifier.clone(),
     });
+    tokio::spawn(follow_chain(state.clone()));
+    tokio::spawn(deliver_notifications(bot.clone(), notifier));
 
-    Dispatcher::builder(
-        bot,
-        Update::filter_message()
-            .filter_command::<Command>()
-            .endpoint(answer),
-    )
-    .dependencies(dptree::deps![state, admin_token, rate_limiter])
-    .enable_ctrlc_handler()
-    .build()
-    .dispatch()
-    .await;
+    let handler = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<Command>()
+                .endpoint(answer),
+        )
+        .branch(Update::filter_callback_query().endpoint(keypad));
+
+    Dispatcher::builder(bot, handler)
+        .dependencies(dptree::deps![state, admin_token, rate_limiter, desk])
+        .enable_ctrlc_handler()
+        .build()
+        .dispatch()
+        .await;
 
     info!("TrinityChain Telegram Bot stopped.");
 }
@@ -1,13 +1,20 @@
 #![forbid(unsafe_code)]
 use clap::{Parser, Subcommand};
 use colored::*;
+use trinitychain::build_info;
+use trinitychain::cli;
+use trinitychain::config::set_data_dir;
 use trinitychain::wallet;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(name = "trinity-user", author, version = build_info::long_version(), about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Data directory (defaults to $TRINITY_DATA_DIR, then
+    /// $XDG_DATA_HOME/trinitychain)
+    #[arg(long, global = true)]
+    data_dir: Option<std::path::PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -23,8 +30,15 @@ enum Commands {
     Status,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if let Some(dir) = &cli.data_dir {
+        set_data_dir(dir);
+    }
 
     match &cli.command {
         Commands::Login { name } => {
@@ -1,46 +1,79 @@
 #![forbid(unsafe_code)]
-//! Backup wallet with password encryption
+//! Back up a wallet, its address book labels and the miner's HD mnemonic
+//!
+//! Usage: trinity-wallet-backup [wallet_name] [--plaintext]
 
 use std::io::{self, Write};
-use trinitychain::wallet::{self, EncryptedWallet};
+use trinitychain::addressbook;
+use trinitychain::cli;
+use trinitychain::config::load_config;
+use trinitychain::hdwallet::TRINITY_DERIVATION_PATH;
+use trinitychain::wallet::{self, BackupContents, HdMetadata, WalletBackup};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::args();
+    let plaintext = args.iter().any(|arg| arg == "--plaintext");
+    let wallet_name = args.iter().skip(1).find(|arg| !arg.starts_with("--"));
     println!("🔐 Wallet Backup Tool\n");
 
-    // Load current wallet
-    let wallet = wallet::load_default_wallet()?;
+    // Load the wallet
+    let wallet = match wallet_name {
+        Some(name) => wallet::load_named_wallet(name)?,
+        None => wallet::load_default_wallet()?,
+    };
 
     println!(
         "📍 Wallet Address: {}...\n",
         &wallet.address[..42.min(wallet.address.len())]
     );
 
-    // Get password (twice for confirmation)
-    print!("Enter backup password: ");
-    io::stdout().flush()?;
-    let password = rpassword::read_password()?;
+    let labels = addressbook::load_default()?.list();
+    let hd = load_config()
+        .ok()
+        .and_then(|config| config.miner.hd_mnemonic_file)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|mnemonic| HdMetadata {
+            mnemonic: mnemonic.trim().to_string(),
+            derivation_path: TRINITY_DERIVATION_PATH.to_string(),
+        });
+    println!("🏷️  Address book labels: {}", labels.len());
+    println!("🌱 HD mnemonic: {}", if hd.is_some() { "included" } else { "none" });
 
-    print!("Confirm backup password: ");
-    io::stdout().flush()?;
-    let password_confirm = rpassword::read_password()?;
+    let password = if plaintext {
+        println!("\n⚠️  --plaintext: the backup will NOT be encrypted");
+        None
+    } else {
+        // Get password (twice for confirmation)
+        print!("\nEnter backup password: ");
+        io::stdout().flush()?;
+        let password = rpassword::read_password()?;
 
-    if password != password_confirm {
-        eprintln!("\n❌ Passwords do not match!");
-        std::process::exit(1);
-    }
+        print!("Confirm backup password: ");
+        io::stdout().flush()?;
+        let password_confirm = rpassword::read_password()?;
 
-    if password.len() < 8 {
-        eprintln!("\n❌ Password must be at least 8 characters!");
-        std::process::exit(1);
-    }
+        if password != password_confirm {
+            eprintln!("\n❌ Passwords do not match!");
+            std::process::exit(1);
+        }
 
-    // Encrypt wallet
-    println!("\n🔒 Encrypting wallet...");
-    let encrypted = EncryptedWallet::from_wallet(&wallet, &password)?;
+        if password.len() < 8 {
+            eprintln!("\n❌ Password must be at least 8 characters!");
+            std::process::exit(1);
+        }
+        Some(password)
+    };
 
-    // Save encrypted backup
+    // Seal and save the backup
+    println!("\n🔒 Writing backup...");
+    let contents = BackupContents { wallets: vec![wallet], labels, hd };
+    let backup = WalletBackup::seal(&contents, password.as_deref())?;
     let backup_path = wallet::get_wallet_dir()?.join("wallet_backup.json");
-    encrypted.save(&backup_path)?;
+    backup.save(&backup_path)?;
 
     println!("✅ Wallet backed up successfully!");
     println!("📁 Backup location: {}", backup_path.display());
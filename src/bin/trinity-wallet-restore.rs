@@ -1,12 +1,69 @@
 #![forbid(unsafe_code)]
-//! Restore wallet from encrypted backup
+//! Restore wallets, address book labels and the HD mnemonic from a backup
+//!
+//! Usage: trinity-wallet-restore [backup_file_path]
 
 use std::io::{self, Write};
-use std::path::PathBuf;
-use trinitychain::wallet::{self, EncryptedWallet};
+use std::path::{Path, PathBuf};
+use trinitychain::addressbook;
+use trinitychain::cli;
+use trinitychain::config::load_config;
+use trinitychain::wallet::{self, BackupContents, EncryptedWallet, Wallet, WalletBackup};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
+fn prompt(message: &str) -> io::Result<String> {
+    print!("{}", message);
+    io::stdout().flush()?;
+    rpassword::read_password()
+}
+
+fn confirm_overwrite(path: &Path) -> io::Result<bool> {
+    if !path.exists() {
+        return Ok(true);
+    }
+    print!("\n⚠️  WARNING: {} already exists. Overwrite? (yes/no): ", path.display());
+    io::stdout().flush()?;
+    let mut response = String::new();
+    io::stdin().read_line(&mut response)?;
+    Ok(response.trim().to_lowercase() == "yes")
+}
+
+/// Read either a versioned backup or a wallet file saved by older versions
+/// of trinity-wallet-backup. Returns the contents and the password used.
+fn open_backup(path: &Path) -> Result<(BackupContents, Option<String>), Box<dyn std::error::Error>> {
+    let backup = match WalletBackup::load(path) {
+        Ok(backup) => backup,
+        Err(e) => {
+            let Ok(legacy) = EncryptedWallet::load(&path.to_path_buf()) else {
+                return Err(e.into());
+            };
+            println!("📜 Legacy single-wallet backup\n");
+            let password = prompt("Enter backup password: ")?;
+            let wallet = legacy.decrypt(&password)?;
+            let contents = BackupContents { wallets: vec![wallet], ..Default::default() };
+            return Ok((contents, Some(password)));
+        }
+    };
+    println!("📜 Backup version {} from {}\n", backup.version, backup.created);
+    if !backup.is_encrypted() {
+        return Ok((backup.open(None)?, None));
+    }
+    let password = prompt("Enter backup password: ")?;
+    Ok((backup.open(Some(&password))?, Some(password)))
+}
+
+fn wallet_path(wallet: &Wallet) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(match &wallet.name {
+        Some(name) => wallet::get_named_wallet_path(name)?,
+        None => wallet::get_default_wallet_path()?,
+    })
+}
+
+fn main() {
+    cli::exit_on_error(run());
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args = cli::args();
 
     println!("🔓 Wallet Restore Tool\n");
 
@@ -25,53 +82,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("📁 Backup file: {}\n", backup_path.display());
 
-    // Load encrypted backup
-    let encrypted = EncryptedWallet::load(&backup_path)?;
-
-    println!(
-        "📍 Wallet Address: {}...\n",
-        &encrypted.address[..42.min(encrypted.address.len())]
-    );
-
-    // Get password
-    print!("Enter backup password: ");
-    io::stdout().flush()?;
-    let password = rpassword::read_password()?;
-
-    // Decrypt wallet
-    println!("\n🔓 Decrypting wallet...");
-    let wallet = match encrypted.decrypt(&password) {
-        Ok(w) => w,
-        Err(_) => {
-            eprintln!("❌ Failed to decrypt - incorrect password!");
+    println!("🔓 Opening backup...");
+    let (contents, password) = match open_backup(&backup_path) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("❌ Failed to restore: {}", e);
             std::process::exit(1);
         }
     };
 
-    // Check if wallet already exists
-    let wallet_path = wallet::get_default_wallet_path()?;
-    if wallet_path.exists() {
-        print!("\n⚠️  WARNING: A wallet already exists. Overwrite? (yes/no): ");
-        io::stdout().flush()?;
-        let mut response = String::new();
-        io::stdin().read_line(&mut response)?;
-
-        if response.trim().to_lowercase() != "yes" {
-            println!("Restore cancelled.");
-            std::process::exit(0);
+    // Wallets are stored encrypted; an unencrypted backup needs a new password
+    let password = match password {
+        Some(password) => password,
+        None => prompt("Enter a password for the restored wallets: ")?,
+    };
+
+    for restored in &contents.wallets {
+        let path = wallet_path(restored)?;
+        if !confirm_overwrite(&path)? {
+            println!("Skipped {}", path.display());
+            continue;
         }
+        EncryptedWallet::from_wallet(restored, &password)?.save(&path)?;
+        println!("✅ Wallet restored to {}", path.display());
+        println!(
+            "📍 Address: {}...",
+            &restored.address[..42.min(restored.address.len())]
+        );
     }
 
-    // Save wallet
-    wallet::ensure_wallet_dir()?;
-    wallet.save(&wallet_path)?;
+    if !contents.labels.is_empty() {
+        let book = addressbook::load_default()?;
+        let added = contents
+            .labels
+            .iter()
+            .filter(|entry| {
+                book.add(entry.label.clone(), entry.address.clone(), entry.notes.clone())
+                    .is_ok()
+            })
+            .count();
+        addressbook::save_default(&book)?;
+        println!(
+            "🏷️  Restored {} of {} address book labels",
+            added,
+            contents.labels.len()
+        );
+    }
 
-    println!("✅ Wallet restored successfully!");
-    println!("📁 Wallet location: {}", wallet_path.display());
-    println!(
-        "📍 Address: {}...",
-        &wallet.address[..42.min(wallet.address.len())]
-    );
+    if let Some(hd) = &contents.hd {
+        let target = load_config()
+            .ok()
+            .and_then(|config| config.miner.hd_mnemonic_file)
+            .map(PathBuf::from);
+        match target {
+            Some(path) if confirm_overwrite(&path)? => {
+                std::fs::write(&path, format!("{}\n", hd.mnemonic))?;
+                println!("🌱 HD mnemonic ({}) restored to {}", hd.derivation_path, path.display());
+            }
+            _ => println!(
+                "🌱 The backup holds an HD mnemonic; set miner.hd_mnemonic_file to restore it"
+            ),
+        }
+    }
     println!();
 
     Ok(())
@@ -2,6 +2,18 @@
 //! Wallet CLI for TrinityChain - Beautiful edition!
 
 use colored::*;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use trinitychain::audit::TransactionAuditLog;
+use trinitychain::cli::{self, CliError, ErrorKind};
+use trinitychain::crypto;
+use trinitychain::hdwallet::{AuditReport, HDWallet, KeyChain};
+use trinitychain::network::NetworkNode;
+use trinitychain::qr::{self, PaymentRequest};
+use trinitychain::security::current_timestamp;
+use trinitychain::spending::{Approval, SpendingLedger};
+use trinitychain::transaction::Transaction;
+use trinitychain::units::{self, Unit};
 use trinitychain::wallet::{self};
 
 const LOGO: &str = r#"
@@ -19,12 +31,17 @@ const LOGO: &str = r#"
 ╚═══════════════════════════════════════════════════════════════╝
 "#;
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
+#[tokio::main]
+async fn main() {
+    cli::exit_on_error(run().await);
+}
+
+async fn run() -> Result<(), CliError> {
+    let args = cli::args();
 
     if args.len() < 2 {
         print_usage();
-        return;
+        return Ok(());
     }
 
     match args[1].as_str() {
@@ -43,22 +60,381 @@ fn main() {
             }
         }
         "list" => list_wallets(),
-        "help" => print_usage(),
+        "receive" => receive(&args[2..]),
+        "audit" => audit(&args[2..]),
+        "bump-fee" => bump_fee(&args[2..]).await,
+        "sign-message" => {
+            let (message, from) = match args.get(2) {
+                Some(message) => (message, wallet_flag(&args[3..])?),
+                None => {
+                    return Err(CliError::usage(
+                        "Usage: trinity-wallet sign-message <message> [--from <wallet_name>]",
+                    ))
+                }
+            };
+            sign_message(message, from)
+        }
+        "verify-message" => match (args.get(2), args.get(3), args.get(4)) {
+            (Some(address), Some(signature), Some(message)) => {
+                verify_message(address, signature, message)
+            }
+            _ => Err(CliError::usage(
+                "Usage: trinity-wallet verify-message <address> <signature> <message>",
+            )),
+        },
+        "help" => {
+            print_usage();
+            Ok(())
+        }
         _ => {
-            println!(
-                "{}",
-                format!("❌ Unknown command: {}", args[1]).red().bold()
-            );
             print_usage();
+            Err(CliError::usage(format!("Unknown command: {}", args[1])))
+        }
+    }
+}
+
+/// Parse an optional `--from <wallet_name>` from the remaining arguments
+fn wallet_flag(rest: &[String]) -> Result<Option<String>, CliError> {
+    match rest {
+        [] => Ok(None),
+        [flag, name] if flag == "--from" => Ok(Some(name.clone())),
+        _ => Err(CliError::usage("Expected --from <wallet_name>")),
+    }
+}
+
+/// Print a `trinity:` payment request for a wallet, optionally as a QR code
+/// on the terminal and/or written to an SVG or PNG file
+fn receive(rest: &[String]) -> Result<(), CliError> {
+    const USAGE: &str = "Usage: trinity-wallet receive [wallet_name] [--amount <TRC>] [--memo <text>] [--qr] [--qr-file <path.svg|path.png>]";
+    let mut name = None;
+    let mut amount = None;
+    let mut memo = None;
+    let mut show_qr = false;
+    let mut qr_file = None;
+    let mut args = rest.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--amount" => amount = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?),
+            "--memo" => memo = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?),
+            "--qr" => show_qr = true,
+            "--qr-file" => qr_file = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?),
+            flag if flag.starts_with("--") => return Err(CliError::usage(USAGE)),
+            _ if name.is_none() => name = Some(arg),
+            _ => return Err(CliError::usage(USAGE)),
         }
     }
+
+    let result = match name {
+        Some(name) => wallet::load_named_wallet(name),
+        None => wallet::load_default_wallet(),
+    };
+    let wallet = result.map_err(|e| CliError::new(ErrorKind::Wallet, format!("Wallet not found: {}", e)))?;
+    let address = crypto::address_from_hex(&wallet.address)
+        .map_err(|e| CliError::new(ErrorKind::InvalidAddress, e.to_string()))?;
+    let mut request = PaymentRequest::new(address);
+    if let Some(amount) = amount {
+        request = request.with_amount(units::parse_amount(amount, Unit::Trc)?);
+    }
+    if let Some(memo) = memo {
+        request = request.with_memo(memo.as_str());
+    }
+    let code = request.qr_code()?;
+
+    if let Some(path) = qr_file {
+        let image = if path.to_lowercase().ends_with(".png") {
+            qr::render_png(&code)?
+        } else if path.to_lowercase().ends_with(".svg") {
+            qr::render_svg(&code).into_bytes()
+        } else {
+            return Err(CliError::usage("The QR code file must end in .svg or .png"));
+        };
+        std::fs::write(path, image)?;
+    }
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "address": wallet.address, "uri": request.to_string(), "qr_file": qr_file })
+        );
+        return Ok(());
+    }
+    println!("{} {}", "📍 Address:".bright_cyan(), wallet.address);
+    println!("{} {}", "🔗 Request:".bright_cyan(), request);
+    if show_qr {
+        println!();
+        println!("{}", qr::render_terminal(&code));
+    }
+    if let Some(path) = qr_file {
+        println!("{}", format!("🖼️  QR code written to {}", path).green());
+    }
+    Ok(())
+}
+
+/// Derive the first addresses of the HD wallet in `miner.hd_mnemonic_file`
+/// and report unused gaps, reused addresses and funds a restoring wallet
+/// would miss
+fn audit(rest: &[String]) -> Result<(), CliError> {
+    const USAGE: &str =
+        "Usage: trinity-wallet audit [--count <n>] [--gap-limit <n>] [--mnemonic-file <path>]";
+    let number = |value: Option<&String>| -> Result<usize, CliError> {
+        value.and_then(|v| v.parse().ok()).ok_or_else(|| CliError::usage(USAGE))
+    };
+    let (config, chain) = cli::load_blockchain_from_config()?;
+    let mut gap_limit = config.wallet.gap_limit;
+    let mut count = None;
+    let mut mnemonic_file = config.miner.hd_mnemonic_file.clone();
+    let mut args = rest.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => count = Some(number(args.next())?),
+            "--gap-limit" => gap_limit = number(args.next())?,
+            "--mnemonic-file" => mnemonic_file = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?.clone()),
+            _ => return Err(CliError::usage(USAGE)),
+        }
+    }
+    // Far enough past the gap limit to find funds a restore would miss
+    let count = count.unwrap_or(gap_limit * 2 + 20);
+    let count = u32::try_from(count).map_err(|_| CliError::usage("--count is too large"))?;
+
+    let path = mnemonic_file.ok_or_else(|| {
+        CliError::new(
+            ErrorKind::Wallet,
+            "No HD mnemonic: set miner.hd_mnemonic_file or pass --mnemonic-file",
+        )
+    })?;
+    let phrase = std::fs::read_to_string(&path)?;
+    let seed = HDWallet::seed_from_mnemonic(phrase.trim(), None)
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e))?;
+    let report = HDWallet::audit(&seed, count, &chain.blocks, &chain.state, gap_limit)
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e))?;
+
+    if cli::json_output() {
+        let gaps: Vec<_> = report
+            .gaps()
+            .into_iter()
+            .map(|(chain, first, length)| serde_json::json!({ "chain": chain, "first_index": first, "length": length }))
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "gap_limit": report.gap_limit,
+                "addresses": report.addresses.iter().filter(|entry| entry.is_used()).collect::<Vec<_>>(),
+                "gaps": gaps,
+                "reused": report.reused(),
+                "beyond_gap_limit": report.beyond_gap_limit(),
+            })
+        );
+    } else {
+        print_audit(&report, count);
+    }
+    Ok(())
+}
+
+/// Replace a stuck pending transfer of the wallet with a copy paying a
+/// higher fee, re-signed, and rebroadcast it. Without `--fee` the lowest fee
+/// the replacement rules accept is paid.
+async fn bump_fee(rest: &[String]) -> Result<(), CliError> {
+    const USAGE: &str = "Usage: trinity-wallet bump-fee <txid> [--fee <amount>] [--from <wallet_name>] [--yes]";
+    let mut args = rest.iter();
+    let txid = args.next().ok_or_else(|| CliError::usage(USAGE))?;
+    let mut hash = [0u8; 32];
+    hex::decode_to_slice(txid, &mut hash).map_err(|_| CliError::usage("txid must be a 64-character hex string"))?;
+    let (mut fee, mut from, mut confirmed) = (None, None, false);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fee" => fee = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?.clone()),
+            "--from" => from = Some(args.next().ok_or_else(|| CliError::usage(USAGE))?.clone()),
+            "--yes" => confirmed = true,
+            _ => return Err(CliError::usage(USAGE)),
+        }
+    }
+
+    let wallet = match &from {
+        Some(name) => wallet::load_named_wallet(name),
+        None => wallet::load_default_wallet(),
+    }
+    .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let keypair = wallet
+        .get_keypair()
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let (config, mut chain) = cli::load_blockchain_from_config()?;
+    if let Ok(mempool_data) = std::fs::read_to_string("mempool.json") {
+        if let Ok(txs) = serde_json::from_str::<Vec<Transaction>>(&mempool_data) {
+            for tx in txs {
+                let _ = chain.mempool.add_transaction(tx);
+            }
+        }
+    }
+
+    let current = chain
+        .mempool
+        .get_transaction(&hash)
+        .ok_or_else(|| CliError::new(ErrorKind::General, format!("{} is not pending", txid)))?
+        .fee_area();
+    let fee = match fee {
+        Some(fee) => units::parse_amount(&fee, config.display.unit)
+            .map_err(|_| CliError::usage(format!("Invalid fee: {}", fee)))?,
+        None => chain.mempool.min_replacement_fee(&hash).unwrap_or(current),
+    };
+    let mut bumped = chain.mempool.fee_bump(&hash, fee)?;
+    if bumped.sender != keypair.address() {
+        return Err(CliError::new(
+            ErrorKind::Wallet,
+            format!("{} was not sent by wallet {}", txid, wallet.address),
+        ));
+    }
+
+    // Only the fee increase leaves the wallet a second time
+    let increase = fee - current;
+    let mut ledger = wallet::get_wallet_dir()
+        .and_then(|dir| SpendingLedger::load_from_data_dir(&dir))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    if ledger.check(&config.wallet.spending_policy(), &wallet.address, increase)? == Approval::NeedsConfirmation
+        && !confirmed
+    {
+        return Err(CliError::new(
+            ErrorKind::SpendingLimit,
+            "The fee increase needs confirmation (above wallet.confirm_above); rerun with --yes",
+        ));
+    }
+
+    let signature = keypair.sign(&bumped.signable_message(&chain.chain_id()))?;
+    bumped.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+    let tx = Transaction::Transfer(bumped);
+    let evicted = chain.replace_transaction(tx.clone())?;
+    let audit_source = format!("wallet:{}", from.as_deref().unwrap_or("default"));
+    wallet::get_wallet_dir()
+        .map(|dir| TransactionAuditLog::open_in_dir(&dir))
+        .and_then(|log| log.record(&audit_source, &tx))
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let all_txs = serde_json::to_string(&chain.mempool.get_all_transactions())
+        .map_err(|e| CliError::new(ErrorKind::Storage, e.to_string()))?;
+    std::fs::write("mempool.json", all_txs)?;
+    ledger.record(&wallet.address, increase, current_timestamp())?;
+
+    let network_node = NetworkNode::new(Arc::new(RwLock::new(chain)));
+    network_node.broadcast_transaction(&tx).await;
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({
+                "txid": tx.hash_str(),
+                "replaced": txid,
+                "fee": units::format_number(fee, Unit::Trc),
+                "evicted": evicted.iter().map(hex::encode).collect::<Vec<_>>(),
+            })
+        );
+        return Ok(());
+    }
+    println!("{}", format!("⛽ Replaced {} with {}", txid, tx.hash_str()).bright_green());
+    println!("   Fee: {} (was {})", config.display.format(fee), config.display.format(current));
+    if evicted.len() > 1 {
+        println!(
+            "   {}",
+            format!("{} pending transaction(s) spending its outputs were dropped", evicted.len() - 1).yellow()
+        );
+    }
+    Ok(())
+}
+
+fn print_audit(report: &AuditReport, count: u32) {
+    let label = |chain: KeyChain| match chain {
+        KeyChain::Receive => "receive",
+        KeyChain::Change => "change",
+    };
+    println!(
+        "{}",
+        format!("🔎 Audited {} receive and {} change addresses (gap limit {})", count, count, report.gap_limit)
+            .bright_cyan()
+    );
+    let used: Vec<_> = report.addresses.iter().filter(|entry| entry.is_used()).collect();
+    if used.is_empty() {
+        println!("{}", "No derived address has received anything".yellow());
+        return;
+    }
+    for entry in &used {
+        println!(
+            "  {:<7} #{:<4} {}  {} payment(s), balance {}",
+            label(entry.chain),
+            entry.index,
+            entry.address,
+            entry.receipts,
+            units::format_amount(entry.balance, Unit::Trc)
+        );
+    }
+    for (chain, first, length) in report.gaps() {
+        println!(
+            "{}",
+            format!("⚠️  Gap: {} unused {} address(es) from #{}", length, label(chain), first).yellow()
+        );
+    }
+    for entry in report.reused() {
+        println!(
+            "{}",
+            format!("♻️  Reused: {} #{} received {} payments", label(entry.chain), entry.index, entry.receipts).yellow()
+        );
+    }
+    let hidden = report.beyond_gap_limit();
+    for entry in &hidden {
+        println!(
+            "{}",
+            format!(
+                "❗ Beyond the gap limit: {} #{} holds {}; a wallet restored from the mnemonic would not find it",
+                label(entry.chain),
+                entry.index,
+                units::format_amount(entry.balance, Unit::Trc)
+            )
+            .red()
+        );
+    }
+    if hidden.is_empty() {
+        println!("{}", "✅ Every used address is within the gap limit".green());
+    }
+}
+
+fn sign_message(message: &str, from: Option<String>) -> Result<(), CliError> {
+    let result = match &from {
+        Some(name) => wallet::load_named_wallet(name),
+        None => wallet::load_default_wallet(),
+    };
+    let wallet = result.map_err(|e| CliError::new(ErrorKind::Wallet, format!("Wallet not found: {}", e)))?;
+    let keypair = wallet
+        .get_keypair()
+        .map_err(|e| CliError::new(ErrorKind::Wallet, e.to_string()))?;
+    let signature = keypair.sign_message(message)?;
+
+    if cli::json_output() {
+        println!(
+            "{}",
+            serde_json::json!({ "address": wallet.address, "message": message, "signature": signature })
+        );
+    } else {
+        println!("{} {}", "📍 Address:  ".bright_cyan(), wallet.address);
+        println!("{} {}", "✍️  Signature:".bright_cyan(), signature);
+    }
+    Ok(())
+}
+
+fn verify_message(address: &str, signature: &str, message: &str) -> Result<(), CliError> {
+    let addr = crypto::address_from_hex(address)
+        .map_err(|e| CliError::new(ErrorKind::InvalidAddress, e.to_string()))?;
+    crypto::verify_message(&addr, message, signature)
+        .map_err(|e| CliError::new(ErrorKind::Rejected, e.to_string()))?;
+
+    if cli::json_output() {
+        println!("{}", serde_json::json!({ "address": address, "valid": true }));
+    } else {
+        println!("{}", format!("✅ Signature is valid for {}", address).green());
+    }
+    Ok(())
 }
 
 fn print_banner() {
     println!("{}", LOGO.bright_cyan());
 }
 
-fn create_wallet(name: Option<String>) {
+fn create_wallet(name: Option<String>) -> Result<(), CliError> {
     print_banner();
 
     println!(
@@ -132,20 +508,13 @@ fn create_wallet(name: Option<String>) {
             println!();
         }
         Err(e) => {
-            println!("{}", "╔══════════════════════════════════════════╗".red());
-            println!(
-                "{}",
-                "║       ❌ Wallet Creation Failed!        ║".red().bold()
-            );
-            println!("{}", "╠══════════════════════════════════════════╣".red());
-            println!("{}", format!("║  Error: {:<32} ║", e.to_string()).red());
-            println!("{}", "╚══════════════════════════════════════════╝".red());
-            println!();
+            return Err(CliError::new(ErrorKind::Wallet, format!("Wallet creation failed: {}", e)));
         }
     }
+    Ok(())
 }
 
-fn show_address(address: Option<String>) {
+fn show_address(address: Option<String>) -> Result<(), CliError> {
     print_banner();
 
     println!(
@@ -213,25 +582,19 @@ fn show_address(address: Option<String>) {
             println!();
         }
         Err(e) => {
-            println!("{}", "╔══════════════════════════════════════════╗".red());
-            println!(
-                "{}",
-                "║         ❌ Wallet Not Found!            ║".red().bold()
-            );
-            println!("{}", "╠══════════════════════════════════════════╣".red());
-            println!("{}", format!("║  Error: {:<32} ║", e.to_string()).red());
-            println!("{}", "╚══════════════════════════════════════════╝".red());
-            println!();
-            println!(
-                "{}",
-                "💡 Run 'trinity-wallet new' to create a wallet".yellow()
-            );
-            println!();
+            if !cli::json_output() {
+                println!(
+                    "{}",
+                    "💡 Run 'trinity-wallet new' to create a wallet".yellow()
+                );
+            }
+            return Err(CliError::new(ErrorKind::Wallet, format!("Wallet not found: {}", e)));
         }
     }
+    Ok(())
 }
 
-fn list_wallets() {
+fn list_wallets() -> Result<(), CliError> {
     print_banner();
 
     println!(
@@ -296,11 +659,9 @@ fn list_wallets() {
             }
             println!();
         }
-        Err(e) => {
-            println!("{}", format!("❌ Error: {}", e).red());
-            println!();
-        }
+        Err(e) => return Err(CliError::new(ErrorKind::Wallet, e.to_string())),
     }
+    Ok(())
 }
 
 fn print_usage() {
@@ -344,6 +705,26 @@ fn print_usage() {
         "{}",
         "║    📋 list      List all available wallets              ║".bright_yellow()
     );
+    println!(
+        "{}",
+        "║    📥 receive   Payment request, --qr shows a QR code   ║".bright_yellow()
+    );
+    println!(
+        "{}",
+        "║    🔎 audit     Check HD addresses for gaps and reuse   ║".bright_yellow()
+    );
+    println!(
+        "{}",
+        "║    ⛽ bump-fee  Raise the fee of a stuck transaction    ║".bright_yellow()
+    );
+    println!(
+        "{}",
+        "║    ✍️  sign-message    Prove control of your address     ║".bright_yellow()
+    );
+    println!(
+        "{}",
+        "║    🔍 verify-message  Check a signed message            ║".bright_yellow()
+    );
     println!(
         "{}",
         "║    ❓ help      Show this help message                  ║".bright_yellow()
@@ -376,6 +757,10 @@ fn print_usage() {
         "{}",
         "║    $ trinity-wallet list                                 ║".white()
     );
+    println!(
+        "{}",
+        "║    $ trinity-wallet receive --amount 2.5 --qr            ║".white()
+    );
     println!(
         "{}",
         "║                                                          ║".bright_yellow()
@@ -1,8 +1,48 @@
 // core.rs now splits responsibilities into submodules for easier maintenance.
+// Blocks, UTXO state and undo data are consensus-critical and build without
+// the `node` feature; chain management needs storage and the miner.
+pub mod addressindex;
+#[cfg(feature = "node")]
+pub mod archive;
+pub mod block;
+pub mod clock;
+#[cfg(feature = "node")]
+pub mod journal;
+#[cfg(feature = "node")]
 pub mod chain;
+pub mod names;
+pub mod pow;
 pub mod state;
+pub mod subdivisions;
+pub mod timeindex;
+#[cfg(feature = "node")]
+pub mod tips;
+pub mod undo;
+pub mod utxocommit;
+pub mod utxostats;
 pub mod validation;
+#[cfg(feature = "node")]
+pub mod versionbits;
 
+pub use addressindex::*;
+#[cfg(feature = "node")]
+pub use archive::*;
+pub use block::*;
+pub use clock::*;
+#[cfg(feature = "node")]
+pub use journal::*;
+#[cfg(feature = "node")]
 pub use chain::*;
+pub use names::*;
+pub use pow::*;
 pub use state::*;
+pub use subdivisions::*;
+pub use timeindex::*;
+#[cfg(feature = "node")]
+pub use tips::*;
+pub use undo::*;
+pub use utxocommit::*;
+pub use utxostats::*;
 pub use validation::*;
+#[cfg(feature = "node")]
+pub use versionbits::*;
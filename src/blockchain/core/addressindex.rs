@@ -0,0 +1,220 @@
+//! Per-address activity index
+//!
+//! Explorers show an address's totals and when it was first and last seen
+//! above its history. Deriving those from the blocks means walking every
+//! transaction the address ever touched, so the index keeps the aggregates
+//! per address and updates them as blocks are applied and reverted. Like the
+//! time index it is derived from the blocks and rebuilt on load.
+
+use crate::crypto::Address;
+use crate::geometry::Coord;
+use crate::transaction::Transaction;
+use std::collections::{BTreeMap, HashMap};
+
+use super::block::Block;
+
+/// Aggregate confirmed activity of an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressSummary {
+    /// Paid to the address by transfers and coinbases
+    pub received: Coord,
+    /// Paid by the address, fees included
+    pub sent: Coord,
+    /// Confirmed transactions involving the address
+    pub tx_count: u64,
+    /// Height of the first block involving the address
+    pub first_seen_height: u64,
+    /// Height of the latest block involving the address
+    pub last_active_height: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AddressActivity {
+    received: Coord,
+    sent: Coord,
+    /// Transactions involving the address, by block height
+    heights: BTreeMap<u64, u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AddressIndex {
+    addresses: HashMap<Address, AddressActivity>,
+}
+
+/// What one transaction does to one address
+struct Involvement {
+    address: Address,
+    received: Coord,
+    sent: Coord,
+}
+
+impl AddressIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rebuild(blocks: &[Block]) -> Self {
+        let mut index = Self::new();
+        for block in blocks {
+            index.insert(block);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, block: &Block) {
+        let height = block.header.height;
+        for tx in &block.transactions {
+            for involvement in Self::involvements(tx) {
+                let activity = self.addresses.entry(involvement.address).or_default();
+                activity.received = activity.received.saturating_add(involvement.received);
+                activity.sent = activity.sent.saturating_add(involvement.sent);
+                *activity.heights.entry(height).or_default() += 1;
+            }
+        }
+    }
+
+    /// Undo [`insert`](Self::insert) of the same block
+    pub fn remove(&mut self, block: &Block) {
+        let height = block.header.height;
+        for tx in &block.transactions {
+            for involvement in Self::involvements(tx) {
+                let Some(activity) = self.addresses.get_mut(&involvement.address) else {
+                    continue;
+                };
+                activity.received = activity.received.saturating_sub(involvement.received);
+                activity.sent = activity.sent.saturating_sub(involvement.sent);
+                if let Some(count) = activity.heights.get_mut(&height) {
+                    *count -= 1;
+                    if *count == 0 {
+                        activity.heights.remove(&height);
+                    }
+                }
+                if activity.heights.is_empty() {
+                    self.addresses.remove(&involvement.address);
+                }
+            }
+        }
+    }
+
+    /// Confirmed activity of `address`, `None` if no block involves it
+    pub fn get(&self, address: &Address) -> Option<AddressSummary> {
+        let activity = self.addresses.get(address)?;
+        Some(AddressSummary {
+            received: activity.received,
+            sent: activity.sent,
+            tx_count: activity.heights.values().sum(),
+            first_seen_height: *activity.heights.keys().next()?,
+            last_active_height: *activity.heights.keys().next_back()?,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// Addresses a transaction involves, matching the address history of
+    /// the API. An address appearing twice in one transaction is counted
+    /// once.
+    fn involvements(tx: &Transaction) -> Vec<Involvement> {
+        let zero = Coord::from_num(0);
+        let mut involvements: Vec<Involvement> = Vec::new();
+        let mut add = |address: Address, received: Coord, sent: Coord| {
+            match involvements.iter_mut().find(|i| i.address == address) {
+                Some(existing) => {
+                    existing.received = existing.received.saturating_add(received);
+                    existing.sent = existing.sent.saturating_add(sent);
+                }
+                None => involvements.push(Involvement { address, received, sent }),
+            }
+        };
+        match tx {
+            Transaction::Transfer(transfer) => match &transfer.sponsor {
+                Some(sponsor) => {
+                    add(transfer.sender, zero, transfer.amount);
+                    add(sponsor.payer, zero, transfer.fee_area);
+                    add(transfer.new_owner, transfer.amount, zero);
+                }
+                None => {
+                    add(transfer.sender, zero, transfer.amount.saturating_add(transfer.fee_area));
+                    add(transfer.new_owner, transfer.amount, zero);
+                }
+            },
+            Transaction::Subdivision(subdivision) => add(subdivision.owner_address, zero, subdivision.fee_area),
+            Transaction::Coinbase(coinbase) => {
+                let beneficiary = coinbase.beneficiary_amount().unwrap_or(coinbase.reward_area);
+                add(coinbase.beneficiary_address, beneficiary, zero);
+                for output in &coinbase.outputs {
+                    add(output.address, output.amount, zero);
+                }
+            }
+            Transaction::Announce(announce) => add(announce.address(), zero, zero),
+            Transaction::Register(register) => add(register.owner, zero, zero),
+            Transaction::Data(data) => add(data.owner, zero, data.fee_area),
+        }
+        involvements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{CoinbaseOutput, CoinbaseTx, TransferTx};
+
+    fn coinbase(height: u64, beneficiary: Address, outputs: Vec<CoinbaseOutput>) -> Transaction {
+        Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(10),
+            beneficiary_address: beneficiary,
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs,
+        })
+    }
+
+    fn transfer(sender: Address, new_owner: Address, amount: u32, fee: u32) -> Transaction {
+        Transaction::Transfer(TransferTx::new(
+            [9u8; 32],
+            new_owner,
+            sender,
+            Coord::from_num(amount),
+            Coord::from_num(fee),
+            1,
+        ))
+    }
+
+    #[test]
+    fn test_summaries_follow_applied_and_reverted_blocks() {
+        let (miner, alice, bob) = ([1u8; 32], [2u8; 32], [3u8; 32]);
+        let share = CoinbaseOutput { address: alice, amount: Coord::from_num(4) };
+        let blocks = [
+            Block::new(0, [0; 32], 1, vec![coinbase(0, miner, Vec::new())]),
+            Block::new(1, [0; 32], 1, vec![coinbase(1, miner, vec![share]), transfer(miner, bob, 3, 1)]),
+            Block::new(2, [0; 32], 1, vec![coinbase(2, miner, Vec::new()), transfer(bob, alice, 2, 0)]),
+        ];
+        let mut index = AddressIndex::rebuild(&blocks);
+
+        let summary = index.get(&miner).unwrap();
+        assert_eq!(summary.received, Coord::from_num(26));
+        assert_eq!(summary.sent, Coord::from_num(4));
+        assert_eq!(summary.tx_count, 4);
+        assert_eq!((summary.first_seen_height, summary.last_active_height), (0, 2));
+
+        let summary = index.get(&bob).unwrap();
+        assert_eq!((summary.received, summary.sent), (Coord::from_num(3), Coord::from_num(2)));
+        assert_eq!((summary.tx_count, summary.first_seen_height, summary.last_active_height), (2, 1, 2));
+
+        index.remove(&blocks[2]);
+        let summary = index.get(&alice).unwrap();
+        assert_eq!((summary.received, summary.tx_count), (Coord::from_num(4), 1));
+        assert_eq!(index.get(&bob).unwrap().last_active_height, 1);
+
+        index.remove(&blocks[1]);
+        assert!(index.get(&alice).is_none() && index.get(&bob).is_none());
+        assert_eq!(index.len(), 1);
+    }
+}
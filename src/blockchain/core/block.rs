@@ -0,0 +1,162 @@
+//! Blocks and block headers
+//!
+//! Consensus-critical and free of node dependencies, so it is part of the
+//! `core` build.
+
+use crate::blockchain::core::clock::{Clock, SystemClock};
+use crate::transaction::Transaction;
+use sha2::{Digest, Sha256};
+
+/// Top three header version bits identifying a version-bits block, see
+/// [`crate::blockchain::versionbits`]
+pub const VERSIONBITS_TOP_BITS: u32 = 0x2000_0000;
+
+/// Height from which the merkle root commits to wtxids instead of txids.
+/// A constant rather than a consensus parameter because blocks are built
+/// and their headers checked without a chain at hand.
+pub const WITNESS_MERKLE_ACTIVATION_HEIGHT: u64 = 20_000;
+
+pub type Sha256Hash = [u8; 32];
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub timestamp: u64,
+    pub previous_hash: Sha256Hash,
+    pub merkle_root: Sha256Hash,
+    pub difficulty: u32,
+    pub nonce: u64,
+    /// Version-bits field used for soft-fork signaling. Legacy headers have
+    /// version 0 and hash exactly as before the field existed.
+    #[serde(default)]
+    pub version: u32,
+}
+
+impl BlockHeader {
+    pub fn hash(&self) -> Sha256Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(self.height.to_le_bytes());
+        hasher.update(self.timestamp.to_le_bytes());
+        hasher.update(self.previous_hash);
+        hasher.update(self.merkle_root);
+        hasher.update(self.difficulty.to_le_bytes());
+        hasher.update(self.nonce.to_le_bytes());
+        if self.version != 0 {
+            hasher.update(self.version.to_le_bytes());
+        }
+        hasher.finalize().into()
+    }
+
+    /// The bytes [`Self::hash`] covers, for proof-of-work algorithms
+    /// hashing the header their own way
+    pub fn pow_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(96);
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.previous_hash);
+        bytes.extend_from_slice(&self.merkle_root);
+        bytes.extend_from_slice(&self.difficulty.to_le_bytes());
+        bytes.extend_from_slice(&self.nonce.to_le_bytes());
+        if self.version != 0 {
+            bytes.extend_from_slice(&self.version.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Proof-of-work this header represents, see [`block_work`]
+    pub fn work(&self) -> u128 {
+        block_work(self.difficulty)
+    }
+}
+
+/// Expected number of hashes needed to meet the target of `difficulty`
+/// leading zero bits, i.e. `2^difficulty`. Saturates at `u128::MAX`.
+pub fn block_work(difficulty: u32) -> u128 {
+    1u128.checked_shl(difficulty).unwrap_or(u128::MAX)
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<Transaction>,
+}
+
+impl Block {
+    pub fn new(
+        height: u64,
+        previous_hash: Sha256Hash,
+        difficulty: u32,
+        transactions: Vec<Transaction>,
+    ) -> Self {
+        Self::new_with_clock(height, previous_hash, difficulty, transactions, &SystemClock)
+    }
+
+    /// Like [`Block::new`], stamped with the time of `clock`
+    pub fn new_with_clock(
+        height: u64,
+        previous_hash: Sha256Hash,
+        difficulty: u32,
+        transactions: Vec<Transaction>,
+        clock: &dyn Clock,
+    ) -> Self {
+        let timestamp = clock.now_millis();
+        let merkle_root = Block::calculate_merkle_root(height, &transactions);
+
+        Block {
+            header: BlockHeader {
+                height,
+                timestamp,
+                previous_hash,
+                merkle_root,
+                difficulty,
+                nonce: 0,
+                version: VERSIONBITS_TOP_BITS,
+            },
+            transactions,
+        }
+    }
+
+    /// Set the header version, e.g. to [`Blockchain::next_block_version`]
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.header.version = version;
+        self
+    }
+
+    pub fn hash(&self) -> Sha256Hash {
+        self.header.hash()
+    }
+
+    /// Commitment to the transactions of a block at `height`: their wtxids,
+    /// covering the witness data, from [`WITNESS_MERKLE_ACTIVATION_HEIGHT`]
+    /// on and their txids below it
+    pub fn calculate_merkle_root(height: u64, transactions: &[Transaction]) -> Sha256Hash {
+        let mut hasher = Sha256::new();
+        for tx in transactions {
+            if height >= WITNESS_MERKLE_ACTIVATION_HEIGHT {
+                hasher.update(tx.wtxid());
+            } else {
+                hasher.update(tx.hash());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    pub fn hash_as_u256(hash: &Sha256Hash) -> [u8; 32] {
+        *hash
+    }
+
+    pub fn hash_to_target(difficulty: &u32) -> [u8; 32] {
+        let mut target = [0xFF; 32];
+        let leading_zeros = *difficulty / 8;
+        let partial_bits = *difficulty % 8;
+
+        for item in target.iter_mut().take(leading_zeros as usize) {
+            *item = 0;
+        }
+
+        if leading_zeros < 32 && partial_bits > 0 {
+            target[leading_zeros as usize] = (0xFF >> partial_bits) as u8;
+        }
+        target
+    }
+}
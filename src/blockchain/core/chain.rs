@@ -1,126 +1,201 @@
 use crate::crypto::Address;
 use crate::error::ChainError;
-use crate::mempool::Mempool;
-use crate::miner::mine_block;
-use crate::transaction::{CoinbaseTx, Transaction};
+use crate::mempool::{Mempool, MempoolSnapshot, RejectedEntry, RestoreReport, MEMPOOL_SNAPSHOT_VERSION};
+use crate::miner::mine_block_with;
+use crate::transaction::{CoinbaseOutput, CoinbaseTx, SigningDomain, Transaction};
 use crate::persistence::{Persistence, InMemoryPersistence};
-use crate::geometry::Coord;
-use sha2::{Digest, Sha256};
-// HashMap not needed in this module currently
-
-pub type Sha256Hash = [u8; 32];
-
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct BlockHeader {
-    pub height: u64,
-    pub timestamp: u64,
-    pub previous_hash: Sha256Hash,
-    pub merkle_root: Sha256Hash,
-    pub difficulty: u32,
-    pub nonce: u64,
-}
+use crate::checkpoint::Checkpoint;
+use crate::geometry::{coord_checked_add, coord_checked_mul, coord_checked_sub, coord_checked_sum, Coord};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-impl BlockHeader {
-    pub fn hash(&self) -> Sha256Hash {
-        let mut hasher = Sha256::new();
-        hasher.update(self.height.to_le_bytes());
-        hasher.update(self.timestamp.to_le_bytes());
-        hasher.update(self.previous_hash);
-        hasher.update(self.merkle_root);
-        hasher.update(self.difficulty.to_le_bytes());
-        hasher.update(self.nonce.to_le_bytes());
-        hasher.finalize().into()
-    }
-}
+// Blockchain struct and implementation
+use crate::blockchain::core::addressindex::AddressIndex;
+use crate::blockchain::core::subdivisions::SubdivisionIndex;
+use crate::blockchain::core::archive::SpentArchive;
+use crate::blockchain::core::block::{Block, BlockHeader, Sha256Hash};
+use crate::blockchain::core::clock::{system_clock, Clock};
+use crate::blockchain::core::journal::{BlockEvent, BlockJournal};
+use crate::blockchain::core::names::NameRegistry;
+use crate::blockchain::core::pow::{PowAlgorithm, DEFAULT_POW};
+use crate::blockchain::core::timeindex::{TimeIndex, TimeRange};
+use crate::blockchain::core::undo::BlockUndo;
+use crate::blockchain::core::utxostats::UtxoStatsTracker;
+use crate::blockchain::core::state::TriangleState;
+use crate::blockchain::core::tips::{ChainTip, ForkTracker, TipStatus};
+use crate::blockchain::core::validation::validate_no_double_spend;
+use crate::blockchain::core::versionbits::{
+    block_version, deployment_state, Deployment, DeploymentState, DEFAULT_SIGNALING_PERIOD,
+};
+// These imports were not used after refactor; keep commented for future use if needed.
+// use crate::transaction::TransferTx;
+// use crate::geometry::GEOMETRIC_TOLERANCE;
+
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 10;
+pub const TARGET_BLOCK_TIME: u64 = 30;
+/// Number of most recent blocks listed one-by-one in a block locator before
+/// the spacing starts doubling.
+pub const LOCATOR_DENSE_BLOCKS: usize = 10;
+/// Maximum number of headers returned for a single locator query
+pub const MAX_HEADERS_PER_REQUEST: usize = 2000;
+/// Lowest difficulty (leading zero bits) the retarget can reach
+pub const MIN_DIFFICULTY: u32 = 1;
+/// Highest difficulty the retarget can reach; 256 bits would be unminable
+pub const MAX_DIFFICULTY: u32 = 255;
+/// Height from which the emergency difficulty rule is enforced
+pub const EMERGENCY_DIFFICULTY_ACTIVATION_HEIGHT: u64 = 10_000;
+/// Height from which every transaction signature must cover the chain id
+pub const CHAIN_ID_SIGNATURE_ACTIVATION_HEIGHT: u64 = 20_000;
+/// Height from which a block's timestamp must be past the median of its
+/// predecessors'
+pub const MEDIAN_TIME_PAST_ACTIVATION_HEIGHT: u64 = 20_000;
+/// Height from which the coinbase must commit to its block's height and
+/// claimed fees
+pub const COINBASE_COMMITMENT_ACTIVATION_HEIGHT: u64 = 20_000;
+/// Blocks whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// How far ahead of our clock a block's timestamp may be
+pub const MAX_FUTURE_BLOCK_TIME_MS: u64 = 2 * 60 * 60 * 1000;
+/// A block arriving this many target block times after its parent may be
+/// mined at reduced difficulty
+pub const EMERGENCY_BLOCK_TIME_MULTIPLIER: u64 = 4;
+/// Blocks a data transaction keeps its payload outside archive mode, about
+/// a day at the 30 second target
+pub const DATA_PAYLOAD_RETENTION: u64 = 2_880;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct Block {
-    pub header: BlockHeader,
-    pub transactions: Vec<Transaction>,
+/// Consensus rule parameters that can differ between networks
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsensusParams {
+    /// Height at which the emergency difficulty rule activates. Before it,
+    /// block difficulty is not checked against the retarget.
+    pub emergency_difficulty_height: u64,
+    /// Height at which signatures must start covering the chain id. Below
+    /// it, blocks may still carry transactions signed without it.
+    pub chain_id_signature_height: u64,
+    /// Height at which block timestamps must exceed the median time past
+    pub median_time_past_height: u64,
+    /// Height at which the coinbase's `height` and `fees` must match its
+    /// block. Below it they are not checked.
+    pub coinbase_commitment_height: u64,
+    /// Blocks per version-bits signaling period
+    pub signaling_period: u64,
+    /// Soft forks deployed by version-bits signaling
+    pub deployments: Vec<Deployment>,
+    /// Fraction (0 to 1) of each block's fees that is burned. The miner may
+    /// claim the rest in the coinbase on top of the block subsidy.
+    pub fee_burn_fraction: Coord,
+    /// Fixed shares of every block reward (subsidy plus claimed fees) paid
+    /// to other addresses than the miner's, e.g. a treasury. Each fraction
+    /// is between 0 and 1 and together they must leave the miner something.
+    pub reward_shares: Vec<RewardShare>,
+    /// Hash the proof-of-work of every block is measured with
+    pub pow: &'static dyn PowAlgorithm,
+    /// Block subsidy of a network launched from chain parameters. Without
+    /// one a coinbase may claim up to [`CoinbaseTx::MAX_REWARD_AREA`] besides
+    /// its fees and the built-in miners claim their own fixed amounts.
+    pub subsidy: Option<SubsidySchedule>,
 }
 
-impl Block {
-    pub fn new(
-        height: u64,
-        previous_hash: Sha256Hash,
-        difficulty: u32,
-        transactions: Vec<Transaction>,
-    ) -> Self {
-        let timestamp = chrono::Utc::now().timestamp_millis() as u64;
-        let merkle_root = Block::calculate_merkle_root(&transactions);
+/// Subsidy that halves every `halving_interval` blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubsidySchedule {
+    pub initial: Coord,
+    /// Blocks between halvings; 0 never halves
+    pub halving_interval: u64,
+}
 
-        Block {
-            header: BlockHeader {
-                height,
-                timestamp,
-                previous_hash,
-                merkle_root,
-                difficulty,
-                nonce: 0,
-            },
-            transactions,
+impl SubsidySchedule {
+    /// Subsidy of the block at `height`
+    pub fn at(&self, height: u64) -> Coord {
+        if self.halving_interval == 0 {
+            return self.initial;
         }
-    }
-
-    pub fn hash(&self) -> Sha256Hash {
-        self.header.hash()
-    }
-
-    pub fn calculate_merkle_root(transactions: &[Transaction]) -> Sha256Hash {
-        let mut hasher = Sha256::new();
-        for tx in transactions {
-            hasher.update(tx.hash());
+        match height / self.halving_interval {
+            halvings if halvings >= 64 => Coord::from_num(0),
+            halvings => self.initial >> halvings as u32,
         }
-        hasher.finalize().into()
-    }
-
-    pub fn hash_as_u256(hash: &Sha256Hash) -> [u8; 32] {
-        *hash
     }
+}
 
-    pub fn hash_to_target(difficulty: &u32) -> [u8; 32] {
-        let mut target = [0xFF; 32];
-        let leading_zeros = *difficulty / 8;
-        let partial_bits = *difficulty % 8;
-
-        for item in target.iter_mut().take(leading_zeros as usize) {
-            *item = 0;
-        }
+/// Fraction of each block reward a network pays to `address`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardShare {
+    pub address: Address,
+    pub fraction: Coord,
+}
 
-        if leading_zeros < 32 && partial_bits > 0 {
-            target[leading_zeros as usize] = (0xFF >> partial_bits) as u8;
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            emergency_difficulty_height: EMERGENCY_DIFFICULTY_ACTIVATION_HEIGHT,
+            chain_id_signature_height: CHAIN_ID_SIGNATURE_ACTIVATION_HEIGHT,
+            median_time_past_height: MEDIAN_TIME_PAST_ACTIVATION_HEIGHT,
+            coinbase_commitment_height: COINBASE_COMMITMENT_ACTIVATION_HEIGHT,
+            signaling_period: DEFAULT_SIGNALING_PERIOD,
+            deployments: Vec::new(),
+            fee_burn_fraction: Coord::from_num(0),
+            reward_shares: Vec::new(),
+            pow: DEFAULT_POW,
+            subsidy: None,
         }
-        target
     }
 }
 
-// Blockchain struct and implementation
-use crate::blockchain::core::state::TriangleState;
-use crate::blockchain::core::validation::validate_no_double_spend;
-// These imports were not used after refactor; keep commented for future use if needed.
-// use crate::transaction::TransferTx;
-// use crate::geometry::GEOMETRIC_TOLERANCE;
-
-pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u64 = 10;
-pub const TARGET_BLOCK_TIME: u64 = 30;
-
 pub struct Blockchain {
     pub blocks: Vec<Block>,
+    /// Cumulative work up to and including each block, indexed by height
+    pub chain_work: Vec<u128>,
+    /// Latest authority-signed checkpoint; the chain never reorganizes below it
+    pub checkpoint: Option<Checkpoint>,
+    /// Spent-triangle history, kept only in archive mode
+    pub archive: Option<SpentArchive>,
+    /// Public keys revealed on chain by signatures or announcements
+    pub public_keys: HashMap<Address, Vec<u8>>,
+    /// Names registered on chain
+    pub names: NameRegistry,
+    /// Block heights by timestamp
+    pub time_index: TimeIndex,
+    /// Confirmed activity per address
+    pub address_index: AddressIndex,
+    /// Children of every subdivided output
+    pub subdivisions: SubdivisionIndex,
+    /// Composition of the UTXO set
+    pub utxo_stats: UtxoStatsTracker,
     pub difficulty: u32,
     pub mempool: Mempool,
     pub state: TriangleState,
     pub persistence: Box<dyn Persistence>,
+    pub consensus: ConsensusParams,
+    /// Time for block templates and the future-timestamp limit
+    pub clock: Arc<dyn Clock>,
+    /// Tips of competing branches seen by `reorganize`
+    pub forks: ForkTracker,
+    /// Blocks recently applied to and reverted from the tip
+    pub journal: BlockJournal,
 }
 
 impl Clone for Blockchain {
     fn clone(&self) -> Self {
         Self {
             blocks: self.blocks.clone(),
+            chain_work: self.chain_work.clone(),
+            checkpoint: self.checkpoint.clone(),
+            archive: self.archive.clone(),
+            public_keys: self.public_keys.clone(),
+            names: self.names.clone(),
+            time_index: self.time_index.clone(),
+            address_index: self.address_index.clone(),
+            subdivisions: self.subdivisions.clone(),
+            utxo_stats: self.utxo_stats.clone(),
             difficulty: self.difficulty,
             mempool: self.mempool.clone(),
             state: self.state.clone(),
             // Persistence cannot be cloned as a trait object; use a fresh in-memory backend for clones.
             persistence: Box::new(InMemoryPersistence::new()),
+            consensus: self.consensus.clone(),
+            clock: self.clock.clone(),
+            forks: self.forks.clone(),
+            journal: self.journal.clone(),
         }
     }
 }
@@ -138,40 +213,109 @@ impl Blockchain {
         persistence: Box<dyn Persistence>,
     ) -> Result<Self, ChainError> {
         let genesis_block = Self::create_genesis_block(genesis_miner_address, initial_difficulty)?;
+        Self::from_genesis(genesis_block, ConsensusParams::default(), persistence)
+    }
 
+    /// Start a chain at `genesis` under the rules of `consensus`, as for a
+    /// network launched from chain parameters
+    pub fn from_genesis(
+        genesis_block: Block,
+        consensus: ConsensusParams,
+        persistence: Box<dyn Persistence>,
+    ) -> Result<Self, ChainError> {
         let mut blockchain = Blockchain {
             blocks: vec![],
-            difficulty: initial_difficulty,
+            chain_work: vec![],
+            checkpoint: None,
+            archive: None,
+            public_keys: HashMap::new(),
+            names: NameRegistry::new(),
+            time_index: TimeIndex::new(),
+            address_index: AddressIndex::new(),
+            subdivisions: SubdivisionIndex::new(),
+            utxo_stats: UtxoStatsTracker::default(),
+            difficulty: genesis_block.header.difficulty,
             mempool: Mempool::new(),
             state: TriangleState::new(),
             persistence,
+            consensus,
+            clock: system_clock(),
+            forks: ForkTracker::default(),
+            journal: BlockJournal::default(),
         };
 
         blockchain.apply_block(genesis_block)?;
         Ok(blockchain)
     }
 
+    /// Build an in-memory chain by applying `blocks` from their genesis on,
+    /// with the same validation as blocks from peers
+    pub fn from_blocks(blocks: Vec<Block>) -> Result<Self, ChainError> {
+        let genesis = blocks
+            .first()
+            .filter(|block| block.header.height == 0)
+            .ok_or_else(|| ChainError::InvalidBlock("Blocks must start with the genesis block.".to_string()))?;
+        let mut chain = Blockchain {
+            blocks: vec![],
+            chain_work: vec![],
+            checkpoint: None,
+            archive: None,
+            public_keys: HashMap::new(),
+            names: NameRegistry::new(),
+            time_index: TimeIndex::new(),
+            address_index: AddressIndex::new(),
+            subdivisions: SubdivisionIndex::new(),
+            utxo_stats: UtxoStatsTracker::default(),
+            difficulty: genesis.header.difficulty,
+            mempool: Mempool::new(),
+            state: TriangleState::new(),
+            persistence: Box::new(InMemoryPersistence::new()),
+            consensus: ConsensusParams::default(),
+            clock: system_clock(),
+            forks: ForkTracker::default(),
+            journal: BlockJournal::default(),
+        };
+        for block in blocks {
+            chain.apply_block(block)?;
+        }
+        Ok(chain)
+    }
+
     fn create_genesis_block(miner_address: Address, initial_difficulty: u32) -> Result<Block, ChainError> {
-        let coinbase_tx = Transaction::Coinbase(CoinbaseTx {
+        let coinbase = CoinbaseTx {
             reward_area: Coord::from_num(1_000_000.0),
             beneficiary_address: miner_address,
             nonce: 0,
-        });
+            height: 0,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        };
+        Self::build_genesis_block(coinbase, 1672531200000, initial_difficulty, DEFAULT_POW)
+    }
 
-        let transactions = vec![coinbase_tx];
-        let merkle_root = Block::calculate_merkle_root(&transactions);
+    /// Mined genesis block holding `coinbase` alone. Genesis is exempt from
+    /// the reward limits, so the coinbase may pay out any allocation.
+    pub fn build_genesis_block(
+        coinbase: CoinbaseTx,
+        timestamp: u64,
+        difficulty: u32,
+        pow: &dyn PowAlgorithm,
+    ) -> Result<Block, ChainError> {
+        let transactions = vec![Transaction::Coinbase(coinbase)];
+        let merkle_root = Block::calculate_merkle_root(0, &transactions);
 
         let header = BlockHeader {
             height: 0,
-            timestamp: 1672531200000,
+            timestamp,
             previous_hash: [0u8; 32],
             merkle_root,
-            difficulty: initial_difficulty,
+            difficulty,
             nonce: 0,
+            version: 0,
         };
 
-        let genesis_block = Block { header, transactions };
-        mine_block(genesis_block)
+        mine_block_with(Block { header, transactions }, pow)
     }
 
     pub fn calculate_block_reward(height: u64) -> f64 {
@@ -209,15 +353,40 @@ impl Blockchain {
                     hex::encode(block.header.previous_hash)
                 )));
             }
+            self.check_timestamp(&block.header)?;
         } else if !self.blocks.is_empty() {
             return Err(ChainError::InvalidBlock("Genesis block can only be applied to an empty chain.".to_string()));
         }
 
+        if let Some(checkpoint) = &self.checkpoint {
+            if block.header.height == checkpoint.height && block.hash() != checkpoint.hash {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Block conflicts with the checkpoint at height {}.",
+                    checkpoint.height
+                )));
+            }
+        }
+
+        if !is_genesis && block.header.height >= self.consensus.emergency_difficulty_height {
+            let required = self.required_difficulty(block.header.timestamp);
+            if block.header.difficulty < required {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Block difficulty {} is below the required {}.",
+                    block.header.difficulty, required
+                )));
+            }
+        }
+
         if !self.verify_pow(&block) {
             return Err(ChainError::InvalidBlock("Invalid Proof-of-Work: Block hash does not meet difficulty target.".to_string()));
         }
 
         let mut temp_state = self.state.clone();
+        let mut consumed = Vec::new();
+        let mut announced = HashSet::new();
+        let mut registered = HashSet::new();
+        let mut undo = BlockUndo::new(block.header.height, self.difficulty);
+        let domain = self.signing_domain(block.header.height);
 
         validate_no_double_spend(&block)?;
 
@@ -228,12 +397,68 @@ impl Blockchain {
                     return Err(ChainError::InvalidBlock("First transaction in a block must be a Coinbase transaction.".to_string()));
                 }
             } else {
-                tx.validate(&temp_state)?;
+                tx.validate(&temp_state, &domain)?;
+            }
+            if let Transaction::Announce(announce) = tx {
+                let address = announce.address();
+                if self.public_keys.contains_key(&address) || !announced.insert(address) {
+                    return Err(ChainError::InvalidTransaction(format!(
+                        "Public key of {} is already known",
+                        hex::encode(address)
+                    )));
+                }
+            }
+            if let Transaction::Register(register) = tx {
+                self.names.check(register, block.header.height)?;
+                if !registered.insert(&register.name) {
+                    return Err(ChainError::InvalidTransaction(format!(
+                        "Name '{}' is registered twice in the block",
+                        register.name
+                    )));
+                }
+            }
+            if self.archive.is_some() {
+                consumed.push(SpentArchive::consumed_inputs(tx, &temp_state));
             }
+            undo.record(tx, &temp_state)?;
             temp_state.apply_transaction(tx, block.header.height)?;
         }
 
-        let expected_merkle_root = Block::calculate_merkle_root(&block.transactions);
+        if let Some(Transaction::Coinbase(coinbase)) = block.transactions.first() {
+            let fees = self.claimable_fees(&block.transactions)?;
+            if block.header.height >= self.consensus.coinbase_commitment_height {
+                if coinbase.height != block.header.height {
+                    return Err(ChainError::InvalidBlock(format!(
+                        "Coinbase commits to height {}, but the block is at height {}.",
+                        coinbase.height, block.header.height
+                    )));
+                }
+                if coinbase.fees != fees {
+                    return Err(ChainError::InvalidBlock(format!(
+                        "Coinbase claims {} in fees, but the block pays {} after burning.",
+                        coinbase.fees, fees
+                    )));
+                }
+            }
+            let outputs = self.reward_outputs(coinbase.reward_area)?;
+            if !is_genesis && coinbase.outputs != outputs {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Coinbase pays {} reward outputs, but the network's reward split requires {} of {}.",
+                    coinbase.outputs.len(),
+                    outputs.len(),
+                    coinbase.reward_area
+                )));
+            }
+            let allowed = coord_checked_add(self.max_subsidy(block.header.height), fees)?;
+            if !is_genesis && coinbase.reward_area > allowed {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Coinbase claims {}, but the subsidy and unburned fees allow at most {}.",
+                    coinbase.reward_area, allowed
+                )));
+            }
+        }
+
+        let expected_merkle_root = Block::calculate_merkle_root(block.header.height, &block.transactions);
         if expected_merkle_root != block.header.merkle_root {
             return Err(ChainError::InvalidBlock(format!(
                 "Merkle root mismatch. Expected {}, but got {}.",
@@ -242,22 +467,758 @@ impl Blockchain {
             )));
         }
 
+        let work = self.total_work().saturating_add(block.header.work());
         self.blocks.push(block.clone());
+        self.chain_work.push(work);
+        self.time_index.insert(&block);
+        self.address_index.insert(&block);
+        self.subdivisions.insert(&block);
         self.state = temp_state;
-
+        self.utxo_stats.apply(&undo, &self.state);
+        for (address, key) in block.transactions.iter().filter_map(Self::revealed_key) {
+            if let std::collections::hash_map::Entry::Vacant(entry) = self.public_keys.entry(address) {
+                entry.insert(key);
+                undo.revealed_keys.push(address);
+            }
+        }
         for tx in &block.transactions {
-            self.mempool.remove_transaction(&tx.hash());
+            if let Transaction::Register(register) = tx {
+                let previous = self.names.register(register, block.header.height);
+                undo.names.push((register.name.clone(), previous));
+            }
+        }
+
+        self.journal.record(&block, BlockEvent::Applied);
+        self.mempool.confirm_block(&block.transactions);
+        self.mempool.expire();
+        // Announcements of keys this block revealed are now redundant, and
+        // registrations of names it took can no longer be mined
+        let next_height = block.header.height + 1;
+        for tx in self.mempool.get_all_transactions() {
+            let stale = match &tx {
+                Transaction::Announce(a) => self.public_keys.contains_key(&a.address()),
+                Transaction::Register(r) => self.names.check(r, next_height).is_err(),
+                _ => false,
+            };
+            if stale {
+                self.mempool.remove_transaction(&tx.hash());
+            }
         }
 
         // Persist blockchain state after successfully applying the block.
         let _ = self.persistence.save_blockchain_state(&block, &self.state, self.difficulty as u64);
+        let _ = self.persistence.save_block_undo(&undo);
+        let _ = self.persistence.save_utxo_stats(&self.utxo_stats.snapshot(block.header.height, block.header.timestamp));
+        if let Some(archive) = &mut self.archive {
+            let spent = archive.record_block(&block, consumed)?;
+            let _ = self.persistence.save_spent_utxos(&spent);
+        } else {
+            self.prune_data_payloads(block.header.height);
+        }
 
-        self.adjust_difficulty();
+        let retargeted = self.adjust_difficulty();
+        if !retargeted
+            && block.header.height >= self.consensus.emergency_difficulty_height
+            && block.header.difficulty < self.difficulty
+        {
+            // An emergency block carries the reduced difficulty forward until
+            // the next retarget.
+            self.difficulty = block.header.difficulty.max(MIN_DIFFICULTY);
+        }
 
         Ok(())
     }
 
-    fn adjust_difficulty(&mut self) {
+    /// Drop the data payloads of the block that just fell
+    /// [`DATA_PAYLOAD_RETENTION`] blocks behind `tip_height`. The txids commit
+    /// to the payload hashes, so the block stays valid.
+    fn prune_data_payloads(&mut self, tip_height: u64) {
+        let Some(height) = tip_height.checked_sub(DATA_PAYLOAD_RETENTION) else {
+            return;
+        };
+        let Some(block) = self.blocks.get_mut(height as usize) else {
+            return;
+        };
+        let mut pruned = false;
+        for tx in &mut block.transactions {
+            if let Transaction::Data(data) = tx {
+                pruned |= !data.is_pruned();
+                data.prune();
+            }
+        }
+        if pruned {
+            let _ = self.persistence.save_block(block);
+        }
+    }
+
+    /// Take the time from `clock`, here and in the mempool
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.mempool.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Median timestamp of the last [`MEDIAN_TIME_SPAN`] blocks, 0 without
+    /// blocks
+    pub fn median_time_past(&self) -> u64 {
+        let start = self.blocks.len().saturating_sub(MEDIAN_TIME_SPAN);
+        let mut timestamps: Vec<u64> = self.blocks[start..].iter().map(|b| b.header.timestamp).collect();
+        timestamps.sort_unstable();
+        timestamps.get(timestamps.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Timestamp rules for a header extending the tip: at most
+    /// [`MAX_FUTURE_BLOCK_TIME_MS`] ahead of our clock and, once active,
+    /// past the median time past
+    pub fn check_timestamp(&self, header: &BlockHeader) -> Result<(), ChainError> {
+        if header.timestamp > self.clock.now_millis().saturating_add(MAX_FUTURE_BLOCK_TIME_MS) {
+            return Err(ChainError::InvalidBlock("Block timestamp is too far in the future.".to_string()));
+        }
+        if header.height >= self.consensus.median_time_past_height && header.timestamp <= self.median_time_past() {
+            return Err(ChainError::InvalidBlock(format!(
+                "Block timestamp {} is not after the median time past {}.",
+                header.timestamp,
+                self.median_time_past()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Timestamp for a block template on the tip: now, or just past the
+    /// median time past if our clock is behind it
+    pub fn next_timestamp(&self) -> u64 {
+        self.clock.now_millis().max(self.median_time_past() + 1)
+    }
+
+    /// Minimum difficulty for the next block if it carries `timestamp` (ms).
+    ///
+    /// Normally this is the current retarget difficulty. Once the emergency
+    /// rule is active, every full `EMERGENCY_BLOCK_TIME_MULTIPLIER` target
+    /// block times since the tip halves it (down to `MIN_DIFFICULTY`), so a
+    /// network that lost most of its hash power does not stall until the next
+    /// retarget.
+    pub fn required_difficulty(&self, timestamp: u64) -> u32 {
+        let Some(tip) = self.blocks.last() else {
+            return self.difficulty;
+        };
+        if tip.header.height + 1 < self.consensus.emergency_difficulty_height {
+            return self.difficulty;
+        }
+
+        let window = EMERGENCY_BLOCK_TIME_MULTIPLIER * TARGET_BLOCK_TIME * 1000;
+        let gap = timestamp.saturating_sub(tip.header.timestamp);
+        let halvings = (gap / window).min(u32::BITS as u64) as u32;
+        self.difficulty
+            .checked_shr(halvings)
+            .unwrap_or(0)
+            .max(MIN_DIFFICULTY)
+            .min(self.difficulty)
+    }
+
+    /// State of every configured deployment for the next block
+    pub fn deployment_states(&self) -> Vec<(&Deployment, DeploymentState)> {
+        let height = self.blocks.len() as u64;
+        self.consensus
+            .deployments
+            .iter()
+            .map(|d| (d, deployment_state(&self.blocks, &self.consensus, d, height)))
+            .collect()
+    }
+
+    /// Whether the deployment called `name` is active for the next block.
+    /// Consensus rules introduced by a soft fork are gated on this.
+    pub fn is_deployment_active(&self, name: &str) -> bool {
+        self.deployment_states()
+            .iter()
+            .any(|(d, state)| d.name == name && *state == DeploymentState::Active)
+    }
+
+    /// Header version for the next block, signaling every started deployment
+    pub fn next_block_version(&self) -> u32 {
+        block_version(&self.blocks, &self.consensus, self.blocks.len() as u64)
+    }
+
+    /// Cumulative work of the whole chain; the best chain is the one with
+    /// the most work, not the most blocks.
+    pub fn total_work(&self) -> u128 {
+        self.chain_work.last().copied().unwrap_or(0)
+    }
+
+    /// Cumulative work up to and including the block at `height`
+    pub fn work_at(&self, height: u64) -> Option<u128> {
+        self.chain_work.get(height as usize).copied()
+    }
+
+    /// The active tip followed by the known fork tips that are not part of
+    /// the active chain, highest first
+    pub fn chain_tips(&self) -> Vec<ChainTip> {
+        let mut forks: Vec<ChainTip> = self
+            .forks
+            .tips()
+            .iter()
+            .filter(|tip| self.blocks.get(tip.height as usize).map(|b| b.hash()) != Some(tip.hash))
+            .cloned()
+            .collect();
+        forks.sort_by_key(|tip| std::cmp::Reverse(tip.height));
+        let active = self.blocks.last().map(|tip| ChainTip {
+            height: tip.header.height,
+            hash: tip.hash(),
+            branch_len: 0,
+            status: TipStatus::Active,
+        });
+        active.into_iter().chain(forks).collect()
+    }
+
+    /// Block on the active chain with header hash `hash`. Uses the
+    /// persistence hash index and falls back to a scan when the backend
+    /// does not know the block, e.g. after loading from a database.
+    pub fn block_by_hash(&self, hash: &Sha256Hash) -> Option<&Block> {
+        let indexed = self
+            .persistence
+            .block_height_by_hash(hash)
+            .ok()
+            .flatten()
+            .and_then(|height| self.blocks.get(height as usize))
+            .filter(|block| block.hash() == *hash);
+        indexed.or_else(|| self.blocks.iter().rev().find(|block| block.hash() == *hash))
+    }
+
+    /// Adopt a verified checkpoint. Returns `false` if it is not newer than
+    /// the current one; fails if our chain has a different block at its
+    /// height.
+    pub fn set_checkpoint(&mut self, checkpoint: Checkpoint) -> Result<bool, ChainError> {
+        if self.checkpoint.as_ref().is_some_and(|c| c.height >= checkpoint.height) {
+            return Ok(false);
+        }
+        if let Some(block) = self.blocks.get(checkpoint.height as usize) {
+            if block.hash() != checkpoint.hash {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Checkpoint at height {} conflicts with our block {}.",
+                    checkpoint.height,
+                    hex::encode(block.hash())
+                )));
+            }
+        }
+        self.checkpoint = Some(checkpoint);
+        Ok(true)
+    }
+
+    /// Switch to archive mode: keep every spent triangle from now on. The
+    /// history of blocks already applied is rebuilt by replaying them.
+    pub fn enable_archive(&mut self) -> Result<(), ChainError> {
+        if self.archive.is_none() {
+            let archive = SpentArchive::rebuild(&self.blocks)?;
+            // Genesis spends nothing, so this is the whole history
+            let _ = self.persistence.save_spent_utxos(&archive.spent_above(0));
+            self.archive = Some(archive);
+        }
+        Ok(())
+    }
+
+    /// Cumulative work of every block in `blocks`, which must start at genesis
+    pub fn cumulative_work(blocks: &[Block]) -> Vec<u128> {
+        blocks
+            .iter()
+            .scan(0u128, |total, block| {
+                *total = total.saturating_add(block.header.work());
+                Some(*total)
+            })
+            .collect()
+    }
+
+    /// Switch to `branch` if that gives a chain with more cumulative work.
+    ///
+    /// `branch` is a contiguous run of blocks whose first entry builds on one
+    /// of ours; every block after that fork point is replaced, by reverting
+    /// them with their undo records where available. Returns `false` and
+    /// leaves the chain untouched when it already has at least as much work.
+    /// Transactions of the abandoned blocks go back to the mempool if they
+    /// are still valid.
+    pub fn reorganize(&mut self, branch: Vec<Block>) -> Result<bool, ChainError> {
+        let Some(first) = branch.first() else {
+            return Ok(false);
+        };
+        let fork_height = match first.header.height.checked_sub(1) {
+            Some(height) if self.blocks.get(height as usize).map(|b| b.hash()) == Some(first.header.previous_hash) => {
+                height as usize
+            }
+            _ => {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Branch starting at height {} does not connect to the chain.",
+                    first.header.height
+                )))
+            }
+        };
+
+        if let Some(checkpoint) = &self.checkpoint {
+            let tip_height = self.blocks.len() as u64 - 1;
+            let conflicts = branch
+                .iter()
+                .any(|b| b.header.height == checkpoint.height && b.hash() != checkpoint.hash);
+            if conflicts || ((fork_height as u64) < checkpoint.height && checkpoint.height <= tip_height) {
+                return Err(ChainError::InvalidBlock(format!(
+                    "Branch from height {} would reorganize below the checkpoint at height {}.",
+                    fork_height + 1,
+                    checkpoint.height
+                )));
+            }
+        }
+
+        let branch_work = branch
+            .iter()
+            .fold(self.chain_work[fork_height], |total, b| total.saturating_add(b.header.work()));
+        if branch_work <= self.total_work() {
+            self.forks.extend(ChainTip::of_branch(&branch, TipStatus::ValidHeaders));
+            return Ok(false);
+        }
+
+        let tip = ChainTip::of_branch(&branch, TipStatus::Invalid);
+        let switched = match self.switch_with_undo(fork_height, &branch) {
+            Ok(Some(disconnected)) => Ok(disconnected),
+            Ok(None) => self.switch_by_replay(fork_height, branch),
+            Err(e) => Err(e),
+        };
+        let disconnected = switched.inspect_err(|_| self.forks.extend(tip))?;
+        self.forks.extend(ChainTip::of_branch(&disconnected, TipStatus::ValidFork));
+
+        for block in &self.blocks[fork_height + 1..] {
+            self.mempool.confirm_block(&block.transactions);
+        }
+        let domain = self.signing_domain(self.next_height());
+        self.mempool.prune(&self.state, &domain);
+        for tx in disconnected.iter().flat_map(|b| b.transactions.iter().skip(1)) {
+            let _ = self.mempool.accept_transaction(tx.clone(), &self.state, &domain);
+        }
+        Ok(true)
+    }
+
+    /// Replace our blocks above `fork_height` with `branch` by reverting
+    /// them with their undo records. Returns `None`, with the chain
+    /// untouched, if a record is missing. If the branch turns out invalid our
+    /// blocks are restored.
+    fn switch_with_undo(&mut self, fork_height: usize, branch: &[Block]) -> Result<Option<Vec<Block>>, ChainError> {
+        let tip_height = self.blocks.len() - 1;
+        for height in fork_height + 1..=tip_height {
+            if !matches!(self.persistence.load_block_undo(height as u64), Ok(Some(_))) {
+                return Ok(None);
+            }
+        }
+
+        let mut disconnected = Vec::new();
+        while self.blocks.len() > fork_height + 1 {
+            disconnected.push(self.revert_block()?);
+        }
+        disconnected.reverse();
+        for (applied, block) in branch.iter().enumerate() {
+            if let Err(e) = self.apply_block(block.clone()) {
+                for _ in 0..applied {
+                    self.revert_block()?;
+                }
+                for block in disconnected {
+                    self.apply_block(block)?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(Some(disconnected))
+    }
+
+    /// Replace our blocks above `fork_height` with `branch` by replaying the
+    /// shared prefix and the branch onto a scratch chain, for blocks applied
+    /// before undo records were kept
+    fn switch_by_replay(&mut self, fork_height: usize, branch: Vec<Block>) -> Result<Vec<Block>, ChainError> {
+        let mut candidate = Blockchain {
+            blocks: vec![],
+            chain_work: vec![],
+            checkpoint: None,
+            archive: self.archive.as_ref().map(|_| SpentArchive::default()),
+            public_keys: HashMap::new(),
+            names: NameRegistry::new(),
+            time_index: TimeIndex::new(),
+            address_index: AddressIndex::new(),
+            subdivisions: SubdivisionIndex::new(),
+            utxo_stats: UtxoStatsTracker::new(self.utxo_stats.dust_threshold()),
+            difficulty: self.blocks[0].header.difficulty,
+            mempool: Mempool::new(),
+            state: TriangleState::new(),
+            persistence: Box::new(InMemoryPersistence::new()),
+            consensus: self.consensus.clone(),
+            clock: self.clock.clone(),
+            forks: ForkTracker::default(),
+            journal: BlockJournal::default(),
+        };
+        for block in self.blocks[..=fork_height].iter().cloned().chain(branch) {
+            candidate.apply_block(block)?;
+        }
+
+        let disconnected = self.blocks.split_off(fork_height + 1);
+        for block in disconnected.iter().rev() {
+            self.journal.record(block, BlockEvent::Reverted);
+        }
+        for block in &candidate.blocks[fork_height + 1..] {
+            self.journal.record(block, BlockEvent::Applied);
+        }
+        self.blocks = candidate.blocks;
+        self.chain_work = candidate.chain_work;
+        self.state = candidate.state;
+        self.archive = candidate.archive;
+        self.public_keys = candidate.public_keys;
+        self.names = candidate.names;
+        self.time_index = candidate.time_index;
+        self.address_index = candidate.address_index;
+        self.subdivisions = candidate.subdivisions;
+        self.utxo_stats = candidate.utxo_stats;
+        self.difficulty = candidate.difficulty;
+
+        let _ = self.persistence.remove_blocks_above(fork_height as u64);
+        for block in &self.blocks[fork_height + 1..] {
+            let _ = self.persistence.save_block(block);
+            if let Ok(Some(undo)) = candidate.persistence.load_block_undo(block.header.height) {
+                let _ = self.persistence.save_block_undo(&undo);
+            }
+        }
+        for stats in candidate.persistence.load_utxo_stats(TimeRange::ALL).unwrap_or_default() {
+            if stats.height > fork_height as u64 {
+                let _ = self.persistence.save_utxo_stats(&stats);
+            }
+        }
+        if let Some(archive) = &self.archive {
+            let _ = self.persistence.save_spent_utxos(&archive.spent_above(fork_height as u64));
+        }
+        if let Some(tip) = self.blocks.last() {
+            let _ = self.persistence.save_blockchain_state(tip, &self.state, self.difficulty as u64);
+        }
+        Ok(disconnected)
+    }
+
+    /// Count outputs worth less than `threshold` as dust from now on,
+    /// rescanning the UTXO set
+    pub fn set_dust_threshold(&mut self, threshold: Coord) {
+        self.utxo_stats = UtxoStatsTracker::from_state(&self.state, threshold);
+    }
+
+    /// Blocks stamped within `range`, lowest height first
+    pub fn blocks_in(&self, range: TimeRange) -> impl Iterator<Item = &Block> {
+        self.time_index
+            .heights_in(range)
+            .into_iter()
+            .filter_map(|height| self.blocks.get(height as usize))
+    }
+
+    /// Disconnect the tip block using its undo record, in time proportional
+    /// to the block rather than the chain. Its transactions are not returned
+    /// to the mempool. Fails for genesis, the checkpointed block, or a block
+    /// without an undo record.
+    pub fn revert_block(&mut self) -> Result<Block, ChainError> {
+        let height = match self.blocks.last() {
+            Some(tip) if tip.header.height > 0 => tip.header.height,
+            _ => return Err(ChainError::InvalidBlock("Cannot revert the genesis block.".to_string())),
+        };
+        if self.checkpoint.as_ref().is_some_and(|c| c.height == height) {
+            return Err(ChainError::InvalidBlock(format!(
+                "Cannot revert the checkpointed block at height {}.",
+                height
+            )));
+        }
+        let undo = self.persistence.load_block_undo(height)?.ok_or_else(|| {
+            ChainError::DatabaseError(format!("No undo record for block {}", height))
+        })?;
+
+        self.utxo_stats.revert(&undo, &self.state);
+        self.state.revert(&undo)?;
+        for address in &undo.revealed_keys {
+            self.public_keys.remove(address);
+        }
+        for (name, previous) in undo.names.iter().rev() {
+            self.names.restore(name, previous.clone());
+        }
+        if let Some(archive) = &mut self.archive {
+            archive.forget_block(&undo);
+        }
+        let block = self.blocks.pop().expect("tip exists");
+        self.chain_work.pop();
+        self.time_index.remove(&block);
+        self.address_index.remove(&block);
+        self.subdivisions.remove(&block);
+        self.journal.record(&block, BlockEvent::Reverted);
+        self.difficulty = undo.difficulty;
+
+        let _ = self.persistence.remove_blocks_above(height - 1);
+        if let Some(tip) = self.blocks.last() {
+            let _ = self.persistence.save_blockchain_state(tip, &self.state, self.difficulty as u64);
+        }
+        Ok(block)
+    }
+
+    /// Validate a transaction, signatures included, against the chain and
+    /// the mempool, and add it to the mempool. One spending inputs of
+    /// pending transactions replaces them if it pays enough more, see
+    /// [`Mempool::replace_transaction`].
+    pub fn accept_transaction(&mut self, tx: Transaction) -> Result<(), ChainError> {
+        self.replace_transaction(tx).map(|_| ())
+    }
+
+    /// [`accept_transaction`](Self::accept_transaction), returning the
+    /// pending transactions the new one replaced
+    pub fn replace_transaction(&mut self, tx: Transaction) -> Result<Vec<Sha256Hash>, ChainError> {
+        if matches!(tx, Transaction::Coinbase(_)) {
+            return Err(ChainError::InvalidTransaction(
+                "Coinbase transactions cannot be relayed".to_string(),
+            ));
+        }
+        if let Transaction::Announce(announce) = &tx {
+            if self.public_key_of(&announce.address()).is_some() {
+                return Err(ChainError::InvalidTransaction(
+                    "Public key is already known".to_string(),
+                ));
+            }
+        }
+        let next_height = self.next_height();
+        if let Transaction::Register(register) = &tx {
+            self.names.check(register, next_height)?;
+        }
+        let domain = self.signing_domain(next_height);
+        match self.mempool.accept_transaction(tx.clone(), &self.state, &domain) {
+            Err(ChainError::AlreadySpent { .. }) => self.mempool.replace_transaction(tx, &self.state, &domain),
+            result => result.map(|()| Vec::new()),
+        }
+    }
+
+    /// Load the transactions of a mempool snapshot, possibly taken on another
+    /// node, revalidating each one as if it had just been relayed
+    pub fn restore_mempool(&mut self, snapshot: &MempoolSnapshot) -> Result<RestoreReport, ChainError> {
+        if snapshot.version != MEMPOOL_SNAPSHOT_VERSION {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Unsupported mempool snapshot version {} (expected {})",
+                snapshot.version, MEMPOOL_SNAPSHOT_VERSION
+            )));
+        }
+        let mut report = RestoreReport::default();
+        for entry in &snapshot.entries {
+            let result = entry.transaction().and_then(|tx| {
+                let hash = tx.hash();
+                if self.mempool.get_transaction(&hash).is_some() {
+                    return Ok(None);
+                }
+                self.accept_transaction(tx).map(|()| Some(hash))
+            });
+            match result {
+                Ok(Some(hash)) => {
+                    self.mempool.restore_entry(&hash, entry);
+                    report.restored.push(hex::encode(hash));
+                }
+                Ok(None) => report.already_pending += 1,
+                Err(e) => report.rejected.push(RejectedEntry {
+                    txid: entry.txid.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Most a coinbase at `height` may claim besides fees
+    pub fn max_subsidy(&self, height: u64) -> Coord {
+        self.consensus.subsidy.map_or(CoinbaseTx::MAX_REWARD_AREA, |subsidy| subsidy.at(height))
+    }
+
+    /// Subsidy the built-in miners claim at `height`: the network's
+    /// schedule, or `fixed` on a network without one
+    pub fn mining_subsidy(&self, height: u64, fixed: Coord) -> Coord {
+        self.consensus.subsidy.map_or(fixed, |subsidy| subsidy.at(height))
+    }
+
+    /// Fees paid by `transactions` that the coinbase may claim: their total
+    /// less the burned fraction
+    pub fn claimable_fees(&self, transactions: &[Transaction]) -> Result<Coord, ChainError> {
+        let fees = coord_checked_sum(transactions.iter().map(Transaction::fee_area))?;
+        let fraction = self.consensus.fee_burn_fraction.clamp(Coord::from_num(0), Coord::from_num(1));
+        coord_checked_sub(fees, coord_checked_mul(fees, fraction)?)
+    }
+
+    /// Outputs a coinbase claiming `reward` must pay under
+    /// [`ConsensusParams::reward_shares`], in order. Shares that round to
+    /// nothing are left out.
+    pub fn reward_outputs(&self, reward: Coord) -> Result<Vec<CoinbaseOutput>, ChainError> {
+        let mut outputs = Vec::new();
+        let mut paid = Coord::from_num(0);
+        for share in &self.consensus.reward_shares {
+            let fraction = share.fraction.clamp(Coord::from_num(0), Coord::from_num(1));
+            let amount = coord_checked_mul(reward, fraction)?;
+            if amount > Coord::from_num(0) {
+                paid = coord_checked_add(paid, amount)?;
+                outputs.push(CoinbaseOutput { address: share.address, amount });
+            }
+        }
+        if !outputs.is_empty() && paid >= reward {
+            return Err(ChainError::InvalidBlock(
+                "Reward shares leave nothing for the miner.".to_string(),
+            ));
+        }
+        Ok(outputs)
+    }
+
+    /// Height of the block that created each of `owner`'s unspent triangles.
+    /// UTXOs are keyed by the hash of the creating transaction (or of its
+    /// change output / subdivision child), so they are found by scanning the
+    /// chain from the tip.
+    pub fn utxo_creation_heights(&self, owner: &Address) -> HashMap<Sha256Hash, u64> {
+        let owned: Vec<(Sha256Hash, Coord)> = self
+            .state
+            .utxo_set
+            .iter()
+            .filter(|(_, triangle)| triangle.owner == *owner)
+            .map(|(hash, triangle)| (*hash, triangle.effective_value()))
+            .collect();
+        let mut heights = HashMap::new();
+
+        for block in self.blocks.iter().rev() {
+            if heights.len() == owned.len() {
+                break;
+            }
+            for tx in &block.transactions {
+                let mut outputs = vec![tx.hash()];
+                match tx {
+                    Transaction::Transfer(transfer) if transfer.sender == *owner => {
+                        outputs.extend(owned.iter().map(|(_, value)| transfer.change_transaction(*value).hash()));
+                    }
+                    Transaction::Subdivision(subdivision) => {
+                        outputs.extend(subdivision.children.iter().map(|child| child.hash()));
+                    }
+                    Transaction::Coinbase(coinbase) => {
+                        let txid = tx.hash();
+                        outputs.extend((0..coinbase.outputs.len()).map(|index| CoinbaseTx::output_hash(&txid, index)));
+                    }
+                    _ => {}
+                }
+                for output in outputs {
+                    if owned.iter().any(|(hash, _)| *hash == output) {
+                        heights.entry(output).or_insert(block.header.height);
+                    }
+                }
+            }
+        }
+        heights
+    }
+
+    /// Identifies the chain in transaction signatures: the hash of its
+    /// genesis block
+    pub fn chain_id(&self) -> Sha256Hash {
+        self.blocks.first().map_or([0u8; 32], Block::hash)
+    }
+
+    /// Signatures valid in the block at `height`
+    pub fn signing_domain(&self, height: u64) -> SigningDomain {
+        SigningDomain {
+            chain_id: self.chain_id(),
+            allow_legacy: height < self.consensus.chain_id_signature_height,
+        }
+    }
+
+    /// Height of the next block
+    pub fn next_height(&self) -> u64 {
+        self.blocks.last().map_or(0, |b| b.header.height + 1)
+    }
+
+    /// Public key of `address`, if it has signed a transaction or announced
+    /// its key on chain. Addresses are key hashes, so this is the only way to
+    /// learn a payee's key without asking them.
+    pub fn public_key_of(&self, address: &Address) -> Option<Vec<u8>> {
+        self.public_keys.get(address).cloned()
+    }
+
+    /// Address `name` is registered to at the tip
+    pub fn resolve_name(&self, name: &str) -> Option<Address> {
+        self.names.resolve(name, self.blocks.last().map_or(0, |b| b.header.height))
+    }
+
+    /// Address and public key revealed by `tx`: a signer's key or an
+    /// announced one
+    fn revealed_key(tx: &Transaction) -> Option<(Address, Vec<u8>)> {
+        let (address, key) = match tx {
+            Transaction::Transfer(tx) => (tx.sender, tx.public_key.as_ref()?),
+            Transaction::Subdivision(tx) => (tx.owner_address, tx.public_key.as_ref()?),
+            Transaction::Announce(tx) => (tx.address(), &tx.public_key),
+            Transaction::Register(tx) => (tx.owner, tx.public_key.as_ref()?),
+            Transaction::Data(tx) => (tx.owner, tx.public_key.as_ref()?),
+            Transaction::Coinbase(_) => return None,
+        };
+        crate::memo::check_recipient_key(key, &address).ok()?;
+        Some((address, key.clone()))
+    }
+
+    /// Public keys revealed by `blocks`, by address
+    pub fn key_directory(blocks: &[Block]) -> HashMap<Address, Vec<u8>> {
+        let mut keys = HashMap::new();
+        for (address, key) in blocks.iter().flat_map(|b| &b.transactions).filter_map(Self::revealed_key) {
+            keys.entry(address).or_insert(key);
+        }
+        keys
+    }
+
+    /// Builds a block locator: hashes of the most recent blocks, then hashes
+    /// spaced exponentially further apart back to genesis. A peer can find the
+    /// most recent block we have in common with it by scanning the list in
+    /// order.
+    pub fn block_locator(&self) -> Vec<Sha256Hash> {
+        let mut locator = Vec::new();
+        let Some(tip) = self.blocks.last() else {
+            return locator;
+        };
+
+        let mut height = tip.header.height;
+        let mut step = 1u64;
+        loop {
+            if let Some(block) = self.blocks.get(height as usize) {
+                locator.push(block.hash());
+            }
+            if height == 0 {
+                break;
+            }
+            if locator.len() >= LOCATOR_DENSE_BLOCKS {
+                step = step.saturating_mul(2);
+            }
+            height = height.saturating_sub(step);
+        }
+        locator
+    }
+
+    /// Returns the height of the first locator entry that is part of this
+    /// chain, i.e. the fork point with the peer that sent the locator.
+    pub fn find_locator_fork_point(&self, locator: &[Sha256Hash]) -> Option<u64> {
+        let heights: std::collections::HashMap<Sha256Hash, u64> = self
+            .blocks
+            .iter()
+            .map(|b| (b.hash(), b.header.height))
+            .collect();
+        locator.iter().find_map(|hash| heights.get(hash).copied())
+    }
+
+    /// Headers following the fork point described by `locator`, up to and
+    /// including `stop_hash` (when given) and at most `max` entries. An
+    /// unknown locator is answered from genesis.
+    pub fn headers_after_locator(
+        &self,
+        locator: &[Sha256Hash],
+        stop_hash: Option<Sha256Hash>,
+        max: usize,
+    ) -> Vec<BlockHeader> {
+        let start = self
+            .find_locator_fork_point(locator)
+            .map_or(0, |height| height as usize + 1);
+
+        let mut headers = Vec::new();
+        for block in self.blocks.iter().skip(start).take(max) {
+            headers.push(block.header.clone());
+            if stop_hash == Some(block.hash()) {
+                break;
+            }
+        }
+        headers
+    }
+
+    /// Retarget every `DIFFICULTY_ADJUSTMENT_INTERVAL` blocks. Returns `true`
+    /// when a retarget happened.
+    fn adjust_difficulty(&mut self) -> bool {
         let current_height = self.blocks.last().map_or(0, |b| b.header.height);
         if current_height > 0 && current_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
             let last_adjustment_block = self.blocks.get((current_height - DIFFICULTY_ADJUSTMENT_INTERVAL) as usize);
@@ -268,14 +1229,654 @@ impl Blockchain {
                 let ratio = actual_time as f64 / expected_time as f64;
                 let ratio = ratio.clamp(0.25, 4.0);
                 let new_difficulty = (self.difficulty as f64 * ratio) as u32;
-                self.difficulty = new_difficulty.max(1);
+                self.difficulty = new_difficulty.clamp(MIN_DIFFICULTY, MAX_DIFFICULTY);
+                return true;
             }
         }
+        false
     }
 
     fn verify_pow(&self, block: &Block) -> bool {
-        let hash_target = Block::hash_to_target(&block.header.difficulty);
-        let block_hash_int = Block::hash_as_u256(&block.hash());
-        block_hash_int <= hash_target
+        self.consensus.pow.meets_target(&block.header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::core::block::{block_work, VERSIONBITS_TOP_BITS};
+    use crate::blockchain::core::clock::MockClock;
+    use crate::blockchain::core::names::NAME_REGISTRATION_PERIOD;
+    use crate::miner::mine_block;
+    use std::time::Duration;
+
+    fn next_block(chain: &Blockchain, difficulty: u32, gap_ms: u64) -> Block {
+        let tip = chain.blocks.last().unwrap();
+        let height = tip.header.height + 1;
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [1u8; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let mut block = Block::new(height, tip.hash(), difficulty, vec![coinbase]);
+        block.header.timestamp = tip.header.timestamp + gap_ms;
+        mine_block(block).unwrap()
+    }
+
+    #[test]
+    fn test_reorganize_follows_most_work() {
+        let base = Blockchain::new([1u8; 32], 1).unwrap();
+        let mut heavy = base.clone();
+        for _ in 0..2 {
+            let block = next_block(&heavy, 3, 1000);
+            heavy.apply_block(block).unwrap();
+        }
+        let mut chain = base;
+        for _ in 0..3 {
+            let block = next_block(&chain, 1, 1000);
+            chain.apply_block(block).unwrap();
+        }
+        assert_eq!(chain.total_work(), 2 + 3 * 2);
+        assert_eq!(heavy.total_work(), 2 + 2 * 8);
+        let light_branch = chain.blocks[1..].to_vec();
+
+        assert!(chain.reorganize(heavy.blocks[1..].to_vec()).unwrap());
+        assert_eq!(chain.blocks.len(), 3);
+        assert_eq!(chain.blocks.last().unwrap().hash(), heavy.blocks.last().unwrap().hash());
+        assert_eq!(chain.chain_work, heavy.chain_work);
+        assert_eq!(chain.persistence.load_blockchain().unwrap().blocks.len(), 3);
+        let journal: Vec<_> = chain.journal.recent(5).map(|e| (e.event, e.height)).collect();
+        assert_eq!(
+            journal,
+            [
+                (BlockEvent::Applied, 2),
+                (BlockEvent::Applied, 1),
+                (BlockEvent::Reverted, 1),
+                (BlockEvent::Reverted, 2),
+                (BlockEvent::Reverted, 3)
+            ]
+        );
+
+        // The longer branch has less work and is refused
+        let light_tip = light_branch.last().unwrap().hash();
+        assert!(!chain.reorganize(light_branch).unwrap());
+        assert_eq!(chain.total_work(), heavy.total_work());
+        assert_eq!(block_work(MAX_DIFFICULTY), u128::MAX);
+
+        // The abandoned chain stays visible as a validated fork, and a
+        // heavier block that fails validation as an invalid one
+        let mut invalid = next_block(&chain, 3, 1000);
+        invalid.transactions.clear();
+        assert!(chain.reorganize(vec![invalid.clone()]).is_err());
+        let tips = chain.chain_tips();
+        assert_eq!(tips.len(), 3);
+        assert_eq!((tips[0].height, tips[0].status), (2, TipStatus::Active));
+        assert_eq!(tips[1], ChainTip { height: 3, hash: light_tip, branch_len: 3, status: TipStatus::ValidFork });
+        assert_eq!(tips[2], ChainTip { height: 3, hash: invalid.hash(), branch_len: 1, status: TipStatus::Invalid });
+    }
+
+    #[test]
+    fn test_block_timestamps_follow_the_clock_and_median_time_past() {
+        let mut chain = Blockchain::new([1u8; 32], 0).unwrap();
+        let genesis_time = chain.blocks[0].header.timestamp;
+        let clock = MockClock::new(genesis_time);
+        chain.set_clock(Arc::new(clock.clone()));
+        chain.consensus.median_time_past_height = 0;
+
+        // Templates are stamped by the clock, and never at or before the
+        // median time past
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(TARGET_BLOCK_TIME));
+            let block = Block::new_with_clock(chain.next_height(), chain.blocks.last().unwrap().hash(), 0, vec![], chain.clock.as_ref());
+            assert_eq!(block.header.timestamp, clock.now_millis());
+            let block = next_block(&chain, 0, TARGET_BLOCK_TIME * 1000);
+            chain.apply_block(block).unwrap();
+        }
+        assert_eq!(chain.median_time_past(), genesis_time + 2 * TARGET_BLOCK_TIME * 1000);
+        clock.set(genesis_time);
+        assert_eq!(chain.next_timestamp(), chain.median_time_past() + 1);
+
+        // A block at the median time past is refused once the rule is active
+        let mut stale = next_block(&chain, 0, 0);
+        stale.header.timestamp = chain.median_time_past();
+        let stale = mine_block(stale).unwrap();
+        assert!(chain.check_timestamp(&stale.header).is_err());
+        chain.consensus.median_time_past_height = 5;
+        assert!(chain.check_timestamp(&stale.header).is_ok());
+
+        // A block more than two hours ahead of the clock waits for it
+        let ahead = next_block(&chain, 0, 3 * 60 * 60 * 1000);
+        assert!(chain.clone().apply_block(ahead.clone()).is_err());
+        clock.advance(Duration::from_secs(3 * 60 * 60));
+        chain.apply_block(ahead).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_prevents_deep_reorg() {
+        let base = Blockchain::new([1u8; 32], 1).unwrap();
+        let mut heavy = base.clone();
+        for _ in 0..2 {
+            let block = next_block(&heavy, 3, 1000);
+            heavy.apply_block(block).unwrap();
+        }
+        let mut chain = base;
+        for _ in 0..2 {
+            let block = next_block(&chain, 1, 1000);
+            chain.apply_block(block).unwrap();
+        }
+
+        let conflicting = Checkpoint { height: 1, hash: heavy.blocks[1].hash() };
+        assert!(chain.set_checkpoint(conflicting).is_err());
+        let ours = Checkpoint { height: 1, hash: chain.blocks[1].hash() };
+        assert!(chain.set_checkpoint(ours.clone()).unwrap());
+        assert!(!chain.set_checkpoint(Checkpoint { height: 0, hash: chain.blocks[0].hash() }).unwrap());
+
+        // The heavier branch forks below the checkpoint and is refused
+        assert!(chain.reorganize(heavy.blocks[1..].to_vec()).is_err());
+        assert_eq!(chain.checkpoint, Some(ours));
+        assert_eq!(chain.blocks.len(), 3);
+        assert!(heavy.set_checkpoint(Checkpoint { height: 3, hash: [0u8; 32] }).unwrap());
+        let block = next_block(&heavy, 1, 1000);
+        assert!(heavy.apply_block(block).is_err());
+    }
+
+    fn chain_with_emergency_rule() -> Blockchain {
+        let mut chain = Blockchain::new([1u8; 32], 2).unwrap();
+        chain.consensus.emergency_difficulty_height = 1;
+        chain
+    }
+
+    #[test]
+    fn test_miners_signal_until_deployment_activates() {
+        let mut chain = Blockchain::new([1u8; 32], 0).unwrap();
+        chain.consensus.signaling_period = 2;
+        chain.consensus.deployments.push(Deployment {
+            name: "geometry-v2".to_string(),
+            bit: 3,
+            start_height: 2,
+            timeout_height: 100,
+            threshold: 2,
+        });
+
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            states.push(chain.deployment_states()[0].1);
+            let version = chain.next_block_version();
+            let block = next_block(&chain, 0, TARGET_BLOCK_TIME * 1000).with_version(version);
+            chain.apply_block(mine_block(block).unwrap()).unwrap();
+        }
+        assert_eq!(
+            states,
+            // States for heights 1 through 6
+            vec![
+                DeploymentState::Defined,
+                DeploymentState::Started,
+                DeploymentState::Started,
+                DeploymentState::LockedIn,
+                DeploymentState::LockedIn,
+                DeploymentState::Active,
+            ]
+        );
+        assert!(chain.is_deployment_active("geometry-v2"));
+        assert_eq!(chain.next_block_version(), VERSIONBITS_TOP_BITS);
+    }
+
+    #[test]
+    fn test_blocks_are_checked_with_the_network_pow_algorithm() {
+        use crate::blockchain::core::pow::FastPow;
+        use crate::miner::mine_block_with;
+
+        let mut chain = Blockchain::new([1u8; 32], 1).unwrap();
+        chain.consensus.pow = &FastPow;
+        let mut template = next_block(&chain, chain.difficulty, 1_000);
+        // Solved for SHA-256 only, so no proof of work under FastPow
+        while FastPow.meets_target(&template.header) {
+            template.header.timestamp += 1;
+            template = mine_block(template).unwrap();
+        }
+        let err = chain.clone().apply_block(template.clone()).unwrap_err();
+        assert!(err.to_string().contains("Proof-of-Work"), "{}", err);
+
+        let fast = mine_block_with(template, &FastPow).unwrap();
+        assert!(FastPow.meets_target(&fast.header));
+        chain.apply_block(fast).unwrap();
+        assert_eq!(chain.blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_low_difficulty_block_rejected_without_gap() {
+        let mut chain = chain_with_emergency_rule();
+        let block = next_block(&chain, 1, TARGET_BLOCK_TIME * 1000);
+        assert!(chain.apply_block(block).is_err());
+
+        let block = next_block(&chain, 2, TARGET_BLOCK_TIME * 1000);
+        assert!(chain.apply_block(block).is_ok());
+    }
+
+    #[test]
+    fn test_emergency_block_after_long_gap() {
+        let mut chain = chain_with_emergency_rule();
+        let gap = EMERGENCY_BLOCK_TIME_MULTIPLIER * TARGET_BLOCK_TIME * 1000;
+        let tip_timestamp = chain.blocks[0].header.timestamp;
+        assert_eq!(chain.required_difficulty(tip_timestamp + gap - 1), 2);
+        assert_eq!(chain.required_difficulty(tip_timestamp + gap), 1);
+
+        let block = next_block(&chain, 1, gap);
+        chain.apply_block(block).unwrap();
+        // The reduced difficulty sticks until the next retarget
+        assert_eq!(chain.difficulty, 1);
+    }
+
+    #[test]
+    fn test_required_difficulty_respects_floor() {
+        let mut chain = chain_with_emergency_rule();
+        chain.difficulty = 40;
+        let tip_timestamp = chain.blocks[0].header.timestamp;
+        assert_eq!(chain.required_difficulty(tip_timestamp + u64::MAX / 2), MIN_DIFFICULTY);
+    }
+
+    #[test]
+    fn test_emergency_rule_inactive_before_activation_height() {
+        let mut chain = Blockchain::new([1u8; 32], 2).unwrap();
+        let block = next_block(&chain, 0, TARGET_BLOCK_TIME * 1000);
+        assert!(chain.apply_block(block).is_ok());
+        assert_eq!(chain.difficulty, 2);
+    }
+
+    #[test]
+    fn test_utxo_creation_heights() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::TransferTx;
+
+        let keypair = KeyPair::generate().unwrap();
+        let owner = keypair.address();
+        let mut chain = Blockchain::new(owner, 0).unwrap();
+        let chain_id = chain.chain_id();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+
+        // Height 1: an unrelated block. Height 2: spend the genesis reward.
+        let block = next_block(&chain, 0, TARGET_BLOCK_TIME * 1000);
+        chain.apply_block(block).unwrap();
+        let input_value = chain.state.utxo_set[&genesis_utxo].effective_value();
+        let mut transfer = TransferTx::new(genesis_utxo, [2u8; 32], owner, Coord::from_num(1), Coord::from_num(0), 2);
+        let signature = keypair.sign(&transfer.signable_message(&chain_id)).unwrap();
+        transfer.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        let change_utxo = transfer.change_transaction(input_value - Coord::from_num(1)).hash();
+
+        let tip = chain.blocks.last().unwrap();
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: owner,
+            nonce: 2,
+            height: 2,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let block = Block::new(2, tip.hash(), 0, vec![coinbase.clone(), Transaction::Transfer(transfer)]);
+        chain.apply_block(mine_block(block).unwrap()).unwrap();
+
+        let heights = chain.utxo_creation_heights(&owner);
+        assert_eq!(heights.len(), 2);
+        assert_eq!(heights[&change_utxo], 2);
+        assert_eq!(heights[&coinbase.hash()], 2);
+        assert!(chain.utxo_creation_heights(&[3u8; 32]).is_empty());
+
+        // The transfer revealed the owner's key; the payee has never signed
+        assert_eq!(chain.public_key_of(&owner), Some(keypair.public_key_bytes().to_vec()));
+        assert_eq!(chain.public_key_of(&[2u8; 32]), None);
+        assert_eq!(Blockchain::key_directory(&chain.blocks), chain.public_keys);
+    }
+
+    #[test]
+    fn test_signatures_must_cover_the_chain_id_after_activation() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::TransferTx;
+
+        let keypair = KeyPair::generate().unwrap();
+        let owner = keypair.address();
+        let mut chain = Blockchain::new(owner, 0).unwrap();
+        chain.consensus.chain_id_signature_height = 2;
+        let chain_id = chain.chain_id();
+        let input = chain.blocks[0].transactions[0].hash();
+        let spend = |message: &dyn Fn(&TransferTx) -> Vec<u8>| {
+            let mut transfer = TransferTx::new(input, [2u8; 32], owner, Coord::from_num(1), Coord::from_num(0), 1);
+            let signature = keypair.sign(&message(&transfer)).unwrap();
+            transfer.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+            Transaction::Transfer(transfer)
+        };
+        let block_with = |chain: &Blockchain, tx: Transaction| {
+            let mut block = next_block(chain, 0, TARGET_BLOCK_TIME * 1000);
+            block.transactions.push(tx);
+            block.header.merkle_root = Block::calculate_merkle_root(block.header.height, &block.transactions);
+            mine_block(block).unwrap()
+        };
+
+        // A transaction signed for another chain is never valid here
+        let replayed = spend(&|tx| tx.signable_message(&[9u8; 32]));
+        assert!(chain.clone().apply_block(block_with(&chain, replayed.clone())).is_err());
+        assert!(chain.accept_transaction(replayed).is_err());
+
+        // Legacy signatures are accepted below the activation height only
+        let legacy = spend(&|tx| tx.legacy_signable_message());
+        chain.clone().apply_block(block_with(&chain, legacy.clone())).unwrap();
+        chain.apply_block(next_block(&chain, 0, TARGET_BLOCK_TIME * 1000)).unwrap();
+        assert!(chain.clone().apply_block(block_with(&chain, legacy.clone())).is_err());
+        assert!(chain.accept_transaction(legacy).is_err());
+
+        let bound = spend(&|tx| tx.signable_message(&chain_id));
+        chain.accept_transaction(bound.clone()).unwrap();
+        chain.apply_block(block_with(&chain, bound)).unwrap();
+    }
+
+    #[test]
+    fn test_coinbase_fee_claim() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::TransferTx;
+
+        let keypair = KeyPair::generate().unwrap();
+        let mut chain = Blockchain::new(keypair.address(), 0).unwrap();
+        let chain_id = chain.chain_id();
+        chain.consensus.fee_burn_fraction = Coord::from_num(0.25);
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let mut transfer = TransferTx::new(genesis_utxo, [2u8; 32], keypair.address(), Coord::from_num(10), Coord::from_num(8), 1);
+        let signature = keypair.sign(&transfer.signable_message(&chain_id)).unwrap();
+        transfer.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        let transfer = Transaction::Transfer(transfer);
+        assert_eq!(chain.claimable_fees(std::slice::from_ref(&transfer)).unwrap(), Coord::from_num(6));
+
+        let block_claiming = |chain: &Blockchain, height: u64, reward: Coord, fees: f64| {
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: reward,
+                beneficiary_address: keypair.address(),
+                nonce: 1,
+                height,
+                fees: Coord::from_num(fees),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            mine_block(Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase, transfer.clone()])).unwrap()
+        };
+        // Before activation a legacy coinbase, committing to neither, still
+        // applies, but may not claim the burned quarter of the fee either
+        let unburned = CoinbaseTx::MAX_REWARD_AREA + Coord::from_num(6);
+        let mut legacy = chain.clone();
+        assert!(legacy.apply_block(block_claiming(&legacy, 0, unburned + Coord::from_num(2), 0.0)).is_err());
+        legacy.apply_block(block_claiming(&legacy, 0, unburned, 0.0)).unwrap();
+        assert_eq!(legacy.state.get_balance(&keypair.address()), Coord::from_num(1_000_000 - 18) + unburned);
+
+        // After it the coinbase must commit to its block's height and
+        // unburned fees, and the burned quarter cannot be claimed
+        chain.consensus.coinbase_commitment_height = 1;
+        assert!(chain.apply_block(block_claiming(&chain, 0, unburned, 0.0)).is_err());
+        assert!(chain.apply_block(block_claiming(&chain, 2, unburned, 6.0)).is_err());
+        assert!(chain.apply_block(block_claiming(&chain, 1, unburned, 8.0)).is_err());
+        assert!(chain.apply_block(block_claiming(&chain, 1, unburned + Coord::from_num(2), 6.0)).is_err());
+        chain.apply_block(block_claiming(&chain, 1, unburned, 6.0)).unwrap();
+        assert_eq!(chain.state.get_balance(&keypair.address()), Coord::from_num(1_000_000 - 18) + unburned);
+    }
+
+    #[test]
+    fn test_reward_split() {
+        let miner = [1u8; 32];
+        let treasury = [2u8; 32];
+        let mut chain = Blockchain::new(miner, 0).unwrap();
+        chain.consensus.reward_shares = vec![RewardShare { address: treasury, fraction: Coord::from_num(0.25) }];
+        let reward = Coord::from_num(100);
+        let outputs = chain.reward_outputs(reward).unwrap();
+        assert_eq!(outputs, vec![CoinbaseOutput { address: treasury, amount: Coord::from_num(25) }]);
+
+        let block_paying = |chain: &Blockchain, outputs: Vec<CoinbaseOutput>| {
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: reward,
+                beneficiary_address: miner,
+                nonce: 1,
+                height: 1,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs,
+            });
+            mine_block(Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase])).unwrap()
+        };
+        // Skipping the treasury or paying it more or less is rejected
+        assert!(chain.apply_block(block_paying(&chain, Vec::new())).is_err());
+        let short = vec![CoinbaseOutput { address: treasury, amount: Coord::from_num(24) }];
+        assert!(chain.apply_block(block_paying(&chain, short)).is_err());
+        let elsewhere = vec![CoinbaseOutput { address: [3u8; 32], amount: Coord::from_num(25) }];
+        assert!(chain.apply_block(block_paying(&chain, elsewhere)).is_err());
+
+        let block = block_paying(&chain, outputs);
+        chain.apply_block(block.clone()).unwrap();
+        assert_eq!(chain.state.get_balance(&treasury), Coord::from_num(25));
+        assert_eq!(chain.state.get_balance(&miner), Coord::from_num(1_000_000 + 75));
+        let txid = block.transactions[0].hash();
+        let treasury_utxo = CoinbaseTx::output_hash(&txid, 0);
+        assert_eq!(chain.state.utxo_set[&treasury_utxo].owner, treasury);
+        assert_eq!(chain.utxo_creation_heights(&treasury).get(&treasury_utxo), Some(&1));
+
+        chain.revert_block().unwrap();
+        assert!(!chain.state.utxo_set.contains_key(&treasury_utxo));
+        assert_eq!(chain.state.get_balance(&treasury), Coord::from_num(0));
+
+        chain.consensus.reward_shares.push(RewardShare { address: [3u8; 32], fraction: Coord::from_num(0.75) });
+        assert!(chain.reward_outputs(reward).is_err());
+    }
+
+    #[test]
+    fn test_key_announcements() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::AnnounceTx;
+
+        let miner = KeyPair::generate().unwrap();
+        let stranger = KeyPair::generate().unwrap();
+        let mut chain = Blockchain::new(miner.address(), 0).unwrap();
+        let domain = chain.signing_domain(1);
+        let announce = |keypair: &KeyPair| Transaction::Announce(AnnounceTx::new(keypair.public_key_bytes().to_vec()));
+        let block_with = |chain: &Blockchain, txs: Vec<Transaction>| {
+            let tip = chain.blocks.last().unwrap();
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: miner.address(),
+                nonce: tip.header.height + 1,
+                height: tip.header.height + 1,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let txs = std::iter::once(coinbase).chain(txs).collect();
+            mine_block(Block::new(tip.header.height + 1, tip.hash(), 0, txs)).unwrap()
+        };
+
+        // An address without triangles cannot announce, nor can a key be
+        // announced twice
+        assert!(chain.apply_block(block_with(&chain, vec![announce(&stranger)])).is_err());
+        assert!(chain.apply_block(block_with(&chain, vec![announce(&miner), announce(&miner)])).is_err());
+        assert_eq!(chain.public_key_of(&miner.address()), None);
+
+        chain.mempool.accept_transaction(announce(&miner), &chain.state, &domain).unwrap();
+        assert!(chain.mempool.accept_transaction(announce(&stranger), &chain.state, &domain).is_err());
+        chain.apply_block(block_with(&chain, vec![announce(&miner)])).unwrap();
+        assert_eq!(chain.public_key_of(&miner.address()), Some(miner.public_key_bytes().to_vec()));
+        assert!(chain.mempool.is_empty());
+        assert!(chain.apply_block(block_with(&chain, vec![announce(&miner)])).is_err());
+    }
+
+    #[test]
+    fn test_name_registration() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::RegisterNameTx;
+
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let carol = KeyPair::generate().unwrap();
+        let mut chain = Blockchain::new(alice.address(), 0).unwrap();
+        let domain = chain.signing_domain(1);
+        let register = |keypair: &KeyPair, name: &str, nonce: u64| {
+            let mut tx = RegisterNameTx::new(name.to_string(), keypair.address(), nonce);
+            let signature = keypair.sign(&tx.signable_message(&domain.chain_id)).unwrap();
+            tx.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+            Transaction::Register(tx)
+        };
+        let block_with = |chain: &Blockchain, txs: Vec<Transaction>| {
+            let tip = chain.blocks.last().unwrap();
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: bob.address(),
+                nonce: tip.header.height + 1,
+                height: tip.header.height + 1,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let txs = std::iter::once(coinbase).chain(txs).collect();
+            mine_block(Block::new(tip.header.height + 1, tip.hash(), 0, txs)).unwrap()
+        };
+
+        // Malformed names and owners without triangles are rejected
+        assert!(chain.apply_block(block_with(&chain, vec![register(&alice, "-alice", 0)])).is_err());
+        assert!(chain.apply_block(block_with(&chain, vec![register(&carol, "carol", 0)])).is_err());
+        assert!(chain
+            .apply_block(block_with(&chain, vec![register(&alice, "alice", 0), register(&alice, "alice", 1)]))
+            .is_err());
+
+        chain.mempool.accept_transaction(register(&alice, "alice", 0), &chain.state, &domain).unwrap();
+        assert!(chain.mempool.accept_transaction(register(&alice, "alice", 1), &chain.state, &domain).is_err());
+        chain.apply_block(block_with(&chain, vec![register(&alice, "alice", 0)])).unwrap();
+        assert_eq!(chain.resolve_name("alice"), Some(alice.address()));
+        assert!(chain.mempool.is_empty());
+
+        // Bob owns the coinbase triangle, but the name is taken; Alice may
+        // renew it
+        assert!(chain.apply_block(block_with(&chain, vec![register(&bob, "alice", 0)])).is_err());
+        chain.apply_block(block_with(&chain, vec![register(&alice, "alice", 1)])).unwrap();
+        let record = chain.names.get("alice").unwrap().clone();
+        assert_eq!(record.registered_height, 1);
+        assert_eq!(record.expires_height, 2 + NAME_REGISTRATION_PERIOD);
+        assert_eq!(NameRegistry::rebuild(&chain.blocks).get("alice"), Some(&record));
+
+        chain.revert_block().unwrap();
+        assert_eq!(chain.names.get("alice").unwrap().expires_height, 1 + NAME_REGISTRATION_PERIOD);
+        chain.revert_block().unwrap();
+        assert_eq!(chain.resolve_name("alice"), None);
+    }
+
+    #[test]
+    fn test_data_transactions() {
+        use crate::crypto::KeyPair;
+        use crate::transaction::{DataTx, DATA_FEE_PER_BYTE, MAX_DATA_PAYLOAD};
+
+        let owner = KeyPair::generate().unwrap();
+        let mut chain = Blockchain::new(owner.address(), 0).unwrap();
+        let domain = chain.signing_domain(1);
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let data = |payload: Vec<u8>, fee: Coord| {
+            let mut tx = DataTx::new(genesis_utxo, owner.address(), payload, fee, 1);
+            let signature = owner.sign(&tx.signable_message(&domain.chain_id)).unwrap();
+            tx.sign(signature.to_vec(), owner.public_key_bytes().to_vec());
+            Transaction::Data(tx)
+        };
+        let block_with = |chain: &Blockchain, txs: Vec<Transaction>| {
+            let tip = chain.blocks.last().unwrap();
+            let fees = chain.claimable_fees(&txs).unwrap();
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1).saturating_add(fees),
+                beneficiary_address: [3u8; 32],
+                nonce: tip.header.height + 1,
+                height: tip.header.height + 1,
+                fees,
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let txs = std::iter::once(coinbase).chain(txs).collect();
+            mine_block(Block::new(tip.header.height + 1, tip.hash(), 0, txs)).unwrap()
+        };
+
+        // The fee scales with the payload, which is bounded
+        let payload = b"sha256:9f86d081884c7d65".to_vec();
+        let fee = DataTx::min_fee(payload.len());
+        let underpaid = data(payload.clone(), fee.saturating_sub(DATA_FEE_PER_BYTE));
+        assert!(chain.mempool.accept_transaction(underpaid, &chain.state, &domain).is_err());
+        let oversized = data(vec![0; MAX_DATA_PAYLOAD + 1], DataTx::min_fee(MAX_DATA_PAYLOAD + 1));
+        assert!(chain.mempool.accept_transaction(oversized, &chain.state, &domain).is_err());
+
+        let tx = data(payload.clone(), fee);
+        let value = chain.state.utxo_set[&genesis_utxo].effective_value();
+        chain.mempool.accept_transaction(tx.clone(), &chain.state, &domain).unwrap();
+        chain.apply_block(block_with(&chain, vec![tx.clone()])).unwrap();
+        let change = &chain.state.utxo_set[&tx.hash()];
+        assert_eq!(change.owner, owner.address());
+        assert_eq!(change.effective_value(), value.saturating_sub(fee));
+
+        // Pruning keeps the txid and the block hash
+        let block_hash = chain.blocks[1].hash();
+        chain.prune_data_payloads(1 + DATA_PAYLOAD_RETENTION);
+        let Transaction::Data(pruned) = &chain.blocks[1].transactions[1] else {
+            panic!("expected a data transaction");
+        };
+        assert!(pruned.is_pruned());
+        assert_eq!(chain.blocks[1].transactions[1].hash(), tx.hash());
+        assert_eq!(chain.blocks[1].hash(), block_hash);
+
+        chain.revert_block().unwrap();
+        assert!(chain.state.utxo_set.contains_key(&genesis_utxo));
+        assert!(!chain.state.utxo_set.contains_key(&tx.hash()));
+    }
+
+    #[test]
+    fn test_revert_block_with_undo() {
+        use crate::blockchain::core::utxostats::DEFAULT_DUST_THRESHOLD;
+        use crate::crypto::KeyPair;
+        use crate::transaction::TransferTx;
+
+        let keypair = KeyPair::generate().unwrap();
+        let owner = keypair.address();
+        let mut chain = Blockchain::new(owner, 0).unwrap();
+        let chain_id = chain.chain_id();
+        assert!(chain.revert_block().is_err());
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let utxos_before = chain.state.utxo_set.clone();
+        let stats_before = chain.utxo_stats.clone();
+        let balance_before = chain.state.get_balance(&owner);
+
+        let mut transfer = TransferTx::new(genesis_utxo, [2u8; 32], owner, Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = keypair.sign(&transfer.signable_message(&chain_id)).unwrap();
+        transfer.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [3u8; 32],
+            nonce: 1,
+            height: 1,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let block = Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase, Transaction::Transfer(transfer)]);
+        let block = mine_block(block).unwrap();
+        chain.apply_block(block.clone()).unwrap();
+        assert!(chain.public_key_of(&owner).is_some());
+        assert_eq!(chain.persistence.load_block_undo(1).unwrap().unwrap().spent.len(), 1);
+        assert_eq!(chain.utxo_stats, UtxoStatsTracker::from_state(&chain.state, DEFAULT_DUST_THRESHOLD));
+        assert_eq!(chain.persistence.load_utxo_stats(TimeRange::ALL).unwrap().len(), 2);
+
+        // Reverting restores exactly the state before the block
+        assert_eq!(chain.revert_block().unwrap().hash(), block.hash());
+        assert_eq!(chain.blocks.len(), 1);
+        assert_eq!(chain.chain_work.len(), 1);
+        assert_eq!(chain.state.utxo_set, utxos_before);
+        assert_eq!(chain.state.get_balance(&owner), balance_before);
+        assert_eq!(chain.state.get_balance(&[2u8; 32]), Coord::from_num(0));
+        assert_eq!(chain.state.get_balance(&[3u8; 32]), Coord::from_num(0));
+        assert_eq!(chain.public_key_of(&owner), None);
+        assert_eq!(chain.persistence.load_block_undo(1).unwrap(), None);
+        assert_eq!(chain.utxo_stats, stats_before);
+        assert_eq!(chain.persistence.load_utxo_stats(TimeRange::ALL).unwrap().len(), 1);
+
+        // The block can be applied again
+        chain.apply_block(block).unwrap();
+        assert_eq!(chain.state.get_balance(&[2u8; 32]), Coord::from_num(10));
     }
 }
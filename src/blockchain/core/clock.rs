@@ -0,0 +1,83 @@
+//! Wall-clock time for consensus code
+//!
+//! Block templates, the future-timestamp limit and mempool expiry all ask a
+//! [`Clock`] for the time instead of reading the system clock themselves.
+//! Nodes use [`SystemClock`]; tests hand a [`MockClock`] to
+//! [`Blockchain::set_clock`](crate::blockchain::Blockchain::set_clock) and
+//! move it forward by hand, so rules that depend on time run the same on
+//! every run and without sleeping.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Source of the current time
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+
+    /// Seconds since the Unix epoch
+    fn now_secs(&self) -> i64 {
+        (self.now_millis() / 1000) as i64
+    }
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        chrono::Utc::now().timestamp_millis() as u64
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test keeps one handle and gives the other to the code under test.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    millis: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(millis: u64) -> Self {
+        Self { millis: Arc::new(AtomicU64::new(millis)) }
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.millis.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_millis(&self) -> u64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
+
+/// Shared system clock, the default wherever a clock is stored
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_is_shared_between_clones() {
+        let clock = MockClock::new(1_500);
+        let handle: Arc<dyn Clock> = Arc::new(clock.clone());
+        assert_eq!((handle.now_millis(), handle.now_secs()), (1_500, 1));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(handle.now_millis(), 3_500);
+        clock.set(10);
+        assert_eq!(handle.now_secs(), 0);
+        assert!(SystemClock.now_millis() > 1_600_000_000_000);
+    }
+}
@@ -0,0 +1,59 @@
+//! Recent changes to the active chain
+//!
+//! Every block connected to or disconnected from the tip is noted, so an
+//! operator comparing two nodes that disagree can see which blocks each one
+//! applied and reverted last, including through reorganizations. Like the
+//! fork tips the journal lives in memory only and starts empty on restart.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use super::block::{Block, Sha256Hash};
+
+/// Journal entries kept at most; the oldest ones are dropped first
+pub const MAX_JOURNAL_ENTRIES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockEvent {
+    Applied,
+    Reverted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub height: u64,
+    pub hash: Sha256Hash,
+    pub event: BlockEvent,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockJournal {
+    entries: VecDeque<JournalEntry>,
+}
+
+impl BlockJournal {
+    pub fn record(&mut self, block: &Block, event: BlockEvent) {
+        self.entries.push_back(JournalEntry {
+            height: block.header.height,
+            hash: block.hash(),
+            event,
+        });
+        if self.entries.len() > MAX_JOURNAL_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Up to `limit` entries, newest first
+    pub fn recent(&self, limit: usize) -> impl Iterator<Item = &JournalEntry> {
+        self.entries.iter().rev().take(limit)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,156 @@
+//! On-chain name registry
+//!
+//! Register transactions map a human-readable name to an address. A name
+//! goes to whoever registers it first and stays theirs for
+//! [`NAME_REGISTRATION_PERIOD`] blocks; registering it again before then
+//! renews it, and once it expires anyone may take it. Like the public key
+//! directory the registry is derived from the blocks, so it is rebuilt on
+//! load and restored from undo records when blocks are reverted.
+
+use crate::crypto::Address;
+use crate::error::ChainError;
+use crate::transaction::{RegisterNameTx, Transaction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::block::Block;
+
+/// Blocks a registration lasts, about a year at the 30 second target
+pub const NAME_REGISTRATION_PERIOD: u64 = 1_051_200;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRecord {
+    pub owner: Address,
+    /// Height of the first registration, kept across renewals
+    pub registered_height: u64,
+    /// First height at which the name is free again
+    pub expires_height: u64,
+}
+
+impl NameRecord {
+    pub fn is_active(&self, height: u64) -> bool {
+        height < self.expires_height
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NameRegistry {
+    names: HashMap<String, NameRecord>,
+}
+
+impl NameRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry after applying every registration in `blocks`
+    pub fn rebuild(blocks: &[Block]) -> Self {
+        let mut registry = Self::new();
+        for block in blocks {
+            for tx in &block.transactions {
+                if let Transaction::Register(register) = tx {
+                    if registry.check(register, block.header.height).is_ok() {
+                        registry.register(register, block.header.height);
+                    }
+                }
+            }
+        }
+        registry
+    }
+
+    /// Record of `name`, expired or not
+    pub fn get(&self, name: &str) -> Option<&NameRecord> {
+        self.names.get(name)
+    }
+
+    /// Owner of `name` if its registration is still active at `height`
+    pub fn resolve(&self, name: &str, height: u64) -> Option<Address> {
+        self.names
+            .get(name)
+            .filter(|record| record.is_active(height))
+            .map(|record| record.owner)
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Whether `tx` may register its name in a block at `height`: the name
+    /// must be free, expired or already held by the same owner
+    pub fn check(&self, tx: &RegisterNameTx, height: u64) -> Result<(), ChainError> {
+        match self.names.get(&tx.name) {
+            Some(record) if record.is_active(height) && record.owner != tx.owner => {
+                Err(ChainError::InvalidTransaction(format!(
+                    "Name '{}' is registered to {} until height {}",
+                    tx.name,
+                    hex::encode(record.owner),
+                    record.expires_height
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Register or renew the name of `tx` at `height`, returning the record
+    /// it replaces
+    pub fn register(&mut self, tx: &RegisterNameTx, height: u64) -> Option<NameRecord> {
+        let registered_height = match self.names.get(&tx.name) {
+            Some(record) if record.is_active(height) && record.owner == tx.owner => record.registered_height,
+            _ => height,
+        };
+        self.names.insert(
+            tx.name.clone(),
+            NameRecord {
+                owner: tx.owner,
+                registered_height,
+                expires_height: height.saturating_add(NAME_REGISTRATION_PERIOD),
+            },
+        )
+    }
+
+    /// Put back the record `register` replaced
+    pub fn restore(&mut self, name: &str, previous: Option<NameRecord>) {
+        match previous {
+            Some(record) => self.names.insert(name.to_string(), record),
+            None => self.names.remove(name),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(name: &str, owner: u8) -> RegisterNameTx {
+        RegisterNameTx::new(name.to_string(), [owner; 32], 0)
+    }
+
+    #[test]
+    fn test_first_come_renewal_and_expiry() {
+        let mut registry = NameRegistry::new();
+        assert!(registry.register(&registration("alice", 1), 10).is_none());
+        assert_eq!(registry.resolve("alice", 11), Some([1; 32]));
+
+        // Taken by someone else until it expires
+        assert!(registry.check(&registration("alice", 2), 11).is_err());
+        let expiry = 10 + NAME_REGISTRATION_PERIOD;
+        assert!(registry.check(&registration("alice", 2), expiry).is_ok());
+        assert_eq!(registry.resolve("alice", expiry), None);
+
+        // Renewal keeps the original registration height
+        registry.check(&registration("alice", 1), 500).unwrap();
+        let previous = registry.register(&registration("alice", 1), 500);
+        let record = registry.get("alice").unwrap();
+        assert_eq!(record.registered_height, 10);
+        assert_eq!(record.expires_height, 500 + NAME_REGISTRATION_PERIOD);
+
+        registry.restore("alice", previous);
+        assert_eq!(registry.get("alice").unwrap().expires_height, expiry);
+        registry.restore("alice", None);
+        assert!(registry.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Proof-of-work hash algorithms
+//!
+//! The hash a block's proof-of-work is measured with is a consensus
+//! parameter, [`ConsensusParams::pow`](crate::blockchain::ConsensusParams::pow),
+//! rather than being wired into the miner and block validation. Mainnet uses
+//! [`Sha256Pow`], the header hash that is also the block's id; a test
+//! network can pick [`FastPow`] so mining at low difficulty costs next to
+//! nothing. A later change of algorithm (e.g. a memory-hard one) only needs
+//! a new implementation and a network selecting it.
+//!
+//! The block id stays the SHA-256 header hash whatever the algorithm.
+
+use crate::blockchain::core::block::{Block, BlockHeader, Sha256Hash};
+use std::fmt;
+
+/// Hash a header's proof-of-work is measured with
+pub trait PowAlgorithm: fmt::Debug + Send + Sync {
+    /// Name selecting the algorithm in `network.pow_algorithm`
+    fn name(&self) -> &'static str;
+
+    /// Proof-of-work hash of `header`
+    fn hash(&self, header: &BlockHeader) -> Sha256Hash;
+
+    /// Whether the header meets the target of its own difficulty
+    fn meets_target(&self, header: &BlockHeader) -> bool {
+        Block::hash_as_u256(&self.hash(header)) <= Block::hash_to_target(&header.difficulty)
+    }
+}
+
+impl PartialEq for dyn PowAlgorithm {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+/// SHA-256 over the header: the block id doubles as its proof-of-work
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Pow;
+
+impl PowAlgorithm for Sha256Pow {
+    fn name(&self) -> &'static str {
+        "sha256"
+    }
+
+    fn hash(&self, header: &BlockHeader) -> Sha256Hash {
+        header.hash()
+    }
+}
+
+/// FNV-1a over the header, for test networks only: trivially fast and
+/// trivially forgeable
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FastPow;
+
+impl PowAlgorithm for FastPow {
+    fn name(&self) -> &'static str {
+        "fast"
+    }
+
+    fn hash(&self, header: &BlockHeader) -> Sha256Hash {
+        const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        let bytes = header.pow_bytes();
+        let mut hash = [0u8; 32];
+        // Four lanes seeded apart, so every byte of the result varies
+        for (lane, chunk) in hash.chunks_exact_mut(8).enumerate() {
+            let lane_hash = bytes
+                .iter()
+                .fold(FNV_OFFSET ^ lane as u64, |acc, byte| (acc ^ *byte as u64).wrapping_mul(FNV_PRIME));
+            chunk.copy_from_slice(&lane_hash.to_be_bytes());
+        }
+        hash
+    }
+}
+
+/// Algorithm of networks that do not pick one
+pub const DEFAULT_POW: &dyn PowAlgorithm = &Sha256Pow;
+
+/// Every algorithm a network can select
+pub const POW_ALGORITHMS: &[&dyn PowAlgorithm] = &[&Sha256Pow, &FastPow];
+
+/// Algorithm named `name`, see [`PowAlgorithm::name`]
+pub fn pow_algorithm(name: &str) -> Option<&'static dyn PowAlgorithm> {
+    POW_ALGORITHMS
+        .iter()
+        .copied()
+        .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn header(nonce: u64) -> BlockHeader {
+        let mut block = Block::new(1, [3u8; 32], 4, Vec::new());
+        block.header.nonce = nonce;
+        block.header
+    }
+
+    #[test]
+    fn test_sha256_pow_is_the_block_id() {
+        let header = header(7);
+        assert_eq!(Sha256Pow.hash(&header), header.hash());
+        let digest: Sha256Hash = Sha256::digest(header.pow_bytes()).into();
+        assert_eq!(digest, header.hash());
+        let legacy = BlockHeader { version: 0, ..header };
+        let digest: Sha256Hash = Sha256::digest(legacy.pow_bytes()).into();
+        assert_eq!(digest, legacy.hash());
+    }
+
+    #[test]
+    fn test_algorithms_disagree_and_are_selected_by_name() {
+        let header = header(7);
+        assert_ne!(FastPow.hash(&header), Sha256Pow.hash(&header));
+        assert_ne!(FastPow.hash(&header), FastPow.hash(&self::header(8)));
+
+        // Work done under one algorithm proves nothing under the other
+        let fast_only = (0..).map(self::header).find(|h| FastPow.meets_target(h) && !Sha256Pow.meets_target(h));
+        assert!(fast_only.is_some());
+
+        assert_eq!(pow_algorithm("SHA256").unwrap(), DEFAULT_POW);
+        assert_eq!(pow_algorithm(" fast ").unwrap().name(), "fast");
+        assert!(pow_algorithm("scrypt").is_none());
+    }
+}
@@ -1,10 +1,24 @@
 use crate::crypto::Address;
 use crate::error::ChainError;
-use crate::geometry::{Coord, Point, Triangle, GEOMETRIC_TOLERANCE};
-use crate::transaction::{Transaction, TransferTx};
-use std::collections::HashMap;
-
-use super::chain::Sha256Hash;
+use crate::geometry::{
+    coord_checked_add, coord_checked_sub, coord_checked_sum, Coord, Point, Triangle,
+    TriangleId, GEOMETRIC_TOLERANCE,
+};
+use crate::transaction::{CoinbaseTx, Transaction};
+use std::collections::{HashMap, HashSet};
+
+use super::block::Sha256Hash;
+use super::undo::BlockUndo;
+
+/// An address whose cached balance disagrees with its unspent triangles
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceMismatch {
+    pub address: Address,
+    /// Balance in `address_balances`
+    pub stored: Coord,
+    /// Sum of the effective values of the address's UTXOs
+    pub computed: Coord,
+}
 
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct TriangleState {
@@ -24,94 +38,201 @@ impl TriangleState {
         }
     }
 
+    /// Addresses whose entry in `address_balances` differs from what
+    /// [`rebuild_address_balances`](Self::rebuild_address_balances) would
+    /// produce, sorted by address. A missing entry counts as zero.
+    pub fn balance_mismatches(&self) -> Vec<BalanceMismatch> {
+        let mut computed: HashMap<Address, Coord> = HashMap::new();
+        for triangle in self.utxo_set.values() {
+            *computed.entry(triangle.owner).or_insert(Coord::from_num(0)) += triangle.effective_value();
+        }
+        let addresses: HashSet<&Address> = self.address_balances.keys().chain(computed.keys()).collect();
+        let mut mismatches: Vec<_> = addresses
+            .into_iter()
+            .map(|address| BalanceMismatch {
+                address: *address,
+                stored: self.get_balance(address),
+                computed: computed.get(address).copied().unwrap_or(Coord::from_num(0)),
+            })
+            .filter(|m| m.stored != m.computed)
+            .collect();
+        mismatches.sort_by_key(|m| m.address);
+        mismatches
+    }
+
     pub fn get_balance(&self, address: &Address) -> Coord {
         *self.address_balances.get(address).unwrap_or(&Coord::from_num(0))
     }
 
+    /// Credit `amount` to `address`, failing on overflow.
+    fn credit(&mut self, address: Address, amount: Coord) -> Result<(), ChainError> {
+        let balance = self.address_balances.entry(address).or_insert(Coord::from_num(0));
+        *balance = coord_checked_add(*balance, amount)?;
+        Ok(())
+    }
+
+    /// Debit `amount` from `address`, flooring the balance at zero.
+    fn debit(&mut self, address: Address, amount: Coord) -> Result<(), ChainError> {
+        let balance = self.address_balances.entry(address).or_insert(Coord::from_num(0));
+        *balance = coord_checked_sub(*balance, amount)?.max(Coord::from_num(0));
+        Ok(())
+    }
+
+    /// Apply `tx` to the UTXO set and balances. All value arithmetic is
+    /// overflow-checked and performed before the state is touched, so a
+    /// failing transaction leaves the UTXO set unchanged.
     pub fn apply_transaction(&mut self, tx: &Transaction, _block_height: u64) -> Result<(), ChainError> {
         match tx {
             Transaction::Coinbase(tx) => {
-                let new_triangle = Triangle::new(
-                    Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
-                    Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
-                    Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
-                    None,
-                    tx.beneficiary_address,
-                ).with_effective_value(tx.reward_area);
-
                 let tx_hash = Transaction::Coinbase(tx.clone()).hash();
-                self.utxo_set.insert(tx_hash, new_triangle);
-                *self.address_balances.entry(tx.beneficiary_address).or_insert(Coord::from_num(0)) += tx.reward_area;
+                // The beneficiary's triangle is keyed by the txid, each
+                // reward output's by its own hash
+                for (index, (address, amount)) in tx.payouts()?.into_iter().enumerate() {
+                    let hash = match index {
+                        0 => tx_hash,
+                        _ => CoinbaseTx::output_hash(&tx_hash, index - 1),
+                    };
+                    let new_triangle = Triangle::new(
+                        Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
+                        Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
+                        Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
+                        None,
+                        address,
+                    ).with_effective_value(amount).with_id(TriangleId::root(hash));
+                    self.credit(address, amount)?;
+                    self.utxo_set.insert(hash, new_triangle);
+                }
             }
             Transaction::Transfer(tx) => {
                 let input_hash = tx.input_hash;
-                let consumed_triangle = self.utxo_set.remove(&input_hash).ok_or_else(|| ChainError::TriangleNotFound(format!("Input UTXO not found for transfer: {}", hex::encode(input_hash))))?;
+                let consumed_triangle = self.utxo_set.get(&input_hash).cloned().ok_or(ChainError::UnknownUtxo(input_hash))?;
 
                 if consumed_triangle.owner != tx.sender {
-                    self.utxo_set.insert(input_hash, consumed_triangle.clone());
                     return Err(ChainError::InvalidTransaction(format!("Sender {} does not own input UTXO (owned by {})", hex::encode(tx.sender), hex::encode(consumed_triangle.owner))));
                 }
 
-                let input_value = consumed_triangle.effective_value();
-                let total_spent = tx.amount + tx.fee_area;
-                let remaining_value = input_value - total_spent;
+                let input_value = consumed_triangle.checked_effective_value()?;
+                let total_spent = coord_checked_add(tx.amount, tx.sender_fee())?;
+                let remaining_value = coord_checked_sub(input_value, total_spent)?;
+                if remaining_value < Coord::from_num(0) {
+                    return Err(ChainError::InsufficientValue { available: input_value, required: total_spent });
+                }
 
-                let sender_balance = self.address_balances.entry(tx.sender).or_insert(Coord::from_num(0));
-                *sender_balance -= input_value;
-                if *sender_balance < Coord::from_num(0) { *sender_balance = Coord::from_num(0); }
+                // The sponsor's triangle pays the fee; what is left returns
+                // to the sponsor
+                let sponsor = match &tx.sponsor {
+                    Some(sponsor) => {
+                        let triangle = self.utxo_set.get(&sponsor.input_hash).cloned().ok_or(ChainError::UnknownUtxo(sponsor.input_hash))?;
+                        if triangle.owner != sponsor.payer {
+                            return Err(ChainError::InvalidTransaction(format!("Fee sponsor {} does not own its triangle (owned by {})", hex::encode(sponsor.payer), hex::encode(triangle.owner))));
+                        }
+                        let value = triangle.checked_effective_value()?;
+                        let remaining = coord_checked_sub(value, tx.fee_area)?;
+                        if remaining < Coord::from_num(0) {
+                            return Err(ChainError::InsufficientValue { available: value, required: tx.fee_area });
+                        }
+                        Some((sponsor, triangle, value, remaining))
+                    }
+                    None => None,
+                };
+
+                self.utxo_set.remove(&input_hash);
+                self.debit(tx.sender, input_value)?;
 
                 let new_owner_triangle = consumed_triangle.clone().change_owner(tx.new_owner).with_effective_value(tx.amount);
                 let tx_hash = Transaction::Transfer(tx.clone()).hash();
                 self.utxo_set.insert(tx_hash, new_owner_triangle);
-                *self.address_balances.entry(tx.new_owner).or_insert(Coord::from_num(0)) += tx.amount;
+                self.credit(tx.new_owner, tx.amount)?;
 
                 if remaining_value > GEOMETRIC_TOLERANCE {
-                    let change_tx = Transaction::Transfer(TransferTx {
-                        input_hash: tx_hash,
-                        new_owner: tx.sender,
-                        sender: tx.sender,
-                        amount: remaining_value,
-                        fee_area: Coord::from_num(0),
-                        nonce: tx.nonce + 1,
-                        signature: None,
-                        public_key: None,
-                        memo: Some("Change".to_string()),
-                    });
+                    let change_tx = tx.change_transaction(remaining_value);
 
                     let change_hash = change_tx.hash();
                     let change_triangle = consumed_triangle.change_owner(tx.sender).with_effective_value(remaining_value);
                     self.utxo_set.insert(change_hash, change_triangle);
-                    *self.address_balances.entry(tx.sender).or_insert(Coord::from_num(0)) += remaining_value;
+                    self.credit(tx.sender, remaining_value)?;
+                }
+
+                if let Some((sponsor, triangle, value, remaining)) = sponsor {
+                    self.utxo_set.remove(&sponsor.input_hash);
+                    self.debit(sponsor.payer, value)?;
+                    if remaining > GEOMETRIC_TOLERANCE {
+                        if let Some(change_tx) = tx.sponsor_change_transaction(remaining) {
+                            self.utxo_set.insert(change_tx.hash(), triangle.with_effective_value(remaining));
+                            self.credit(sponsor.payer, remaining)?;
+                        }
+                    }
                 }
             }
             Transaction::Subdivision(tx) => {
                 let input_hash = tx.parent_hash;
-                let consumed_triangle = self.utxo_set.remove(&input_hash).ok_or_else(|| ChainError::TriangleNotFound(format!("Parent UTXO for subdivision not found: {}", hex::encode(input_hash))))?;
+                let consumed_triangle = self.utxo_set.get(&input_hash).ok_or(ChainError::UnknownUtxo(input_hash))?;
 
                 if consumed_triangle.owner != tx.owner_address {
-                    self.utxo_set.insert(input_hash, consumed_triangle.clone());
                     return Err(ChainError::InvalidTransaction(format!("Subdivision owner {} does not match parent triangle owner {}", hex::encode(tx.owner_address), hex::encode(consumed_triangle.owner))));
                 }
 
-                let parent_value = consumed_triangle.effective_value();
-                let owner_balance = self.address_balances.entry(tx.owner_address).or_insert(Coord::from_num(0));
-                *owner_balance -= parent_value;
-                if *owner_balance < Coord::from_num(0) { *owner_balance = Coord::from_num(0); }
+                let parent_value = consumed_triangle.checked_effective_value()?;
+                let child_values = tx
+                    .children
+                    .iter()
+                    .map(|c| c.checked_effective_value())
+                    .collect::<Result<Vec<Coord>, ChainError>>()?;
+                let total_child_value = coord_checked_sum(child_values.iter().copied())?;
+                let expected_value = coord_checked_sub(parent_value, tx.fee_area)?;
 
-                let total_child_value: Coord = tx.children.iter().map(|c| c.effective_value()).sum();
-                let expected_value = parent_value - tx.fee_area;
-
-                if (total_child_value - expected_value).abs() > GEOMETRIC_TOLERANCE {
-                    self.utxo_set.insert(input_hash, consumed_triangle);
-                    *self.address_balances.entry(tx.owner_address).or_insert(Coord::from_num(0)) += parent_value;
+                if coord_checked_sub(total_child_value, expected_value)?.abs() > GEOMETRIC_TOLERANCE {
                     return Err(ChainError::InvalidTransaction(format!("Value mismatch in subdivision: parent ({}) - fee ({}) != children total ({}).", parent_value, tx.fee_area, total_child_value)));
                 }
 
-                for child in &tx.children {
+                self.utxo_set.remove(&input_hash);
+                self.debit(tx.owner_address, parent_value)?;
+                for (child, value) in tx.children.iter().zip(child_values) {
                     self.utxo_set.insert(child.hash(), child.clone());
-                    *self.address_balances.entry(tx.owner_address).or_insert(Coord::from_num(0)) += child.effective_value();
+                    self.credit(tx.owner_address, value)?;
                 }
             }
+            Transaction::Data(tx) => {
+                let consumed_triangle = self.utxo_set.get(&tx.input_hash).cloned().ok_or(ChainError::UnknownUtxo(tx.input_hash))?;
+                if consumed_triangle.owner != tx.owner {
+                    return Err(ChainError::InvalidTransaction(format!("Data transaction owner {} does not own its fee triangle (owned by {})", hex::encode(tx.owner), hex::encode(consumed_triangle.owner))));
+                }
+                let input_value = consumed_triangle.checked_effective_value()?;
+                let remaining_value = coord_checked_sub(input_value, tx.fee_area)?;
+                if remaining_value < Coord::from_num(0) {
+                    return Err(ChainError::InsufficientValue { available: input_value, required: tx.fee_area });
+                }
+
+                self.utxo_set.remove(&tx.input_hash);
+                self.debit(tx.owner, input_value)?;
+                // The change is keyed by the txid
+                if remaining_value > GEOMETRIC_TOLERANCE {
+                    let tx_hash = Transaction::Data(tx.clone()).hash();
+                    self.utxo_set.insert(tx_hash, consumed_triangle.with_effective_value(remaining_value));
+                    self.credit(tx.owner, remaining_value)?;
+                }
+            }
+            // Key announcements and names are indexed by the chain, not the
+            // UTXO set
+            Transaction::Announce(_) | Transaction::Register(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Reverse the block `undo` describes: drop the UTXOs it created and
+    /// restore the triangles it spent. Outputs created and spent within the
+    /// block did not exist before it and stay absent.
+    pub fn revert(&mut self, undo: &BlockUndo) -> Result<(), ChainError> {
+        let spent: HashSet<Sha256Hash> = undo.spent.iter().map(|(hash, _)| *hash).collect();
+        let created: HashSet<Sha256Hash> = undo.created.iter().copied().collect();
+        for hash in undo.created.iter().filter(|hash| !spent.contains(*hash)) {
+            if let Some(triangle) = self.utxo_set.remove(hash) {
+                self.debit(triangle.owner, triangle.checked_effective_value()?)?;
+            }
+        }
+        for (hash, triangle) in undo.spent.iter().filter(|(hash, _)| !created.contains(hash)) {
+            self.credit(triangle.owner, triangle.checked_effective_value()?)?;
+            self.utxo_set.insert(*hash, triangle.clone());
         }
         Ok(())
     }
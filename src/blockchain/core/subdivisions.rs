@@ -0,0 +1,137 @@
+//! Subdivision lineage index
+//!
+//! A subdivision spends a triangle and creates its three children, each
+//! naming the parent in `parent_hash`. Once the parent is spent nothing in
+//! the UTXO set leads from it to its children any more, and children that
+//! were themselves subdivided are gone from it too. The index keeps the
+//! children of every subdivided output by parent hash, so a triangle's whole
+//! subdivision tree can be walked down from its root. Like the address index
+//! it is derived from the blocks and rebuilt on load.
+
+use crate::geometry::Triangle;
+use crate::transaction::Transaction;
+use std::collections::HashMap;
+
+use super::block::{Block, Sha256Hash};
+
+#[derive(Debug, Clone, Default)]
+pub struct SubdivisionIndex {
+    /// Children created by subdividing an output, keyed by its hash
+    children: HashMap<Sha256Hash, Vec<(Sha256Hash, Triangle)>>,
+    /// Parent of every output created by a subdivision
+    parents: HashMap<Sha256Hash, Sha256Hash>,
+}
+
+impl SubdivisionIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rebuild(blocks: &[Block]) -> Self {
+        let mut index = Self::new();
+        for block in blocks {
+            index.insert(block);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            if let Transaction::Subdivision(subdivision) = tx {
+                // Children with equal hashes, such as those of a degenerate
+                // triangle, are one output in the UTXO set
+                let mut children: Vec<(Sha256Hash, Triangle)> = Vec::new();
+                for child in &subdivision.children {
+                    let hash = child.hash();
+                    if !children.iter().any(|(existing, _)| *existing == hash) {
+                        children.push((hash, child.clone()));
+                        self.parents.insert(hash, subdivision.parent_hash);
+                    }
+                }
+                self.children.insert(subdivision.parent_hash, children);
+            }
+        }
+    }
+
+    /// Undo [`insert`](Self::insert) of the same block
+    pub fn remove(&mut self, block: &Block) {
+        for tx in &block.transactions {
+            if let Transaction::Subdivision(subdivision) = tx {
+                for (hash, _) in self.children.remove(&subdivision.parent_hash).unwrap_or_default() {
+                    self.parents.remove(&hash);
+                }
+            }
+        }
+    }
+
+    /// Outputs created by subdividing `hash`, empty if it was never
+    /// subdivided
+    pub fn children(&self, hash: &Sha256Hash) -> &[(Sha256Hash, Triangle)] {
+        self.children.get(hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// The output `hash` was subdivided from
+    pub fn parent(&self, hash: &Sha256Hash) -> Option<Sha256Hash> {
+        self.parents.get(hash).copied()
+    }
+
+    /// The triangle of an output created by a subdivision, spent or not
+    pub fn triangle(&self, hash: &Sha256Hash) -> Option<&Triangle> {
+        let parent = self.parents.get(hash)?;
+        self.children(parent).iter().find(|(child, _)| child == hash).map(|(_, triangle)| triangle)
+    }
+
+    /// Outputs that were subdivided
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Coord, Point};
+    use crate::transaction::SubdivisionTx;
+
+    fn subdivide(hash: Sha256Hash, triangle: &Triangle) -> (Transaction, [Sha256Hash; 3]) {
+        let children = triangle.subdivide();
+        let hashes = [children[0].hash(), children[1].hash(), children[2].hash()];
+        let tx = SubdivisionTx::new(hash, children.to_vec(), triangle.owner, Coord::from_num(0), 1);
+        (Transaction::Subdivision(tx), hashes)
+    }
+
+    #[test]
+    fn test_children_follow_applied_and_reverted_blocks() {
+        let root = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(8), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(8)),
+            None,
+            [1u8; 32],
+        );
+        let (first, children) = subdivide([7u8; 32], &root);
+        let child = root.subdivide()[1].clone();
+        let (second, grandchildren) = subdivide(children[1], &child);
+        let blocks = [
+            Block::new(1, [0; 32], 1, vec![first]),
+            Block::new(2, [0; 32], 1, vec![second]),
+        ];
+        let mut index = SubdivisionIndex::rebuild(&blocks);
+
+        assert_eq!(index.len(), 2);
+        let hashes: Vec<_> = index.children(&[7u8; 32]).iter().map(|(hash, _)| *hash).collect();
+        assert_eq!(hashes, children);
+        assert_eq!(index.parent(&grandchildren[2]), Some(children[1]));
+        assert_eq!(index.triangle(&children[1]), Some(&child));
+        assert!(index.children(&children[0]).is_empty());
+
+        index.remove(&blocks[1]);
+        assert!(index.children(&children[1]).is_empty());
+        assert_eq!(index.parent(&grandchildren[2]), None);
+        assert_eq!(index.parent(&children[1]), Some([7u8; 32]));
+    }
+}
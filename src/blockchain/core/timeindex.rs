@@ -0,0 +1,157 @@
+//! Block timestamp index for date range queries
+//!
+//! Block timestamps are not strictly increasing, so "all blocks in March"
+//! cannot be answered by a binary search over heights. The index keeps
+//! (timestamp, height) pairs ordered by time; a range query walks only the
+//! matching part and returns heights in chain order. Transactions are found
+//! through the blocks that contain them. Like the name registry it is
+//! derived from the blocks and rebuilt on load.
+
+use chrono::{DateTime, NaiveDate, NaiveTime};
+use std::collections::BTreeSet;
+
+use super::block::Block;
+
+/// Inclusive range of block timestamps, in milliseconds like
+/// [`BlockHeader::timestamp`](super::block::BlockHeader::timestamp)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+impl TimeRange {
+    pub const ALL: TimeRange = TimeRange { from: 0, to: u64::MAX };
+
+    /// Range between optional bounds, each an RFC 3339 time, a `YYYY-MM-DD`
+    /// date (UTC; as `to` it includes the whole day) or Unix milliseconds.
+    /// Errors are messages for the user.
+    pub fn parse(from: Option<&str>, to: Option<&str>) -> Result<Self, String> {
+        let range = TimeRange {
+            from: from.map(|text| parse_bound(text, false)).transpose()?.unwrap_or(0),
+            to: to.map(|text| parse_bound(text, true)).transpose()?.unwrap_or(u64::MAX),
+        };
+        if range.from > range.to {
+            return Err(format!("Time range starts after it ends ({} > {})", range.from, range.to));
+        }
+        Ok(range)
+    }
+
+    pub fn contains(&self, timestamp: u64) -> bool {
+        (self.from..=self.to).contains(&timestamp)
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+}
+
+fn parse_bound(text: &str, end: bool) -> Result<u64, String> {
+    if let Ok(millis) = text.parse::<u64>() {
+        return Ok(millis);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return u64::try_from(time.timestamp_millis()).map_err(|_| invalid_bound(text));
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| invalid_bound(text))?;
+    let time = if end {
+        NaiveTime::from_hms_milli_opt(23, 59, 59, 999).expect("valid time")
+    } else {
+        NaiveTime::MIN
+    };
+    u64::try_from(date.and_time(time).and_utc().timestamp_millis()).map_err(|_| invalid_bound(text))
+}
+
+fn invalid_bound(text: &str) -> String {
+    format!(
+        "'{}' is not a date (YYYY-MM-DD), RFC 3339 time or Unix timestamp in milliseconds",
+        text
+    )
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TimeIndex {
+    /// (timestamp, height) of every block on the chain
+    entries: BTreeSet<(u64, u64)>,
+}
+
+impl TimeIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rebuild(blocks: &[Block]) -> Self {
+        let mut index = Self::new();
+        for block in blocks {
+            index.insert(block);
+        }
+        index
+    }
+
+    pub fn insert(&mut self, block: &Block) {
+        self.entries.insert((block.header.timestamp, block.header.height));
+    }
+
+    pub fn remove(&mut self, block: &Block) {
+        self.entries.remove(&(block.header.timestamp, block.header.height));
+    }
+
+    /// Heights of the blocks with a timestamp in `range`, lowest first
+    pub fn heights_in(&self, range: TimeRange) -> Vec<u64> {
+        let mut heights: Vec<u64> = self
+            .entries
+            .range((range.from, 0)..=(range.to, u64::MAX))
+            .map(|&(_, height)| height)
+            .collect();
+        heights.sort_unstable();
+        heights
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u64, timestamp: u64) -> Block {
+        let mut block = Block::new(height, [0; 32], 1, Vec::new());
+        block.header.timestamp = timestamp;
+        block
+    }
+
+    #[test]
+    fn test_range_queries_follow_timestamps_not_heights() {
+        // Height 2 is stamped before height 1
+        let blocks = [block(0, 1_000), block(1, 5_000), block(2, 4_000), block(3, 9_000)];
+        let mut index = TimeIndex::rebuild(&blocks);
+        assert_eq!(index.heights_in(TimeRange::ALL), vec![0, 1, 2, 3]);
+        assert_eq!(index.heights_in(TimeRange { from: 3_000, to: 5_000 }), vec![1, 2]);
+        assert!(index.heights_in(TimeRange { from: 9_001, to: 10_000 }).is_empty());
+
+        index.remove(&blocks[3]);
+        assert_eq!(index.heights_in(TimeRange { from: 5_000, to: u64::MAX }), vec![1]);
+    }
+
+    #[test]
+    fn test_parse_bounds() {
+        let march = TimeRange::parse(Some("2026-03-01"), Some("2026-03-31")).unwrap();
+        assert_eq!(march.from, 1_772_323_200_000);
+        assert_eq!(march.to, 1_775_001_599_999);
+        assert!(march.contains(1_772_323_200_000) && !march.contains(1_775_001_600_000));
+
+        let exact = TimeRange::parse(Some("2026-03-01T12:00:00+01:00"), Some("1775000000000")).unwrap();
+        assert_eq!(exact.from, 1_772_362_800_000);
+        assert_eq!(exact.to, 1_775_000_000_000);
+
+        assert!(TimeRange::parse(None, None).unwrap().is_all());
+        assert!(TimeRange::parse(Some("March"), None).is_err());
+        assert!(TimeRange::parse(Some("2026-04-01"), Some("2026-03-01")).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Chain tips seen besides the active one
+//!
+//! A node only keeps the blocks of its best chain, so competing branches
+//! used to vanish without a trace once a reorganization was decided. The
+//! tracker remembers the tip of every branch the node has weighed against
+//! its own: chains it switched away from, branches with less work and
+//! branches that failed validation. Like Bitcoin's `getchaintips` this lets
+//! operators see whether the network is diverging. Tips are kept in memory
+//! only and reset on restart.
+
+use serde::Serialize;
+
+use super::block::{Block, Sha256Hash};
+
+/// Fork tips remembered at most; the oldest ones are dropped first
+pub const MAX_FORK_TIPS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TipStatus {
+    /// Tip of the chain the node follows
+    Active,
+    /// Fully validated branch, e.g. our old chain after a reorganization
+    ValidFork,
+    /// Branch that was never validated because it has less work than ours
+    ValidHeaders,
+    /// Branch containing a block that failed validation
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: Sha256Hash,
+    /// Blocks between the tip and the point where it joins the active
+    /// chain; zero for the active tip
+    pub branch_len: u64,
+    pub status: TipStatus,
+}
+
+impl ChainTip {
+    /// Tip of `branch`, a contiguous run of blocks off the active chain
+    pub fn of_branch(branch: &[Block], status: TipStatus) -> Option<Self> {
+        branch.last().map(|tip| ChainTip {
+            height: tip.header.height,
+            hash: tip.hash(),
+            branch_len: branch.len() as u64,
+            status,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ForkTracker {
+    tips: Vec<ChainTip>,
+}
+
+impl ForkTracker {
+    /// Remember `tip`, replacing an earlier entry for the same block unless
+    /// that one was already validated
+    pub fn record(&mut self, tip: ChainTip) {
+        let validated = self
+            .tips
+            .iter()
+            .any(|known| known.hash == tip.hash && known.status != TipStatus::ValidHeaders);
+        if validated && tip.status == TipStatus::ValidHeaders {
+            return;
+        }
+        self.tips.retain(|known| known.hash != tip.hash);
+        self.tips.push(tip);
+        if self.tips.len() > MAX_FORK_TIPS {
+            self.tips.remove(0);
+        }
+    }
+
+    pub fn tips(&self) -> &[ChainTip] {
+        &self.tips
+    }
+}
+
+impl Extend<ChainTip> for ForkTracker {
+    fn extend<I: IntoIterator<Item = ChainTip>>(&mut self, tips: I) {
+        for tip in tips {
+            self.record(tip);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Per-block undo records
+//!
+//! Blocks only say what they spend, not what the spent triangles held, so
+//! disconnecting one used to mean replaying the chain from genesis. An undo
+//! record keeps exactly what a block changed: the triangles it consumed, the
+//! UTXOs it created, the keys it revealed first, the names it registered
+//! and the difficulty before it. Reverting the tip with it takes time proportional to the block.
+
+use crate::crypto::Address;
+use crate::error::ChainError;
+use crate::geometry::{coord_checked_add, coord_checked_sub, Triangle, GEOMETRIC_TOLERANCE};
+use crate::transaction::{CoinbaseTx, Transaction};
+use serde::{Deserialize, Serialize};
+
+use super::block::Sha256Hash;
+use super::names::NameRecord;
+use super::state::TriangleState;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockUndo {
+    pub height: u64,
+    /// Triangles the block consumed, with their full data
+    pub spent: Vec<(Sha256Hash, Triangle)>,
+    /// UTXOs the block created
+    pub created: Vec<Sha256Hash>,
+    /// Addresses whose public key the block revealed first
+    pub revealed_keys: Vec<Address>,
+    /// Retarget difficulty before the block was applied
+    pub difficulty: u32,
+    /// Names the block registered, with the records they replaced
+    #[serde(default)]
+    pub names: Vec<(String, Option<NameRecord>)>,
+}
+
+impl BlockUndo {
+    pub fn new(height: u64, difficulty: u32) -> Self {
+        BlockUndo {
+            height,
+            spent: Vec::new(),
+            created: Vec::new(),
+            revealed_keys: Vec::new(),
+            difficulty,
+            names: Vec::new(),
+        }
+    }
+
+    /// Record `tx`, given the state just before it is applied
+    pub fn record(&mut self, tx: &Transaction, state: &TriangleState) -> Result<(), ChainError> {
+        let inputs = spent_inputs(tx, state);
+        self.created.extend(created_outputs(tx, &inputs)?.into_iter().map(|(output, _)| output));
+        self.spent.extend(inputs);
+        Ok(())
+    }
+}
+
+/// The outputs `tx` spends and their triangles as found in `state`, which
+/// must be the state just before `tx` is applied
+pub fn spent_inputs(tx: &Transaction, state: &TriangleState) -> Vec<(Sha256Hash, Triangle)> {
+    tx.inputs()
+        .into_iter()
+        .filter_map(|input| state.utxo_set.get(&input).map(|triangle| (input, triangle.clone())))
+        .collect()
+}
+
+/// UTXO hashes created by `tx` spending `inputs`, each with the input it
+/// was split from (`None` for coinbase rewards), mirroring
+/// [`TriangleState::apply_transaction`]
+pub fn created_outputs(
+    tx: &Transaction,
+    inputs: &[(Sha256Hash, Triangle)],
+) -> Result<Vec<(Sha256Hash, Option<Sha256Hash>)>, ChainError> {
+    let input = |hash: &Sha256Hash| inputs.iter().find(|(input, _)| input == hash).map(|(_, triangle)| triangle);
+    Ok(match tx {
+        Transaction::Coinbase(coinbase) => {
+            let txid = tx.hash();
+            std::iter::once(txid)
+                .chain((0..coinbase.outputs.len()).map(|index| CoinbaseTx::output_hash(&txid, index)))
+                .map(|hash| (hash, None))
+                .collect()
+        }
+        Transaction::Transfer(transfer) => {
+            let parent = Some(transfer.input_hash);
+            let mut outputs = vec![(tx.hash(), parent)];
+            if let Some(triangle) = input(&transfer.input_hash) {
+                let remaining = coord_checked_sub(
+                    triangle.checked_effective_value()?,
+                    coord_checked_add(transfer.amount, transfer.sender_fee())?,
+                )?;
+                if remaining > GEOMETRIC_TOLERANCE {
+                    outputs.push((transfer.change_transaction(remaining).hash(), parent));
+                }
+            }
+            if let Some(sponsor) = &transfer.sponsor {
+                if let Some(triangle) = input(&sponsor.input_hash) {
+                    let remaining = coord_checked_sub(triangle.checked_effective_value()?, transfer.fee_area)?;
+                    if remaining > GEOMETRIC_TOLERANCE {
+                        let change = transfer.sponsor_change_transaction(remaining).map(|change| change.hash());
+                        outputs.extend(change.map(|change| (change, Some(sponsor.input_hash))));
+                    }
+                }
+            }
+            outputs
+        }
+        Transaction::Subdivision(subdivision) => subdivision
+            .children
+            .iter()
+            .map(|child| (child.hash(), Some(subdivision.parent_hash)))
+            .collect(),
+        Transaction::Data(data) => match input(&data.input_hash) {
+            Some(triangle) if coord_checked_sub(triangle.checked_effective_value()?, data.fee_area)? > GEOMETRIC_TOLERANCE => {
+                vec![(tx.hash(), Some(data.input_hash))]
+            }
+            _ => Vec::new(),
+        },
+        Transaction::Announce(_) | Transaction::Register(_) => Vec::new(),
+    })
+}
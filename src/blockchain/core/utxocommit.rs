@@ -0,0 +1,292 @@
+//! UTXO set commitment and address balance proofs
+//!
+//! The UTXO set is committed to by a Merkle tree over its outputs sorted by
+//! owner, then by hash, so the outputs of one address are consecutive
+//! leaves. A [`BalanceProof`] carries those leaves with their branches plus
+//! the leaves right before and after them, which belong to other owners:
+//! the verifier learns both that the address owns the listed triangles and
+//! that it owns nothing else, without the rest of the set.
+//!
+//! The commitment is not yet part of the block header. Until it is, a light
+//! client or auditor must get the commitment of a height from sources it
+//! trusts, e.g. several independent nodes, and compare it with the one a
+//! proof was built against.
+
+use crate::crypto::Address;
+use crate::error::ChainError;
+use crate::geometry::{coord_checked_add, Coord, Triangle};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::block::Sha256Hash;
+use super::state::TriangleState;
+
+/// Domain separator of the commitment over the tree root
+const COMMITMENT_DOMAIN: &[u8] = b"TrinityChain utxo commitment";
+
+/// Hash of one output as a tree leaf
+fn leaf_hash(hash: &Sha256Hash, triangle: &Triangle) -> Sha256Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(hash);
+    hasher.update(triangle.hash());
+    hasher.update(triangle.effective_value().to_bits().to_le_bytes());
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Sha256Hash, right: &Sha256Hash) -> Sha256Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Commitment to a tree of `leaf_count` leaves with root `root`
+fn commit(leaf_count: u64, root: &Sha256Hash) -> Sha256Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(COMMITMENT_DOMAIN);
+    hasher.update(leaf_count.to_le_bytes());
+    hasher.update(root);
+    hasher.finalize().into()
+}
+
+/// Merkle tree over the UTXO set. A level of odd width carries its last
+/// node up unpaired.
+#[derive(Debug, Clone)]
+pub struct UtxoTree {
+    /// Outputs in leaf order
+    leaves: Vec<(Sha256Hash, Triangle)>,
+    /// Node hashes, leaves first, ending with the root
+    levels: Vec<Vec<Sha256Hash>>,
+}
+
+impl UtxoTree {
+    pub fn build(state: &TriangleState) -> Self {
+        let mut leaves: Vec<(Sha256Hash, Triangle)> =
+            state.utxo_set.iter().map(|(hash, triangle)| (*hash, triangle.clone())).collect();
+        leaves.sort_by(|(a_hash, a), (b_hash, b)| a.owner.cmp(&b.owner).then(a_hash.cmp(b_hash)));
+
+        let mut levels = vec![leaves.iter().map(|(hash, triangle)| leaf_hash(hash, triangle)).collect::<Vec<_>>()];
+        while levels.last().is_some_and(|level| level.len() > 1) {
+            let next = levels
+                .last()
+                .map(|level| {
+                    level
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [left, right] => node_hash(left, right),
+                            [single] => *single,
+                            _ => unreachable!("chunks of two"),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            levels.push(next);
+        }
+        UtxoTree { leaves, levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Commitment to the whole set: its size and the tree root
+    pub fn commitment(&self) -> Sha256Hash {
+        let root = self.levels.last().and_then(|level| level.first()).copied().unwrap_or([0u8; 32]);
+        commit(self.leaves.len() as u64, &root)
+    }
+
+    fn prove_leaf(&self, index: usize) -> ProvenOutput {
+        let mut branch = Vec::new();
+        let mut position = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                branch.push(level[sibling]);
+            }
+            position /= 2;
+        }
+        let (hash, triangle) = self.leaves[index].clone();
+        ProvenOutput {
+            index: index as u64,
+            hash,
+            triangle,
+            branch,
+        }
+    }
+
+    /// Proof of every output `address` owns, and that it owns no others
+    pub fn balance_proof(&self, address: Address, height: u64, block_hash: Sha256Hash) -> BalanceProof {
+        let start = self.leaves.partition_point(|(_, triangle)| triangle.owner < address);
+        let end = self.leaves.partition_point(|(_, triangle)| triangle.owner <= address);
+        BalanceProof {
+            address,
+            height,
+            block_hash,
+            leaf_count: self.leaves.len() as u64,
+            commitment: self.commitment(),
+            outputs: (start..end).map(|index| self.prove_leaf(index)).collect(),
+            before: start.checked_sub(1).map(|index| self.prove_leaf(index)),
+            after: (end < self.leaves.len()).then(|| self.prove_leaf(end)),
+        }
+    }
+}
+
+/// An output and its branch up to the tree root
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvenOutput {
+    /// Position among the leaves
+    pub index: u64,
+    /// UTXO hash of the output
+    pub hash: Sha256Hash,
+    pub triangle: Triangle,
+    /// Sibling hashes from the leaf up; levels where the node is unpaired
+    /// contribute none
+    pub branch: Vec<Sha256Hash>,
+}
+
+impl ProvenOutput {
+    /// Root of a tree of `leaf_count` leaves this output's branch leads to
+    fn root(&self, leaf_count: u64) -> Result<Sha256Hash, ChainError> {
+        if self.index >= leaf_count {
+            return Err(ChainError::CryptoError(format!("Leaf {} is outside the {} committed outputs", self.index, leaf_count)));
+        }
+        let mut node = leaf_hash(&self.hash, &self.triangle);
+        let mut siblings = self.branch.iter();
+        let (mut position, mut width) = (self.index, leaf_count);
+        while width > 1 {
+            let sibling = position ^ 1;
+            if sibling < width {
+                let sibling_hash = siblings
+                    .next()
+                    .ok_or_else(|| ChainError::CryptoError("Merkle branch is too short".to_string()))?;
+                node = if position % 2 == 0 { node_hash(&node, sibling_hash) } else { node_hash(sibling_hash, &node) };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+        if siblings.next().is_some() {
+            return Err(ChainError::CryptoError("Merkle branch is too long".to_string()));
+        }
+        Ok(node)
+    }
+}
+
+/// Outputs an address owns under a UTXO commitment, and the neighbouring
+/// leaves showing it owns no others
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceProof {
+    pub address: Address,
+    /// Height of the block whose UTXO set is committed to
+    pub height: u64,
+    pub block_hash: Sha256Hash,
+    /// Outputs in the whole set
+    pub leaf_count: u64,
+    pub commitment: Sha256Hash,
+    /// The address's outputs, in leaf order
+    pub outputs: Vec<ProvenOutput>,
+    /// Leaf right before the first output, if any
+    pub before: Option<ProvenOutput>,
+    /// Leaf right after the last output, if any
+    pub after: Option<ProvenOutput>,
+}
+
+impl BalanceProof {
+    /// Check the proof against `commitment` and return the address's
+    /// balance
+    pub fn verify(&self, commitment: &Sha256Hash) -> Result<Coord, ChainError> {
+        let invalid = |reason: &str| Err(ChainError::CryptoError(format!("Invalid balance proof: {}", reason)));
+        if self.commitment != *commitment {
+            return invalid("it was built against another commitment");
+        }
+
+        let proven = self.before.iter().chain(&self.outputs).chain(&self.after);
+        for output in proven {
+            if commit(self.leaf_count, &output.root(self.leaf_count)?) != *commitment {
+                return invalid("an output is not in the committed set");
+            }
+        }
+
+        // The outputs fill exactly the leaves between the neighbours
+        let start = self.before.as_ref().map_or(0, |before| before.index + 1);
+        let end = self.after.as_ref().map_or(self.leaf_count, |after| after.index);
+        if end < start || self.outputs.len() as u64 != end - start {
+            return invalid("outputs are missing between the neighbouring leaves");
+        }
+        if self.outputs.iter().zip(start..).any(|(output, index)| output.index != index) {
+            return invalid("outputs are not consecutive leaves");
+        }
+        if self.outputs.iter().any(|output| output.triangle.owner != self.address) {
+            return invalid("an output belongs to another address");
+        }
+        if self.before.as_ref().is_some_and(|before| before.triangle.owner >= self.address)
+            || self.after.as_ref().is_some_and(|after| after.triangle.owner <= self.address)
+        {
+            return invalid("a neighbouring leaf may belong to the address");
+        }
+
+        self.outputs
+            .iter()
+            .try_fold(Coord::from_num(0), |total, output| coord_checked_add(total, output.triangle.effective_value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+
+    fn triangle(owner: u8, value: i32) -> Triangle {
+        Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(value), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(1)),
+            None,
+            [owner; 32],
+        )
+        .with_effective_value(Coord::from_num(value))
+    }
+
+    #[test]
+    fn test_balance_proofs_cover_exactly_the_address_outputs() {
+        let mut state = TriangleState::new();
+        for (n, owner, value) in [(1u8, 1u8, 5), (2, 2, 3), (3, 2, 4), (4, 3, 7), (5, 2, 1), (6, 4, 2), (7, 3, 6)] {
+            state.utxo_set.insert([n; 32], triangle(owner, value));
+        }
+        let tree = UtxoTree::build(&state);
+        let commitment = tree.commitment();
+
+        let proof = tree.balance_proof([2u8; 32], 9, [9u8; 32]);
+        assert_eq!(proof.outputs.len(), 3);
+        assert_eq!(proof.verify(&commitment).unwrap(), Coord::from_num(8));
+        // Round trip as a light client would receive it
+        let json = serde_json::to_string(&proof).unwrap();
+        let received: BalanceProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.verify(&commitment).unwrap(), Coord::from_num(8));
+
+        // Edges of the set and an address without outputs
+        assert_eq!(tree.balance_proof([1u8; 32], 9, [9u8; 32]).verify(&commitment).unwrap(), Coord::from_num(5));
+        assert_eq!(tree.balance_proof([4u8; 32], 9, [9u8; 32]).verify(&commitment).unwrap(), Coord::from_num(2));
+        let empty = tree.balance_proof([5u8; 32], 9, [9u8; 32]);
+        assert!(empty.outputs.is_empty() && empty.after.is_none());
+        assert_eq!(empty.verify(&commitment).unwrap(), Coord::from_num(0));
+
+        // Hiding an output, inflating one or using another set all fail
+        let mut hidden = proof.clone();
+        hidden.outputs.pop();
+        assert!(hidden.verify(&commitment).is_err());
+        let mut inflated = proof.clone();
+        inflated.outputs[0].triangle = inflated.outputs[0].triangle.clone().with_effective_value(Coord::from_num(100));
+        assert!(inflated.verify(&commitment).is_err());
+        state.utxo_set.remove(&[1u8; 32]);
+        assert!(proof.verify(&UtxoTree::build(&state).commitment()).is_err());
+        let nothing = UtxoTree::build(&TriangleState::new());
+        let proof = nothing.balance_proof([2u8; 32], 0, [0u8; 32]);
+        assert_eq!(proof.verify(&nothing.commitment()).unwrap(), Coord::from_num(0));
+    }
+}
@@ -0,0 +1,189 @@
+//! UTXO set composition statistics
+//!
+//! Subdivision splits value into ever smaller triangles. Whether that is
+//! fragmenting the value space shows in the UTXO set: how many outputs there
+//! are, how their values are distributed, and how many are dust, worth less
+//! than a configured threshold. [`UtxoStatsTracker`] keeps those figures for
+//! the current set, updated from each block's undo record instead of a scan
+//! of the whole set, and the chain stores a [`UtxoStats`] point per block so
+//! the history can be charted.
+
+use crate::geometry::Coord;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use super::state::TriangleState;
+use super::undo::BlockUndo;
+
+/// Outputs worth less than this count as dust unless configured otherwise:
+/// 1/1024 TRC, what one byte of a data transaction costs
+pub const DEFAULT_DUST_THRESHOLD: Coord = Coord::from_bits(1 << 22);
+
+/// Exclusive upper bounds, in TRC, of the value buckets; the last bucket
+/// holds everything from 1000 TRC up
+pub const SIZE_BUCKET_BOUNDS: [f64; 7] = [0.001, 0.01, 0.1, 1.0, 10.0, 100.0, 1000.0];
+
+const BUCKETS: usize = SIZE_BUCKET_BOUNDS.len() + 1;
+
+/// Composition of the UTXO set after the block at `height`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoStats {
+    pub height: u64,
+    /// Timestamp of the block, in milliseconds
+    pub timestamp: u64,
+    pub count: u64,
+    pub total_value: Coord,
+    /// Outputs worth less than `dust_threshold`
+    pub dust_count: u64,
+    /// Threshold in effect when the point was recorded
+    pub dust_threshold: Coord,
+    /// Outputs per value bucket, see [`SIZE_BUCKET_BOUNDS`]
+    pub size_buckets: Vec<u64>,
+}
+
+/// Running totals over the current UTXO set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UtxoStatsTracker {
+    dust_threshold: Coord,
+    count: u64,
+    total_value: Coord,
+    dust_count: u64,
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for UtxoStatsTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_DUST_THRESHOLD)
+    }
+}
+
+impl UtxoStatsTracker {
+    /// Totals of an empty UTXO set
+    pub fn new(dust_threshold: Coord) -> Self {
+        UtxoStatsTracker {
+            dust_threshold,
+            count: 0,
+            total_value: Coord::from_num(0),
+            dust_count: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+
+    /// Totals of `state`, scanning every output
+    pub fn from_state(state: &TriangleState, dust_threshold: Coord) -> Self {
+        let mut tracker = Self::new(dust_threshold);
+        for triangle in state.utxo_set.values() {
+            tracker.add(triangle.effective_value());
+        }
+        tracker
+    }
+
+    pub fn dust_threshold(&self) -> Coord {
+        self.dust_threshold
+    }
+
+    /// Account for a block from its undo record, given the state just after
+    /// the block. Outputs created and spent within the block are skipped.
+    pub fn apply(&mut self, undo: &BlockUndo, state: &TriangleState) {
+        let created: HashSet<_> = undo.created.iter().collect();
+        for (hash, triangle) in &undo.spent {
+            if !created.contains(hash) {
+                self.remove(triangle.effective_value());
+            }
+        }
+        for hash in &undo.created {
+            if let Some(triangle) = state.utxo_set.get(hash) {
+                self.add(triangle.effective_value());
+            }
+        }
+    }
+
+    /// Undo [`apply`](Self::apply), given the state still holding the
+    /// block's outputs
+    pub fn revert(&mut self, undo: &BlockUndo, state: &TriangleState) {
+        let created: HashSet<_> = undo.created.iter().collect();
+        for hash in &undo.created {
+            if let Some(triangle) = state.utxo_set.get(hash) {
+                self.remove(triangle.effective_value());
+            }
+        }
+        for (hash, triangle) in &undo.spent {
+            if !created.contains(hash) {
+                self.add(triangle.effective_value());
+            }
+        }
+    }
+
+    /// Current totals as the point of the block at `height`
+    pub fn snapshot(&self, height: u64, timestamp: u64) -> UtxoStats {
+        UtxoStats {
+            height,
+            timestamp,
+            count: self.count,
+            total_value: self.total_value,
+            dust_count: self.dust_count,
+            dust_threshold: self.dust_threshold,
+            size_buckets: self.buckets.to_vec(),
+        }
+    }
+
+    fn add(&mut self, value: Coord) {
+        self.count += 1;
+        self.total_value = self.total_value.saturating_add(value);
+        self.dust_count += u64::from(value < self.dust_threshold);
+        self.buckets[bucket(value)] += 1;
+    }
+
+    fn remove(&mut self, value: Coord) {
+        self.count = self.count.saturating_sub(1);
+        self.total_value = self.total_value.saturating_sub(value);
+        self.dust_count = self.dust_count.saturating_sub(u64::from(value < self.dust_threshold));
+        let bucket = &mut self.buckets[bucket(value)];
+        *bucket = bucket.saturating_sub(1);
+    }
+}
+
+/// Index of the value bucket `value` falls in
+fn bucket(value: Coord) -> usize {
+    SIZE_BUCKET_BOUNDS
+        .iter()
+        .position(|&bound| value < Coord::from_num(bound))
+        .unwrap_or(SIZE_BUCKET_BOUNDS.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Triangle};
+
+    fn triangle(value: f64) -> Triangle {
+        let point = |x: i32, y: i32| Point::new(Coord::from_num(x), Coord::from_num(y));
+        let (a, b, c) = (point(0, 0), point(1, 0), point(0, 1));
+        Triangle::new_with_value(a, b, c, None, [1; 32], Coord::from_num(value))
+    }
+
+    #[test]
+    fn test_tracker_follows_blocks_and_matches_a_scan() {
+        let mut state = TriangleState::new();
+        let mut tracker = UtxoStatsTracker::new(Coord::from_num(0.01));
+
+        // A block splitting 50 TRC into a 49.995 output and a dust output,
+        // which it also spends into a second dust output
+        state.utxo_set.insert([1; 32], triangle(49.995));
+        state.utxo_set.insert([3; 32], triangle(0.004));
+        let mut undo = BlockUndo::new(1, 0);
+        undo.spent = vec![([0; 32], triangle(50.0)), ([2; 32], triangle(0.005))];
+        undo.created = vec![[1; 32], [2; 32], [3; 32]];
+        tracker.add(Coord::from_num(50));
+        tracker.apply(&undo, &state);
+
+        assert_eq!(tracker, UtxoStatsTracker::from_state(&state, Coord::from_num(0.01)));
+        let mut reverted = tracker.clone();
+        reverted.revert(&undo, &state);
+        assert_eq!((reverted.count, reverted.total_value), (1, Coord::from_num(50)));
+        let stats = tracker.snapshot(1, 1_000);
+        assert_eq!((stats.count, stats.dust_count), (2, 1));
+        assert_eq!(stats.size_buckets, vec![0, 1, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(bucket(Coord::from_num(5000)), SIZE_BUCKET_BOUNDS.len());
+    }
+}
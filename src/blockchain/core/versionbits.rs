@@ -0,0 +1,191 @@
+//! Version-bit soft-fork signaling (BIP9 style)
+//!
+//! Each deployment owns one bit of the block header version. Miners set the
+//! bit while the deployment is `Started`; once `threshold` blocks of a
+//! signaling period carry it the deployment locks in and becomes active one
+//! period later. Deployments that do not lock in before their timeout fail.
+//! States only change on period boundaries, so every block of a period shares
+//! the same state.
+
+use crate::blockchain::core::block::Block;
+use crate::blockchain::core::chain::ConsensusParams;
+
+pub use crate::blockchain::core::block::VERSIONBITS_TOP_BITS;
+/// Mask selecting the top three version bits
+pub const VERSIONBITS_TOP_MASK: u32 = 0xE000_0000;
+/// Number of bits available for concurrent deployments
+pub const VERSIONBITS_NUM_BITS: u8 = 29;
+/// Default number of blocks in a signaling period
+pub const DEFAULT_SIGNALING_PERIOD: u64 = 144;
+
+/// A consensus change deployed by miner signaling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deployment {
+    pub name: String,
+    /// Header version bit, below [`VERSIONBITS_NUM_BITS`]
+    pub bit: u8,
+    /// First height at which signaling may start (rounded up to a period)
+    pub start_height: u64,
+    /// Height after which a deployment that has not locked in fails
+    pub timeout_height: u64,
+    /// Signaling blocks needed within one period to lock in
+    pub threshold: u64,
+}
+
+impl Deployment {
+    /// Whether `version` signals readiness for this deployment
+    pub fn is_signaled_by(&self, version: u32) -> bool {
+        version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS && version & (1 << self.bit) != 0
+    }
+}
+
+/// Lifecycle of a [`Deployment`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentState {
+    Defined,
+    Started,
+    LockedIn,
+    Active,
+    Failed,
+}
+
+/// State of `deployment` for the block at `height`, given the chain's
+/// `blocks` (which must include every block of the periods before `height`).
+pub fn deployment_state(
+    blocks: &[Block],
+    params: &ConsensusParams,
+    deployment: &Deployment,
+    height: u64,
+) -> DeploymentState {
+    let period = params.signaling_period.max(1);
+    let target = height - height % period;
+    let mut state = DeploymentState::Defined;
+    let mut start = 0;
+
+    while start < target {
+        let end = start + period;
+        state = match state {
+            DeploymentState::Defined if end >= deployment.timeout_height => DeploymentState::Failed,
+            DeploymentState::Defined if end >= deployment.start_height => DeploymentState::Started,
+            DeploymentState::Started => {
+                if count_signals(blocks, deployment, start, end) >= deployment.threshold {
+                    DeploymentState::LockedIn
+                } else if end >= deployment.timeout_height {
+                    DeploymentState::Failed
+                } else {
+                    DeploymentState::Started
+                }
+            }
+            DeploymentState::LockedIn => DeploymentState::Active,
+            other => other,
+        };
+        start = end;
+    }
+    state
+}
+
+/// Blocks in heights `start..end` that signal for `deployment`
+pub fn count_signals(blocks: &[Block], deployment: &Deployment, start: u64, end: u64) -> u64 {
+    blocks
+        .iter()
+        .skip(start as usize)
+        .take((end - start) as usize)
+        .filter(|block| deployment.is_signaled_by(block.header.version))
+        .count() as u64
+}
+
+/// Header version a miner should use for the block at `height`: the
+/// version-bits marker plus the bit of every deployment still collecting
+/// signals.
+pub fn block_version(blocks: &[Block], params: &ConsensusParams, height: u64) -> u32 {
+    params
+        .deployments
+        .iter()
+        .filter(|deployment| {
+            matches!(
+                deployment_state(blocks, params, deployment, height),
+                DeploymentState::Started | DeploymentState::LockedIn
+            )
+        })
+        .fold(VERSIONBITS_TOP_BITS, |version, deployment| version | (1 << deployment.bit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::core::block::BlockHeader;
+
+    fn chain_with_versions(versions: &[u32]) -> Vec<Block> {
+        versions
+            .iter()
+            .enumerate()
+            .map(|(height, &version)| Block {
+                header: BlockHeader {
+                    height: height as u64,
+                    timestamp: 0,
+                    previous_hash: [0; 32],
+                    merkle_root: [0; 32],
+                    difficulty: 0,
+                    nonce: 0,
+                    version,
+                },
+                transactions: vec![],
+            })
+            .collect()
+    }
+
+    fn params(deployment: Deployment) -> ConsensusParams {
+        ConsensusParams {
+            signaling_period: 4,
+            deployments: vec![deployment],
+            ..ConsensusParams::default()
+        }
+    }
+
+    fn deployment() -> Deployment {
+        Deployment {
+            name: "testdummy".to_string(),
+            bit: 1,
+            start_height: 4,
+            timeout_height: 16,
+            threshold: 3,
+        }
+    }
+
+    #[test]
+    fn test_signaling_locks_in_and_activates() {
+        let params = params(deployment());
+        let d = &params.deployments[0];
+        let signal = VERSIONBITS_TOP_BITS | 0b10;
+        let plain = VERSIONBITS_TOP_BITS;
+        // Period 0 is Defined, period 1 Started (3 of 4 signal), period 2
+        // LockedIn and period 3 Active.
+        let blocks = chain_with_versions(&[
+            0, 0, 0, 0, //
+            signal, plain, signal, signal, //
+            plain, plain, plain, plain,
+        ]);
+
+        assert_eq!(deployment_state(&blocks, &params, d, 3), DeploymentState::Defined);
+        assert_eq!(deployment_state(&blocks, &params, d, 4), DeploymentState::Started);
+        assert_eq!(deployment_state(&blocks, &params, d, 8), DeploymentState::LockedIn);
+        assert_eq!(deployment_state(&blocks, &params, d, 12), DeploymentState::Active);
+        assert_eq!(block_version(&blocks, &params, 4), signal);
+        assert_eq!(block_version(&blocks, &params, 12), plain);
+    }
+
+    #[test]
+    fn test_deployment_fails_at_timeout() {
+        let params = params(deployment());
+        let d = &params.deployments[0];
+        // Two signals per period never reach the threshold of three
+        let signal = VERSIONBITS_TOP_BITS | 0b10;
+        let blocks = chain_with_versions(&[signal, signal, 0, 0].repeat(4));
+
+        assert_eq!(deployment_state(&blocks, &params, d, 12), DeploymentState::Started);
+        assert_eq!(deployment_state(&blocks, &params, d, 16), DeploymentState::Failed);
+        // Bits without the version-bits marker do not count
+        assert!(!d.is_signaled_by(0b10));
+    }
+}
@@ -0,0 +1,178 @@
+//! Bloom filters for light-client transaction relay
+//!
+//! A light client loads a filter over its addresses (and any txids or UTXO
+//! hashes it follows) into each peer with [`NetworkMessage::FilterLoad`];
+//! the peer then relays only transactions with something in the filter.
+//! The false-positive rate is what keeps the peer from learning exactly
+//! which addresses belong to the client, so filters are never built with a
+//! rate below [`MIN_FALSE_POSITIVE_RATE`] and each gets a random tweak, so
+//! filters from different sessions cannot be lined up.
+//!
+//! [`NetworkMessage::FilterLoad`]: crate::network::NetworkMessage::FilterLoad
+
+use crate::transaction::Transaction;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Largest filter a peer accepts, in bytes
+pub const MAX_FILTER_BYTES: usize = 36_000;
+
+/// Most hash functions a peer accepts in a filter
+pub const MAX_HASH_FUNCS: u32 = 50;
+
+/// Longest element that may be added to a loaded filter
+pub const MAX_ELEMENT_BYTES: usize = 64;
+
+/// Rate used when a client does not pick one: one unrelated transaction in
+/// a hundred is relayed alongside its own
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Lowest rate a filter is built with; lower ones would all but name the
+/// client's addresses to every peer
+pub const MIN_FALSE_POSITIVE_RATE: f64 = 0.0001;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    #[serde(with = "serde_bytes")]
+    bits: Vec<u8>,
+    hash_funcs: u32,
+    tweak: u32,
+}
+
+impl BloomFilter {
+    /// Empty filter sized for `elements` at `false_positive_rate`, raised to
+    /// [`MIN_FALSE_POSITIVE_RATE`] if lower and capped at
+    /// [`MAX_FILTER_BYTES`]
+    pub fn new(elements: usize, false_positive_rate: f64) -> Self {
+        let rate = false_positive_rate.clamp(MIN_FALSE_POSITIVE_RATE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let elements = elements.max(1) as f64;
+        let bits = (-elements * rate.ln() / (ln2 * ln2)).ceil();
+        let bytes = ((bits / 8.0).ceil() as usize).clamp(1, MAX_FILTER_BYTES);
+        let hash_funcs = ((bytes * 8) as f64 / elements * ln2).round() as u32;
+        BloomFilter {
+            bits: vec![0; bytes],
+            hash_funcs: hash_funcs.clamp(1, MAX_HASH_FUNCS),
+            tweak: rand::random(),
+        }
+    }
+
+    /// Filter over `addresses` at `false_positive_rate`
+    pub fn for_addresses(addresses: &[crate::crypto::Address], false_positive_rate: f64) -> Self {
+        let mut filter = BloomFilter::new(addresses.len(), false_positive_rate);
+        for address in addresses {
+            filter.insert(address);
+        }
+        filter
+    }
+
+    /// Whether a peer should accept this filter
+    pub fn is_within_limits(&self) -> bool {
+        !self.bits.is_empty()
+            && self.bits.len() <= MAX_FILTER_BYTES
+            && (1..=MAX_HASH_FUNCS).contains(&self.hash_funcs)
+    }
+
+    pub fn insert(&mut self, element: &[u8]) {
+        for bit in self.bit_indices(element) {
+            self.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn contains(&self, element: &[u8]) -> bool {
+        self.bit_indices(element).into_iter().all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// Whether `tx` touches anything in the filter: its txid, a UTXO it
+    /// spends or an address it pays or spends from
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        self.contains(&tx.hash())
+            || tx.inputs().iter().any(|input| self.contains(input))
+            || transaction_addresses(tx).iter().any(|address| self.contains(address))
+    }
+
+    /// Bit positions of `element`: four bytes of a tweaked SHA-256 digest
+    /// each, hashing again with a counter once a digest is used up
+    fn bit_indices(&self, element: &[u8]) -> Vec<usize> {
+        let bits = self.bits.len() * 8;
+        let mut indices = Vec::with_capacity(self.hash_funcs as usize);
+        for round in 0u32.. {
+            let digest = Sha256::new()
+                .chain_update(self.tweak.to_le_bytes())
+                .chain_update(round.to_le_bytes())
+                .chain_update(element)
+                .finalize();
+            for chunk in digest.chunks_exact(4) {
+                if indices.len() == self.hash_funcs as usize {
+                    return indices;
+                }
+                indices.push(u32::from_le_bytes(chunk.try_into().expect("4 bytes")) as usize % bits);
+            }
+        }
+        indices
+    }
+}
+
+/// Addresses `tx` pays to or spends from
+fn transaction_addresses(tx: &Transaction) -> Vec<crate::crypto::Address> {
+    match tx {
+        Transaction::Transfer(transfer) => std::iter::once(transfer.sender)
+            .chain(std::iter::once(transfer.new_owner))
+            .chain(transfer.sponsor.as_ref().map(|sponsor| sponsor.payer))
+            .collect(),
+        Transaction::Subdivision(subdivision) => std::iter::once(subdivision.owner_address)
+            .chain(subdivision.children.iter().map(|child| child.owner))
+            .collect(),
+        Transaction::Coinbase(coinbase) => std::iter::once(coinbase.beneficiary_address)
+            .chain(coinbase.outputs.iter().map(|output| output.address))
+            .collect(),
+        Transaction::Announce(announce) => vec![announce.address()],
+        Transaction::Register(register) => vec![register.owner],
+        Transaction::Data(data) => vec![data.owner],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Coord;
+    use crate::transaction::TransferTx;
+
+    #[test]
+    fn test_filter_matches_transactions_of_its_addresses() {
+        let mine = [1u8; 32];
+        let mut filter = BloomFilter::new(1, DEFAULT_FALSE_POSITIVE_RATE);
+        // Fixed so the unrelated transaction below is no false positive
+        filter.tweak = 7;
+        filter.insert(&mine);
+        assert!(filter.is_within_limits());
+        let transfer = |sender, new_owner| {
+            Transaction::Transfer(TransferTx::new([9u8; 32], new_owner, sender, Coord::from_num(1), Coord::from_num(0), 1))
+        };
+        assert!(filter.matches(&transfer(mine, [2u8; 32])));
+        assert!(filter.matches(&transfer([2u8; 32], mine)));
+        assert!(!filter.matches(&transfer([2u8; 32], [3u8; 32])));
+
+        // A loaded filter grows to follow a transaction by its txid
+        let other = transfer([4u8; 32], [5u8; 32]);
+        filter.insert(&other.hash());
+        assert!(filter.matches(&other));
+    }
+
+    #[test]
+    fn test_false_positive_rate_has_a_floor() {
+        let addresses: Vec<[u8; 32]> = (0..100u8).map(|i| [i; 32]).collect();
+        let filter = BloomFilter::for_addresses(&addresses, 0.0);
+        let expected = BloomFilter::new(addresses.len(), MIN_FALSE_POSITIVE_RATE);
+        assert_eq!((filter.bits.len(), filter.hash_funcs), (expected.bits.len(), expected.hash_funcs));
+        assert!(addresses.iter().all(|address| filter.contains(address)));
+
+        let loose = BloomFilter::for_addresses(&addresses, 0.05);
+        let hits = (0..20_000u32).filter(|i| loose.contains(&i.to_be_bytes())).count();
+        assert!((400..1_600).contains(&hits), "{} false positives in 20000", hits);
+
+        let huge = BloomFilter::new(10_000_000, MIN_FALSE_POSITIVE_RATE);
+        assert_eq!(huge.bits.len(), MAX_FILTER_BYTES);
+        assert!(huge.is_within_limits());
+    }
+}
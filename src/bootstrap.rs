@@ -0,0 +1,416 @@
+//! Cold-start bootstrap from a chain snapshot
+//!
+//! A new node can skip most of its initial sync by downloading a chain
+//! snapshot over HTTP(S) with `trinity-node bootstrap --url`. Snapshots hold
+//! blocks only and nothing in them is taken on trust: the blocks must match
+//! every known checkpoint at or below their tip, at least one checkpoint must
+//! be covered, and they are replayed with full validation before the
+//! database is written. With `--publisher`, the snapshot must also match a
+//! manifest signed by that operator (see [`crate::manifest`]), which then
+//! stands in for covering a checkpoint. Blocks above the snapshot are synced
+//! from peers as usual. Once blocks commit to their UTXO set, snapshots can carry the set
+//! itself and be checked against the commitment instead of replayed.
+
+use crate::blockchain::{Block, Blockchain, Sha256Hash};
+use crate::checkpoint::Checkpoint;
+use crate::compression;
+use crate::error::ChainError;
+use crate::manifest::{Manifest, MAX_MANIFEST_SIZE};
+use crate::persistence::Database;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Checkpoints shipped with this release as (height, block hash). A
+/// snapshot must agree with every one it reaches.
+pub const EMBEDDED_CHECKPOINTS: &[(u64, &str)] = &[];
+
+/// Largest snapshot download accepted
+pub const MAX_SNAPSHOT_SIZE: u64 = 8 << 30;
+
+/// Largest snapshot accepted once decompressed, so a compressed download
+/// costs no more memory than an uncompressed one of the largest size
+pub const MAX_DECODED_SNAPSHOT_SIZE: u64 = MAX_SNAPSHOT_SIZE;
+
+/// Format version of snapshot files
+const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    blocks: &'a [Block],
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    version: u32,
+    blocks: Vec<Block>,
+}
+
+/// Write `blocks` as a snapshot file, compressed at `level` unless it is 0
+pub fn write_chain_snapshot(blocks: &[Block], path: &Path, level: i32) -> Result<(), ChainError> {
+    let data = bincode::serialize(&SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        blocks,
+    })?;
+    compression::write_snapshot(path, &data, level)
+}
+
+/// Blocks of a snapshot file written by [`write_chain_snapshot`], at most
+/// `limit` bytes once decompressed
+pub fn read_chain_snapshot(path: &Path, limit: u64) -> Result<Vec<Block>, ChainError> {
+    let snapshot: Snapshot = bincode::deserialize(&compression::read_snapshot(path, limit)?)?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(ChainError::InvalidBlock(format!(
+            "Unsupported snapshot version {}",
+            snapshot.version
+        )));
+    }
+    Ok(snapshot.blocks)
+}
+
+/// [`EMBEDDED_CHECKPOINTS`] as checkpoints
+pub fn embedded_checkpoints() -> Vec<Checkpoint> {
+    EMBEDDED_CHECKPOINTS
+        .iter()
+        .map(|(height, hash)| parse_checkpoint(&format!("{}:{}", height, hash)).expect("valid embedded checkpoint"))
+        .collect()
+}
+
+/// Parse a checkpoint given as `HEIGHT:HASH`
+pub fn parse_checkpoint(text: &str) -> Result<Checkpoint, ChainError> {
+    let invalid = || ChainError::InvalidBlock(format!("Checkpoint '{}' is not HEIGHT:HASH", text));
+    let (height, hash) = text.split_once(':').ok_or_else(invalid)?;
+    let height = height.parse().map_err(|_| invalid())?;
+    let mut block_hash: Sha256Hash = [0; 32];
+    hex::decode_to_slice(hash, &mut block_hash).map_err(|_| invalid())?;
+    Ok(Checkpoint {
+        height,
+        hash: block_hash,
+    })
+}
+
+/// Check `blocks` against `checkpoints`, returning how many of them the
+/// blocks reach. Fails if the blocks contradict any, or reach none.
+pub fn verify_checkpoints(blocks: &[Block], checkpoints: &[Checkpoint]) -> Result<usize, ChainError> {
+    let covered = match_checkpoints(blocks, checkpoints)?;
+    if covered == 0 {
+        return Err(ChainError::InvalidBlock(
+            "Snapshot reaches no known checkpoint; pass --checkpoint HEIGHT:HASH from a source you trust".to_string(),
+        ));
+    }
+    Ok(covered)
+}
+
+/// Like [`verify_checkpoints`], but reaching none is fine
+pub fn match_checkpoints(blocks: &[Block], checkpoints: &[Checkpoint]) -> Result<usize, ChainError> {
+    let mut covered = 0;
+    for checkpoint in checkpoints {
+        let Some(block) = blocks.get(checkpoint.height as usize) else {
+            continue;
+        };
+        if block.header.height != checkpoint.height || block.hash() != checkpoint.hash {
+            return Err(ChainError::InvalidBlock(format!(
+                "Snapshot does not match the checkpoint at height {}",
+                checkpoint.height
+            )));
+        }
+        covered += 1;
+    }
+    Ok(covered)
+}
+
+/// Download `url` to `dest`, returning its size in bytes. Fails once the
+/// download grows beyond `limit` bytes.
+pub async fn download(url: &str, dest: &Path, limit: u64) -> Result<u64, ChainError> {
+    let network_error = |e: reqwest::Error| ChainError::NetworkError(format!("Downloading {}: {}", url, e));
+    let mut response = reqwest::get(url).await.map_err(network_error)?;
+    if !response.status().is_success() {
+        return Err(ChainError::NetworkError(format!("{} answered {}", url, response.status())));
+    }
+    let mut file = std::fs::File::create(dest)?;
+    let mut size = 0u64;
+    while let Some(chunk) = response.chunk().await.map_err(network_error)? {
+        size += chunk.len() as u64;
+        if size > limit {
+            return Err(ChainError::NetworkError(format!(
+                "{} is larger than {} bytes",
+                url, limit
+            )));
+        }
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    Ok(size)
+}
+
+/// Replay `blocks` and write the resulting chain to a new database at
+/// `db_path`. The database is built next to it and moved into place only
+/// once complete, so a failed bootstrap leaves nothing behind.
+pub fn initialize(blocks: Vec<Block>, db_path: &Path) -> Result<Blockchain, ChainError> {
+    if db_path.exists() {
+        return Err(ChainError::DatabaseError(format!(
+            "{} already exists; bootstrap only initializes an empty data directory",
+            db_path.display()
+        )));
+    }
+    let chain = Blockchain::from_blocks(blocks)?;
+    let tip = chain.blocks.last().expect("chain has a genesis block");
+
+    let partial = db_path.with_extension("bootstrap");
+    if partial.exists() {
+        std::fs::remove_file(&partial)?;
+    }
+    {
+        let db = Database::open(&partial.to_string_lossy())?;
+        for block in &chain.blocks[..chain.blocks.len() - 1] {
+            db.save_block(block)?;
+        }
+        for block in &chain.blocks[1..] {
+            if let Some(undo) = chain.persistence.load_block_undo(block.header.height)? {
+                db.save_block_undo(&undo)?;
+            }
+        }
+        db.save_blockchain_state(tip, &chain.state, chain.difficulty as u64)?;
+    }
+    std::fs::rename(&partial, db_path)?;
+    Ok(chain)
+}
+
+/// Signed manifest a snapshot must match
+#[derive(Debug, Clone, Copy)]
+pub struct ManifestCheck<'a> {
+    /// Where the manifest is; by default the snapshot URL with
+    /// `.manifest.json` appended
+    pub url: Option<&'a str>,
+    /// Hex public key or address that must have signed it
+    pub publisher: &'a str,
+}
+
+/// Name a manifest lists the snapshot at `url` under: the last path segment
+fn snapshot_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Download the manifest for the snapshot at `url`, check its signature and
+/// return it
+async fn fetch_manifest(url: &str, check: ManifestCheck<'_>, data_dir: &Path) -> Result<Manifest, ChainError> {
+    let manifest_url = check
+        .url
+        .map_or_else(|| format!("{}.manifest.json", url), str::to_string);
+    let path = data_dir.join("bootstrap.manifest.json");
+    let result = async {
+        download(&manifest_url, &path, MAX_MANIFEST_SIZE).await?;
+        let manifest = Manifest::load(&path)?;
+        manifest.verify(check.publisher)?;
+        Ok(manifest)
+    }
+    .await;
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Download the snapshot at `url` into `data_dir`, verify it against the
+/// embedded checkpoints, `checkpoints` and the signed manifest, if any, and
+/// initialize the database at `db_path` from it
+pub async fn bootstrap(
+    url: &str,
+    checkpoints: &[Checkpoint],
+    manifest: Option<ManifestCheck<'_>>,
+    data_dir: &Path,
+    db_path: &Path,
+) -> Result<Blockchain, ChainError> {
+    if db_path.exists() {
+        return Err(ChainError::DatabaseError(format!(
+            "{} already exists; bootstrap only initializes an empty data directory",
+            db_path.display()
+        )));
+    }
+    std::fs::create_dir_all(data_dir)?;
+    let download_path = data_dir.join("bootstrap.snapshot");
+    let result = async {
+        let manifest = match manifest {
+            Some(check) => Some(fetch_manifest(url, check, data_dir).await?),
+            None => None,
+        };
+        download(url, &download_path, MAX_SNAPSHOT_SIZE).await?;
+        if let Some(manifest) = &manifest {
+            // A mirror may rename the file; a single entry is the snapshot
+            let name = match manifest.files.as_slice() {
+                [only] => only.name.as_str(),
+                _ => snapshot_name(url),
+            };
+            manifest.verify_file(name, &download_path)?;
+        }
+        let blocks = read_chain_snapshot(&download_path, MAX_DECODED_SNAPSHOT_SIZE)?;
+        let mut known = embedded_checkpoints();
+        known.extend_from_slice(checkpoints);
+        if manifest.is_some() {
+            match_checkpoints(&blocks, &known)?;
+        } else {
+            verify_checkpoints(&blocks, &known)?;
+        }
+        initialize(blocks, db_path)
+    }
+    .await;
+    let _ = std::fs::remove_file(&download_path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+    use crate::geometry::Coord;
+    use crate::miner::mine_block;
+    use crate::transaction::{CoinbaseTx, Transaction};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn chain_of(length: u64) -> Blockchain {
+        let mut chain = Blockchain::new([1; 32], 0).unwrap();
+        for height in 1..length {
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: [2; 32],
+                nonce: height,
+                height,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let tip = chain.blocks.last().unwrap();
+            let block = Block::new(height, tip.hash(), 0, vec![coinbase]);
+            chain.apply_block(mine_block(block).unwrap()).unwrap();
+        }
+        chain
+    }
+
+    /// Serve `body` once over HTTP on a local port
+    async fn serve_once(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await.unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(&body).await.unwrap();
+        });
+        format!("http://127.0.0.1:{}/chain.snapshot", port)
+    }
+
+    #[test]
+    fn test_checkpoints_must_match_and_be_reached() {
+        let chain = chain_of(4);
+        let at = |height: usize| Checkpoint {
+            height: height as u64,
+            hash: chain.blocks[height].hash(),
+        };
+        assert_eq!(verify_checkpoints(&chain.blocks, &[at(2), at(3)]).unwrap(), 2);
+
+        let beyond = || Checkpoint { height: 10, hash: [0; 32] };
+        assert!(verify_checkpoints(&chain.blocks, &[beyond()]).is_err());
+        assert_eq!(verify_checkpoints(&chain.blocks, &[at(1), beyond()]).unwrap(), 1);
+        let wrong = Checkpoint { height: 2, hash: chain.blocks[1].hash() };
+        assert!(verify_checkpoints(&chain.blocks, &[at(3), wrong]).is_err());
+
+        let text = format!("3:{}", hex::encode(chain.blocks[3].hash()));
+        assert_eq!(parse_checkpoint(&text).unwrap(), at(3));
+        assert!(parse_checkpoint("3").is_err());
+        assert!(parse_checkpoint("x:00").is_err());
+    }
+
+    #[test]
+    fn test_snapshots_are_bounded_once_decompressed() {
+        let chain = chain_of(3);
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("chain.snapshot");
+        write_chain_snapshot(&chain.blocks, &snapshot, compression::DEFAULT_LEVEL).unwrap();
+        assert_eq!(read_chain_snapshot(&snapshot, MAX_DECODED_SNAPSHOT_SIZE).unwrap().len(), 3);
+
+        // A small download that would expand to 64 MiB
+        let bomb = dir.path().join("bomb.snapshot");
+        compression::write_snapshot(&bomb, &vec![0u8; 64 << 20], compression::DEFAULT_LEVEL).unwrap();
+        assert!(std::fs::metadata(&bomb).unwrap().len() < 64 << 10);
+        let err = read_chain_snapshot(&bomb, 1 << 20).unwrap_err();
+        assert!(err.to_string().contains("exceeds"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_downloads_verifies_and_initializes() {
+        let chain = chain_of(5);
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("chain.snapshot");
+        write_chain_snapshot(&chain.blocks, &snapshot, compression::DEFAULT_LEVEL).unwrap();
+        let data_dir = dir.path().join("data");
+        let db_path = data_dir.join("trinitychain.db");
+        let tip = [Checkpoint {
+            height: 4,
+            hash: chain.blocks[4].hash(),
+        }];
+
+        // A snapshot off the checkpointed chain leaves nothing behind
+        let other = chain_of(6);
+        let other_snapshot = dir.path().join("other.snapshot");
+        write_chain_snapshot(&other.blocks, &other_snapshot, 0).unwrap();
+        let url = serve_once(std::fs::read(&other_snapshot).unwrap()).await;
+        assert!(bootstrap(&url, &tip, None, &data_dir, &db_path).await.is_err());
+        assert!(!db_path.exists());
+        assert!(!data_dir.join("bootstrap.snapshot").exists());
+
+        let url = serve_once(std::fs::read(&snapshot).unwrap()).await;
+        bootstrap(&url, &tip, None, &data_dir, &db_path).await.unwrap();
+        let db = Database::open(&db_path.to_string_lossy()).unwrap();
+        let loaded = db.load_blockchain().unwrap();
+        assert_eq!(loaded.blocks.last().unwrap().hash(), tip[0].hash);
+        assert_eq!(loaded.state.utxo_set, chain.state.utxo_set);
+        assert!(db.load_block_undo(4).unwrap().is_some());
+
+        // An existing database is never overwritten
+        let url = serve_once(std::fs::read(&snapshot).unwrap()).await;
+        assert!(bootstrap(&url, &tip, None, &data_dir, &db_path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_manifest_replaces_checkpoints() {
+        let chain = chain_of(3);
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("chain.snapshot");
+        write_chain_snapshot(&chain.blocks, &snapshot, compression::DEFAULT_LEVEL).unwrap();
+        let operator = KeyPair::generate().unwrap();
+        let publisher = hex::encode(operator.address());
+        let manifest = Manifest::create(std::slice::from_ref(&snapshot), &operator).unwrap();
+        let manifest_json = serde_json::to_vec(&manifest).unwrap();
+        let data_dir = dir.path().join("data");
+        let db_path = data_dir.join("trinitychain.db");
+
+        // Signed by someone else
+        let manifest_url = serve_once(manifest_json.clone()).await;
+        let url = serve_once(std::fs::read(&snapshot).unwrap()).await;
+        let stranger = hex::encode([9u8; 32]);
+        let check = ManifestCheck { url: Some(&manifest_url), publisher: &stranger };
+        assert!(bootstrap(&url, &[], Some(check), &data_dir, &db_path).await.is_err());
+
+        // A different snapshot than the one signed
+        let other = dir.path().join("other.snapshot");
+        write_chain_snapshot(&chain_of(4).blocks, &other, 0).unwrap();
+        let manifest_url = serve_once(manifest_json.clone()).await;
+        let url = serve_once(std::fs::read(&other).unwrap()).await;
+        let check = ManifestCheck { url: Some(&manifest_url), publisher: &publisher };
+        assert!(bootstrap(&url, &[], Some(check), &data_dir, &db_path).await.is_err());
+        assert!(!db_path.exists());
+
+        let manifest_url = serve_once(manifest_json).await;
+        let url = serve_once(std::fs::read(&snapshot).unwrap()).await;
+        let check = ManifestCheck { url: Some(&manifest_url), publisher: &publisher };
+        let loaded = bootstrap(&url, &[], Some(check), &data_dir, &db_path).await.unwrap();
+        assert_eq!(loaded.blocks.len(), 3);
+        assert!(!data_dir.join("bootstrap.manifest.json").exists());
+    }
+}
@@ -0,0 +1,576 @@
+//! Telegram bot payments from a hot wallet
+//!
+//! A Telegram user first links a wallet address by signing
+//! [`link_message`] with it. A linked user can then ask the bot to pay from
+//! the operator's hot wallet. Every payment is held until the user types a
+//! one-time code on an inline keypad. The code is sent to them in a private
+//! message, so a stolen group session or a hijacked chat is not enough.
+//! Payments are capped per user by [`crate::config::TelegramConfig`]. The
+//! amounts are counted in a separate [`SpendingLedger`]. Every step is
+//! appended to an audit log that is never truncated.
+
+use crate::config::TelegramConfig;
+use crate::crypto::{address_from_hex, verify_message, Address};
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use crate::spending::{SpendingLedger, SpendingPolicy};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File names inside the data directory
+pub const BOT_LINKS_FILE: &str = "bot_links.json";
+pub const BOT_SPENDING_FILE: &str = "bot_spending.json";
+pub const BOT_AUDIT_FILE: &str = "bot_audit.log";
+
+/// Digits in a confirmation code
+pub const CODE_LENGTH: usize = 6;
+
+/// A payment not confirmed within this long is dropped
+pub const CONFIRMATION_TTL_SECS: u64 = 5 * 60;
+
+/// Wrong codes allowed before the payment is cancelled
+pub const MAX_CODE_ATTEMPTS: u32 = 3;
+
+/// The message a user signs to link their wallet to a Telegram account
+pub fn link_message(user_id: i64) -> String {
+    format!("Link Telegram user {} to TrinityChain", user_id)
+}
+
+/// Telegram users and the wallet address each one proved to own
+#[derive(Debug, Default)]
+pub struct BotLinks {
+    path: Option<PathBuf>,
+    /// Hex addresses keyed by Telegram user id
+    links: BTreeMap<i64, String>,
+}
+
+impl BotLinks {
+    /// Create an in-memory set of links
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the links stored at `path`; a missing file yields no links
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut links = BotLinks {
+            path: Some(path.to_path_buf()),
+            links: BTreeMap::new(),
+        };
+        if !path.exists() {
+            return Ok(links);
+        }
+
+        let content = fs::read_to_string(path)?;
+        links.links = serde_json::from_str(&content)
+            .map_err(|e| ChainError::WalletError(format!("Invalid bot links {}: {}", path.display(), e)))?;
+        Ok(links)
+    }
+
+    /// Load `bot_links.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(BOT_LINKS_FILE))
+    }
+
+    /// Write the links to their backing file (no-op when in memory)
+    pub fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&self.links)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize bot links: {}", e)))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Link `address` to `user_id` once `signature` proves the user holds
+    /// its key, replacing any previous link
+    pub fn link(&mut self, user_id: i64, address: &str, signature: &str) -> Result<(), ChainError> {
+        verify_message(&address_from_hex(address)?, &link_message(user_id), signature)?;
+        self.links.insert(user_id, address.to_string());
+        self.save()
+    }
+
+    /// Remove a user's link, returning the address it pointed to
+    pub fn unlink(&mut self, user_id: i64) -> Result<Option<String>, ChainError> {
+        let removed = self.links.remove(&user_id);
+        if removed.is_some() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    pub fn address(&self, user_id: i64) -> Option<&str> {
+        self.links.get(&user_id).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    Linked,
+    Unlinked,
+    Requested,
+    Confirmed,
+    Cancelled,
+    Rejected,
+    Sent,
+    Failed,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Unix timestamp (seconds)
+    pub at: u64,
+    pub user_id: i64,
+    pub event: AuditEvent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Coord>,
+    /// Transaction ids, or why the payment was refused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Append-only audit log with one JSON entry per line
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> Self {
+        AuditLog {
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// The log at `bot_audit.log` in a node data directory
+    pub fn open_in_data_dir(data_dir: &Path) -> Self {
+        Self::open(&data_dir.join(BOT_AUDIT_FILE))
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), ChainError> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let line = serde_json::to_string(entry)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize audit entry: {}", e)))?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Result<Vec<AuditEntry>, ChainError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| ChainError::WalletError(format!("Invalid audit entry: {}", e)))
+            })
+            .collect()
+    }
+}
+
+/// A key on the confirmation keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keypress {
+    Digit(u8),
+    Backspace,
+    Cancel,
+}
+
+impl Keypress {
+    /// Parse the callback data of a keypad button
+    pub fn parse(data: &str) -> Option<Self> {
+        match data.strip_prefix("pay:")? {
+            "back" => Some(Keypress::Backspace),
+            "cancel" => Some(Keypress::Cancel),
+            digit => match digit.as_bytes() {
+                [d @ b'0'..=b'9'] => Some(Keypress::Digit(d - b'0')),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn callback_data(self) -> String {
+        match self {
+            Keypress::Digit(d) => format!("pay:{}", d),
+            Keypress::Backspace => "pay:back".to_string(),
+            Keypress::Cancel => "pay:cancel".to_string(),
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Keypress::Digit(d) => d.to_string(),
+            Keypress::Backspace => "⌫".to_string(),
+            Keypress::Cancel => "✖ Cancel".to_string(),
+        }
+    }
+}
+
+/// Rows of the confirmation keypad, phone layout
+pub fn keypad() -> Vec<Vec<Keypress>> {
+    let mut rows: Vec<Vec<Keypress>> = (0..3)
+        .map(|row| (1..=3).map(|col| Keypress::Digit(row * 3 + col)).collect())
+        .collect();
+    rows.push(vec![Keypress::Cancel, Keypress::Digit(0), Keypress::Backspace]);
+    rows
+}
+
+/// A payment waiting for its confirmation code
+#[derive(Debug, Clone)]
+pub struct PendingPayment {
+    pub user_id: i64,
+    pub recipient: Address,
+    pub amount: Coord,
+    /// Sent to the user privately; never logged
+    pub code: String,
+    /// Digits typed on the keypad so far
+    pub entered: String,
+    pub attempts: u32,
+    pub created_at: u64,
+}
+
+/// What a keypress did to a pending payment
+#[derive(Debug, Clone)]
+pub enum KeypadOutcome {
+    /// Code still incomplete; this many digits typed
+    Entered(usize),
+    /// Right code: the payment is confirmed and no longer pending
+    Confirmed(PendingPayment),
+    /// Wrong code; the entry is cleared
+    WrongCode { attempts_left: u32 },
+    /// Cancelled by the user or after too many wrong codes
+    Cancelled,
+}
+
+/// Links, limits, pending confirmations and the audit trail of the bot's
+/// payments
+pub struct PaymentDesk {
+    config: TelegramConfig,
+    links: BotLinks,
+    ledger: SpendingLedger,
+    audit: AuditLog,
+    pending: HashMap<i64, PendingPayment>,
+}
+
+impl PaymentDesk {
+    pub fn new(config: TelegramConfig, links: BotLinks, ledger: SpendingLedger, audit: AuditLog) -> Self {
+        PaymentDesk {
+            config,
+            links,
+            ledger,
+            audit,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Open the links, ledger and audit log kept in a node data directory
+    pub fn open_in_data_dir(config: TelegramConfig, data_dir: &Path) -> Result<Self, ChainError> {
+        Ok(Self::new(
+            config,
+            BotLinks::load_from_data_dir(data_dir)?,
+            SpendingLedger::load(&data_dir.join(BOT_SPENDING_FILE))?,
+            AuditLog::open_in_data_dir(data_dir),
+        ))
+    }
+
+    /// Name of the wallet payments are made from, if payments are enabled
+    pub fn hot_wallet(&self) -> Option<&str> {
+        self.config.hot_wallet.as_deref()
+    }
+
+    pub fn linked_address(&self, user_id: i64) -> Option<&str> {
+        self.links.address(user_id)
+    }
+
+    pub fn link(&mut self, user_id: i64, address: &str, signature: &str, now: u64) -> Result<(), ChainError> {
+        self.links.link(user_id, address, signature)?;
+        self.log(now, user_id, AuditEvent::Linked, Some(address.to_string()), None, None)
+    }
+
+    pub fn unlink(&mut self, user_id: i64, now: u64) -> Result<Option<String>, ChainError> {
+        self.pending.remove(&user_id);
+        let removed = self.links.unlink(user_id)?;
+        if removed.is_some() {
+            self.log(now, user_id, AuditEvent::Unlinked, removed.clone(), None, None)?;
+        }
+        Ok(removed)
+    }
+
+    /// The user's limits and what they sent in the last 24 hours
+    pub fn allowance(&self, user_id: i64, now: u64) -> Option<(SpendingPolicy, Coord)> {
+        let policy = self.config.spending_policy(user_id)?;
+        Some((policy, self.ledger.spent_today(&ledger_key(user_id), now)))
+    }
+
+    /// Hold a payment of `amount` to `recipient` until it is confirmed,
+    /// replacing any payment the user still has pending
+    pub fn request(
+        &mut self,
+        user_id: i64,
+        recipient: Address,
+        amount: Coord,
+        now: u64,
+    ) -> Result<PendingPayment, ChainError> {
+        let address = Some(hex::encode(recipient));
+        if let Err(e) = self.check(user_id, amount, now) {
+            self.log(now, user_id, AuditEvent::Rejected, address, Some(amount), Some(e.to_string()))?;
+            return Err(e);
+        }
+
+        let code = format!("{:0width$}", rand::thread_rng().gen_range(0..10u32.pow(CODE_LENGTH as u32)), width = CODE_LENGTH);
+        let payment = PendingPayment {
+            user_id,
+            recipient,
+            amount,
+            code,
+            entered: String::new(),
+            attempts: 0,
+            created_at: now,
+        };
+        self.pending.insert(user_id, payment.clone());
+        self.log(now, user_id, AuditEvent::Requested, address, Some(amount), None)?;
+        Ok(payment)
+    }
+
+    /// Apply a keypad press by `user_id` to their pending payment
+    pub fn press(&mut self, user_id: i64, key: Keypress, now: u64) -> Result<KeypadOutcome, ChainError> {
+        let Some(payment) = self.pending.get_mut(&user_id) else {
+            return Err(ChainError::WalletError("No payment is waiting for confirmation".to_string()));
+        };
+        if now.saturating_sub(payment.created_at) > CONFIRMATION_TTL_SECS {
+            let payment = self.pending.remove(&user_id).expect("pending payment");
+            self.log_payment(now, &payment, AuditEvent::Cancelled, Some("expired".to_string()))?;
+            return Err(ChainError::WalletError("The payment expired; send /pay again".to_string()));
+        }
+
+        match key {
+            Keypress::Digit(d) => payment.entered.push(char::from(b'0' + d)),
+            Keypress::Backspace => {
+                payment.entered.pop();
+            }
+            Keypress::Cancel => {
+                let payment = self.pending.remove(&user_id).expect("pending payment");
+                self.log_payment(now, &payment, AuditEvent::Cancelled, None)?;
+                return Ok(KeypadOutcome::Cancelled);
+            }
+        }
+        if payment.entered.len() < CODE_LENGTH {
+            return Ok(KeypadOutcome::Entered(payment.entered.len()));
+        }
+
+        if payment.entered != payment.code {
+            payment.entered.clear();
+            payment.attempts += 1;
+            let attempts_left = MAX_CODE_ATTEMPTS.saturating_sub(payment.attempts);
+            if attempts_left > 0 {
+                return Ok(KeypadOutcome::WrongCode { attempts_left });
+            }
+            let payment = self.pending.remove(&user_id).expect("pending payment");
+            self.log_payment(now, &payment, AuditEvent::Cancelled, Some("too many wrong codes".to_string()))?;
+            return Ok(KeypadOutcome::Cancelled);
+        }
+
+        let payment = self.pending.remove(&user_id).expect("pending payment");
+        // Another payment may have used up the allowance in the meantime
+        if let Err(e) = self.check(user_id, payment.amount, now) {
+            self.log_payment(now, &payment, AuditEvent::Rejected, Some(e.to_string()))?;
+            return Err(e);
+        }
+        self.log_payment(now, &payment, AuditEvent::Confirmed, None)?;
+        Ok(KeypadOutcome::Confirmed(payment))
+    }
+
+    /// Count a confirmed payment that was submitted, `spent` including fees
+    pub fn record_sent(
+        &mut self,
+        payment: &PendingPayment,
+        spent: Coord,
+        txids: &[String],
+        now: u64,
+    ) -> Result<(), ChainError> {
+        self.ledger.record(&ledger_key(payment.user_id), spent, now)?;
+        self.log_payment(now, payment, AuditEvent::Sent, Some(txids.join(",")))
+    }
+
+    /// Note a confirmed payment that could not be submitted
+    pub fn record_failed(&mut self, payment: &PendingPayment, reason: &str, now: u64) -> Result<(), ChainError> {
+        self.log_payment(now, payment, AuditEvent::Failed, Some(reason.to_string()))
+    }
+
+    fn check(&self, user_id: i64, amount: Coord, now: u64) -> Result<(), ChainError> {
+        if self.links.address(user_id).is_none() {
+            return Err(ChainError::WalletError("Link a wallet first with /link".to_string()));
+        }
+        let policy = self
+            .config
+            .spending_policy(user_id)
+            .ok_or_else(|| ChainError::SpendingLimit("payments are not enabled for this user".to_string()))?;
+        policy.check(amount, self.ledger.spent_today(&ledger_key(user_id), now))?;
+        Ok(())
+    }
+
+    fn log_payment(
+        &self,
+        now: u64,
+        payment: &PendingPayment,
+        event: AuditEvent,
+        detail: Option<String>,
+    ) -> Result<(), ChainError> {
+        let address = Some(hex::encode(payment.recipient));
+        self.log(now, payment.user_id, event, address, Some(payment.amount), detail)
+    }
+
+    fn log(
+        &self,
+        at: u64,
+        user_id: i64,
+        event: AuditEvent,
+        address: Option<String>,
+        amount: Option<Coord>,
+        detail: Option<String>,
+    ) -> Result<(), ChainError> {
+        self.audit.append(&AuditEntry {
+            at,
+            user_id,
+            event,
+            address,
+            amount,
+            detail,
+        })
+    }
+}
+
+/// Ledger key for a Telegram user, kept apart from wallet addresses
+fn ledger_key(user_id: i64) -> String {
+    format!("telegram:{}", user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    const ALICE: i64 = 1001;
+    const NOW: u64 = 1_700_000_000;
+
+    fn desk(dir: &Path) -> PaymentDesk {
+        let config = TelegramConfig {
+            hot_wallet: Some("hot".to_string()),
+            max_per_payment: Some(Coord::from_num(5)),
+            daily_allowance: Some(Coord::from_num(8)),
+            allowances: HashMap::new(),
+        };
+        PaymentDesk::open_in_data_dir(config, dir).unwrap()
+    }
+
+    fn type_code(desk: &mut PaymentDesk, code: &str) -> KeypadOutcome {
+        let mut outcome = KeypadOutcome::Entered(0);
+        for digit in code.bytes() {
+            outcome = desk.press(ALICE, Keypress::Digit(digit - b'0'), NOW).unwrap();
+        }
+        outcome
+    }
+
+    #[test]
+    fn test_link_requires_signature_and_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = KeyPair::generate().unwrap();
+        let address = hex::encode(keypair.address());
+        let mut links = BotLinks::load_from_data_dir(dir.path()).unwrap();
+
+        let wrong_user = keypair.sign_message(&link_message(ALICE + 1)).unwrap();
+        assert!(links.link(ALICE, &address, &wrong_user).is_err());
+        let signature = keypair.sign_message(&link_message(ALICE)).unwrap();
+        links.link(ALICE, &address, &signature).unwrap();
+
+        let reloaded = BotLinks::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.address(ALICE), Some(address.as_str()));
+        assert_eq!(reloaded.address(ALICE + 1), None);
+    }
+
+    #[test]
+    fn test_payment_needs_code_and_respects_allowance() {
+        let dir = tempfile::tempdir().unwrap();
+        let keypair = KeyPair::generate().unwrap();
+        let address = hex::encode(keypair.address());
+        let mut desk = desk(dir.path());
+        let recipient = [7u8; 32];
+
+        assert!(desk.request(ALICE, recipient, Coord::from_num(1), NOW).is_err());
+        let signature = keypair.sign_message(&link_message(ALICE)).unwrap();
+        desk.link(ALICE, &address, &signature, NOW).unwrap();
+        assert!(matches!(
+            desk.request(ALICE, recipient, Coord::from_num(6), NOW),
+            Err(ChainError::SpendingLimit(_))
+        ));
+
+        // Wrong codes clear the entry and eventually cancel
+        let payment = desk.request(ALICE, recipient, Coord::from_num(5), NOW).unwrap();
+        let wrong: String = payment.code.chars().map(|c| if c == '0' { '1' } else { '0' }).collect();
+        assert!(matches!(type_code(&mut desk, &wrong), KeypadOutcome::WrongCode { attempts_left: 2 }));
+        assert!(matches!(type_code(&mut desk, &wrong), KeypadOutcome::WrongCode { attempts_left: 1 }));
+        assert!(matches!(type_code(&mut desk, &wrong), KeypadOutcome::Cancelled));
+        assert!(desk.press(ALICE, Keypress::Digit(1), NOW).is_err());
+
+        let payment = desk.request(ALICE, recipient, Coord::from_num(5), NOW).unwrap();
+        desk.press(ALICE, Keypress::Digit(9), NOW).unwrap();
+        assert!(matches!(desk.press(ALICE, Keypress::Backspace, NOW).unwrap(), KeypadOutcome::Entered(0)));
+        let KeypadOutcome::Confirmed(confirmed) = type_code(&mut desk, &payment.code) else {
+            panic!("right code must confirm");
+        };
+        desk.record_sent(&confirmed, confirmed.amount, &["ab".repeat(32)], NOW).unwrap();
+
+        // 5 of the 8 allowed today are used up
+        assert!(matches!(
+            desk.request(ALICE, recipient, Coord::from_num(4), NOW),
+            Err(ChainError::SpendingLimit(_))
+        ));
+        let (_, spent) = desk.allowance(ALICE, NOW).unwrap();
+        assert_eq!(spent, Coord::from_num(5));
+
+        let events: Vec<AuditEvent> = AuditLog::open_in_data_dir(dir.path())
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.event)
+            .collect();
+        use AuditEvent::*;
+        assert_eq!(
+            events,
+            [Rejected, Linked, Rejected, Requested, Cancelled, Requested, Confirmed, Sent, Rejected]
+        );
+    }
+
+    #[test]
+    fn test_keypress_round_trip() {
+        for key in keypad().into_iter().flatten() {
+            assert_eq!(Keypress::parse(&key.callback_data()), Some(key));
+        }
+        assert_eq!(Keypress::parse("pay:10"), None);
+        assert_eq!(Keypress::parse("other:1"), None);
+    }
+}
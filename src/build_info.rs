@@ -0,0 +1,81 @@
+//! What build of TrinityChain is running
+//!
+//! The git commit and build date are embedded by `build.rs`; every binary's
+//! `--version` and the `/api/version` endpoint report from here so they
+//! always agree.
+
+use crate::network::PROTOCOL_VERSION;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Full commit hash, or "unknown" when built outside a git checkout
+pub const GIT_COMMIT: &str = env!("TRINITY_GIT_COMMIT");
+/// RFC 3339 UTC date of the commit (or `SOURCE_DATE_EPOCH`), not the wall clock
+pub const BUILD_DATE: &str = env!("TRINITY_BUILD_DATE");
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_date: &'static str,
+    pub features: Vec<&'static str>,
+    pub protocol_version: u32,
+}
+
+/// Cargo features this build was compiled with
+pub fn features() -> Vec<&'static str> {
+    [
+        ("cli", cfg!(feature = "cli")),
+        ("api", cfg!(feature = "api")),
+        ("telegram", cfg!(feature = "telegram")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: VERSION,
+        git_commit: GIT_COMMIT,
+        build_date: BUILD_DATE,
+        features: features(),
+        protocol_version: PROTOCOL_VERSION,
+    }
+}
+
+static LONG_VERSION: Lazy<String> = Lazy::new(|| {
+    format!(
+        "{} ({} {}), protocol {}",
+        VERSION, GIT_COMMIT, BUILD_DATE, PROTOCOL_VERSION
+    )
+});
+
+/// Version string for `--version`, e.g.
+/// "0.2.0 (3f2c1ab… 2026-10-16T09:30:00Z), protocol 3"
+pub fn long_version() -> &'static str {
+    &LONG_VERSION
+}
+
+/// The `--version` line for `binary`
+pub fn version_line(binary: &str) -> String {
+    format!("{} {}", binary, long_version())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_is_consistent() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert!(long_version().starts_with(VERSION));
+        assert!(long_version().contains(GIT_COMMIT));
+        assert!(version_line("trinity-node")
+            .ends_with(&format!("protocol {}", PROTOCOL_VERSION)));
+        assert_eq!(info.features.contains(&"api"), cfg!(feature = "api"));
+    }
+}
@@ -362,6 +362,7 @@ mod tests {
                     difficulty: 1,
                     nonce: 0,
                     merkle_root: [0; 32],
+                    version: 0,
                 },
                 transactions: vec![],
             };
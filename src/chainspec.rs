@@ -0,0 +1,304 @@
+//! Chain parameters for launching private networks
+//!
+//! A [`ChainSpec`] describes a new network: its name, initial difficulty,
+//! proof-of-work, block subsidy schedule and the premine paid out by its
+//! genesis block. `trinity-node init-network` mines that genesis block and
+//! writes the spec to a [`ChainParams`] file together with the genesis hash
+//! and the network magic derived from it. Every node of the network sets
+//! `network.chain_params` to a copy of the file: the node rebuilds the
+//! genesis block from the spec, refuses to start if its hash differs from
+//! the recorded one or from the genesis of the stored chain, and validates
+//! blocks under the spec's rules.
+//!
+//! The magic is the start of the genesis hash, so every chain has one,
+//! including the built-in network. Peers exchange it after the handshake
+//! and disconnect from peers of another network.
+
+use crate::blockchain::{pow_algorithm, Block, Blockchain, ConsensusParams, Sha256Hash, SubsidySchedule};
+use crate::blockchain::{MAX_DIFFICULTY, MIN_DIFFICULTY};
+use crate::checkpoint::hex_hash;
+use crate::crypto::address_from_hex;
+use crate::geometry::{coord_checked_sum, Coord};
+use crate::transaction::{CoinbaseOutput, CoinbaseTx};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Bytes of the genesis hash that make up the network magic
+pub const MAGIC_LEN: usize = 4;
+
+/// Magic of the network whose genesis block hashes to `genesis_hash`
+pub fn network_magic(genesis_hash: &Sha256Hash) -> [u8; MAGIC_LEN] {
+    let mut magic = [0u8; MAGIC_LEN];
+    magic.copy_from_slice(&genesis_hash[..MAGIC_LEN]);
+    magic
+}
+
+/// Description of a new network, as written by its operator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+    /// Network id, used as `network.network_id` by its nodes
+    pub name: String,
+    /// Difficulty of the genesis block and of the blocks until the first
+    /// retarget
+    #[serde(default = "default_initial_difficulty")]
+    pub initial_difficulty: u32,
+    /// Genesis timestamp in milliseconds since the epoch
+    pub genesis_timestamp: u64,
+    /// Proof-of-work hash, as in `network.pow_algorithm`
+    #[serde(default = "default_pow_algorithm")]
+    pub pow_algorithm: String,
+    pub reward: RewardSpec,
+    /// Paid out by the genesis coinbase, the first entry as its beneficiary
+    pub premine: Vec<Allocation>,
+}
+
+/// Block subsidy schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewardSpec {
+    /// Subsidy of the first blocks
+    pub initial: f64,
+    /// Blocks between halvings; 0 never halves
+    #[serde(default)]
+    pub halving_interval: u64,
+}
+
+/// Triangle value the genesis block pays to `address`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Allocation {
+    /// Hex address
+    pub address: String,
+    pub amount: f64,
+}
+
+fn default_initial_difficulty() -> u32 {
+    MIN_DIFFICULTY
+}
+
+fn default_pow_algorithm() -> String {
+    "sha256".to_string()
+}
+
+impl ChainSpec {
+    /// Read a spec from a TOML file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid chain spec {}: {}", path.display(), e))
+    }
+
+    /// Consensus rules of the network
+    pub fn consensus(&self) -> Result<ConsensusParams, String> {
+        let pow = pow_algorithm(&self.pow_algorithm)
+            .ok_or_else(|| format!("Unknown pow_algorithm '{}'", self.pow_algorithm))?;
+        let initial = Coord::checked_from_num(self.reward.initial)
+            .filter(|initial| *initial > Coord::from_num(0) && *initial <= CoinbaseTx::MAX_REWARD_AREA)
+            .ok_or_else(|| {
+                format!(
+                    "reward.initial must be above 0 and at most {}, got {}",
+                    CoinbaseTx::MAX_REWARD_AREA,
+                    self.reward.initial
+                )
+            })?;
+        Ok(ConsensusParams {
+            pow,
+            subsidy: Some(SubsidySchedule {
+                initial,
+                halving_interval: self.reward.halving_interval,
+            }),
+            ..ConsensusParams::default()
+        })
+    }
+
+    /// The genesis coinbase, paying the premine
+    fn genesis_coinbase(&self) -> Result<CoinbaseTx, String> {
+        let mut payouts = Vec::with_capacity(self.premine.len());
+        for allocation in &self.premine {
+            let address = address_from_hex(&allocation.address)
+                .map_err(|e| format!("Premine address '{}': {}", allocation.address, e))?;
+            let amount = Coord::checked_from_num(allocation.amount)
+                .filter(|amount| *amount > Coord::from_num(0))
+                .ok_or_else(|| format!("Premine amount {} of {} must be above 0", allocation.amount, allocation.address))?;
+            payouts.push(CoinbaseOutput { address, amount });
+        }
+        if payouts.is_empty() {
+            return Err("The premine needs at least one allocation, the genesis beneficiary".to_string());
+        }
+        let reward_area = coord_checked_sum(payouts.iter().map(|output| output.amount)).map_err(|e| e.to_string())?;
+        let beneficiary = payouts.remove(0);
+        Ok(CoinbaseTx {
+            reward_area,
+            beneficiary_address: beneficiary.address,
+            nonce: 0,
+            height: 0,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: payouts,
+        })
+    }
+
+    /// Mine the genesis block of the network
+    pub fn genesis_block(&self) -> Result<Block, String> {
+        if self.name.trim().is_empty() {
+            return Err("The network needs a name".to_string());
+        }
+        if !(MIN_DIFFICULTY..=MAX_DIFFICULTY).contains(&self.initial_difficulty) {
+            return Err(format!(
+                "initial_difficulty must be between {} and {}, got {}",
+                MIN_DIFFICULTY, MAX_DIFFICULTY, self.initial_difficulty
+            ));
+        }
+        let consensus = self.consensus()?;
+        Blockchain::build_genesis_block(
+            self.genesis_coinbase()?,
+            self.genesis_timestamp,
+            self.initial_difficulty,
+            consensus.pow,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+/// A spec with the genesis hash and magic it produces, shared by every node
+/// of the network
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainParams {
+    #[serde(with = "hex_hash")]
+    pub genesis_hash: Sha256Hash,
+    /// Hex network magic
+    pub magic: String,
+    pub spec: ChainSpec,
+}
+
+impl ChainParams {
+    /// Mine the genesis block of `spec` and record its hash
+    pub fn generate(spec: ChainSpec) -> Result<(Self, Block), String> {
+        let genesis = spec.genesis_block()?;
+        let genesis_hash = genesis.hash();
+        let params = ChainParams {
+            genesis_hash,
+            magic: hex::encode(network_magic(&genesis_hash)),
+            spec,
+        };
+        Ok((params, genesis))
+    }
+
+    /// Read a params file and check that its spec still produces the
+    /// recorded genesis block, which is returned
+    pub fn load(path: &Path) -> Result<(Self, Block), String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        let params: ChainParams =
+            toml::from_str(&content).map_err(|e| format!("Invalid chain params {}: {}", path.display(), e))?;
+        let genesis = params.spec.genesis_block()?;
+        if genesis.hash() != params.genesis_hash {
+            return Err(format!(
+                "Chain params {} record genesis {}, but their spec produces {}",
+                path.display(),
+                hex::encode(params.genesis_hash),
+                hex::encode(genesis.hash())
+            ));
+        }
+        Ok((params, genesis))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = toml::to_string_pretty(self).map_err(|e| format!("Cannot serialize chain params: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+    }
+
+    /// `config.toml` lines that make a node join the network, with the
+    /// params file at `path`
+    pub fn config_snippet(&self, path: &Path) -> String {
+        format!(
+            "[network]\nnetwork_id = \"{}\"\nchain_params = \"{}\"\n",
+            self.spec.name,
+            path.display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Block;
+    use crate::miner::mine_block_with;
+    use crate::transaction::Transaction;
+
+    fn spec() -> ChainSpec {
+        toml::from_str(
+            r#"
+            name = "acme"
+            genesis_timestamp = 1767225600000
+            pow_algorithm = "fast"
+
+            [reward]
+            initial = 8.0
+            halving_interval = 2
+
+            [[premine]]
+            address = "0101010101010101010101010101010101010101010101010101010101010101"
+            amount = 500.0
+
+            [[premine]]
+            address = "0202020202020202020202020202020202020202020202020202020202020202"
+            amount = 250.5
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn coinbase_block(chain: &Blockchain, reward: f64) -> Block {
+        let tip = chain.blocks.last().unwrap();
+        let height = tip.header.height + 1;
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(reward),
+            beneficiary_address: [3u8; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let mut block = Block::new_with_clock(height, tip.hash(), tip.header.difficulty, vec![coinbase], chain.clock.as_ref());
+        block.header.timestamp = block.header.timestamp.max(tip.header.timestamp + 1);
+        mine_block_with(block, chain.consensus.pow).unwrap()
+    }
+
+    #[test]
+    fn test_params_build_the_premined_genesis_and_enforce_the_subsidy() {
+        let dir = tempfile::tempdir().unwrap();
+        let (params, genesis) = ChainParams::generate(spec()).unwrap();
+        assert_eq!(params.magic, hex::encode(&genesis.hash()[..MAGIC_LEN]));
+        let path = dir.path().join("chain_params.toml");
+        params.save(&path).unwrap();
+        let (loaded, loaded_genesis) = ChainParams::load(&path).unwrap();
+        assert_eq!((loaded, loaded_genesis.hash()), (params.clone(), genesis.hash()));
+        assert!(params.config_snippet(&path).contains("network_id = \"acme\""));
+
+        let mut chain =
+            Blockchain::from_genesis(genesis, params.spec.consensus().unwrap(), Box::new(crate::persistence::InMemoryPersistence::new()))
+                .unwrap();
+        assert_eq!(chain.chain_id(), params.genesis_hash);
+        assert_eq!(chain.state.get_balance(&[1u8; 32]), Coord::from_num(500));
+        assert_eq!(chain.state.get_balance(&[2u8; 32]), Coord::from_num(250.5));
+
+        // Height 1 gets the initial 8, heights 2 and 3 half of it
+        assert!(chain.apply_block(coinbase_block(&chain, 9.0)).is_err());
+        chain.apply_block(coinbase_block(&chain, 8.0)).unwrap();
+        assert_eq!(chain.max_subsidy(2), Coord::from_num(4));
+        assert!(chain.apply_block(coinbase_block(&chain, 8.0)).is_err());
+        chain.apply_block(coinbase_block(&chain, 4.0)).unwrap();
+
+        // A spec edited after the launch no longer matches its genesis
+        let tampered = std::fs::read_to_string(&path).unwrap().replace("250.5", "2500.5");
+        std::fs::write(&path, tampered).unwrap();
+        assert!(ChainParams::load(&path).unwrap_err().contains("produces"));
+
+        let mut empty = spec();
+        empty.premine.clear();
+        assert!(ChainParams::generate(empty).is_err());
+    }
+}
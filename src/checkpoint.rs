@@ -0,0 +1,221 @@
+//! Signed checkpoints (optional federated safety)
+//!
+//! While the network is young, operators may configure a set of authority
+//! keys under `[checkpoints]`. A checkpoint names the block hash at a height;
+//! once it carries valid signatures from at least `threshold` distinct
+//! authorities, the node treats it as a finality hint: competing blocks at
+//! that height are rejected and the chain is never reorganized below it.
+//! Nodes that leave checkpoints disabled ignore them entirely.
+
+use crate::blockchain::Sha256Hash;
+use crate::config::CheckpointConfig;
+use crate::crypto::{verify_signature, KeyPair};
+use crate::error::ChainError;
+use serde::{Deserialize, Serialize};
+
+/// Domain separator so checkpoint signatures cannot be replayed as anything else
+const SIGNING_DOMAIN: &[u8] = b"TrinityChain checkpoint";
+
+/// A block the authorities vouch for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u64,
+    #[serde(with = "hex_hash")]
+    pub hash: Sha256Hash,
+}
+
+impl Checkpoint {
+    /// Bytes each authority signs: the domain, the height (little endian)
+    /// and the block hash
+    pub fn signable_message(&self) -> Vec<u8> {
+        let mut message = SIGNING_DOMAIN.to_vec();
+        message.extend_from_slice(&self.height.to_le_bytes());
+        message.extend_from_slice(&self.hash);
+        message
+    }
+}
+
+/// One authority's signature over a [`Checkpoint`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointSignature {
+    /// Compressed secp256k1 public key, hex in JSON
+    #[serde(with = "hex_bytes")]
+    pub public_key: Vec<u8>,
+    /// Compact signature, hex in JSON
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub signatures: Vec<CheckpointSignature>,
+}
+
+impl SignedCheckpoint {
+    pub fn new(checkpoint: Checkpoint) -> Self {
+        SignedCheckpoint {
+            checkpoint,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Add `keypair`'s signature
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<(), ChainError> {
+        let signature = keypair.sign(&self.checkpoint.signable_message())?;
+        self.signatures.push(CheckpointSignature {
+            public_key: keypair.public_key_bytes().to_vec(),
+            signature: signature.to_vec(),
+        });
+        Ok(())
+    }
+}
+
+/// The configured authority keys and how many of them must sign
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointAuthority {
+    keys: Vec<Vec<u8>>,
+    threshold: usize,
+}
+
+impl CheckpointAuthority {
+    pub fn new(keys: Vec<Vec<u8>>, threshold: usize) -> Result<Self, ChainError> {
+        if threshold == 0 || threshold > keys.len() {
+            return Err(ChainError::CryptoError(format!(
+                "Checkpoint threshold must be between 1 and the {} authority key(s), got {}",
+                keys.len(),
+                threshold
+            )));
+        }
+        Ok(CheckpointAuthority { keys, threshold })
+    }
+
+    /// The authority described by `config`, or `None` when checkpoints are
+    /// disabled
+    pub fn from_config(config: &CheckpointConfig) -> Result<Option<Self>, ChainError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let keys = config
+            .authorities
+            .iter()
+            .map(|key| {
+                hex::decode(key)
+                    .map_err(|e| ChainError::CryptoError(format!("Invalid checkpoint authority key '{}': {}", key, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(keys, config.threshold).map(Some)
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn authority_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check that at least `threshold` distinct authorities signed. Signatures
+    /// by unknown keys or that do not verify are ignored.
+    pub fn verify(&self, signed: &SignedCheckpoint) -> Result<(), ChainError> {
+        let message = signed.checkpoint.signable_message();
+        let mut signers: Vec<&[u8]> = Vec::new();
+        for sig in &signed.signatures {
+            let authorized = self.keys.contains(&sig.public_key);
+            if authorized
+                && !signers.contains(&sig.public_key.as_slice())
+                && verify_signature(&sig.public_key, &message, &sig.signature).is_ok()
+            {
+                signers.push(&sig.public_key);
+            }
+        }
+        if signers.len() < self.threshold {
+            return Err(ChainError::CryptoError(format!(
+                "Checkpoint at height {} has {} valid authority signature(s), {} required",
+                signed.checkpoint.height,
+                signers.len(),
+                self.threshold
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+pub(crate) mod hex_hash {
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        super::hex_bytes::deserialize(deserializer)?
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("block hash must be 32 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authority(keys: &[&KeyPair], threshold: usize) -> CheckpointAuthority {
+        let keys = keys.iter().map(|k| k.public_key_bytes().to_vec()).collect();
+        CheckpointAuthority::new(keys, threshold).unwrap()
+    }
+
+    #[test]
+    fn test_threshold_of_distinct_authorities() {
+        let a = KeyPair::generate().unwrap();
+        let b = KeyPair::generate().unwrap();
+        let outsider = KeyPair::generate().unwrap();
+        let authority = authority(&[&a, &b], 2);
+
+        let mut signed = SignedCheckpoint::new(Checkpoint { height: 10, hash: [7u8; 32] });
+        signed.sign(&a).unwrap();
+        // A repeated signature or one from an unknown key does not count
+        signed.sign(&a).unwrap();
+        signed.sign(&outsider).unwrap();
+        assert!(authority.verify(&signed).is_err());
+
+        signed.sign(&b).unwrap();
+        assert!(authority.verify(&signed).is_ok());
+
+        // Signatures do not carry over to a different block
+        signed.checkpoint.hash = [8u8; 32];
+        assert!(authority.verify(&signed).is_err());
+    }
+
+    #[test]
+    fn test_config_and_json() {
+        let key = KeyPair::generate().unwrap();
+        let config = CheckpointConfig {
+            enabled: true,
+            authorities: vec![hex::encode(key.public_key_bytes())],
+            threshold: 2,
+        };
+        assert!(CheckpointAuthority::from_config(&config).is_err());
+        let disabled = CheckpointConfig { enabled: false, ..config.clone() };
+        assert_eq!(CheckpointAuthority::from_config(&disabled).unwrap(), None);
+
+        let mut signed = SignedCheckpoint::new(Checkpoint { height: 3, hash: [1u8; 32] });
+        signed.sign(&key).unwrap();
+        let json = serde_json::to_value(&signed).unwrap();
+        assert_eq!(json["checkpoint"]["hash"], hex::encode([1u8; 32]));
+        let parsed: SignedCheckpoint = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, signed);
+    }
+}
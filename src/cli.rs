@@ -1,8 +1,219 @@
 //! Shared CLI utilities
+//!
+//! Every binary exits through [`exit_on_error`], so failures map to the same
+//! exit codes everywhere. Scripts can tell a bad address from insufficient
+//! funds or an unreachable node without parsing messages; with `--json` the
+//! error is also printed as one line of JSON on stderr.
 
 use crate::blockchain::Blockchain;
-use crate::config::{load_config, Config};
+use crate::build_info;
+use crate::config::{args_with_data_dir, load_config, Config};
+use crate::error::ChainError;
 use crate::persistence::Database;
+use colored::Colorize;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Exit codes of the command-line tools. They are a stable interface: a
+/// code never changes meaning, new kinds get new codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Anything not covered below
+    General = 1,
+    /// Missing or malformed arguments
+    Usage = 2,
+    InvalidAddress = 3,
+    InsufficientFunds = 4,
+    /// Wallet missing, locked or unreadable
+    Wallet = 5,
+    /// Node or peer could not be reached
+    NodeUnreachable = 6,
+    /// Transaction or block refused by validation
+    Rejected = 7,
+    /// Config, database or file access failed
+    Storage = 8,
+    /// A wallet spending limit refused the payment, or it was not confirmed
+    SpendingLimit = 9,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+
+    /// Name of the kind in `--json` output
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::General => "general",
+            ErrorKind::Usage => "usage",
+            ErrorKind::InvalidAddress => "invalid_address",
+            ErrorKind::InsufficientFunds => "insufficient_funds",
+            ErrorKind::Wallet => "wallet",
+            ErrorKind::NodeUnreachable => "node_unreachable",
+            ErrorKind::Rejected => "rejected",
+            ErrorKind::Storage => "storage",
+            ErrorKind::SpendingLimit => "spending_limit",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CliError {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Usage, message)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": {
+                "code": self.kind.code(),
+                "exit_code": self.kind.exit_code(),
+                "message": self.message,
+            }
+        })
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<ChainError> for CliError {
+    fn from(err: ChainError) -> Self {
+        let kind = match err.root() {
+            ChainError::InsufficientFunds(_) => ErrorKind::InsufficientFunds,
+            ChainError::SpendingLimit(_) => ErrorKind::SpendingLimit,
+            ChainError::InvalidAddress(_) => ErrorKind::InvalidAddress,
+            ChainError::WalletError(_) | ChainError::AuthenticationError(_) => ErrorKind::Wallet,
+            ChainError::NetworkError(_) => ErrorKind::NodeUnreachable,
+            ChainError::DatabaseError(_) | ChainError::IoError(_) | ChainError::BincodeError(_) => {
+                ErrorKind::Storage
+            }
+            ChainError::InvalidTransaction(_)
+            | ChainError::InvalidBlock(_)
+            | ChainError::InvalidBlockLinkage
+            | ChainError::InvalidProofOfWork
+            | ChainError::InvalidMerkleRoot
+            | ChainError::DoubleSpendDetected(_)
+            | ChainError::TriangleNotFound(_)
+            | ChainError::UnknownUtxo(_)
+            | ChainError::InsufficientValue { .. }
+            | ChainError::AlreadySpent { .. }
+            | ChainError::DuplicateTransaction
+            | ChainError::BlockAlreadyExists
+            | ChainError::OrphanBlock
+            | ChainError::MempoolFull => ErrorKind::Rejected,
+            _ => ErrorKind::General,
+        };
+        CliError::new(kind, err.to_string())
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(err: std::io::Error) -> Self {
+        use std::io::ErrorKind as Io;
+        let kind = match err.kind() {
+            Io::ConnectionRefused | Io::ConnectionReset | Io::ConnectionAborted | Io::TimedOut => {
+                ErrorKind::NodeUnreachable
+            }
+            _ => ErrorKind::Storage,
+        };
+        CliError::new(kind, err.to_string())
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for CliError {
+    fn from(err: Box<dyn std::error::Error>) -> Self {
+        let err = match err.downcast::<CliError>() {
+            Ok(err) => return *err,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<ChainError>() {
+            Ok(err) => return (*err).into(),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<std::io::Error>() {
+            Ok(err) => return (*err).into(),
+            Err(err) => err,
+        };
+        let usage = err.is::<std::num::ParseIntError>() || err.is::<std::num::ParseFloatError>();
+        CliError::new(if usage { ErrorKind::Usage } else { ErrorKind::General }, err.to_string())
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::new(ErrorKind::General, message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        CliError::new(ErrorKind::General, message)
+    }
+}
+
+/// [`args_with_data_dir`] with the `--json` flag removed as well. The flag
+/// makes [`exit_on_error`] print errors as JSON, see [`json_output`].
+/// `--version` (or `-V`) prints the build version and exits.
+pub fn args() -> Vec<String> {
+    let (args, json) = split_json_arg(args_with_data_dir());
+    if args.iter().skip(1).any(|arg| arg == "--version" || arg == "-V") {
+        let binary = args
+            .first()
+            .and_then(|path| std::path::Path::new(path).file_name())
+            .map_or("trinitychain".into(), |name| name.to_string_lossy());
+        println!("{}", build_info::version_line(&binary));
+        std::process::exit(0);
+    }
+    if json {
+        JSON_ERRORS.store(true, Ordering::Relaxed);
+    }
+    args
+}
+
+fn split_json_arg(mut args: Vec<String>) -> (Vec<String>, bool) {
+    let before = args.len();
+    args.retain(|arg| arg != "--json");
+    let json = args.len() != before;
+    (args, json)
+}
+
+/// Whether `--json` was given; binaries then also print results as JSON
+pub fn json_output() -> bool {
+    JSON_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Exit with the code of the error, if any, after printing it to stderr
+pub fn exit_on_error<E: Into<CliError>>(result: Result<(), E>) {
+    if let Err(err) = result {
+        let err = err.into();
+        if json_output() {
+            eprintln!("{}", err.to_json());
+        } else {
+            eprintln!("{} {}", "❌".red(), err.message.red());
+        }
+        std::process::exit(err.kind.exit_code());
+    }
+}
 
 pub fn load_blockchain_from_config() -> Result<(Config, Blockchain), Box<dyn std::error::Error>> {
     let config = load_config()?;
@@ -10,3 +221,87 @@ pub fn load_blockchain_from_config() -> Result<(Config, Blockchain), Box<dyn std
     let blockchain = db.load_blockchain()?;
     Ok((config, blockchain))
 }
+
+/// Call the API of the node running on this machine, authenticating with the
+/// configured admin token. Returns the decoded JSON body of a 2xx response.
+pub async fn node_api_request(
+    config: &Config,
+    method: &str,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let host = format!("127.0.0.1:{}", config.api.admin_api_port(config.network.api_port));
+    let mut stream = tokio::net::TcpStream::connect(&host)
+        .await
+        .map_err(|e| format!("Cannot reach node API at {}: {}", host, e))?;
+
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut request = format!(
+        "{} /api{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    );
+    if let Some(token) = &config.api.admin_token {
+        request.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    request.push_str(&body);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let (head, payload) = response
+        .split_once("\r\n\r\n")
+        .ok_or("Malformed HTTP response from node")?;
+    let status: u16 = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or("Malformed HTTP status line from node")?;
+
+    let json: serde_json::Value =
+        serde_json::from_str(payload).unwrap_or_else(|_| serde_json::Value::String(payload.to_string()));
+    if !(200..300).contains(&status) {
+        let message = json
+            .get("error")
+            .and_then(|e| e.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| json.to_string());
+        return Err(format!("Node returned {}: {}", status, message).into());
+    }
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_map_to_stable_exit_codes() {
+        let short: CliError = ChainError::InsufficientFunds("short by 3".into()).into();
+        assert_eq!(short.kind.exit_code(), 4);
+        let bad_address: CliError = crate::crypto::address_from_hex("zz").unwrap_err().into();
+        assert_eq!(bad_address.kind, ErrorKind::InvalidAddress);
+        let refused: Box<dyn std::error::Error> =
+            Box::new(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        assert_eq!(CliError::from(refused).kind, ErrorKind::NodeUnreachable);
+        let bad_amount: Box<dyn std::error::Error> = "x".parse::<f64>().unwrap_err().into();
+        assert_eq!(CliError::from(bad_amount).kind, ErrorKind::Usage);
+
+        let json = short.to_json();
+        assert_eq!(json["error"]["code"], "insufficient_funds");
+        assert_eq!(json["error"]["exit_code"], 4);
+    }
+
+    #[test]
+    fn test_json_flag_is_removed_from_args() {
+        let args = vec!["send".to_string(), "--json".to_string(), "abc".to_string()];
+        assert_eq!(split_json_arg(args), (vec!["send".to_string(), "abc".to_string()], true));
+        assert!(!split_json_arg(vec!["send".to_string()]).1);
+    }
+}
@@ -0,0 +1,119 @@
+//! zstd compression for bulky P2P messages and snapshot files
+//!
+//! Peers advertising [`features::ZSTD`](crate::network::features::ZSTD)
+//! receive large messages, such as header batches during sync and full
+//! blocks, wrapped in a compressed frame. Snapshot files are written
+//! compressed and read back whether or not they are, recognized by the zstd
+//! frame magic. Level 0 turns compression off when writing; decompressing is
+//! always possible.
+
+use crate::error::ChainError;
+use std::io::Read;
+use std::path::Path;
+
+/// zstd level used unless configured otherwise
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Highest zstd level
+pub const MAX_LEVEL: i32 = 22;
+
+/// Messages smaller than this are not worth compressing
+pub const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// First bytes of every zstd frame
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// `level` if it is a usable zstd level, 0 meaning off
+pub fn check_level(level: i32) -> Result<i32, ChainError> {
+    if !(0..=MAX_LEVEL).contains(&level) {
+        return Err(ChainError::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Compression level must be between 0 (off) and {}, got {}", MAX_LEVEL, level),
+        )));
+    }
+    Ok(level)
+}
+
+pub fn compress(data: &[u8], level: i32) -> Result<Vec<u8>, ChainError> {
+    Ok(zstd::bulk::compress(data, level)?)
+}
+
+/// Decompress `data`, failing if it would grow beyond `limit` bytes so a
+/// small frame cannot expand into an arbitrarily large allocation
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ChainError> {
+    Ok(zstd::bulk::decompress(data, limit)?)
+}
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+/// Write a snapshot file, compressed at `level` unless it is 0
+pub fn write_snapshot(path: &Path, data: &[u8], level: i32) -> Result<(), ChainError> {
+    if check_level(level)? == 0 {
+        std::fs::write(path, data)?;
+    } else {
+        std::fs::write(path, compress(data, level)?)?;
+    }
+    Ok(())
+}
+
+/// Read a snapshot file written by [`write_snapshot`] at any level, failing
+/// if its contents exceed `limit` bytes once decompressed
+pub fn read_snapshot(path: &Path, limit: u64) -> Result<Vec<u8>, ChainError> {
+    let too_large = || {
+        ChainError::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Snapshot {} exceeds {} bytes", path.display(), limit),
+        ))
+    };
+    let data = std::fs::read(path)?;
+    if !is_compressed(&data) {
+        if data.len() as u64 > limit {
+            return Err(too_large());
+        }
+        return Ok(data);
+    }
+    // The frame header's content size cannot be trusted, so decode as a
+    // stream and stop one byte past the limit
+    let mut decoded = Vec::new();
+    zstd::stream::read::Decoder::new(data.as_slice())?
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut decoded)?;
+    if decoded.len() as u64 > limit {
+        return Err(too_large());
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_and_limits() {
+        let data = b"triangle ".repeat(1000);
+        let compressed = compress(&data, DEFAULT_LEVEL).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        for level in [0, DEFAULT_LEVEL] {
+            let path = dir.path().join(format!("snapshot-{}", level));
+            write_snapshot(&path, &data, level).unwrap();
+            assert_eq!(is_compressed(&std::fs::read(&path).unwrap()), level > 0);
+            assert_eq!(read_snapshot(&path, data.len() as u64).unwrap(), data);
+            assert!(read_snapshot(&path, data.len() as u64 - 1).is_err());
+        }
+
+        // A few kilobytes that would expand to 64 MiB are stopped at the limit
+        let bomb = dir.path().join("bomb");
+        write_snapshot(&bomb, &vec![0u8; 64 << 20], DEFAULT_LEVEL).unwrap();
+        assert!(std::fs::metadata(&bomb).unwrap().len() < 64 << 10);
+        assert!(read_snapshot(&bomb, 1 << 20).is_err());
+        assert!(check_level(23).is_err());
+        assert!(check_level(-1).is_err());
+    }
+}
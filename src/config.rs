@@ -1,18 +1,394 @@
 //! Configuration management for TrinityChain
+//!
+//! Everything a node or wallet stores on disk lives in one data directory,
+//! taken from `--data-dir`, then `TRINITY_DATA_DIR`, then
+//! `$XDG_DATA_HOME/trinitychain` (`~/.local/share/trinitychain` when unset).
+//! Files found at the locations used before that (`trinitychain.db` in the
+//! working directory, `~/.trinitychain/`) are moved there on first use.
+//!
+//! Settings are layered, each layer overriding the one before: built-in
+//! defaults, the selected profile's built-in presets, `config.toml`, the
+//! profile's `[profiles.<name>]` table in that file, `TRINITY_<SECTION>_<KEY>`
+//! environment variables and finally `--set section.key=value` flags. The
+//! profile comes from `--profile`, then `TRINITY_PROFILE`, then the file's
+//! top-level `profile` key.
 
-use serde::Deserialize;
+use crate::addnode::ADDNODE_FILE;
+use crate::banlist::BANLIST_FILE;
+use crate::blockchain::{pow_algorithm, PowAlgorithm, DEFAULT_POW, POW_ALGORITHMS};
+use crate::geometry::Coord;
+use crate::mempool::MempoolLimits;
+use crate::network::PeerLimits;
+use crate::sendqueue::{OverflowPolicy, SendQueueLimits};
+use crate::spending::SpendingPolicy;
+use crate::sync::SyncBufferLimits;
+use crate::units::{self, Unit};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-#[derive(Debug, Deserialize)]
+/// Environment variable overriding the data directory
+pub const DATA_DIR_ENV: &str = "TRINITY_DATA_DIR";
+
+/// Chain database file name inside the data directory
+pub const DATABASE_FILE: &str = "trinitychain.db";
+
+/// Config file read from the working directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Environment variable selecting the config profile
+pub const PROFILE_ENV: &str = "TRINITY_PROFILE";
+
+/// Prefix of environment variables overriding config keys, e.g.
+/// `TRINITY_NETWORK_API_PORT` for `network.api_port`
+pub const CONFIG_ENV_PREFIX: &str = "TRINITY_";
+
+const APP_DIR: &str = "trinitychain";
+
+/// Top-level sections of the config file
+const CONFIG_SECTIONS: &[&str] = &[
+    "network",
+    "database",
+    "miner",
+    "api",
+    "ai_validation",
+    "telemetry",
+    "maintenance",
+    "checkpoints",
+    "dev",
+    "display",
+    "wallet",
+    "telegram",
+    "notify",
+    "watchtower",
+    "analytics",
+    "trace",
+    "memory",
+];
+
+/// Built-in profiles, applied over the defaults and under `config.toml`
+const BUILTIN_PROFILES: &[(&str, &str)] = &[
+    ("dev", "[network]\nnetwork_id = \"devnet\"\n"),
+    (
+        "testnet",
+        "[network]\nnetwork_id = \"testnet\"\np2p_port = 18333\napi_port = 18080\n\
+         [database]\npath = \"testnet.db\"\n",
+    ),
+    (
+        "mainnet",
+        "[network]\nnetwork_id = \"mainnet\"\np2p_port = 8333\napi_port = 8080\n",
+    ),
+];
+
+/// Set from `--data-dir`, see [`args_with_data_dir`]
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set from `--profile`, see [`set_profile`]
+static PROFILE_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Set from `--set`, see [`set_config_overrides`]
+static CLI_OVERRIDES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Set by headless runs, see [`ignore_config_file`]
+static IGNORE_CONFIG_FILE: OnceLock<()> = OnceLock::new();
+
+/// Use `path` as the data directory for the rest of the process. Only the
+/// first call has an effect.
+pub fn set_data_dir(path: impl Into<PathBuf>) {
+    let _ = DATA_DIR_OVERRIDE.set(path.into());
+}
+
+/// Load config with the named profile for the rest of the process. Only the
+/// first call has an effect.
+pub fn set_profile(name: impl Into<String>) {
+    let _ = PROFILE_OVERRIDE.set(name.into());
+}
+
+/// Apply `section.key=value` overrides on top of every other config layer
+/// for the rest of the process. Only the first call has an effect.
+pub fn set_config_overrides(overrides: Vec<String>) {
+    let _ = CLI_OVERRIDES.set(overrides);
+}
+
+/// Leave `config.toml` out of the config layers for the rest of the
+/// process, so settings come from the defaults, the profile, `TRINITY_*`
+/// variables and `--set` alone, as suits a container
+pub fn ignore_config_file() {
+    let _ = IGNORE_CONFIG_FILE.set(());
+}
+
+/// The command line arguments with `--data-dir <dir>`, `--profile <name>`
+/// and `--set <section.key=value>` (each also as `--flag=value`) removed and
+/// applied, so every binary accepts them whatever its own argument parsing
+/// looks like
+pub fn args_with_data_dir() -> Vec<String> {
+    let global = split_global_args(std::env::args().collect());
+    if let Some(dir) = global.data_dir {
+        set_data_dir(dir);
+    }
+    if let Some(profile) = global.profile {
+        set_profile(profile);
+    }
+    if !global.overrides.is_empty() {
+        set_config_overrides(global.overrides);
+    }
+    global.rest
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct GlobalArgs {
+    rest: Vec<String>,
+    data_dir: Option<PathBuf>,
+    profile: Option<String>,
+    overrides: Vec<String>,
+}
+
+fn split_global_args(args: Vec<String>) -> GlobalArgs {
+    let mut global = GlobalArgs {
+        rest: Vec::with_capacity(args.len()),
+        ..GlobalArgs::default()
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        if !matches!(flag.as_str(), "--data-dir" | "--profile" | "--set") {
+            global.rest.push(arg);
+            continue;
+        }
+        let Some(value) = inline.or_else(|| args.next()) else {
+            continue;
+        };
+        match flag.as_str() {
+            "--data-dir" => global.data_dir = Some(PathBuf::from(value)),
+            "--profile" => global.profile = Some(value),
+            _ => global.overrides.push(value),
+        }
+    }
+    global
+}
+
+/// The data directory, which may not exist yet
+pub fn data_dir() -> PathBuf {
+    resolve_data_dir(
+        DATA_DIR_OVERRIDE.get().cloned(),
+        std::env::var_os(DATA_DIR_ENV),
+        std::env::var_os("XDG_DATA_HOME"),
+        dirs::home_dir(),
+    )
+}
+
+fn resolve_data_dir(
+    cli: Option<PathBuf>,
+    env: Option<OsString>,
+    xdg_data_home: Option<OsString>,
+    home: Option<PathBuf>,
+) -> PathBuf {
+    if let Some(dir) = cli {
+        return dir;
+    }
+    if let Some(dir) = env.filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    // The XDG spec says relative values are invalid and must be ignored
+    if let Some(base) = xdg_data_home.map(PathBuf::from).filter(|base| base.is_absolute()) {
+        return base.join(APP_DIR);
+    }
+    match home {
+        Some(home) => home.join(".local").join("share").join(APP_DIR),
+        None => PathBuf::from("."),
+    }
+}
+
+/// Create the data directory and move legacy files into it
+pub fn prepare_data_dir() -> io::Result<PathBuf> {
+    let dir = data_dir();
+    fs::create_dir_all(&dir)?;
+    let legacy_home = dirs::home_dir().map(|home| home.join(".trinitychain"));
+    for moved in migrate_legacy_files(&dir, Path::new("."), legacy_home.as_deref())? {
+        eprintln!("Moved {} to the data directory", moved.display());
+    }
+    Ok(dir)
+}
+
+/// Move files from where earlier versions kept them into `data_dir`: the
+/// chain database and node state from `working_dir`, everything in the old
+/// `~/.trinitychain` wallet directory. Files already present in `data_dir`
+/// are never overwritten. Returns the legacy paths that were moved.
+pub fn migrate_legacy_files(
+    data_dir: &Path,
+    working_dir: &Path,
+    legacy_home: Option<&Path>,
+) -> io::Result<Vec<PathBuf>> {
+    let mut candidates = vec![
+        (working_dir.join(DATABASE_FILE), DATABASE_FILE.to_string()),
+        // The default database path in config.toml
+        (working_dir.join("trinity.db"), DATABASE_FILE.to_string()),
+        (working_dir.join(BANLIST_FILE), BANLIST_FILE.to_string()),
+        (working_dir.join(ADDNODE_FILE), ADDNODE_FILE.to_string()),
+    ];
+    if let Some(legacy_home) = legacy_home.filter(|dir| dir.is_dir()) {
+        for entry in fs::read_dir(legacy_home)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                candidates.push((entry.path(), entry.file_name().to_string_lossy().into_owned()));
+            }
+        }
+    }
+
+    let mut moved = Vec::new();
+    for (from, name) in candidates {
+        let to = data_dir.join(name);
+        if !from.is_file() || to.exists() || same_file(&from, &to) {
+            continue;
+        }
+        if fs::rename(&from, &to).is_err() {
+            // Across file systems
+            fs::copy(&from, &to)?;
+            fs::remove_file(&from)?;
+        }
+        moved.push(from);
+    }
+    Ok(moved)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Config {
+    /// Profile the config was loaded with, if any
+    #[serde(default)]
+    pub profile: Option<String>,
     pub network: NetworkConfig,
     pub database: DatabaseConfig,
     pub miner: MinerConfig,
     #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
     pub ai_validation: AIValidationConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub checkpoints: CheckpointConfig,
+    #[serde(default)]
+    pub dev: DevConfig,
+    #[serde(default)]
+    pub display: DisplayConfig,
+    #[serde(default)]
+    pub wallet: WalletConfig,
+    #[serde(default)]
+    pub telegram: TelegramConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub watchtower: WatchtowerConfig,
+    #[serde(default)]
+    pub analytics: AnalyticsConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+}
+
+impl Config {
+    /// Directory for the chain database and node state (ban list, ...)
+    pub fn data_dir(&self) -> PathBuf {
+        data_dir()
+    }
+
+    /// The effective config as TOML, with the admin token masked
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        let mut table = toml::Table::try_from(self)?;
+        if let Some(toml::Value::Table(api)) = table.get_mut("api") {
+            if api.contains_key("admin_token") {
+                api.insert("admin_token".to_string(), toml::Value::String("********".to_string()));
+            }
+        }
+        toml::to_string_pretty(&table)
+    }
+
+    /// JSON Schema of `config.toml`, for deployment tooling to check config
+    /// files before rollout. Every key is optional, since the file is
+    /// layered over the defaults, and unknown keys are refused. The
+    /// `[profiles.<name>]` tables take the same sections.
+    pub fn schema() -> serde_json::Value {
+        let mut schema = serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default();
+        remove_required(&mut schema);
+        let mut sections = schema["properties"].clone();
+        if let Some(sections) = sections.as_object_mut() {
+            sections.remove("profile");
+        }
+        schema["properties"]["profiles"] = serde_json::json!({
+            "description": "Sections applied only under the named profile",
+            "type": "object",
+            "additionalProperties": {
+                "type": "object",
+                "properties": sections,
+                "additionalProperties": false
+            }
+        });
+        schema
+    }
 }
 
-#[derive(Debug, Deserialize)]
+fn remove_required(schema: &mut serde_json::Value) {
+    match schema {
+        serde_json::Value::Object(object) => {
+            object.remove("required");
+            object.values_mut().for_each(remove_required);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(remove_required),
+        _ => {}
+    }
+}
+
+/// Amounts are TRC numbers or strings with a unit, see [`units`]
+fn amount_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(vec![InstanceType::Number, InstanceType::String].into()),
+        ..Default::default()
+    }
+    .into()
+}
+
+fn amount_map_schema(generator: &mut SchemaGenerator) -> Schema {
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..Default::default()
+    };
+    schema.object().additional_properties = Some(Box::new(amount_schema(generator)));
+    schema.into()
+}
+
+/// Units are matched ignoring case; the usual spellings are listed
+fn unit_schema(_: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        enum_values: Some(["TRC", "trc", "mTRC", "mtrc", "millitrc", "area"].map(Into::into).to_vec()),
+        ..Default::default()
+    }
+    .into()
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct NetworkConfig {
     pub p2p_port: u16,
     pub api_port: u16,
@@ -20,25 +396,563 @@ pub struct NetworkConfig {
     pub network_id: String,
     #[serde(default)]
     pub bootstrap_peers: Vec<String>,
+    /// Connected peers the embedded miner waits for; 0 mines without any
     #[serde(default = "default_min_peers")]
     pub min_peers: u16,
+    /// Connections accepted from other nodes; when full, the worst-scoring
+    /// inbound peer is evicted to make room
+    #[serde(default = "default_max_inbound_peers")]
+    pub max_inbound_peers: usize,
+    #[serde(default = "default_max_outbound_peers")]
+    pub max_outbound_peers: usize,
+    /// Outbound slots kept free for addnode peers
+    #[serde(default = "default_reserved_outbound_peers")]
+    pub reserved_outbound_peers: usize,
+    /// SOCKS5 proxy (`host:port`, e.g. Tor at `127.0.0.1:9050`) for all
+    /// outbound peer connections. Required to connect to `.onion` peers.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// zstd level (1-22) for large messages to peers that accept
+    /// compression; 0 sends everything uncompressed
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
+    /// Bytes each peer may have waiting to be sent
+    #[serde(default = "default_send_queue_bytes")]
+    pub send_queue_bytes: usize,
+    /// `disconnect` a peer whose queue is too full for a block, or `drop`
+    /// the block for it; transactions that do not fit are always dropped
+    #[serde(default)]
+    pub send_queue_overflow: OverflowPolicy,
+    /// Proof-of-work hash of the network: `sha256`, or `fast` for test
+    /// networks that want mining to cost nothing. Every node of a network
+    /// must agree on it.
+    #[serde(default = "default_pow_algorithm")]
+    pub pow_algorithm: String,
+    /// Keep the cache of known-invalid blocks and transactions in
+    /// `known_invalid.json` across restarts
+    #[serde(default = "default_persist_known_invalid")]
+    pub persist_known_invalid: bool,
+    /// Chain parameters file written by `trinity-node init-network`; relative
+    /// paths are inside the data directory. Its genesis block and consensus
+    /// rules replace the built-in ones, and `pow_algorithm` is ignored.
+    #[serde(default)]
+    pub chain_params: Option<String>,
+}
+
+impl NetworkConfig {
+    pub fn send_queue_limits(&self) -> SendQueueLimits {
+        SendQueueLimits {
+            max_bytes: self.send_queue_bytes,
+            overflow: self.send_queue_overflow,
+        }
+    }
+
+    /// The algorithm named by `pow_algorithm`
+    pub fn pow_algorithm(&self) -> Result<&'static dyn PowAlgorithm, String> {
+        pow_algorithm(&self.pow_algorithm).ok_or_else(|| {
+            let known: Vec<_> = POW_ALGORITHMS.iter().map(|algorithm| algorithm.name()).collect();
+            format!(
+                "Unknown network.pow_algorithm '{}' (expected one of: {})",
+                self.pow_algorithm,
+                known.join(", ")
+            )
+        })
+    }
+
+    pub fn peer_limits(&self) -> PeerLimits {
+        PeerLimits {
+            max_inbound: self.max_inbound_peers,
+            max_outbound: self.max_outbound_peers,
+            reserved_outbound: self.reserved_outbound_peers,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct DatabaseConfig {
-    #[serde(default = "default_data_dir")]
+    /// SQLite file; relative paths are inside the data directory
+    #[serde(default = "default_database_path")]
     pub path: String,
+    /// Archive node: keep spent triangles for historical ownership and
+    /// provenance queries instead of only the UTXO set
+    #[serde(default)]
+    pub archive: bool,
+    /// zstd level (1-22) for snapshot files this node writes; 0 writes them
+    /// uncompressed. Either kind is read back.
+    #[serde(default = "default_compression_level")]
+    pub snapshot_compression_level: i32,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct MinerConfig {
     pub threads: usize,
     pub beneficiary_address: String,
     #[serde(default = "default_mining_enabled")]
     pub enabled: bool,
+    /// Pay each block's reward to a fresh HD address (indexed by block
+    /// height) derived from the mnemonic in `hd_mnemonic_file`
+    #[serde(default)]
+    pub rotate_addresses: bool,
+    #[serde(default)]
+    pub hd_mnemonic_file: Option<String>,
+    /// Rebuild the block being mined after this many seconds so new
+    /// transactions get in; 0 disables
+    #[serde(default = "default_template_refresh_secs")]
+    pub template_refresh_secs: u64,
+    /// Rebuild the block being mined once this many more transactions are
+    /// pending than it holds; 0 disables
+    #[serde(default = "default_template_refresh_txs")]
+    pub template_refresh_txs: usize,
+    /// Share of the time the miner spends hashing, 1-100
+    #[serde(default = "default_cpu_percent")]
+    pub cpu_percent: u8,
+    /// Local hours to pause mining, as `HH-HH`; may wrap past midnight
+    #[serde(default)]
+    pub pause_hours: Option<String>,
+    /// Pause mining while on battery, where that can be detected
+    #[serde(default)]
+    pub pause_on_battery: bool,
+    /// Include transactions sent by the wallets in the data directory in
+    /// every block this node mines, whatever their fee
+    #[serde(default)]
+    pub prioritize_wallets: bool,
+    /// Hex addresses whose transactions are included regardless of fee,
+    /// in addition to the wallets
+    #[serde(default)]
+    pub priority_addresses: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Bearer token required by operator endpoints (peer management, bans).
+    /// Those endpoints are disabled when unset.
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// Largest request body accepted; bigger requests get `413`
+    #[serde(default = "default_max_request_body_bytes")]
+    pub max_request_body_bytes: usize,
+    /// Requests still running after this long get `408`
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// When set, `network.api_port` serves only the public read-only
+    /// endpoints and the full API moves to this port
+    #[serde(default)]
+    pub admin_port: Option<u16>,
+    /// Address the admin listener binds to
+    #[serde(default = "default_admin_bind")]
+    pub admin_bind: String,
+    /// Lifetime of a wallet session opened by `/api/wallet/unlock` that
+    /// asks for none
+    #[serde(default = "default_wallet_session_secs")]
+    pub wallet_session_secs: u64,
+    /// Longest wallet session a client may ask for
+    #[serde(default = "default_wallet_session_max_secs")]
+    pub wallet_session_max_secs: u64,
+}
+
+impl ApiConfig {
+    /// Port the full API (mining, wallets, peer management) is served on
+    pub fn admin_api_port(&self, api_port: u16) -> u16 {
+        self.admin_port.unwrap_or(api_port)
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            admin_token: None,
+            max_request_body_bytes: default_max_request_body_bytes(),
+            request_timeout_secs: default_request_timeout_secs(),
+            admin_port: None,
+            admin_bind: default_admin_bind(),
+            wallet_session_secs: default_wallet_session_secs(),
+            wallet_session_max_secs: default_wallet_session_max_secs(),
+        }
+    }
+}
+
+/// Opt-in anonymized usage reporting, see [`crate::telemetry`]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// `http://` URL the report is POSTed to
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default = "default_telemetry_interval")]
+    pub interval_secs: u64,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: None,
+            interval_secs: default_telemetry_interval(),
+        }
+    }
+}
+
+/// Transaction propagation tracing for debugging, see [`crate::txtrace`].
+/// Off by default; a node without it relays traced transactions untraced.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TraceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Name the node reports its hops under; defaults to its P2P port
+    #[serde(default)]
+    pub name: Option<String>,
+    /// `http://` URL of the `/api/trace/report` endpoint hop reports are
+    /// POSTed to
+    #[serde(default)]
+    pub aggregator: Option<String>,
+}
+
+/// Caps on what the node holds in memory for unconfirmed data. A full
+/// mempool evicts the transactions paying least per byte for better-paying
+/// ones; a full sync buffer drops its highest blocks, which are downloaded
+/// again later.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct MemoryConfig {
+    #[serde(default = "default_mempool_max_transactions")]
+    pub mempool_max_transactions: usize,
+    /// Summed serialized size of the pending transactions
+    #[serde(default = "default_mempool_max_bytes")]
+    pub mempool_max_bytes: usize,
+    /// Downloaded blocks waiting for their predecessors during sync
+    #[serde(default = "default_sync_buffer_blocks")]
+    pub sync_buffer_blocks: usize,
+    #[serde(default = "default_sync_buffer_bytes")]
+    pub sync_buffer_bytes: usize,
+}
+
+impl MemoryConfig {
+    pub fn mempool_limits(&self) -> MempoolLimits {
+        MempoolLimits {
+            max_transactions: self.mempool_max_transactions,
+            max_bytes: self.mempool_max_bytes,
+        }
+    }
+
+    pub fn sync_buffer_limits(&self) -> SyncBufferLimits {
+        SyncBufferLimits {
+            max_blocks: self.sync_buffer_blocks,
+            max_bytes: self.sync_buffer_bytes,
+        }
+    }
+}
+
+fn default_mempool_max_transactions() -> usize {
+    crate::mempool::DEFAULT_MEMPOOL_MAX_TRANSACTIONS
+}
+
+fn default_mempool_max_bytes() -> usize {
+    crate::mempool::DEFAULT_MEMPOOL_MAX_BYTES
+}
+
+fn default_sync_buffer_blocks() -> usize {
+    crate::sync::DEFAULT_SYNC_BUFFER_BLOCKS
+}
+
+fn default_sync_buffer_bytes() -> usize {
+    crate::sync::DEFAULT_SYNC_BUFFER_BYTES
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            mempool_max_transactions: default_mempool_max_transactions(),
+            mempool_max_bytes: default_mempool_max_bytes(),
+            sync_buffer_blocks: default_sync_buffer_blocks(),
+            sync_buffer_bytes: default_sync_buffer_bytes(),
+        }
+    }
+}
+
+/// Scheduled database upkeep, see [`crate::maintenance`]. An interval of
+/// zero turns the task off.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct MaintenanceConfig {
+    #[serde(default = "default_compact_interval")]
+    pub compact_interval_hours: u64,
+    #[serde(default = "default_integrity_check_interval")]
+    pub integrity_check_interval_hours: u64,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            compact_interval_hours: default_compact_interval(),
+            integrity_check_interval_hours: default_integrity_check_interval(),
+        }
+    }
+}
+
+/// Optional signed checkpoints, see [`crate::checkpoint`]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct CheckpointConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Hex-encoded compressed public keys allowed to sign checkpoints
+    #[serde(default)]
+    pub authorities: Vec<String>,
+    /// Distinct authority signatures a checkpoint needs
+    #[serde(default = "default_checkpoint_threshold")]
+    pub threshold: usize,
 }
 
-#[derive(Debug, Deserialize)]
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            authorities: Vec::new(),
+            threshold: default_checkpoint_threshold(),
+        }
+    }
+}
+
+/// Development-only settings. Network faults are for exercising sync and
+/// reorganization on local testnets, see [`crate::netsim`]; never set them
+/// on a node serving a real network.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DevConfig {
+    /// Delay added to every P2P message this node sends
+    #[serde(default)]
+    pub simulate_latency_ms: u64,
+    /// Fraction (0 to 1) of sent P2P messages silently dropped
+    #[serde(default)]
+    pub drop_rate: f64,
+}
+
+/// How CLI tools show amounts, see [`crate::units`]. The API always reports
+/// exact TRC amounts regardless.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// "TRC", "mTRC" or "area"; also the unit of bare amounts typed on the
+    /// command line
+    #[serde(default)]
+    #[schemars(schema_with = "unit_schema")]
+    pub unit: Unit,
+    /// Decimal places shown
+    #[serde(default = "default_display_decimals")]
+    pub decimals: usize,
+}
+
+impl DisplayConfig {
+    /// `value` rounded in the configured unit, with its symbol
+    pub fn format(&self, value: Coord) -> String {
+        format!(
+            "{} {}",
+            units::format_rounded(value, self.unit, self.decimals),
+            self.unit
+        )
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            unit: Unit::default(),
+            decimals: default_display_decimals(),
+        }
+    }
+}
+
+/// Spending limits enforced whenever a local wallet signs a payment, in
+/// `trinity-send` and the API's wallet endpoints. Amounts are numbers in TRC
+/// or strings with a unit (`"500mTRC"`); unset limits do not apply.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct WalletConfig {
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_optional_amount",
+        serialize_with = "units::serialize_optional_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub max_per_transaction: Option<Coord>,
+    /// Total over any 24 hours
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_optional_amount",
+        serialize_with = "units::serialize_optional_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub max_per_day: Option<Coord>,
+    /// Payments above this must be confirmed explicitly
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_optional_amount",
+        serialize_with = "units::serialize_optional_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub confirm_above: Option<Coord>,
+    /// Unused HD addresses in a row after which `trinity-wallet audit`
+    /// reports later funds as hidden from a restoring wallet
+    #[serde(default = "default_gap_limit")]
+    pub gap_limit: usize,
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        Self {
+            max_per_transaction: None,
+            max_per_day: None,
+            confirm_above: None,
+            gap_limit: default_gap_limit(),
+        }
+    }
+}
+
+impl WalletConfig {
+    pub fn spending_policy(&self) -> SpendingPolicy {
+        SpendingPolicy {
+            max_per_transaction: self.max_per_transaction,
+            max_per_day: self.max_per_day,
+            confirm_above: self.confirm_above,
+        }
+    }
+}
+
+/// Payments through the Telegram bot, see [`crate::botpay`]. Amounts are
+/// written as for [`WalletConfig`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// Wallet the bot pays from; payments are disabled when unset
+    #[serde(default)]
+    pub hot_wallet: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_optional_amount",
+        serialize_with = "units::serialize_optional_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub max_per_payment: Option<Coord>,
+    /// What a linked user may send over any 24 hours. Users without an
+    /// allowance cannot pay.
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_optional_amount",
+        serialize_with = "units::serialize_optional_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub daily_allowance: Option<Coord>,
+    /// Per-user allowances keyed by Telegram user id, overriding
+    /// `daily_allowance`
+    #[serde(
+        default,
+        deserialize_with = "units::deserialize_amount_map",
+        serialize_with = "units::serialize_amount_map"
+    )]
+    #[schemars(schema_with = "amount_map_schema")]
+    pub allowances: HashMap<String, Coord>,
+}
+
+impl TelegramConfig {
+    /// Limits for a Telegram user, or `None` if they may not pay at all
+    pub fn spending_policy(&self, user_id: i64) -> Option<SpendingPolicy> {
+        self.hot_wallet.as_ref()?;
+        let allowance = self
+            .allowances
+            .get(&user_id.to_string())
+            .copied()
+            .or(self.daily_allowance)?;
+        Some(SpendingPolicy {
+            max_per_transaction: self.max_per_payment,
+            max_per_day: Some(allowance),
+            confirm_above: None,
+        })
+    }
+}
+
+/// Shell commands run on chain events, see [`crate::notify`]. `%s` is
+/// replaced by the block hash or txid.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Run when the best block changes
+    #[serde(default)]
+    pub blocknotify: Option<String>,
+    /// Run when a transaction touching a local wallet enters the mempool
+    /// and again when it is mined
+    #[serde(default)]
+    pub walletnotify: Option<String>,
+}
+
+/// Alerts on spends from cold-storage addresses, see
+/// [`crate::watchtower`]. Alerts are always logged; `webhook` and
+/// `telegram_chat_id` add deliveries.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct WatchtowerConfig {
+    /// Hex addresses that should never spend; the watchtower is off when
+    /// empty
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// `http://` URL each alert is POSTed to as JSON
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Telegram chat sent each alert, by the bot whose token is in
+    /// `TELOXIDE_TOKEN`
+    #[serde(default)]
+    pub telegram_chat_id: Option<i64>,
+    /// Depth at which a spend is reported as final
+    #[serde(default = "default_watchtower_confirmations")]
+    pub confirmations: u64,
+}
+
+fn default_watchtower_confirmations() -> u64 {
+    6
+}
+
+impl Default for WatchtowerConfig {
+    fn default() -> Self {
+        Self {
+            addresses: Vec::new(),
+            webhook: None,
+            telegram_chat_id: None,
+            confirmations: default_watchtower_confirmations(),
+        }
+    }
+}
+
+/// Chain statistics served under `/api/analytics`
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    /// Outputs worth less than this count as dust in the UTXO statistics
+    #[serde(
+        default = "default_dust_threshold",
+        deserialize_with = "units::deserialize_amount",
+        serialize_with = "units::serialize_amount"
+    )]
+    #[schemars(schema_with = "amount_schema")]
+    pub dust_threshold: Coord,
+}
+
+fn default_dust_threshold() -> Coord {
+    crate::blockchain::DEFAULT_DUST_THRESHOLD
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        Self { dust_threshold: default_dust_threshold() }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct AIValidationConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
@@ -67,6 +981,10 @@ impl Default for AIValidationConfig {
     }
 }
 
+fn default_checkpoint_threshold() -> usize {
+    1
+}
+
 fn default_enabled() -> bool {
     true
 }
@@ -83,44 +1001,234 @@ fn default_timeout() -> u64 {
     30
 }
 
-pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
-    let config_str = fs::read_to_string("config.toml").unwrap_or_default();
-    let config: Config = if config_str.is_empty() {
-        // Provide sane defaults when config.toml is absent
+/// Used for everything config.toml and the other layers leave unset
+impl Default for Config {
+    fn default() -> Self {
         Config {
+            profile: None,
             network: NetworkConfig {
                 p2p_port: 8333,
                 api_port: 8080,
                 network_id: default_network_id(),
                 bootstrap_peers: Vec::new(),
                 min_peers: default_min_peers(),
+                max_inbound_peers: default_max_inbound_peers(),
+                max_outbound_peers: default_max_outbound_peers(),
+                reserved_outbound_peers: default_reserved_outbound_peers(),
+                proxy: None,
+                compression_level: default_compression_level(),
+                send_queue_bytes: default_send_queue_bytes(),
+                send_queue_overflow: OverflowPolicy::default(),
+                pow_algorithm: default_pow_algorithm(),
+                persist_known_invalid: default_persist_known_invalid(),
+                chain_params: None,
             },
             database: DatabaseConfig {
-                path: default_data_dir(),
+                path: default_database_path(),
+                archive: false,
+                snapshot_compression_level: default_compression_level(),
             },
             miner: MinerConfig {
                 threads: 1,
                 beneficiary_address: "00000000000000000000000000000000".to_string(),
                 enabled: default_mining_enabled(),
+                rotate_addresses: false,
+                hd_mnemonic_file: None,
+                template_refresh_secs: default_template_refresh_secs(),
+                template_refresh_txs: default_template_refresh_txs(),
+                cpu_percent: default_cpu_percent(),
+                pause_hours: None,
+                pause_on_battery: false,
+                prioritize_wallets: false,
+                priority_addresses: Vec::new(),
             },
+            api: ApiConfig::default(),
             ai_validation: AIValidationConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            checkpoints: CheckpointConfig::default(),
+            dev: DevConfig::default(),
+            display: DisplayConfig::default(),
+            wallet: WalletConfig::default(),
+            telegram: TelegramConfig::default(),
+            notify: NotifyConfig::default(),
+            watchtower: WatchtowerConfig::default(),
+            analytics: AnalyticsConfig::default(),
+            trace: TraceConfig::default(),
+            memory: MemoryConfig::default(),
         }
+    }
+}
+
+/// Load the effective config, see the module docs for how layers combine
+pub fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
+    let data_dir = prepare_data_dir()?;
+    let file = if IGNORE_CONFIG_FILE.get().is_some() {
+        String::new()
     } else {
-        toml::from_str(&config_str)?
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("Cannot read {}: {}", CONFIG_FILE, err).into()),
+        }
     };
+    let profile = PROFILE_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| std::env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty()));
+    let overrides = CLI_OVERRIDES.get().map(Vec::as_slice).unwrap_or_default();
+    let mut config = resolve_config(&file, profile, std::env::vars(), overrides)?;
 
     // Validate critical values
     if config.database.path.is_empty() {
         return Err("database.path must be set in config.toml".into());
     }
+    if config.database.path != ":memory:" && Path::new(&config.database.path).is_relative() {
+        config.database.path = data_dir.join(&config.database.path).to_string_lossy().into_owned();
+    }
+    if let Some(path) = config.network.chain_params.as_mut().filter(|path| Path::new(path.as_str()).is_relative()) {
+        *path = data_dir.join(&*path).to_string_lossy().into_owned();
+    }
 
     if config.miner.beneficiary_address.is_empty() {
         return Err("miner.beneficiary_address must be set in config.toml".into());
     }
+    config.network.pow_algorithm()?;
+
+    Ok(config)
+}
+
+/// Combine the config layers: defaults, built-in profile, `file`, the file's
+/// profile table, `TRINITY_*` variables from `env` and `overrides`
+fn resolve_config(
+    file: &str,
+    profile: Option<String>,
+    env: impl IntoIterator<Item = (String, String)>,
+    overrides: &[String],
+) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut merged = toml::Table::try_from(Config::default())?;
+    let mut file: toml::Table = toml::from_str(file).map_err(|e| format!("Invalid {}: {}", CONFIG_FILE, e))?;
+    let mut file_profiles = match file.remove("profiles") {
+        Some(toml::Value::Table(profiles)) => profiles,
+        Some(_) => return Err("profiles in config.toml must be a table".into()),
+        None => toml::Table::new(),
+    };
+    let profile = match (profile, file.remove("profile")) {
+        (Some(name), _) => Some(name),
+        (None, Some(toml::Value::String(name))) => Some(name),
+        (None, Some(_)) => return Err("profile in config.toml must be a string".into()),
+        (None, None) => None,
+    };
+
+    if let Some(name) = &profile {
+        let builtin = BUILTIN_PROFILES.iter().find(|(builtin, _)| builtin == name);
+        if builtin.is_none() && !file_profiles.contains_key(name) {
+            return Err(format!(
+                "Unknown profile '{}' (built-in profiles are dev, testnet and mainnet)",
+                name
+            )
+            .into());
+        }
+        if let Some((_, preset)) = builtin {
+            merge_tables(&mut merged, toml::from_str(preset)?);
+        }
+        merge_tables(&mut merged, file);
+        match file_profiles.remove(name) {
+            Some(toml::Value::Table(table)) => merge_tables(&mut merged, table),
+            Some(_) => return Err(format!("profiles.{} in config.toml must be a table", name).into()),
+            None => {}
+        }
+    } else {
+        merge_tables(&mut merged, file);
+    }
 
+    let mut env: Vec<_> = env
+        .into_iter()
+        .filter_map(|(name, value)| Some((env_key(&name)?, value)))
+        .collect();
+    env.sort();
+    for (path, value) in env {
+        set_key(&mut merged, &path, &value).map_err(|e| format!("{}{}: {}", CONFIG_ENV_PREFIX, path, e))?;
+    }
+    for assignment in overrides {
+        let (path, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("--set {}: expected section.key=value", assignment))?;
+        set_key(&mut merged, path.trim(), value.trim()).map_err(|e| format!("--set {}: {}", assignment, e))?;
+    }
+
+    let mut config: Config = merged.try_into()?;
+    config.profile = profile;
     Ok(config)
 }
 
+/// Recursively overlay `layer` on `base`; tables merge, anything else replaces
+fn merge_tables(base: &mut toml::Table, layer: toml::Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => merge_tables(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The `section.key` path for a `TRINITY_SECTION_KEY` variable, or `None`
+/// for variables that are not config overrides (such as `TRINITY_DATA_DIR`)
+fn env_key(name: &str) -> Option<String> {
+    let rest = name.strip_prefix(CONFIG_ENV_PREFIX)?.to_ascii_lowercase();
+    CONFIG_SECTIONS.iter().find_map(|section| {
+        let key = rest.strip_prefix(section)?.strip_prefix('_')?;
+        (!key.is_empty()).then(|| format!("{}.{}", section, key))
+    })
+}
+
+/// Set the dotted `path` in `table` from its text form. Values keep the type
+/// of the setting they replace: text stays text and lists may be written
+/// comma-separated. Anything else is read as a TOML value, else as text, so
+/// a number meant as text for an unset setting must be quoted (`"1234"`).
+fn set_key(table: &mut toml::Table, path: &str, raw: &str) -> Result<(), String> {
+    let mut keys: Vec<&str> = path.split('.').map(str::trim).collect();
+    let last = keys.pop().filter(|key| !key.is_empty()).ok_or("empty key")?;
+    match keys.first() {
+        None => return Err("expected section.key".to_string()),
+        Some(section) if !CONFIG_SECTIONS.contains(section) => {
+            return Err(format!("unknown section '{}'", section));
+        }
+        Some(_) => {}
+    }
+
+    let mut table = table;
+    for key in keys {
+        table = table
+            .entry(key)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| format!("'{}' is not a section", key))?;
+    }
+    let parsed = || {
+        toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+    };
+    let value = match table.get(last) {
+        Some(toml::Value::String(_)) => toml::Value::String(raw.to_string()),
+        Some(toml::Value::Array(_)) => parsed().filter(toml::Value::is_array).unwrap_or_else(|| {
+            toml::Value::Array(
+                raw.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            )
+        }),
+        _ => parsed().unwrap_or_else(|| toml::Value::String(raw.to_string())),
+    };
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
 fn default_network_id() -> String {
     "devnet".to_string()
 }
@@ -129,10 +1237,261 @@ fn default_min_peers() -> u16 {
     1
 }
 
-fn default_data_dir() -> String {
-    "./data".to_string()
+fn default_max_inbound_peers() -> usize {
+    PeerLimits::default().max_inbound
+}
+
+fn default_max_outbound_peers() -> usize {
+    PeerLimits::default().max_outbound
+}
+
+fn default_reserved_outbound_peers() -> usize {
+    PeerLimits::default().reserved_outbound
+}
+
+fn default_compression_level() -> i32 {
+    crate::compression::DEFAULT_LEVEL
+}
+
+fn default_pow_algorithm() -> String {
+    DEFAULT_POW.name().to_string()
+}
+
+fn default_persist_known_invalid() -> bool {
+    true
+}
+
+fn default_send_queue_bytes() -> usize {
+    crate::sendqueue::DEFAULT_MAX_QUEUE_BYTES
+}
+
+fn default_gap_limit() -> usize {
+    crate::hdwallet::DEFAULT_GAP_LIMIT
+}
+
+fn default_template_refresh_secs() -> u64 {
+    30
+}
+
+fn default_template_refresh_txs() -> usize {
+    10
+}
+
+fn default_cpu_percent() -> u8 {
+    100
+}
+
+fn default_max_request_body_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_admin_bind() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_wallet_session_secs() -> u64 {
+    300
+}
+
+fn default_wallet_session_max_secs() -> u64 {
+    3600
+}
+
+fn default_display_decimals() -> usize {
+    6
+}
+
+fn default_telemetry_interval() -> u64 {
+    6 * 60 * 60
+}
+
+fn default_compact_interval() -> u64 {
+    7 * 24
+}
+
+fn default_integrity_check_interval() -> u64 {
+    24
+}
+
+fn default_database_path() -> String {
+    DATABASE_FILE.to_string()
 }
 
 fn default_mining_enabled() -> bool {
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dir_precedence() {
+        let home = Some(PathBuf::from("/home/alice"));
+        let resolve = |cli: Option<&str>, env: Option<&str>, xdg: Option<&str>| {
+            resolve_data_dir(cli.map(PathBuf::from), env.map(OsString::from), xdg.map(OsString::from), home.clone())
+        };
+        assert_eq!(resolve(Some("/cli"), Some("/env"), Some("/xdg")), PathBuf::from("/cli"));
+        assert_eq!(resolve(None, Some("/env"), Some("/xdg")), PathBuf::from("/env"));
+        assert_eq!(resolve(None, Some(""), Some("/xdg")), PathBuf::from("/xdg/trinitychain"));
+        assert_eq!(resolve(None, None, Some("relative")), PathBuf::from("/home/alice/.local/share/trinitychain"));
+        assert_eq!(resolve_data_dir(None, None, None, None), PathBuf::from("."));
+
+        let args = |args: &[&str]| split_global_args(args.iter().map(|arg| arg.to_string()).collect());
+        let global = args(&["trinity-send", "--data-dir", "/d", "abc", "5"]);
+        assert_eq!(global.rest, vec!["trinity-send".to_string(), "abc".to_string(), "5".to_string()]);
+        assert_eq!(global.data_dir, Some(PathBuf::from("/d")));
+        assert_eq!(args(&["trinity-node", "--data-dir=/d"]).data_dir, Some(PathBuf::from("/d")));
+        assert_eq!(args(&["trinity-node", "run"]).data_dir, None);
+
+        let global = args(&["trinity-node", "--profile", "testnet", "--set=api.admin_port=3001", "--set", "dev.drop_rate=0.1"]);
+        assert_eq!(global.rest, vec!["trinity-node".to_string()]);
+        assert_eq!(global.profile.as_deref(), Some("testnet"));
+        assert_eq!(global.overrides, vec!["api.admin_port=3001".to_string(), "dev.drop_rate=0.1".to_string()]);
+    }
+
+    #[test]
+    fn test_migrate_legacy_files() {
+        let working_dir = tempfile::tempdir().unwrap();
+        let legacy_home = tempfile::tempdir().unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+        fs::write(working_dir.path().join("trinity.db"), "chain").unwrap();
+        fs::write(working_dir.path().join(BANLIST_FILE), "bans").unwrap();
+        fs::write(legacy_home.path().join("wallet.json"), "old wallet").unwrap();
+        fs::write(legacy_home.path().join("addressbook.json"), "contacts").unwrap();
+        fs::write(data_dir.path().join("wallet.json"), "new wallet").unwrap();
+
+        let moved = migrate_legacy_files(data_dir.path(), working_dir.path(), Some(legacy_home.path())).unwrap();
+        assert_eq!(moved.len(), 3);
+        assert_eq!(fs::read_to_string(data_dir.path().join(DATABASE_FILE)).unwrap(), "chain");
+        assert_eq!(fs::read_to_string(data_dir.path().join(BANLIST_FILE)).unwrap(), "bans");
+        assert_eq!(fs::read_to_string(data_dir.path().join("addressbook.json")).unwrap(), "contacts");
+        // An existing file wins and the legacy one is left alone
+        assert_eq!(fs::read_to_string(data_dir.path().join("wallet.json")).unwrap(), "new wallet");
+        assert!(legacy_home.path().join("wallet.json").exists());
+        assert!(!working_dir.path().join("trinity.db").exists());
+
+        // Migrating a directory into itself does nothing
+        assert!(migrate_legacy_files(data_dir.path(), data_dir.path(), Some(data_dir.path())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_config_layers() {
+        let file = "[network]\np2p_port = 9000\napi_port = 3000\nbootstrap_peers = [\"a:1\"]\n\
+                    [profiles.testnet.network]\napi_port = 4000\n\
+                    [profiles.staging.api]\nadmin_port = 5001\n";
+        let env = |vars: &[(&str, &str)]| {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let config = resolve_config(file, None, env(&[]), &[]).unwrap();
+        assert_eq!(config.network.p2p_port, 9000);
+        assert_eq!(config.network.network_id, "devnet");
+        assert_eq!(config.miner.threads, 1);
+
+        // Built-in preset under the file, the file's profile table over it
+        let config = resolve_config(file, Some("testnet".to_string()), env(&[]), &[]).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("testnet"));
+        assert_eq!(config.network.network_id, "testnet");
+        assert_eq!(config.network.p2p_port, 9000);
+        assert_eq!(config.network.api_port, 4000);
+        assert_eq!(config.database.path, "testnet.db");
+
+        let vars = env(&[
+            ("TRINITY_NETWORK_API_PORT", "5000"),
+            ("TRINITY_NETWORK_BOOTSTRAP_PEERS", "b:2, c:3"),
+            ("TRINITY_AI_VALIDATION_ENABLED", "false"),
+            // Unset text settings need quotes to stay text
+            ("TRINITY_API_ADMIN_TOKEN", "\"12345\""),
+            ("TRINITY_DATA_DIR", "/ignored"),
+        ]);
+        let overrides = ["network.api_port=6000".to_string(), "wallet.max_per_day=\"5 TRC\"".to_string()];
+        let config = resolve_config(file, Some("staging".to_string()), vars, &overrides).unwrap();
+        assert_eq!(config.api.admin_port, Some(5001));
+        assert_eq!(config.network.api_port, 6000);
+        assert_eq!(config.network.bootstrap_peers, vec!["b:2".to_string(), "c:3".to_string()]);
+        assert!(!config.ai_validation.enabled);
+        assert_eq!(config.wallet.max_per_day, Some(Coord::from_num(5)));
+
+        let shown = config.to_toml().unwrap();
+        assert!(shown.contains("profile = \"staging\""));
+        assert!(!shown.contains("12345"));
+        assert!(shown.contains("max_per_day = \"5\""));
+        let reloaded: Config = toml::from_str(&shown).unwrap();
+        assert_eq!(reloaded.wallet.max_per_day, Some(Coord::from_num(5)));
+
+        assert!(resolve_config(file, Some("nope".to_string()), env(&[]), &[]).is_err());
+        assert!(resolve_config(file, None, env(&[]), &["bogus.key=1".to_string()]).is_err());
+        assert!(resolve_config(file, None, env(&[]), &["network.api_port".to_string()]).is_err());
+        assert!(resolve_config(file, None, env(&[("TRINITY_NETWORK_API_PORT", "many")]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_wallet_limits_accept_numbers_and_units() {
+        let wallet: WalletConfig =
+            toml::from_str("max_per_transaction = 100\nmax_per_day = 2.5\nconfirm_above = \"500mTRC\"").unwrap();
+        let policy = wallet.spending_policy();
+        assert_eq!(policy.max_per_transaction, Some(Coord::from_num(100)));
+        assert_eq!(policy.max_per_day, Some(Coord::from_num(2.5)));
+        assert_eq!(policy.confirm_above, Some(Coord::from_num(0.5)));
+
+        assert_eq!(toml::from_str::<WalletConfig>("").unwrap().spending_policy(), SpendingPolicy::default());
+        assert!(toml::from_str::<WalletConfig>("max_per_day = \"5 BTC\"").is_err());
+    }
+
+    #[test]
+    fn test_telegram_allowances() {
+        let telegram: TelegramConfig = toml::from_str(
+            "hot_wallet = \"bot\"\nmax_per_payment = 5\n[allowances]\n\"42\" = \"2500mTRC\"",
+        )
+        .unwrap();
+        let policy = telegram.spending_policy(42).unwrap();
+        assert_eq!(policy.max_per_transaction, Some(Coord::from_num(5)));
+        assert_eq!(policy.max_per_day, Some(Coord::from_num(2.5)));
+        // No default allowance, and no payments at all without a hot wallet
+        assert!(telegram.spending_policy(7).is_none());
+        let disabled = TelegramConfig {
+            hot_wallet: None,
+            ..telegram
+        };
+        assert!(disabled.spending_policy(42).is_none());
+    }
+
+    #[test]
+    fn test_schema_describes_shipped_config() {
+        fn resolve<'a>(root: &'a serde_json::Value, schema: &'a serde_json::Value) -> &'a serde_json::Value {
+            let reference = schema["$ref"].as_str().or_else(|| schema["allOf"][0]["$ref"].as_str());
+            match reference {
+                Some(reference) => &root["definitions"][reference.trim_start_matches("#/definitions/")],
+                None => schema,
+            }
+        }
+        fn check(root: &serde_json::Value, schema: &serde_json::Value, table: &toml::Table, path: &str) {
+            for (key, value) in table {
+                let property = resolve(root, &schema["properties"][key]);
+                assert!(!property.is_null(), "{}{} is missing from the schema", path, key);
+                if let toml::Value::Table(table) = value {
+                    check(root, property, table, &format!("{}{}.", path, key));
+                }
+            }
+        }
+
+        let schema = Config::schema();
+        let shipped: toml::Table = toml::from_str(include_str!("../config.toml")).unwrap();
+        check(&schema, &schema, &shipped, "");
+        let profile: toml::Table = toml::from_str(BUILTIN_PROFILES[1].1).unwrap();
+        check(&schema, &schema["properties"]["profiles"]["additionalProperties"], &profile, "profiles.testnet.");
+
+        // Typos are refused, and nothing is required of a file layered
+        // over the defaults
+        assert_eq!(resolve(&schema, &schema["properties"]["network"])["additionalProperties"], false);
+        assert!(resolve(&schema, &schema["properties"]["network"])["properties"]["p2p_prot"].is_null());
+        assert!(!schema.to_string().contains("\"required\""));
+    }
+}
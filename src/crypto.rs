@@ -9,11 +9,47 @@ use secp256k1::{
     All, Message, PublicKey, Secp256k1, SecretKey,
 };
 use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
 
 /// A thread-safe, lazily initialized Secp256k1 context.
 /// This prevents repeated, unnecessary context creation.
 static SECP256K1_CONTEXT: Lazy<Secp256k1<All>> = Lazy::new(Secp256k1::new);
 
+/// Most signatures remembered as already verified; about 2 MB of digests
+pub const VERIFIED_SIGNATURE_CACHE_SIZE: usize = 50_000;
+
+/// Signatures that have passed [`verify_signature`], so a signature checked
+/// ahead of time (in the mempool or the block download pipeline) is not
+/// checked again when its block is applied. Keyed by a digest of the key,
+/// message and signature together, and only ever holding successes.
+static VERIFIED_SIGNATURES: Lazy<Mutex<VerifiedSignatures>> =
+    Lazy::new(|| Mutex::new(VerifiedSignatures::default()));
+
+#[derive(Default)]
+struct VerifiedSignatures {
+    digests: HashSet<[u8; 32]>,
+    order: VecDeque<[u8; 32]>,
+}
+
+impl VerifiedSignatures {
+    fn insert(&mut self, digest: [u8; 32]) {
+        if !self.digests.insert(digest) {
+            return;
+        }
+        self.order.push_back(digest);
+        if self.order.len() > VERIFIED_SIGNATURE_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.digests.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Domain-separation prefix for off-chain signed messages, so a message
+/// signature can never be passed off as a transaction signature.
+pub const SIGNED_MESSAGE_PREFIX: &[u8] = b"TrinityChain Signed Message:\n";
+
 /// Type alias for the derived address, which is a 32-byte hash.
 /// We use a fixed-size array for internal type safety and performance.
 pub type Address = [u8; 32];
@@ -34,16 +70,16 @@ pub fn address_to_hex(addr: &Address) -> String {
 /// Convert a hex string to an address.
 pub fn address_from_hex(hex_str: &str) -> Result<Address, ChainError> {
     let bytes = hex::decode(hex_str)
-        .map_err(|e| ChainError::CryptoError(format!("Invalid hex address: {}", e)))?;
+        .map_err(|e| ChainError::InvalidAddress(format!("not hex: {}", e)))?;
     if bytes.len() != 32 {
-        return Err(ChainError::CryptoError(format!(
-            "Address must be 32 bytes, got {}",
+        return Err(ChainError::InvalidAddress(format!(
+            "must be 32 bytes, got {}",
             bytes.len()
         )));
     }
     bytes
         .try_into()
-        .map_err(|_| ChainError::CryptoError("Failed to convert bytes into address".to_string()))
+        .map_err(|_| ChainError::InvalidAddress("not 32 bytes".to_string()))
 }
 
 #[derive(Debug, Clone)]
@@ -120,6 +156,48 @@ impl KeyPair {
         let compact_sig_bytes: [u8; COMPACT_SIGNATURE_SIZE] = signature.serialize_compact();
         Ok(compact_sig_bytes)
     }
+
+    /// Signs a text message to prove control of this key's address off-chain.
+    /// Returns the hex-encoded compressed public key followed by the compact signature,
+    /// since an address alone is not enough to recover the key.
+    pub fn sign_message(&self, message: &str) -> Result<String, ChainError> {
+        let signature = self.sign(&signed_message_bytes(&self.address(), message))?;
+        let mut proof = self.public_key_bytes().to_vec();
+        proof.extend_from_slice(&signature);
+        Ok(hex::encode(proof))
+    }
+}
+
+/// The bytes actually signed for an off-chain message: the prefix, the
+/// claimed address and the length-prefixed message.
+fn signed_message_bytes(address: &Address, message: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SIGNED_MESSAGE_PREFIX.len() + 40 + message.len());
+    bytes.extend_from_slice(SIGNED_MESSAGE_PREFIX);
+    bytes.extend_from_slice(address);
+    bytes.extend_from_slice(&(message.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(message.as_bytes());
+    bytes
+}
+
+/// Verifies a signature produced by [`KeyPair::sign_message`] for `address`.
+pub fn verify_message(address: &Address, message: &str, signature_hex: &str) -> Result<(), ChainError> {
+    let proof = hex::decode(signature_hex)
+        .map_err(|e| ChainError::CryptoError(format!("Invalid signature hex: {}", e)))?;
+    if proof.len() != PUBLIC_KEY_SIZE + COMPACT_SIGNATURE_SIZE {
+        return Err(ChainError::CryptoError(format!(
+            "Message signature must be exactly {} bytes, got {}",
+            PUBLIC_KEY_SIZE + COMPACT_SIGNATURE_SIZE,
+            proof.len()
+        )));
+    }
+    let (public_key, signature) = proof.split_at(PUBLIC_KEY_SIZE);
+    let signer: Address = Sha256::digest(public_key).into();
+    if &signer != address {
+        return Err(ChainError::CryptoError(
+            "Signature was made by a different address".to_string(),
+        ));
+    }
+    verify_signature(public_key, &signed_message_bytes(address, message), signature)
 }
 
 /// Verifies an ECDSA signature given the raw public key bytes, message, and signature bytes.
@@ -144,23 +222,36 @@ pub fn verify_signature(
         )));
     }
 
+    // Hash the message
+    let digest = Sha256::digest(message);
+
+    let cache_key: [u8; 32] = Sha256::new()
+        .chain_update(public_key_bytes)
+        .chain_update(digest)
+        .chain_update(signature_bytes)
+        .finalize()
+        .into();
+    if VERIFIED_SIGNATURES.lock().is_ok_and(|cache| cache.digests.contains(&cache_key)) {
+        return Ok(());
+    }
+
     // Using the context from the static Lazy
     let public_key = PublicKey::from_slice(public_key_bytes)
         .map_err(|e| ChainError::CryptoError(format!("Invalid public key: {}", e)))?;
 
-    // Hash the message
-    let digest = Sha256::digest(message);
-
     let message = Message::from_digest_slice(&digest)
         .map_err(|e| ChainError::CryptoError(format!("Failed to create message: {}", e)))?;
 
     let signature = Signature::from_compact(signature_bytes)
         .map_err(|e| ChainError::CryptoError(format!("Invalid signature: {}", e)))?;
 
-    // Return unit type on success, error on failure.
     SECP256K1_CONTEXT
         .verify_ecdsa(&message, &signature, &public_key)
-        .map_err(|_| ChainError::CryptoError("Signature verification failed".to_string()))
+        .map_err(|_| ChainError::CryptoError("Signature verification failed".to_string()))?;
+    if let Ok(mut cache) = VERIFIED_SIGNATURES.lock() {
+        cache.insert(cache_key);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -236,6 +327,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cached_verification_is_bound_to_key_message_and_signature() {
+        let keypair = KeyPair::generate().unwrap();
+        let other = KeyPair::generate().unwrap();
+        let message = b"Cached message";
+        let signature = keypair.sign(message).unwrap();
+
+        assert!(verify_signature(&keypair.public_key_bytes(), message, &signature).is_ok());
+        // Answered from the cache the second time
+        assert!(verify_signature(&keypair.public_key_bytes(), message, &signature).is_ok());
+
+        // Nothing cached for one input carries over to the others
+        assert!(verify_signature(&other.public_key_bytes(), message, &signature).is_err());
+        assert!(verify_signature(&keypair.public_key_bytes(), b"Other message", &signature).is_err());
+        let mut forged = signature;
+        forged[40] ^= 1;
+        assert!(verify_signature(&keypair.public_key_bytes(), message, &forged).is_err());
+    }
+
+    #[test]
+    fn test_signed_message_is_bound_to_address() {
+        let keypair = KeyPair::generate().unwrap();
+        let other = KeyPair::generate().unwrap();
+        let address = keypair.address();
+        let proof = keypair.sign_message("link account 42").unwrap();
+
+        assert!(verify_message(&address, "link account 42", &proof).is_ok());
+        assert!(verify_message(&address, "link account 43", &proof).is_err());
+        assert!(verify_message(&other.address(), "link account 42", &proof).is_err());
+
+        // A plain signature over the same text is not a message signature
+        let mut raw = keypair.public_key_bytes().to_vec();
+        raw.extend_from_slice(&keypair.sign(b"link account 42").unwrap());
+        assert!(verify_message(&address, "link account 42", &hex::encode(raw)).is_err());
+    }
+
     #[test]
     fn test_invalid_key_or_sig_length_check() {
         let keypair = KeyPair::generate().unwrap();
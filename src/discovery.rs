@@ -1,10 +1,297 @@
 //! Peer discovery and bootstrap module
 //!
-//! Handles finding and connecting to peers via DNS seeds and manual configuration
+//! Handles finding and connecting to peers via DNS seeds, manual
+//! configuration and peer exchange (PEX): nodes answer `GetAddr` with
+//! addresses they know, which land in an [`AddressManager`].
 
 use crate::error::ChainError;
 use crate::network::Node;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashSet;
+use std::net::IpAddr;
+use tracing::warn;
+
+/// Most addresses a single `Addr` message may carry
+pub const MAX_ADDR_PER_MESSAGE: usize = 1000;
+/// Buckets for addresses heard about but never connected to
+pub const NEW_BUCKET_COUNT: usize = 64;
+/// Buckets for addresses we have successfully connected to
+pub const TRIED_BUCKET_COUNT: usize = 16;
+/// Entries per bucket
+pub const BUCKET_SIZE: usize = 16;
+/// New buckets the addresses from one source network group can reach, so a
+/// single peer cannot flood the table with addresses it controls
+const NEW_BUCKETS_PER_SOURCE_GROUP: u64 = 8;
+/// Tried buckets one network group can reach
+const TRIED_BUCKETS_PER_GROUP: u64 = 4;
+/// Addresses not seen for this long (seconds) are not accepted
+const ADDRESS_HORIZON: u64 = 30 * 24 * 60 * 60;
+/// Failed connection attempts after which a never-tried address is dropped
+const MAX_FAILED_ATTEMPTS: u32 = 3;
+
+/// A gossiped peer address and when it was last known to be reachable
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerAddress {
+    pub node: Node,
+    /// Unix time in seconds
+    pub last_seen: u64,
+}
+
+/// Coarse network an address belongs to: the /16 of an IPv4 address, the
+/// /32 of an IPv6 one. Addresses in one group are likely run by one party.
+pub fn network_group(node: &Node) -> String {
+    match node.host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            let [a, b, ..] = ip.octets();
+            format!("{}.{}", a, b)
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let [a, b, ..] = ip.segments();
+            format!("{:x}:{:x}", a, b)
+        }
+        // Onion names are random, so group them coarsely by first character
+        Err(_) if node.is_onion() => format!("onion:{}", &node.host[..1]),
+        Err(_) => node.host.to_ascii_lowercase(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AddressEntry {
+    address: PeerAddress,
+    /// Network group of the peer that told us about the address
+    source_group: String,
+    attempts: u32,
+}
+
+/// Known peer addresses in bucketed tables, after Bitcoin's addrman.
+///
+/// Gossiped addresses go to the "new" table, in a bucket chosen by a keyed
+/// hash of the sender's network group and the address's group. Addresses we
+/// connected to move to the "tried" table. Buckets hold [`BUCKET_SIZE`]
+/// entries and the secret key makes placement unpredictable, so a peer
+/// feeding us addresses can only ever occupy a small part of either table.
+pub struct AddressManager {
+    key: [u8; 32],
+    new: Vec<Vec<AddressEntry>>,
+    tried: Vec<Vec<AddressEntry>>,
+}
+
+impl AddressManager {
+    pub fn new() -> Self {
+        Self {
+            key: rand::random(),
+            new: vec![Vec::new(); NEW_BUCKET_COUNT],
+            tried: vec![Vec::new(); TRIED_BUCKET_COUNT],
+        }
+    }
+
+    fn keyed_hash(&self, parts: &[&[u8]]) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.key);
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().expect("digest has 8 bytes"))
+    }
+
+    fn new_bucket(&self, node: &Node, source_group: &str) -> usize {
+        let group = network_group(node);
+        let slot = self.keyed_hash(&[group.as_bytes(), source_group.as_bytes()]) % NEW_BUCKETS_PER_SOURCE_GROUP;
+        (self.keyed_hash(&[source_group.as_bytes(), &slot.to_le_bytes()]) % NEW_BUCKET_COUNT as u64) as usize
+    }
+
+    fn tried_bucket(&self, node: &Node) -> usize {
+        let slot = self.keyed_hash(&[node.addr().as_bytes()]) % TRIED_BUCKETS_PER_GROUP;
+        let group = network_group(node);
+        (self.keyed_hash(&[group.as_bytes(), &slot.to_le_bytes()]) % TRIED_BUCKET_COUNT as u64) as usize
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &AddressEntry> {
+        self.new.iter().chain(&self.tried).flatten()
+    }
+
+    fn entry_mut(&mut self, node: &Node) -> Option<&mut AddressEntry> {
+        self.new
+            .iter_mut()
+            .chain(self.tried.iter_mut())
+            .flatten()
+            .find(|entry| entry.address.node == *node)
+    }
+
+    /// Take `node` out of the new table
+    fn take_new(&mut self, node: &Node) -> Option<AddressEntry> {
+        self.new.iter_mut().find_map(|bucket| {
+            let position = bucket.iter().position(|entry| entry.address.node == *node)?;
+            Some(bucket.remove(position))
+        })
+    }
+
+    /// Put `entry` in its new bucket, displacing the stalest entry of a full
+    /// bucket if `entry` is fresher. Returns whether it was stored.
+    fn insert_new(&mut self, entry: AddressEntry) -> bool {
+        let index = self.new_bucket(&entry.address.node, &entry.source_group);
+        let bucket = &mut self.new[index];
+        if bucket.len() < BUCKET_SIZE {
+            bucket.push(entry);
+            return true;
+        }
+        let Some((stalest, last_seen)) = bucket
+            .iter()
+            .enumerate()
+            .map(|(i, known)| (i, known.address.last_seen))
+            .min_by_key(|(_, last_seen)| *last_seen)
+        else {
+            return false;
+        };
+        if last_seen >= entry.address.last_seen {
+            return false;
+        }
+        bucket[stalest] = entry;
+        true
+    }
+
+    /// Record addresses gossiped by `source`. Unusable addresses and ones
+    /// not seen within the last 30 days are ignored; timestamps from the
+    /// future are clamped to `now`. Returns how many were new to us.
+    pub fn add(&mut self, addresses: &[PeerAddress], source: &Node, now: u64) -> usize {
+        let source_group = network_group(source);
+        let mut added = 0;
+        for address in addresses.iter().take(MAX_ADDR_PER_MESSAGE) {
+            if !is_usable(&address.node) || address.last_seen.saturating_add(ADDRESS_HORIZON) < now {
+                continue;
+            }
+            let last_seen = address.last_seen.min(now);
+            if let Some(known) = self.entry_mut(&address.node) {
+                known.address.last_seen = known.address.last_seen.max(last_seen);
+                continue;
+            }
+            let entry = AddressEntry {
+                address: PeerAddress { node: address.node.clone(), last_seen },
+                source_group: source_group.clone(),
+                attempts: 0,
+            };
+            if self.insert_new(entry) {
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// Move `node` to the tried table after a successful connection. A full
+    /// tried bucket sends its stalest entry back to the new table.
+    pub fn mark_good(&mut self, node: &Node, now: u64) {
+        if !is_usable(node) {
+            return;
+        }
+        if let Some(entry) = self.tried.iter_mut().flatten().find(|entry| entry.address.node == *node) {
+            entry.address.last_seen = now;
+            entry.attempts = 0;
+            return;
+        }
+        let mut entry = self.take_new(node).unwrap_or_else(|| AddressEntry {
+            address: PeerAddress { node: node.clone(), last_seen: now },
+            source_group: network_group(node),
+            attempts: 0,
+        });
+        entry.address.last_seen = now;
+        entry.attempts = 0;
+
+        let index = self.tried_bucket(node);
+        if self.tried[index].len() >= BUCKET_SIZE {
+            let bucket = &mut self.tried[index];
+            let stalest = (0..bucket.len())
+                .min_by_key(|&i| bucket[i].address.last_seen)
+                .expect("bucket is full");
+            let demoted = bucket.remove(stalest);
+            self.insert_new(demoted);
+        }
+        self.tried[index].push(entry);
+    }
+
+    /// Count a failed connection attempt; never-tried addresses are dropped
+    /// after a few failures
+    pub fn mark_failed(&mut self, node: &Node) {
+        let Some(entry) = self.entry_mut(node) else {
+            return;
+        };
+        entry.attempts += 1;
+        if entry.attempts >= MAX_FAILED_ATTEMPTS {
+            self.take_new(node);
+        }
+    }
+
+    /// Up to `count` addresses to connect to, alternating between the tried
+    /// and new tables in random order and skipping those `exclude` rejects
+    pub fn select(&self, count: usize, exclude: impl Fn(&Node) -> bool) -> Vec<Node> {
+        let mut tried = shuffled(&self.tried).into_iter().filter(|node| !exclude(node));
+        let mut new = shuffled(&self.new).into_iter().filter(|node| !exclude(node));
+        let mut selected = Vec::new();
+        while selected.len() < count {
+            let next = if selected.len() % 2 == 0 {
+                tried.next().or_else(|| new.next())
+            } else {
+                new.next().or_else(|| tried.next())
+            };
+            match next {
+                Some(node) => selected.push(node.clone()),
+                None => break,
+            }
+        }
+        selected
+    }
+
+    /// A random sample of at most `max` known addresses, to answer `GetAddr`
+    pub fn sample(&self, max: usize) -> Vec<PeerAddress> {
+        let mut addresses: Vec<PeerAddress> = self.entries().map(|entry| entry.address.clone()).collect();
+        addresses.shuffle(&mut rand::thread_rng());
+        addresses.truncate(max.min(MAX_ADDR_PER_MESSAGE));
+        addresses
+    }
+
+    pub fn contains(&self, node: &Node) -> bool {
+        self.entries().any(|entry| entry.address.node == *node)
+    }
+
+    /// Whether `node` is in the tried table
+    pub fn is_tried(&self, node: &Node) -> bool {
+        self.tried.iter().flatten().any(|entry| entry.address.node == *node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for AddressManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn shuffled(table: &[Vec<AddressEntry>]) -> Vec<&Node> {
+    let mut nodes: Vec<&Node> = table.iter().flatten().map(|entry| &entry.address.node).collect();
+    nodes.shuffle(&mut rand::thread_rng());
+    nodes
+}
+
+/// Addresses worth storing: a real port and, for IP literals, not the
+/// unspecified or a multicast address
+fn is_usable(node: &Node) -> bool {
+    node.port != 0
+        && !node.host.is_empty()
+        && node
+            .host
+            .parse::<IpAddr>()
+            .map_or(true, |ip| !ip.is_unspecified() && !ip.is_multicast())
+}
 
 /// DNS seed configuration for peer discovery
 #[derive(Debug, Clone)]
@@ -49,6 +336,7 @@ pub struct PeerDiscovery {
     dns_seeds: Vec<DnsSeed>,
     bootstrap_peers: Vec<Node>,
     known_peers: HashSet<String>,
+    addresses: AddressManager,
 }
 
 impl PeerDiscovery {
@@ -58,6 +346,7 @@ impl PeerDiscovery {
             dns_seeds: Vec::new(),
             bootstrap_peers: Vec::new(),
             known_peers: HashSet::new(),
+            addresses: AddressManager::new(),
         }
     }
 
@@ -76,6 +365,31 @@ impl PeerDiscovery {
         self.known_peers.insert(peer.addr());
     }
 
+    /// Addresses learned from DNS seeds, bootstrap peers and peer exchange
+    pub fn addresses(&self) -> &AddressManager {
+        &self.addresses
+    }
+
+    pub fn addresses_mut(&mut self) -> &mut AddressManager {
+        &mut self.addresses
+    }
+
+    /// Candidates for `count` new outbound connections: known addresses
+    /// from peer exchange first, then bootstrap peers, skipping those
+    /// `exclude` rejects (e.g. connected or banned)
+    pub fn outbound_candidates(&self, count: usize, exclude: impl Fn(&Node) -> bool) -> Vec<Node> {
+        let mut candidates = self.addresses.select(count, &exclude);
+        for peer in &self.bootstrap_peers {
+            if candidates.len() >= count {
+                break;
+            }
+            if !exclude(peer) && !candidates.contains(peer) {
+                candidates.push(peer.clone());
+            }
+        }
+        candidates
+    }
+
     /// Get all known peers
     pub fn get_known_peers(&self) -> Vec<Node> {
         // For now, return bootstrap peers
@@ -97,7 +411,7 @@ impl PeerDiscovery {
                     }
                 }
                 Err(e) => {
-                    eprintln!("⚠️  Failed to resolve DNS seed: {}", e);
+                    warn!("⚠️  Failed to resolve DNS seed: {}", e);
                 }
             }
         }
@@ -112,26 +426,22 @@ impl PeerDiscovery {
             }
         }
 
+        // Seeds vouch for what they return, so each node is its own source
+        let now = chrono::Utc::now().timestamp() as u64;
+        for node in &discovered {
+            let address = PeerAddress { node: node.clone(), last_seen: now };
+            self.addresses.add(&[address], node, now);
+        }
+
         Ok(discovered)
     }
 
     /// Get random peers for connection attempts
     pub fn get_random_peers(&self, count: usize) -> Vec<Node> {
-        use rand::seq::SliceRandom;
-
         let mut peers: Vec<Node> = self
             .known_peers
             .iter()
-            .filter_map(|addr| {
-                let parts: Vec<&str> = addr.split(':').collect();
-                if parts.len() == 2 {
-                    let host = parts[0].to_string();
-                    let port = parts[1].parse().ok()?;
-                    Some(Node::new(host, port))
-                } else {
-                    None
-                }
-            })
+            .filter_map(|addr| addr.parse::<Node>().ok())
             .collect();
 
         let mut rng = rand::thread_rng();
@@ -230,4 +540,64 @@ mod tests {
         let random = discovery.get_random_peers(5);
         assert_eq!(random.len(), 5);
     }
+
+    #[test]
+    fn test_ipv6_known_peers_round_trip() {
+        let mut discovery = PeerDiscovery::new();
+        let peer = Node::new("2001:db8::1".to_string(), 8333);
+        discovery.add_known_peer(peer.clone());
+
+        assert_eq!(discovery.get_random_peers(1), vec![peer]);
+    }
+
+    fn gossip(host: String, last_seen: u64) -> PeerAddress {
+        PeerAddress { node: Node::new(host, 8333), last_seen }
+    }
+
+    #[test]
+    fn test_one_source_cannot_fill_the_new_table() {
+        let mut addresses = AddressManager::new();
+        let now = 1_700_000_000;
+        let attacker = Node::new("203.0.113.7".to_string(), 8333);
+        let flood: Vec<PeerAddress> = (0..MAX_ADDR_PER_MESSAGE)
+            .map(|i| gossip(format!("10.{}.{}.1", i / 256, i % 256), now - 60))
+            .collect();
+        let added = addresses.add(&flood, &attacker, now);
+        assert!(added <= NEW_BUCKETS_PER_SOURCE_GROUP as usize * BUCKET_SIZE);
+        assert_eq!(addresses.len(), added);
+
+        // Another source still gets fresher addresses in, even into a
+        // bucket the flood filled
+        let honest = Node::new("198.51.100.1".to_string(), 8333);
+        assert_eq!(addresses.add(&[gossip("192.0.2.1".to_string(), now)], &honest, now), 1);
+    }
+
+    #[test]
+    fn test_address_manager_tables() {
+        let mut addresses = AddressManager::new();
+        let now = 1_700_000_000;
+        let source = Node::new("198.51.100.1".to_string(), 8333);
+        let stale = gossip("192.0.2.1".to_string(), now - ADDRESS_HORIZON - 1);
+        let unusable = gossip("0.0.0.0".to_string(), now);
+        let fresh = gossip("192.0.2.2".to_string(), now + 3600);
+        assert_eq!(addresses.add(&[stale, unusable, fresh.clone()], &source, now), 1);
+        assert!(addresses.sample(10)[0].last_seen <= now);
+
+        // A successful connection moves an address to the tried table
+        addresses.mark_good(&fresh.node, now);
+        assert!(addresses.is_tried(&fresh.node));
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses.select(5, |_| false), vec![fresh.node.clone()]);
+        assert!(addresses.select(5, |node| *node == fresh.node).is_empty());
+
+        // Never-tried addresses are dropped after repeated failures
+        let flaky = gossip("192.0.2.3".to_string(), now);
+        addresses.add(std::slice::from_ref(&flaky), &source, now);
+        for _ in 0..MAX_FAILED_ATTEMPTS {
+            addresses.mark_failed(&flaky.node);
+        }
+        assert!(!addresses.contains(&flaky.node));
+        addresses.mark_failed(&fresh.node);
+        assert!(addresses.is_tried(&fresh.node));
+    }
 }
@@ -0,0 +1,327 @@
+//! Startup self-test behind `trinity-node doctor`
+//!
+//! Most nodes that fail to start or sync do so for one of a handful of
+//! reasons: a config that does not parse, a data directory the node cannot
+//! write, a damaged database, a port another process holds, a clock far off
+//! the network's, or a wallet file that cannot be read. The doctor checks
+//! each of them without starting the node and says what to do about every
+//! problem it finds. It only reads, apart from briefly taking the data
+//! directory lock to learn whether a node is running.
+
+use crate::config::{load_config, Config};
+use crate::blockchain::MAX_FUTURE_BLOCK_TIME_MS;
+use crate::lockfile::DataDirLock;
+use crate::persistence::Database;
+use crate::wallet::EncryptedWallet;
+use std::net::TcpListener;
+use std::path::Path;
+
+/// Blocks whose timestamps the clock is compared with
+const CLOCK_SAMPLE_BLOCKS: usize = 11;
+
+/// Clock lead of the network worth a warning, well before blocks are refused
+const CLOCK_WARN_MS: u64 = 5 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Outcome of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a warning or failure
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check { name, status: Status::Pass, detail: detail.into(), hint: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check { name, status: Status::Warn, detail: detail.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check { name, status: Status::Fail, detail: detail.into(), hint: Some(hint.into()) }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|check| check.status == Status::Fail).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.checks.iter().filter(|check| check.status == Status::Warn).count()
+    }
+}
+
+/// Run every check against the configured node
+pub fn run() -> Report {
+    let mut report = Report::default();
+    let data_dir = crate::config::data_dir();
+    report.checks.push(check_data_dir(&data_dir));
+
+    let config = match load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            report.checks.push(Check::fail(
+                "config",
+                e.to_string(),
+                "Fix config.toml, TRINITY_* variables or --set overrides; the remaining checks need a valid config",
+            ));
+            return report;
+        }
+    };
+    report.checks.push(check_config(&config));
+
+    // A running node holds the lock, its ports and its database
+    let lock = DataDirLock::acquire(&data_dir).ok();
+    let running = lock.is_none();
+    if let Some(pid) = lock.as_ref().and_then(DataDirLock::stale_pid) {
+        report.checks.push(Check::warn(
+            "lock",
+            format!("Process {} exited without releasing the data directory lock", pid),
+            "The last node probably crashed; check node.log in the data directory",
+        ));
+    }
+    report.checks.push(check_database(&config.database.path, running));
+    drop(lock);
+
+    let mut ports = vec![("p2p port", config.network.p2p_port), ("api port", config.network.api_port)];
+    if let Some(admin_port) = config.api.admin_port {
+        ports.push(("admin port", admin_port));
+    }
+    for (name, port) in ports {
+        report.checks.push(check_port(name, port, running));
+    }
+
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let timestamps = Some(&config.database.path)
+        .filter(|path| Path::new(path).exists())
+        .and_then(|path| Database::open(path).ok())
+        .and_then(|db| db.load_block_metrics(crate::blockchain::TimeRange::ALL).ok())
+        .map(|metrics| metrics.iter().map(|metric| metric.timestamp).collect::<Vec<_>>())
+        .unwrap_or_default();
+    report.checks.push(check_clock(&timestamps, now));
+
+    report.checks.push(check_wallets(&data_dir));
+    report
+}
+
+/// The data directory exists, is a directory and can be written
+fn check_data_dir(dir: &Path) -> Check {
+    const NAME: &str = "data directory";
+    if !dir.exists() {
+        return Check::warn(
+            NAME,
+            format!("{} does not exist yet", dir.display()),
+            "It is created on first start; pass --data-dir or set TRINITY_DATA_DIR to use another one",
+        );
+    }
+    if !dir.is_dir() {
+        return Check::fail(
+            NAME,
+            format!("{} is not a directory", dir.display()),
+            "Move the file out of the way or point --data-dir elsewhere",
+        );
+    }
+    let probe = dir.join(".doctor-write-test");
+    match std::fs::write(&probe, b"ok").and_then(|()| std::fs::remove_file(&probe)) {
+        Ok(()) => Check::pass(NAME, format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            NAME,
+            format!("Cannot write to {}: {}", dir.display(), e),
+            "Give the user running the node ownership of the directory (chown) or write permission (chmod u+w)",
+        ),
+    }
+}
+
+/// Settings that parse but cannot work together
+fn check_config(config: &Config) -> Check {
+    const NAME: &str = "config";
+    let network = &config.network;
+    let mut ports = vec![network.p2p_port, network.api_port];
+    ports.extend(config.api.admin_port);
+    ports.sort_unstable();
+    if ports.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Check::fail(
+            NAME,
+            "network.p2p_port, network.api_port and api.admin_port must differ",
+            "Give each listener its own port",
+        );
+    }
+    if config.miner.enabled {
+        if let Err(e) = crate::miner::BeneficiarySource::from_config(&config.miner) {
+            return Check::fail(
+                NAME,
+                e.to_string(),
+                "Set miner.beneficiary_address to a wallet address (`trinity-wallet address`) or disable mining",
+            );
+        }
+    }
+    if let Err(e) = crate::miner::LocalPriority::from_config(&config.miner, &config.data_dir()) {
+        return Check::fail(NAME, e.to_string(), "Give miner.priority_addresses as hex wallet addresses");
+    }
+    let profile = config.profile.as_deref().unwrap_or("default");
+    Check::pass(NAME, format!("Valid, profile {}, network {}", profile, network.network_id))
+}
+
+/// SQLite's own integrity check and a replay of the stored chain against the
+/// stored UTXO set. Skipped while a node runs, since it writes as we read.
+fn check_database(path: &str, node_running: bool) -> Check {
+    const NAME: &str = "database";
+    if node_running {
+        return Check::warn(
+            NAME,
+            "Skipped: a node is running on this data directory",
+            "Stop the node and run the doctor again to check the database",
+        );
+    }
+    if path != ":memory:" && !Path::new(path).exists() {
+        return Check::pass(NAME, format!("{} does not exist yet; it is created on first start", path));
+    }
+    let report = Database::open(path).and_then(|db| db.integrity_check());
+    match report {
+        Ok(report) if report.is_ok() => Check::pass(NAME, report.summary()),
+        Ok(report) => Check::fail(
+            NAME,
+            report.summary(),
+            "Run `trinity-node db check` for details; restore a backup or rebuild with `trinity-node bootstrap`",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("Cannot open {}: {}", path, e),
+            "Check the file's permissions, or move it aside and resync",
+        ),
+    }
+}
+
+/// Whether the node could listen on `port`
+fn check_port(name: &'static str, port: u16, node_running: bool) -> Check {
+    match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => Check::pass(name, format!("{} is free", port)),
+        Err(_) if node_running => Check::pass(name, format!("{} is in use, by the running node presumably", port)),
+        Err(e) => Check::fail(
+            name,
+            format!("Cannot listen on {}: {}", port, e),
+            "Stop the process holding it (see `ss -ltnp`) or pick another port with --set",
+        ),
+    }
+}
+
+/// Compare our clock with the timestamps miners across the network put in
+/// the latest blocks. A clock behind them makes the node refuse new blocks
+/// as stamped in the future.
+fn check_clock(timestamps: &[u64], now_ms: u64) -> Check {
+    const NAME: &str = "clock";
+    let recent = &timestamps[timestamps.len().saturating_sub(CLOCK_SAMPLE_BLOCKS)..];
+    let Some(&newest) = recent.iter().max() else {
+        return Check::pass(NAME, "No blocks stored to compare with");
+    };
+    let ahead = newest.saturating_sub(now_ms);
+    let hint = "Enable time synchronization (e.g. `timedatectl set-ntp true`)";
+    if ahead > MAX_FUTURE_BLOCK_TIME_MS {
+        return Check::fail(
+            NAME,
+            format!("The network's latest block is stamped {} s ahead of this clock; new blocks are refused", ahead / 1000),
+            hint,
+        );
+    }
+    if ahead > CLOCK_WARN_MS {
+        return Check::warn(NAME, format!("The network's latest block is stamped {} s ahead of this clock", ahead / 1000), hint);
+    }
+    Check::pass(NAME, "Not behind the timestamps of the latest blocks")
+}
+
+/// Every wallet file in the data directory parses and only its owner may
+/// read it
+fn check_wallets(dir: &Path) -> Check {
+    const NAME: &str = "wallets";
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Check::pass(NAME, "No wallets");
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            name == "wallet.json" || (name.starts_with("wallet_") && name.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+
+    let mut exposed = Vec::new();
+    for path in &paths {
+        if let Err(e) = EncryptedWallet::load(path) {
+            return Check::fail(
+                NAME,
+                format!("{}: {}", path.display(), e),
+                "Fix the file's permissions, or restore it from a backup (`trinity-wallet-restore`)",
+            );
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if std::fs::metadata(path).is_ok_and(|meta| meta.permissions().mode() & 0o077 != 0) {
+                exposed.push(path.display().to_string());
+            }
+        }
+    }
+    if !exposed.is_empty() {
+        return Check::warn(
+            NAME,
+            format!("Readable by other users: {}", exposed.join(", ")),
+            "Restrict them with chmod 600",
+        );
+    }
+    Check::pass(NAME, format!("{} wallet file(s) readable", paths.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dir_and_wallet_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(check_data_dir(dir.path()).status, Status::Pass);
+        assert_eq!(check_data_dir(&dir.path().join("missing")).status, Status::Warn);
+        let file = dir.path().join("file");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(check_data_dir(&file).status, Status::Fail);
+
+        assert_eq!(check_wallets(dir.path()).status, Status::Pass);
+        std::fs::write(dir.path().join("wallet_bob.json"), b"not a wallet").unwrap();
+        let check = check_wallets(dir.path());
+        assert_eq!(check.status, Status::Fail);
+        assert!(check.detail.contains("wallet_bob.json"));
+    }
+
+    #[test]
+    fn test_port_and_clock_checks() {
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(check_port("p2p port", port, false).status, Status::Fail);
+        assert_eq!(check_port("p2p port", port, true).status, Status::Pass);
+        drop(listener);
+
+        let now = 1_700_000_000_000;
+        assert_eq!(check_clock(&[], now).status, Status::Pass);
+        assert_eq!(check_clock(&[now - 60_000, now + 1_000], now).status, Status::Pass);
+        assert_eq!(check_clock(&[now + CLOCK_WARN_MS + 1], now).status, Status::Warn);
+        assert_eq!(check_clock(&[now, now + MAX_FUTURE_BLOCK_TIME_MS + 1], now).status, Status::Fail);
+    }
+}
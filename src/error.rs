@@ -1,69 +1,172 @@
 //! Error types for TrinityChain
+//!
+//! Every [`ChainError`] has a stable machine-readable [`code`](ChainError::code)
+//! that the API and the CLI report next to the message, so callers can tell
+//! failures apart without parsing text. Errors from the standard library and
+//! serialization keep their source, and [`ResultExt::context`] wraps an error
+//! with what was being done when it happened.
 
-use std::fmt;
+use crate::blockchain::Sha256Hash;
+use crate::geometry::Coord;
+use thiserror::Error;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Error)]
 pub enum ChainError {
+    #[error("Invalid block linkage")]
     InvalidBlockLinkage,
+    #[error("Network error: {0}")]
     NetworkError(String),
+    #[error("Database error: {0}")]
     DatabaseError(String),
+    #[error("Invalid proof of work")]
     InvalidProofOfWork,
+    #[error("Invalid Merkle root")]
     InvalidMerkleRoot,
+    #[error("Invalid transaction: {0}")]
     InvalidTransaction(String),
+    #[error("Triangle not found: {0}")]
     TriangleNotFound(String),
+    /// A transaction spends a triangle that is not in the UTXO set
+    #[error("Unknown UTXO {}", hex::encode(.0))]
+    UnknownUtxo(Sha256Hash),
+    /// The input triangle cannot cover amount, fee and the minimum change
+    #[error("Insufficient triangle value: input has {available} but {required} is needed")]
+    InsufficientValue { available: Coord, required: Coord },
+    /// The input is already spent by a transaction waiting in the mempool
+    #[error("UTXO {} is already spent by pending transaction {}", hex::encode(.utxo), hex::encode(.spender))]
+    AlreadySpent { utxo: Sha256Hash, spender: Sha256Hash },
+    #[error("Transaction already in mempool")]
+    DuplicateTransaction,
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Cryptographic error: {0}")]
     CryptoError(String),
+    #[error("Wallet error: {0}")]
     WalletError(String),
+    #[error("Orphan block")]
     OrphanBlock,
+    #[error("API error: {0}")]
     ApiError(String),
+    #[error("Authentication error: {0}")]
     AuthenticationError(String),
+    #[error("Mempool is full")]
     MempoolFull,
-    IoError(String),
-    BincodeError(String),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Bincode error: {0}")]
+    BincodeError(#[from] Box<bincode::ErrorKind>),
+    #[error("Fork not found")]
     ForkNotFound,
+    #[error("Invalid block: {0}")]
     InvalidBlock(String),
+    #[error("Double spend detected: {0}")]
     DoubleSpendDetected(String),
+    #[error("Block already exists")]
     BlockAlreadyExists,
+    #[error("Arithmetic overflow: {0}")]
+    ArithmeticOverflow(String),
+    #[error("Insufficient funds: {0}")]
+    InsufficientFunds(String),
+    /// A wallet spending policy refused the payment
+    #[error("Spending limit: {0}")]
+    SpendingLimit(String),
+    /// `source` annotated with what failed, see [`ResultExt::context`]
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<ChainError>,
+    },
 }
 
-impl fmt::Display for ChainError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl ChainError {
+    /// The error underneath any [`ChainError::Context`] wrappers
+    pub fn root(&self) -> &ChainError {
         match self {
-            ChainError::InvalidBlockLinkage => write!(f, "Invalid block linkage"),
-            ChainError::InvalidProofOfWork => write!(f, "Invalid proof of work"),
-            ChainError::InvalidMerkleRoot => write!(f, "Invalid Merkle root"),
-            ChainError::InvalidTransaction(msg) => write!(f, "Invalid transaction: {}", msg),
-            ChainError::TriangleNotFound(msg) => write!(f, "Triangle not found: {}", msg),
-            ChainError::CryptoError(msg) => write!(f, "Cryptographic error: {}", msg),
-            ChainError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
-            ChainError::NetworkError(msg) => write!(f, "Network error: {}", msg),
-            ChainError::WalletError(msg) => write!(f, "Wallet error: {}", msg),
-            ChainError::OrphanBlock => write!(f, "Orphan block"),
-            ChainError::ApiError(msg) => write!(f, "API error: {}", msg),
-            ChainError::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
-            ChainError::MempoolFull => write!(f, "Mempool is full"),
-            ChainError::IoError(msg) => write!(f, "IO error: {}", msg),
-            ChainError::BincodeError(msg) => write!(f, "Bincode error: {}", msg),
-            ChainError::ForkNotFound => write!(f, "Fork not found"),
-            ChainError::InvalidBlock(msg) => write!(f, "Invalid block: {}", msg),
-            ChainError::DoubleSpendDetected(msg) => write!(f, "Double spend detected: {}", msg),
-            ChainError::BlockAlreadyExists => write!(f, "Block already exists"),
+            ChainError::Context { source, .. } => source.root(),
+            other => other,
         }
     }
-}
-
-impl std::error::Error for ChainError {}
 
-impl From<std::io::Error> for ChainError {
-    fn from(err: std::io::Error) -> Self {
-        ChainError::IoError(err.to_string())
+    /// Stable snake_case identifier of the kind of error; context wrappers
+    /// report the code of the error they wrap
+    pub fn code(&self) -> &'static str {
+        match self.root() {
+            ChainError::InvalidBlockLinkage => "invalid_block_linkage",
+            ChainError::NetworkError(_) => "network_error",
+            ChainError::DatabaseError(_) => "database_error",
+            ChainError::InvalidProofOfWork => "invalid_proof_of_work",
+            ChainError::InvalidMerkleRoot => "invalid_merkle_root",
+            ChainError::InvalidTransaction(_) => "invalid_transaction",
+            ChainError::TriangleNotFound(_) => "triangle_not_found",
+            ChainError::UnknownUtxo(_) => "unknown_utxo",
+            ChainError::InsufficientValue { .. } => "insufficient_value",
+            ChainError::AlreadySpent { .. } => "already_spent",
+            ChainError::DuplicateTransaction => "duplicate_transaction",
+            ChainError::InvalidAddress(_) => "invalid_address",
+            ChainError::CryptoError(_) => "crypto_error",
+            ChainError::WalletError(_) => "wallet_error",
+            ChainError::OrphanBlock => "orphan_block",
+            ChainError::ApiError(_) => "api_error",
+            ChainError::AuthenticationError(_) => "authentication_error",
+            ChainError::MempoolFull => "mempool_full",
+            ChainError::IoError(_) => "io_error",
+            ChainError::BincodeError(_) => "serialization_error",
+            ChainError::ForkNotFound => "fork_not_found",
+            ChainError::InvalidBlock(_) => "invalid_block",
+            ChainError::DoubleSpendDetected(_) => "double_spend",
+            ChainError::BlockAlreadyExists => "block_already_exists",
+            ChainError::ArithmeticOverflow(_) => "arithmetic_overflow",
+            ChainError::InsufficientFunds(_) => "insufficient_funds",
+            ChainError::SpendingLimit(_) => "spending_limit",
+            ChainError::Context { .. } => unreachable!("root() unwraps context"),
+        }
     }
 }
 
-impl From<Box<bincode::ErrorKind>> for ChainError {
-    fn from(err: Box<bincode::ErrorKind>) -> Self {
-        ChainError::BincodeError(err.to_string())
+/// Attach context to the error of a [`Result`]
+pub trait ResultExt<T> {
+    /// Wrap the error, if any, in [`ChainError::Context`] with `context`
+    /// computed lazily
+    fn context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: Into<ChainError>> ResultExt<T> for std::result::Result<T, E> {
+    fn context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| ChainError::Context {
+            context: context().into(),
+            source: Box::new(err.into()),
+        })
     }
 }
 
 /// Convenience alias used across the crate
 pub type Result<T> = std::result::Result<T, ChainError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_codes_and_context_chain() {
+        let missing = std::fs::read("/nonexistent/trinity").context(|| "Reading the chain");
+        let err = missing.unwrap_err();
+        assert_eq!(err.code(), "io_error");
+        assert!(err.to_string().starts_with("Reading the chain: IO error: "));
+        // Context -> ChainError::IoError -> std::io::Error
+        let io = err.source().and_then(|source| source.source()).unwrap();
+        assert!(io.downcast_ref::<std::io::Error>().is_some());
+
+        let short = ChainError::InsufficientValue {
+            available: Coord::from_num(1),
+            required: Coord::from_num(2),
+        };
+        assert_eq!(short.code(), "insufficient_value");
+        assert!(matches!(
+            Err::<(), _>(short).context(|| "Checking tx").unwrap_err().root(),
+            ChainError::InsufficientValue { .. }
+        ));
+        assert_eq!(ChainError::UnknownUtxo([0xab; 32]).to_string(), format!("Unknown UTXO {}", "ab".repeat(32)));
+    }
+}
@@ -127,6 +127,19 @@ pub fn estimate_transaction_size(tx: &Transaction) -> usize {
             // ~50 bytes
             50
         }
+        Transaction::Announce(_) => {
+            // ~40 bytes for the compressed public key
+            40
+        }
+        Transaction::Register(tx) => {
+            // ~150 bytes for owner, nonce, signature and pubkey plus the name
+            150 + tx.name.len()
+        }
+        Transaction::Data(tx) => {
+            // ~190 bytes for input, owner, payload hash, signature and
+            // pubkey plus the payload
+            190 + tx.payload_len as usize
+        }
     }
 }
 
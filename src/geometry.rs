@@ -1,13 +1,67 @@
 use crate::blockchain::Sha256Hash;
 use crate::crypto::Address;
+use crate::error::ChainError;
 use fixed::types::I32F32;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 /// Coordinate type for deterministic geometric calculations.
 pub type Coord = I32F32;
 /// Tolerance for fixed-point comparisons to check for degeneracy/equality.
 pub const GEOMETRIC_TOLERANCE: Coord = I32F32::from_bits(42950); // Approx 1e-5
+/// Consensus minimum effective value of a triangle created by subdivision.
+pub const MIN_TRIANGLE_VALUE: Coord = I32F32::from_bits(429_497); // Approx 1e-4
+
+// ----------------------------------------------------------------------------
+// Checked Fixed-Point Arithmetic
+// ----------------------------------------------------------------------------
+//
+// I32F32 operators panic on overflow in debug builds and wrap in release
+// builds. Consensus code must use these helpers so that every node rejects an
+// overflowing transaction the same way.
+
+/// `a + b`, or `ChainError::ArithmeticOverflow`.
+#[inline]
+pub fn coord_checked_add(a: Coord, b: Coord) -> Result<Coord, ChainError> {
+    a.checked_add(b)
+        .ok_or_else(|| ChainError::ArithmeticOverflow(format!("{} + {}", a, b)))
+}
+
+/// `a - b`, or `ChainError::ArithmeticOverflow`.
+#[inline]
+pub fn coord_checked_sub(a: Coord, b: Coord) -> Result<Coord, ChainError> {
+    a.checked_sub(b)
+        .ok_or_else(|| ChainError::ArithmeticOverflow(format!("{} - {}", a, b)))
+}
+
+/// `a * b`, or `ChainError::ArithmeticOverflow`.
+#[inline]
+pub fn coord_checked_mul(a: Coord, b: Coord) -> Result<Coord, ChainError> {
+    a.checked_mul(b)
+        .ok_or_else(|| ChainError::ArithmeticOverflow(format!("{} * {}", a, b)))
+}
+
+/// Sum of `values`, failing on the first overflow.
+pub fn coord_checked_sum<I: IntoIterator<Item = Coord>>(values: I) -> Result<Coord, ChainError> {
+    values
+        .into_iter()
+        .try_fold(Coord::from_num(0), coord_checked_add)
+}
+
+/// `a + b`, clamped to the `Coord` range. For display and statistics only.
+#[inline]
+pub fn coord_saturating_add(a: Coord, b: Coord) -> Coord {
+    a.saturating_add(b)
+}
+
+/// `a - b`, clamped to the `Coord` range. For display and statistics only.
+#[inline]
+pub fn coord_saturating_sub(a: Coord, b: Coord) -> Coord {
+    a.saturating_sub(b)
+}
 
 // ----------------------------------------------------------------------------
 // 1.4 Coordinate System: Point
@@ -60,6 +114,122 @@ impl Point {
     }
 }
 
+// ----------------------------------------------------------------------------
+// 1.5 Structural Addressing
+// ----------------------------------------------------------------------------
+
+/// Structural address of a triangle: the hash of the transaction that minted
+/// its root triangle, followed by the child index (0-2) taken at each
+/// subdivision. Written as `<root hex>/<indices>`, e.g. `9f2c...e1/021`.
+///
+/// The address follows geometry, not ownership: a transfer keeps the address
+/// of the triangle it spends, so the recipient and change outputs of one
+/// transfer share it. The UTXO hash remains the unique key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TriangleId {
+    pub root: Sha256Hash,
+    pub path: Vec<u8>,
+}
+
+impl TriangleId {
+    /// Address of a root triangle minted by transaction `root`
+    pub fn root(root: Sha256Hash) -> Self {
+        TriangleId {
+            root,
+            path: Vec::new(),
+        }
+    }
+
+    /// Address of child `index` of this triangle
+    pub fn child(&self, index: u8) -> Self {
+        let mut path = self.path.clone();
+        path.push(index);
+        TriangleId {
+            root: self.root,
+            path,
+        }
+    }
+
+    /// Address of the triangle this one was subdivided from
+    pub fn parent(&self) -> Option<Self> {
+        let (_, path) = self.path.split_last()?;
+        Some(TriangleId {
+            root: self.root,
+            path: path.to_vec(),
+        })
+    }
+
+    /// Number of subdivisions between the root and this triangle
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Whether this address is a strict ancestor of `other`
+    pub fn is_ancestor_of(&self, other: &TriangleId) -> bool {
+        self.root == other.root
+            && self.path.len() < other.path.len()
+            && other.path.starts_with(&self.path)
+    }
+
+    /// Derive the triangle at this address by replaying the subdivisions
+    /// from `root`. Returns `None` if `root` is not this address's root.
+    pub fn resolve(&self, root: &Triangle) -> Option<Triangle> {
+        if root.id.as_ref() != Some(&TriangleId::root(self.root)) {
+            return None;
+        }
+        let mut current = root.clone();
+        for &index in &self.path {
+            current = current.subdivide()[index as usize].clone();
+        }
+        Some(current)
+    }
+}
+
+impl fmt::Display for TriangleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.root))?;
+        if !self.path.is_empty() {
+            write!(f, "/")?;
+            for index in &self.path {
+                write!(f, "{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TriangleId {
+    type Err = ChainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |msg: &str| ChainError::InvalidTransaction(format!("Invalid triangle id '{}': {}", s, msg));
+        let (root_hex, path) = s.split_once('/').unwrap_or((s, ""));
+        let mut root = [0u8; 32];
+        hex::decode_to_slice(root_hex, &mut root).map_err(|_| invalid("root must be 64 hex characters"))?;
+        let path = path
+            .chars()
+            .map(|c| match c {
+                '0'..='2' => Ok(c as u8 - b'0'),
+                _ => Err(invalid("child indices must be 0, 1 or 2")),
+            })
+            .collect::<Result<Vec<u8>, ChainError>>()?;
+        Ok(TriangleId { root, path })
+    }
+}
+
+impl Serialize for TriangleId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TriangleId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 // ----------------------------------------------------------------------------
 // 1.3 Triangle Data Structure & Core Methods
 // ----------------------------------------------------------------------------
@@ -75,6 +245,10 @@ pub struct Triangle {
     /// Effective value of this triangle.
     #[serde(default)]
     pub value: Option<Coord>,
+    /// Structural address; `None` for triangles created before addressing
+    /// existed. Not part of the hash: it is derived from the lineage.
+    #[serde(default)]
+    pub id: Option<TriangleId>,
 }
 
 impl Triangle {
@@ -93,6 +267,7 @@ impl Triangle {
             parent_hash,
             owner,
             value: None,
+            id: None,
         }
     }
 
@@ -112,6 +287,7 @@ impl Triangle {
             parent_hash,
             owner,
             value: Some(value),
+            id: None,
         }
     }
 
@@ -127,12 +303,46 @@ impl Triangle {
         new_triangle
     }
 
+    /// Creates a new triangle with the given structural address.
+    pub fn with_id(mut self, id: TriangleId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Subdivision depth below the root, if the triangle is addressed
+    pub fn depth(&self) -> Option<usize> {
+        self.id.as_ref().map(TriangleId::depth)
+    }
+
     /// Creates a new triangle with a specific effective value.
     pub fn with_effective_value(mut self, value: Coord) -> Self {
         self.value = Some(value);
         self
     }
 
+    /// Calculates the area using the Shoelace formula, failing instead of
+    /// wrapping when the vertices are too far apart.
+    pub fn checked_area(&self) -> Result<Coord, ChainError> {
+        let term = |p: &Point, q: &Point, r: &Point| coord_checked_mul(p.x, coord_checked_sub(q.y, r.y)?);
+        let doubled = coord_checked_sum([
+            term(&self.a, &self.b, &self.c)?,
+            term(&self.b, &self.c, &self.a)?,
+            term(&self.c, &self.a, &self.b)?,
+        ])?;
+        let doubled = doubled
+            .checked_abs()
+            .ok_or_else(|| ChainError::ArithmeticOverflow(format!("|{}|", doubled)))?;
+        Ok(doubled / 2)
+    }
+
+    /// Returns the effective value, failing if the area cannot be computed.
+    pub fn checked_effective_value(&self) -> Result<Coord, ChainError> {
+        match self.value {
+            Some(value) => Ok(value),
+            None => self.checked_area(),
+        }
+    }
+
     /// Calculates the area of the triangle using the Shoelace formula.
     pub fn area(&self) -> Coord {
         let val = (self.a.x * (self.b.y - self.c.y)
@@ -221,7 +431,30 @@ impl Triangle {
         let t3 =
             Triangle::new_with_value(mid_ca, mid_bc, self.c, parent_hash, self.owner, child_value);
 
-        [t1, t2, t3]
+        let mut children = [t1, t2, t3];
+        if let Some(id) = &self.id {
+            for (index, child) in children.iter_mut().enumerate() {
+                child.id = Some(id.child(index as u8));
+            }
+        }
+        children
+    }
+
+    /// Whether subdividing this triangle satisfies the consensus minimums:
+    /// every child carries at least [`MIN_TRIANGLE_VALUE`], and a triangle
+    /// with real area does not produce children whose area falls below
+    /// [`GEOMETRIC_TOLERANCE`]. Value-only triangles (e.g. coinbase outputs)
+    /// are bound by the value rule alone.
+    pub fn can_subdivide(&self) -> bool {
+        let has_area = self
+            .checked_area()
+            .is_ok_and(|area| area > GEOMETRIC_TOLERANCE);
+        self.subdivide().iter().all(|child| {
+            child
+                .checked_effective_value()
+                .is_ok_and(|value| value >= MIN_TRIANGLE_VALUE)
+                && (!has_area || child.is_valid())
+        })
     }
 
     // ------------------------------------------------------------------------
@@ -233,7 +466,156 @@ impl Triangle {
         if !self.a.is_valid() || !self.b.is_valid() || !self.c.is_valid() {
             return false;
         }
-        self.area() > GEOMETRIC_TOLERANCE
+        self.checked_area()
+            .is_ok_and(|area| area > GEOMETRIC_TOLERANCE)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// 1.9 Geometric Predicates
+// ----------------------------------------------------------------------------
+//
+// Predicates are evaluated exactly on the raw I32F32 bits: coordinate
+// differences fit in 65 bits and their products in 128, so no tolerance is
+// involved and every node reaches the same answer.
+
+/// Turn direction of the path `a -> b -> c`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    CounterClockwise,
+    Clockwise,
+    Collinear,
+}
+
+/// Position of a point relative to a triangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointLocation {
+    Inside,
+    OnBoundary,
+    Outside,
+}
+
+/// Exact product of two coordinate differences as (negative, magnitude)
+fn wide_product(a: i128, b: i128) -> (bool, u128) {
+    let magnitude = a.unsigned_abs() * b.unsigned_abs();
+    (magnitude != 0 && (a < 0) != (b < 0), magnitude)
+}
+
+fn cmp_wide((p_neg, p): (bool, u128), (q_neg, q): (bool, u128)) -> Ordering {
+    match (p_neg, q_neg) {
+        (false, false) => p.cmp(&q),
+        (true, true) => q.cmp(&p),
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+/// Exact orientation of the triangle `a, b, c`.
+pub fn orientation(a: &Point, b: &Point, c: &Point) -> Orientation {
+    let diff = |p: Coord, q: Coord| p.to_bits() as i128 - q.to_bits() as i128;
+    let lhs = wide_product(diff(b.x, a.x), diff(c.y, a.y));
+    let rhs = wide_product(diff(b.y, a.y), diff(c.x, a.x));
+    match cmp_wide(lhs, rhs) {
+        Ordering::Greater => Orientation::CounterClockwise,
+        Ordering::Less => Orientation::Clockwise,
+        Ordering::Equal => Orientation::Collinear,
+    }
+}
+
+/// Whether `p`, known to be collinear with segment `a`-`b`, lies on it.
+fn on_segment(a: &Point, b: &Point, p: &Point) -> bool {
+    p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+/// Whether the closed segments `p1`-`p2` and `q1`-`q2` share at least one
+/// point, including touching endpoints and collinear overlap.
+pub fn segments_intersect(p1: &Point, p2: &Point, q1: &Point, q2: &Point) -> bool {
+    let o1 = orientation(p1, p2, q1);
+    let o2 = orientation(p1, p2, q2);
+    let o3 = orientation(q1, q2, p1);
+    let o4 = orientation(q1, q2, p2);
+
+    if o1 != o2 && o3 != o4 && o1 != Orientation::Collinear && o2 != Orientation::Collinear
+        && o3 != Orientation::Collinear && o4 != Orientation::Collinear
+    {
+        return true;
+    }
+    (o1 == Orientation::Collinear && on_segment(p1, p2, q1))
+        || (o2 == Orientation::Collinear && on_segment(p1, p2, q2))
+        || (o3 == Orientation::Collinear && on_segment(q1, q2, p1))
+        || (o4 == Orientation::Collinear && on_segment(q1, q2, p2))
+}
+
+impl Triangle {
+    /// Winding of the vertices `a, b, c`; `Collinear` for degenerate triangles.
+    pub fn orientation(&self) -> Orientation {
+        orientation(&self.a, &self.b, &self.c)
+    }
+
+    fn edges(&self) -> [(Point, Point); 3] {
+        [(self.a, self.b), (self.b, self.c), (self.c, self.a)]
+    }
+
+    /// Locate `p` relative to this triangle. A degenerate triangle has no
+    /// interior, so points are either on one of its edges or outside.
+    pub fn locate_point(&self, p: &Point) -> PointLocation {
+        let winding = self.orientation();
+        if winding == Orientation::Collinear {
+            return if self.edges().iter().any(|(u, v)| {
+                orientation(u, v, p) == Orientation::Collinear && on_segment(u, v, p)
+            }) {
+                PointLocation::OnBoundary
+            } else {
+                PointLocation::Outside
+            };
+        }
+
+        let mut on_edge = false;
+        for (u, v) in self.edges() {
+            match orientation(&u, &v, p) {
+                Orientation::Collinear => on_edge = true,
+                side if side != winding => return PointLocation::Outside,
+                _ => {}
+            }
+        }
+        if on_edge {
+            PointLocation::OnBoundary
+        } else {
+            PointLocation::Inside
+        }
+    }
+
+    /// Whether `p` lies inside the triangle or on its boundary
+    pub fn contains_point(&self, p: &Point) -> bool {
+        self.locate_point(p) != PointLocation::Outside
+    }
+
+    /// Whether `other` lies entirely within this triangle (boundary included).
+    /// The test is exact, so a midpoint that [`Triangle::subdivide`] had to
+    /// round can sit one bit outside a parent edge that is not axis-aligned.
+    pub fn contains_triangle(&self, other: &Triangle) -> bool {
+        [other.a, other.b, other.c].iter().all(|p| self.contains_point(p))
+    }
+
+    /// Whether the interiors of the two triangles intersect. Triangles that
+    /// only share an edge or a vertex do not overlap, and degenerate
+    /// triangles overlap nothing.
+    pub fn overlaps(&self, other: &Triangle) -> bool {
+        fn separated_by_edge(t: &Triangle, other: &Triangle) -> bool {
+            let winding = t.orientation();
+            t.edges().iter().any(|(u, v)| {
+                [other.a, other.b, other.c]
+                    .iter()
+                    .all(|p| orientation(u, v, p) != winding)
+            })
+        }
+
+        if self.orientation() == Orientation::Collinear
+            || other.orientation() == Orientation::Collinear
+        {
+            return false;
+        }
+        !separated_by_edge(self, other) && !separated_by_edge(other, self)
     }
 }
 
@@ -344,4 +726,202 @@ mod tests {
         );
         assert!(!t_degenerate.is_valid());
     }
+
+    #[test]
+    fn test_checked_coord_ops() {
+        let one = Coord::from_num(1);
+        assert_eq!(coord_checked_add(one, one).unwrap(), Coord::from_num(2));
+        assert_eq!(coord_checked_sub(one, one).unwrap(), Coord::from_num(0));
+        assert_eq!(coord_checked_mul(Coord::from_num(3), Coord::from_num(2)).unwrap(), Coord::from_num(6));
+        assert!(matches!(
+            coord_checked_add(Coord::MAX, one),
+            Err(ChainError::ArithmeticOverflow(_))
+        ));
+        assert!(coord_checked_sub(Coord::MIN, one).is_err());
+        assert!(coord_checked_mul(Coord::MAX, Coord::from_num(2)).is_err());
+        assert!(coord_checked_sum([Coord::MAX, one, -one]).is_err());
+        assert_eq!(coord_saturating_add(Coord::MAX, one), Coord::MAX);
+        assert_eq!(coord_saturating_sub(Coord::MIN, one), Coord::MIN);
+    }
+
+    #[test]
+    fn test_checked_area_overflow() {
+        let t = setup_test_triangle();
+        assert_eq!(t.checked_area().unwrap(), t.area());
+
+        let huge = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(1_000_000), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(1_000_000)),
+            None,
+            create_test_address("owner"),
+        );
+        assert!(huge.checked_area().is_err());
+        assert!(huge.checked_effective_value().is_err());
+        assert!(!huge.is_valid());
+        assert!(huge.with_effective_value(Coord::from_num(1)).checked_effective_value().is_ok());
+    }
+
+    fn pt(x: f64, y: f64) -> Point {
+        Point::new(Coord::from_num(x), Coord::from_num(y))
+    }
+
+    fn tri(a: Point, b: Point, c: Point) -> Triangle {
+        Triangle::new(a, b, c, None, create_test_address("owner"))
+    }
+
+    #[test]
+    fn test_orientation() {
+        let (a, b, c) = (pt(0.0, 0.0), pt(1.0, 0.0), pt(0.0, 1.0));
+        assert_eq!(orientation(&a, &b, &c), Orientation::CounterClockwise);
+        assert_eq!(orientation(&a, &c, &b), Orientation::Clockwise);
+        assert_eq!(orientation(&a, &b, &pt(5.0, 0.0)), Orientation::Collinear);
+        assert_eq!(orientation(&a, &a, &b), Orientation::Collinear);
+
+        // Extreme coordinates do not overflow
+        let min = Point::new(Coord::MIN, Coord::MIN);
+        let max = Point::new(Coord::MAX, Coord::MAX);
+        assert_eq!(orientation(&min, &max, &Point::new(Coord::MIN, Coord::MAX)), Orientation::CounterClockwise);
+        assert_eq!(orientation(&min, &max, &Point::new(Coord::MAX, Coord::MIN)), Orientation::Clockwise);
+        assert_eq!(orientation(&min, &max, &Point::new(Coord::from_num(0), Coord::from_num(0))), Orientation::Collinear);
+
+        // One bit off the line is still detected
+        let nudged = Point::new(Coord::from_num(2), Coord::from_num(2) + Coord::DELTA);
+        assert_eq!(orientation(&a, &pt(1.0, 1.0), &nudged), Orientation::CounterClockwise);
+    }
+
+    #[test]
+    fn test_point_location() {
+        for t in [
+            tri(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 4.0)),
+            tri(pt(0.0, 0.0), pt(0.0, 4.0), pt(4.0, 0.0)), // clockwise winding
+        ] {
+            assert_eq!(t.locate_point(&pt(1.0, 1.0)), PointLocation::Inside);
+            assert_eq!(t.locate_point(&pt(0.0, 0.0)), PointLocation::OnBoundary); // vertex
+            assert_eq!(t.locate_point(&pt(2.0, 0.0)), PointLocation::OnBoundary); // edge
+            assert_eq!(t.locate_point(&pt(2.0, 2.0)), PointLocation::OnBoundary); // hypotenuse
+            assert_eq!(t.locate_point(&pt(2.5, 2.0)), PointLocation::Outside);
+            assert_eq!(t.locate_point(&pt(-1.0, 1.0)), PointLocation::Outside);
+            assert_eq!(t.locate_point(&pt(5.0, 0.0)), PointLocation::Outside); // on edge line, past vertex
+            assert!(t.contains_point(&pt(2.0, 0.0)));
+            assert!(!t.contains_point(&pt(2.0, -0.5)));
+        }
+
+        let degenerate = tri(pt(0.0, 0.0), pt(2.0, 2.0), pt(4.0, 4.0));
+        assert_eq!(degenerate.locate_point(&pt(1.0, 1.0)), PointLocation::OnBoundary);
+        assert_eq!(degenerate.locate_point(&pt(1.0, 2.0)), PointLocation::Outside);
+        assert_eq!(degenerate.locate_point(&pt(5.0, 5.0)), PointLocation::Outside);
+
+        let point = tri(pt(1.0, 1.0), pt(1.0, 1.0), pt(1.0, 1.0));
+        assert!(point.contains_point(&pt(1.0, 1.0)));
+        assert!(!point.contains_point(&pt(1.0, 1.5)));
+    }
+
+    #[test]
+    fn test_segments_intersect() {
+        let s = |x1, y1, x2, y2| (pt(x1, y1), pt(x2, y2));
+        let cases = [
+            (s(0.0, 0.0, 2.0, 2.0), s(0.0, 2.0, 2.0, 0.0), true),  // proper crossing
+            (s(0.0, 0.0, 2.0, 0.0), s(2.0, 0.0, 3.0, 1.0), true),  // shared endpoint
+            (s(0.0, 0.0, 2.0, 0.0), s(1.0, 0.0, 1.0, 5.0), true),  // T-junction
+            (s(0.0, 0.0, 2.0, 0.0), s(1.0, 0.0, 3.0, 0.0), true),  // collinear overlap
+            (s(0.0, 0.0, 4.0, 0.0), s(1.0, 0.0, 2.0, 0.0), true),  // collinear containment
+            (s(0.0, 0.0, 1.0, 0.0), s(2.0, 0.0, 3.0, 0.0), false), // collinear, disjoint
+            (s(0.0, 0.0, 2.0, 0.0), s(0.0, 1.0, 2.0, 1.0), false), // parallel
+            (s(0.0, 0.0, 2.0, 2.0), s(3.0, 0.0, 2.5, 1.0), false), // would cross if extended
+            (s(1.0, 1.0, 1.0, 1.0), s(0.0, 0.0, 2.0, 2.0), true),  // point on segment
+            (s(1.0, 2.0, 1.0, 2.0), s(0.0, 0.0, 2.0, 2.0), false), // point off segment
+        ];
+        for ((p1, p2), (q1, q2), expected) in cases {
+            assert_eq!(segments_intersect(&p1, &p2, &q1, &q2), expected, "{:?}-{:?} vs {:?}-{:?}", p1, p2, q1, q2);
+            assert_eq!(segments_intersect(&q1, &q2, &p1, &p2), expected);
+            assert_eq!(segments_intersect(&p2, &p1, &q2, &q1), expected);
+        }
+    }
+
+    #[test]
+    fn test_triangle_overlap() {
+        let base = tri(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 4.0));
+        let cases = [
+            (tri(pt(0.0, 0.0), pt(4.0, 0.0), pt(0.0, 4.0)), true),  // identical
+            (tri(pt(0.0, 4.0), pt(4.0, 0.0), pt(0.0, 0.0)), true),  // identical, reversed winding
+            (tri(pt(0.5, 0.5), pt(1.0, 0.5), pt(0.5, 1.0)), true),  // nested
+            (tri(pt(-1.0, -1.0), pt(10.0, -1.0), pt(-1.0, 10.0)), true), // enclosing
+            (tri(pt(1.0, 1.0), pt(5.0, 1.0), pt(1.0, 5.0)), true),  // partial overlap
+            (tri(pt(1.0, -1.0), pt(2.0, 5.0), pt(3.0, -1.0)), true), // edges cross, no vertex inside
+            (tri(pt(4.0, 0.0), pt(0.0, 4.0), pt(4.0, 4.0)), false), // shared edge
+            (tri(pt(4.0, 0.0), pt(6.0, 0.0), pt(5.0, 1.0)), false), // shared vertex
+            (tri(pt(2.0, 0.0), pt(3.0, -1.0), pt(1.0, -1.0)), false), // vertex touching an edge
+            (tri(pt(5.0, 5.0), pt(6.0, 5.0), pt(5.0, 6.0)), false), // disjoint
+            (tri(pt(1.0, 1.0), pt(2.0, 2.0), pt(3.0, 3.0)), false), // degenerate
+        ];
+        for (other, expected) in cases {
+            assert_eq!(base.overlaps(&other), expected, "{:?}", other);
+            assert_eq!(other.overlaps(&base), expected, "{:?}", other);
+        }
+    }
+
+    #[test]
+    fn test_subdivided_children_lie_within_parent() {
+        let parent = setup_test_triangle();
+        let children = parent.subdivide();
+        for (i, child) in children.iter().enumerate() {
+            assert!(parent.contains_triangle(child));
+            assert!(parent.overlaps(child));
+            for other in &children[i + 1..] {
+                assert!(!child.overlaps(other));
+            }
+        }
+
+        let outsider = tri(pt(0.0, 0.0), pt(11.0, 0.0), pt(0.0, 1.0));
+        assert!(!parent.contains_triangle(&outsider));
+    }
+
+    #[test]
+    fn test_triangle_id_roundtrip() {
+        let root = TriangleId::root([0xab; 32]);
+        assert_eq!(root.to_string(), "ab".repeat(32));
+        let id = root.child(0).child(2).child(1);
+        assert_eq!(id.to_string(), format!("{}/021", "ab".repeat(32)));
+        assert_eq!(id.to_string().parse::<TriangleId>().unwrap(), id);
+        assert_eq!(root.to_string().parse::<TriangleId>().unwrap(), root);
+
+        assert_eq!(id.depth(), 3);
+        assert_eq!(id.parent().unwrap(), root.child(0).child(2));
+        assert!(root.parent().is_none());
+        assert!(root.is_ancestor_of(&id));
+        assert!(!id.is_ancestor_of(&id));
+        assert!(!root.child(1).is_ancestor_of(&id));
+
+        assert!("ab/0".parse::<TriangleId>().is_err());
+        assert!(format!("{}/013", "ab".repeat(32)).parse::<TriangleId>().is_err());
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<TriangleId>(&json).unwrap(), id);
+        let bytes = bincode::serialize(&id).unwrap();
+        assert_eq!(bincode::deserialize::<TriangleId>(&bytes).unwrap(), id);
+    }
+
+    #[test]
+    fn test_subdivision_derives_structural_ids() {
+        let root_id = TriangleId::root([7; 32]);
+        let parent = setup_test_triangle().with_id(root_id.clone());
+        let children = parent.subdivide();
+        for (i, child) in children.iter().enumerate() {
+            assert_eq!(child.id, Some(root_id.child(i as u8)));
+            assert_eq!(child.depth(), Some(1));
+        }
+
+        let grandchild = children[2].subdivide()[1].clone();
+        let id = grandchild.id.clone().unwrap();
+        assert_eq!(id.to_string().split('/').nth(1), Some("21"));
+        assert_eq!(id.resolve(&parent), Some(grandchild));
+        assert!(id.resolve(&setup_test_triangle()).is_none());
+
+        // Unaddressed triangles stay unaddressed, and legacy JSON still loads
+        assert!(setup_test_triangle().subdivide()[0].id.is_none());
+        let mut legacy = serde_json::to_value(setup_test_triangle()).unwrap();
+        legacy.as_object_mut().unwrap().remove("id");
+        assert!(serde_json::from_value::<Triangle>(legacy).unwrap().id.is_none());
+    }
 }
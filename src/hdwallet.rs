@@ -1,5 +1,39 @@
+use crate::blockchain::{Block, TriangleState};
+use crate::crypto::{Address, KeyPair};
+use crate::geometry::Coord;
+use crate::transaction::Transaction;
+use bip32::{ChildNumber, DerivationPath, XPrv};
 use bip39::Mnemonic;
 use rand::RngCore;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// BIP-44 account path under which TrinityChain receive keys are derived
+pub const TRINITY_DERIVATION_PATH: &str = "m/44'/333'/0'/0";
+
+/// BIP-44 path of the change keys of the same account
+pub const TRINITY_CHANGE_PATH: &str = "m/44'/333'/0'/1";
+
+/// Unused addresses in a row after which a restoring wallet stops looking
+/// (the BIP-44 gap limit)
+pub const DEFAULT_GAP_LIMIT: usize = 20;
+
+/// External (receive) or internal (change) chain of the account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyChain {
+    Receive,
+    Change,
+}
+
+impl KeyChain {
+    pub fn path(self) -> &'static str {
+        match self {
+            KeyChain::Receive => TRINITY_DERIVATION_PATH,
+            KeyChain::Change => TRINITY_CHANGE_PATH,
+        }
+    }
+}
 
 /// Lightweight HD wallet helpers using BIP-39 for mnemonic and seed derivation.
 ///
@@ -37,11 +71,156 @@ impl HDWallet {
         let pass = passphrase.unwrap_or("");
         Ok(m.to_seed_normalized(pass).to_vec())
     }
+
+    /// Derive the keypair at `index` below [`TRINITY_DERIVATION_PATH`] (BIP-32).
+    pub fn derive_keypair(seed: &[u8], index: u32) -> Result<KeyPair, String> {
+        Self::derive_chain_keypair(seed, KeyChain::Receive, index)
+    }
+
+    /// Derive the keypair at `index` on `chain`.
+    pub fn derive_chain_keypair(seed: &[u8], chain: KeyChain, index: u32) -> Result<KeyPair, String> {
+        let path: DerivationPath = chain
+            .path()
+            .parse()
+            .map_err(|e| format!("invalid derivation path: {}", e))?;
+        let child = ChildNumber::new(index, false)
+            .map_err(|e| format!("invalid child index {}: {}", index, e))?;
+        let xprv = XPrv::derive_from_path(seed, &path)
+            .and_then(|account| account.derive_child(child))
+            .map_err(|e| format!("key derivation failed: {}", e))?;
+        KeyPair::from_secret_bytes(&xprv.to_bytes()).map_err(|e| e.to_string())
+    }
+
+    /// Derive the first `count` addresses of both chains and look up how the
+    /// chain in `blocks` and `state` has used them.
+    pub fn audit(
+        seed: &[u8],
+        count: u32,
+        blocks: &[Block],
+        state: &TriangleState,
+        gap_limit: usize,
+    ) -> Result<AuditReport, String> {
+        let mut derived = Vec::new();
+        for chain in [KeyChain::Receive, KeyChain::Change] {
+            for index in 0..count {
+                derived.push((chain, index, Self::derive_chain_keypair(seed, chain, index)?.address()));
+            }
+        }
+        let mut receipts: HashMap<Address, usize> = derived.iter().map(|&(_, _, address)| (address, 0)).collect();
+        for tx in blocks.iter().flat_map(|block| &block.transactions) {
+            for payee in payees(tx) {
+                if let Some(count) = receipts.get_mut(&payee) {
+                    *count += 1;
+                }
+            }
+        }
+        let addresses = derived
+            .into_iter()
+            .map(|(chain, index, address)| AddressAudit {
+                chain,
+                index,
+                address: hex::encode(address),
+                receipts: receipts[&address],
+                balance: state.get_balance(&address),
+            })
+            .collect();
+        Ok(AuditReport { addresses, gap_limit })
+    }
+}
+
+/// Addresses a transaction pays, once per payment
+fn payees(tx: &Transaction) -> Vec<Address> {
+    match tx {
+        Transaction::Coinbase(coinbase) => coinbase.payouts().unwrap_or_default().into_iter().map(|(address, _)| address).collect(),
+        Transaction::Transfer(transfer) if transfer.sender != transfer.new_owner => vec![transfer.new_owner],
+        _ => Vec::new(),
+    }
+}
+
+/// How the chain has used one derived address
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressAudit {
+    pub chain: KeyChain,
+    pub index: u32,
+    pub address: String,
+    /// Payments received
+    pub receipts: usize,
+    pub balance: Coord,
+}
+
+impl AddressAudit {
+    pub fn is_used(&self) -> bool {
+        self.receipts > 0
+    }
+}
+
+/// Result of [`HDWallet::audit`]
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    /// Receive addresses, then change addresses, each by index
+    pub addresses: Vec<AddressAudit>,
+    pub gap_limit: usize,
+}
+
+impl AuditReport {
+    fn chain(&self, chain: KeyChain) -> impl Iterator<Item = &AddressAudit> {
+        self.addresses.iter().filter(move |entry| entry.chain == chain)
+    }
+
+    /// Runs of unused addresses before a used one, as (chain, first index,
+    /// length)
+    pub fn gaps(&self) -> Vec<(KeyChain, u32, usize)> {
+        let mut gaps = Vec::new();
+        for chain in [KeyChain::Receive, KeyChain::Change] {
+            let mut start = None;
+            for entry in self.chain(chain) {
+                match (entry.is_used(), start) {
+                    (false, None) => start = Some(entry.index),
+                    (true, Some(first)) => {
+                        gaps.push((chain, first, (entry.index - first) as usize));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        gaps
+    }
+
+    /// Addresses paid more than once
+    pub fn reused(&self) -> Vec<&AddressAudit> {
+        self.addresses.iter().filter(|entry| entry.receipts > 1).collect()
+    }
+
+    /// Used addresses a wallet restoring from the mnemonic would not find,
+    /// because they come after [`Self::gap_limit`] unused ones in a row
+    pub fn beyond_gap_limit(&self) -> Vec<&AddressAudit> {
+        let mut hidden = Vec::new();
+        for chain in [KeyChain::Receive, KeyChain::Change] {
+            let mut unused_run = 0;
+            let mut lost = false;
+            for entry in self.chain(chain) {
+                if entry.is_used() {
+                    lost |= unused_run >= self.gap_limit;
+                    unused_run = 0;
+                    if lost {
+                        hidden.push(entry);
+                    }
+                } else {
+                    unused_run += 1;
+                }
+            }
+        }
+        hidden
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::HDWallet;
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::persistence::InMemoryPersistence;
+    use crate::transaction::CoinbaseTx;
 
     #[test]
     fn test_generate_mnemonic_and_seed() {
@@ -55,9 +234,60 @@ mod tests {
         assert_eq!(seed.len(), 64); // BIP-39 seed is 64 bytes
     }
 
+    #[test]
+    fn test_derive_keypair_is_deterministic_per_index() {
+        let m = HDWallet::generate_mnemonic(12).unwrap();
+        let seed = HDWallet::seed_from_mnemonic(&m, None).unwrap();
+
+        let first = HDWallet::derive_keypair(&seed, 0).unwrap();
+        let again = HDWallet::derive_keypair(&seed, 0).unwrap();
+        let second = HDWallet::derive_keypair(&seed, 1).unwrap();
+        assert_eq!(first.address(), again.address());
+        assert_ne!(first.address(), second.address());
+    }
+
     #[test]
     fn test_invalid_word_count() {
         let r = HDWallet::generate_mnemonic(13);
         assert!(r.is_err());
     }
+
+    #[test]
+    fn test_audit_reports_gaps_reuse_and_hidden_funds() {
+        let seed = HDWallet::seed_from_mnemonic(&HDWallet::generate_mnemonic(12).unwrap(), None).unwrap();
+        let receive = |index| HDWallet::derive_keypair(&seed, index).unwrap().address();
+        let change = HDWallet::derive_chain_keypair(&seed, KeyChain::Change, 0).unwrap().address();
+        assert_ne!(change, receive(0));
+
+        // Receive addresses 0 (genesis and again), 2 and 6; gap limit 3
+        let mut chain =
+            Blockchain::new_with_persistence(receive(0), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        for beneficiary in [receive(0), receive(2), receive(6), change] {
+            let height = chain.blocks.len() as u64;
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: beneficiary,
+                nonce: height,
+                height,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let block = Block::new(height, chain.blocks.last().unwrap().hash(), 0, vec![coinbase]);
+            chain.apply_block(crate::miner::mine_block(block).unwrap()).unwrap();
+        }
+
+        let report = HDWallet::audit(&seed, 8, &chain.blocks, &chain.state, 3).unwrap();
+        assert_eq!(report.addresses.len(), 16);
+        assert_eq!(report.addresses[0].receipts, 2);
+        assert_eq!(report.addresses[8].chain, KeyChain::Change);
+        assert!(report.addresses[8].is_used());
+        assert_eq!(report.gaps(), vec![(KeyChain::Receive, 1, 1), (KeyChain::Receive, 3, 3)]);
+        let reused: Vec<u32> = report.reused().iter().map(|entry| entry.index).collect();
+        assert_eq!(reused, vec![0]);
+        let hidden = report.beyond_gap_limit();
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].address, hex::encode(receive(6)));
+        assert!(hidden[0].balance > Coord::from_num(0));
+    }
 }
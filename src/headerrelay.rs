@@ -0,0 +1,275 @@
+//! Header-first block relay
+//!
+//! A newly found block is relayed as its header first, to peers advertising
+//! [`features::HEADER_RELAY`](crate::network::features::HEADER_RELAY), with
+//! the body following as a regular `NewBlock`. A miner hearing of the block a
+//! body transfer earlier stops working on a block that is already orphaned
+//! that much sooner.
+//!
+//! Building on a header whose body nobody has validated is SPV mining, and
+//! doing it block after block extends a chain that may turn out invalid.
+//! [`HeaderRelay`] allows exactly one block on an unvalidated header:
+//!
+//! - Only one unvalidated header is held, and only while it extends the
+//!   validated tip and its body is due ([`HEADER_BODY_TIMEOUT`]).
+//! - The block built on it carries its coinbase and nothing else, without
+//!   fees, since the state after the header is unknown.
+//! - That block is held back until the header's body has been applied, and
+//!   dropped if the body is rejected or never comes, so no second block is
+//!   ever built on top of unvalidated work.
+
+use crate::blockchain::{Block, BlockHeader, Blockchain, Sha256Hash};
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use crate::transaction::Transaction;
+use std::time::{Duration, Instant};
+
+/// How long a relayed header may be built on before its body arrives
+pub const HEADER_BODY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A header relayed ahead of its body
+#[derive(Debug, Clone)]
+pub struct UnvalidatedHeader {
+    pub header: BlockHeader,
+    pub hash: Sha256Hash,
+    /// Peer that relayed it
+    pub peer: String,
+    pub received_at: Instant,
+}
+
+/// The unvalidated header a miner may build on, and the block mined on it
+#[derive(Debug, Default)]
+pub struct HeaderRelay {
+    pending: Option<UnvalidatedHeader>,
+    /// Block mined on `pending`, waiting for its body
+    held: Option<Block>,
+}
+
+impl HeaderRelay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a relayed header that extends `chain`'s tip. Headers that do not
+    /// extend it are ignored (`Ok(false)`); those that do but fail the
+    /// checks possible without the body are errors.
+    pub fn offer(
+        &mut self,
+        chain: &Blockchain,
+        header: BlockHeader,
+        peer: &str,
+        now: Instant,
+    ) -> Result<bool, ChainError> {
+        self.prune(chain, now);
+        let hash = header.hash();
+        let Some(tip) = chain.blocks.last() else {
+            return Ok(false);
+        };
+        if header.previous_hash != tip.hash() || header.height != tip.header.height + 1 {
+            return Ok(false);
+        }
+        if self.pending.as_ref().is_some_and(|pending| pending.hash == hash) {
+            return Ok(false);
+        }
+        check_header(chain, &header)?;
+        // The first header to arrive wins, as the first block would
+        if self.pending.is_some() {
+            return Ok(false);
+        }
+        self.pending = Some(UnvalidatedHeader {
+            header,
+            hash,
+            peer: peer.to_string(),
+            received_at: now,
+        });
+        Ok(true)
+    }
+
+    /// Unvalidated header the next block may be built on instead of
+    /// `chain`'s tip, if any
+    pub fn mining_parent(&mut self, chain: &Blockchain, now: Instant) -> Option<&UnvalidatedHeader> {
+        self.prune(chain, now);
+        self.pending.as_ref()
+    }
+
+    /// Check a block about to be mined or held against the guard: it either
+    /// builds on the validated tip, or on the pending header with nothing
+    /// but a fee-less coinbase
+    pub fn check_template(&self, chain: &Blockchain, block: &Block) -> Result<(), ChainError> {
+        if chain.blocks.last().is_some_and(|tip| tip.hash() == block.header.previous_hash) {
+            return Ok(());
+        }
+        let Some(pending) = self.pending.as_ref().filter(|p| p.hash == block.header.previous_hash) else {
+            return Err(ChainError::InvalidBlock(
+                "Block builds on neither the validated tip nor the pending header; \
+                 at most one block may be mined on an unvalidated header."
+                    .to_string(),
+            ));
+        };
+        if block.header.height != pending.header.height + 1 {
+            return Err(ChainError::InvalidBlock(format!(
+                "Invalid block height. Expected {}, but got {}.",
+                pending.header.height + 1,
+                block.header.height
+            )));
+        }
+        match block.transactions.as_slice() {
+            [Transaction::Coinbase(coinbase)] if coinbase.fees == Coord::from_num(0) => Ok(()),
+            _ => Err(ChainError::InvalidBlock(
+                "A block on an unvalidated header may only carry a coinbase without fees.".to_string(),
+            )),
+        }
+    }
+
+    /// Hold a block mined on the pending header until its body is applied
+    pub fn hold(&mut self, chain: &Blockchain, block: Block, now: Instant) -> Result<(), ChainError> {
+        self.prune(chain, now);
+        self.check_template(chain, &block)?;
+        self.held = Some(block);
+        Ok(())
+    }
+
+    /// Block mined on the pending header, waiting for its body
+    pub fn held(&self) -> Option<&Block> {
+        self.held.as_ref()
+    }
+
+    /// Call after applying blocks to `chain`: once the pending header's body
+    /// is the tip, the block held on it is returned for applying
+    pub fn body_applied(&mut self, chain: &Blockchain) -> Option<Block> {
+        let tip = chain.blocks.last()?.hash();
+        if self.pending.as_ref().is_some_and(|pending| pending.hash == tip) {
+            self.pending = None;
+            return self.held.take().filter(|block| block.header.previous_hash == tip);
+        }
+        None
+    }
+
+    /// The body of `hash` failed validation: forget the header and the block
+    /// held on it. Returns the header if it was pending.
+    pub fn body_rejected(&mut self, hash: &Sha256Hash) -> Option<UnvalidatedHeader> {
+        if self.pending.as_ref().is_some_and(|pending| pending.hash == *hash) {
+            self.held = None;
+            return self.pending.take();
+        }
+        None
+    }
+
+    /// Drop a header the chain moved past, or whose body is overdue
+    fn prune(&mut self, chain: &Blockchain, now: Instant) {
+        let tip = chain.blocks.last().map(|b| b.hash());
+        let stale = self.pending.as_ref().is_some_and(|pending| {
+            Some(pending.header.previous_hash) != tip
+                || now.saturating_duration_since(pending.received_at) > HEADER_BODY_TIMEOUT
+        });
+        if stale {
+            self.pending = None;
+            self.held = None;
+        }
+    }
+}
+
+/// Checks of a header extending the tip that need no body: proof-of-work,
+/// the required difficulty, the checkpoint and a sane timestamp
+pub fn check_header(chain: &Blockchain, header: &BlockHeader) -> Result<(), ChainError> {
+    if !chain.consensus.pow.meets_target(header) {
+        return Err(ChainError::InvalidBlock(
+            "Invalid Proof-of-Work: Block hash does not meet difficulty target.".to_string(),
+        ));
+    }
+    if header.height >= chain.consensus.emergency_difficulty_height {
+        let required = chain.required_difficulty(header.timestamp);
+        if header.difficulty < required {
+            return Err(ChainError::InvalidBlock(format!(
+                "Block difficulty {} is below the required {}.",
+                header.difficulty, required
+            )));
+        }
+    }
+    if let Some(checkpoint) = &chain.checkpoint {
+        if header.height == checkpoint.height && header.hash() != checkpoint.hash {
+            return Err(ChainError::InvalidBlock(format!(
+                "Block conflicts with the checkpoint at height {}.",
+                checkpoint.height
+            )));
+        }
+    }
+    chain.check_timestamp(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::miner::mine_block;
+    use crate::transaction::CoinbaseTx;
+
+    fn coinbase(height: u64, fees: u32) -> Transaction {
+        Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [1u8; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(fees),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        })
+    }
+
+    fn mine_on(parent: &BlockHeader, difficulty: u32, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block::new(parent.height + 1, parent.hash(), difficulty, transactions);
+        block.header.timestamp = parent.timestamp + 1_000;
+        mine_block(block).unwrap()
+    }
+
+    #[test]
+    fn test_one_block_on_an_unvalidated_header() {
+        let mut chain = Blockchain::new([1u8; 32], 1).unwrap();
+        let tip = chain.blocks[0].header.clone();
+        let relayed = mine_on(&tip, chain.difficulty, vec![coinbase(1, 0)]);
+        let now = Instant::now();
+
+        let mut relay = HeaderRelay::new();
+        assert!(relay.offer(&chain, relayed.header.clone(), "peer", now).unwrap());
+        assert!(!relay.offer(&chain, relayed.header.clone(), "other", now).unwrap());
+        assert_eq!(relay.mining_parent(&chain, now).unwrap().hash, relayed.hash());
+
+        // One fee-less coinbase-only block on the header, nothing more
+        let with_fees = mine_on(&relayed.header, chain.difficulty, vec![coinbase(2, 1)]);
+        assert!(relay.check_template(&chain, &with_fees).is_err());
+        let child = mine_on(&relayed.header, chain.difficulty, vec![coinbase(2, 0)]);
+        let grandchild = mine_on(&child.header, chain.difficulty, vec![coinbase(3, 0)]);
+        assert!(relay.hold(&chain, grandchild, now).is_err());
+        relay.hold(&chain, child.clone(), now).unwrap();
+
+        // The held block is released once the body is applied
+        chain.apply_block(relayed.clone()).unwrap();
+        assert_eq!(relay.body_applied(&chain).unwrap().hash(), child.hash());
+        chain.apply_block(child).unwrap();
+        assert!(relay.mining_parent(&chain, now).is_none());
+    }
+
+    #[test]
+    fn test_rejected_or_overdue_bodies_drop_the_held_block() {
+        let chain = Blockchain::new([1u8; 32], 1).unwrap();
+        let tip = chain.blocks[0].header.clone();
+        let relayed = mine_on(&tip, chain.difficulty, vec![coinbase(1, 0)]);
+        let child = mine_on(&relayed.header, chain.difficulty, vec![coinbase(2, 0)]);
+        let now = Instant::now();
+
+        let mut relay = HeaderRelay::new();
+        relay.offer(&chain, relayed.header.clone(), "peer", now).unwrap();
+        relay.hold(&chain, child.clone(), now).unwrap();
+        assert_eq!(relay.body_rejected(&relayed.hash()).unwrap().peer, "peer");
+        assert!(relay.held().is_none());
+
+        relay.offer(&chain, relayed.header.clone(), "peer", now).unwrap();
+        relay.hold(&chain, child, now).unwrap();
+        assert!(relay.mining_parent(&chain, now + HEADER_BODY_TIMEOUT * 2).is_none());
+        assert!(relay.held().is_none());
+
+        // Headers without valid work are refused
+        let mut forged = relayed.header.clone();
+        forged.difficulty = 250;
+        assert!(relay.offer(&chain, forged, "peer", now).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Cache of blocks and transactions known to be invalid
+//!
+//! A peer relaying the same invalid block or transaction again would make
+//! the node validate it again. Rejections that cannot turn valid later — a
+//! bad signature, bad proof-of-work, a block that fails on top of its own
+//! parent — are remembered here by hash with the reason, and a repeat is
+//! refused without validation and counted against the peer instead.
+//!
+//! The cache holds the [`MAX_KNOWN_INVALID`] most recently seen entries.
+//! With a backing file it survives restarts; the file is written by
+//! [`KnownInvalid::flush`] rather than on every rejection, so a flood of
+//! invalid transactions does not turn into a flood of writes.
+
+use crate::blockchain::Sha256Hash;
+use crate::error::ChainError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the cache inside the data directory
+pub const KNOWN_INVALID_FILE: &str = "known_invalid.json";
+
+/// Entries kept; the least recently seen is dropped for a new one
+pub const MAX_KNOWN_INVALID: usize = 5_000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidKind {
+    Block,
+    Transaction,
+}
+
+/// Why an item was refused, and how often it came back
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InvalidEntry {
+    /// Hex hash of the block or transaction
+    pub hash: String,
+    pub kind: InvalidKind,
+    pub reason: String,
+    /// Unix timestamp (seconds) of the first rejection
+    pub rejected_at: u64,
+    /// Times it was offered again and refused from the cache
+    pub repeats: u64,
+}
+
+/// Bounded least-recently-seen cache of invalid items, optionally backed by
+/// a file
+#[derive(Debug, Default)]
+pub struct KnownInvalid {
+    path: Option<PathBuf>,
+    /// Entry and its position in `order`
+    entries: HashMap<Sha256Hash, (InvalidEntry, u64)>,
+    /// Hashes by when they were last seen, oldest first
+    order: BTreeMap<u64, Sha256Hash>,
+    next_seen: u64,
+    /// Changed since the last flush
+    dirty: bool,
+}
+
+impl KnownInvalid {
+    /// Create an in-memory cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the cache stored at `path`. A missing file yields an empty
+    /// cache that is created on the first flush.
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut cache = KnownInvalid {
+            path: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        if !path.exists() {
+            return Ok(cache);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let entries: Vec<InvalidEntry> = serde_json::from_str(&content).map_err(|e| {
+            ChainError::DatabaseError(format!("Invalid known-invalid cache {}: {}", path.display(), e))
+        })?;
+        for entry in entries {
+            let mut hash = [0u8; 32];
+            if hex::decode_to_slice(&entry.hash, &mut hash).is_ok() {
+                cache.store(hash, entry);
+            }
+        }
+        cache.dirty = false;
+        Ok(cache)
+    }
+
+    /// Load `known_invalid.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(KNOWN_INVALID_FILE))
+    }
+
+    /// Remember `hash` as invalid for `reason`
+    pub fn insert(&mut self, hash: Sha256Hash, kind: InvalidKind, reason: &str, now: u64) {
+        let entry = InvalidEntry {
+            hash: hex::encode(hash),
+            kind,
+            reason: reason.to_string(),
+            rejected_at: now,
+            repeats: 0,
+        };
+        self.store(hash, entry);
+    }
+
+    fn store(&mut self, hash: Sha256Hash, entry: InvalidEntry) {
+        if let Some((_, seen)) = self.entries.remove(&hash) {
+            self.order.remove(&seen);
+        }
+        while self.entries.len() >= MAX_KNOWN_INVALID {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            self.entries.remove(&oldest);
+        }
+        self.order.insert(self.next_seen, hash);
+        self.entries.insert(hash, (entry, self.next_seen));
+        self.next_seen += 1;
+        self.dirty = true;
+    }
+
+    /// The entry for `hash` if it is known invalid, counting the repeat and
+    /// marking it recently seen
+    pub fn check(&mut self, hash: &Sha256Hash) -> Option<InvalidEntry> {
+        let (entry, seen) = self.entries.get_mut(hash)?;
+        entry.repeats += 1;
+        self.order.remove(seen);
+        *seen = self.next_seen;
+        self.order.insert(self.next_seen, *hash);
+        self.next_seen += 1;
+        self.dirty = true;
+        Some(entry.clone())
+    }
+
+    pub fn contains(&self, hash: &Sha256Hash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// All entries, most recently seen first
+    pub fn list(&self) -> Vec<InvalidEntry> {
+        self.order.values().rev().map(|hash| self.entries[hash].0.clone()).collect()
+    }
+
+    /// Write the cache to its backing file if it changed (no-op for
+    /// in-memory caches)
+    pub fn flush(&mut self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        // Oldest first, so a reload restores the same order
+        let entries: Vec<&InvalidEntry> = self.order.values().map(|hash| &self.entries[hash].0).collect();
+        let json = serde_json::to_string(&entries).map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to serialize known-invalid cache: {}", e))
+        })?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: usize) -> Sha256Hash {
+        let mut hash = [0u8; 32];
+        hash[..8].copy_from_slice(&(n as u64).to_le_bytes());
+        hash
+    }
+
+    #[test]
+    fn test_least_recently_seen_is_dropped_and_cache_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = KnownInvalid::load_from_data_dir(dir.path()).unwrap();
+        for n in 0..MAX_KNOWN_INVALID {
+            cache.insert(hash(n), InvalidKind::Transaction, "Invalid signature", 1_700_000_000);
+        }
+        // Seeing the oldest again keeps it; the next oldest goes instead
+        assert_eq!(cache.check(&hash(0)).unwrap().repeats, 1);
+        cache.insert(hash(MAX_KNOWN_INVALID), InvalidKind::Block, "Invalid proof of work", 1_700_000_001);
+        assert_eq!(cache.len(), MAX_KNOWN_INVALID);
+        assert!(cache.contains(&hash(0)));
+        assert!(!cache.contains(&hash(1)));
+        assert!(cache.check(&hash(1)).is_none());
+        assert_eq!(cache.list()[0].kind, InvalidKind::Block);
+
+        // Nothing is written until the flush
+        assert!(KnownInvalid::load_from_data_dir(dir.path()).unwrap().is_empty());
+        cache.flush().unwrap();
+        let mut reloaded = KnownInvalid::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.list(), cache.list());
+        assert_eq!(reloaded.check(&hash(0)).unwrap().repeats, 2);
+    }
+}
@@ -9,13 +9,17 @@
 //! - [`transaction`] - Transaction types and operations
 //! - [`block`] - Block structure and validation
 //! - [`mempool`] - Transaction mempool
+//! - [`memo`] - Plaintext and recipient-encrypted transfer memos
 //!
 //! ## Geometric System  
 //! - [`geometry`] - Triangle primitives and calculations
 //! - [`fees`] - Fee calculations (geometric)
+//! - [`units`] - TRC denominations and amount formatting
 //!
 //! ## Consensus
 //! - [`miner`] - Proof-of-work mining
+//! - [`checkpoint`] - Optional authority-signed checkpoints
+//! - [`manifest`] - Operator-signed manifests for mirrored chain exports
 //!
 //! ## Cryptography
 //! - [`crypto`] - Signatures and verification (secp256k1)
@@ -24,19 +28,54 @@
 //! ## State Management
 //! - [`wallet`] - Wallet operations and UTXO selection
 //! - [`hdwallet`] - HD wallet (BIP-39/BIP-32)
+//! - [`payment`] - Pay-by-value planning (UTXO selection and subdivision)
+//! - [`qr`] - QR codes for addresses and `trinity:` payment requests
 //! - [`persistence`] - Database layer (SQLite)
+//! - [`maintenance`] - Scheduled database compaction and integrity checks
+//! - [`audit`] - Append-only log of transactions submitted through the node
 //! - [`cache`] - Caching utilities
 //!
 //! ## Networking & Integration
 //! - [`network`] - P2P networking
+//! - [`compression`] - zstd compression for P2P messages and snapshots
+//! - [`bootstrap`] - Cold-start database download from a verified chain snapshot
 //! - [`discovery`] - Peer discovery
 //! - [`sync`] - Chain synchronization
+//! - [`headerrelay`] - Header-first block relay and its one-block SPV mining guard
+//! - [`pipeline`] - Staged header and signature checks for downloaded blocks
+//! - [`knowninvalid`] - Cache of invalid blocks and transactions refused without revalidation
+//! - [`peerstats`] - Long-term per-peer statistics for network health
+//! - [`propagation`] - Block and transaction relay latency per peer
+//! - [`txtrace`] - Opt-in hop-by-hop tracing of transaction propagation
+//! - [`sendqueue`] - Bounded per-peer send queues with block priority
+//! - [`bloom`] - Bloom filters limiting transaction relay to light clients
+//! - [`socks`] - SOCKS5 client for connecting to peers over Tor
+//! - [`botpay`] - Telegram bot payments from a hot wallet
+//! - [`notify`] - blocknotify/walletnotify shell command hooks
 //!
 //! ## Configuration & Utilities
 //! - [`config`] - Configuration management
+//! - [`lockfile`] - Single-instance lock on the data directory
+//! - [`doctor`] - Startup self-test of config, storage, ports, clock and wallets
+//! - [`build_info`] - Version, commit and build date of the running binary
+//! - [`telemetry`] - Opt-in anonymized node statistics reporting
+//! - [`watchlist`] - Address watchlists feeding bot, webhook and SSE notifications
+//! - [`watchtower`] - Alerts on spends from cold-storage addresses
+//! - [`tui`] - Terminal dashboard widgets shared by the node and miner
 //! - [`error`] - Error types
 //! - [`cli`] - CLI utilities
 //! - [`addressbook`] - Address book management
+//!
+//! # Features
+//!
+//! Everything above that needs storage, networking or a terminal is behind
+//! the `node` feature, which the default `cli` and `api` features enable.
+//! Building with `--no-default-features --features core` leaves the
+//! consensus core — [`geometry`], [`transaction`], [`memo`], [`crypto`],
+//! [`units`], [`error`] and the block, UTXO state and validation parts of
+//! [`blockchain`] — which compiles for `wasm32-unknown-unknown`, so wallets
+//! and explorers can verify signatures, merkle roots and triangle geometry in
+//! the browser.
 
 #![forbid(unsafe_code)]
 
@@ -44,52 +83,133 @@
 // Core Blockchain
 // ============================================================================
 pub mod blockchain;
+#[cfg(feature = "node")]
 pub mod mempool;
+pub mod memo;
 pub mod transaction;
 
 // ============================================================================
 // Geometric System
 // ============================================================================
+#[cfg(feature = "node")]
 pub mod fees;
 pub mod geometry;
+pub mod units;
 
 // ============================================================================
 // Consensus & Mining
 // ============================================================================
+#[cfg(feature = "node")]
+pub mod checkpoint;
+#[cfg(feature = "node")]
+pub mod manifest;
+#[cfg(feature = "node")]
 pub mod miner;
 
 // ============================================================================
 // Cryptography & Security
 // ============================================================================
+#[cfg(feature = "node")]
+pub mod banlist;
 pub mod crypto;
+#[cfg(feature = "node")]
 pub mod security;
 
 // ============================================================================
 // State Management
 // ============================================================================
+#[cfg(feature = "node")]
+pub mod audit;
+#[cfg(feature = "node")]
 pub mod cache;
+#[cfg(feature = "node")]
 pub mod hdwallet;
+#[cfg(feature = "node")]
+pub mod maintenance;
+#[cfg(feature = "node")]
+pub mod payment;
+#[cfg(feature = "node")]
 pub mod persistence;
+#[cfg(feature = "node")]
+pub mod qr;
+#[cfg(feature = "node")]
+pub mod spending;
+#[cfg(feature = "node")]
 pub mod wallet;
 
 // ============================================================================
 // Networking
 // ============================================================================
+#[cfg(feature = "node")]
+pub mod addnode;
+#[cfg(feature = "node")]
+pub mod bloom;
+#[cfg(feature = "node")]
+pub mod bootstrap;
+#[cfg(feature = "node")]
+pub mod compression;
+#[cfg(feature = "node")]
 pub mod discovery;
+#[cfg(feature = "node")]
+pub mod headerrelay;
+#[cfg(feature = "node")]
+pub mod knowninvalid;
+#[cfg(feature = "node")]
+pub mod netsim;
+#[cfg(feature = "node")]
 pub mod network;
+#[cfg(feature = "node")]
+pub mod pipeline;
+#[cfg(feature = "node")]
+pub mod peerstats;
+#[cfg(feature = "node")]
+pub mod propagation;
+#[cfg(feature = "node")]
+pub mod rejected;
+#[cfg(feature = "node")]
+pub mod sendqueue;
+#[cfg(feature = "node")]
+pub mod socks;
+#[cfg(feature = "node")]
 pub mod sync;
+#[cfg(feature = "node")]
+pub mod txtrace;
 
 // ============================================================================
 // Integration
 // ============================================================================
 #[cfg(feature = "api")]
 pub mod api;
+#[cfg(feature = "node")]
+pub mod botpay;
 
 // ============================================================================
 // Configuration & Utilities
 // ============================================================================
+#[cfg(feature = "node")]
 pub mod addressbook;
+#[cfg(feature = "node")]
+pub mod build_info;
+#[cfg(feature = "node")]
+pub mod chainspec;
+#[cfg(feature = "node")]
 pub mod cli;
+#[cfg(feature = "node")]
 pub mod config;
 pub mod error;
+#[cfg(feature = "node")]
+pub mod doctor;
+#[cfg(feature = "node")]
+pub mod lockfile;
+#[cfg(feature = "node")]
 pub mod node;
+#[cfg(feature = "node")]
+pub mod notify;
+#[cfg(feature = "node")]
+pub mod telemetry;
+#[cfg(feature = "node")]
+pub mod tui;
+#[cfg(feature = "node")]
+pub mod watchlist;
+#[cfg(feature = "node")]
+pub mod watchtower;
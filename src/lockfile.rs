@@ -0,0 +1,162 @@
+//! Single-instance lock on the data directory
+//!
+//! Two processes writing the same chain database (say `trinity-node` and
+//! `trinity-miner`) corrupt each other's state. Every process that opens the
+//! data directory read-write first takes an exclusive OS lock on
+//! [`LOCK_FILE`] and writes its PID into it, so a second one can say who is
+//! in the way. The OS releases the lock when the holder exits, even if it
+//! crashes; a PID left behind by a crashed process is treated as stale and
+//! replaced. Read-only tools such as `trinity-balance` take no lock.
+
+use crate::error::ChainError;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// File name of the lock inside the data directory
+pub const LOCK_FILE: &str = "trinitychain.lock";
+
+/// Exclusive hold on a data directory, released when dropped or when the
+/// process exits
+#[derive(Debug)]
+pub struct DataDirLock {
+    file: File,
+    path: PathBuf,
+    /// PID a crashed process left in the lock file
+    stale_pid: Option<u32>,
+}
+
+impl DataDirLock {
+    /// Lock `data_dir` for this process, failing with a message naming the
+    /// current holder if another process has it
+    pub fn acquire(data_dir: &Path) -> Result<Self, ChainError> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let holder = read_pid(&mut file);
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(in_use(data_dir, holder)),
+            // Without OS locks (some network filesystems) fall back to
+            // asking whether the recorded process still runs
+            Err(TryLockError::Error(e)) if e.kind() == std::io::ErrorKind::Unsupported => {
+                if let Some(pid) = holder.filter(|&pid| pid != std::process::id() && process_alive(pid)) {
+                    return Err(in_use(data_dir, Some(pid)));
+                }
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(DataDirLock {
+            file,
+            path,
+            stale_pid: holder.filter(|&pid| pid != std::process::id()),
+        })
+    }
+
+    /// Lock the configured data directory, see [`crate::config::data_dir`]
+    pub fn acquire_default() -> Result<Self, ChainError> {
+        Self::acquire(&crate::config::data_dir())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// PID of a process that exited without releasing the lock, if the lock
+    /// was taken over from one
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale_pid
+    }
+}
+
+impl Drop for DataDirLock {
+    fn drop(&mut self) {
+        // Leave the file but empty it, so the next process does not mistake
+        // a clean exit for a crash. Removing it could race a process that
+        // has it open and is about to lock it.
+        let _ = self.file.set_len(0);
+    }
+}
+
+/// PID recorded in the lock file, if any
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+fn in_use(data_dir: &Path, holder: Option<u32>) -> ChainError {
+    let holder = holder.map_or_else(|| "another process".to_string(), |pid| format!("process {}", pid));
+    ChainError::DatabaseError(format!(
+        "Data directory {} is in use by {}. Only one process may open it read-write at a time: \
+         stop it, or work through the running node's API instead (e.g. `trinity-node mempool`, \
+         POST /api/mining/start rather than trinity-miner)",
+        data_dir.display(),
+        holder
+    ))
+}
+
+fn process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_holder_is_refused_until_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = DataDirLock::acquire(dir.path()).unwrap();
+        assert_eq!(lock.stale_pid(), None);
+        assert_eq!(
+            std::fs::read_to_string(lock.path()).unwrap(),
+            std::process::id().to_string()
+        );
+
+        // Locks are per open file, so a second handle in this process is
+        // refused like another process would be
+        let err = DataDirLock::acquire(dir.path()).unwrap_err().to_string();
+        assert!(err.contains(&format!("process {}", std::process::id())));
+        assert!(err.contains("API"));
+
+        drop(lock);
+        assert_eq!(std::fs::read_to_string(dir.path().join(LOCK_FILE)).unwrap(), "");
+        DataDirLock::acquire(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn test_lock_left_by_a_crash_is_taken_over() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(LOCK_FILE), "4000000").unwrap();
+        let lock = DataDirLock::acquire(dir.path()).unwrap();
+        assert_eq!(lock.stale_pid(), Some(4_000_000));
+        assert_eq!(
+            std::fs::read_to_string(lock.path()).unwrap(),
+            std::process::id().to_string()
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Scheduled database maintenance
+//!
+//! Every block rewrites the stored UTXO set, which leaves free pages behind
+//! in SQLite on long-running nodes. The node compacts the database and checks
+//! it against a replay of the chain on the intervals in `[maintenance]`;
+//! `trinity-node db compact` and `trinity-node db check` run the same tasks by
+//! hand.
+
+use crate::config::MaintenanceConfig;
+use crate::error::ChainError;
+use crate::node::NodeState;
+use crate::persistence::Persistence;
+use crate::tui::format_bytes;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    Compact,
+    IntegrityCheck,
+}
+
+impl Task {
+    pub const ALL: [Task; 2] = [Task::Compact, Task::IntegrityCheck];
+
+    /// Time between runs, `None` when the task is turned off
+    pub fn interval(self, config: &MaintenanceConfig) -> Option<Duration> {
+        let hours = match self {
+            Task::Compact => config.compact_interval_hours,
+            Task::IntegrityCheck => config.integrity_check_interval_hours,
+        };
+        (hours > 0).then(|| Duration::from_secs(hours * 60 * 60))
+    }
+}
+
+/// Run `task` once on a blocking thread and log the outcome. Returns whether
+/// the database passed; a compaction always passes if it ran.
+pub async fn run(task: Task, persistence: Arc<Box<dyn Persistence>>) -> Result<bool, ChainError> {
+    tokio::task::spawn_blocking(move || match task {
+        Task::Compact => {
+            let stats = persistence.compact()?;
+            info!(
+                "Compacted database: {} -> {} ({} reclaimed)",
+                format_bytes(stats.bytes_before),
+                format_bytes(stats.bytes_after),
+                format_bytes(stats.reclaimed())
+            );
+            Ok(true)
+        }
+        Task::IntegrityCheck => {
+            let report = persistence.integrity_check()?;
+            if report.is_ok() {
+                info!("Database integrity check: {}", report.summary());
+            } else {
+                error!("Database integrity check failed: {}", report.summary());
+            }
+            Ok(report.is_ok())
+        }
+    })
+    .await
+    .map_err(|e| ChainError::DatabaseError(format!("Maintenance task panicked: {}", e)))?
+}
+
+/// Run every enabled task on its interval until the process exits, first one
+/// interval after startup. A failed integrity check marks the node degraded.
+pub fn spawn(config: &MaintenanceConfig, persistence: Arc<Box<dyn Persistence>>, state: Arc<RwLock<NodeState>>) {
+    for task in Task::ALL {
+        let Some(interval) = task.interval(config) else {
+            continue;
+        };
+        let persistence = persistence.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                match run(task, persistence.clone()).await {
+                    Ok(true) => {}
+                    Ok(false) => *state.write().await = NodeState::Degraded,
+                    Err(e) => error!("Database maintenance ({:?}) failed: {}", task, e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::persistence::InMemoryPersistence;
+
+    #[tokio::test]
+    async fn test_tasks_run_and_report() {
+        let config = MaintenanceConfig {
+            compact_interval_hours: 0,
+            integrity_check_interval_hours: 24,
+        };
+        assert_eq!(Task::Compact.interval(&config), None);
+        assert_eq!(Task::IntegrityCheck.interval(&config), Some(Duration::from_secs(86_400)));
+
+        let memory = InMemoryPersistence::new();
+        Blockchain::new_with_persistence([1u8; 32], 0, Box::new(memory.clone())).unwrap();
+        let persistence: Arc<Box<dyn Persistence>> = Arc::new(Box::new(memory.clone()));
+        assert!(run(Task::Compact, persistence.clone()).await.unwrap());
+        assert!(run(Task::IntegrityCheck, persistence.clone()).await.unwrap());
+
+        memory.state.lock().unwrap().utxo_set.clear();
+        assert!(!run(Task::IntegrityCheck, persistence).await.unwrap());
+    }
+}
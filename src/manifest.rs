@@ -0,0 +1,233 @@
+//! Signed manifests for mirrored chain exports
+//!
+//! Snapshots are often served by third-party mirrors. A manifest lists the
+//! files of an export with their size and SHA-256 and is signed by the
+//! operator who produced it, so a download from any mirror can be checked
+//! against a key the user already trusts. `trinity-node db snapshot --sign`
+//! writes one next to the snapshot, `trinity-node bootstrap --publisher`
+//! checks it before replaying, and `trinity-node manifest` signs and
+//! verifies arbitrary files.
+
+use crate::blockchain::Sha256Hash;
+use crate::checkpoint::{hex_bytes, hex_hash};
+use crate::crypto::{verify_signature, KeyPair};
+use crate::error::ChainError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Domain separator so manifest signatures cannot be replayed as anything else
+const SIGNING_DOMAIN: &[u8] = b"TrinityChain manifest";
+
+/// Format version of manifest files
+const MANIFEST_VERSION: u32 = 1;
+
+/// Largest manifest accepted
+pub const MAX_MANIFEST_SIZE: u64 = 1 << 20;
+
+/// One exported file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// File name, without directories
+    pub name: String,
+    pub size: u64,
+    #[serde(with = "hex_hash")]
+    pub sha256: Sha256Hash,
+}
+
+impl ManifestEntry {
+    /// Describe the file at `path`
+    pub fn of_file(path: &Path) -> Result<Self, ChainError> {
+        let name = file_name(path)?;
+        let (size, sha256) = hash_file(path)?;
+        Ok(ManifestEntry { name, size, sha256 })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// RFC 3339 time of signing
+    pub created: String,
+    pub files: Vec<ManifestEntry>,
+    /// Compressed public key of the signer, hex in JSON
+    #[serde(with = "hex_bytes")]
+    pub public_key: Vec<u8>,
+    /// Compact signature over [`Manifest::signable_message`], hex in JSON
+    #[serde(with = "hex_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl Manifest {
+    /// Hash `paths` and sign the list with `keypair`
+    pub fn create(paths: &[PathBuf], keypair: &KeyPair) -> Result<Self, ChainError> {
+        let files = paths
+            .iter()
+            .map(|path| ManifestEntry::of_file(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut manifest = Manifest {
+            version: MANIFEST_VERSION,
+            created: chrono::Utc::now().to_rfc3339(),
+            files,
+            public_key: keypair.public_key_bytes().to_vec(),
+            signature: Vec::new(),
+        };
+        manifest.signature = keypair.sign(&manifest.signable_message())?.to_vec();
+        Ok(manifest)
+    }
+
+    /// Bytes the signer signs: the domain, the version, the creation time and
+    /// every entry, each string prefixed by its length (little endian)
+    pub fn signable_message(&self) -> Vec<u8> {
+        let mut message = SIGNING_DOMAIN.to_vec();
+        message.extend_from_slice(&self.version.to_le_bytes());
+        message.extend_from_slice(&(self.created.len() as u64).to_le_bytes());
+        message.extend_from_slice(self.created.as_bytes());
+        for entry in &self.files {
+            message.extend_from_slice(&(entry.name.len() as u64).to_le_bytes());
+            message.extend_from_slice(entry.name.as_bytes());
+            message.extend_from_slice(&entry.size.to_le_bytes());
+            message.extend_from_slice(&entry.sha256);
+        }
+        message
+    }
+
+    /// Address of the signer
+    pub fn signer_address(&self) -> String {
+        hex::encode(Sha256::digest(&self.public_key))
+    }
+
+    /// Check the signature and that it was made by `publisher`, given as a
+    /// hex public key or address
+    pub fn verify(&self, publisher: &str) -> Result<(), ChainError> {
+        if self.version != MANIFEST_VERSION {
+            return Err(ChainError::CryptoError(format!(
+                "Unsupported manifest version {}",
+                self.version
+            )));
+        }
+        let publisher = publisher.to_lowercase();
+        if publisher != hex::encode(&self.public_key) && publisher != self.signer_address() {
+            return Err(ChainError::CryptoError(format!(
+                "Manifest is signed by {}, not by {}",
+                self.signer_address(),
+                publisher
+            )));
+        }
+        verify_signature(&self.public_key, &self.signable_message(), &self.signature)
+            .map_err(|e| ChainError::CryptoError(format!("Manifest signature is invalid: {}", e)))
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&ManifestEntry> {
+        self.files.iter().find(|entry| entry.name == name)
+    }
+
+    /// Check the file at `path` against the entry `name`
+    pub fn verify_file(&self, name: &str, path: &Path) -> Result<(), ChainError> {
+        let entry = self
+            .entry(name)
+            .ok_or_else(|| ChainError::CryptoError(format!("Manifest does not list '{}'", name)))?;
+        let (size, sha256) = hash_file(path)?;
+        if size != entry.size || sha256 != entry.sha256 {
+            return Err(ChainError::CryptoError(format!(
+                "'{}' does not match its manifest entry ({} bytes, SHA-256 {}; expected {} bytes, {})",
+                name,
+                size,
+                hex::encode(sha256),
+                entry.size,
+                hex::encode(entry.sha256)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check every listed file in `dir`
+    pub fn verify_files(&self, dir: &Path) -> Result<(), ChainError> {
+        for entry in &self.files {
+            self.verify_file(&entry.name, &dir.join(&entry.name))?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ChainError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| ChainError::CryptoError(format!("Failed to encode manifest: {}", e)))?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        serde_json::from_str(&std::fs::read_to_string(path)?)
+            .map_err(|e| ChainError::CryptoError(format!("Invalid manifest {}: {}", path.display(), e)))
+    }
+}
+
+/// Where the manifest of `file` is written: `<file>.manifest.json`
+pub fn manifest_path(file: &Path) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(".manifest.json");
+    PathBuf::from(path)
+}
+
+fn file_name(path: &Path) -> Result<String, ChainError> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| ChainError::CryptoError(format!("{} has no file name", path.display())))
+}
+
+/// Size and SHA-256 of a file, read in chunks
+fn hash_file(path: &Path) -> Result<(u64, Sha256Hash), ChainError> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((size, hasher.finalize().into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("chain.snapshot");
+        let notes = dir.path().join("NOTES.txt");
+        std::fs::write(&snapshot, b"blocks").unwrap();
+        std::fs::write(&notes, b"height 100").unwrap();
+        let operator = KeyPair::generate().unwrap();
+        let address = hex::encode(operator.address());
+
+        let manifest = Manifest::create(&[snapshot.clone(), notes.clone()], &operator).unwrap();
+        let path = manifest_path(&snapshot);
+        assert!(path.to_string_lossy().ends_with("chain.snapshot.manifest.json"));
+        manifest.save(&path).unwrap();
+        let loaded = Manifest::load(&path).unwrap();
+        assert_eq!(loaded, manifest);
+
+        loaded.verify(&address).unwrap();
+        loaded.verify(&hex::encode(operator.public_key_bytes())).unwrap();
+        loaded.verify_files(dir.path()).unwrap();
+        let stranger = hex::encode(KeyPair::generate().unwrap().address());
+        assert!(loaded.verify(&stranger).is_err());
+
+        // Editing the list breaks the signature, editing a file its entry
+        let mut forged = loaded.clone();
+        forged.files[0].size += 1;
+        assert!(forged.verify(&address).is_err());
+        std::fs::write(&notes, b"height 101").unwrap();
+        assert!(loaded.verify_file("NOTES.txt", &notes).is_err());
+        assert!(loaded.verify_file("chain.snapshot", &snapshot).is_ok());
+        assert!(loaded.verify_file("other", &snapshot).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! Transfer memos
+//!
+//! A memo is either a plaintext public note or encrypted to the recipient's
+//! public key, ECIES style: the sender makes an ephemeral secp256k1 key,
+//! derives an AES-256-GCM key from its ECDH secret with the recipient's key
+//! and stores `enc1:` followed by base64 of the ephemeral public key, the
+//! nonce and the ciphertext. Only the recipient's secret key recovers the
+//! text; everyone else just sees that an encrypted memo is attached.
+
+use crate::crypto::{Address, KeyPair};
+use crate::error::ChainError;
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+use secp256k1::{constants::PUBLIC_KEY_SIZE, ecdh::SharedSecret, PublicKey, SecretKey};
+use sha2::{Digest, Sha256};
+
+/// Marks a memo as encrypted
+pub const ENCRYPTED_MEMO_PREFIX: &str = "enc1:";
+
+/// Longest plaintext (in bytes) whose encrypted form still fits in a memo
+pub const MAX_ENCRYPTED_MEMO_LENGTH: usize = 125;
+
+/// Domain separator for the key derivation
+const KDF_DOMAIN: &[u8] = b"TrinityChain memo";
+const NONCE_SIZE: usize = 12;
+
+pub fn is_encrypted(memo: &str) -> bool {
+    memo.starts_with(ENCRYPTED_MEMO_PREFIX)
+}
+
+fn cipher(shared: &SharedSecret, ephemeral: &PublicKey) -> Result<Aes256Gcm, ChainError> {
+    let mut hasher = Sha256::new();
+    hasher.update(KDF_DOMAIN);
+    hasher.update(shared.secret_bytes());
+    hasher.update(ephemeral.serialize());
+    Aes256Gcm::new_from_slice(&hasher.finalize())
+        .map_err(|e| ChainError::CryptoError(format!("Failed to create cipher: {}", e)))
+}
+
+/// Encrypt `plaintext` so that only the holder of `recipient_public_key`
+/// (compressed secp256k1) can read it
+pub fn encrypt(plaintext: &str, recipient_public_key: &[u8]) -> Result<String, ChainError> {
+    if plaintext.len() > MAX_ENCRYPTED_MEMO_LENGTH {
+        return Err(ChainError::InvalidTransaction(format!(
+            "Encrypted memo exceeds maximum length of {} bytes",
+            MAX_ENCRYPTED_MEMO_LENGTH
+        )));
+    }
+    let recipient = PublicKey::from_slice(recipient_public_key)
+        .map_err(|e| ChainError::CryptoError(format!("Invalid recipient public key: {}", e)))?;
+    let ephemeral = KeyPair::from_secret_key(SecretKey::new(&mut OsRng));
+    let shared = SharedSecret::new(&recipient, &ephemeral.secret_key);
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(&shared, &ephemeral.public_key)?
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| ChainError::CryptoError(format!("Encryption failed: {}", e)))?;
+
+    let mut payload = ephemeral.public_key_bytes().to_vec();
+    payload.extend_from_slice(&nonce);
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", ENCRYPTED_MEMO_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+/// Decrypt an encrypted memo with `keypair`. Fails if the memo is not
+/// encrypted, malformed, or addressed to someone else.
+pub fn decrypt(memo: &str, keypair: &KeyPair) -> Result<String, ChainError> {
+    let encoded = memo
+        .strip_prefix(ENCRYPTED_MEMO_PREFIX)
+        .ok_or_else(|| ChainError::CryptoError("Memo is not encrypted".to_string()))?;
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| ChainError::CryptoError(format!("Invalid encrypted memo: {}", e)))?;
+    if payload.len() < PUBLIC_KEY_SIZE + NONCE_SIZE {
+        return Err(ChainError::CryptoError("Encrypted memo is truncated".to_string()));
+    }
+    let (ephemeral, rest) = payload.split_at(PUBLIC_KEY_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+    let ephemeral = PublicKey::from_slice(ephemeral)
+        .map_err(|e| ChainError::CryptoError(format!("Invalid encrypted memo: {}", e)))?;
+    let shared = SharedSecret::new(&ephemeral, &keypair.secret_key);
+
+    let plaintext = cipher(&shared, &ephemeral)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| ChainError::CryptoError("Memo is not addressed to this key".to_string()))?;
+    String::from_utf8(plaintext).map_err(|e| ChainError::CryptoError(format!("Invalid UTF-8: {}", e)))
+}
+
+/// Text to show for `memo` to the owner of `keypair`, if any: plaintext
+/// memos as they are, encrypted ones decrypted when addressed to them and a
+/// placeholder otherwise
+pub fn display(memo: &str, keypair: Option<&KeyPair>) -> String {
+    if !is_encrypted(memo) {
+        return memo.to_string();
+    }
+    match keypair.map(|keypair| decrypt(memo, keypair)) {
+        Some(Ok(plaintext)) => format!("🔓 {}", plaintext),
+        _ => "🔒 encrypted memo".to_string(),
+    }
+}
+
+/// Check that `public_key` belongs to `address`, so a memo is not
+/// accidentally encrypted to someone other than the payee
+pub fn check_recipient_key(public_key: &[u8], address: &Address) -> Result<(), ChainError> {
+    let derived: Address = Sha256::digest(public_key).into();
+    if derived != *address {
+        return Err(ChainError::CryptoError(
+            "Public key does not belong to the recipient address".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TransferTx;
+
+    #[test]
+    fn test_only_recipient_can_read_memo() {
+        let recipient = KeyPair::generate().unwrap();
+        let eavesdropper = KeyPair::generate().unwrap();
+        let memo = encrypt("invoice 42", &recipient.public_key_bytes()).unwrap();
+
+        assert!(is_encrypted(&memo));
+        assert!(!memo.contains("invoice"));
+        assert_eq!(decrypt(&memo, &recipient).unwrap(), "invoice 42");
+        assert!(decrypt(&memo, &eavesdropper).is_err());
+        assert_eq!(display(&memo, Some(&recipient)), "🔓 invoice 42");
+        assert_eq!(display(&memo, Some(&eavesdropper)), "🔒 encrypted memo");
+        assert_eq!(display("public note", None), "public note");
+    }
+
+    #[test]
+    fn test_longest_encrypted_memo_fits() {
+        let recipient = KeyPair::generate().unwrap();
+        let longest = "x".repeat(MAX_ENCRYPTED_MEMO_LENGTH);
+        let memo = encrypt(&longest, &recipient.public_key_bytes()).unwrap();
+        assert!(memo.len() <= TransferTx::MAX_MEMO_LENGTH);
+        assert!(encrypt(&format!("{}x", longest), &recipient.public_key_bytes()).is_err());
+    }
+}
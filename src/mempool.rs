@@ -1,15 +1,59 @@
 //! Mempool for TrinityChain
 
-use crate::blockchain::Sha256Hash;
+use crate::blockchain::{system_clock, Clock, Sha256Hash, TriangleState};
 use crate::crypto::Address;
 use crate::error::ChainError;
-use crate::transaction::Transaction;
-use chrono::Utc;
+use crate::geometry::{coord_checked_add, Coord, Triangle};
+use crate::transaction::{SigningDomain, Transaction, TransferTx};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
 
-const MAX_MEMPOOL_SIZE: usize = 10000; // Max transactions in mempool
+/// Default for [`MempoolLimits::max_transactions`]
+pub const DEFAULT_MEMPOOL_MAX_TRANSACTIONS: usize = 10_000;
+/// Default for [`MempoolLimits::max_bytes`]
+pub const DEFAULT_MEMPOOL_MAX_BYTES: usize = 64 * 1024 * 1024;
 const MAX_TX_PER_ADDRESS: usize = 100; // Max transactions per sender address
+/// Max unconfirmed ancestors of a transaction, itself included
+pub const MAX_ANCESTORS: usize = 25;
+/// Max unconfirmed descendants of a transaction, itself included
+pub const MAX_DESCENDANTS: usize = 25;
+/// How long a transaction may stay pending before it is dropped
+pub const MEMPOOL_EXPIRY: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+/// Fee a replacement must pay on top of the fees of everything it evicts
+/// (about 1e-4), so the same inputs cannot be relayed again and again for
+/// free
+pub const MIN_REPLACEMENT_FEE_INCREMENT: Coord = Coord::from_bits(429_497);
+
+/// Caps on what the mempool holds, set from `[memory]` in the node config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MempoolLimits {
+    pub max_transactions: usize,
+    /// Summed serialized size of the pending transactions
+    pub max_bytes: usize,
+}
+
+impl Default for MempoolLimits {
+    fn default() -> Self {
+        MempoolLimits {
+            max_transactions: DEFAULT_MEMPOOL_MAX_TRANSACTIONS,
+            max_bytes: DEFAULT_MEMPOOL_MAX_BYTES,
+        }
+    }
+}
+
+/// Mempool fill against its [`MempoolLimits`]
+#[derive(Debug, Clone, Serialize)]
+pub struct MempoolUsage {
+    pub transactions: usize,
+    pub bytes: usize,
+    pub max_transactions: usize,
+    pub max_bytes: usize,
+    /// Transactions evicted to make room since the node started
+    pub evicted: u64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MempoolTransaction {
@@ -17,11 +61,155 @@ pub struct MempoolTransaction {
     timestamp: i64,
 }
 
+/// Operator-facing view of a pending transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct MempoolEntryInfo {
+    pub hash: String,
+    pub fee_area: f64,
+    /// Serialized size in bytes
+    pub size: usize,
+    /// Fee area per byte of serialized transaction
+    pub fee_per_byte: f64,
+    /// Fee area per kilobyte of serialized transaction
+    pub fee_density: f64,
+    /// Pinned by the operator for inclusion ahead of fee order
+    pub prioritized: bool,
+    /// Unix timestamp (seconds) when the transaction entered the mempool
+    pub received_at: i64,
+}
+
+/// How a pending transaction fails to fit the chain state and the rest of
+/// the mempool; see [`Mempool::conflicts`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Another pending transaction spends the same input
+    DoubleSpend,
+    /// The input is neither an unspent triangle nor a pending output
+    MissingInput,
+}
+
+/// How pending transactions change an address's balance once mined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingBalance {
+    /// Value pending transactions pay to the address
+    pub incoming: Coord,
+    /// Value pending transactions take from it, fees included
+    pub outgoing: Coord,
+    /// Confirmed value that pending transactions spend. Change only comes
+    /// back once they are mined, so this is unavailable until then.
+    pub locked: Coord,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MempoolConflict {
+    pub hash: String,
+    pub input: String,
+    pub kind: ConflictKind,
+    /// The other spender, for double spends
+    pub other: Option<String>,
+}
+
+/// Format version written into [`MempoolSnapshot`]s
+pub const MEMPOOL_SNAPSHOT_VERSION: u32 = 1;
+
+/// Pending transactions written out to be loaded into another node, e.g.
+/// when moving a node to new hardware or attaching a mempool to a bug report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolSnapshot {
+    pub version: u32,
+    /// Unix milliseconds
+    pub created_at: u64,
+    /// Tip the transactions were pending on
+    pub tip_height: u64,
+    pub tip_hash: String,
+    /// Every transaction after the pending one it spends from
+    pub entries: Vec<SnapshotEntry>,
+}
+
+/// One pending transaction of a [`MempoolSnapshot`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub txid: String,
+    /// Unix timestamp (seconds) when the transaction entered the mempool
+    pub received_at: i64,
+    pub prioritized: bool,
+    /// Hex of the bincode encoding
+    pub raw: String,
+}
+
+impl SnapshotEntry {
+    /// The transaction, checked against the entry's txid
+    pub fn transaction(&self) -> Result<Transaction, ChainError> {
+        let bytes = hex::decode(self.raw.trim())
+            .map_err(|e| ChainError::InvalidTransaction(format!("Invalid hex: {}", e)))?;
+        let tx: Transaction = bincode::deserialize(&bytes)
+            .map_err(|e| ChainError::InvalidTransaction(format!("Malformed transaction: {}", e)))?;
+        if !tx.hash_str().eq_ignore_ascii_case(&self.txid) {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Encoded transaction hashes to {}",
+                tx.hash_str()
+            )));
+        }
+        Ok(tx)
+    }
+}
+
+/// Outcome of loading a [`MempoolSnapshot`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RestoreReport {
+    /// Txids of the entries added to the mempool
+    pub restored: Vec<String>,
+    /// Entries that were pending here already
+    pub already_pending: usize,
+    /// Entries that no longer validate, e.g. because they were mined or
+    /// their inputs spent since the snapshot
+    pub rejected: Vec<RejectedEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RejectedEntry {
+    pub txid: String,
+    pub reason: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mempool {
     transactions: HashMap<Sha256Hash, MempoolTransaction>,
     #[serde(skip)]
     by_sender: HashMap<Address, Vec<Sha256Hash>>,
+    /// Transactions the operator pinned for inclusion regardless of fee
+    #[serde(default)]
+    prioritized: HashSet<Sha256Hash>,
+    /// UTXOs created by pending transactions, mapped to the creating transaction
+    #[serde(skip)]
+    outputs: HashMap<Sha256Hash, Sha256Hash>,
+    /// Unconfirmed parent of a pending transaction. Only the primary input
+    /// can be pending: fee sponsors pay from confirmed triangles.
+    #[serde(skip)]
+    parent: HashMap<Sha256Hash, Sha256Hash>,
+    /// Pending transactions spending outputs of a pending transaction
+    #[serde(skip)]
+    children: HashMap<Sha256Hash, Vec<Sha256Hash>>,
+    /// UTXOs, confirmed or pending, consumed by a pending transaction,
+    /// mapped to the spender
+    #[serde(skip)]
+    spent: HashMap<Sha256Hash, Sha256Hash>,
+    /// Triangles of the outputs of validated pending transactions. With
+    /// `spent` this projects the chain state as if the mempool were mined.
+    #[serde(skip)]
+    created: HashMap<Sha256Hash, Triangle>,
+    /// Time of receipt and expiry
+    #[serde(skip, default = "system_clock")]
+    clock: Arc<dyn Clock>,
+    #[serde(skip)]
+    limits: MempoolLimits,
+    /// Summed serialized size of `transactions`
+    #[serde(skip)]
+    bytes: usize,
+    /// Transactions evicted to make room
+    #[serde(skip)]
+    evicted: u64,
 }
 
 impl Default for Mempool {
@@ -35,24 +223,298 @@ impl Mempool {
         Mempool {
             transactions: HashMap::new(),
             by_sender: HashMap::new(),
+            prioritized: HashSet::new(),
+            outputs: HashMap::new(),
+            parent: HashMap::new(),
+            children: HashMap::new(),
+            spent: HashMap::new(),
+            created: HashMap::new(),
+            clock: system_clock(),
+            limits: MempoolLimits::default(),
+            bytes: 0,
+            evicted: 0,
         }
     }
 
-    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), ChainError> {
-        if self.transactions.len() >= MAX_MEMPOOL_SIZE {
-            self.evict_lowest_fee()?;
+    /// Hold at most what `limits` allow from now on. Transactions already
+    /// pending stay until they leave or are evicted for new ones.
+    pub fn set_limits(&mut self, limits: MempoolLimits) {
+        self.limits = limits;
+    }
+
+    pub fn usage(&self) -> MempoolUsage {
+        MempoolUsage {
+            transactions: self.transactions.len(),
+            bytes: self.bytes,
+            max_transactions: self.limits.max_transactions,
+            max_bytes: self.limits.max_bytes,
+            evicted: self.evicted,
         }
+    }
 
-        let tx_hash = tx.hash();
-        if self.transactions.contains_key(&tx_hash) {
+    /// Take the time from `clock` from now on
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Validate `tx` against the projected state: the chain state with the
+    /// effects of every pending transaction applied. A transaction spending
+    /// the output of a pending transaction is accepted as part of that
+    /// package, subject to [`MAX_ANCESTORS`] and [`MAX_DESCENDANTS`]; one
+    /// spending an output another pending transaction already consumes is
+    /// rejected. Signatures are checked for `domain`.
+    pub fn accept_transaction(
+        &mut self,
+        tx: Transaction,
+        state: &TriangleState,
+        domain: &SigningDomain,
+    ) -> Result<(), ChainError> {
+        if self.transactions.contains_key(&tx.hash()) {
+            return Err(ChainError::DuplicateTransaction);
+        }
+        if let Transaction::Announce(announce) = &tx {
+            announce.validate(state)?;
+            tx.validate_size()?;
+            return self.insert(tx, Vec::new());
+        }
+        if let Transaction::Register(register) = &tx {
+            register.validate(state, domain)?;
+            tx.validate_size()?;
+            if self.pending_registration(&register.name).is_some() {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Name '{}' already has a pending registration",
+                    register.name
+                )));
+            }
+            return self.insert(tx, Vec::new());
+        }
+        let input = Self::input_of(&tx).ok_or_else(|| {
+            ChainError::InvalidTransaction("Coinbase transactions cannot be in mempool".to_string())
+        })?;
+        let triangle = self.projected_utxo(&input, state)?;
+
+        if let Some(parent) = self.outputs.get(&input) {
+            let mut chain = self.ancestors(parent);
+            chain.insert(0, *parent);
+            if chain.len() + 1 > MAX_ANCESTORS {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Transaction would have more than {} unconfirmed ancestors",
+                    MAX_ANCESTORS
+                )));
+            }
+            for ancestor in &chain {
+                if self.descendants(ancestor).len() + 2 > MAX_DESCENDANTS {
+                    return Err(ChainError::InvalidTransaction(format!(
+                        "Ancestor {} would exceed {} unconfirmed descendants",
+                        hex::encode(ancestor),
+                        MAX_DESCENDANTS
+                    )));
+                }
+            }
+        }
+
+        // The transaction only reads its inputs, so a view of the projected
+        // state holding just them is enough to validate and apply it
+        let mut view = TriangleState::new();
+        view.utxo_set.insert(input, triangle);
+        if let Transaction::Transfer(TransferTx { sponsor: Some(sponsor), .. }) = &tx {
+            // A pending sponsor triangle would give the transaction a second
+            // unconfirmed parent
+            if self.outputs.contains_key(&sponsor.input_hash) {
+                return Err(ChainError::InvalidTransaction(
+                    "Fee sponsor must pay from a confirmed triangle".to_string(),
+                ));
+            }
+            view.utxo_set.insert(sponsor.input_hash, self.projected_utxo(&sponsor.input_hash, state)?);
+        }
+        match &tx {
+            Transaction::Transfer(transfer) => transfer.validate_with_state(&view, domain)?,
+            Transaction::Subdivision(subdivision) => subdivision.validate(&view, domain)?,
+            // Blocks may carry pruned payloads, relayed transactions may not
+            Transaction::Data(data) if data.is_pruned() => {
+                return Err(ChainError::InvalidTransaction("Data transaction has no payload".to_string()))
+            }
+            Transaction::Data(data) => data.validate(&view, domain)?,
+            Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => {
+                unreachable!("no input")
+            }
+        }
+        tx.validate_size()?;
+        view.apply_transaction(&tx, 0)?;
+
+        let outputs = view.utxo_set.keys().copied().collect();
+        self.insert(tx, outputs)?;
+        self.created.extend(view.utxo_set);
+        Ok(())
+    }
+
+    /// The unspent triangle `utxo` in the projected state
+    fn projected_utxo(&self, utxo: &Sha256Hash, state: &TriangleState) -> Result<Triangle, ChainError> {
+        if let Some(spender) = self.spent.get(utxo) {
+            return Err(ChainError::AlreadySpent {
+                utxo: *utxo,
+                spender: *spender,
+            });
+        }
+        self.created
+            .get(utxo)
+            .or_else(|| state.utxo_set.get(utxo))
+            .cloned()
+            .ok_or(ChainError::UnknownUtxo(*utxo))
+    }
+
+    /// Accept `transactions`, in dependency order, as one package: either all
+    /// of them enter the mempool or none do.
+    pub fn accept_package(
+        &mut self,
+        transactions: Vec<Transaction>,
+        state: &TriangleState,
+        domain: &SigningDomain,
+    ) -> Result<(), ChainError> {
+        let mut staged = self.clone();
+        for tx in transactions {
+            staged.accept_transaction(tx, state, domain)?;
+        }
+        *self = staged;
+        Ok(())
+    }
+
+    /// The pending transactions spending an input of `tx` and, after them,
+    /// their descendants: what accepting `tx` as a replacement would evict
+    fn replaced_by(&self, tx: &Transaction) -> (Vec<Sha256Hash>, Vec<Sha256Hash>) {
+        let mut conflicts: Vec<Sha256Hash> = Vec::new();
+        for input in tx.inputs() {
+            if let Some(spender) = self.spent.get(&input) {
+                if !conflicts.contains(spender) {
+                    conflicts.push(*spender);
+                }
+            }
+        }
+        let mut evicted = conflicts.clone();
+        for conflict in &conflicts {
+            for descendant in self.descendants(conflict) {
+                if !evicted.contains(&descendant) {
+                    evicted.push(descendant);
+                }
+            }
+        }
+        (conflicts, evicted)
+    }
+
+    /// Lowest fee a replacement of the pending `tx_hash` must pay: the fees
+    /// of it and its descendants plus [`MIN_REPLACEMENT_FEE_INCREMENT`]
+    pub fn min_replacement_fee(&self, tx_hash: &Sha256Hash) -> Option<Coord> {
+        let tx = self.get_transaction(tx_hash)?;
+        let (_, evicted) = self.replaced_by(tx);
+        Some(self.replacement_fee(&evicted))
+    }
+
+    fn replacement_fee(&self, evicted: &[Sha256Hash]) -> Coord {
+        evicted
+            .iter()
+            .filter_map(|hash| self.get_transaction(hash))
+            .map(Transaction::fee_area)
+            .fold(MIN_REPLACEMENT_FEE_INCREMENT, Coord::saturating_add)
+    }
+
+    /// Accept `tx` in place of the pending transactions spending the same
+    /// inputs, which are evicted together with their descendants. `tx` must
+    /// pay a higher fee per byte than each transaction it conflicts with,
+    /// and at least [`MIN_REPLACEMENT_FEE_INCREMENT`] more than the fees of
+    /// everything it evicts; it is then validated like any other
+    /// transaction. Returns the evicted transactions.
+    pub fn replace_transaction(
+        &mut self,
+        tx: Transaction,
+        state: &TriangleState,
+        domain: &SigningDomain,
+    ) -> Result<Vec<Sha256Hash>, ChainError> {
+        let (conflicts, evicted) = self.replaced_by(&tx);
+        if evicted.len() > MAX_DESCENDANTS {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Replacement would evict {} transactions (max: {})",
+                evicted.len(),
+                MAX_DESCENDANTS
+            )));
+        }
+        let fee_per_byte = tx.fee_per_byte();
+        for conflict in &conflicts {
+            let replaced = &self.transactions[conflict].tx;
+            if fee_per_byte <= replaced.fee_per_byte() {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Replacement pays {:.6} per byte, not more than the {:.6} of {}",
+                    fee_per_byte,
+                    replaced.fee_per_byte(),
+                    replaced.hash_str()
+                )));
+            }
+        }
+        let required = self.replacement_fee(&evicted);
+        if !conflicts.is_empty() && tx.fee_area() < required {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Replacement pays a fee of {}, at least {} is needed",
+                tx.fee_area(),
+                required
+            )));
+        }
+
+        let mut staged = self.clone();
+        for conflict in &conflicts {
+            staged.remove_with_descendants(conflict);
+        }
+        staged.accept_transaction(tx, state, domain)?;
+        *self = staged;
+        Ok(evicted)
+    }
+
+    /// An unsigned copy of the pending transfer `tx_hash` paying `fee_area`
+    /// instead, for its sender to sign and submit with
+    /// [`replace_transaction`](Self::replace_transaction). Sponsored
+    /// transfers are refused: their fee is not the sender's to raise.
+    pub fn fee_bump(&self, tx_hash: &Sha256Hash, fee_area: Coord) -> Result<TransferTx, ChainError> {
+        let tx = self.get_transaction(tx_hash).ok_or_else(|| {
+            ChainError::InvalidTransaction(format!("{} is not in the mempool", hex::encode(tx_hash)))
+        })?;
+        let Transaction::Transfer(transfer) = tx else {
+            return Err(ChainError::InvalidTransaction("Only transfers can be fee-bumped".to_string()));
+        };
+        if transfer.sponsor.is_some() {
             return Err(ChainError::InvalidTransaction(
-                "Transaction already in mempool".to_string(),
+                "The fee of a sponsored transfer is paid by its sponsor".to_string(),
             ));
         }
+        if fee_area <= transfer.fee_area {
+            return Err(ChainError::InvalidTransaction(format!(
+                "New fee {} does not exceed the current {}",
+                fee_area, transfer.fee_area
+            )));
+        }
+        Ok(TransferTx {
+            fee_area,
+            signature: None,
+            public_key: None,
+            ..transfer.clone()
+        })
+    }
+
+    /// Add a transaction without validating it against chain state
+    pub fn add_transaction(&mut self, tx: Transaction) -> Result<(), ChainError> {
+        let outputs = Self::known_outputs(&tx);
+        self.insert(tx, outputs)
+    }
+
+    fn insert(&mut self, tx: Transaction, outputs: Vec<Sha256Hash>) -> Result<(), ChainError> {
+        let tx_hash = tx.hash();
+        if self.transactions.contains_key(&tx_hash) {
+            return Err(ChainError::DuplicateTransaction);
+        }
 
         let sender = match &tx {
             Transaction::Transfer(tx) => tx.sender,
             Transaction::Subdivision(tx) => tx.owner_address,
+            Transaction::Announce(tx) => tx.address(),
+            Transaction::Register(tx) => tx.owner,
+            Transaction::Data(tx) => tx.owner,
             Transaction::Coinbase(_) => {
                 return Err(ChainError::InvalidTransaction(
                     "Coinbase transactions cannot be in mempool".to_string(),
@@ -60,54 +522,345 @@ impl Mempool {
             }
         };
 
-        let sender_txs = self.by_sender.entry(sender).or_default();
-        if sender_txs.len() >= MAX_TX_PER_ADDRESS {
+        if self.by_sender.get(&sender).map_or(0, Vec::len) >= MAX_TX_PER_ADDRESS {
             return Err(ChainError::InvalidTransaction(
                 "Exceeded maximum transactions per address".to_string(),
             ));
         }
 
+        let inputs = tx.inputs();
+        for utxo in &inputs {
+            if let Some(spender) = self.spent.get(utxo) {
+                return Err(ChainError::AlreadySpent { utxo: *utxo, spender: *spender });
+            }
+        }
+        let size = tx.size();
+        self.make_room(&tx, size)?;
+
+        let input = Self::input_of(&tx);
         let mempool_tx = MempoolTransaction {
             tx,
-            timestamp: Utc::now().timestamp(),
+            timestamp: self.clock.now_secs(),
         };
-
         self.transactions.insert(tx_hash, mempool_tx);
-        sender_txs.push(tx_hash);
+        self.bytes += size;
+        self.by_sender.entry(sender).or_default().push(tx_hash);
+        for utxo in inputs {
+            self.spent.insert(utxo, tx_hash);
+        }
+
+        if let Some(parent) = input.and_then(|input| self.outputs.get(&input).copied()) {
+            self.parent.insert(tx_hash, parent);
+            self.children.entry(parent).or_default().push(tx_hash);
+        }
+        for output in outputs {
+            self.outputs.insert(output, tx_hash);
+        }
 
         Ok(())
     }
 
-    fn evict_lowest_fee(&mut self) -> Result<(), ChainError> {
-        if let Some(eviction_candidate) = self
+    /// Make room under the limits for `tx` of `size` bytes by evicting the
+    /// pending packages that pay least per byte, each with its descendants.
+    /// Only packages paying less per byte than `tx` go, never prioritized
+    /// ones or unconfirmed ancestors of `tx`; if that does not free enough,
+    /// nothing is evicted and `tx` is refused with `MempoolFull`.
+    fn make_room(&mut self, tx: &Transaction, size: usize) -> Result<(), ChainError> {
+        let limits = self.limits;
+        let fits = |count: usize, bytes: usize| count < limits.max_transactions && bytes + size <= limits.max_bytes;
+        if fits(self.transactions.len(), self.bytes) {
+            return Ok(());
+        }
+        if size > limits.max_bytes {
+            return Err(ChainError::MempoolFull);
+        }
+
+        let fee_per_byte = tx.fee_per_byte();
+        let mut protected: HashSet<Sha256Hash> = HashSet::new();
+        if let Some(parent) = Self::input_of(tx).and_then(|input| self.outputs.get(&input).copied()) {
+            protected.extend(self.ancestors(&parent));
+            protected.insert(parent);
+        }
+        let mut candidates: Vec<(f64, Sha256Hash)> = self
             .transactions
-            .values()
-            .min_by_key(|tx| tx.tx.fee_area())
-            .map(|tx| tx.tx.hash())
-        {
-            self.remove_transaction(&eviction_candidate);
-            Ok(())
-        } else {
-            Err(ChainError::MempoolFull)
+            .iter()
+            .filter(|(hash, _)| !self.prioritized.contains(*hash) && !protected.contains(*hash))
+            .map(|(hash, mtx)| (mtx.tx.fee_per_byte(), *hash))
+            .collect();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (mut count, mut bytes) = (self.transactions.len(), self.bytes);
+        let mut doomed: HashSet<Sha256Hash> = HashSet::new();
+        for (density, hash) in candidates {
+            if fits(count, bytes) {
+                break;
+            }
+            if density >= fee_per_byte {
+                break;
+            }
+            if doomed.contains(&hash) {
+                continue;
+            }
+            let mut package = self.descendants(&hash);
+            package.insert(0, hash);
+            // A descendant can be prioritized even if its ancestor is not
+            if package.iter().any(|h| self.prioritized.contains(h)) {
+                continue;
+            }
+            for hash in package {
+                if doomed.insert(hash) {
+                    count -= 1;
+                    bytes = bytes.saturating_sub(self.transactions[&hash].tx.size());
+                }
+            }
         }
+        if !fits(count, bytes) {
+            return Err(ChainError::MempoolFull);
+        }
+
+        for hash in &doomed {
+            self.remove_transaction(hash);
+        }
+        self.evicted += doomed.len() as u64;
+        Ok(())
     }
 
+    /// The primary UTXO a mempool transaction spends, the only one that may
+    /// be a pending output
+    fn input_of(tx: &Transaction) -> Option<Sha256Hash> {
+        tx.inputs().first().copied()
+    }
+
+    /// Outputs of `tx` that can be derived without knowing its input value
+    fn known_outputs(tx: &Transaction) -> Vec<Sha256Hash> {
+        match tx {
+            Transaction::Transfer(_) => vec![tx.hash()],
+            Transaction::Subdivision(tx) => tx.children.iter().map(|c| c.hash()).collect(),
+            // The change of a data transaction exists only if the fee
+            // leaves some value
+            Transaction::Data(_) | Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Pending registration of `name`, if any
+    pub fn pending_registration(&self, name: &str) -> Option<Sha256Hash> {
+        self.transactions.iter().find_map(|(hash, mempool_tx)| match &mempool_tx.tx {
+            Transaction::Register(register) if register.name == name => Some(*hash),
+            _ => None,
+        })
+    }
+
+    /// Unconfirmed ancestors of `tx_hash`, nearest first
+    pub fn ancestors(&self, tx_hash: &Sha256Hash) -> Vec<Sha256Hash> {
+        let mut ancestors = Vec::new();
+        let mut current = *tx_hash;
+        while let Some(parent) = self.parent.get(&current) {
+            ancestors.push(*parent);
+            current = *parent;
+        }
+        ancestors
+    }
+
+    /// Unconfirmed descendants of `tx_hash`
+    pub fn descendants(&self, tx_hash: &Sha256Hash) -> Vec<Sha256Hash> {
+        let mut descendants = Vec::new();
+        let mut queue = vec![*tx_hash];
+        while let Some(hash) = queue.pop() {
+            for child in self.children.get(&hash).into_iter().flatten() {
+                descendants.push(*child);
+                queue.push(*child);
+            }
+        }
+        descendants
+    }
+
+    /// Transactions in block-assembly order: operator-prioritized ones first,
+    /// then by descending fee density, with every transaction placed after its
+    /// unconfirmed parent.
     pub fn get_transactions_by_fee(&self, limit: usize) -> Vec<Transaction> {
-        let mut txs: Vec<Transaction> = self
+        self.get_transactions_favoring(limit, &HashSet::new())
+    }
+
+    /// Transactions in block-assembly order, with those sent by `senders`
+    /// ranked with the operator-prioritized ones regardless of fee
+    pub fn get_transactions_favoring(&self, limit: usize, senders: &HashSet<Address>) -> Vec<Transaction> {
+        self.entries_by_fee_density(senders)
+            .into_iter()
+            .take(limit)
+            .filter_map(|(hash, _)| self.transactions.get(&hash).map(|mtx| mtx.tx.clone()))
+            .collect()
+    }
+
+    /// Hashes of the pending transactions sent by any of `senders`
+    pub fn sent_by(&self, senders: &HashSet<Address>) -> HashSet<Sha256Hash> {
+        senders
+            .iter()
+            .filter_map(|sender| self.by_sender.get(sender))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Pending transactions with their fee density, in block-assembly order
+    pub fn fee_density_report(&self) -> Vec<MempoolEntryInfo> {
+        self.entries_by_fee_density(&HashSet::new())
+            .into_iter()
+            .map(|(_, info)| info)
+            .collect()
+    }
+
+    fn entries_by_fee_density(&self, senders: &HashSet<Address>) -> Vec<(Sha256Hash, MempoolEntryInfo)> {
+        let mut ranked = self.ranked_entries(senders);
+        let rank: HashMap<Sha256Hash, usize> = ranked
+            .iter()
+            .enumerate()
+            .map(|(i, (hash, _))| (*hash, i))
+            .collect();
+
+        // Topological order over the package forest, best rank first
+        let mut ready: BinaryHeap<Reverse<usize>> = ranked
+            .iter()
+            .enumerate()
+            .filter(|(_, (hash, _))| !self.parent.contains_key(hash))
+            .map(|(i, _)| Reverse(i))
+            .collect();
+        let mut slots: Vec<Option<(Sha256Hash, MempoolEntryInfo)>> =
+            ranked.drain(..).map(Some).collect();
+        let mut ordered = Vec::with_capacity(slots.len());
+        while let Some(Reverse(i)) = ready.pop() {
+            let Some((hash, info)) = slots[i].take() else {
+                continue;
+            };
+            for child in self.children.get(&hash).into_iter().flatten() {
+                if let Some(&child_rank) = rank.get(child) {
+                    ready.push(Reverse(child_rank));
+                }
+            }
+            ordered.push((hash, info));
+        }
+        ordered
+    }
+
+    fn ranked_entries(&self, senders: &HashSet<Address>) -> Vec<(Sha256Hash, MempoolEntryInfo)> {
+        let favored = self.sent_by(senders);
+        let mut entries: Vec<(bool, Sha256Hash, MempoolEntryInfo)> = self
             .transactions
-            .values()
-            .map(|mtx| mtx.tx.clone())
+            .iter()
+            .map(|(hash, mtx)| {
+                let fee_per_byte = mtx.tx.fee_per_byte();
+                let info = MempoolEntryInfo {
+                    hash: hex::encode(hash),
+                    fee_area: mtx.tx.fee_area().to_num::<f64>(),
+                    size: mtx.tx.size(),
+                    fee_per_byte,
+                    fee_density: fee_per_byte * 1000.0,
+                    prioritized: self.prioritized.contains(hash),
+                    received_at: mtx.timestamp,
+                };
+                (info.prioritized || favored.contains(hash), *hash, info)
+            })
             .collect();
-        txs.sort_by_key(|b| std::cmp::Reverse(b.fee_area()));
-        txs.truncate(limit);
-        txs
+        entries.sort_by(|(a_first, _, a), (b_first, _, b)| {
+            b_first
+                .cmp(a_first)
+                .then(b.fee_density.total_cmp(&a.fee_density))
+                .then(a.received_at.cmp(&b.received_at))
+        });
+        entries.into_iter().map(|(_, hash, info)| (hash, info)).collect()
     }
 
+    /// Every pending transaction, parents before the transactions spending
+    /// their outputs, with when it was received and whether it is pinned
+    pub fn snapshot(&self, tip_height: u64, tip_hash: &Sha256Hash) -> Result<MempoolSnapshot, ChainError> {
+        let mut pending: Vec<(usize, &Sha256Hash, &MempoolTransaction)> = self
+            .transactions
+            .iter()
+            .map(|(hash, mtx)| (self.ancestors(hash).len(), hash, mtx))
+            .collect();
+        pending.sort_by_key(|(depth, hash, mtx)| (*depth, mtx.timestamp, **hash));
+        let entries = pending
+            .into_iter()
+            .map(|(_, hash, mtx)| {
+                let raw = bincode::serialize(&mtx.tx)
+                    .map_err(|e| ChainError::InvalidTransaction(format!("Serialization failed: {}", e)))?;
+                Ok(SnapshotEntry {
+                    txid: hex::encode(hash),
+                    received_at: mtx.timestamp,
+                    prioritized: self.prioritized.contains(hash),
+                    raw: hex::encode(raw),
+                })
+            })
+            .collect::<Result<_, ChainError>>()?;
+        Ok(MempoolSnapshot {
+            version: MEMPOOL_SNAPSHOT_VERSION,
+            created_at: self.clock.now_millis(),
+            tip_height,
+            tip_hash: hex::encode(tip_hash),
+            entries,
+        })
+    }
+
+    /// Carry over the receive time and pin of a restored snapshot entry
+    pub fn restore_entry(&mut self, tx_hash: &Sha256Hash, entry: &SnapshotEntry) {
+        if let Some(mtx) = self.transactions.get_mut(tx_hash) {
+            mtx.timestamp = entry.received_at;
+            if entry.prioritized {
+                self.prioritized.insert(*tx_hash);
+            }
+        }
+    }
+
+    /// Pin a pending transaction so block templates include it first
+    pub fn prioritize(&mut self, tx_hash: &Sha256Hash) -> Result<(), ChainError> {
+        if !self.transactions.contains_key(tx_hash) {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Transaction {} is not in the mempool",
+                hex::encode(tx_hash)
+            )));
+        }
+        self.prioritized.insert(*tx_hash);
+        Ok(())
+    }
+
+    /// Remove an operator pin. Returns `false` if the transaction was not pinned.
+    pub fn deprioritize(&mut self, tx_hash: &Sha256Hash) -> bool {
+        self.prioritized.remove(tx_hash)
+    }
+
+    /// Remove a transaction, e.g. because it was confirmed. Its pending
+    /// children stay: their input is now a regular UTXO.
     pub fn remove_transaction(&mut self, tx_hash: &Sha256Hash) {
+        self.prioritized.remove(tx_hash);
+        let created = &mut self.created;
+        self.outputs.retain(|output, creator| {
+            if creator == tx_hash {
+                created.remove(output);
+            }
+            creator != tx_hash
+        });
+        self.spent.retain(|_, spender| spender != tx_hash);
+        if let Some(parent) = self.parent.remove(tx_hash) {
+            if let Some(siblings) = self.children.get_mut(&parent) {
+                siblings.retain(|h| h != tx_hash);
+                if siblings.is_empty() {
+                    self.children.remove(&parent);
+                }
+            }
+        }
+        for child in self.children.remove(tx_hash).unwrap_or_default() {
+            self.parent.remove(&child);
+        }
+
         if let Some(mempool_tx) = self.transactions.remove(tx_hash) {
+            self.bytes = self.bytes.saturating_sub(mempool_tx.tx.size());
             let sender = match &mempool_tx.tx {
                 Transaction::Transfer(tx) => tx.sender,
                 Transaction::Subdivision(tx) => tx.owner_address,
+                Transaction::Announce(tx) => tx.address(),
+                Transaction::Register(tx) => tx.owner,
+                Transaction::Data(tx) => tx.owner,
                 Transaction::Coinbase(_) => return,
             };
 
@@ -120,6 +873,48 @@ impl Mempool {
         }
     }
 
+    /// Update the mempool for a newly connected block: drop the transactions
+    /// it confirmed and evict the pending ones spending the same inputs,
+    /// together with their descendants. Returns the number evicted.
+    pub fn confirm_block(&mut self, transactions: &[Transaction]) -> usize {
+        for tx in transactions {
+            self.remove_transaction(&tx.hash());
+        }
+        let conflicts: Vec<Sha256Hash> = transactions
+            .iter()
+            .flat_map(Transaction::inputs)
+            .filter_map(|input| self.spent.get(&input).copied())
+            .collect();
+        conflicts.iter().map(|hash| self.remove_with_descendants(hash)).sum()
+    }
+
+    /// Drop the transactions pending for longer than [`MEMPOOL_EXPIRY`],
+    /// with their descendants. Returns the number removed.
+    pub fn expire(&mut self) -> usize {
+        let cutoff = self.clock.now_secs() - MEMPOOL_EXPIRY.as_secs() as i64;
+        let expired: Vec<Sha256Hash> = self
+            .transactions
+            .iter()
+            .filter(|(_, mtx)| mtx.timestamp <= cutoff)
+            .map(|(hash, _)| *hash)
+            .collect();
+        expired.iter().map(|hash| self.remove_with_descendants(hash)).sum()
+    }
+
+    /// Remove a transaction that will not be confirmed, together with every
+    /// pending transaction that depends on it. Returns the number removed.
+    pub fn remove_with_descendants(&mut self, tx_hash: &Sha256Hash) -> usize {
+        if !self.transactions.contains_key(tx_hash) {
+            return 0;
+        }
+        let mut doomed = self.descendants(tx_hash);
+        doomed.insert(0, *tx_hash);
+        for hash in &doomed {
+            self.remove_transaction(hash);
+        }
+        doomed.len()
+    }
+
     pub fn get_transaction(&self, tx_hash: &Sha256Hash) -> Option<&Transaction> {
         self.transactions.get(tx_hash).map(|mtx| &mtx.tx)
     }
@@ -145,16 +940,539 @@ impl Mempool {
         self.transactions.is_empty()
     }
 
-    pub fn prune(&mut self, state: &crate::blockchain::TriangleState) {
+    /// Effect of the pending transactions on the balance of `address`, whose
+    /// confirmed triangles are in `state`
+    pub fn pending_balance(&self, address: &Address, state: &TriangleState) -> Result<PendingBalance, ChainError> {
+        let mut pending = PendingBalance::default();
+        for mempool_tx in self.transactions.values() {
+            let tx = &mempool_tx.tx;
+            match tx {
+                Transaction::Transfer(transfer) => {
+                    if transfer.new_owner == *address {
+                        pending.incoming = coord_checked_add(pending.incoming, transfer.amount)?;
+                    }
+                    if transfer.sender == *address {
+                        let spent = coord_checked_add(transfer.amount, transfer.sender_fee())?;
+                        pending.outgoing = coord_checked_add(pending.outgoing, spent)?;
+                    }
+                    if transfer.sponsor.as_ref().is_some_and(|sponsor| sponsor.payer == *address) {
+                        pending.outgoing = coord_checked_add(pending.outgoing, transfer.fee_area)?;
+                    }
+                }
+                Transaction::Subdivision(subdivision) if subdivision.owner_address == *address => {
+                    pending.outgoing = coord_checked_add(pending.outgoing, subdivision.fee_area)?;
+                }
+                _ => {}
+            }
+            for input in tx.inputs() {
+                if let Some(triangle) = state.utxo_set.get(&input).filter(|t| t.owner == *address) {
+                    pending.locked = coord_checked_add(pending.locked, triangle.checked_effective_value()?)?;
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    /// Drop transactions that are no longer valid against `state`. Package
+    /// members are judged through their root: if it is invalid, the whole
+    /// package goes.
+    pub fn prune(&mut self, state: &TriangleState, domain: &SigningDomain) {
         let mut invalid_hashes = Vec::new();
         for (hash, mempool_tx) in self.transactions.iter() {
-            if mempool_tx.tx.validate(state).is_err() {
+            if !self.parent.contains_key(hash) && mempool_tx.tx.validate(state, domain).is_err() {
                 invalid_hashes.push(*hash);
             }
         }
 
         for hash in invalid_hashes {
-            self.remove_transaction(&hash);
+            self.remove_with_descendants(&hash);
+        }
+    }
+
+    /// Pending transactions that cannot all be mined on top of `state`:
+    /// inputs spent twice within the mempool, and inputs that no longer
+    /// exist. Admission rules out both, so any entry here means the mempool
+    /// has drifted from the chain, e.g. after a reorganization.
+    pub fn conflicts(&self, state: &TriangleState) -> Vec<MempoolConflict> {
+        let mut spenders: HashMap<Sha256Hash, Vec<Sha256Hash>> = HashMap::new();
+        for (hash, mempool_tx) in &self.transactions {
+            for input in mempool_tx.tx.inputs() {
+                spenders.entry(input).or_default().push(*hash);
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        for (input, mut hashes) in spenders {
+            hashes.sort();
+            if !state.utxo_set.contains_key(&input) && !self.outputs.contains_key(&input) {
+                conflicts.extend(hashes.iter().map(|hash| MempoolConflict {
+                    hash: hex::encode(hash),
+                    input: hex::encode(input),
+                    kind: ConflictKind::MissingInput,
+                    other: None,
+                }));
+            }
+            for pair in hashes.windows(2) {
+                conflicts.push(MempoolConflict {
+                    hash: hex::encode(pair[1]),
+                    input: hex::encode(input),
+                    kind: ConflictKind::DoubleSpend,
+                    other: Some(hex::encode(pair[0])),
+                });
+            }
         }
+        conflicts.sort_by(|a, b| (&a.hash, a.kind).cmp(&(&b.hash, b.kind)));
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::MockClock;
+    use crate::crypto::KeyPair;
+    use crate::geometry::{Coord, Point, Triangle};
+    use crate::transaction::{sponsor_transfer, TransactionBuilder};
+
+    const CHAIN: Sha256Hash = [7; 32];
+
+    fn domain() -> SigningDomain {
+        SigningDomain::new(CHAIN)
+    }
+
+    fn transfer(seed: u8, fee: i32) -> Transaction {
+        Transaction::Transfer(TransferTx::new(
+            [seed; 32],
+            [2u8; 32],
+            [seed; 32],
+            Coord::from_num(10),
+            Coord::from_num(fee),
+            1,
+        ))
+    }
+
+    #[test]
+    fn test_transactions_ordered_by_fee_density() {
+        let mut mempool = Mempool::new();
+        let cheap = transfer(1, 1);
+        let rich = transfer(2, 5);
+        mempool.add_transaction(cheap.clone()).unwrap();
+        mempool.add_transaction(rich.clone()).unwrap();
+
+        let report = mempool.fee_density_report();
+        assert_eq!(report[0].hash, rich.hash_str());
+        assert!(report[0].fee_density > report[1].fee_density);
+        assert_eq!(mempool.get_transactions_by_fee(1)[0].hash(), rich.hash());
+    }
+
+    #[test]
+    fn test_conflicts_report_missing_inputs_and_double_spends() {
+        let mut mempool = Mempool::new();
+        let first = transfer(1, 1);
+        mempool.add_transaction(first.clone()).unwrap();
+        let conflicts = mempool.conflicts(&TriangleState::new());
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::MissingInput);
+        assert_eq!(conflicts[0].hash, first.hash_str());
+
+        let mut state = TriangleState::new();
+        let triangle = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(1), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(1)),
+            None,
+            [1u8; 32],
+        );
+        state.utxo_set.insert([1u8; 32], triangle);
+        assert!(mempool.conflicts(&state).is_empty());
+
+        // The spent index is not persisted, so a reloaded mempool can take a
+        // second spender of the same input
+        let mut reloaded: Mempool = bincode::deserialize(&bincode::serialize(&mempool).unwrap()).unwrap();
+        let second = transfer(1, 2);
+        reloaded.add_transaction(second).unwrap();
+        let conflicts = reloaded.conflicts(&state);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::DoubleSpend);
+        assert_eq!(conflicts[0].input, hex::encode([1u8; 32]));
+        assert!(conflicts[0].other.is_some());
+    }
+
+    #[test]
+    fn test_pending_balance() {
+        let mut mempool = Mempool::new();
+        mempool.add_transaction(transfer(1, 1)).unwrap();
+        let mut state = TriangleState::new();
+        let triangle = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(1), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(1)),
+            None,
+            [1u8; 32],
+        )
+        .with_effective_value(Coord::from_num(20));
+        state.utxo_set.insert([1u8; 32], triangle);
+
+        let sender = mempool.pending_balance(&[1u8; 32], &state).unwrap();
+        assert_eq!(
+            sender,
+            PendingBalance {
+                incoming: Coord::from_num(0),
+                outgoing: Coord::from_num(11),
+                locked: Coord::from_num(20),
+            }
+        );
+        let recipient = mempool.pending_balance(&[2u8; 32], &state).unwrap();
+        assert_eq!((recipient.incoming, recipient.locked), (Coord::from_num(10), Coord::from_num(0)));
+    }
+
+    #[test]
+    fn test_prioritized_transaction_goes_first_and_survives_eviction() {
+        let mut mempool = Mempool::new();
+        let cheap = transfer(1, 0);
+        mempool.add_transaction(cheap.clone()).unwrap();
+        mempool.add_transaction(transfer(2, 5)).unwrap();
+
+        mempool.prioritize(&cheap.hash()).unwrap();
+        assert_eq!(mempool.get_transactions_by_fee(1)[0].hash(), cheap.hash());
+        assert!(mempool.fee_density_report()[0].prioritized);
+
+        mempool.set_limits(MempoolLimits { max_transactions: 2, ..MempoolLimits::default() });
+        mempool.add_transaction(transfer(3, 9)).unwrap();
+        assert!(mempool.get_transaction(&cheap.hash()).is_some());
+        assert!(mempool.get_transaction(&transfer(2, 5).hash()).is_none());
+
+        assert!(mempool.deprioritize(&cheap.hash()));
+        assert!(!mempool.deprioritize(&cheap.hash()));
+        assert!(mempool.prioritize(&[9u8; 32]).is_err());
+
+        mempool.remove_transaction(&cheap.hash());
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_lowest_fee_per_byte_packages() {
+        let mut mempool = Mempool::new();
+        let size = transfer(1, 1).size();
+        mempool.set_limits(MempoolLimits { max_transactions: 100, max_bytes: 3 * size });
+        let low = transfer(1, 1);
+        let mid = transfer(2, 4);
+        let high = transfer(3, 6);
+        for tx in [&low, &mid, &high] {
+            mempool.add_transaction(tx.clone()).unwrap();
+        }
+        assert_eq!(mempool.usage().bytes, 3 * size);
+
+        // Paying no more per byte than anything pending is refused outright
+        assert!(matches!(mempool.add_transaction(transfer(4, 1)), Err(ChainError::MempoolFull)));
+        assert_eq!(mempool.len(), 3);
+
+        mempool.add_transaction(transfer(5, 5)).unwrap();
+        assert!(mempool.get_transaction(&low.hash()).is_none());
+        assert!(mempool.get_transaction(&mid.hash()).is_some());
+        let usage = mempool.usage();
+        assert_eq!((usage.transactions, usage.bytes, usage.evicted), (3, 3 * size, 1));
+
+        mempool.remove_transaction(&high.hash());
+        assert_eq!(mempool.usage().bytes, 2 * size);
+    }
+
+    fn signed_transfer(
+        keypair: &KeyPair,
+        input_hash: Sha256Hash,
+        new_owner: Address,
+        amount: f64,
+        fee: f64,
+    ) -> TransferTx {
+        let tx = TransferTx::new(
+            input_hash,
+            new_owner,
+            keypair.address(),
+            Coord::from_num(amount),
+            Coord::from_num(fee),
+            1,
+        );
+        sign_for(tx, keypair, &CHAIN)
+    }
+
+    fn sign_for(mut tx: TransferTx, keypair: &KeyPair, chain_id: &Sha256Hash) -> TransferTx {
+        let signature = keypair.sign(&tx.signable_message(chain_id)).unwrap();
+        tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        tx
+    }
+
+    /// A chain state where `owner` holds a single triangle worth 10
+    fn funded_state(owner: Address) -> (TriangleState, Sha256Hash) {
+        let triangle = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(4), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(5)),
+            None,
+            owner,
+        );
+        let hash = triangle.hash();
+        let mut state = TriangleState::new();
+        state.utxo_set.insert(hash, triangle);
+        (state, hash)
+    }
+
+    #[test]
+    fn test_package_child_accepted_and_ordered_after_parent() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+
+        let parent = signed_transfer(&alice, utxo, bob.address(), 1.0, 0.001);
+        let parent_tx = Transaction::Transfer(parent.clone());
+        // Bob spends the output he has not received on chain yet, paying a
+        // much higher fee than the parent.
+        let child = Transaction::Transfer(signed_transfer(
+            &bob,
+            parent_tx.hash(),
+            [7u8; 32],
+            0.5,
+            0.25,
+        ));
+        // Alice spends her unconfirmed change
+        let change_hash = parent.change_transaction(Coord::from_num(10) - Coord::from_num(1.001)).hash();
+        let change_spend = Transaction::Transfer(signed_transfer(
+            &alice,
+            change_hash,
+            [8u8; 32],
+            2.0,
+            0.01,
+        ));
+
+        let mut mempool = Mempool::new();
+        assert!(mempool.accept_transaction(child.clone(), &state, &domain()).is_err());
+        mempool.accept_transaction(parent_tx.clone(), &state, &domain()).unwrap();
+        mempool.accept_transaction(child.clone(), &state, &domain()).unwrap();
+        mempool.accept_transaction(change_spend.clone(), &state, &domain()).unwrap();
+
+        assert_eq!(mempool.ancestors(&child.hash()), vec![parent_tx.hash()]);
+        assert_eq!(mempool.descendants(&parent_tx.hash()).len(), 2);
+
+        let order: Vec<Sha256Hash> = mempool
+            .get_transactions_by_fee(10)
+            .iter()
+            .map(|tx| tx.hash())
+            .collect();
+        assert_eq!(order[0], parent_tx.hash());
+        assert_eq!(order.len(), 3);
+
+        // Confirming the parent keeps the children; dropping it removes them
+        let mut confirmed = mempool.clone();
+        confirmed.remove_transaction(&parent_tx.hash());
+        assert_eq!(confirmed.len(), 2);
+        assert!(confirmed.ancestors(&child.hash()).is_empty());
+        assert_eq!(mempool.remove_with_descendants(&parent_tx.hash()), 3);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_restores_on_another_node() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+        let mut chain = crate::blockchain::Blockchain::new([1u8; 32], 1).unwrap();
+        let chain_id = chain.chain_id();
+        let domain = SigningDomain::new(chain_id);
+        let transfer = |keypair: &KeyPair, input, owner, amount, fee| {
+            Transaction::Transfer(sign_for(signed_transfer(keypair, input, owner, amount, fee), keypair, &chain_id))
+        };
+        let parent = transfer(&alice, utxo, bob.address(), 1.0, 0.001);
+        let child = transfer(&bob, parent.hash(), [7u8; 32], 0.5, 0.25);
+        let mut mempool = Mempool::new();
+        mempool.accept_transaction(parent.clone(), &state, &domain).unwrap();
+        mempool.accept_transaction(child.clone(), &state, &domain).unwrap();
+        mempool.prioritize(&child.hash()).unwrap();
+
+        let mut snapshot = mempool.snapshot(7, &[3u8; 32]).unwrap();
+        assert_eq!(snapshot.tip_height, 7);
+        let txids: Vec<&str> = snapshot.entries.iter().map(|e| e.txid.as_str()).collect();
+        assert_eq!(txids, vec![parent.hash_str(), child.hash_str()]);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(serde_json::from_str::<MempoolSnapshot>(&json).unwrap(), snapshot);
+
+        // A double spend paying too little to replace the package is
+        // rejected without stopping the rest
+        let double_spend = transfer(&alice, utxo, [8u8; 32], 2.0, 0.1);
+        snapshot.entries.push(SnapshotEntry {
+            txid: double_spend.hash_str(),
+            received_at: 0,
+            prioritized: false,
+            raw: hex::encode(bincode::serialize(&double_spend).unwrap()),
+        });
+        chain.state = state;
+        let report = chain.restore_mempool(&snapshot).unwrap();
+        assert_eq!(report.restored, vec![parent.hash_str(), child.hash_str()]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].txid, double_spend.hash_str());
+
+        // Receive times and pins carry over
+        let entry = |mempool: &Mempool| {
+            let report = mempool.fee_density_report();
+            report.into_iter().find(|info| info.hash == child.hash_str()).unwrap()
+        };
+        assert!(entry(&chain.mempool).prioritized);
+        assert_eq!(entry(&chain.mempool).received_at, entry(&mempool).received_at);
+
+        let again = chain.restore_mempool(&snapshot).unwrap();
+        assert!(again.restored.is_empty());
+        assert_eq!(again.already_pending, 2);
+
+        snapshot.entries[0].txid = child.hash_str();
+        assert!(snapshot.entries[0].transaction().is_err());
+        snapshot.version = MEMPOOL_SNAPSHOT_VERSION + 1;
+        assert!(chain.restore_mempool(&snapshot).is_err());
+    }
+
+    #[test]
+    fn test_transactions_expire_with_their_descendants() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+        let clock = MockClock::new(1_000_000);
+        let mut mempool = Mempool::new();
+        mempool.set_clock(Arc::new(clock.clone()));
+        let parent = Transaction::Transfer(signed_transfer(&alice, utxo, bob.address(), 1.0, 0.001));
+        mempool.accept_transaction(parent.clone(), &state, &domain()).unwrap();
+
+        clock.advance(Duration::from_secs(60));
+        let child = Transaction::Transfer(signed_transfer(&bob, parent.hash(), [7u8; 32], 0.5, 0.25));
+        mempool.accept_transaction(child, &state, &domain()).unwrap();
+        assert_eq!(mempool.snapshot(0, &[0; 32]).unwrap().created_at, 1_060_000);
+
+        clock.advance(MEMPOOL_EXPIRY - Duration::from_secs(61));
+        assert_eq!(mempool.expire(), 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(mempool.expire(), 2);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_package_ancestor_limit() {
+        let alice = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+        let mut mempool = Mempool::new();
+
+        let mut input = utxo;
+        let mut value = Coord::from_num(10);
+        for i in 0..=MAX_ANCESTORS {
+            let tx = signed_transfer(&alice, input, [9u8; 32], 0.1, 0.01);
+            value -= Coord::from_num(0.1) + Coord::from_num(0.01);
+            let result = mempool.accept_transaction(Transaction::Transfer(tx.clone()), &state, &domain());
+            if i < MAX_ANCESTORS {
+                result.unwrap();
+            } else {
+                assert!(result.is_err());
+            }
+            input = tx.change_transaction(value).hash();
+        }
+        assert_eq!(mempool.len(), MAX_ANCESTORS);
+    }
+
+    #[test]
+    fn test_projected_state_rejects_and_evicts_conflicts() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+        let mut mempool = Mempool::new();
+
+        let to_bob = Transaction::Transfer(signed_transfer(&alice, utxo, bob.address(), 1.0, 0.01));
+        let bob_spends = Transaction::Transfer(signed_transfer(&bob, to_bob.hash(), [7u8; 32], 0.5, 0.01));
+        mempool.accept_transaction(to_bob.clone(), &state, &domain()).unwrap();
+        mempool.accept_transaction(bob_spends.clone(), &state, &domain()).unwrap();
+
+        // Alice cannot spend the same triangle again, nor Bob his output
+        let double_spend = Transaction::Transfer(signed_transfer(&alice, utxo, [8u8; 32], 2.0, 0.5));
+        let result = mempool.accept_transaction(double_spend.clone(), &state, &domain());
+        assert!(matches!(result, Err(ChainError::AlreadySpent { utxo: spent, .. }) if spent == utxo));
+        let bob_again = Transaction::Transfer(signed_transfer(&bob, to_bob.hash(), [9u8; 32], 0.4, 0.01));
+        assert!(mempool.accept_transaction(bob_again, &state, &domain()).is_err());
+
+        // A block confirming the double spend evicts the whole conflicting package
+        assert_eq!(mempool.confirm_block(&[double_spend]), 2);
+        assert!(mempool.is_empty());
+        assert!(mempool.spent.is_empty() && mempool.created.is_empty());
+    }
+
+    #[test]
+    fn test_fee_bump_replaces_transaction_and_descendants() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+        let mut mempool = Mempool::new();
+
+        let stuck = Transaction::Transfer(signed_transfer(&alice, utxo, bob.address(), 1.0, 0.0));
+        let bob_spends = Transaction::Transfer(signed_transfer(&bob, stuck.hash(), [7u8; 32], 0.5, 0.01));
+        mempool.accept_transaction(stuck.clone(), &state, &domain()).unwrap();
+        mempool.accept_transaction(bob_spends.clone(), &state, &domain()).unwrap();
+        let required = mempool.min_replacement_fee(&stuck.hash()).unwrap();
+        assert_eq!(required, Coord::from_num(0.01) + MIN_REPLACEMENT_FEE_INCREMENT);
+
+        // Paying more than the stuck transfer but not for Bob's spend too
+        // is not enough
+        let short = mempool.fee_bump(&stuck.hash(), Coord::from_num(0.005)).unwrap();
+        let short = Transaction::Transfer(sign_for(short, &alice, &CHAIN));
+        let err = mempool.replace_transaction(short, &state, &domain()).unwrap_err();
+        assert!(err.to_string().contains("at least"));
+        assert_eq!(mempool.len(), 2);
+
+        let bumped = mempool.fee_bump(&stuck.hash(), required).unwrap();
+        assert!(bumped.signature.is_none());
+        let bumped = Transaction::Transfer(sign_for(bumped, &alice, &CHAIN));
+        let evicted = mempool.replace_transaction(bumped.clone(), &state, &domain()).unwrap();
+        assert_eq!(evicted, vec![stuck.hash(), bob_spends.hash()]);
+        assert_eq!(mempool.len(), 1);
+        assert!(mempool.get_transaction(&bumped.hash()).is_some());
+
+        // Lowering the fee again is refused, as is bumping a sponsored fee
+        assert!(mempool.fee_bump(&bumped.hash(), Coord::from_num(0.001)).is_err());
+        let lower = Transaction::Transfer(signed_transfer(&alice, utxo, bob.address(), 1.0, 0.001));
+        assert!(mempool.replace_transaction(lower, &state, &domain()).is_err());
+        assert!(mempool.fee_bump(&[9u8; 32], Coord::from_num(1)).is_err());
+    }
+
+    #[test]
+    fn test_sponsored_transfer_reserves_both_inputs() {
+        let alice = KeyPair::generate().unwrap();
+        let sponsor = KeyPair::generate().unwrap();
+        let (mut state, utxo) = funded_state(alice.address());
+        let (sponsor_state, sponsor_utxo) = funded_state(sponsor.address());
+        state.utxo_set.extend(sponsor_state.utxo_set);
+        let sponsored = |input: Sha256Hash, sponsor_input: Sha256Hash| {
+            let tx = TransactionBuilder::new()
+                .chain(CHAIN)
+                .input(input)
+                .to([7u8; 32])
+                .amount(Coord::from_num(1))
+                .fee(Coord::from_num(0.5))
+                .sponsor(sponsor_input, sponsor.address())
+                .sign(&alice)
+                .unwrap();
+            sponsor_transfer(tx, &sponsor, &CHAIN).unwrap()
+        };
+
+        let mut mempool = Mempool::new();
+        let tx = sponsored(utxo, sponsor_utxo);
+        mempool.accept_transaction(tx.clone(), &state, &domain()).unwrap();
+
+        // The sponsor's triangle is spent like any input
+        let sponsor_spends = Transaction::Transfer(signed_transfer(&sponsor, sponsor_utxo, [8u8; 32], 1.0, 0.01));
+        let result = mempool.accept_transaction(sponsor_spends.clone(), &state, &domain());
+        assert!(matches!(result, Err(ChainError::AlreadySpent { spender, .. }) if spender == tx.hash()));
+
+        // Sponsors pay from confirmed triangles only
+        let Transaction::Transfer(transfer) = &tx else { unreachable!() };
+        let alice_change = transfer.change_transaction(Coord::from_num(9)).hash();
+        let sponsor_change = transfer.sponsor_change_transaction(Coord::from_num(9.5)).unwrap().hash();
+        assert!(mempool.created.contains_key(&alice_change) && mempool.created.contains_key(&sponsor_change));
+        let result = mempool.accept_transaction(sponsored(alice_change, sponsor_change), &state, &domain());
+        assert!(matches!(result, Err(ChainError::InvalidTransaction(msg)) if msg.contains("confirmed")));
+
+        // A block spending the sponsor's triangle elsewhere evicts it
+        assert_eq!(mempool.confirm_block(&[sponsor_spends]), 1);
+        assert!(mempool.is_empty());
     }
 }
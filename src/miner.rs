@@ -1,10 +1,82 @@
 //! Proof-of-Work (PoW) implementation for TrinityChain.
 
-use crate::blockchain::{Block, Sha256Hash};
+use crate::blockchain::{Block, PowAlgorithm, Sha256Hash, DEFAULT_POW};
+use crate::config::MinerConfig;
+use crate::crypto::{address_from_hex, Address};
 use crate::error::ChainError;
+use crate::hdwallet::HDWallet;
+use crate::mempool::Mempool;
+use crate::transaction::{CoinbaseTx, Transaction};
+use chrono::Timelike;
 use rayon::prelude::*;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Nonces tried between checks whether the block template is stale
+pub const NONCES_PER_ROUND: u64 = 50_000;
+
+/// How often a paused miner checks whether it may resume
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a power source reading is reused before asking the OS again
+const BATTERY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the embedded miner pays its block rewards.
+pub enum BeneficiarySource {
+    /// Every block pays the same address
+    Fixed(Address),
+    /// Each block pays a fresh HD address whose child index is the block height
+    Rotating { seed: Vec<u8> },
+}
+
+impl BeneficiarySource {
+    /// Resolve the beneficiary from the `[miner]` config section. Fails on a
+    /// malformed or all-zero address so rewards are never burned.
+    pub fn from_config(config: &MinerConfig) -> Result<Self, ChainError> {
+        if config.rotate_addresses {
+            let path = config.hd_mnemonic_file.as_deref().ok_or_else(|| {
+                ChainError::WalletError(
+                    "miner.rotate_addresses requires miner.hd_mnemonic_file".to_string(),
+                )
+            })?;
+            let phrase = std::fs::read_to_string(path)?;
+            let seed = HDWallet::seed_from_mnemonic(phrase.trim(), None)
+                .map_err(ChainError::WalletError)?;
+            return Ok(BeneficiarySource::Rotating { seed });
+        }
+
+        let address = address_from_hex(config.beneficiary_address.trim()).map_err(|e| {
+            ChainError::WalletError(format!("Invalid miner.beneficiary_address: {}", e))
+        })?;
+        if address == [0u8; 32] {
+            return Err(ChainError::WalletError(
+                "miner.beneficiary_address is the zero address; rewards would be burned".to_string(),
+            ));
+        }
+        Ok(BeneficiarySource::Fixed(address))
+    }
+
+    /// Address that receives the coinbase of the block at `height`.
+    pub fn address_for_height(&self, height: u64) -> Result<Address, ChainError> {
+        match self {
+            BeneficiarySource::Fixed(address) => Ok(*address),
+            BeneficiarySource::Rotating { seed } => {
+                let index = u32::try_from(height).map_err(|_| {
+                    ChainError::WalletError(format!("No HD index for height {}", height))
+                })?;
+                let keypair = HDWallet::derive_keypair(seed, index).map_err(ChainError::WalletError)?;
+                Ok(keypair.address())
+            }
+        }
+    }
+}
 
 /// Checks if a hash meets the required difficulty target.
 /// The difficulty is the required number of leading zeros in the hash.
@@ -42,20 +114,42 @@ pub fn is_hash_valid(hash: &Sha256Hash, difficulty: u64) -> bool {
 }
 
 /// Mines a new block by searching for a nonce that satisfies the current difficulty.
-pub fn mine_block(mut block: Block) -> Result<Block, ChainError> {
-    let difficulty = block.header.difficulty;
-    let mut nonce: u64 = 0;
+pub fn mine_block(block: Block) -> Result<Block, ChainError> {
+    mine_block_with(block, DEFAULT_POW)
+}
 
+/// [`mine_block`] for a network using `pow`
+pub fn mine_block_with(mut block: Block, pow: &dyn PowAlgorithm) -> Result<Block, ChainError> {
+    block.header.nonce = 0;
     loop {
-        block.header.nonce = nonce;
-        let hash = block.hash();
-
-        if is_hash_valid(&hash, difficulty as u64) {
+        if mine_nonce_range(&mut block, u64::MAX, pow)?.0 {
             return Ok(block);
         }
+    }
+}
 
-        nonce = nonce.checked_add(1).ok_or(ChainError::InvalidProofOfWork)?;
+/// Change the coinbase extra nonce, treated as a little-endian counter, and
+/// recompute the merkle root. Gives the header a fresh nonce space.
+pub fn roll_extra_nonce(block: &mut Block) -> Result<(), ChainError> {
+    let Some(Transaction::Coinbase(coinbase)) = block.transactions.first_mut() else {
+        return Err(ChainError::InvalidBlock(
+            "Cannot roll the extra nonce of a block without a coinbase".to_string(),
+        ));
+    };
+    let extra_nonce = &mut coinbase.extra_nonce;
+    match extra_nonce.iter().position(|byte| *byte != u8::MAX) {
+        Some(index) => {
+            extra_nonce[..index].fill(0);
+            extra_nonce[index] += 1;
+        }
+        None if extra_nonce.len() < CoinbaseTx::MAX_EXTRA_NONCE_LEN => {
+            extra_nonce.fill(0);
+            extra_nonce.push(1);
+        }
+        None => return Err(ChainError::InvalidProofOfWork),
     }
+    block.header.merkle_root = Block::calculate_merkle_root(block.header.height, &block.transactions);
+    Ok(())
 }
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
@@ -100,3 +194,618 @@ pub fn mine_block_parallel(block: Block) -> Result<Block, ChainError> {
         Err(_) => Err(ChainError::InvalidProofOfWork),
     }
 }
+
+/// When a block template being mined is rebuilt, so transactions that
+/// arrived meanwhile make it into the block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateRefresh {
+    /// Rebuild a template this old; `None` never rebuilds for age
+    pub max_age: Option<Duration>,
+    /// Rebuild once this many more transactions are pending than the
+    /// template holds; `None` ignores the mempool
+    pub mempool_growth: Option<usize>,
+}
+
+impl TemplateRefresh {
+    pub fn from_config(config: &MinerConfig) -> Self {
+        Self {
+            max_age: Some(Duration::from_secs(config.template_refresh_secs)).filter(|age| !age.is_zero()),
+            mempool_growth: Some(config.template_refresh_txs).filter(|txs| *txs > 0),
+        }
+    }
+
+    /// Whether a template `age` old, holding `template_txs` transactions
+    /// besides the coinbase, should be rebuilt while `pending` are waiting
+    pub fn is_due(&self, age: Duration, template_txs: usize, pending: usize) -> bool {
+        self.max_age.is_some_and(|max_age| age >= max_age)
+            || self
+                .mempool_growth
+                .is_some_and(|growth| pending.saturating_sub(template_txs) >= growth)
+    }
+}
+
+impl Default for TemplateRefresh {
+    fn default() -> Self {
+        Self {
+            max_age: Some(Duration::from_secs(30)),
+            mempool_growth: Some(10),
+        }
+    }
+}
+
+/// Hashing statistics of a miner, kept across template rebuilds and blocks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HashRateStats {
+    pub hashes: u64,
+    pub elapsed: Duration,
+    /// Templates replaced before they were solved
+    pub refreshes: u64,
+}
+
+impl HashRateStats {
+    pub fn record(&mut self, hashes: u64, elapsed: Duration) {
+        self.hashes = self.hashes.saturating_add(hashes);
+        self.elapsed += elapsed;
+    }
+
+    /// Average hashes per second
+    pub fn hash_rate(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.hashes as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Senders whose transactions go into every block this node mines ahead of
+/// fee order: `miner.priority_addresses`, and the wallets in the data
+/// directory when `miner.prioritize_wallets` is set. Counts how often that
+/// changes a template.
+#[derive(Debug, Default)]
+pub struct LocalPriority {
+    addresses: HashSet<Address>,
+    /// Read at every template, so wallets created while the node runs count
+    wallet_dir: Option<PathBuf>,
+    templates: AtomicU64,
+    included: AtomicU64,
+    promoted: AtomicU64,
+}
+
+/// How often [`LocalPriority`] shaped block templates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LocalPriorityStats {
+    /// Templates holding at least one prioritized transaction
+    pub templates: u64,
+    /// Prioritized transactions put in templates
+    pub included: u64,
+    /// Of those, the ones fee order alone would have left out
+    pub promoted: u64,
+}
+
+impl LocalPriority {
+    pub fn from_config(config: &MinerConfig, wallet_dir: &Path) -> Result<Self, ChainError> {
+        let addresses = config
+            .priority_addresses
+            .iter()
+            .map(|hex| {
+                address_from_hex(hex.trim()).map_err(|e| {
+                    ChainError::WalletError(format!("Invalid miner.priority_addresses entry '{}': {}", hex, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            addresses,
+            wallet_dir: config.prioritize_wallets.then(|| wallet_dir.to_path_buf()),
+            ..Self::default()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.addresses.is_empty() || self.wallet_dir.is_some()
+    }
+
+    /// Senders given priority right now
+    pub fn senders(&self) -> HashSet<Address> {
+        let mut senders = self.addresses.clone();
+        if let Some(dir) = &self.wallet_dir {
+            senders.extend(crate::notify::wallet_addresses(dir));
+        }
+        senders
+    }
+
+    /// The transactions [`select`](Self::select) would return, without
+    /// counting them as a template
+    pub fn preview(&self, mempool: &Mempool, limit: usize) -> Vec<Transaction> {
+        if !self.is_enabled() {
+            return mempool.get_transactions_by_fee(limit);
+        }
+        mempool.get_transactions_favoring(limit, &self.senders())
+    }
+
+    /// Up to `limit` transactions for a block template in assembly order,
+    /// prioritized senders' first
+    pub fn select(&self, mempool: &Mempool, limit: usize) -> Vec<Transaction> {
+        if !self.is_enabled() {
+            return mempool.get_transactions_by_fee(limit);
+        }
+        let senders = self.senders();
+        let local = mempool.sent_by(&senders);
+        let selected = mempool.get_transactions_favoring(limit, &senders);
+        let included: Vec<_> = selected.iter().map(Transaction::hash).filter(|hash| local.contains(hash)).collect();
+        if !included.is_empty() {
+            let by_fee: HashSet<_> = mempool.get_transactions_by_fee(limit).iter().map(Transaction::hash).collect();
+            let promoted = included.iter().filter(|hash| !by_fee.contains(*hash)).count();
+            self.templates.fetch_add(1, Ordering::Relaxed);
+            self.included.fetch_add(included.len() as u64, Ordering::Relaxed);
+            self.promoted.fetch_add(promoted as u64, Ordering::Relaxed);
+        }
+        selected
+    }
+
+    pub fn stats(&self) -> LocalPriorityStats {
+        LocalPriorityStats {
+            templates: self.templates.load(Ordering::Relaxed),
+            included: self.included.load(Ordering::Relaxed),
+            promoted: self.promoted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Local hours during which the miner pauses, written `HH-HH`: from the
+/// start of `start` to the start of `end`, wrapping past midnight when
+/// `start > end`, so `22-07` pauses overnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PauseHours {
+    pub start: u8,
+    pub end: u8,
+}
+
+impl PauseHours {
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+impl FromStr for PauseHours {
+    type Err = ChainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ChainError::WalletError(format!("Invalid miner.pause_hours '{}', expected HH-HH", s));
+        let (start, end) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let hour = |h: &str| h.trim().parse::<u8>().ok().filter(|h| *h < 24).ok_or_else(invalid);
+        Ok(PauseHours {
+            start: hour(start)?,
+            end: hour(end)?,
+        })
+    }
+}
+
+impl TryFrom<String> for PauseHours {
+    type Error = ChainError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PauseHours> for String {
+    fn from(hours: PauseHours) -> Self {
+        hours.to_string()
+    }
+}
+
+impl fmt::Display for PauseHours {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.start, self.end)
+    }
+}
+
+/// Why a throttled miner is not hashing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseReason {
+    Schedule,
+    Battery,
+}
+
+/// How much CPU the miner may use, and when it stops altogether
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThrottleSettings {
+    /// Share of the time spent hashing, 1-100
+    pub cpu_percent: u8,
+    pub pause_hours: Option<PauseHours>,
+    /// Pause while the machine runs on battery, where that can be detected
+    pub pause_on_battery: bool,
+}
+
+impl Default for ThrottleSettings {
+    fn default() -> Self {
+        Self {
+            cpu_percent: 100,
+            pause_hours: None,
+            pause_on_battery: false,
+        }
+    }
+}
+
+impl ThrottleSettings {
+    pub fn from_config(config: &MinerConfig) -> Result<Self, ChainError> {
+        let settings = Self {
+            cpu_percent: config.cpu_percent,
+            pause_hours: config.pause_hours.as_deref().map(str::parse).transpose()?,
+            pause_on_battery: config.pause_on_battery,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), ChainError> {
+        if !(1..=100).contains(&self.cpu_percent) {
+            return Err(ChainError::WalletError(format!(
+                "miner.cpu_percent must be between 1 and 100, got {}",
+                self.cpu_percent
+            )));
+        }
+        Ok(())
+    }
+
+    /// Time to rest after hashing for `busy` to stay at `cpu_percent`
+    pub fn idle_time(&self, busy: Duration) -> Duration {
+        let percent = u32::from(self.cpu_percent.clamp(1, 100));
+        busy * (100 - percent) / percent
+    }
+
+    /// Whether to pause at local `hour`, given whether the machine is on
+    /// battery (`None` when unknown, which never pauses)
+    pub fn pause_reason(&self, hour: u8, on_battery: Option<bool>) -> Option<PauseReason> {
+        if self.pause_hours.is_some_and(|hours| hours.contains(hour)) {
+            Some(PauseReason::Schedule)
+        } else if self.pause_on_battery && on_battery == Some(true) {
+            Some(PauseReason::Battery)
+        } else {
+            None
+        }
+    }
+}
+
+/// Throttle settings shared between a running miner and whatever adjusts
+/// them at runtime, such as `POST /api/mining/throttle`
+#[derive(Debug, Default)]
+pub struct MinerThrottle {
+    settings: Mutex<ThrottleSettings>,
+    /// Last power source reading and when it was taken
+    battery: Mutex<Option<(Instant, Option<bool>)>>,
+}
+
+impl MinerThrottle {
+    pub fn new(settings: ThrottleSettings) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            battery: Mutex::new(None),
+        }
+    }
+
+    pub fn settings(&self) -> ThrottleSettings {
+        self.settings.lock().map(|settings| settings.clone()).unwrap_or_default()
+    }
+
+    /// Replace the settings; a miner picks them up after its current round
+    pub fn set(&self, settings: ThrottleSettings) -> Result<(), ChainError> {
+        settings.validate()?;
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+        Ok(())
+    }
+
+    /// Why the miner should not hash right now, if it should not
+    pub fn current_pause(&self) -> Option<PauseReason> {
+        let settings = self.settings();
+        let on_battery = if settings.pause_on_battery { self.on_battery() } else { None };
+        settings.pause_reason(chrono::Local::now().hour() as u8, on_battery)
+    }
+
+    fn on_battery(&self) -> Option<bool> {
+        let Ok(mut cached) = self.battery.lock() else {
+            return on_battery();
+        };
+        match *cached {
+            Some((read_at, reading)) if read_at.elapsed() < BATTERY_POLL_INTERVAL => reading,
+            _ => {
+                let reading = on_battery();
+                *cached = Some((Instant::now(), reading));
+                reading
+            }
+        }
+    }
+}
+
+/// Whether the machine is running on battery: a battery is discharging and
+/// no mains adapter is online. `None` where this cannot be detected.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> Option<bool> {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).map(|s| s.trim().to_string()).ok();
+    let mut battery_discharging = None;
+    for supply in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = supply.path();
+        match read(path.join("type")).as_deref() {
+            Some("Mains") | Some("USB") if read(path.join("online")).as_deref() == Some("1") => return Some(false),
+            Some("Battery") => {
+                let discharging = read(path.join("status")).as_deref() == Some("Discharging");
+                battery_discharging = Some(battery_discharging.unwrap_or(false) || discharging);
+            }
+            _ => {}
+        }
+    }
+    battery_discharging
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn on_battery() -> Option<bool> {
+    None
+}
+
+/// Try `count` nonces starting at the header's current nonce. Returns
+/// whether the block was solved and how many hashes it took. A solved block
+/// carries the winning nonce, otherwise the header is left at the next nonce
+/// to try. When the header nonce wraps, the coinbase extra nonce is rolled.
+pub fn mine_nonce_range(block: &mut Block, count: u64, pow: &dyn PowAlgorithm) -> Result<(bool, u64), ChainError> {
+    let difficulty = block.header.difficulty as u64;
+    for attempt in 0..count {
+        if is_hash_valid(&pow.hash(&block.header), difficulty) {
+            return Ok((true, attempt + 1));
+        }
+        match block.header.nonce.checked_add(1) {
+            Some(nonce) => block.header.nonce = nonce,
+            None => {
+                roll_extra_nonce(block)?;
+                block.header.nonce = 0;
+            }
+        }
+    }
+    Ok((false, count))
+}
+
+/// Mine `template` in rounds of [`NONCES_PER_ROUND`] nonces. Between rounds
+/// `rebuild` is given the current template and its age; a template it
+/// returns replaces the current one and mining starts over on it. Hashes
+/// are counted in `stats` throughout, so rebuilding never loses them.
+/// `throttle` is consulted before every round: the miner rests after each
+/// round to keep to its CPU share, and waits while it is paused. Hashes are
+/// those of `pow`, the network's proof-of-work algorithm.
+pub async fn mine_with_refresh<F, Fut>(
+    template: Block,
+    pow: &dyn PowAlgorithm,
+    stats: &Mutex<HashRateStats>,
+    throttle: &MinerThrottle,
+    mut rebuild: F,
+) -> Result<Block, ChainError>
+where
+    F: FnMut(&Block, Duration) -> Fut,
+    Fut: Future<Output = Option<Block>>,
+{
+    let mut block = template;
+    let mut built = Instant::now();
+    loop {
+        while throttle.current_pause().is_some() {
+            tokio::time::sleep(PAUSE_CHECK_INTERVAL).await;
+        }
+
+        let round = Instant::now();
+        let (solved, hashes) = mine_nonce_range(&mut block, NONCES_PER_ROUND, pow)?;
+        let busy = round.elapsed();
+        if let Ok(mut stats) = stats.lock() {
+            stats.record(hashes, busy);
+        }
+        if solved {
+            return Ok(block);
+        }
+        let idle = throttle.settings().idle_time(busy);
+        if idle.is_zero() {
+            tokio::task::yield_now().await;
+        } else {
+            tokio::time::sleep(idle).await;
+        }
+
+        if let Some(fresh) = rebuild(&block, built.elapsed()).await {
+            block = fresh;
+            built = Instant::now();
+            if let Ok(mut stats) = stats.lock() {
+                stats.refreshes += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn miner_config(beneficiary: &str) -> MinerConfig {
+        MinerConfig {
+            threads: 1,
+            beneficiary_address: beneficiary.to_string(),
+            enabled: true,
+            rotate_addresses: false,
+            hd_mnemonic_file: None,
+            template_refresh_secs: 30,
+            template_refresh_txs: 10,
+            cpu_percent: 100,
+            pause_hours: None,
+            pause_on_battery: false,
+            prioritize_wallets: false,
+            priority_addresses: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_template_refresh_policy() {
+        let refresh = TemplateRefresh::from_config(&miner_config(""));
+        assert!(!refresh.is_due(Duration::from_secs(5), 2, 11));
+        assert!(refresh.is_due(Duration::from_secs(5), 2, 12));
+        assert!(refresh.is_due(Duration::from_secs(30), 0, 0));
+
+        let mut config = miner_config("");
+        config.template_refresh_secs = 0;
+        config.template_refresh_txs = 0;
+        let never = TemplateRefresh::from_config(&config);
+        assert!(!never.is_due(Duration::from_secs(3600), 0, 1000));
+    }
+
+    #[test]
+    fn test_exhausted_header_nonce_rolls_extra_nonce() {
+        use crate::geometry::Coord;
+
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [7u8; 32],
+            nonce: 0,
+            height: 1,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let mut block = Block::new(1, [0u8; 32], 64, vec![coinbase]);
+        let merkle_root = block.header.merkle_root;
+        block.header.nonce = u64::MAX - 1;
+
+        let (solved, hashes) = mine_nonce_range(&mut block, 3, DEFAULT_POW).unwrap();
+        assert!(!solved);
+        assert_eq!(hashes, 3);
+        assert_eq!(block.header.nonce, 1);
+        assert_ne!(block.header.merkle_root, merkle_root);
+        assert_eq!(block.header.merkle_root, Block::calculate_merkle_root(block.header.height, &block.transactions));
+        let Transaction::Coinbase(coinbase) = &block.transactions[0] else { unreachable!() };
+        assert_eq!(coinbase.extra_nonce, vec![1]);
+
+        // The extra nonce counts up with carries and grows when it overflows
+        let mut rolled = |extra_nonce: Vec<u8>| {
+            if let Transaction::Coinbase(coinbase) = &mut block.transactions[0] {
+                coinbase.extra_nonce = extra_nonce;
+            }
+            roll_extra_nonce(&mut block).unwrap();
+            match &block.transactions[0] {
+                Transaction::Coinbase(coinbase) => coinbase.extra_nonce.clone(),
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(rolled(vec![0xff, 0x05]), vec![0x00, 0x06]);
+        assert_eq!(rolled(vec![0xff, 0xff]), vec![0x00, 0x00, 0x01]);
+
+        let mut no_coinbase = Block::new(1, [0u8; 32], 64, vec![]);
+        assert!(roll_extra_nonce(&mut no_coinbase).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_refreshed_template_keeps_hash_stats() {
+        // Unsolvable within a round, so the first template is always replaced
+        let hard = Block::new(1, [0u8; 32], 64, vec![]);
+        let easy = Block::new(1, [0u8; 32], 0, vec![]);
+        let stats = Mutex::new(HashRateStats::default());
+        let mined = mine_with_refresh(hard, DEFAULT_POW, &stats, &MinerThrottle::default(), |template, _| {
+            let fresh = (template.header.difficulty == 64).then(|| easy.clone());
+            async move { fresh }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(mined.header.difficulty, 0);
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.refreshes, 1);
+        assert_eq!(stats.hashes, NONCES_PER_ROUND + 1);
+    }
+
+    #[test]
+    fn test_throttle_settings() {
+        let mut config = miner_config("");
+        config.cpu_percent = 25;
+        config.pause_hours = Some("22-07".to_string());
+        config.pause_on_battery = true;
+        let settings = ThrottleSettings::from_config(&config).unwrap();
+        assert_eq!(settings.idle_time(Duration::from_millis(100)), Duration::from_millis(300));
+        assert_eq!(ThrottleSettings::default().idle_time(Duration::from_millis(100)), Duration::ZERO);
+
+        // The window wraps past midnight; an unknown power source never pauses
+        assert_eq!(settings.pause_reason(23, None), Some(PauseReason::Schedule));
+        assert_eq!(settings.pause_reason(6, Some(false)), Some(PauseReason::Schedule));
+        assert_eq!(settings.pause_reason(7, None), None);
+        assert_eq!(settings.pause_reason(12, Some(true)), Some(PauseReason::Battery));
+        assert!("09-17".parse::<PauseHours>().unwrap().contains(9));
+        assert!(!"09-17".parse::<PauseHours>().unwrap().contains(17));
+
+        config.pause_hours = Some("9-25".to_string());
+        assert!(ThrottleSettings::from_config(&config).is_err());
+        config.pause_hours = None;
+        config.cpu_percent = 0;
+        assert!(ThrottleSettings::from_config(&config).is_err());
+
+        let throttle = MinerThrottle::default();
+        assert!(throttle.set(ThrottleSettings { cpu_percent: 101, ..settings.clone() }).is_err());
+        throttle.set(settings.clone()).unwrap();
+        assert_eq!(throttle.settings(), settings);
+    }
+
+    #[test]
+    fn test_beneficiary_rejects_zero_and_malformed_addresses() {
+        assert!(BeneficiarySource::from_config(&miner_config(&"00".repeat(32))).is_err());
+        assert!(BeneficiarySource::from_config(&miner_config("00")).is_err());
+
+        let source = BeneficiarySource::from_config(&miner_config(&"ab".repeat(32))).unwrap();
+        assert_eq!(source.address_for_height(7).unwrap(), [0xab; 32]);
+    }
+
+    #[test]
+    fn test_local_priority_includes_wallet_transactions_regardless_of_fee() {
+        use crate::transaction::TransferTx;
+        use crate::geometry::Coord;
+
+        let transfer = |sender: u8, fee: i32| {
+            Transaction::Transfer(TransferTx::new([sender; 32], [9u8; 32], [sender; 32], Coord::from_num(1), Coord::from_num(fee), 1))
+        };
+        let mut mempool = Mempool::new();
+        let (local, rich, richer) = (transfer(1, 0), transfer(2, 5), transfer(3, 6));
+        for tx in [&local, &rich, &richer] {
+            mempool.add_transaction(tx.clone()).unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let off = LocalPriority::from_config(&miner_config(""), dir.path()).unwrap();
+        assert!(!off.is_enabled());
+        assert_eq!(off.select(&mempool, 2)[0].hash(), richer.hash());
+
+        let mut config = miner_config("");
+        config.priority_addresses = vec!["zz".to_string()];
+        assert!(LocalPriority::from_config(&config, dir.path()).is_err());
+
+        // The wallet is found in the data directory
+        config.priority_addresses.clear();
+        config.prioritize_wallets = true;
+        std::fs::write(dir.path().join("wallet.json"), format!(r#"{{"address":"{}"}}"#, "01".repeat(32))).unwrap();
+        let priority = LocalPriority::from_config(&config, dir.path()).unwrap();
+        let selected: Vec<_> = priority.select(&mempool, 2).iter().map(Transaction::hash).collect();
+        assert_eq!(selected, vec![local.hash(), richer.hash()]);
+        priority.select(&mempool, 3);
+        assert_eq!(priority.stats(), LocalPriorityStats { templates: 2, included: 2, promoted: 1 });
+    }
+
+    #[test]
+    fn test_rotating_beneficiary_changes_per_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("miner.mnemonic");
+        std::fs::write(&path, HDWallet::generate_mnemonic(12).unwrap()).unwrap();
+
+        let mut config = miner_config("");
+        config.rotate_addresses = true;
+        config.hd_mnemonic_file = Some(path.to_string_lossy().into_owned());
+
+        let source = BeneficiarySource::from_config(&config).unwrap();
+        let first = source.address_for_height(1).unwrap();
+        assert_ne!(first, source.address_for_height(2).unwrap());
+        assert_eq!(first, source.address_for_height(1).unwrap());
+    }
+}
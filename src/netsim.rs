@@ -0,0 +1,75 @@
+//! Network fault injection for testing
+//!
+//! Local testnets and integration tests run over loopback, where messages
+//! arrive instantly and never get lost, so sync and reorganization code is
+//! rarely exercised under the conditions it meets in the wild. A node
+//! configured with faults (`[dev]` in `config.toml`) delays every P2P
+//! message it sends and drops a share of them. Whole messages are dropped,
+//! never parts of one, so framing stays intact; handshakes always go
+//! through so connections still come up.
+
+use crate::config::DevConfig;
+use crate::error::ChainError;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NetworkFaults {
+    pub latency: Duration,
+    /// Fraction (0 to 1) of messages dropped
+    pub drop_rate: f64,
+}
+
+impl NetworkFaults {
+    pub fn new(latency: Duration, drop_rate: f64) -> Result<Self, ChainError> {
+        if !(0.0..=1.0).contains(&drop_rate) {
+            return Err(ChainError::NetworkError(format!(
+                "Drop rate {} is not between 0 and 1",
+                drop_rate
+            )));
+        }
+        Ok(Self { latency, drop_rate })
+    }
+
+    pub fn from_config(config: &DevConfig) -> Result<Self, ChainError> {
+        Self::new(Duration::from_millis(config.simulate_latency_ms), config.drop_rate)
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.latency.is_zero() || self.drop_rate > 0.0
+    }
+
+    /// Wait out the simulated latency, then decide whether the message
+    /// survives. Returns `false` if it should be dropped.
+    pub async fn deliver(&self) -> bool {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        !self.drops()
+    }
+
+    /// Whether the next message is lost
+    pub fn drops(&self) -> bool {
+        self.drop_rate > 0.0 && rand::random::<f64>() < self.drop_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_faults_delay_and_drop() {
+        assert!(!NetworkFaults::default().is_active());
+        assert!(NetworkFaults::new(Duration::ZERO, 1.5).is_err());
+
+        let lossless = NetworkFaults::new(Duration::from_millis(30), 0.0).unwrap();
+        let start = Instant::now();
+        assert!(lossless.deliver().await);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        let lossy = NetworkFaults::new(Duration::ZERO, 1.0).unwrap();
+        assert!(lossy.is_active());
+        assert!(!lossy.deliver().await);
+    }
+}
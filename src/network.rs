@@ -1,16 +1,206 @@
 //! P2P Networking for TrinityChain
 
-use crate::blockchain::Blockchain;
+use crate::addnode::AddNodeList;
+use crate::banlist::{BanEntry, BanList, DEFAULT_MISBEHAVIOR_BAN};
+use crate::bloom::{BloomFilter, MAX_ELEMENT_BYTES};
+use crate::chainspec::{network_magic, MAGIC_LEN};
+use crate::blockchain::{Block, Blockchain, Sha256Hash, MAX_FUTURE_BLOCK_TIME_MS, MAX_HEADERS_PER_REQUEST};
+use crate::checkpoint::{CheckpointAuthority, SignedCheckpoint};
+use crate::compression;
+use crate::discovery::{PeerAddress, PeerDiscovery, MAX_ADDR_PER_MESSAGE};
 use crate::error::ChainError;
-use std::collections::HashMap;
+use crate::headerrelay::{HeaderRelay, UnvalidatedHeader};
+use crate::netsim::NetworkFaults;
+use crate::peerstats::{ConnectionCounters, PeerStatsRecorder, SAMPLE_INTERVAL};
+use crate::pipeline::{self, CheckedBlock};
+use crate::propagation::{InventoryKind, PropagationReport, PropagationTracker};
+use crate::knowninvalid::{InvalidKind, KnownInvalid};
+use crate::rejected::{RejectedBlock, RejectedBlockLog};
+use crate::sendqueue::{Frame, SendError, SendPriority, SendQueue, SendQueueLimits, SendQueueStats};
+use crate::sync::{negotiate_headers, NodeSynchronizer, SyncBufferLimits, BLOCK_REQUEST_TIMEOUT};
+use crate::transaction::Transaction;
+use crate::txtrace::{HopReport, TraceLog, TraceTag};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU16, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::AbortHandle;
+use tracing::{error, info, warn};
+
+/// Transactions kept for relay while no peer could take them
+pub const MAX_PENDING_RELAY: usize = 1000;
 
 /// Maximum message size to prevent DoS attacks (10MB)
 const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
 
+/// P2P protocol version announced in the handshake
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// Oldest peer protocol version still accepted
+pub const MIN_PEER_PROTOCOL_VERSION: u32 = 1;
+
+/// First protocol version that exchanges [`NetworkMessage::Capabilities`]
+pub const CAPABILITIES_VERSION: u32 = 2;
+
+/// First protocol version that announces [`NetworkMessage::ChainTip`]; older
+/// peers are compared by height alone
+pub const CHAIN_WORK_VERSION: u32 = 3;
+
+/// First protocol version whose transactions sign the chain id; older peers
+/// would reject them, so they are not sent transactions
+pub const CHAIN_ID_SIGNATURES_VERSION: u32 = 4;
+
+/// Feature bits a node advertises in [`NetworkMessage::Capabilities`]
+pub mod features {
+    /// Serves full blocks (`GetBlock`)
+    pub const FULL_BLOCKS: u64 = 1 << 0;
+    /// Answers mempool synchronization (`GetMempool`, `GetTransactions`)
+    pub const MEMPOOL_SYNC: u64 = 1 << 1;
+    /// Answers locator-based header queries (`GetHeaders`)
+    pub const HEADERS_SYNC: u64 = 1 << 2;
+    /// Understands signed checkpoints (`Checkpoint`)
+    pub const CHECKPOINTS: u64 = 1 << 3;
+    /// Exchanges peer addresses (`GetAddr`, `Addr`)
+    pub const PEER_EXCHANGE: u64 = 1 << 4;
+    /// Answers keepalive pings (`Ping`, `Pong`)
+    pub const KEEPALIVE: u64 = 1 << 5;
+    /// Accepts zstd-compressed frames (`Compressed`)
+    pub const ZSTD: u64 = 1 << 6;
+    /// Filters transaction relay by a bloom filter the peer loads
+    /// (`FilterLoad`, `FilterAdd`, `FilterClear`)
+    pub const BLOOM: u64 = 1 << 7;
+    /// Takes new block headers ahead of their bodies (`NewHeader`)
+    pub const HEADER_RELAY: u64 = 1 << 8;
+    /// Takes transactions relayed with a trace tag (`TracedTransaction`)
+    pub const TX_TRACE: u64 = 1 << 9;
+    /// Checks the magic of the sender's network (`NetworkMagic`)
+    pub const NETWORK_MAGIC: u64 = 1 << 10;
+
+    /// Features implied by every protocol version 1 peer
+    pub const LEGACY: u64 = FULL_BLOCKS | MEMPOOL_SYNC | HEADERS_SYNC;
+    /// Features offered by this node
+    pub const LOCAL: u64 = FULL_BLOCKS
+        | MEMPOOL_SYNC
+        | HEADERS_SYNC
+        | CHECKPOINTS
+        | PEER_EXCHANGE
+        | KEEPALIVE
+        | ZSTD
+        | BLOOM
+        | HEADER_RELAY
+        | TX_TRACE
+        | NETWORK_MAGIC;
+
+    /// Names of the known bits set in `bits`
+    pub fn names(bits: u64) -> Vec<&'static str> {
+        [
+            (FULL_BLOCKS, "full_blocks"),
+            (MEMPOOL_SYNC, "mempool_sync"),
+            (HEADERS_SYNC, "headers_sync"),
+            (CHECKPOINTS, "checkpoints"),
+            (PEER_EXCHANGE, "peer_exchange"),
+            (KEEPALIVE, "keepalive"),
+            (ZSTD, "zstd"),
+            (BLOOM, "bloom"),
+            (HEADER_RELAY, "header_relay"),
+            (TX_TRACE, "tx_trace"),
+            (NETWORK_MAGIC, "network_magic"),
+        ]
+        .into_iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// Maximum number of transactions requested from a peer in one round trip
+const MAX_MEMPOOL_FETCH: usize = 1000;
+
+/// How often free outbound slots are filled from known addresses
+const OUTBOUND_FILL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often new entries of the known-invalid cache are written to disk
+const KNOWN_INVALID_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often each peer advertising [`features::KEEPALIVE`] is pinged
+pub const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Consecutive unanswered pings after which a peer is disconnected
+pub const MAX_MISSED_PONGS: u32 = 3;
+
+/// Which side opened a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// What was negotiated with a peer during the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCapabilities {
+    /// Version the peer announced
+    pub peer_version: u32,
+    /// Version used on the connection: the lower of ours and the peer's
+    pub protocol_version: u32,
+    /// Feature bits both sides support
+    pub features: u64,
+}
+
+impl PeerCapabilities {
+    fn negotiate(peer_version: u32) -> Self {
+        PeerCapabilities {
+            peer_version,
+            protocol_version: peer_version.min(PROTOCOL_VERSION),
+            features: features::LEGACY & features::LOCAL,
+        }
+    }
+
+    pub fn supports(&self, message: &NetworkMessage) -> bool {
+        let required = message.required_features();
+        self.protocol_version >= message.min_protocol_version() && self.features & required == required
+    }
+}
+
+impl serde::Serialize for PeerCapabilities {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("PeerCapabilities", 4)?;
+        state.serialize_field("peer_version", &self.peer_version)?;
+        state.serialize_field("protocol_version", &self.protocol_version)?;
+        state.serialize_field("features", &self.features)?;
+        state.serialize_field("feature_names", &features::names(self.features))?;
+        state.end()
+    }
+}
+
+/// Connection slot limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerLimits {
+    pub max_inbound: usize,
+    pub max_outbound: usize,
+    /// Outbound slots only operator-chosen peers (addnodes) may fill, so
+    /// automatically found peers can never crowd them out
+    pub reserved_outbound: usize,
+}
+
+impl Default for PeerLimits {
+    fn default() -> Self {
+        PeerLimits {
+            max_inbound: 64,
+            max_outbound: 8,
+            reserved_outbound: 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct Node {
     pub host: String,
@@ -22,68 +212,420 @@ impl Node {
         Node { host, port }
     }
 
+    /// `host:port`, with IPv6 literals in brackets (`[::1]:8333`)
     pub fn addr(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+        if self.is_ipv6() {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.host.parse::<Ipv6Addr>().is_ok()
+    }
+
+    /// Tor onion service, only reachable through a SOCKS5 proxy
+    pub fn is_onion(&self) -> bool {
+        self.host.ends_with(".onion")
+    }
+}
+
+impl FromStr for Node {
+    type Err = ChainError;
+
+    /// Parse `host:port`, `[ipv6]:port` or `<v3 onion>.onion:port`
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let invalid = |reason: &str| ChainError::NetworkError(format!("{} in '{}'", reason, input));
+        let (host, port) = match input.strip_prefix('[') {
+            Some(rest) => {
+                let (host, port) = rest.split_once("]:").ok_or_else(|| invalid("Expected [ipv6]:port"))?;
+                let ip = host.parse::<Ipv6Addr>().map_err(|_| invalid("Invalid IPv6 address"))?;
+                (ip.to_string(), port)
+            }
+            None => {
+                let (host, port) = input.rsplit_once(':').ok_or_else(|| invalid("Expected host:port"))?;
+                if host.contains(':') {
+                    return Err(invalid("IPv6 addresses must be bracketed"));
+                }
+                (host.to_string(), port)
+            }
+        };
+        let port = port.parse::<u16>().map_err(|_| invalid("Invalid port"))?;
+        if host.is_empty() {
+            return Err(invalid("Missing host"));
+        }
+        let mut node = Node::new(host, port);
+        if node.host.to_ascii_lowercase().ends_with(".onion") {
+            node.host.make_ascii_lowercase();
+        }
+        if node.is_onion() && !is_valid_onion(&node.host) {
+            return Err(invalid("Invalid onion address"));
+        }
+        Ok(node)
     }
 }
 
-/// Manages a pool of active P2P connections
+/// Version 3 onion addresses are 56 base32 characters followed by `.onion`
+fn is_valid_onion(host: &str) -> bool {
+    host.strip_suffix(".onion").is_some_and(|name| {
+        name.len() == 56 && name.bytes().all(|b| b.is_ascii_lowercase() || (b'2'..=b'7').contains(&b))
+    })
+}
+
+/// Manages a pool of active P2P connections.
+///
+/// The write half of each stream is owned by a writer task draining the
+/// connection's [`SendQueue`]; the read half is owned by the connection's
+/// handler task so replies can be sent while it waits for the next message.
 struct ConnectionPool {
-    connections: RwLock<HashMap<String, Arc<RwLock<TcpStream>>>>,
+    connections: RwLock<HashMap<String, PeerConnection>>,
+    /// Simulated latency and loss applied to everything sent
+    faults: NetworkFaults,
+    /// zstd level for large frames to peers advertising [`features::ZSTD`];
+    /// 0 sends everything uncompressed
+    compression_level: i32,
+    send_limits: SendQueueLimits,
+}
+
+type ConnectionInfo = (
+    Node,
+    Direction,
+    Instant,
+    Option<PeerCapabilities>,
+    Option<Duration>,
+    SendQueueStats,
+);
+
+struct PeerConnection {
+    node: Node,
+    queue: Arc<SendQueue>,
+    writer_task: AbortHandle,
+    direction: Direction,
+    connected_at: Instant,
+    /// Set once the peer's handshake arrives
+    capabilities: Option<PeerCapabilities>,
+    /// Whether the peer's `GetAddr` was answered; only the first one is
+    answered_getaddr: bool,
+    /// The connection's handler task, aborted when the peer stops answering
+    reader_task: Option<AbortHandle>,
+    keepalive: Keepalive,
+    /// Loaded by the peer; only transactions matching it are relayed
+    relay_filter: Option<BloomFilter>,
+    /// Traffic and uptime for the long-term peer statistics
+    counters: Arc<ConnectionCounters>,
+}
+
+impl Drop for PeerConnection {
+    /// Stop the writer, which closes our end of the stream
+    fn drop(&mut self) {
+        self.writer_task.abort();
+        self.counters.close();
+    }
+}
+
+/// Write the frames queued for a peer until a write fails
+async fn write_frames(queue: Arc<SendQueue>, mut writer: OwnedWriteHalf, addr: String, counters: Arc<ConnectionCounters>) {
+    loop {
+        let frame = queue.pop().await;
+        let len = frame.len() as u32;
+        let result = match writer.write_all(&len.to_be_bytes()).await {
+            Ok(()) => writer.write_all(&frame).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            error!("❌ Failed to write to {}: {}", addr, e);
+            queue.close();
+            return;
+        }
+        counters.sent(frame.len() + 4);
+    }
+}
+
+/// Ping bookkeeping for one connection
+#[derive(Default)]
+struct Keepalive {
+    /// Nonce and send time of the ping awaiting its pong
+    outstanding: Option<(u64, Instant)>,
+    /// Pings in a row that went unanswered
+    missed: u32,
+    /// Round trip of the last answered ping
+    latency: Option<Duration>,
 }
 
 impl ConnectionPool {
-    fn new() -> Self {
+    fn new(faults: NetworkFaults, compression_level: i32, send_limits: SendQueueLimits) -> Self {
         ConnectionPool {
             connections: RwLock::new(HashMap::new()),
+            faults,
+            compression_level,
+            send_limits,
+        }
+    }
+
+    /// `data` wrapped in a `Compressed` frame, if it is large enough to be
+    /// worth it and compression makes it smaller
+    fn compress_frame(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if self.compression_level == 0 || data.len() < compression::MIN_COMPRESSED_SIZE {
+            return None;
         }
+        let payload = compression::compress(data, self.compression_level).ok()?;
+        let frame = bincode::serialize(&NetworkMessage::Compressed(payload)).ok()?;
+        (frame.len() < data.len()).then_some(frame)
     }
 
     /// Add a new connection to the pool
-    async fn add(&self, node: &Node, stream: TcpStream) {
+    async fn add(&self, node: &Node, writer: OwnedWriteHalf, direction: Direction, counters: Arc<ConnectionCounters>) {
+        let mut connections = self.connections.write().await;
+        let queue = Arc::new(SendQueue::new(self.send_limits));
+        let writer_task = tokio::spawn(write_frames(queue.clone(), writer, node.addr(), counters.clone()));
+        let connection = PeerConnection {
+            node: node.clone(),
+            queue,
+            writer_task: writer_task.abort_handle(),
+            direction,
+            connected_at: Instant::now(),
+            capabilities: None,
+            answered_getaddr: false,
+            reader_task: None,
+            keepalive: Keepalive::default(),
+            relay_filter: None,
+            counters,
+        };
+        connections.insert(node.addr(), connection);
+    }
+
+    async fn set_reader_task(&self, node: &Node, task: AbortHandle) {
+        if let Some(connection) = self.connections.write().await.get_mut(&node.addr()) {
+            connection.reader_task = Some(task);
+        }
+    }
+
+    /// Start a new ping round. Every peer advertising keepalive whose last
+    /// ping is still unanswered counts a miss; returns the peers to ping
+    /// with their nonces, and the peers that missed `max_missed` in a row.
+    async fn next_pings(&self, max_missed: u32) -> (Vec<(Node, u64)>, Vec<Node>) {
+        let mut connections = self.connections.write().await;
+        let mut pings = Vec::new();
+        let mut unresponsive = Vec::new();
+        for connection in connections.values_mut() {
+            let keepalive = connection
+                .capabilities
+                .is_some_and(|c| c.features & features::KEEPALIVE != 0);
+            if !keepalive {
+                continue;
+            }
+            if connection.keepalive.outstanding.is_some() {
+                connection.keepalive.missed += 1;
+            }
+            if connection.keepalive.missed >= max_missed {
+                unresponsive.push(connection.node.clone());
+                continue;
+            }
+            let nonce = rand::random();
+            connection.keepalive.outstanding = Some((nonce, Instant::now()));
+            pings.push((connection.node.clone(), nonce));
+        }
+        (pings, unresponsive)
+    }
+
+    /// Match a pong to the outstanding ping, returning the round trip
+    async fn record_pong(&self, node: &Node, nonce: u64) -> Option<Duration> {
+        let mut connections = self.connections.write().await;
+        let keepalive = &mut connections.get_mut(&node.addr())?.keepalive;
+        match keepalive.outstanding {
+            Some((sent_nonce, sent_at)) if sent_nonce == nonce => {
+                let latency = sent_at.elapsed();
+                keepalive.outstanding = None;
+                keepalive.missed = 0;
+                keepalive.latency = Some(latency);
+                Some(latency)
+            }
+            _ => None,
+        }
+    }
+
+    async fn set_capabilities(&self, node: &Node, capabilities: PeerCapabilities) {
+        if let Some(connection) = self.connections.write().await.get_mut(&node.addr()) {
+            connection.capabilities = Some(capabilities);
+        }
+    }
+
+    async fn capabilities(&self, node: &Node) -> Option<PeerCapabilities> {
+        self.connections
+            .read()
+            .await
+            .get(&node.addr())
+            .and_then(|connection| connection.capabilities)
+    }
+
+    async fn direction(&self, node: &Node) -> Option<Direction> {
+        self.connections
+            .read()
+            .await
+            .get(&node.addr())
+            .map(|connection| connection.direction)
+    }
+
+    /// Flag the peer's `GetAddr` as answered; `false` if it already was
+    async fn take_getaddr(&self, node: &Node) -> bool {
+        match self.connections.write().await.get_mut(&node.addr()) {
+            Some(connection) if !connection.answered_getaddr => {
+                connection.answered_getaddr = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replace the peer's relay filter; `None` relays everything again
+    async fn set_relay_filter(&self, node: &Node, filter: Option<BloomFilter>) {
+        if let Some(connection) = self.connections.write().await.get_mut(&node.addr()) {
+            connection.relay_filter = filter;
+        }
+    }
+
+    /// Add `element` to the peer's relay filter; `false` if it has none
+    async fn add_to_relay_filter(&self, node: &Node, element: &[u8]) -> bool {
+        match self
+            .connections
+            .write()
+            .await
+            .get_mut(&node.addr())
+            .and_then(|connection| connection.relay_filter.as_mut())
+        {
+            Some(filter) => {
+                filter.insert(element);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the peer's relay filter, if any, lets `tx` through
+    async fn wants(&self, node: &Node, tx: &Transaction) -> bool {
+        self.connections
+            .read()
+            .await
+            .get(&node.addr())
+            .and_then(|connection| connection.relay_filter.as_ref())
+            .is_none_or(|filter| filter.matches(tx))
+    }
+
+    /// Number of connections opened in `direction`
+    async fn count(&self, direction: Direction) -> usize {
+        let connections = self.connections.read().await;
+        connections.values().filter(|c| c.direction == direction).count()
+    }
+
+    /// Remove a connection from the pool, returning whether it existed
+    async fn remove(&self, node: &Node) -> bool {
         let mut connections = self.connections.write().await;
-        connections.insert(node.addr(), Arc::new(RwLock::new(stream)));
+        connections.remove(&node.addr()).is_some()
+    }
+
+    /// Stop the connection's handler task. Dropping the writer lets a live
+    /// peer close its end, but a dead one never does, so its read would
+    /// otherwise wait forever.
+    async fn abort_reader(&self, node: &Node) {
+        if let Some(task) = self
+            .connections
+            .read()
+            .await
+            .get(&node.addr())
+            .and_then(|connection| connection.reader_task.as_ref())
+        {
+            task.abort();
+        }
     }
 
-    /// Remove a connection from the pool
-    async fn remove(&self, node: &Node) {
+    /// Drop every connection to `host`, returning how many were closed
+    async fn remove_host(&self, host: &str) -> usize {
         let mut connections = self.connections.write().await;
-        connections.remove(&node.addr());
+        let before = connections.len();
+        connections.retain(|_, connection| connection.node.host != host);
+        before - connections.len()
     }
 
-    /// Broadcast a message to all connected peers
-    async fn broadcast(&self, message: &NetworkMessage) {
+    /// Queue a message for all connected peers, returning how many took it
+    /// and the peers to disconnect because their queue overflowed
+    async fn broadcast(&self, message: &NetworkMessage) -> (usize, Vec<Node>) {
         let connections = self.connections.read().await;
-        let data = match bincode::serialize(message) {
-            Ok(data) => data,
+        let data: Frame = match bincode::serialize(message) {
+            Ok(data) => Arc::new(data),
             Err(e) => {
-                eprintln!("❌ Failed to serialize message for broadcast: {}", e);
-                return;
+                error!("❌ Failed to serialize message for broadcast: {}", e);
+                return (0, Vec::new());
             }
         };
-        let len = data.len() as u32;
+        let priority = message.send_priority();
+        if !self.faults.latency.is_zero() {
+            tokio::time::sleep(self.faults.latency).await;
+        }
 
-        for (addr, stream_lock) in connections.iter() {
-            let mut stream = stream_lock.write().await;
-            if let Err(e) = stream.write_all(&len.to_be_bytes()).await {
-                eprintln!("❌ Failed to write len to {}: {}", addr, e);
+        // Compressed once, for the first peer that accepts it
+        let mut compressed: Option<Option<Frame>> = None;
+        let mut sent = 0;
+        let mut overflowed = Vec::new();
+        for connection in connections.values() {
+            // Peers that have not completed the handshake, or speak an older
+            // protocol, are skipped
+            if !connection.capabilities.is_some_and(|c| c.supports(message)) {
                 continue;
             }
-            if let Err(e) = stream.write_all(&data).await {
-                eprintln!("❌ Failed to write data to {}: {}", addr, e);
+            // Light clients get only the transactions their filter matches
+            if let (NetworkMessage::NewTransaction(tx), Some(filter)) = (message, &connection.relay_filter) {
+                if !filter.matches(tx) {
+                    continue;
+                }
+            }
+            // A simulated loss looks like a successful send to the sender
+            if self.faults.drops() {
+                sent += 1;
+                continue;
+            }
+            let mut frame = &data;
+            if connection.capabilities.is_some_and(|c| c.features & features::ZSTD != 0) {
+                let smaller = compressed.get_or_insert_with(|| self.compress_frame(&data).map(Arc::new));
+                if let Some(smaller) = smaller {
+                    frame = smaller;
+                }
+            }
+            match connection.queue.push(frame.clone(), priority) {
+                Ok(()) => sent += 1,
+                Err(SendError::Overflow) => overflowed.push(connection.node.clone()),
+                Err(_) => {}
             }
         }
+        (sent, overflowed)
     }
 
     /// Get a list of all peer nodes
     async fn list_peers(&self) -> Vec<Node> {
+        self.list_connections()
+            .await
+            .into_iter()
+            .map(|(node, ..)| node)
+            .collect()
+    }
+
+    /// Every peer with the direction and time of its connection and what
+    /// was negotiated with it
+    async fn list_connections(&self) -> Vec<ConnectionInfo> {
         self.connections
             .read()
             .await
-            .keys()
-            .map(|addr| {
-                let parts: Vec<&str> = addr.split(':').collect();
-                Node::new(parts[0].to_string(), parts[1].parse().unwrap_or(0))
+            .values()
+            .map(|c| {
+                (
+                    c.node.clone(),
+                    c.direction,
+                    c.connected_at,
+                    c.capabilities,
+                    c.keepalive.latency,
+                    c.queue.stats(),
+                )
             })
             .collect()
     }
@@ -91,168 +633,2390 @@ impl ConnectionPool {
 
 pub struct NetworkNode {
     pub blockchain: Arc<RwLock<Blockchain>>,
+    pub synchronizer: Arc<NodeSynchronizer>,
+    pub banlist: Arc<RwLock<BanList>>,
+    /// Peers added by the operator, reconnected on startup
+    pub addnodes: Arc<RwLock<AddNodeList>>,
+    /// First sightings of relayed blocks and transactions, per-peer latency
+    pub propagation: Arc<RwLock<PropagationTracker>>,
+    /// Recently refused blocks and why, for diagnosing divergence
+    pub rejected_blocks: Arc<RwLock<RejectedBlockLog>>,
+    /// Blocks and transactions refused for good, not validated again
+    pub known_invalid: Arc<Mutex<KnownInvalid>>,
+    /// Per-peer counters not yet written to the database
+    pub peer_stats: Arc<std::sync::Mutex<PeerStatsRecorder>>,
+    limits: PeerLimits,
+    ping_interval: Duration,
+    /// SOCKS5 proxy (e.g. Tor) used for every outbound connection
+    proxy: Option<String>,
+    /// Keys whose signed checkpoints are honored; `None` ignores checkpoints
+    checkpoint_authority: Option<CheckpointAuthority>,
+    /// Latest checkpoint adopted, handed to peers as they connect
+    latest_checkpoint: RwLock<Option<SignedCheckpoint>>,
+    /// Bootstrap peers and addresses learned through peer exchange
+    pub discovery: Arc<RwLock<PeerDiscovery>>,
+    /// Port we accept connections on, advertised to outbound peers; 0
+    /// until `serve` starts
+    listen_port: AtomicU16,
     pool: Arc<ConnectionPool>,
+    /// Local transactions no peer has received yet, oldest first. They are
+    /// announced to the next peer that completes its handshake.
+    pending_relay: Mutex<VecDeque<Transaction>>,
+    /// Filter loaded into every peer advertising [`features::BLOOM`], for
+    /// running as a light client; `None` takes every transaction
+    relay_filter: RwLock<Option<BloomFilter>>,
+    /// Held while downloaded blocks are fed through the validation
+    /// pipeline, so only one batch is in flight at a time
+    applying_downloads: Mutex<()>,
+    /// Header relayed ahead of its body, and the block mined on it
+    header_relay: Mutex<HeaderRelay>,
+    /// Name this node reports traced transactions under; `None` while
+    /// tracing is off
+    trace_name: Option<String>,
+    /// `/api/trace/report` URL hop reports are POSTed to
+    trace_aggregator: Option<String>,
+    /// Hops of traced transactions seen here or reported to this node
+    pub traces: Arc<RwLock<TraceLog>>,
 }
 
 impl NetworkNode {
     pub fn new(blockchain: Arc<RwLock<Blockchain>>) -> Self {
         NetworkNode {
             blockchain,
-            pool: Arc::new(ConnectionPool::new()),
+            synchronizer: Arc::new(NodeSynchronizer::new()),
+            banlist: Arc::new(RwLock::new(BanList::new())),
+            addnodes: Arc::new(RwLock::new(AddNodeList::new())),
+            propagation: Arc::new(RwLock::new(PropagationTracker::new())),
+            rejected_blocks: Arc::new(RwLock::new(RejectedBlockLog::new())),
+            known_invalid: Arc::new(Mutex::new(KnownInvalid::new())),
+            peer_stats: Arc::new(std::sync::Mutex::new(PeerStatsRecorder::new())),
+            limits: PeerLimits::default(),
+            ping_interval: PING_INTERVAL,
+            proxy: None,
+            checkpoint_authority: None,
+            latest_checkpoint: RwLock::new(None),
+            discovery: Arc::new(RwLock::new(PeerDiscovery::new())),
+            listen_port: AtomicU16::new(0),
+            pool: Arc::new(ConnectionPool::new(
+                NetworkFaults::default(),
+                compression::DEFAULT_LEVEL,
+                SendQueueLimits::default(),
+            )),
+            pending_relay: Mutex::new(VecDeque::new()),
+            relay_filter: RwLock::new(None),
+            applying_downloads: Mutex::new(()),
+            header_relay: Mutex::new(HeaderRelay::new()),
+            trace_name: None,
+            trace_aggregator: None,
+            traces: Arc::new(RwLock::new(TraceLog::new())),
         }
     }
 
-    pub async fn start_server(self: Arc<Self>, port: u16) -> Result<(), ChainError> {
-        let addr = format!("0.0.0.0:{}", port);
-        let listener = TcpListener::bind(&addr)
-            .await
-            .map_err(|e| ChainError::NetworkError(format!("Failed to bind: {}", e)))?;
+    /// Use a (typically disk-backed) addnode list instead of the in-memory default
+    pub fn with_addnodes(mut self, addnodes: AddNodeList) -> Self {
+        self.addnodes = Arc::new(RwLock::new(addnodes));
+        self
+    }
 
-        println!("🌐 Node listening on {}", addr);
+    /// Delay and drop sent messages to simulate a bad network (testing only)
+    pub fn with_network_faults(mut self, faults: NetworkFaults) -> Self {
+        self.pool = Arc::new(ConnectionPool::new(faults, self.pool.compression_level, self.pool.send_limits));
+        self
+    }
 
-        loop {
-            let (socket, peer_addr) = listener
-                .accept()
-                .await
-                .map_err(|e| ChainError::NetworkError(format!("Accept error: {}", e)))?;
+    /// Compress large messages to peers that accept it at zstd `level`
+    /// instead of [`compression::DEFAULT_LEVEL`]; 0 turns it off
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.pool = Arc::new(ConnectionPool::new(self.pool.faults, level, self.pool.send_limits));
+        self
+    }
 
-            println!("📡 New connection from {}", peer_addr);
-            let node = Node::new(peer_addr.ip().to_string(), peer_addr.port());
-            self.pool.add(&node, socket).await;
+    /// Bound each peer's send queue by `limits` instead of the defaults
+    pub fn with_send_queue_limits(mut self, limits: SendQueueLimits) -> Self {
+        self.pool = Arc::new(ConnectionPool::new(self.pool.faults, self.pool.compression_level, limits));
+        self
+    }
 
-            let self_clone = self.clone();
-            tokio::spawn(async move {
-                if let Err(e) = self_clone.handle_connection(&node).await {
-                    eprintln!("❌ Connection error with {}: {}", node.addr(), e);
-                    self_clone.pool.remove(&node).await;
-                }
-            });
-        }
+    /// Bound the buffer of downloaded blocks awaiting application
+    pub fn with_sync_buffer_limits(mut self, limits: SyncBufferLimits) -> Self {
+        self.synchronizer = Arc::new(NodeSynchronizer::with_buffer_limits(limits));
+        self
     }
 
-    pub async fn connect_peer(self: Arc<Self>, host: String, port: u16) -> Result<(), ChainError> {
-        let addr = format!("{}:{}", host, port);
-        println!("🔗 Connecting to peer: {}", addr);
+    /// Override the default connection slot limits
+    pub fn with_peer_limits(mut self, limits: PeerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        let stream = TcpStream::connect(&addr)
-            .await
-            .map_err(|e| ChainError::NetworkError(format!("Failed to connect: {}", e)))?;
+    /// Ping peers this often instead of every [`PING_INTERVAL`]
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
 
-        let node = Node::new(host, port);
-        self.pool.add(&node, stream).await;
+    pub fn peer_limits(&self) -> PeerLimits {
+        self.limits
+    }
 
-        let self_clone = self.clone();
-        tokio::spawn(async move {
-            if let Err(e) = self_clone.handle_connection(&node).await {
-                eprintln!("❌ Connection error with {}: {}", node.addr(), e);
-                self_clone.pool.remove(&node).await;
-            }
-        });
+    /// Route outbound connections through the SOCKS5 proxy at `proxy`
+    /// (`host:port`). Required to reach `.onion` peers.
+    pub fn with_proxy(mut self, proxy: String) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
 
-        Ok(())
+    /// Honor checkpoints signed by `authority`
+    pub fn with_checkpoint_authority(mut self, authority: CheckpointAuthority) -> Self {
+        self.checkpoint_authority = Some(authority);
+        self
     }
 
-    async fn handle_connection(&self, node: &Node) -> Result<(), ChainError> {
-        let stream_lock = self
-            .pool
-            .connections
-            .read()
-            .await
-            .get(&node.addr())
-            .cloned()
-            .ok_or_else(|| ChainError::NetworkError("Connection not in pool".to_string()))?;
+    pub fn checkpoint_authority(&self) -> Option<&CheckpointAuthority> {
+        self.checkpoint_authority.as_ref()
+    }
 
-        loop {
-            let mut stream = stream_lock.write().await;
-            let mut len_bytes = [0u8; 4];
-            stream.read_exact(&mut len_bytes).await?;
-            let len = u32::from_be_bytes(len_bytes) as usize;
+    /// Verify a signed checkpoint, adopt it and relay it to peers. Returns
+    /// `false` if it is not newer than the checkpoint we already follow.
+    pub async fn submit_checkpoint(&self, signed: SignedCheckpoint) -> Result<bool, ChainError> {
+        let authority = self
+            .checkpoint_authority
+            .as_ref()
+            .ok_or_else(|| ChainError::NetworkError("Checkpoints are not enabled".to_string()))?;
+        authority.verify(&signed)?;
+        if !self.blockchain.write().await.set_checkpoint(signed.checkpoint.clone())? {
+            return Ok(false);
+        }
+        info!(
+            "📌 Checkpoint at height {}: {}",
+            signed.checkpoint.height,
+            hex::encode(signed.checkpoint.hash)
+        );
+        let message = NetworkMessage::Checkpoint(Box::new(signed.clone()));
+        *self.latest_checkpoint.write().await = Some(signed);
+        self.broadcast(&message).await;
+        Ok(true)
+    }
 
-            if len > MAX_MESSAGE_SIZE {
-                return Err(ChainError::NetworkError("Message too large".to_string()));
-            }
+    /// Close the connection to `node`. Returns `false` if it was not connected.
+    pub async fn disconnect_peer(&self, node: &Node) -> bool {
+        let removed = self.pool.remove(node).await;
+        if removed {
+            info!("🔌 Disconnected from {}", node.addr());
+        }
+        removed
+    }
 
-            let mut buffer = vec![0u8; len];
-            stream.read_exact(&mut buffer).await?;
+    /// Use a (typically disk-backed) ban list instead of the in-memory default
+    pub fn with_banlist(mut self, banlist: BanList) -> Self {
+        self.banlist = Arc::new(RwLock::new(banlist));
+        self
+    }
 
-            let message: NetworkMessage = bincode::deserialize(&buffer)?;
+    /// Use a (typically disk-backed) rejected-block log instead of the
+    /// in-memory default
+    pub fn with_rejected_block_log(mut self, log: RejectedBlockLog) -> Self {
+        self.rejected_blocks = Arc::new(RwLock::new(log));
+        self
+    }
 
-            match message {
-                NetworkMessage::GetBlockHeaders { after_height } => {
-                    let chain = self.blockchain.read().await;
-                    let headers = chain
-                        .blocks
-                        .iter()
-                        .filter(|b| b.header.height > after_height)
-                        .map(|b| b.header.clone())
-                        .collect();
-                    let response = NetworkMessage::BlockHeaders(headers);
-                    self.send_message(node, &response).await?;
-                }
-                NetworkMessage::GetBlock(hash) => {
-                    let chain = self.blockchain.read().await;
-                    if let Some(block) = chain.blocks.iter().find(|b| b.hash() == hash) {
-                        let response = NetworkMessage::Block(Box::new(block.clone()));
-                        self.send_message(node, &response).await?;
-                    }
-                }
-                NetworkMessage::GetPeers => {
-                    let peers = self.list_peers().await;
-                    let response = NetworkMessage::Peers(peers);
-                    self.send_message(node, &response).await?;
-                }
-                NetworkMessage::Peers(peers) => {
-                    for _peer in peers {
-                        // self.clone().connect_peer(peer.host, peer.port).await?;
-                    }
-                }
-                _ => {} // Implement other message types
-            }
-        }
+    /// Use a (typically disk-backed) known-invalid cache instead of the
+    /// in-memory default
+    pub fn with_known_invalid(mut self, cache: KnownInvalid) -> Self {
+        self.known_invalid = Arc::new(Mutex::new(cache));
+        self
     }
 
-    async fn send_message(&self, node: &Node, message: &NetworkMessage) -> Result<(), ChainError> {
-        let stream_lock = self
-            .pool
-            .connections
-            .read()
-            .await
-            .get(&node.addr())
-            .cloned()
-            .ok_or_else(|| ChainError::NetworkError("Connection not in pool".to_string()))?;
+    /// Trace transactions under `name`, POSTing hop reports to
+    /// `aggregator` when set (debugging only, see [`crate::txtrace`])
+    pub fn with_tx_trace(mut self, name: String, aggregator: Option<String>) -> Self {
+        self.trace_name = Some(name);
+        self.trace_aggregator = aggregator;
+        self
+    }
 
-        let data = bincode::serialize(message)?;
-        let len = data.len() as u32;
+    /// Whether traced transactions are reported and forwarded
+    pub fn tracing_enabled(&self) -> bool {
+        self.trace_name.is_some()
+    }
 
-        let mut stream = stream_lock.write().await;
-        stream.write_all(&len.to_be_bytes()).await?;
-        stream.write_all(&data).await?;
-        Ok(())
+    /// Log a refused block. `peer` is the sender when known.
+    pub async fn record_rejected_block(&self, hash: Sha256Hash, height: u64, peer: Option<&Node>, reason: &str) {
+        let entry = RejectedBlock {
+            hash: hex::encode(hash),
+            height,
+            peer: peer.map(|node| node.addr()),
+            reason: reason.to_string(),
+            rejected_at: unix_now(),
+        };
+        if let Err(e) = self.rejected_blocks.write().await.record(entry) {
+            warn!("⚠️  Failed to save rejected-block log: {}", e);
+        }
     }
 
-    pub async fn broadcast_transaction(&self, tx: &crate::transaction::Transaction) {
-        let message = NetworkMessage::NewTransaction(Box::new(tx.clone()));
-        self.pool.broadcast(&message).await;
+    /// Ban `host` and drop any open connections to it
+    pub async fn ban_peer(
+        &self,
+        host: &str,
+        reason: &str,
+        duration: Option<std::time::Duration>,
+    ) -> Result<BanEntry, ChainError> {
+        let entry = self.banlist.write().await.ban(host, reason, duration)?;
+        if let Ok(mut stats) = self.peer_stats.lock() {
+            stats.record_ban(&entry.host);
+        }
+        let dropped = self.pool.remove_host(&entry.host).await;
+        info!("🚫 Banned {} ({}), closed {} connection(s)", entry.host, reason, dropped);
+        Ok(entry)
     }
 
-    pub async fn broadcast_block(&self, block: &crate::blockchain::Block) {
-        let message = NetworkMessage::NewBlock(Box::new(block.clone()));
-        self.pool.broadcast(&message).await;
+    /// Lift a ban. Returns `false` if the host was not banned.
+    pub async fn unban_peer(&self, host: &str) -> Result<bool, ChainError> {
+        self.banlist.write().await.unban(host)
     }
 
-    pub async fn list_peers(&self) -> Vec<Node> {
-        self.pool.list_peers().await
+    pub async fn is_banned(&self, host: &str) -> bool {
+        self.banlist.read().await.is_banned(host)
     }
-}
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
-pub enum NetworkMessage {
-    GetBlockHeaders { after_height: u64 },
-    BlockHeaders(Vec<crate::blockchain::BlockHeader>),
-    GetBlock(crate::blockchain::Sha256Hash),
-    Block(Box<crate::blockchain::Block>),
-    NewBlock(Box<crate::blockchain::Block>),
-    NewTransaction(Box<crate::transaction::Transaction>),
-    GetPeers,
-    Peers(Vec<Node>),
+    /// Count a protocol failure against a peer and ban it once the
+    /// synchronizer considers it unreliable.
+    async fn penalize(&self, node: &Node, reason: &str) {
+        let _ = self.synchronizer.record_sync_failure(&node.addr()).await;
+        let unreliable = self
+            .synchronizer
+            .get_peer_info(&node.addr())
+            .await
+            .is_some_and(|info| info.is_unreliable());
+        if unreliable {
+            if let Err(e) = self.ban_peer(&node.host, reason, Some(DEFAULT_MISBEHAVIOR_BAN)).await {
+                warn!("⚠️  Failed to ban {}: {}", node.host, e);
+            }
+        }
+    }
+
+    pub async fn start_server(self: Arc<Self>, port: u16) -> Result<(), ChainError> {
+        let addr = format!("0.0.0.0:{}", port);
+        let listener = TcpListener::bind(&addr)
+            .await
+            .map_err(|e| ChainError::NetworkError(format!("Failed to bind: {}", e)))?;
+
+        info!("🌐 Node listening on {}", addr);
+        self.serve(listener).await
+    }
+
+    /// Accept peer connections on an already bound listener.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<(), ChainError> {
+        if let Ok(addr) = listener.local_addr() {
+            self.listen_port.store(addr.port(), Ordering::Relaxed);
+        }
+        tokio::spawn(self.clone().watch_stalled_downloads());
+        tokio::spawn(self.clone().fill_outbound_slots());
+        tokio::spawn(self.clone().keepalive());
+        tokio::spawn(self.clone().sample_peer_stats());
+        tokio::spawn(self.clone().flush_known_invalid());
+        loop {
+            let (socket, peer_addr) = listener
+                .accept()
+                .await
+                .map_err(|e| ChainError::NetworkError(format!("Accept error: {}", e)))?;
+
+            if self.is_banned(&peer_addr.ip().to_string()).await {
+                info!("🚫 Refused connection from banned peer {}", peer_addr);
+                continue;
+            }
+            if !self.make_inbound_room().await {
+                info!("🚫 Refused connection from {}: no inbound slots", peer_addr);
+                continue;
+            }
+            info!("📡 New connection from {}", peer_addr);
+            let node = Node::new(peer_addr.ip().to_string(), peer_addr.port());
+            self.clone().register_connection(node, socket, Direction::Inbound).await;
+        }
+    }
+
+    /// Ensure an inbound slot is free, evicting the worst-scoring inbound
+    /// peer if all are taken. Returns `false` if inbound connections are
+    /// disabled.
+    async fn make_inbound_room(&self) -> bool {
+        if self.limits.max_inbound == 0 {
+            return false;
+        }
+        if self.pool.count(Direction::Inbound).await < self.limits.max_inbound {
+            return true;
+        }
+        match self.select_inbound_eviction().await {
+            Some(victim) => {
+                info!("⏏️  Evicting inbound peer {} to free a slot", victim.addr());
+                self.disconnect_peer(&victim).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The inbound peer to drop when inbound slots are full: the one with the
+    /// most sync failures, then the fewest blocks and transactions relayed
+    /// first, then the most recently connected. Outbound peers are never
+    /// evicted.
+    async fn select_inbound_eviction(&self) -> Option<Node> {
+        let connections = self.pool.list_connections().await;
+        let propagation = self.propagation.read().await;
+        let mut scored = Vec::new();
+        for (node, direction, connected_at, ..) in connections {
+            if direction != Direction::Inbound {
+                continue;
+            }
+            let failures = self
+                .synchronizer
+                .get_peer_info(&node.addr())
+                .await
+                .map_or(0, |info| info.sync_failures);
+            let first_relays = propagation
+                .peer_stats(&node.addr())
+                .map_or(0, |stats| stats.first_relays);
+            scored.push(((failures, Reverse(first_relays), connected_at), node));
+        }
+        scored
+            .into_iter()
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, node)| node)
+    }
+
+    /// Open an outbound connection. Automatically chosen peers may use all
+    /// outbound slots except the reserved ones; see [`Self::connect_manual_peer`].
+    pub async fn connect_peer(self: Arc<Self>, host: String, port: u16) -> Result<(), ChainError> {
+        self.open_outbound(host, port, false).await
+    }
+
+    /// Open an outbound connection to a peer chosen by the operator (an
+    /// addnode), which may also use the reserved outbound slots.
+    pub async fn connect_manual_peer(self: Arc<Self>, host: String, port: u16) -> Result<(), ChainError> {
+        self.open_outbound(host, port, true).await
+    }
+
+    /// Periodically connect to known addresses while automatic outbound
+    /// slots are free, so the node finds peers beyond its bootstrap list
+    async fn fill_outbound_slots(self: Arc<Self>) {
+        // Startup connects to bootstrap peers and addnodes itself
+        let start = tokio::time::Instant::now() + OUTBOUND_FILL_INTERVAL;
+        let mut interval = tokio::time::interval_at(start, OUTBOUND_FILL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let slots = self.limits.max_outbound.saturating_sub(self.limits.reserved_outbound);
+            let free = slots.saturating_sub(self.pool.count(Direction::Outbound).await);
+            if free == 0 {
+                continue;
+            }
+            let connected: HashSet<Node> = self.pool.list_peers().await.into_iter().collect();
+            let candidates = {
+                let banlist = self.banlist.read().await;
+                let discovery = self.discovery.read().await;
+                discovery.outbound_candidates(free, |node| {
+                    connected.contains(node)
+                        || banlist.is_banned(&node.host)
+                        || (node.is_onion() && self.proxy.is_none())
+                })
+            };
+            for node in candidates {
+                if let Err(e) = self.clone().connect_peer(node.host.clone(), node.port).await {
+                    warn!("⚠️  Failed to connect to known address {}: {}", node.addr(), e);
+                    self.discovery.write().await.addresses_mut().mark_failed(&node);
+                }
+            }
+        }
+    }
+
+    /// Ping every peer advertising keepalive each `ping_interval`, feeding
+    /// round trips to the synchronizer and dropping peers that leave
+    /// [`MAX_MISSED_PONGS`] pings in a row unanswered
+    async fn keepalive(self: Arc<Self>) {
+        let start = tokio::time::Instant::now() + self.ping_interval;
+        let mut interval = tokio::time::interval_at(start, self.ping_interval);
+        loop {
+            interval.tick().await;
+            let (pings, unresponsive) = self.pool.next_pings(MAX_MISSED_PONGS).await;
+            for node in unresponsive {
+                let reason = format!("{} pings unanswered", MAX_MISSED_PONGS);
+                self.drop_peer(&node, &reason).await;
+            }
+            for (node, nonce) in pings {
+                if let Err(e) = self.send_message(&node, &NetworkMessage::Ping { nonce }).await {
+                    warn!("⚠️  Failed to ping {}: {}", node.addr(), e);
+                }
+            }
+        }
+    }
+
+    /// Every [`SAMPLE_INTERVAL`], write what each peer added to the
+    /// long-term statistics
+    async fn sample_peer_stats(self: Arc<Self>) {
+        let start = tokio::time::Instant::now() + SAMPLE_INTERVAL;
+        let mut interval = tokio::time::interval_at(start, SAMPLE_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.save_peer_stats().await;
+        }
+    }
+
+    async fn save_peer_stats(&self) {
+        let samples = match self.peer_stats.lock() {
+            Ok(mut stats) => stats.take(Instant::now(), chrono::Utc::now().timestamp_millis() as u64),
+            Err(_) => return,
+        };
+        if samples.is_empty() {
+            return;
+        }
+        if let Err(e) = self.blockchain.read().await.persistence.save_peer_stats(&samples) {
+            warn!("⚠️  Failed to save peer statistics: {}", e);
+        }
+    }
+
+    /// Write what is otherwise saved periodically — peer statistics and new
+    /// known-invalid entries — before the node shuts down
+    pub async fn flush_state(&self) {
+        self.save_peer_stats().await;
+        self.save_known_invalid().await;
+    }
+
+    /// Every [`KNOWN_INVALID_FLUSH_INTERVAL`], write new entries of the
+    /// known-invalid cache to its file
+    async fn flush_known_invalid(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(KNOWN_INVALID_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.save_known_invalid().await;
+        }
+    }
+
+    async fn save_known_invalid(&self) {
+        if let Err(e) = self.known_invalid.lock().await.flush() {
+            warn!("⚠️  Failed to save known-invalid cache: {}", e);
+        }
+    }
+
+    /// Close the connection to a peer that stopped keeping up
+    async fn drop_peer(&self, node: &Node, reason: &str) {
+        self.pool.abort_reader(node).await;
+        self.pool.remove(node).await;
+        self.propagation.write().await.remove_peer(&node.addr());
+        info!("🔌 Disconnected from {}: {}", node.addr(), reason);
+    }
+
+    /// Queue `message` for every peer, disconnecting those whose queue
+    /// overflows. Returns how many peers took it.
+    async fn broadcast(&self, message: &NetworkMessage) -> usize {
+        let (sent, overflowed) = self.pool.broadcast(message).await;
+        for node in overflowed {
+            self.drop_peer(&node, "send queue full").await;
+        }
+        sent
+    }
+
+    async fn open_outbound(self: Arc<Self>, host: String, port: u16, manual: bool) -> Result<(), ChainError> {
+        let node = Node::new(host, port);
+        let addr = node.addr();
+        if self.is_banned(&node.host).await {
+            return Err(ChainError::NetworkError(format!("Peer {} is banned", node.host)));
+        }
+        let manual = manual || self.addnodes.read().await.nodes().contains(&node);
+        let slots = if manual {
+            self.limits.max_outbound
+        } else {
+            self.limits.max_outbound.saturating_sub(self.limits.reserved_outbound)
+        };
+        if self.pool.count(Direction::Outbound).await >= slots {
+            return Err(ChainError::NetworkError(format!(
+                "No free outbound slot for {} ({} of {} in use)",
+                addr,
+                self.pool.count(Direction::Outbound).await,
+                self.limits.max_outbound
+            )));
+        }
+        info!("🔗 Connecting to peer: {}", addr);
+
+        let stream = match &self.proxy {
+            Some(proxy) => crate::socks::connect(proxy, &node.host, node.port).await?,
+            None if node.is_onion() => {
+                return Err(ChainError::NetworkError(format!(
+                    "Onion peer {} requires network.proxy to be set",
+                    addr
+                )))
+            }
+            None => TcpStream::connect(&addr)
+                .await
+                .map_err(|e| ChainError::NetworkError(format!("Failed to connect: {}", e)))?,
+        };
+
+        self.register_connection(node, stream, Direction::Outbound).await;
+
+        Ok(())
+    }
+
+    /// Pool a freshly established connection, start its handler and send our
+    /// handshake.
+    async fn register_connection(self: Arc<Self>, node: Node, stream: TcpStream, direction: Direction) {
+        let (reader, writer) = stream.into_split();
+        let counters = match self.peer_stats.lock() {
+            Ok(mut stats) => stats.connect(&node.host),
+            Err(_) => Arc::new(ConnectionCounters::default()),
+        };
+        self.pool.add(&node, writer, direction, counters.clone()).await;
+
+        let self_clone = self.clone();
+        let handler_node = node.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = self_clone.handle_connection(&handler_node, reader, &counters).await {
+                error!("❌ Connection error with {}: {}", handler_node.addr(), e);
+            }
+            self_clone.pool.remove(&handler_node).await;
+            self_clone.propagation.write().await.remove_peer(&handler_node.addr());
+        });
+        self.pool.set_reader_task(&node, task.abort_handle()).await;
+
+        let handshake = self.local_handshake().await;
+        if let Err(e) = self.send_message(&node, &handshake).await {
+            error!("❌ Failed to send handshake to {}: {}", node.addr(), e);
+        }
+    }
+
+    /// Magic of our network, from the genesis block
+    async fn network_magic(&self) -> [u8; MAGIC_LEN] {
+        network_magic(&self.blockchain.read().await.chain_id())
+    }
+
+    async fn local_handshake(&self) -> NetworkMessage {
+        let chain = self.blockchain.read().await;
+        NetworkMessage::Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            best_height: chain.blocks.last().map_or(0, |b| b.header.height),
+        }
+    }
+
+    /// Decode one frame; `None` for a message type we do not know, sent by
+    /// a newer peer
+    async fn decode_message(&self, node: &Node, data: &[u8]) -> Result<Option<NetworkMessage>, ChainError> {
+        match bincode::deserialize(data) {
+            Ok(message) => Ok(Some(message)),
+            Err(e) => {
+                // Frames are length-prefixed, so unknown ones can be skipped
+                let newer = self
+                    .pool
+                    .capabilities(node)
+                    .await
+                    .is_some_and(|c| c.peer_version > PROTOCOL_VERSION);
+                if newer {
+                    return Ok(None);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn handle_connection(
+        &self,
+        node: &Node,
+        mut reader: OwnedReadHalf,
+        counters: &ConnectionCounters,
+    ) -> Result<(), ChainError> {
+        loop {
+            let mut len_bytes = [0u8; 4];
+            reader.read_exact(&mut len_bytes).await?;
+            let len = u32::from_be_bytes(len_bytes) as usize;
+
+            if len > MAX_MESSAGE_SIZE {
+                return Err(ChainError::NetworkError("Message too large".to_string()));
+            }
+
+            let mut buffer = vec![0u8; len];
+            reader.read_exact(&mut buffer).await?;
+            counters.received(len + 4);
+
+            let mut message = match self.decode_message(node, &buffer).await? {
+                Some(message) => message,
+                None => continue,
+            };
+            if let NetworkMessage::Compressed(payload) = message {
+                let data = compression::decompress(&payload, MAX_MESSAGE_SIZE)?;
+                message = match self.decode_message(node, &data).await? {
+                    Some(NetworkMessage::Compressed(_)) => {
+                        return Err(ChainError::NetworkError("Nested compressed message".to_string()));
+                    }
+                    Some(message) => message,
+                    None => continue,
+                };
+            }
+
+            if self.is_banned(&node.host).await {
+                return Err(ChainError::NetworkError(format!("Peer {} is banned", node.host)));
+            }
+
+            match message {
+                NetworkMessage::Handshake { protocol_version, best_height } => {
+                    if protocol_version < MIN_PEER_PROTOCOL_VERSION {
+                        return Err(ChainError::NetworkError(format!(
+                            "Unsupported protocol version {} (minimum {})",
+                            protocol_version, MIN_PEER_PROTOCOL_VERSION
+                        )));
+                    }
+                    let capabilities = PeerCapabilities::negotiate(protocol_version);
+                    self.pool.set_capabilities(node, capabilities).await;
+                    if capabilities.protocol_version >= CAPABILITIES_VERSION {
+                        let message = NetworkMessage::Capabilities { features: features::LOCAL };
+                        self.send_message(node, &message).await?;
+                    }
+                    if capabilities.protocol_version >= CHAIN_WORK_VERSION {
+                        let tip = self.local_chain_tip().await;
+                        self.send_message(node, &tip).await?;
+                    }
+                    if self.pool.direction(node).await == Some(Direction::Outbound) {
+                        self.discovery.write().await.addresses_mut().mark_good(node, unix_now());
+                    }
+                    if self.synchronizer.register_peer(node.clone(), best_height).await.is_err() {
+                        let _ = self.synchronizer.update_peer_height(&node.addr(), best_height).await;
+                    }
+                    // Mempool sync: ask the peer what it has pending so a
+                    // restarted node converges with the network's pending set.
+                    // With a relay filter that waits until the peer's
+                    // capabilities say whether it can load it first.
+                    let filtered = self.relay_filter.read().await.is_some();
+                    if !filtered || capabilities.protocol_version < CAPABILITIES_VERSION {
+                        self.send_message(node, &NetworkMessage::GetMempool).await?;
+                    }
+                    self.relay_pending(node).await;
+
+                    // Peers announcing their chain work are followed up once
+                    // their ChainTip arrives
+                    let local_height = self.local_height().await;
+                    if capabilities.protocol_version < CHAIN_WORK_VERSION && best_height > local_height {
+                        self.request_headers(node).await?;
+                    }
+                }
+                NetworkMessage::GetHeaders { locator, stop_hash } => {
+                    let headers = {
+                        let chain = self.blockchain.read().await;
+                        chain.headers_after_locator(&locator, stop_hash, MAX_HEADERS_PER_REQUEST)
+                    };
+                    self.send_message(node, &NetworkMessage::BlockHeaders(headers)).await?;
+                }
+                NetworkMessage::BlockHeaders(headers) => {
+                    let negotiation = {
+                        let chain = self.blockchain.read().await;
+                        negotiate_headers(&chain, &headers)
+                    };
+                    let local_work = self.blockchain.read().await.total_work();
+                    match negotiation {
+                        Ok(negotiation) if !negotiation.missing.is_empty() && negotiation.chain_work <= local_work => {
+                            info!(
+                                "🔀 Ignoring branch from {} at height {}: not more work than ours",
+                                node.addr(),
+                                negotiation.fork_height
+                            );
+                        }
+                        Ok(negotiation) => {
+                            if !negotiation.missing.is_empty() {
+                                info!(
+                                    "🔀 Fork point with {} at height {}, fetching {} block(s)",
+                                    node.addr(),
+                                    negotiation.fork_height,
+                                    negotiation.missing.len()
+                                );
+                            }
+                            let missing = (negotiation.fork_height + 1..)
+                                .zip(negotiation.missing)
+                                .collect();
+                            let assignments = self.synchronizer.schedule_downloads(missing, node).await;
+                            self.request_blocks(assignments).await;
+                        }
+                        Err(e) => {
+                            warn!("⚠️  Bad headers from {}: {}", node.addr(), e);
+                            self.penalize(node, "sent headers that do not connect").await;
+                        }
+                    }
+                }
+                NetworkMessage::Block(block) => {
+                    if self.synchronizer.block_arrived((*block).clone()).await {
+                        self.apply_downloaded_blocks().await;
+                    } else {
+                        self.accept_block(node, *block).await?;
+                    }
+                }
+                NetworkMessage::NewBlock(block) => {
+                    self.record_relay(node, block.hash(), InventoryKind::Block).await;
+                    self.accept_block(node, *block).await?;
+                }
+                NetworkMessage::NewHeader(header) => {
+                    let height = header.height;
+                    let offered = {
+                        let chain = self.blockchain.read().await;
+                        self.header_relay
+                            .lock()
+                            .await
+                            .offer(&chain, *header, &node.addr(), Instant::now())
+                    };
+                    match offered {
+                        Ok(true) => info!("📨 Header {} from {}, body to follow", height, node.addr()),
+                        Ok(false) => {}
+                        Err(e) => {
+                            warn!("⚠️  Rejected header from {}: {}", node.addr(), e);
+                            self.penalize(node, "sent an invalid header").await;
+                        }
+                    }
+                }
+                NetworkMessage::GetBlockHeaders { after_height } => {
+                    let chain = self.blockchain.read().await;
+                    let headers = chain
+                        .blocks
+                        .iter()
+                        .filter(|b| b.header.height > after_height)
+                        .map(|b| b.header.clone())
+                        .collect();
+                    let response = NetworkMessage::BlockHeaders(headers);
+                    self.send_message(node, &response).await?;
+                }
+                NetworkMessage::GetBlock(hash) => {
+                    let chain = self.blockchain.read().await;
+                    if let Some(block) = chain.blocks.iter().find(|b| b.hash() == hash) {
+                        let response = NetworkMessage::Block(Box::new(block.clone()));
+                        self.send_message(node, &response).await?;
+                    }
+                }
+                NetworkMessage::GetPeers => {
+                    let peers = self.list_peers().await;
+                    let response = NetworkMessage::Peers(peers);
+                    self.send_message(node, &response).await?;
+                }
+                NetworkMessage::Peers(peers) => {
+                    for _peer in peers {
+                        // self.clone().connect_peer(peer.host, peer.port).await?;
+                    }
+                }
+                NetworkMessage::GetAddr { listen_port } => {
+                    // The port comes with the IP we see the peer at; it is
+                    // only an unverified claim until someone connects to it
+                    let inbound = self.pool.direction(node).await == Some(Direction::Inbound);
+                    if let Some(port) = listen_port.filter(|_| inbound) {
+                        let advertised = PeerAddress { node: Node::new(node.host.clone(), port), last_seen: unix_now() };
+                        self.discovery.write().await.addresses_mut().add(&[advertised], node, unix_now());
+                    }
+                    // Answering once per connection keeps peers from
+                    // scraping the whole table
+                    if self.pool.take_getaddr(node).await {
+                        let addresses = self
+                            .discovery
+                            .read()
+                            .await
+                            .addresses()
+                            .sample(MAX_ADDR_PER_MESSAGE)
+                            .into_iter()
+                            .filter(|address| address.node.host != node.host)
+                            .collect();
+                        self.send_message(node, &NetworkMessage::Addr(addresses)).await?;
+                    }
+                }
+                NetworkMessage::Addr(addresses) => {
+                    if addresses.len() > MAX_ADDR_PER_MESSAGE {
+                        self.penalize(node, "sent too many addresses").await;
+                        continue;
+                    }
+                    let addresses: Vec<PeerAddress> = {
+                        let banlist = self.banlist.read().await;
+                        addresses.into_iter().filter(|a| !banlist.is_banned(&a.node.host)).collect()
+                    };
+                    let added = self.discovery.write().await.addresses_mut().add(&addresses, node, unix_now());
+                    if added > 0 {
+                        info!("📒 Learned {} peer address(es) from {}", added, node.addr());
+                    }
+                }
+                NetworkMessage::GetMempool => {
+                    let mut pending = self.blockchain.read().await.mempool.get_all_transactions();
+                    let mut hashes = Vec::with_capacity(pending.len());
+                    for tx in pending.drain(..) {
+                        if self.pool.wants(node, &tx).await {
+                            hashes.push(tx.hash());
+                        }
+                    }
+                    self.send_message(node, &NetworkMessage::MempoolInventory(hashes)).await?;
+                }
+                NetworkMessage::MempoolInventory(hashes) => {
+                    let missing: Vec<Sha256Hash> = {
+                        let chain = self.blockchain.read().await;
+                        hashes
+                            .into_iter()
+                            .filter(|h| chain.mempool.get_transaction(h).is_none())
+                            .take(MAX_MEMPOOL_FETCH)
+                            .collect()
+                    };
+                    if !missing.is_empty() {
+                        self.send_message(node, &NetworkMessage::GetTransactions(missing)).await?;
+                    }
+                }
+                NetworkMessage::GetTransactions(hashes) => {
+                    let txs: Vec<Transaction> = {
+                        let chain = self.blockchain.read().await;
+                        hashes
+                            .iter()
+                            .take(MAX_MEMPOOL_FETCH)
+                            .filter_map(|h| chain.mempool.get_transaction(h).cloned())
+                            .collect()
+                    };
+                    if !txs.is_empty() {
+                        self.send_message(node, &NetworkMessage::Transactions(txs)).await?;
+                    }
+                }
+                NetworkMessage::Transactions(txs) => {
+                    let mut accepted = 0;
+                    for tx in txs.into_iter().take(MAX_MEMPOOL_FETCH) {
+                        if self.accept_transaction(node, tx).await.is_ok() {
+                            accepted += 1;
+                        }
+                    }
+                    if accepted > 0 {
+                        info!("📥 Synced {} mempool transaction(s) from {}", accepted, node.addr());
+                    }
+                }
+                NetworkMessage::Capabilities { features: peer_features } => {
+                    if let Some(mut capabilities) = self.pool.capabilities(node).await {
+                        capabilities.features = peer_features & features::LOCAL;
+                        self.pool.set_capabilities(node, capabilities).await;
+                    }
+                    let magic = NetworkMessage::NetworkMagic(self.network_magic().await);
+                    if self.pool.capabilities(node).await.is_some_and(|c| c.supports(&magic)) {
+                        self.send_message(node, &magic).await?;
+                    }
+                    // Mempool sync deferred at the handshake, filtered if
+                    // the peer can
+                    let filter = self.relay_filter.read().await.clone();
+                    if let Some(filter) = filter {
+                        let load = NetworkMessage::FilterLoad(filter);
+                        if self.pool.capabilities(node).await.is_some_and(|c| c.supports(&load)) {
+                            self.send_message(node, &load).await?;
+                        }
+                        self.send_message(node, &NetworkMessage::GetMempool).await?;
+                    }
+                    // Ask outbound peers for addresses, telling them where
+                    // we listen unless we hide behind a proxy
+                    let get_addr = NetworkMessage::GetAddr {
+                        listen_port: Some(self.listen_port.load(Ordering::Relaxed))
+                            .filter(|port| *port != 0 && self.proxy.is_none()),
+                    };
+                    let outbound = self.pool.direction(node).await == Some(Direction::Outbound);
+                    if outbound && self.pool.capabilities(node).await.is_some_and(|c| c.supports(&get_addr)) {
+                        self.send_message(node, &get_addr).await?;
+                    }
+                    let checkpoint = self.latest_checkpoint.read().await.clone();
+                    if let Some(checkpoint) = checkpoint {
+                        let message = NetworkMessage::Checkpoint(Box::new(checkpoint));
+                        if self.pool.capabilities(node).await.is_some_and(|c| c.supports(&message)) {
+                            self.send_message(node, &message).await?;
+                        }
+                    }
+                }
+                NetworkMessage::ChainTip { height, chain_work } => {
+                    let _ = self
+                        .synchronizer
+                        .update_peer_chain_work(&node.addr(), height, chain_work)
+                        .await;
+                    if chain_work > self.blockchain.read().await.total_work() {
+                        self.request_headers(node).await?;
+                    }
+                }
+                NetworkMessage::Checkpoint(signed) => {
+                    if self.checkpoint_authority.is_some() {
+                        match self.submit_checkpoint(*signed).await {
+                            Ok(_) => {}
+                            Err(ChainError::CryptoError(e)) => {
+                                warn!("⚠️  Bad checkpoint from {}: {}", node.addr(), e);
+                                self.penalize(node, "sent an invalid checkpoint").await;
+                            }
+                            Err(e) => warn!("⚠️  Checkpoint from {} not adopted: {}", node.addr(), e),
+                        }
+                    }
+                }
+                NetworkMessage::NewTransaction(tx) => {
+                    self.record_relay(node, tx.hash(), InventoryKind::Transaction).await;
+                    if let Err(e) = self.accept_transaction(node, *tx).await {
+                        warn!("⚠️  Rejected transaction from {}: {}", node.addr(), e);
+                    }
+                }
+                NetworkMessage::TracedTransaction { tx, trace } => {
+                    self.record_relay(node, tx.hash(), InventoryKind::Transaction).await;
+                    if let Err(e) = self.accept_traced_transaction(node, *tx, trace).await {
+                        warn!("⚠️  Rejected transaction from {}: {}", node.addr(), e);
+                    }
+                }
+                NetworkMessage::Ping { nonce } => {
+                    self.send_message(node, &NetworkMessage::Pong { nonce }).await?;
+                }
+                NetworkMessage::Pong { nonce } => {
+                    if let Some(latency) = self.pool.record_pong(node, nonce).await {
+                        let _ = self.synchronizer.record_latency(&node.addr(), latency).await;
+                    }
+                }
+                NetworkMessage::FilterLoad(filter) => {
+                    if !filter.is_within_limits() {
+                        self.penalize(node, "sent an oversized bloom filter").await;
+                        continue;
+                    }
+                    self.pool.set_relay_filter(node, Some(filter)).await;
+                }
+                NetworkMessage::FilterAdd(element) => {
+                    if element.len() > MAX_ELEMENT_BYTES || !self.pool.add_to_relay_filter(node, &element).await {
+                        self.penalize(node, "sent a bad bloom filter update").await;
+                    }
+                }
+                NetworkMessage::FilterClear => {
+                    self.pool.set_relay_filter(node, None).await;
+                }
+                NetworkMessage::NetworkMagic(magic) => {
+                    let local = self.network_magic().await;
+                    if magic != local {
+                        return Err(ChainError::NetworkError(format!(
+                            "Peer is on another network (magic {}, ours {})",
+                            hex::encode(magic),
+                            hex::encode(local)
+                        )));
+                    }
+                }
+                // Unwrapped above
+                NetworkMessage::Compressed(_) => {}
+            }
+        }
+    }
+
+    async fn record_relay(&self, node: &Node, hash: Sha256Hash, kind: InventoryKind) {
+        self.propagation
+            .write()
+            .await
+            .record_relay(hash, kind, &node.addr(), Instant::now());
+    }
+
+    async fn local_chain_tip(&self) -> NetworkMessage {
+        let chain = self.blockchain.read().await;
+        NetworkMessage::ChainTip {
+            height: chain.blocks.last().map_or(0, |b| b.header.height),
+            chain_work: chain.total_work(),
+        }
+    }
+
+    async fn local_height(&self) -> u64 {
+        let chain = self.blockchain.read().await;
+        chain.blocks.last().map_or(0, |b| b.header.height)
+    }
+
+    /// Send our block locator so the peer can answer with the headers after
+    /// our common ancestor.
+    async fn request_headers(&self, node: &Node) -> Result<(), ChainError> {
+        let locator = self.blockchain.read().await.block_locator();
+        let message = NetworkMessage::GetHeaders {
+            locator,
+            stop_hash: None,
+        };
+        self.send_message(node, &message).await
+    }
+
+    /// Send `GetBlock` requests for each peer's share of a download.
+    async fn request_blocks(&self, assignments: Vec<(Node, Vec<Sha256Hash>)>) {
+        for (peer, hashes) in assignments {
+            for hash in hashes {
+                if let Err(e) = self.send_message(&peer, &NetworkMessage::GetBlock(hash)).await {
+                    warn!("⚠️  Failed to request block from {}: {}", peer.addr(), e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Apply buffered downloads that continue our tip, in height order. A
+    /// download that forks below our tip is a competing branch: once all of
+    /// it has arrived it replaces our blocks if it carries more work.
+    ///
+    /// Blocks continuing the tip go through the [`pipeline`], whose header
+    /// and signature stages run ahead of the block being applied, and the
+    /// chain lock is only taken per block. Blocks that arrive meanwhile are
+    /// picked up once the batch is through.
+    async fn apply_downloaded_blocks(&self) {
+        let _applying = self.applying_downloads.lock().await;
+        loop {
+            let (next_height, pow, domain) = {
+                let chain = self.blockchain.read().await;
+                let next_height = chain.next_height();
+                (next_height, chain.consensus.pow, chain.signing_domain(next_height))
+            };
+            if let Some(start) = self.synchronizer.first_pending_height().await {
+                if start < next_height {
+                    self.apply_downloaded_branch(start).await;
+                    return;
+                }
+            }
+            let blocks = self.synchronizer.take_ready_blocks(next_height).await;
+            if blocks.is_empty() {
+                return;
+            }
+            let mut checked = pipeline::validate_blocks(blocks, pow, domain);
+            while let Some(CheckedBlock { block, result }) = checked.recv().await {
+                let height = block.header.height;
+                let hash = block.hash();
+                let result = match result {
+                    Ok(()) => {
+                        let mut chain = self.blockchain.write().await;
+                        // Already applied from a relay while this one was in the pipeline
+                        if chain.blocks.last().is_some_and(|tip| tip.header.height >= height) {
+                            continue;
+                        }
+                        chain.apply_block(block)
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("⚠️  Downloaded block {} failed validation: {}", height, e);
+                    self.record_rejected_block(hash, height, None, &e.to_string()).await;
+                    self.synchronizer.reset_downloads().await;
+                    return;
+                }
+                info!("📦 Applied block {}", height);
+                self.release_held_block().await;
+            }
+        }
+    }
+
+    /// Apply and relay the block mined on a relayed header once that
+    /// header's body has become our tip
+    async fn release_held_block(&self) {
+        let released = {
+            let chain = self.blockchain.read().await;
+            self.header_relay.lock().await.body_applied(&chain)
+        };
+        let Some(block) = released else {
+            return;
+        };
+        let height = block.header.height;
+        let result = self.blockchain.write().await.apply_block(block.clone());
+        match result {
+            Ok(()) => {
+                info!("⛏️  Applied block {} mined on a relayed header", height);
+                self.broadcast_block(&block).await;
+            }
+            Err(e) => warn!("⚠️  Block {} mined on a relayed header is invalid: {}", height, e),
+        }
+    }
+
+    /// Reorganize onto the downloaded branch forking at `start` once all of
+    /// it has arrived
+    async fn apply_downloaded_branch(&self, start: u64) {
+        let mut chain = self.blockchain.write().await;
+        if self.synchronizer.blocks_in_flight().await > 0 {
+            return;
+        }
+        let branch = self.synchronizer.take_ready_blocks(start).await;
+        let first_hash = branch.first().map_or([0u8; 32], |b| b.hash());
+        match chain.reorganize(branch) {
+            Ok(true) => info!(
+                "🔀 Reorganized to a chain with more work from height {}, new tip {}",
+                start,
+                chain.blocks.len() - 1
+            ),
+            Ok(false) => info!("🔀 Downloaded branch at height {} has no more work than ours", start),
+            Err(e) => {
+                warn!("⚠️  Downloaded branch at height {} failed validation: {}", start, e);
+                let reason = format!("downloaded branch failed validation: {}", e);
+                self.record_rejected_block(first_hash, start, None, &reason).await;
+                self.synchronizer.reset_downloads().await;
+            }
+        }
+    }
+
+    /// Periodically hand block requests that a peer failed to answer in time
+    /// to other peers.
+    async fn watch_stalled_downloads(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(BLOCK_REQUEST_TIMEOUT / 4);
+        loop {
+            interval.tick().await;
+            let assignments = self.synchronizer.reassign_stalled(BLOCK_REQUEST_TIMEOUT).await;
+            if !assignments.is_empty() {
+                info!("⏱️  Re-assigning stalled block downloads to {} peer(s)", assignments.len());
+                self.request_blocks(assignments).await;
+            }
+        }
+    }
+
+    /// Apply a block received from a peer if it extends our tip. A block that
+    /// is ahead of us means we are missing a segment, so renegotiate headers.
+    async fn accept_block(&self, node: &Node, block: Block) -> Result<(), ChainError> {
+        let hash = block.hash();
+        if let Some(known) = self.known_invalid.lock().await.check(&hash) {
+            warn!("⚠️  Refused known invalid block {} from {}: {}", block.header.height, node.addr(), known.reason);
+            self.penalize(node, "sent invalid blocks").await;
+            return Ok(());
+        }
+        let (tip_height, tip_hash) = {
+            let chain = self.blockchain.read().await;
+            match chain.blocks.last() {
+                Some(tip) => (tip.header.height, tip.hash()),
+                None => return Ok(()),
+            }
+        };
+
+        if block.header.height <= tip_height {
+            // A block we do not have at a height we do may belong to a
+            // heavier branch; the header negotiation compares the work.
+            let known = self
+                .blockchain
+                .read()
+                .await
+                .blocks
+                .get(block.header.height as usize)
+                .is_some_and(|b| b.hash() == block.hash());
+            if known {
+                return Ok(());
+            }
+            return self.request_headers(node).await;
+        }
+        if block.header.previous_hash != tip_hash {
+            return self.request_headers(node).await;
+        }
+
+        let height = block.header.height;
+        let (result, lasting) = {
+            let mut chain = self.blockchain.write().await;
+            // A block that no longer extends the tip, or is stamped too far
+            // ahead of our clock, may still be valid later
+            let extends_tip = chain.blocks.last().is_some_and(|tip| tip.hash() == block.header.previous_hash);
+            let ahead = block.header.timestamp > chain.clock.now_millis().saturating_add(MAX_FUTURE_BLOCK_TIME_MS);
+            (chain.apply_block(block), extends_tip && !ahead)
+        };
+        match result {
+            Ok(()) => {
+                let _ = self.synchronizer.record_block_received(&node.addr()).await;
+                if let Ok(mut stats) = self.peer_stats.lock() {
+                    stats.record_block(&node.host);
+                }
+                info!("📦 Applied block {} from {}", height, node.addr());
+                self.release_held_block().await;
+            }
+            Err(e) => {
+                warn!("⚠️  Rejected block {} from {}: {}", height, node.addr(), e);
+                self.header_relay.lock().await.body_rejected(&hash);
+                self.record_rejected_block(hash, height, Some(node), &e.to_string()).await;
+                if lasting && rejects_item(&e) {
+                    self.known_invalid.lock().await.insert(hash, InvalidKind::Block, &e.to_string(), unix_now());
+                }
+                self.penalize(node, "sent invalid blocks").await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate a transaction relayed by `node` (including its signature)
+    /// and add it to the local mempool. Transactions spending outputs of
+    /// pending transactions are validated as part of their package. One that
+    /// fails checks no later state can fix is remembered as invalid, and
+    /// relaying it again counts against the peer.
+    async fn accept_transaction(&self, node: &Node, tx: Transaction) -> Result<(), ChainError> {
+        let hash = tx.hash();
+        if let Some(known) = self.known_invalid.lock().await.check(&hash) {
+            self.penalize(node, "relayed invalid transactions").await;
+            return Err(ChainError::InvalidTransaction(format!("Known invalid: {}", known.reason)));
+        }
+        let mut chain = self.blockchain.write().await;
+        if chain.mempool.get_transaction(&hash).is_some() {
+            return Ok(());
+        }
+        let domain = chain.signing_domain(chain.next_height());
+        let result = chain.accept_transaction(tx.clone());
+        drop(chain);
+        if result.is_err() {
+            let lasting = match &tx {
+                Transaction::Coinbase(_) => Err(ChainError::InvalidTransaction("Coinbase transactions cannot be relayed".to_string())),
+                tx => tx.validate_stateless(&domain),
+            };
+            if let Err(e) = lasting {
+                self.known_invalid.lock().await.insert(hash, InvalidKind::Transaction, &e.to_string(), unix_now());
+            }
+        }
+        result
+    }
+
+    /// Accept a transaction relayed with a trace tag. With tracing off the
+    /// tag is dropped; with it on the hop is reported, and a transaction new
+    /// here is forwarded to the other tracing peers with the next hop's tag.
+    async fn accept_traced_transaction(&self, node: &Node, tx: Transaction, trace: TraceTag) -> Result<(), ChainError> {
+        let Some(name) = &self.trace_name else {
+            return self.accept_transaction(node, tx).await;
+        };
+        let received_ms = self.now_millis().await;
+        let hash = tx.hash();
+        let duplicate = self.blockchain.read().await.mempool.get_transaction(&hash).is_some();
+        self.accept_transaction(node, tx.clone()).await?;
+        self.record_hop(HopReport::new(&trace, hash, name, &node.addr(), received_ms, duplicate))
+            .await;
+        if !duplicate {
+            if let Some(next) = trace.next_hop(self.now_millis().await) {
+                self.relay_traced(&tx, next, Some(node)).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a hop report and POST it to the aggregator, if one is set
+    async fn record_hop(&self, report: HopReport) {
+        self.traces.write().await.record(report.clone());
+        let Some(aggregator) = self.trace_aggregator.clone() else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        tokio::spawn(async move {
+            match serde_json::to_string(&report) {
+                Ok(body) => {
+                    if let Err(e) = crate::telemetry::post_json(&aggregator, &body, proxy.as_deref()).await {
+                        warn!("⚠️  Failed to report trace hop to {}: {}", aggregator, e);
+                    }
+                }
+                Err(e) => warn!("⚠️  Failed to encode trace hop: {}", e),
+            }
+        });
+    }
+
+    /// Send `tx` tagged with `trace` to every peer but `except` that
+    /// advertises [`features::TX_TRACE`]. The origin (`except` unset) sends
+    /// it untagged to the other peers. Returns how many peers it was sent to.
+    async fn relay_traced(&self, tx: &Transaction, trace: TraceTag, except: Option<&Node>) -> usize {
+        let traced = NetworkMessage::TracedTransaction {
+            tx: Box::new(tx.clone()),
+            trace,
+        };
+        let plain = NetworkMessage::NewTransaction(Box::new(tx.clone()));
+        let mut sent = 0;
+        for peer in self.pool.list_peers().await {
+            if except == Some(&peer) || !self.pool.wants(&peer, tx).await {
+                continue;
+            }
+            let message = if self.pool.capabilities(&peer).await.is_some_and(|c| c.supports(&traced)) {
+                &traced
+            } else if except.is_none() {
+                &plain
+            } else {
+                continue;
+            };
+            if self.send_message(&peer, message).await.is_ok() {
+                sent += 1;
+            }
+        }
+        sent
+    }
+
+    async fn now_millis(&self) -> u64 {
+        self.blockchain.read().await.clock.now_millis()
+    }
+
+    async fn send_message(&self, node: &Node, message: &NetworkMessage) -> Result<(), ChainError> {
+        let (queue, capabilities) = self
+            .pool
+            .connections
+            .read()
+            .await
+            .get(&node.addr())
+            .map(|connection| (connection.queue.clone(), connection.capabilities))
+            .ok_or_else(|| ChainError::NetworkError("Connection not in pool".to_string()))?;
+        let version = capabilities.map_or(MIN_PEER_PROTOCOL_VERSION, |c| c.protocol_version);
+        if version < message.min_protocol_version() {
+            return Err(ChainError::NetworkError(format!(
+                "{} speaks protocol {}, message needs {}",
+                node.addr(),
+                version,
+                message.min_protocol_version()
+            )));
+        }
+        let features = capabilities.map_or(features::LEGACY, |c| c.features);
+        if features & message.required_features() != message.required_features() {
+            return Err(ChainError::NetworkError(format!(
+                "{} does not support this message",
+                node.addr()
+            )));
+        }
+
+        let mut data = bincode::serialize(message)?;
+        if features & features::ZSTD != 0 {
+            if let Some(frame) = self.pool.compress_frame(&data) {
+                data = frame;
+            }
+        }
+        let handshake = matches!(message, NetworkMessage::Handshake { .. });
+        if !handshake && !self.pool.faults.deliver().await {
+            return Ok(());
+        }
+
+        match queue.push(Arc::new(data), message.send_priority()) {
+            Ok(()) => Ok(()),
+            Err(SendError::Overflow) => {
+                self.drop_peer(node, "send queue full").await;
+                Err(ChainError::NetworkError(format!("Send queue of {} overflowed", node.addr())))
+            }
+            Err(SendError::Dropped) => Err(ChainError::NetworkError(format!("Send queue of {} is full", node.addr()))),
+            Err(SendError::Closed) => Err(ChainError::NetworkError(format!("Connection to {} is closed", node.addr()))),
+        }
+    }
+
+    /// Announce a local transaction to every peer. Returns how many peers
+    /// it was sent to; with none, it is queued and announced to the next
+    /// peer that connects.
+    pub async fn broadcast_transaction(&self, tx: &crate::transaction::Transaction) -> usize {
+        self.propagation.write().await.record_local(tx.hash(), Instant::now());
+        let message = NetworkMessage::NewTransaction(Box::new(tx.clone()));
+        let sent = self.broadcast(&message).await;
+        if sent == 0 {
+            self.queue_for_relay(tx).await;
+        }
+        sent
+    }
+
+    /// Announce a local transaction like
+    /// [`broadcast_transaction`](Self::broadcast_transaction), tagged for
+    /// tracing to the peers that take tags. Returns the peer count and the
+    /// trace id; fails while tracing is off.
+    pub async fn broadcast_traced_transaction(&self, tx: &Transaction) -> Result<(usize, u64), ChainError> {
+        if !self.tracing_enabled() {
+            return Err(ChainError::NetworkError("Transaction tracing is disabled".to_string()));
+        }
+        self.propagation.write().await.record_local(tx.hash(), Instant::now());
+        let trace = TraceTag::new(rand::random(), self.now_millis().await);
+        let sent = self.relay_traced(tx, trace, None).await;
+        if sent == 0 {
+            self.queue_for_relay(tx).await;
+        }
+        Ok((sent, trace.id))
+    }
+
+    async fn queue_for_relay(&self, tx: &Transaction) {
+        let mut pending = self.pending_relay.lock().await;
+        if !pending.iter().any(|queued| queued.hash() == tx.hash()) {
+            if pending.len() >= MAX_PENDING_RELAY {
+                pending.pop_front();
+            }
+            pending.push_back(tx.clone());
+        }
+    }
+
+    /// Number of transactions waiting for a peer to relay them to
+    pub async fn pending_relay_count(&self) -> usize {
+        self.pending_relay.lock().await.len()
+    }
+
+    /// Announce queued transactions to a peer that just completed its
+    /// handshake. Those that were mined or evicted meanwhile are dropped;
+    /// those the peer could not take stay queued.
+    async fn relay_pending(&self, node: &Node) {
+        let queued: Vec<Transaction> = self.pending_relay.lock().await.drain(..).collect();
+        if queued.is_empty() {
+            return;
+        }
+        let queued: Vec<Transaction> = {
+            let chain = self.blockchain.read().await;
+            queued
+                .into_iter()
+                .filter(|tx| chain.mempool.get_transaction(&tx.hash()).is_some())
+                .collect()
+        };
+        let total = queued.len();
+        let mut unsent = Vec::new();
+        for tx in queued {
+            let message = NetworkMessage::NewTransaction(Box::new(tx.clone()));
+            if !self.pool.wants(node, &tx).await || self.send_message(node, &message).await.is_err() {
+                unsent.push(tx);
+            }
+        }
+        if unsent.len() < total {
+            info!(
+                "📤 Relayed {} queued transaction(s) to {}",
+                total - unsent.len(),
+                node.addr()
+            );
+        }
+        if !unsent.is_empty() {
+            let mut pending = self.pending_relay.lock().await;
+            for tx in unsent.into_iter().rev() {
+                pending.push_front(tx);
+            }
+            pending.truncate(MAX_PENDING_RELAY);
+        }
+    }
+
+    /// Announce a block to every peer: its header first to those advertising
+    /// [`features::HEADER_RELAY`], then the body to all
+    pub async fn broadcast_block(&self, block: &crate::blockchain::Block) {
+        self.propagation.write().await.record_local(block.hash(), Instant::now());
+        self.broadcast(&NetworkMessage::NewHeader(Box::new(block.header.clone()))).await;
+        let message = NetworkMessage::NewBlock(Box::new(block.clone()));
+        self.broadcast(&message).await;
+    }
+
+    /// Header relayed ahead of its body that the next block may be mined on
+    /// instead of our tip, see [`crate::headerrelay`]
+    pub async fn mining_parent(&self) -> Option<UnvalidatedHeader> {
+        let chain = self.blockchain.read().await;
+        self.header_relay.lock().await.mining_parent(&chain, Instant::now()).cloned()
+    }
+
+    /// Apply and announce a block mined locally. A block mined on the
+    /// relayed header is held until that header's body has been applied;
+    /// returns whether the block was applied now.
+    pub async fn submit_mined_block(&self, block: Block) -> Result<bool, ChainError> {
+        let mut chain = self.blockchain.write().await;
+        if chain.blocks.last().is_some_and(|tip| tip.hash() != block.header.previous_hash) {
+            self.header_relay.lock().await.hold(&chain, block, Instant::now())?;
+            return Ok(false);
+        }
+        chain.apply_block(block.clone())?;
+        drop(chain);
+        self.broadcast_block(&block).await;
+        Ok(true)
+    }
+
+    /// Ask every peer that supports it to relay only transactions matching
+    /// `filter`, e.g. one built with [`BloomFilter::for_addresses`] over the
+    /// wallet's addresses; `None` clears it. Peers that connect later get it
+    /// after their handshake. Returns how many peers took it.
+    pub async fn set_relay_filter(&self, filter: Option<BloomFilter>) -> usize {
+        *self.relay_filter.write().await = filter.clone();
+        let message = match filter {
+            Some(filter) => NetworkMessage::FilterLoad(filter),
+            None => NetworkMessage::FilterClear,
+        };
+        self.broadcast(&message).await
+    }
+
+    /// Add `element`, e.g. a new address or a txid to follow, to the relay
+    /// filter loaded into peers. Fails if no filter is set.
+    pub async fn add_to_relay_filter(&self, element: &[u8]) -> Result<usize, ChainError> {
+        if element.len() > MAX_ELEMENT_BYTES {
+            return Err(ChainError::NetworkError(format!(
+                "Filter elements are at most {} bytes",
+                MAX_ELEMENT_BYTES
+            )));
+        }
+        match self.relay_filter.write().await.as_mut() {
+            Some(filter) => filter.insert(element),
+            None => return Err(ChainError::NetworkError("No relay filter is set".to_string())),
+        }
+        Ok(self.broadcast(&NetworkMessage::FilterAdd(element.to_vec())).await)
+    }
+
+    pub async fn list_peers(&self) -> Vec<Node> {
+        self.pool.list_peers().await
+    }
+
+    /// Connected peers with their connection direction and relay statistics
+    pub async fn peer_details(&self) -> Vec<PeerDetails> {
+        let connections = self.pool.list_connections().await;
+        let tracker = self.propagation.read().await;
+        connections
+            .into_iter()
+            .map(|(node, direction, _, capabilities, latency, send_queue)| {
+                let propagation = tracker.peer_stats(&node.addr()).map(|stats| stats.report());
+                PeerDetails {
+                    node,
+                    direction,
+                    capabilities,
+                    ping_ms: latency.map(|latency| latency.as_millis() as u64),
+                    send_queue,
+                    propagation,
+                }
+            })
+            .collect()
+    }
+
+    /// Number of (inbound, outbound) connections
+    pub async fn connection_counts(&self) -> (usize, usize) {
+        (
+            self.pool.count(Direction::Inbound).await,
+            self.pool.count(Direction::Outbound).await,
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
+
+/// Whether `error` is about the block itself rather than our storage, a
+/// block we already have or one whose parent is missing
+fn rejects_item(error: &ChainError) -> bool {
+    !matches!(
+        error.root(),
+        ChainError::DatabaseError(_)
+            | ChainError::IoError(_)
+            | ChainError::NetworkError(_)
+            | ChainError::BlockAlreadyExists
+            | ChainError::OrphanBlock
+    )
+}
+
+/// A connected peer as reported by the API
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PeerDetails {
+    #[serde(flatten)]
+    pub node: Node,
+    pub direction: Direction,
+    /// `None` until the peer's handshake arrives
+    pub capabilities: Option<PeerCapabilities>,
+    /// Round trip of the last answered keepalive ping; `None` until one is
+    pub ping_ms: Option<u64>,
+    /// What is waiting to be written to the peer
+    pub send_queue: SendQueueStats,
+    /// `None` until the peer has relayed something
+    pub propagation: Option<PropagationReport>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub enum NetworkMessage {
+    GetBlockHeaders { after_height: u64 },
+    BlockHeaders(Vec<crate::blockchain::BlockHeader>),
+    GetBlock(crate::blockchain::Sha256Hash),
+    Block(Box<crate::blockchain::Block>),
+    NewBlock(Box<crate::blockchain::Block>),
+    NewTransaction(Box<crate::transaction::Transaction>),
+    GetPeers,
+    Peers(Vec<Node>),
+    /// First message on every connection, in both directions
+    Handshake { protocol_version: u32, best_height: u64 },
+    /// Request the hashes of the peer's pending transactions
+    GetMempool,
+    MempoolInventory(Vec<crate::blockchain::Sha256Hash>),
+    GetTransactions(Vec<crate::blockchain::Sha256Hash>),
+    Transactions(Vec<crate::transaction::Transaction>),
+    /// Request the headers after the most recent locator hash we share,
+    /// optionally stopping at `stop_hash`
+    GetHeaders {
+        locator: Vec<crate::blockchain::Sha256Hash>,
+        stop_hash: Option<crate::blockchain::Sha256Hash>,
+    },
+    /// Feature bits, sent right after the handshake to peers speaking
+    /// protocol 2 or later. New variants must be appended here so older
+    /// peers keep decoding the existing ones.
+    Capabilities { features: u64 },
+    /// Our tip and the cumulative work of our chain, sent after the
+    /// handshake to peers speaking protocol 3 or later
+    ChainTip { height: u64, chain_work: u128 },
+    /// Authority-signed checkpoint, only sent to peers advertising
+    /// [`features::CHECKPOINTS`]
+    Checkpoint(Box<SignedCheckpoint>),
+    /// Request known peer addresses, sent to outbound peers advertising
+    /// [`features::PEER_EXCHANGE`]. `listen_port` is where the sender
+    /// accepts connections, if anywhere.
+    GetAddr { listen_port: Option<u16> },
+    /// Known peer addresses, at most [`MAX_ADDR_PER_MESSAGE`]
+    Addr(Vec<PeerAddress>),
+    /// Keepalive probe, sent every [`PING_INTERVAL`] to peers advertising
+    /// [`features::KEEPALIVE`]
+    Ping { nonce: u64 },
+    /// Answer to a `Ping`, echoing its nonce
+    Pong { nonce: u64 },
+    /// Another message, bincode-encoded and zstd-compressed. Only sent to
+    /// peers advertising [`features::ZSTD`], for frames of at least
+    /// [`compression::MIN_COMPRESSED_SIZE`] bytes; never nested.
+    Compressed(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// Relay only transactions matching this filter to the sender, which
+    /// must advertise [`features::BLOOM`] to be answered
+    FilterLoad(BloomFilter),
+    /// Add an element to the filter loaded with `FilterLoad`
+    FilterAdd(#[serde(with = "serde_bytes")] Vec<u8>),
+    /// Drop the loaded filter and relay every transaction again
+    FilterClear,
+    /// Header of a new block, sent ahead of its `NewBlock` to peers
+    /// advertising [`features::HEADER_RELAY`]
+    NewHeader(Box<crate::blockchain::BlockHeader>),
+    /// A transaction traced for debugging, sent by nodes with tracing on to
+    /// peers advertising [`features::TX_TRACE`]; see [`crate::txtrace`]
+    TracedTransaction {
+        tx: Box<crate::transaction::Transaction>,
+        trace: TraceTag,
+    },
+    /// Magic of the sender's network, the start of its genesis hash; sent
+    /// after `Capabilities` to peers advertising [`features::NETWORK_MAGIC`].
+    /// A peer on another network is disconnected.
+    NetworkMagic([u8; MAGIC_LEN]),
+}
+
+impl NetworkMessage {
+    /// Lowest negotiated protocol version a peer must speak to be sent this
+    /// message
+    pub fn min_protocol_version(&self) -> u32 {
+        match self {
+            NetworkMessage::Capabilities { .. } => CAPABILITIES_VERSION,
+            NetworkMessage::ChainTip { .. } => CHAIN_WORK_VERSION,
+            NetworkMessage::Checkpoint(_) => CAPABILITIES_VERSION,
+            NetworkMessage::GetAddr { .. } | NetworkMessage::Addr(_) => CAPABILITIES_VERSION,
+            NetworkMessage::Ping { .. } | NetworkMessage::Pong { .. } => CAPABILITIES_VERSION,
+            NetworkMessage::Compressed(_) => CAPABILITIES_VERSION,
+            NetworkMessage::FilterLoad(_) | NetworkMessage::FilterAdd(_) | NetworkMessage::FilterClear => {
+                CAPABILITIES_VERSION
+            }
+            NetworkMessage::NewHeader(_) | NetworkMessage::NetworkMagic(_) => CAPABILITIES_VERSION,
+            NetworkMessage::NewTransaction(_)
+            | NetworkMessage::Transactions(_)
+            | NetworkMessage::TracedTransaction { .. } => CHAIN_ID_SIGNATURES_VERSION,
+            _ => MIN_PEER_PROTOCOL_VERSION,
+        }
+    }
+
+    /// Queue lane of this message: transaction relay waits behind
+    /// everything else
+    pub fn send_priority(&self) -> SendPriority {
+        match self {
+            NetworkMessage::NewTransaction(_)
+            | NetworkMessage::Transactions(_)
+            | NetworkMessage::TracedTransaction { .. }
+            | NetworkMessage::MempoolInventory(_) => SendPriority::Low,
+            _ => SendPriority::High,
+        }
+    }
+
+    /// Feature bits a peer must advertise to be sent this message
+    pub fn required_features(&self) -> u64 {
+        match self {
+            NetworkMessage::Checkpoint(_) => features::CHECKPOINTS,
+            NetworkMessage::GetAddr { .. } | NetworkMessage::Addr(_) => features::PEER_EXCHANGE,
+            NetworkMessage::Ping { .. } | NetworkMessage::Pong { .. } => features::KEEPALIVE,
+            NetworkMessage::Compressed(_) => features::ZSTD,
+            NetworkMessage::FilterLoad(_) | NetworkMessage::FilterAdd(_) | NetworkMessage::FilterClear => {
+                features::BLOOM
+            }
+            NetworkMessage::NewHeader(_) => features::HEADER_RELAY,
+            NetworkMessage::TracedTransaction { .. } => features::TX_TRACE,
+            NetworkMessage::NetworkMagic(_) => features::NETWORK_MAGIC,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+    use crate::geometry::Coord;
+    use crate::transaction::TransferTx;
+    use std::time::Duration;
+
+    async fn spawn_node(chain: Blockchain) -> (Arc<NetworkNode>, u16) {
+        let node = Arc::new(NetworkNode::new(Arc::new(RwLock::new(chain))));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(node.clone().serve(listener));
+        (node, port)
+    }
+
+    /// A signed transfer spending the genesis coinbase of `chain`
+    fn spend_genesis(keypair: &KeyPair, chain: &Blockchain) -> Transaction {
+        let coinbase_hash = chain.blocks[0].transactions[0].hash();
+        let mut transfer = TransferTx::new(
+            coinbase_hash,
+            [7u8; 32],
+            keypair.address(),
+            Coord::from_num(10),
+            Coord::from_num(0),
+            1,
+        );
+        let signature = keypair.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+        Transaction::Transfer(transfer)
+    }
+
+    #[tokio::test]
+    async fn test_mempool_sync_on_connect() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain_a = Blockchain::new(keypair.address(), 0).unwrap();
+            let chain_b = Blockchain::new(keypair.address(), 0).unwrap();
+
+            // Spend the genesis coinbase on node A only
+            let tx = spend_genesis(&keypair, &chain_a);
+
+            let (node_a, port_a) = spawn_node(chain_a).await;
+            node_a.blockchain.write().await.mempool.add_transaction(tx.clone()).unwrap();
+
+            let (node_b, _) = spawn_node(chain_b).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+
+            loop {
+                if node_b.blockchain.read().await.mempool.get_transaction(&tx.hash()).is_some() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("mempool was not synchronized");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_without_peers_is_queued_until_one_connects() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain_a = Blockchain::new(keypair.address(), 0).unwrap();
+            let chain_b = Blockchain::new(keypair.address(), 0).unwrap();
+            let tx = spend_genesis(&keypair, &chain_a);
+
+            let (node_a, _) = spawn_node(chain_a).await;
+            node_a.blockchain.write().await.mempool.add_transaction(tx.clone()).unwrap();
+            assert_eq!(node_a.broadcast_transaction(&tx).await, 0);
+            assert_eq!(node_a.broadcast_transaction(&tx).await, 0);
+            assert_eq!(node_a.pending_relay_count().await, 1);
+
+            let (node_b, port_b) = spawn_node(chain_b).await;
+            node_a.clone().connect_peer("127.0.0.1".to_string(), port_b).await.unwrap();
+
+            while node_a.pending_relay_count().await > 0
+                || node_b.blockchain.read().await.mempool.get_transaction(&tx.hash()).is_none()
+            {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(node_a.broadcast_transaction(&tx).await, 1);
+        })
+        .await
+        .expect("queued transaction was not relayed");
+    }
+
+    #[tokio::test]
+    async fn test_header_negotiation_catches_up_behind_peer() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain_b = Blockchain::new(keypair.address(), 0).unwrap();
+            let mut chain_a = chain_b.clone();
+            for height in 1..=3 {
+                let tip = chain_a.blocks.last().unwrap().hash();
+                let coinbase = Transaction::Coinbase(crate::transaction::CoinbaseTx {
+                    reward_area: Coord::from_num(1),
+                    beneficiary_address: keypair.address(),
+                    nonce: height,
+                    height,
+                    fees: Coord::from_num(0),
+                    extra_nonce: Vec::new(),
+                    outputs: Vec::new(),
+                });
+                let block = crate::miner::mine_block(Block::new(height, tip, 0, vec![coinbase])).unwrap();
+                chain_a.apply_block(block).unwrap();
+            }
+            let tip_a = chain_a.blocks.last().unwrap().hash();
+
+            let (_, port_a) = spawn_node(chain_a).await;
+            let (node_b, _) = spawn_node(chain_b).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+
+            loop {
+                if node_b.blockchain.read().await.blocks.last().unwrap().hash() == tip_a {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("node did not catch up with its peer");
+    }
+
+    fn mine_blocks(chain: &mut Blockchain, count: u64, difficulty: u32, tag: u8) {
+        for _ in 0..count {
+            let tip = chain.blocks.last().unwrap();
+            let height = tip.header.height + 1;
+            let coinbase = Transaction::Coinbase(crate::transaction::CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: [tag; 32],
+                nonce: height,
+                height,
+                fees: Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let block = Block::new(height, tip.hash(), difficulty, vec![coinbase]);
+            chain.apply_block(crate::miner::mine_block(block).unwrap()).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shorter_chain_with_more_work_wins() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let genesis = Blockchain::new([1u8; 32], 0).unwrap();
+            let mut heavy = genesis.clone();
+            mine_blocks(&mut heavy, 1, 3, 2);
+            let mut long = genesis;
+            mine_blocks(&mut long, 3, 0, 3);
+            assert!(heavy.total_work() > long.total_work());
+            let heavy_tip = heavy.blocks.last().unwrap().hash();
+
+            let (node_a, port_a) = spawn_node(heavy).await;
+            let (node_b, _) = spawn_node(long).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+
+            loop {
+                if node_b.blockchain.read().await.blocks.last().unwrap().hash() == heavy_tip {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(node_b.blockchain.read().await.blocks.len(), 2);
+            // The longer but lighter chain is never adopted
+            assert_eq!(node_a.blockchain.read().await.blocks.last().unwrap().hash(), heavy_tip);
+        })
+        .await
+        .expect("node did not switch to the chain with more work");
+    }
+
+    #[test]
+    fn test_peer_address_formats() {
+        let v4: Node = "10.0.0.1:8333".parse().unwrap();
+        assert_eq!(v4, Node::new("10.0.0.1".to_string(), 8333));
+        assert_eq!(v4.addr(), "10.0.0.1:8333");
+
+        let v6: Node = "[2001:DB8:0::1]:8333".parse().unwrap();
+        assert_eq!(v6.host, "2001:db8::1");
+        assert!(v6.is_ipv6());
+        assert_eq!(v6.addr(), "[2001:db8::1]:8333");
+        assert_eq!(v6.addr().parse::<Node>().unwrap(), v6);
+        assert!("2001:db8::1:8333".parse::<Node>().is_err());
+        assert!("[not-ip]:8333".parse::<Node>().is_err());
+
+        let name = "pg6mmjiyjmcrsslvykfwnntlaru7p5svn6y2ymmju6nubxndf4pscryd";
+        let onion: Node = format!("{}.ONION:9333", name.to_uppercase()).parse().unwrap();
+        assert!(onion.is_onion());
+        assert_eq!(onion.addr(), format!("{}.onion:9333", name));
+        assert!("short.onion:9333".parse::<Node>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_onion_peer_requires_proxy() {
+        let keypair = KeyPair::generate().unwrap();
+        let node = Arc::new(NetworkNode::new(Arc::new(RwLock::new(
+            Blockchain::new(keypair.address(), 0).unwrap(),
+        ))));
+        let onion = format!("{}.onion", "a".repeat(56));
+        let err = node.connect_peer(onion, 9333).await.unwrap_err();
+        assert!(err.to_string().contains("proxy"));
+    }
+
+    #[tokio::test]
+    async fn test_outbound_slots_reserved_for_addnodes() {
+        let keypair = KeyPair::generate().unwrap();
+        let (_a, port_a) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+        let (_b, port_b) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+        let (_c, port_c) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+        let limits = PeerLimits {
+            max_inbound: 8,
+            max_outbound: 2,
+            reserved_outbound: 1,
+        };
+        let node = Arc::new(
+            NetworkNode::new(Arc::new(RwLock::new(Blockchain::new(keypair.address(), 0).unwrap())))
+                .with_peer_limits(limits),
+        );
+        let localhost = "127.0.0.1".to_string();
+
+        node.clone().connect_peer(localhost.clone(), port_a).await.unwrap();
+        // The last slot is reserved for addnodes
+        assert!(node.clone().connect_peer(localhost.clone(), port_b).await.is_err());
+        node.addnodes.write().await.add(Node::new(localhost.clone(), port_b)).unwrap();
+        node.clone().connect_peer(localhost.clone(), port_b).await.unwrap();
+        assert_eq!(node.connection_counts().await, (0, 2));
+        assert!(node.clone().connect_manual_peer(localhost, port_c).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_full_inbound_slots_evict_a_peer() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let limits = PeerLimits {
+                max_inbound: 1,
+                ..PeerLimits::default()
+            };
+            let hub = Arc::new(
+                NetworkNode::new(Arc::new(RwLock::new(Blockchain::new(keypair.address(), 0).unwrap())))
+                    .with_peer_limits(limits),
+            );
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(hub.clone().serve(listener));
+
+            let (first, _) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            first.clone().connect_peer("127.0.0.1".to_string(), port).await.unwrap();
+            while hub.connection_counts().await != (1, 0) {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+
+            let (second, _) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            second.clone().connect_peer("127.0.0.1".to_string(), port).await.unwrap();
+
+            // The first peer is dropped and sees its connection close
+            while !first.list_peers().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(hub.connection_counts().await, (1, 0));
+            assert_eq!(second.list_peers().await.len(), 1);
+        })
+        .await
+        .expect("inbound peer was not evicted");
+    }
+
+    #[tokio::test]
+    async fn test_peers_negotiate_capabilities() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let (node_a, port_a) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let (node_b, _) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+
+            for node in [&node_a, &node_b] {
+                loop {
+                    let details = node.peer_details().await;
+                    // Features start at the legacy set until the peer's
+                    // Capabilities message arrives
+                    if let Some(capabilities) = details.first().and_then(|d| d.capabilities) {
+                        assert_eq!(capabilities.protocol_version, PROTOCOL_VERSION);
+                        if capabilities.features == features::LOCAL {
+                            break;
+                        }
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }
+        })
+        .await
+        .expect("capabilities were not negotiated");
+    }
+
+    #[tokio::test]
+    async fn test_peers_of_another_network_are_disconnected() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let (node_a, port_a) = spawn_node(Blockchain::new([1u8; 32], 0).unwrap()).await;
+            let (node_b, _) = spawn_node(Blockchain::new([2u8; 32], 0).unwrap()).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+            while !node_a.peer_details().await.is_empty() || !node_b.peer_details().await.is_empty() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("peers on different networks stayed connected");
+    }
+
+    #[tokio::test]
+    async fn test_traced_transaction_is_reported_and_forwarded() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain = Blockchain::new(keypair.address(), 0).unwrap();
+            let tx = spend_genesis(&keypair, &chain);
+            let traced = |name: &str| {
+                let chain = Blockchain::new(keypair.address(), 0).unwrap();
+                NetworkNode::new(Arc::new(RwLock::new(chain))).with_tx_trace(name.to_string(), None)
+            };
+            let mut nodes = Vec::new();
+            for node in [traced("a"), traced("b"), NetworkNode::new(Arc::new(RwLock::new(chain)))] {
+                let node = Arc::new(node);
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let port = listener.local_addr().unwrap().port();
+                tokio::spawn(node.clone().serve(listener));
+                nodes.push((node, port));
+            }
+            let [(a, _), (b, port_b), (c, _)] = &nodes[..] else { unreachable!() };
+            a.clone().connect_peer("127.0.0.1".to_string(), *port_b).await.unwrap();
+            c.clone().connect_peer("127.0.0.1".to_string(), *port_b).await.unwrap();
+            loop {
+                let details = b.peer_details().await;
+                if details.len() == 2 && details.iter().all(|d| d.capabilities.is_some_and(|c| c.features == features::LOCAL)) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert!(c.broadcast_traced_transaction(&tx).await.is_err());
+
+            // C only hears of the transaction through B forwarding it
+            a.blockchain.write().await.mempool.add_transaction(tx.clone()).unwrap();
+            let (sent, id) = a.broadcast_traced_transaction(&tx).await.unwrap();
+            assert_eq!(sent, 1);
+            while c.blockchain.read().await.mempool.get_transaction(&tx.hash()).is_none() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+
+            let hops = b.traces.read().await.hops(&crate::txtrace::trace_id_hex(id)).unwrap();
+            assert_eq!(hops.len(), 1);
+            assert_eq!((hops[0].node.as_str(), hops[0].hop, hops[0].duplicate), ("b", 1, false));
+            assert_eq!(hops[0].tx, hex::encode(tx.hash()));
+            // With tracing off the tag is dropped
+            assert!(c.traces.read().await.is_empty());
+        })
+        .await
+        .expect("traced transaction was not forwarded");
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_is_relayed_to_peers() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let authority_keys = [KeyPair::generate().unwrap(), KeyPair::generate().unwrap()];
+            let authority = CheckpointAuthority::new(
+                authority_keys.iter().map(|k| k.public_key_bytes().to_vec()).collect(),
+                2,
+            )
+            .unwrap();
+            let mut chain = Blockchain::new([1u8; 32], 0).unwrap();
+            mine_blocks(&mut chain, 2, 0, 1);
+            let checkpoint = crate::checkpoint::Checkpoint {
+                height: 2,
+                hash: chain.blocks[2].hash(),
+            };
+
+            let spawn = |chain: Blockchain| {
+                let authority = authority.clone();
+                async move {
+                    let node = Arc::new(
+                        NetworkNode::new(Arc::new(RwLock::new(chain))).with_checkpoint_authority(authority),
+                    );
+                    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                    let port = listener.local_addr().unwrap().port();
+                    tokio::spawn(node.clone().serve(listener));
+                    (node, port)
+                }
+            };
+            let (node_a, port_a) = spawn(chain.clone()).await;
+            let (node_b, _) = spawn(chain).await;
+
+            let mut signed = SignedCheckpoint::new(checkpoint.clone());
+            signed.sign(&authority_keys[0]).unwrap();
+            assert!(node_a.submit_checkpoint(signed.clone()).await.is_err());
+            signed.sign(&authority_keys[1]).unwrap();
+            assert!(node_a.submit_checkpoint(signed.clone()).await.unwrap());
+            assert!(!node_a.submit_checkpoint(signed).await.unwrap());
+
+            // A peer connecting later is handed the checkpoint
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+            loop {
+                if node_b.blockchain.read().await.checkpoint.as_ref() == Some(&checkpoint) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("checkpoint was not relayed");
+    }
+
+    #[tokio::test]
+    async fn test_peers_exchange_addresses() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let (node_a, port_a) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let (node_b, port_b) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let known = Node::new("192.0.2.10".to_string(), 8333);
+            node_a.discovery.write().await.addresses_mut().mark_good(&known, unix_now());
+
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+            let listening_b = Node::new("127.0.0.1".to_string(), port_b);
+            let connected_a = Node::new("127.0.0.1".to_string(), port_a);
+            loop {
+                let learned = node_b.discovery.read().await.addresses().contains(&known);
+                let advertised = node_a.discovery.read().await.addresses().contains(&listening_b);
+                if learned && advertised {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            // The peer we dialed is remembered as a working address
+            assert!(node_b.discovery.read().await.addresses().is_tried(&connected_a));
+        })
+        .await
+        .expect("addresses were not exchanged");
+    }
+
+    #[tokio::test]
+    async fn test_simulated_loss_drops_all_but_handshakes() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let mut chain = Blockchain::new([1u8; 32], 0).unwrap();
+            let lossy = NetworkNode::new(Arc::new(RwLock::new(chain.clone())))
+                .with_network_faults(NetworkFaults::new(Duration::from_millis(10), 1.0).unwrap());
+            let lossy = Arc::new(lossy);
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(lossy.clone().serve(listener));
+            let (node_b, _) = spawn_node(chain.clone()).await;
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port).await.unwrap();
+
+            // The handshake arrives, the capabilities that follow it do not
+            loop {
+                if node_b.peer_details().await.first().is_some_and(|d| d.capabilities.is_some()) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            mine_blocks(&mut chain, 1, 0, 1);
+            lossy.broadcast_block(&chain.blocks[1]).await;
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert_eq!(node_b.blockchain.read().await.blocks.len(), 1);
+            let capabilities = node_b.peer_details().await[0].capabilities.unwrap();
+            assert_eq!(capabilities.features, features::LEGACY);
+        })
+        .await
+        .expect("lossy peer test timed out");
+    }
+
+    async fn read_frame(stream: &mut TcpStream) -> NetworkMessage {
+        let len = stream.read_u32().await.unwrap() as usize;
+        let mut buffer = vec![0u8; len];
+        stream.read_exact(&mut buffer).await.unwrap();
+        bincode::deserialize(&buffer).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_version_one_peer_is_not_sent_newer_messages() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let (node, port) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let mut legacy = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+            let handshake = bincode::serialize(&NetworkMessage::Handshake {
+                protocol_version: 1,
+                best_height: 0,
+            })
+            .unwrap();
+            legacy.write_u32(handshake.len() as u32).await.unwrap();
+            legacy.write_all(&handshake).await.unwrap();
+
+            // Our handshake, then the mempool request that follows the
+            // peer's handshake; no capabilities message in between
+            assert!(matches!(read_frame(&mut legacy).await, NetworkMessage::Handshake { .. }));
+            assert!(matches!(read_frame(&mut legacy).await, NetworkMessage::GetMempool));
+
+            let peer = node.list_peers().await.pop().unwrap();
+            let capabilities = node.pool.capabilities(&peer).await.unwrap();
+            assert_eq!(capabilities.protocol_version, 1);
+            assert_eq!(capabilities.features, features::LEGACY);
+            let message = NetworkMessage::Capabilities { features: features::LOCAL };
+            assert!(node.send_message(&peer, &message).await.is_err());
+        })
+        .await
+        .expect("legacy handshake failed");
+    }
+
+    #[tokio::test]
+    async fn test_large_messages_compressed_for_zstd_peers() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let (node, port) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let mut peer = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            for message in [
+                NetworkMessage::Handshake { protocol_version: PROTOCOL_VERSION, best_height: 0 },
+                NetworkMessage::Capabilities { features: features::LOCAL },
+            ] {
+                let data = bincode::serialize(&message).unwrap();
+                peer.write_u32(data.len() as u32).await.unwrap();
+                peer.write_all(&data).await.unwrap();
+            }
+            let remote = loop {
+                if let Some(remote) = node.list_peers().await.pop() {
+                    if node.pool.capabilities(&remote).await.is_some_and(|c| c.features == features::LOCAL) {
+                        break remote;
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            };
+
+            let peers: Vec<Node> = (0..200).map(|i| Node::new("127.0.0.1".to_string(), 9000 + i)).collect();
+            node.send_message(&remote, &NetworkMessage::Peers(peers.clone())).await.unwrap();
+            let payload = loop {
+                if let NetworkMessage::Compressed(payload) = read_frame(&mut peer).await {
+                    break payload;
+                }
+            };
+            let data = compression::decompress(&payload, MAX_MESSAGE_SIZE).unwrap();
+            match bincode::deserialize(&data).unwrap() {
+                NetworkMessage::Peers(received) => assert_eq!(received, peers),
+                other => panic!("unexpected message {:?}", other),
+            }
+
+            // Compressed frames from the peer are unwrapped
+            let ping = bincode::serialize(&NetworkMessage::Ping { nonce: 42 }).unwrap();
+            let frame = NetworkMessage::Compressed(compression::compress(&ping, compression::DEFAULT_LEVEL).unwrap());
+            let data = bincode::serialize(&frame).unwrap();
+            peer.write_u32(data.len() as u32).await.unwrap();
+            peer.write_all(&data).await.unwrap();
+            while !matches!(read_frame(&mut peer).await, NetworkMessage::Pong { nonce: 42 }) {}
+        })
+        .await
+        .expect("compressed exchange failed");
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filter_limits_relay_and_mempool_inventory() {
+        async fn write_frame(stream: &mut TcpStream, message: &NetworkMessage) {
+            let data = bincode::serialize(message).unwrap();
+            stream.write_u32(data.len() as u32).await.unwrap();
+            stream.write_all(&data).await.unwrap();
+        }
+        async fn next_inventory(stream: &mut TcpStream) -> Vec<Sha256Hash> {
+            loop {
+                if let NetworkMessage::MempoolInventory(hashes) = read_frame(stream).await {
+                    return hashes;
+                }
+            }
+        }
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain = Blockchain::new(keypair.address(), 0).unwrap();
+            let tx = spend_genesis(&keypair, &chain);
+            let (node, port) = spawn_node(chain).await;
+            node.blockchain.write().await.mempool.add_transaction(tx.clone()).unwrap();
+
+            // A light client following an unrelated address
+            let mut peer = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let mut filter = BloomFilter::new(10, crate::bloom::MIN_FALSE_POSITIVE_RATE);
+            filter.insert(&[5u8; 32]);
+            for message in [
+                NetworkMessage::Handshake { protocol_version: PROTOCOL_VERSION, best_height: 0 },
+                NetworkMessage::Capabilities { features: features::LOCAL & !features::ZSTD },
+                NetworkMessage::FilterLoad(filter),
+                NetworkMessage::GetMempool,
+            ] {
+                write_frame(&mut peer, &message).await;
+            }
+            assert!(next_inventory(&mut peer).await.is_empty());
+
+            // Adding the sender lets its transactions through
+            write_frame(&mut peer, &NetworkMessage::FilterAdd(keypair.address().to_vec())).await;
+            write_frame(&mut peer, &NetworkMessage::GetMempool).await;
+            assert_eq!(next_inventory(&mut peer).await, vec![tx.hash()]);
+
+            let unrelated = Transaction::Transfer(TransferTx::new(
+                [8u8; 32],
+                [9u8; 32],
+                [9u8; 32],
+                Coord::from_num(1),
+                Coord::from_num(0),
+                1,
+            ));
+            assert_eq!(node.broadcast_transaction(&unrelated).await, 0);
+            assert_eq!(node.broadcast_transaction(&tx).await, 1);
+            let relayed = loop {
+                if let NetworkMessage::NewTransaction(relayed) = read_frame(&mut peer).await {
+                    break relayed;
+                }
+            };
+            assert_eq!(relayed.hash(), tx.hash());
+
+            // Clearing the filter relays everything again
+            write_frame(&mut peer, &NetworkMessage::FilterClear).await;
+            write_frame(&mut peer, &NetworkMessage::GetMempool).await;
+            assert_eq!(next_inventory(&mut peer).await, vec![tx.hash()]);
+            assert_eq!(node.broadcast_transaction(&unrelated).await, 1);
+        })
+        .await
+        .expect("filtered relay failed");
+    }
+
+    #[tokio::test]
+    async fn test_peer_that_stops_reading_overflows_and_is_dropped() {
+        tokio::time::timeout(Duration::from_secs(20), async {
+            let keypair = KeyPair::generate().unwrap();
+            let chain = Blockchain::new(keypair.address(), 0).unwrap();
+            let limits = SendQueueLimits { max_bytes: 256 * 1024, overflow: crate::sendqueue::OverflowPolicy::Disconnect };
+            let node = Arc::new(NetworkNode::new(Arc::new(RwLock::new(chain))).with_send_queue_limits(limits));
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(node.clone().serve(listener));
+
+            // Connects and never reads
+            let _stalled = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let peer = loop {
+                if let Some(peer) = node.list_peers().await.pop() {
+                    break peer;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            };
+            let peers = NetworkMessage::Peers((0..2000).map(|i| Node::new("127.0.0.1".to_string(), i)).collect());
+            let mut sends = 0;
+            while node.send_message(&peer, &peers).await.is_ok() {
+                sends += 1;
+                assert!(sends < 10_000, "queue never filled");
+                tokio::task::yield_now().await;
+            }
+            assert!(node.list_peers().await.is_empty());
+        })
+        .await
+        .expect("stalled peer was not dropped");
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_measures_latency_and_drops_silent_peers() {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            let keypair = KeyPair::generate().unwrap();
+            let (_, port_a) = spawn_node(Blockchain::new(keypair.address(), 0).unwrap()).await;
+            let chain = Blockchain::new(keypair.address(), 0).unwrap();
+            let node_b = Arc::new(
+                NetworkNode::new(Arc::new(RwLock::new(chain))).with_ping_interval(Duration::from_millis(50)),
+            );
+            tokio::spawn(node_b.clone().serve(TcpListener::bind("127.0.0.1:0").await.unwrap()));
+
+            // A live peer answers pings and gets a round trip
+            node_b.clone().connect_peer("127.0.0.1".to_string(), port_a).await.unwrap();
+            let peer_a = Node::new("127.0.0.1".to_string(), port_a);
+            loop {
+                let measured = node_b.peer_details().await.first().is_some_and(|d| d.ping_ms.is_some());
+                let info = node_b.synchronizer.get_peer_info(&peer_a.addr()).await;
+                if measured && info.is_some_and(|info| info.latency.is_some()) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+
+            // A peer that advertises keepalive but never answers is dropped
+            let port_b = node_b.listen_port.load(Ordering::Relaxed);
+            let mut silent = TcpStream::connect(("127.0.0.1", port_b)).await.unwrap();
+            for message in [
+                NetworkMessage::Handshake { protocol_version: PROTOCOL_VERSION, best_height: 0 },
+                NetworkMessage::Capabilities { features: features::LOCAL },
+            ] {
+                let data = bincode::serialize(&message).unwrap();
+                silent.write_u32(data.len() as u32).await.unwrap();
+                silent.write_all(&data).await.unwrap();
+            }
+            let mut pings = 0;
+            while let Ok(len) = silent.read_u32().await {
+                let mut buffer = vec![0u8; len as usize];
+                silent.read_exact(&mut buffer).await.unwrap();
+                if matches!(bincode::deserialize(&buffer).unwrap(), NetworkMessage::Ping { .. }) {
+                    pings += 1;
+                }
+            }
+            assert_eq!(pings, MAX_MISSED_PONGS);
+            assert_eq!(node_b.list_peers().await, vec![peer_a]);
+        })
+        .await
+        .expect("keepalive did not run");
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_relayed_transaction_rejected() {
+        let keypair = KeyPair::generate().unwrap();
+        let chain = Blockchain::new(keypair.address(), 0).unwrap();
+        let coinbase_hash = chain.blocks[0].transactions[0].hash();
+        let node = NetworkNode::new(Arc::new(RwLock::new(chain)));
+
+        let unsigned = Transaction::Transfer(TransferTx::new(
+            coinbase_hash,
+            [7u8; 32],
+            keypair.address(),
+            Coord::from_num(10),
+            Coord::from_num(0),
+            1,
+        ));
+        let peer = Node::new("10.0.0.9".to_string(), 8333);
+        node.synchronizer.register_peer(peer.clone(), 0).await.unwrap();
+        assert!(node.accept_transaction(&peer, unsigned.clone()).await.is_err());
+        assert!(node.blockchain.read().await.mempool.is_empty());
+        assert!(node.known_invalid.lock().await.contains(&unsigned.hash()));
+        assert!(!node.is_banned(&peer.host).await);
+
+        // Relaying it again is refused from the cache and counts against
+        // the peer until it is banned
+        for _ in 0..3 {
+            let err = node.accept_transaction(&peer, unsigned.clone()).await.unwrap_err();
+            assert!(err.to_string().contains("Known invalid"));
+        }
+        assert!(node.is_banned(&peer.host).await);
+        assert_eq!(node.known_invalid.lock().await.list()[0].repeats, 3);
+    }
 }
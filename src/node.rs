@@ -1,43 +1,239 @@
+use crate::addnode::{parse_peer_address, AddNodeList};
+use crate::banlist::BanList;
+use crate::chainspec::ChainParams;
+use crate::checkpoint::CheckpointAuthority;
+use crate::compression;
 use crate::config::load_config;
+use crate::lockfile::DataDirLock;
 use crate::persistence::{Database, InMemoryPersistence, Persistence};
-use crate::blockchain::Blockchain;
-use crate::mempool::Mempool;
+use crate::blockchain::{Blockchain, DEFAULT_POW};
+use crate::miner::{BeneficiarySource, LocalPriority, MinerThrottle, TemplateRefresh, ThrottleSettings};
+use crate::netsim::NetworkFaults;
 use crate::network::NetworkNode;
+use crate::knowninvalid::KnownInvalid;
+use crate::rejected::RejectedBlockLog;
+use crate::telemetry::Telemetry;
+use crate::watchlist::{Notifier, Watchlist};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use tracing::{info, warn, error};
 use std::fs;
 use std::net::TcpListener;
+use std::path::Path;
 use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NodeState {
     Booting,
     Syncing,
     Ready,
     Degraded,
+    /// Received SIGINT or SIGTERM and finishing up
+    Stopping,
 }
 
+/// Where node logs are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogOutput {
+    Stdout,
+    /// `node.log` in the data directory, leaving the terminal to a dashboard
+    DataDirFile,
+    /// One JSON object per line on stdout, for container log collectors
+    Json,
+}
+
+/// How long in-flight API requests get to finish once shutdown starts
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+
 pub struct Node {
     pub config: crate::config::Config,
     pub persistence: std::sync::Arc<Box<dyn Persistence>>,
     pub blockchain: Arc<RwLock<Blockchain>>,
-    pub mempool: Arc<RwLock<Mempool>>,
     pub network: Arc<NetworkNode>,
     pub state: Arc<RwLock<NodeState>>,
+    /// Address watchlists, shared by webhook delivery and the API
+    pub notifier: Arc<Notifier>,
+    /// Throttle of the embedded miner, adjustable through the API
+    pub throttle: Arc<MinerThrottle>,
+    /// Senders whose transactions the miners include regardless of fee
+    pub local_priority: Arc<LocalPriority>,
+    /// Set to `true` to stop the API servers
+    shutdown: watch::Sender<bool>,
+    /// Keeps other processes out of the data directory while the node runs
+    _data_dir_lock: DataDirLock,
+}
+
+/// Candidate block for the embedded miner: up to 50 transactions of the
+/// chain's mempool by fee, `priority`'s senders first, after a coinbase for
+/// the next height. While a peer's header waits for its body, the candidate
+/// builds on that header with the coinbase alone.
+async fn next_template(
+    bc: &RwLock<Blockchain>,
+    net: &NetworkNode,
+    beneficiary: &BeneficiarySource,
+    priority: &LocalPriority,
+) -> Result<crate::blockchain::Block, crate::error::ChainError> {
+    if let Some(parent) = net.mining_parent().await {
+        let chain = bc.read().await;
+        let height = parent.header.height + 1;
+        let reward = chain.mining_subsidy(height, crate::geometry::Coord::from_num(1.0));
+        let fees = crate::geometry::Coord::from_num(0);
+        let coinbase = crate::transaction::types::CoinbaseTx { reward_area: reward, beneficiary_address: beneficiary.address_for_height(height)?, nonce: height, height, fees, extra_nonce: Vec::new(), outputs: chain.reward_outputs(reward)? };
+        let mut block = crate::blockchain::Block::new_with_clock(height, parent.hash, parent.header.difficulty, vec![crate::transaction::Transaction::Coinbase(coinbase)], chain.clock.as_ref())
+            .with_version(chain.next_block_version());
+        block.header.timestamp = block.header.timestamp.max(parent.header.timestamp + 1);
+        return Ok(block);
+    }
+
+    let (height, prev_hash, timestamp, difficulty, version) = {
+        let chain = bc.read().await;
+        let last = chain.blocks.last();
+        let height = last.map(|b| b.header.height + 1).unwrap_or(0);
+        let prev_hash = last.map(|b| b.hash()).unwrap_or([0u8;32]);
+        let timestamp = chain.next_timestamp();
+        let difficulty = chain.required_difficulty(timestamp);
+        (height, prev_hash, timestamp, difficulty, chain.next_block_version())
+    };
+
+    let txs = priority.select(&bc.read().await.mempool, 50);
+    // coinbase reward area: small constant for dev mining unless the network
+    // has a subsidy schedule, plus the fees the miner may keep
+    let fees = bc.read().await.claimable_fees(&txs)?;
+    let subsidy = bc.read().await.mining_subsidy(height, crate::geometry::Coord::from_num(1.0));
+    let reward = crate::geometry::coord_checked_add(subsidy, fees)?;
+    let outputs = bc.read().await.reward_outputs(reward)?;
+    let beneficiary_address = beneficiary.address_for_height(height)?;
+    let mut txs_with_coinbase = vec![crate::transaction::Transaction::Coinbase(crate::transaction::types::CoinbaseTx{ reward_area: reward, beneficiary_address, nonce: height, height, fees, extra_nonce: Vec::new(), outputs })];
+    txs_with_coinbase.extend(txs);
+
+    let mut block = crate::blockchain::Block::new(height, prev_hash, difficulty, txs_with_coinbase).with_version(version);
+    block.header.timestamp = timestamp;
+    Ok(block)
+}
+
+/// The embedded miner's loop and what it shares with the rest of the node
+struct EmbeddedMiner {
+    blockchain: Arc<RwLock<Blockchain>>,
+    network: Arc<NetworkNode>,
+    persistence: Arc<Box<dyn Persistence>>,
+    beneficiary: BeneficiarySource,
+    /// Connected peers required before mining
+    min_peers: usize,
+    refresh: TemplateRefresh,
+    throttle: Arc<MinerThrottle>,
+    priority: Arc<LocalPriority>,
+}
+
+impl EmbeddedMiner {
+    /// Mine on a background task while enough peers are connected and the
+    /// chain's mempool holds transactions
+    fn spawn(self) -> tokio::task::JoinHandle<()> {
+        let EmbeddedMiner { blockchain: bc, network: net, persistence: pers, beneficiary, min_peers, refresh, throttle, priority } = self;
+        let hash_stats = std::sync::Mutex::new(crate::miner::HashRateStats::default());
+        tokio::spawn(async move {
+            loop {
+                let (inbound, outbound) = net.connection_counts().await;
+                if inbound + outbound < min_peers {
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+
+                if bc.read().await.mempool.is_empty() {
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    continue;
+                }
+
+                let template = match next_template(&bc, &net, &beneficiary, &priority).await {
+                    Ok(block) => block,
+                    Err(e) => {
+                        error!("Miner stopped: {}", e);
+                        break;
+                    }
+                };
+                let (bc_ref, net_ref, beneficiary_ref, priority_ref) = (&*bc, &*net, &beneficiary, &*priority);
+                let pow = bc.read().await.consensus.pow;
+                let mined = crate::miner::mine_with_refresh(template, pow, &hash_stats, &throttle, move |template, age| {
+                    let previous_hash = template.header.previous_hash;
+                    let template_txs = template.transactions.len().saturating_sub(1);
+                    async move {
+                        let parent = match net_ref.mining_parent().await {
+                            Some(header) => Some(header.hash),
+                            None => bc_ref.read().await.blocks.last().map(|b| b.hash()),
+                        };
+                        let pending = bc_ref.read().await.mempool.len();
+                        if parent == Some(previous_hash) && !refresh.is_due(age, template_txs, pending) {
+                            return None;
+                        }
+                        next_template(bc_ref, net_ref, beneficiary_ref, priority_ref).await.ok()
+                    }
+                })
+                .await;
+                match mined {
+                    Ok(mined) => {
+                        info!("Mined new block at height {}", mined.header.height);
+                        // apply to chain and announce, or hold until the
+                        // relayed header it builds on is validated
+                        match net.submit_mined_block(mined.clone()).await {
+                            Ok(true) => {
+                                let _ = pers.as_ref().save_blockchain_state(&mined, &bc.read().await.state, bc.read().await.difficulty as u64);
+                            }
+                            Ok(false) => info!("Holding block {} until its parent's body is validated", mined.header.height),
+                            Err(e) => warn!("Failed to apply mined block: {}", e),
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Mining failed: {}", e);
+                    }
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        })
+    }
 }
 
 impl Node {
     pub async fn init() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::init_with_logs(LogOutput::Stdout).await
+    }
+
+    pub async fn init_with_logs(logs: LogOutput) -> Result<Self, Box<dyn std::error::Error>> {
         // Load and validate config
         let config = load_config()?;
 
-        tracing_subscriber::fmt::init();
+        match logs {
+            LogOutput::Stdout => tracing_subscriber::fmt::init(),
+            LogOutput::Json => tracing_subscriber::fmt().json().init(),
+            LogOutput::DataDirFile => {
+                fs::create_dir_all(config.data_dir())?;
+                let file = fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(config.data_dir().join("node.log"))?;
+                tracing_subscriber::fmt()
+                    .with_writer(std::sync::Mutex::new(file))
+                    .with_ansi(false)
+                    .init();
+            }
+        }
         info!("Starting TrinityChain node (network_id = {})", config.network.network_id);
 
+        // Only one process may write the data directory
+        let data_dir_lock = DataDirLock::acquire(&config.data_dir())?;
+        if let Some(pid) = data_dir_lock.stale_pid() {
+            warn!("Process {} exited without releasing the data directory lock; taking it over", pid);
+        }
+
         // Setup persistence
         let persistence_box: Box<dyn Persistence> = match Database::open(&config.database.path) {
-            Ok(db) => Box::new(db),
+            Ok(db) => {
+                // Undo a write the last run did not finish before loading
+                match db.recover() {
+                    Ok(report) if report.is_clean() => info!("Database {}", report.summary()),
+                    Ok(report) => warn!("Recovered database: {}", report.summary()),
+                    Err(e) => warn!("Database recovery failed: {}", e),
+                }
+                Box::new(db)
+            }
             Err(e) => {
                 warn!("Failed to open DB at {}: {}. Falling back to in-memory persistence.", config.database.path, e);
                 Box::new(InMemoryPersistence::new())
@@ -45,8 +241,14 @@ impl Node {
         };
         let persistence = std::sync::Arc::new(persistence_box);
 
+        // A private network's parameters, checked against their genesis
+        let chain_params = match &config.network.chain_params {
+            Some(path) => Some(ChainParams::load(Path::new(path))?),
+            None => None,
+        };
+
         // Load or create blockchain
-        let blockchain = match persistence.load_blockchain() {
+        let mut blockchain = match persistence.load_blockchain() {
             Ok(chain) => chain,
             Err(e) => {
                 warn!("Failed to load blockchain from persistence: {}. Creating new chain.", e);
@@ -58,14 +260,138 @@ impl Node {
             }
         };
 
+        match &chain_params {
+            Some((params, genesis)) => {
+                // An empty database loads as the built-in genesis block alone;
+                // the network starts from its own genesis instead
+                if blockchain.chain_id() != params.genesis_hash && blockchain.blocks.len() == 1 {
+                    let consensus = params.spec.consensus()?;
+                    blockchain = Blockchain::from_genesis(genesis.clone(), consensus, Box::new(InMemoryPersistence::new()))
+                        .map_err(|e| format!("Failed to create blockchain: {}", e))?;
+                    persistence.save_blockchain_state(genesis, &blockchain.state, blockchain.difficulty as u64)?;
+                    info!("Created the genesis block of '{}' from chain params", params.spec.name);
+                }
+                if blockchain.chain_id() != params.genesis_hash {
+                    return Err(format!(
+                        "The stored chain starts at genesis {}, but network.chain_params is for {}; use another data directory",
+                        hex::encode(blockchain.chain_id()),
+                        hex::encode(params.genesis_hash)
+                    )
+                    .into());
+                }
+                blockchain.consensus = params.spec.consensus()?;
+                info!("Network '{}' from chain params (magic {})", params.spec.name, params.magic);
+            }
+            None => blockchain.consensus.pow = config.network.pow_algorithm()?,
+        }
+        if blockchain.consensus.pow != DEFAULT_POW {
+            warn!("Proof-of-work algorithm '{}': for test networks only", blockchain.consensus.pow.name());
+        }
+
+        blockchain.set_dust_threshold(config.analytics.dust_threshold);
+        blockchain.mempool.set_limits(config.memory.mempool_limits());
+
+        if config.database.archive {
+            info!("Archive mode: keeping spent triangle history");
+            blockchain.enable_archive()?;
+        }
+
         let blockchain = Arc::new(RwLock::new(blockchain));
-        let mempool = Arc::new(RwLock::new(Mempool::new()));
         let state = Arc::new(RwLock::new(NodeState::Booting));
 
-        // Network
-        let network = Arc::new(NetworkNode::new(blockchain.clone()));
+        // Network, with the ban list and rejected-block log persisted in the
+        // data directory
+        let banlist = match BanList::load_from_data_dir(&config.data_dir()) {
+            Ok(list) => list,
+            Err(e) => {
+                warn!("Failed to load ban list: {}. Starting with an empty one.", e);
+                BanList::new()
+            }
+        };
+        let addnodes = match AddNodeList::load_from_data_dir(&config.data_dir()) {
+            Ok(list) => list,
+            Err(e) => {
+                warn!("Failed to load addnode list: {}. Starting with an empty one.", e);
+                AddNodeList::new()
+            }
+        };
+        let rejected_blocks = match RejectedBlockLog::load_from_data_dir(&config.data_dir()) {
+            Ok(log) => log,
+            Err(e) => {
+                warn!("Failed to load rejected-block log: {}. Starting with an empty one.", e);
+                RejectedBlockLog::new()
+            }
+        };
+        let known_invalid = if config.network.persist_known_invalid {
+            match KnownInvalid::load_from_data_dir(&config.data_dir()) {
+                Ok(cache) => cache,
+                Err(e) => {
+                    warn!("Failed to load known-invalid cache: {}. Starting with an empty one.", e);
+                    KnownInvalid::new()
+                }
+            }
+        } else {
+            KnownInvalid::new()
+        };
+        let mut network = NetworkNode::new(blockchain.clone())
+            .with_banlist(banlist)
+            .with_addnodes(addnodes)
+            .with_rejected_block_log(rejected_blocks)
+            .with_known_invalid(known_invalid)
+            .with_peer_limits(config.network.peer_limits())
+            .with_send_queue_limits(config.network.send_queue_limits())
+            .with_sync_buffer_limits(config.memory.sync_buffer_limits())
+            .with_compression_level(compression::check_level(config.network.compression_level)?);
+        if let Some(proxy) = &config.network.proxy {
+            network = network.with_proxy(proxy.clone());
+        }
+        let faults = NetworkFaults::from_config(&config.dev)?;
+        if faults.is_active() {
+            warn!(
+                "Simulating network faults: {} ms latency, {:.0}% of messages dropped",
+                faults.latency.as_millis(),
+                faults.drop_rate * 100.0
+            );
+            network = network.with_network_faults(faults);
+        }
+        if config.trace.enabled {
+            let name = config.trace.name.clone().unwrap_or_else(|| format!("node:{}", config.network.p2p_port));
+            warn!("Transaction tracing is on: traced transactions are reported as {}", name);
+            network = network.with_tx_trace(name, config.trace.aggregator.clone());
+        }
+        if let Some(authority) = CheckpointAuthority::from_config(&config.checkpoints)? {
+            info!(
+                "Following checkpoints signed by {} of {} authorities",
+                authority.threshold(),
+                authority.authority_count()
+            );
+            network = network.with_checkpoint_authority(authority);
+        }
+        let network = Arc::new(network);
 
-        Ok(Self { config, persistence, blockchain, mempool, network, state })
+        let watchlist = match Watchlist::load_from_data_dir(&config.data_dir()) {
+            Ok(watchlist) => watchlist,
+            Err(e) => {
+                warn!("Failed to load watchlist: {}. Starting with an empty one.", e);
+                Watchlist::new()
+            }
+        };
+        let notifier = Arc::new(Notifier::new(watchlist));
+        let throttle = Arc::new(MinerThrottle::new(ThrottleSettings::from_config(&config.miner)?));
+        let local_priority = Arc::new(LocalPriority::from_config(&config.miner, &config.data_dir())?);
+
+        Ok(Self {
+            config,
+            persistence,
+            blockchain,
+            network,
+            state,
+            notifier,
+            throttle,
+            local_priority,
+            shutdown: watch::channel(false).0,
+            _data_dir_lock: data_dir_lock,
+        })
     }
 
     pub async fn start(self: Arc<Self>) -> Result<(), Box<dyn std::error::Error>> {
@@ -97,25 +423,61 @@ impl Node {
 
         // Bootstrap peers
         for peer in &self.config.network.bootstrap_peers {
-            let parts: Vec<&str> = peer.split(':').collect();
-            if parts.len() == 2 {
-                let host = parts[0].to_string();
-                if let Ok(port) = parts[1].parse::<u16>() {
+            match parse_peer_address(peer) {
+                Ok(peer) => {
+                    self.network.discovery.write().await.add_bootstrap_peer(peer.clone());
                     let net2 = self.network.clone();
                     tokio::spawn(async move {
-                        let _ = net2.connect_peer(host, port).await;
+                        let _ = net2.connect_peer(peer.host, peer.port).await;
                     });
                 }
+                Err(e) => warn!("Ignoring bootstrap peer: {}", e),
             }
         }
 
-        // 3) Ensure API port is available and start API server
+        // Operator-added peers from previous runs
+        let addnodes = self.network.addnodes.read().await.nodes().to_vec();
+        for peer in addnodes {
+            let net2 = self.network.clone();
+            tokio::spawn(async move {
+                if let Err(e) = net2.connect_manual_peer(peer.host.clone(), peer.port).await {
+                    warn!("Failed to connect to addnode {}: {}", peer.addr(), e);
+                }
+            });
+        }
+
+        // Opt-in telemetry
+        let telemetry = Arc::new(self.telemetry());
+        if self.config.telemetry.enabled && !telemetry.is_active() {
+            warn!("Telemetry is enabled but no telemetry.endpoint is set; not reporting");
+        }
+        telemetry.spawn_reporter(self.network.clone());
+
+        // Scheduled database compaction and integrity checks
+        crate::maintenance::spawn(&self.config.maintenance, self.persistence.clone(), self.state.clone());
+
+        // Watchlist notifications
+        self.notifier.clone().spawn(self.blockchain.clone(), crate::watchlist::POLL_INTERVAL);
+        self.notifier.spawn_webhooks(self.config.network.proxy.clone());
+
+        // blocknotify / walletnotify commands
+        crate::notify::spawn(&self.config.notify, self.blockchain.clone(), self.config.data_dir());
+
+        // Alerts on spends from cold-storage addresses
+        crate::watchtower::spawn(&self.config.watchtower, self.blockchain.clone(), self.config.network.proxy.clone());
+
+        // 3) Ensure API ports are available and start API server
         let api_port = self.config.network.api_port;
         let api_bind = format!("0.0.0.0:{}", api_port);
         TcpListener::bind(&api_bind).map_err(|e| format!("API port {} unavailable: {}", api_port, e))?;
+        if let Some(admin_port) = self.config.api.admin_port {
+            let admin_bind = format!("{}:{}", self.config.api.admin_bind, admin_port);
+            TcpListener::bind(&admin_bind)
+                .map_err(|e| format!("Admin API address {} unavailable: {}", admin_bind, e))?;
+        }
 
         let node = self.clone();
-        let _api_task = tokio::spawn(async move {
+        let api_task = tokio::spawn(async move {
             if let Err(e) = Node::start_api(node, api_port).await {
                 error!("API server failed: {}", e);
             }
@@ -133,82 +495,69 @@ impl Node {
             *s = NodeState::Ready;
         }
 
-        // Start miner loop if enabled and node is Ready
-        if self.config.miner.enabled {
-            let bc = self.blockchain.clone();
-            let mp = self.mempool.clone();
-            let pers = self.persistence.clone();
-            let _net = self.network.clone();
-            let min_peers = self.config.network.min_peers;
-            tokio::spawn(async move {
-                loop {
-                    // Basic gating: require node Ready, sufficient peers and non-empty mempool
-                    // Check node state
-                    // (we can't access self.state from here easily; rely on network/mempool checks)
-                    let peer_count = 0usize; // best-effort; network exposes peer listing elsewhere
-                    if peer_count < min_peers as usize {
-                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                        continue;
-                    }
-
-                    if mp.read().await.is_empty() {
-                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                        continue;
-                    }
-
-                    // Create a candidate block
-                    let (height, prev_hash, difficulty) = {
-                        let chain = bc.read().await;
-                        let last = chain.blocks.last();
-                        let height = last.map(|b| b.header.height + 1).unwrap_or(0);
-                        let prev_hash = last.map(|b| b.hash()).unwrap_or([0u8;32]);
-                        let difficulty = chain.difficulty;
-                        (height, prev_hash, difficulty)
-                    };
-
-                    let txs = mp.read().await.get_transactions_by_fee(50);
-                    let mut txs_with_coinbase = vec![];
-                    // coinbase reward area: small constant for dev mining
-                    let reward = crate::geometry::Coord::from_num(1.0);
-                    let beneficiary = {
-                        let mut addr = [0u8;32];
-                        let bytes = hex::decode("00").unwrap_or_default();
-                        for (i,b) in bytes.iter().take(32).enumerate() { addr[i] = *b; }
-                        addr
-                    };
-                    txs_with_coinbase.push(crate::transaction::Transaction::Coinbase(crate::transaction::types::CoinbaseTx{ reward_area: reward, beneficiary_address: beneficiary, nonce: 0 }));
-                    txs_with_coinbase.extend(txs);
-
-                    let block = crate::blockchain::core::chain::Block::new(height, prev_hash, difficulty, txs_with_coinbase);
-                    match crate::miner::mine_block(block) {
-                        Ok(mined) => {
-                            info!("Mined new block at height {}", mined.header.height);
-                            // apply to chain
-                            if let Err(e) = bc.write().await.apply_block(mined.clone()) {
-                                warn!("Failed to apply mined block: {}", e);
-                            } else {
-                                // persist
-                                let _ = pers.as_ref().save_blockchain_state(&mined, &bc.read().await.state, bc.read().await.difficulty as u64);
-                            }
-                        }
-                        Err(e) => {
-                            warn!("Mining failed: {}", e);
-                        }
-                    }
-                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        // Start miner loop if enabled and node is Ready. Never mine without a
+        // usable beneficiary: the rewards would be unspendable.
+        let beneficiary = if self.config.miner.enabled {
+            match BeneficiarySource::from_config(&self.config.miner) {
+                Ok(source) => Some(source),
+                Err(e) => {
+                    error!("Refusing to start miner: {}", e);
+                    None
                 }
-            });
+            }
+        } else {
+            None
+        };
+        if let Some(beneficiary) = beneficiary {
+            EmbeddedMiner {
+                blockchain: self.blockchain.clone(),
+                network: self.network.clone(),
+                persistence: self.persistence.clone(),
+                beneficiary,
+                min_peers: self.config.network.min_peers as usize,
+                refresh: TemplateRefresh::from_config(&self.config.miner),
+                throttle: self.throttle.clone(),
+                priority: self.local_priority.clone(),
+            }
+            .spawn();
         }
 
-        // Node main loop - health logging
-        loop {
-            info!("Node running: chain height = {}", self.blockchain.read().await.blocks.len());
-            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        // Node main loop - health logging until SIGINT or SIGTERM
+        let signal = shutdown_signal();
+        tokio::pin!(signal);
+        let mut heartbeat = tokio::time::interval(Duration::from_secs(10));
+        let signal = loop {
+            tokio::select! {
+                signal = &mut signal => break signal,
+                _ = heartbeat.tick() => {
+                    info!("Node running: chain height = {}", self.blockchain.read().await.blocks.len());
+                }
+            }
+        };
+
+        // Fail readiness first, then let the API drain and save what is
+        // only held in memory. Chain data is written block by block already.
+        info!("Received {}, shutting down", signal);
+        *self.state.write().await = NodeState::Stopping;
+        self.shutdown.send_replace(true);
+        if tokio::time::timeout(SHUTDOWN_GRACE, api_task).await.is_err() {
+            warn!("API requests still running after {:?}; stopping anyway", SHUTDOWN_GRACE);
         }
+        self.network.flush_state().await;
+        info!("Shutdown complete");
+        Ok(())
     }
 
     
 
+    fn telemetry(&self) -> Telemetry {
+        Telemetry::new(
+            self.config.telemetry.clone(),
+            self.config.network.network_id.clone(),
+            self.config.network.proxy.clone(),
+        )
+    }
+
     #[cfg(feature = "api")]
     async fn start_api(node: Arc<Self>, port: u16) -> Result<(), Box<dyn std::error::Error>> {
         // Build a shared API node that observes the authoritative orchestrator
@@ -219,19 +568,154 @@ impl Node {
             node.network.clone(),
             Some(node.state.clone()),
         );
+        let api_node = api_node
+            .with_admin_token(node.config.api.admin_token.clone())
+            .with_request_limits(
+                node.config.api.max_request_body_bytes,
+                Duration::from_secs(node.config.api.request_timeout_secs),
+            )
+            .with_telemetry(node.telemetry())
+            .with_notifier(node.notifier.clone())
+            .with_persistence(node.persistence.clone())
+            .with_session_ttl(
+                Duration::from_secs(node.config.api.wallet_session_secs),
+                Duration::from_secs(node.config.api.wallet_session_max_secs),
+            )
+            .with_miner_throttle(node.throttle.clone())
+            .with_local_priority(node.local_priority.clone())
+            .with_template_refresh(TemplateRefresh::from_config(&node.config.miner));
+        let api_node = match crate::wallet::get_wallet_dir() {
+            Ok(dir) => {
+                let ledger = crate::spending::SpendingLedger::load_from_data_dir(&dir).unwrap_or_else(|e| {
+                    warn!("Failed to load spending ledger: {}. Daily limits start from zero.", e);
+                    crate::spending::SpendingLedger::new()
+                });
+                api_node
+                    .with_keystore_dir(dir.join("keystore"))
+                    .with_spending_limits(node.config.wallet.spending_policy(), ledger)
+                    .with_audit_log(crate::audit::TransactionAuditLog::open_in_dir(&dir))
+            }
+            Err(_) => api_node.with_spending_limits(node.config.wallet.spending_policy(), Default::default()),
+        };
         let api_node = std::sync::Arc::new(api_node);
 
-        // Ensure the API server binds to the same port requested by the node
-        std::env::set_var("PORT", port.to_string());
+        use crate::api::ApiProfile;
+        let Some(admin_port) = node.config.api.admin_port else {
+            info!("Starting axum API server (shared) on 0.0.0.0:{}", port);
+            let addr = ([0, 0, 0, 0], port).into();
+            crate::api::serve_api_until(api_node, ApiProfile::Admin, addr, node.stopped()).await?;
+            return Ok(());
+        };
 
-        info!("Starting axum API server (shared) on 0.0.0.0:{}", port);
-        crate::api::run_api_server(api_node).await?;
+        // Split profiles: read-only endpoints on the public port, the full
+        // API on the admin address
+        let public_addr: std::net::SocketAddr = ([0, 0, 0, 0], port).into();
+        let admin_addr: std::net::SocketAddr = format!("{}:{}", node.config.api.admin_bind, admin_port)
+            .parse()
+            .map_err(|e| format!("Invalid api.admin_bind '{}': {}", node.config.api.admin_bind, e))?;
+        info!("Starting public API on {} and admin API on {}", public_addr, admin_addr);
+        let serve = |profile, addr| {
+            let api_node = api_node.clone();
+            let stopped = node.stopped();
+            async move {
+                crate::api::serve_api_until(api_node, profile, addr, stopped)
+                    .await
+                    .map_err(|e| format!("{} API on {}: {}", profile.as_str(), addr, e))
+            }
+        };
+        tokio::try_join!(serve(ApiProfile::Public, public_addr), serve(ApiProfile::Admin, admin_addr))?;
         Ok(())
     }
 
+    /// Resolves once shutdown has started
+    fn stopped(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut shutdown = self.shutdown.subscribe();
+        async move {
+            let _ = shutdown.wait_for(|stopping| *stopping).await;
+        }
+    }
+
     #[cfg(not(feature = "api"))]
     async fn start_api(_node: Arc<Self>, _port: u16) -> Result<(), Box<dyn std::error::Error>> {
         Err("API feature not enabled in this build".into())
     }
 
 }
+
+/// Wait for SIGINT (Ctrl-C) or, on Unix, SIGTERM as sent by container
+/// runtimes, and name the one received
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = terminate.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, MinerConfig};
+    use crate::crypto::KeyPair;
+    use crate::geometry::Coord;
+    use crate::transaction::{Transaction, TransferTx};
+
+    #[tokio::test]
+    async fn test_embedded_miner_mines_pending_transactions_for_the_beneficiary() {
+        let alice = KeyPair::generate().unwrap();
+        let beneficiary = [7u8; 32];
+        let mut chain = Blockchain::new_with_persistence(alice.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let mut transfer = TransferTx::new(genesis_utxo, [5u8; 32], alice.address(), Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+        let transfer = Transaction::Transfer(transfer);
+        chain.mempool.add_transaction(transfer.clone()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = MinerConfig {
+            beneficiary_address: hex::encode(beneficiary),
+            ..Config::default().miner
+        };
+        let blockchain = Arc::new(RwLock::new(chain));
+        let miner = EmbeddedMiner {
+            blockchain: blockchain.clone(),
+            network: Arc::new(NetworkNode::new(blockchain.clone())),
+            persistence: Arc::new(Box::new(InMemoryPersistence::new())),
+            beneficiary: BeneficiarySource::from_config(&config).unwrap(),
+            min_peers: 0,
+            refresh: TemplateRefresh::from_config(&config),
+            throttle: Arc::new(MinerThrottle::new(ThrottleSettings::from_config(&config).unwrap())),
+            priority: Arc::new(LocalPriority::from_config(&config, dir.path()).unwrap()),
+        }
+        .spawn();
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(30);
+        while blockchain.read().await.blocks.len() < 2 {
+            assert!(tokio::time::Instant::now() < deadline, "no block was mined");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        miner.abort();
+
+        let chain = blockchain.read().await;
+        let block = &chain.blocks[1];
+        assert!(matches!(&block.transactions[0], Transaction::Coinbase(coinbase) if coinbase.beneficiary_address == beneficiary));
+        assert_eq!(block.transactions[1].hash(), transfer.hash());
+        assert!(chain.mempool.is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+//! External command hooks, like bitcoind's `-blocknotify` and
+//! `-walletnotify`
+//!
+//! `[notify] blocknotify` runs whenever the best block changes and
+//! `walletnotify` runs for every transaction touching a wallet in the data
+//! directory: once when it enters the mempool and again when it is mined.
+//! Each `%s` in the command is replaced by the block hash or txid, and the
+//! command runs through the shell without the node waiting for it.
+
+use crate::blockchain::{Blockchain, Sha256Hash};
+use crate::config::NotifyConfig;
+use crate::crypto::{address_from_hex, Address};
+use crate::transaction::Transaction;
+use crate::watchlist::{MAX_REORG_DEPTH, POLL_INTERVAL};
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// What happened since the last [`HookScanner::scan`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Events {
+    /// Hash of the new best block, if it changed
+    pub new_tip: Option<String>,
+    /// Wallet transactions newly in the mempool or newly mined
+    pub wallet_txids: Vec<String>,
+}
+
+/// Follows the chain and mempool, remembering what was already reported
+#[derive(Debug, Default)]
+pub struct HookScanner {
+    /// Recent (height, hash) of the main chain, newest last
+    scanned: VecDeque<(u64, Sha256Hash)>,
+    /// Wallet transactions reported from the mempool and not yet mined
+    pending: HashSet<Sha256Hash>,
+    /// Best block at the last scan
+    tip: Option<Sha256Hash>,
+}
+
+impl HookScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Changes in `chain` relevant to `wallets` since the last scan. The
+    /// first scan only records the tip and the mempool, so nothing that
+    /// happened before the node started is reported.
+    pub fn scan(&mut self, chain: &Blockchain, wallets: &HashSet<Address>) -> Events {
+        let mut events = Events::default();
+        let Some(tip) = chain.blocks.last() else {
+            return events;
+        };
+        let tip_height = tip.header.height;
+        let tip_hash = tip.hash();
+        let first_scan = self.tip.is_none();
+        let hash_at = |height: u64| chain.blocks.get(height as usize).map(|b| b.hash());
+
+        // Step back over blocks that are no longer on the main chain
+        while let Some(&(height, hash)) = self.scanned.back() {
+            if hash_at(height) == Some(hash) {
+                break;
+            }
+            self.scanned.pop_back();
+        }
+
+        match self.scanned.back() {
+            Some(&(last, _)) => {
+                for height in last + 1..=tip_height {
+                    let block = &chain.blocks[height as usize];
+                    for tx in &block.transactions {
+                        let txid = tx.hash();
+                        self.pending.remove(&txid);
+                        if touches(tx, wallets) {
+                            events.wallet_txids.push(hex::encode(txid));
+                        }
+                    }
+                    self.scanned.push_back((height, block.hash()));
+                }
+            }
+            None => self.scanned.push_back((tip_height, tip_hash)),
+        }
+        while self.scanned.len() > MAX_REORG_DEPTH {
+            self.scanned.pop_front();
+        }
+
+        let mempool = chain.mempool.get_all_transactions();
+        let in_mempool: HashSet<Sha256Hash> = mempool.iter().map(Transaction::hash).collect();
+        self.pending.retain(|txid| in_mempool.contains(txid));
+        for tx in &mempool {
+            if touches(tx, wallets) && self.pending.insert(tx.hash()) && !first_scan {
+                events.wallet_txids.push(tx.hash_str());
+            }
+        }
+
+        if self.tip.is_some_and(|hash| hash != tip_hash) {
+            events.new_tip = Some(hex::encode(tip_hash));
+        }
+        self.tip = Some(tip_hash);
+        events
+    }
+}
+
+/// Whether `tx` moves value to or from, or is signed by, one of `wallets`
+pub fn touches(tx: &Transaction, wallets: &HashSet<Address>) -> bool {
+    match tx {
+        Transaction::Transfer(transfer) => {
+            wallets.contains(&transfer.sender)
+                || wallets.contains(&transfer.new_owner)
+                || transfer.sponsor.as_ref().is_some_and(|s| wallets.contains(&s.payer))
+        }
+        Transaction::Subdivision(subdivision) => wallets.contains(&subdivision.owner_address),
+        Transaction::Coinbase(coinbase) => {
+            wallets.contains(&coinbase.beneficiary_address)
+                || coinbase.outputs.iter().any(|output| wallets.contains(&output.address))
+        }
+        Transaction::Announce(announce) => wallets.contains(&announce.address()),
+        Transaction::Register(register) => wallets.contains(&register.owner),
+        Transaction::Data(data) => wallets.contains(&data.owner),
+    }
+}
+
+/// Addresses of the wallet files (`wallet.json`, `wallet_<name>.json`) in
+/// `dir`, encrypted or not. Unreadable files are skipped.
+pub fn wallet_addresses(dir: &Path) -> HashSet<Address> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return HashSet::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| is_wallet_file(path))
+        .filter_map(|path| {
+            let wallet: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+            address_from_hex(wallet.get("address")?.as_str()?).ok()
+        })
+        .collect()
+}
+
+fn is_wallet_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    name == "wallet.json" || (name.starts_with("wallet_") && name.ends_with(".json"))
+}
+
+/// `command` with every `%s` replaced by `value`
+pub fn expand(command: &str, value: &str) -> String {
+    command.replace("%s", value)
+}
+
+/// Run `command` through the shell in the background, logging failures
+pub fn run_command(command: String) {
+    tokio::task::spawn_blocking(move || {
+        #[cfg(unix)]
+        let status = std::process::Command::new("sh").arg("-c").arg(&command).status();
+        #[cfg(not(unix))]
+        let status = std::process::Command::new("cmd").arg("/C").arg(&command).status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => tracing::warn!(command = %command, %status, "notify command failed"),
+            Err(e) => tracing::warn!(command = %command, error = %e, "could not run notify command"),
+        }
+    });
+}
+
+/// Run the configured hooks for changes in `chain` until the process exits.
+/// Does nothing when neither hook is set.
+pub fn spawn(config: &NotifyConfig, chain: Arc<RwLock<Blockchain>>, wallet_dir: PathBuf) {
+    if config.blocknotify.is_none() && config.walletnotify.is_none() {
+        return;
+    }
+    let config = config.clone();
+    tokio::spawn(async move {
+        let mut scanner = HookScanner::new();
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let wallets = match config.walletnotify {
+                Some(_) => wallet_addresses(&wallet_dir),
+                None => HashSet::new(),
+            };
+            let events = scanner.scan(&*chain.read().await, &wallets);
+            if let (Some(command), Some(hash)) = (&config.blocknotify, &events.new_tip) {
+                run_command(expand(command, hash));
+            }
+            if let Some(command) = &config.walletnotify {
+                for txid in &events.wallet_txids {
+                    run_command(expand(command, txid));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Block;
+    use crate::crypto::KeyPair;
+    use crate::geometry::Coord;
+    use crate::persistence::InMemoryPersistence;
+    use crate::transaction::{CoinbaseTx, TransferTx};
+
+    fn mine(chain: &mut Blockchain, txs: Vec<Transaction>) {
+        let height = chain.blocks.len() as u64;
+        let mut transactions = vec![Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [3; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        })];
+        transactions.extend(txs);
+        let block = Block::new(height, chain.blocks.last().unwrap().hash(), 0, transactions);
+        chain.apply_block(crate::miner::mine_block(block).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_reports_tip_changes_and_wallet_transactions() {
+        let alice = KeyPair::generate().unwrap();
+        let bob = [5u8; 32];
+        let mut chain =
+            Blockchain::new_with_persistence(alice.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let wallets = HashSet::from([bob]);
+        let mut scanner = HookScanner::new();
+
+        // The first scan reports nothing that came before it
+        assert_eq!(scanner.scan(&chain, &wallets), Events::default());
+        mine(&mut chain, vec![]);
+        let events = scanner.scan(&chain, &wallets);
+        assert_eq!(events.new_tip, Some(hex::encode(chain.blocks[1].hash())));
+        assert!(events.wallet_txids.is_empty());
+        assert_eq!(scanner.scan(&chain, &wallets), Events::default());
+
+        // A payment to the wallet, once from the mempool and once mined
+        let mut transfer =
+            TransferTx::new(genesis_utxo, bob, alice.address(), Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+        let transfer = Transaction::Transfer(transfer);
+        let state = chain.state.clone();
+        let domain = chain.signing_domain(chain.next_height());
+        chain.mempool.accept_transaction(transfer.clone(), &state, &domain).unwrap();
+        let events = scanner.scan(&chain, &wallets);
+        assert_eq!(events.new_tip, None);
+        assert_eq!(events.wallet_txids, vec![transfer.hash_str()]);
+        assert_eq!(scanner.scan(&chain, &wallets), Events::default());
+
+        mine(&mut chain, vec![transfer.clone()]);
+        let events = scanner.scan(&chain, &wallets);
+        assert_eq!(events.new_tip, Some(hex::encode(chain.blocks[2].hash())));
+        assert_eq!(events.wallet_txids, vec![transfer.hash_str()]);
+    }
+
+    #[test]
+    fn test_wallet_addresses_and_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = hex::encode([7u8; 32]);
+        let wallet = serde_json::json!({ "address": address });
+        std::fs::write(dir.path().join("wallet_savings.json"), wallet.to_string()).unwrap();
+        std::fs::write(dir.path().join("watchlist.json"), r#"{"address":"00"}"#).unwrap();
+        std::fs::write(dir.path().join("wallet.json"), "not json").unwrap();
+        assert_eq!(wallet_addresses(dir.path()), HashSet::from([[7u8; 32]]));
+
+        assert_eq!(expand("notify.sh %s --again %s", "ab"), "notify.sh ab --again ab");
+    }
+}
@@ -0,0 +1,272 @@
+//! Pay-by-value planning
+//!
+//! A transfer spends exactly one triangle and must leave change of at least
+//! `GEOMETRIC_TOLERANCE`. To pay an arbitrary amount the wallet therefore
+//! picks the triangles to spend and, where a triangle is much larger than
+//! the payment, subdivides it first so that only a small child is broken up
+//! and the siblings stay whole. The resulting transactions depend on each
+//! other and are submitted together as one mempool package.
+
+use crate::blockchain::{Sha256Hash, TriangleState};
+use crate::crypto::{Address, KeyPair};
+use crate::error::ChainError;
+use crate::geometry::{
+    coord_checked_add, coord_checked_sub, coord_checked_sum, Coord, Triangle, GEOMETRIC_TOLERANCE,
+};
+use crate::transaction::{SubdivisionTx, Transaction, TransferTx};
+use std::collections::HashSet;
+
+/// What the payer wants to send
+#[derive(Debug, Clone)]
+pub struct PaymentRequest {
+    pub recipient: Address,
+    pub amount: Coord,
+    /// Fee paid by every transfer in the plan (subdivisions are fee-free)
+    pub fee_per_transfer: Coord,
+    pub memo: Option<String>,
+    /// Recipient's public key to encrypt `memo` to; plaintext when `None`
+    pub memo_recipient_key: Option<Vec<u8>>,
+}
+
+/// Transactions that together pay a [`PaymentRequest`], in dependency order
+#[derive(Debug, Clone)]
+pub struct PaymentPlan {
+    pub transactions: Vec<Transaction>,
+    /// Total fees paid by the plan
+    pub fees: Coord,
+}
+
+impl PaymentPlan {
+    /// Number of subdivisions performed before paying
+    pub fn subdivisions(&self) -> usize {
+        self.transactions
+            .iter()
+            .filter(|tx| matches!(tx, Transaction::Subdivision(_)))
+            .count()
+    }
+
+    /// Sign every transaction in the plan with `keypair` for the chain
+    /// `chain_id`
+    pub fn sign(&mut self, keypair: &KeyPair, chain_id: &Sha256Hash) -> Result<(), ChainError> {
+        let public_key = keypair.public_key.serialize().to_vec();
+        for tx in &mut self.transactions {
+            match tx {
+                Transaction::Subdivision(tx) => {
+                    let signature = keypair.sign(&tx.signable_message(chain_id))?;
+                    tx.sign(signature.to_vec(), public_key.clone());
+                }
+                Transaction::Transfer(tx) => {
+                    let signature = keypair.sign(&tx.signable_message(chain_id))?;
+                    tx.sign(signature.to_vec(), public_key.clone());
+                }
+                Transaction::Register(tx) => {
+                    let signature = keypair.sign(&tx.signable_message(chain_id))?;
+                    tx.sign(signature.to_vec(), public_key.clone());
+                }
+                Transaction::Data(tx) => {
+                    let signature = keypair.sign(&tx.signable_message(chain_id))?;
+                    tx.sign(signature.to_vec(), public_key.clone());
+                }
+                Transaction::Coinbase(_) | Transaction::Announce(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plan the transactions `owner` needs to pay `request` from the triangles
+/// it holds in `state`, skipping UTXOs in `locked` (spent by pending
+/// transactions).
+///
+/// The smallest triangle that covers the payment is preferred; it is
+/// subdivided for as long as a child still covers it. If no single triangle
+/// is large enough, the payment is split across several transfers, largest
+/// triangles first.
+pub fn plan_payment(
+    state: &TriangleState,
+    owner: Address,
+    request: &PaymentRequest,
+    locked: &HashSet<Sha256Hash>,
+    nonce: u64,
+) -> Result<PaymentPlan, ChainError> {
+    if request.amount <= Coord::from_num(0) {
+        return Err(ChainError::WalletError("Payment amount must be positive".to_string()));
+    }
+
+    let mut candidates: Vec<(Sha256Hash, &Triangle, Coord)> = state
+        .utxo_set
+        .iter()
+        .filter(|(hash, triangle)| triangle.owner == owner && !locked.contains(*hash))
+        .map(|(hash, triangle)| Ok((*hash, triangle, triangle.checked_effective_value()?)))
+        .collect::<Result<_, ChainError>>()?;
+    // Deterministic order: by value, then by hash
+    candidates.sort_by(|a, b| a.2.cmp(&b.2).then(a.0.cmp(&b.0)));
+
+    let needed = coord_checked_sum([request.amount, request.fee_per_transfer, GEOMETRIC_TOLERANCE])?;
+    let mut transactions = Vec::new();
+
+    if let Some((hash, triangle, _)) = candidates.iter().find(|(_, _, value)| *value >= needed) {
+        let (input, mut subdivisions) = descend(*hash, triangle, owner, needed, nonce);
+        transactions.append(&mut subdivisions);
+        transactions.push(transfer(request, owner, input, request.amount, nonce)?);
+        return Ok(PaymentPlan {
+            transactions,
+            fees: request.fee_per_transfer,
+        });
+    }
+
+    // No single triangle suffices: pay in parts, leaving minimal change on each
+    let mut remaining = request.amount;
+    let reserve = coord_checked_add(request.fee_per_transfer, GEOMETRIC_TOLERANCE)?;
+    for (hash, _, value) in candidates.iter().rev() {
+        if remaining <= Coord::from_num(0) {
+            break;
+        }
+        let spendable = coord_checked_sub(*value, reserve)?;
+        if spendable <= Coord::from_num(0) {
+            continue;
+        }
+        let part = spendable.min(remaining);
+        transactions.push(transfer(request, owner, *hash, part, nonce)?);
+        remaining = coord_checked_sub(remaining, part)?;
+    }
+    if remaining > Coord::from_num(0) {
+        return Err(ChainError::InsufficientFunds(format!(
+            "short by {} after fees",
+            remaining
+        )));
+    }
+
+    let fees = coord_checked_sum(transactions.iter().map(|_| request.fee_per_transfer))?;
+    Ok(PaymentPlan { transactions, fees })
+}
+
+/// Subdivide `triangle` while a child still covers `needed`. Returns the UTXO
+/// to pay from and the subdivisions leading to it.
+fn descend(
+    hash: Sha256Hash,
+    triangle: &Triangle,
+    owner: Address,
+    needed: Coord,
+    nonce: u64,
+) -> (Sha256Hash, Vec<Transaction>) {
+    let mut current_hash = hash;
+    let mut current = triangle.clone();
+    let mut subdivisions = Vec::new();
+
+    // Only triangles with real area are split: the children of a value-only
+    // triangle would be identical and collide in the UTXO set.
+    while current.is_valid() && current.can_subdivide() {
+        let children = current.subdivide();
+        let child_value = children[0].effective_value();
+        if child_value < needed {
+            break;
+        }
+        let child = children[0].clone();
+        subdivisions.push(Transaction::Subdivision(SubdivisionTx::new(
+            current_hash,
+            children.to_vec(),
+            owner,
+            Coord::from_num(0),
+            nonce,
+        )));
+        current_hash = child.hash();
+        current = child;
+    }
+    (current_hash, subdivisions)
+}
+
+fn transfer(
+    request: &PaymentRequest,
+    owner: Address,
+    input: Sha256Hash,
+    amount: Coord,
+    nonce: u64,
+) -> Result<Transaction, ChainError> {
+    let mut tx = TransferTx::new(input, request.recipient, owner, amount, request.fee_per_transfer, nonce);
+    match (&request.memo, &request.memo_recipient_key) {
+        (Some(memo), Some(key)) => tx = tx.with_encrypted_memo(memo, key)?,
+        (Some(memo), None) => tx = tx.with_memo(memo.clone())?,
+        (None, _) => {}
+    }
+    Ok(Transaction::Transfer(tx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Point;
+    use crate::mempool::Mempool;
+    use crate::transaction::SigningDomain;
+
+    const CHAIN: Sha256Hash = [7; 32];
+
+    fn triangle(owner: Address, size: i32) -> Triangle {
+        Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(size), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(size)),
+            None,
+            owner,
+        )
+    }
+
+    fn request(amount: f64) -> PaymentRequest {
+        PaymentRequest {
+            recipient: [9u8; 32],
+            amount: Coord::from_num(amount),
+            fee_per_transfer: Coord::from_num(0.01),
+            memo: Some("invoice 42".to_string()),
+            memo_recipient_key: None,
+        }
+    }
+
+    #[test]
+    fn test_large_triangle_is_subdivided_before_paying() {
+        let keypair = KeyPair::generate().unwrap();
+        let owner = keypair.address();
+        let mut state = TriangleState::new();
+        let big = triangle(owner, 10); // value 50
+        state.utxo_set.insert(big.hash(), big);
+
+        let mut plan = plan_payment(&state, owner, &request(2.0), &HashSet::new(), 1).unwrap();
+        // 50 -> 16.67 -> 5.56 -> pay from the 5.56 child (1.85 would not cover it)
+        assert_eq!(plan.subdivisions(), 2);
+        assert_eq!(plan.transactions.len(), 3);
+        assert!(matches!(plan.transactions.last(), Some(Transaction::Transfer(tx)) if tx.amount == Coord::from_num(2.0)));
+
+        plan.sign(&keypair, &CHAIN).unwrap();
+        let mut mempool = Mempool::new();
+        mempool.accept_package(plan.transactions.clone(), &state, &SigningDomain::new(CHAIN)).unwrap();
+        assert_eq!(mempool.len(), 3);
+
+        // The package is also valid when mined into a single block
+        let mut applied = state.clone();
+        for tx in &plan.transactions {
+            applied.apply_transaction(tx, 1).unwrap();
+        }
+        assert_eq!(applied.get_balance(&[9u8; 32]), Coord::from_num(2.0));
+    }
+
+    #[test]
+    fn test_payment_split_across_triangles() {
+        let keypair = KeyPair::generate().unwrap();
+        let owner = keypair.address();
+        let mut state = TriangleState::new();
+        for size in [2, 3] {
+            let t = triangle(owner, size); // values 2 and 4.5
+            state.utxo_set.insert(t.hash(), t);
+        }
+
+        let mut plan = plan_payment(&state, owner, &request(6.0), &HashSet::new(), 1).unwrap();
+        assert_eq!(plan.subdivisions(), 0);
+        assert_eq!(plan.transactions.len(), 2);
+        assert_eq!(plan.fees, Coord::from_num(0.01) * 2);
+        plan.sign(&keypair, &CHAIN).unwrap();
+        Mempool::new().accept_package(plan.transactions, &state, &SigningDomain::new(CHAIN)).unwrap();
+
+        assert!(plan_payment(&state, owner, &request(6.5), &HashSet::new(), 1).is_err());
+        let locked: HashSet<Sha256Hash> = state.utxo_set.keys().copied().collect();
+        assert!(plan_payment(&state, owner, &request(1.0), &locked, 1).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Long-term per-peer statistics
+//!
+//! `/api/network/peers` shows the connections open right now. To judge the
+//! network's health over days, the node also keeps per-peer counters — time
+//! connected, bytes each way, blocks the peer delivered that became our
+//! tip, bans — and every [`SAMPLE_INTERVAL`] stores what each peer added in
+//! that interval as one [`PeerStatsSample`] row. Peers are keyed by host, so
+//! reconnections from new source ports add up.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often the counters are written to the database
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// What one peer added to the counters in the interval ending at
+/// `recorded_at`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerStatsSample {
+    /// Host of the peer
+    pub peer: String,
+    /// End of the interval, in milliseconds since the epoch
+    pub recorded_at: u64,
+    /// Time connected during the interval, summed over its connections
+    pub uptime_ms: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Relayed blocks from the peer that extended our chain
+    pub blocks: u64,
+    pub bans: u64,
+}
+
+impl PeerStatsSample {
+    fn is_empty(&self) -> bool {
+        self.uptime_ms == 0 && self.bytes_in == 0 && self.bytes_out == 0 && self.blocks == 0 && self.bans == 0
+    }
+
+    fn add(&mut self, other: &PeerStatsSample) {
+        self.uptime_ms += other.uptime_ms;
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.blocks += other.blocks;
+        self.bans += other.bans;
+    }
+}
+
+/// Traffic of one connection, counted by its reader and writer
+#[derive(Debug, Default)]
+pub struct ConnectionCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    closed_at: Mutex<Option<Instant>>,
+}
+
+impl ConnectionCounters {
+    pub fn received(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Stop counting uptime; called when the connection is dropped
+    pub fn close(&self) {
+        if let Ok(mut closed_at) = self.closed_at.lock() {
+            closed_at.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn closed_at(&self) -> Option<Instant> {
+        self.closed_at.lock().ok().and_then(|closed_at| *closed_at)
+    }
+}
+
+/// A connection and what of it was already sampled
+#[derive(Debug)]
+struct Tracked {
+    host: String,
+    counters: Arc<ConnectionCounters>,
+    /// Uptime is sampled from here on
+    since: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+/// Counters since the last sample
+#[derive(Debug, Default)]
+pub struct PeerStatsRecorder {
+    connections: Vec<Tracked>,
+    /// Blocks and bans per host
+    events: HashMap<String, (u64, u64)>,
+}
+
+impl PeerStatsRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start counting a new connection to `host`
+    pub fn connect(&mut self, host: &str) -> Arc<ConnectionCounters> {
+        let counters = Arc::new(ConnectionCounters::default());
+        self.connections.push(Tracked {
+            host: host.to_string(),
+            counters: counters.clone(),
+            since: Instant::now(),
+            bytes_in: 0,
+            bytes_out: 0,
+        });
+        counters
+    }
+
+    pub fn record_block(&mut self, host: &str) {
+        self.events.entry(host.to_string()).or_default().0 += 1;
+    }
+
+    pub fn record_ban(&mut self, host: &str) {
+        self.events.entry(host.to_string()).or_default().1 += 1;
+    }
+
+    /// What each peer added since the last call, as samples stamped
+    /// `recorded_at`. Peers without activity are left out and closed
+    /// connections are forgotten.
+    pub fn take(&mut self, now: Instant, recorded_at: u64) -> Vec<PeerStatsSample> {
+        let mut samples: BTreeMap<String, PeerStatsSample> = BTreeMap::new();
+        for tracked in &mut self.connections {
+            let until = tracked.counters.closed_at().unwrap_or(now).max(tracked.since);
+            let bytes_in = tracked.counters.bytes_in.load(Ordering::Relaxed);
+            let bytes_out = tracked.counters.bytes_out.load(Ordering::Relaxed);
+            let sample = sample_of(&mut samples, &tracked.host, recorded_at);
+            sample.uptime_ms += (until - tracked.since).as_millis() as u64;
+            sample.bytes_in += bytes_in - tracked.bytes_in;
+            sample.bytes_out += bytes_out - tracked.bytes_out;
+            tracked.since = until;
+            tracked.bytes_in = bytes_in;
+            tracked.bytes_out = bytes_out;
+        }
+        self.connections.retain(|tracked| tracked.counters.closed_at().is_none());
+        for (host, (blocks, bans)) in self.events.drain() {
+            let sample = sample_of(&mut samples, &host, recorded_at);
+            sample.blocks += blocks;
+            sample.bans += bans;
+        }
+        samples.into_values().filter(|sample| !sample.is_empty()).collect()
+    }
+}
+
+fn sample_of<'a>(
+    samples: &'a mut BTreeMap<String, PeerStatsSample>,
+    host: &str,
+    recorded_at: u64,
+) -> &'a mut PeerStatsSample {
+    samples.entry(host.to_string()).or_insert_with(|| PeerStatsSample {
+        peer: host.to_string(),
+        recorded_at,
+        ..PeerStatsSample::default()
+    })
+}
+
+/// Network-wide totals of one sample interval
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkHealthPoint {
+    pub timestamp: u64,
+    /// Peers connected at some point during the interval
+    pub peers: usize,
+    pub uptime_ms: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub blocks: u64,
+    pub bans: u64,
+}
+
+/// Sum `samples` per interval, oldest first, and per peer, longest
+/// connected first. A peer's total carries the time of its last sample.
+pub fn summarize(samples: &[PeerStatsSample]) -> (Vec<NetworkHealthPoint>, Vec<PeerStatsSample>) {
+    let mut points: BTreeMap<u64, NetworkHealthPoint> = BTreeMap::new();
+    let mut peers: HashMap<&str, PeerStatsSample> = HashMap::new();
+    for sample in samples {
+        let point = points.entry(sample.recorded_at).or_insert_with(|| NetworkHealthPoint {
+            timestamp: sample.recorded_at,
+            ..NetworkHealthPoint::default()
+        });
+        point.peers += usize::from(sample.uptime_ms > 0);
+        point.uptime_ms += sample.uptime_ms;
+        point.bytes_in += sample.bytes_in;
+        point.bytes_out += sample.bytes_out;
+        point.blocks += sample.blocks;
+        point.bans += sample.bans;
+
+        let total = peers.entry(&sample.peer).or_insert_with(|| PeerStatsSample {
+            peer: sample.peer.clone(),
+            ..PeerStatsSample::default()
+        });
+        total.add(sample);
+        total.recorded_at = total.recorded_at.max(sample.recorded_at);
+    }
+    let mut peers: Vec<_> = peers.into_values().collect();
+    peers.sort_by(|a, b| b.uptime_ms.cmp(&a.uptime_ms).then_with(|| a.peer.cmp(&b.peer)));
+    (points.into_values().collect(), peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_hold_what_each_interval_added() {
+        let mut recorder = PeerStatsRecorder::new();
+        let start = Instant::now();
+        let first = recorder.connect("10.0.0.1");
+        let second = recorder.connect("10.0.0.1");
+        first.received(100);
+        second.sent(40);
+        recorder.record_block("10.0.0.1");
+        recorder.record_ban("10.0.0.2");
+
+        let samples = recorder.take(start + Duration::from_secs(10), 1_000);
+        assert_eq!(samples.len(), 2);
+        assert_eq!((samples[0].bytes_in, samples[0].bytes_out, samples[0].blocks), (100, 40, 1));
+        assert!(samples[0].uptime_ms >= 19_000);
+        assert_eq!((samples[1].peer.as_str(), samples[1].bans, samples[1].uptime_ms), ("10.0.0.2", 1, 0));
+
+        // Only the growth since the last sample; a closed connection stops
+        // accruing uptime and is then forgotten
+        first.received(5);
+        first.close();
+        second.close();
+        let later = recorder.take(Instant::now() + Duration::from_secs(60), 2_000);
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].bytes_in, 5);
+        assert!(later[0].uptime_ms < 1_000);
+        assert!(recorder.take(Instant::now(), 3_000).is_empty());
+
+        let (points, peers) = summarize(&[samples, later].concat());
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[0].timestamp, points[0].peers, points[0].bans), (1_000, 1, 1));
+        assert_eq!(peers[0].peer, "10.0.0.1");
+        assert_eq!((peers[0].bytes_in, peers[0].recorded_at), (105, 2_000));
+    }
+}
@@ -1,13 +1,15 @@
 //! Database persistence layer for TrinityChain
 
-use crate::blockchain::{Block, BlockHeader, Blockchain, TriangleState};
+use crate::blockchain::{block_work, AddressIndex, Block, BlockHeader, BlockJournal, BlockUndo, Blockchain, ConsensusParams, ForkTracker, NameRegistry, Sha256Hash, SpentTriangle, SubdivisionIndex, TimeIndex, TimeRange, TriangleState, UtxoStats, UtxoStatsTracker, DEFAULT_DUST_THRESHOLD};
 use crate::error::ChainError;
 use crate::geometry::Triangle;
 use crate::mempool::Mempool;
+use crate::peerstats::PeerStatsSample;
 use crate::transaction::Transaction;
-use rusqlite::{params, Connection};
+use rusqlite::types::{Type, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension};
 use std::sync::Mutex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Abstraction for persistence backends. Implementations should provide
 /// atomic saving/loading of blockchain state and blocks.
@@ -18,8 +20,219 @@ pub trait Persistence: Send + Sync {
     fn save_utxo_set(&self, state: &TriangleState) -> Result<(), ChainError>;
     fn load_utxo_set(&self) -> Result<TriangleState, ChainError>;
     fn save_difficulty(&self, difficulty: u64) -> Result<(), ChainError>;
+    /// Drop every stored block above `height`, e.g. after a reorganization
+    /// switched to a shorter chain with more work.
+    fn remove_blocks_above(&self, height: u64) -> Result<(), ChainError>;
+    /// Keep triangles spent by a block (archive mode only). Spends above a
+    /// height are dropped again by `remove_blocks_above`.
+    fn save_spent_utxos(&self, spent: &[SpentTriangle]) -> Result<(), ChainError>;
+    /// Keep the undo record of an applied block. Records above a height are
+    /// dropped again by `remove_blocks_above`.
+    fn save_block_undo(&self, undo: &BlockUndo) -> Result<(), ChainError>;
+    /// Undo record of the block at `height`, if one was saved
+    fn load_block_undo(&self, height: u64) -> Result<Option<BlockUndo>, ChainError>;
+    /// Height of the stored block with header hash `hash`
+    fn block_height_by_hash(&self, hash: &Sha256Hash) -> Result<Option<u64>, ChainError>;
+    /// Reclaim the space left by deleted rows
+    fn compact(&self) -> Result<CompactStats, ChainError>;
+    /// Check the storage itself and compare the stored UTXO set with the one
+    /// produced by replaying every stored block
+    fn integrity_check(&self) -> Result<IntegrityReport, ChainError>;
+    /// Difficulty and solve time of the stored blocks stamped in `range`,
+    /// lowest height first
+    fn load_block_metrics(&self, range: TimeRange) -> Result<Vec<BlockMetric>, ChainError>;
+    /// Keep the UTXO set statistics after a block. Points above a height are
+    /// dropped again by `remove_blocks_above`.
+    fn save_utxo_stats(&self, stats: &UtxoStats) -> Result<(), ChainError>;
+    /// UTXO set statistics after each stored block stamped in `range`,
+    /// lowest height first
+    fn load_utxo_stats(&self, range: TimeRange) -> Result<Vec<UtxoStats>, ChainError>;
+    /// Keep per-peer statistics samples. They describe the network rather
+    /// than the chain, so reorganizations leave them alone.
+    fn save_peer_stats(&self, samples: &[PeerStatsSample]) -> Result<(), ChainError>;
+    /// Peer statistics samples recorded in `range`, oldest first
+    fn load_peer_stats(&self, range: TimeRange) -> Result<Vec<PeerStatsSample>, ChainError>;
 }
 
+/// Difficulty and solve time of one block, kept for history charts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct BlockMetric {
+    pub height: u64,
+    /// Block timestamp in milliseconds
+    pub timestamp: u64,
+    pub difficulty: u32,
+    /// Milliseconds since the parent's timestamp; negative if the block is
+    /// stamped before it, 0 for genesis
+    pub solve_time_ms: i64,
+}
+
+impl BlockMetric {
+    /// Metrics of `blocks`, which must be consecutive from their first one
+    pub fn series(blocks: &[Block]) -> Vec<BlockMetric> {
+        let mut parent_timestamp = None;
+        blocks
+            .iter()
+            .map(|block| {
+                let timestamp = block.header.timestamp;
+                let parent = parent_timestamp.replace(timestamp).unwrap_or(timestamp);
+                BlockMetric {
+                    height: block.header.height,
+                    timestamp,
+                    difficulty: block.header.difficulty,
+                    solve_time_ms: timestamp as i64 - parent as i64,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Storage size around a [`Persistence::compact`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactStats {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+impl CompactStats {
+    pub fn reclaimed(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Outcome of a [`Persistence::integrity_check`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// Problems reported by the storage engine itself
+    pub storage_errors: Vec<String>,
+    pub blocks_replayed: u64,
+    /// Why replaying the stored blocks failed, if it did
+    pub replay_error: Option<String>,
+    /// UTXOs the replay produced that are not stored
+    pub missing_utxos: Vec<Sha256Hash>,
+    /// Stored UTXOs the replay did not produce
+    pub unexpected_utxos: Vec<Sha256Hash>,
+    /// UTXOs stored with a different triangle than the replay produced
+    pub mismatched_utxos: Vec<Sha256Hash>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.storage_errors.is_empty()
+            && self.replay_error.is_none()
+            && self.missing_utxos.is_empty()
+            && self.unexpected_utxos.is_empty()
+            && self.mismatched_utxos.is_empty()
+    }
+
+    /// One-line description of what was found
+    pub fn summary(&self) -> String {
+        if !self.storage_errors.is_empty() {
+            return format!("storage is corrupt: {}", self.storage_errors.join("; "));
+        }
+        if let Some(error) = &self.replay_error {
+            return format!("replaying the chain failed: {}", error);
+        }
+        if self.is_ok() {
+            return format!("ok, {} blocks replayed", self.blocks_replayed);
+        }
+        format!(
+            "UTXO set differs from the replayed chain: {} missing, {} unexpected, {} mismatched",
+            self.missing_utxos.len(),
+            self.unexpected_utxos.len(),
+            self.mismatched_utxos.len()
+        )
+    }
+
+    /// Replay `blocks` from an empty state and compare the result with
+    /// `stored`
+    fn replay(&mut self, blocks: &[Block], stored: &TriangleState) {
+        let mut replayed = TriangleState::new();
+        for block in blocks {
+            for tx in &block.transactions {
+                if let Err(e) = replayed.apply_transaction(tx, block.header.height) {
+                    self.replay_error = Some(format!("Block {}: {}", block.header.height, e));
+                    return;
+                }
+            }
+            self.blocks_replayed += 1;
+        }
+        for (hash, triangle) in &replayed.utxo_set {
+            match stored.utxo_set.get(hash) {
+                None => self.missing_utxos.push(*hash),
+                Some(stored) if stored != triangle => self.mismatched_utxos.push(*hash),
+                Some(_) => {}
+            }
+        }
+        self.unexpected_utxos = stored
+            .utxo_set
+            .keys()
+            .filter(|hash| !replayed.utxo_set.contains_key(*hash))
+            .copied()
+            .collect();
+        for hashes in [&mut self.missing_utxos, &mut self.unexpected_utxos, &mut self.mismatched_utxos] {
+            hashes.sort();
+        }
+    }
+}
+
+/// What [`Database::recover`] found and repaired at startup
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Height of the highest stored block before recovery
+    pub stored_tip: Option<u64>,
+    /// Height of the block the stored UTXO set was saved with, `None` for
+    /// databases written before it was recorded
+    pub utxo_height: Option<u64>,
+    /// Height both agree on afterwards
+    pub recovered_height: Option<u64>,
+    /// Blocks dropped because their UTXO changes were never saved
+    pub dropped_blocks: u64,
+    /// Blocks taken out of the UTXO set with their undo records because
+    /// the blocks themselves were already removed
+    pub reverted_blocks: u64,
+    /// Blocks replayed to rebuild the UTXO set when undo records could not
+    pub replayed_blocks: u64,
+}
+
+impl RecoveryReport {
+    /// Whether the database was already consistent
+    pub fn is_clean(&self) -> bool {
+        self.dropped_blocks == 0 && self.reverted_blocks == 0 && self.replayed_blocks == 0
+    }
+
+    /// One-line description of what was done
+    pub fn summary(&self) -> String {
+        let height = |height: Option<u64>| height.map_or("none".to_string(), |height| height.to_string());
+        if self.is_clean() {
+            return format!("consistent at height {}", height(self.recovered_height));
+        }
+        let mut steps = Vec::new();
+        if self.dropped_blocks > 0 {
+            steps.push(format!("dropped {} block(s) whose UTXO changes were never saved", self.dropped_blocks));
+        }
+        if self.reverted_blocks > 0 {
+            steps.push(format!("reverted {} removed block(s) from the UTXO set with undo records", self.reverted_blocks));
+        }
+        if self.replayed_blocks > 0 {
+            steps.push(format!("rebuilt the UTXO set by replaying {} block(s)", self.replayed_blocks));
+        }
+        format!(
+            "blocks ended at height {} but the UTXO set at {}; {}; now consistent at height {}",
+            height(self.stored_tip),
+            height(self.utxo_height),
+            steps.join(", "),
+            height(self.recovered_height)
+        )
+    }
+}
+
+/// `blocks.tx_encoding` of rows whose transactions are JSON text, as written
+/// by older versions
+const TX_ENCODING_JSON: i64 = 0;
+/// `blocks.tx_encoding` of rows whose transactions are bincode, the encoding
+/// used between peers
+const TX_ENCODING_BINCODE: i64 = 1;
+
 pub struct Database {
     conn: Mutex<Connection>,
 }
@@ -38,12 +251,52 @@ impl Database {
                 difficulty INTEGER NOT NULL,
                 nonce INTEGER NOT NULL,
                 merkle_root BLOB NOT NULL,
-                transactions TEXT NOT NULL
+                transactions BLOB NOT NULL,
+                version INTEGER NOT NULL DEFAULT 0,
+                chain_work TEXT,
+                tx_encoding INTEGER NOT NULL DEFAULT 0
             )",
             [],
         )
         .map_err(|e| ChainError::DatabaseError(format!("Failed to create blocks table: {}", e)))?;
 
+        // Hash lookups follow previous_hash links without scanning the table
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_blocks_hash ON blocks (hash)", [])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to create block hash index: {}", e)))?;
+
+        // Databases created before header versions were stored lack the column
+        let has_version: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('blocks') WHERE name = 'version'")
+            .and_then(|mut stmt| stmt.exists([]))
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to inspect blocks table: {}", e)))?;
+        if !has_version {
+            conn.execute("ALTER TABLE blocks ADD COLUMN version INTEGER NOT NULL DEFAULT 0", [])
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to migrate blocks table: {}", e)))?;
+        }
+
+        // Cumulative work is stored as decimal text since it exceeds SQLite's
+        // 64-bit integers; older databases get it computed once here.
+        let has_chain_work: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('blocks') WHERE name = 'chain_work'")
+            .and_then(|mut stmt| stmt.exists([]))
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to inspect blocks table: {}", e)))?;
+        if !has_chain_work {
+            conn.execute("ALTER TABLE blocks ADD COLUMN chain_work TEXT", [])
+                .and_then(|_| Self::backfill_chain_work(&conn))
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to migrate blocks table: {}", e)))?;
+        }
+
+        // Rows written before transactions were stored as bincode keep their
+        // JSON text and are told apart by the encoding column
+        let has_tx_encoding: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('blocks') WHERE name = 'tx_encoding'")
+            .and_then(|mut stmt| stmt.exists([]))
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to inspect blocks table: {}", e)))?;
+        if !has_tx_encoding {
+            conn.execute("ALTER TABLE blocks ADD COLUMN tx_encoding INTEGER NOT NULL DEFAULT 0", [])
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to migrate blocks table: {}", e)))?;
+        }
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS utxo_set (
                 hash BLOB PRIMARY KEY,
@@ -55,6 +308,89 @@ impl Database {
             ChainError::DatabaseError(format!("Failed to create utxo_set table: {}", e))
         })?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS spent_utxos (
+                hash BLOB PRIMARY KEY,
+                triangle_data TEXT NOT NULL,
+                created_height INTEGER NOT NULL,
+                spent_height INTEGER NOT NULL,
+                spending_tx BLOB NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to create spent_utxos table: {}", e))
+        })?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS block_undo (
+                height INTEGER PRIMARY KEY,
+                undo_data TEXT NOT NULL
+            )",
+            [],
+        )
+        .map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to create block_undo table: {}", e))
+        })?;
+
+        // One small row per block so difficulty charts need no block bodies;
+        // databases from before the table existed are filled in once here
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS block_metrics (
+                height INTEGER PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                difficulty INTEGER NOT NULL,
+                solve_time INTEGER NOT NULL
+            )",
+            [],
+        )
+        .and_then(|_| conn.execute("CREATE INDEX IF NOT EXISTS idx_block_metrics_timestamp ON block_metrics (timestamp)", []))
+        .and_then(|_| {
+            conn.execute(
+                "INSERT INTO block_metrics (height, timestamp, difficulty, solve_time)
+                 SELECT b.height, b.timestamp, b.difficulty, b.timestamp - COALESCE(p.timestamp, b.timestamp)
+                 FROM blocks b LEFT JOIN blocks p ON p.height = b.height - 1
+                 WHERE NOT EXISTS (SELECT 1 FROM block_metrics)",
+                [],
+            )
+        })
+        .map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to create block_metrics table: {}", e))
+        })?;
+
+        // Only recorded from the first block applied after the table
+        // existed: the history needs the UTXO set at every height
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS utxo_stats (
+                height INTEGER PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                stats TEXT NOT NULL
+            )",
+            [],
+        )
+        .and_then(|_| conn.execute("CREATE INDEX IF NOT EXISTS idx_utxo_stats_timestamp ON utxo_stats (timestamp)", []))
+        .map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to create utxo_stats table: {}", e))
+        })?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS peers_stats (
+                peer TEXT NOT NULL,
+                recorded_at INTEGER NOT NULL,
+                uptime_ms INTEGER NOT NULL,
+                bytes_in INTEGER NOT NULL,
+                bytes_out INTEGER NOT NULL,
+                blocks INTEGER NOT NULL,
+                bans INTEGER NOT NULL,
+                PRIMARY KEY (peer, recorded_at)
+            )",
+            [],
+        )
+        .and_then(|_| conn.execute("CREATE INDEX IF NOT EXISTS idx_peers_stats_recorded_at ON peers_stats (recorded_at)", []))
+        .map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to create peers_stats table: {}", e))
+        })?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS metadata (
                 key TEXT PRIMARY KEY,
@@ -69,15 +405,51 @@ impl Database {
         Ok(Database { conn: Mutex::new(conn) })
     }
 
-    pub fn save_block(&self, block: &Block) -> Result<(), ChainError> {
-        let transactions_json = serde_json::to_string(&block.transactions).map_err(|e| {
+    fn backfill_chain_work(conn: &Connection) -> rusqlite::Result<()> {
+        let rows: Vec<(i64, i64)> = conn
+            .prepare("SELECT height, difficulty FROM blocks ORDER BY height ASC")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        let mut total = 0u128;
+        for (height, difficulty) in rows {
+            total = total.saturating_add(block_work(difficulty as u32));
+            conn.execute(
+                "UPDATE blocks SET chain_work = ?1 WHERE height = ?2",
+                params![total.to_string(), height],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Cumulative work of the chain ending in `block`, from the stored work
+    /// of its parent
+    fn chain_work_of(conn: &Connection, block: &Block) -> rusqlite::Result<u128> {
+        let parent_work = match block.header.height.checked_sub(1) {
+            Some(parent) => conn
+                .query_row(
+                    "SELECT chain_work FROM blocks WHERE height = ?1",
+                    params![parent as i64],
+                    |row| row.get::<_, Option<String>>(0),
+                )
+                .optional()?
+                .flatten()
+                .and_then(|work| work.parse::<u128>().ok())
+                .unwrap_or(0),
+            None => 0,
+        };
+        Ok(parent_work.saturating_add(block.header.work()))
+    }
+
+    /// Insert or replace `block`, with its transactions in bincode
+    fn insert_block(conn: &Connection, block: &Block) -> Result<(), ChainError> {
+        let transactions = bincode::serialize(&block.transactions).map_err(|e| {
             ChainError::DatabaseError(format!("Failed to serialize transactions: {}", e))
         })?;
-
-        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let chain_work = Self::chain_work_of(conn, block)
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to read chain work: {}", e)))?;
         conn.execute(
-            "INSERT OR REPLACE INTO blocks (height, hash, previous_hash, timestamp, difficulty, nonce, merkle_root, transactions)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT OR REPLACE INTO blocks (height, hash, previous_hash, timestamp, difficulty, nonce, merkle_root, transactions, version, chain_work, tx_encoding)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 block.header.height as i64,
                 block.hash().to_vec(),
@@ -86,21 +458,24 @@ impl Database {
                 block.header.difficulty as i64,
                 block.header.nonce as i64,
                 block.header.merkle_root.to_vec(),
-                transactions_json,
+                transactions,
+                block.header.version as i64,
+                chain_work.to_string(),
+                TX_ENCODING_BINCODE,
             ],
         ).map_err(|e| ChainError::DatabaseError(format!("Failed to save block: {}", e)))?;
-
+        conn.execute(
+            "INSERT OR REPLACE INTO block_metrics (height, timestamp, difficulty, solve_time)
+             SELECT ?1, ?2, ?3, ?2 - COALESCE((SELECT timestamp FROM blocks WHERE height = ?1 - 1), ?2)",
+            params![block.header.height as i64, block.header.timestamp as i64, block.header.difficulty as i64],
+        )
+        .map_err(|e| ChainError::DatabaseError(format!("Failed to save block metrics: {}", e)))?;
         Ok(())
     }
 
-    pub fn save_utxo_set(&self, state: &TriangleState) -> Result<(), ChainError> {
-        // Use a transaction for atomic UTXO set update
-        let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
-        let tx = conn_guard.unchecked_transaction().map_err(|e| {
-            ChainError::DatabaseError(format!("Failed to start transaction: {}", e))
-        })?;
-
-        tx.execute("DELETE FROM utxo_set", [])
+    /// Replace the stored UTXO set with the one in `state`
+    fn write_utxo_set(conn: &Connection, state: &TriangleState) -> Result<(), ChainError> {
+        conn.execute("DELETE FROM utxo_set", [])
             .map_err(|e| ChainError::DatabaseError(format!("Failed to clear utxo_set: {}", e)))?;
 
         for (hash, triangle) in &state.utxo_set {
@@ -108,12 +483,52 @@ impl Database {
                 ChainError::DatabaseError(format!("Failed to serialize triangle: {}", e))
             })?;
 
-            tx.execute(
+            conn.execute(
                 "INSERT INTO utxo_set (hash, triangle_data) VALUES (?1, ?2)",
                 params![hash.to_vec(), triangle_json],
             )
             .map_err(|e| ChainError::DatabaseError(format!("Failed to save UTXO: {}", e)))?;
         }
+        Ok(())
+    }
+
+    /// Record the block the stored UTXO set belongs to, so an interrupted
+    /// write can be told apart at startup
+    fn write_utxo_tip(conn: &Connection, height: u64, hash: &Sha256Hash) -> Result<(), ChainError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO metadata (key, value) VALUES ('utxo_tip', ?1)",
+            params![format!("{}:{}", height, hex::encode(hash))],
+        )
+        .map_err(|e| ChainError::DatabaseError(format!("Failed to save UTXO tip: {}", e)))?;
+        Ok(())
+    }
+
+    /// Block the stored UTXO set belongs to, if recorded
+    fn read_utxo_tip(conn: &Connection) -> Result<Option<(u64, Sha256Hash)>, ChainError> {
+        let value: Option<String> = conn
+            .query_row("SELECT value FROM metadata WHERE key = 'utxo_tip'", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to read UTXO tip: {}", e)))?;
+        Ok(value.and_then(|value| {
+            let (height, hash) = value.split_once(':')?;
+            let hash: Sha256Hash = hex::decode(hash).ok()?.try_into().ok()?;
+            Some((height.parse().ok()?, hash))
+        }))
+    }
+
+    pub fn save_block(&self, block: &Block) -> Result<(), ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        Self::insert_block(&conn, block)
+    }
+
+    pub fn save_utxo_set(&self, state: &TriangleState) -> Result<(), ChainError> {
+        // Use a transaction for atomic UTXO set update
+        let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let tx = conn_guard.unchecked_transaction().map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to start transaction: {}", e))
+        })?;
+
+        Self::write_utxo_set(&tx, state)?;
 
         tx.commit().map_err(|e| {
             ChainError::DatabaseError(format!("Failed to commit transaction: {}", e))
@@ -169,6 +584,203 @@ impl Database {
         Ok(())
     }
 
+    pub fn remove_blocks_above(&self, height: u64) -> Result<(), ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        conn.execute("DELETE FROM blocks WHERE height > ?1", params![height as i64])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to remove blocks: {}", e)))?;
+        conn.execute("DELETE FROM spent_utxos WHERE spent_height > ?1", params![height as i64])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to remove spent UTXOs: {}", e)))?;
+        conn.execute("DELETE FROM block_undo WHERE height > ?1", params![height as i64])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to remove undo records: {}", e)))?;
+        conn.execute("DELETE FROM block_metrics WHERE height > ?1", params![height as i64])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to remove block metrics: {}", e)))?;
+        conn.execute("DELETE FROM utxo_stats WHERE height > ?1", params![height as i64])
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to remove UTXO statistics: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn load_block_metrics(&self, range: TimeRange) -> Result<Vec<BlockMetric>, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let bound = |millis: u64| i64::try_from(millis).unwrap_or(i64::MAX);
+        let mut stmt = conn
+            .prepare(
+                "SELECT height, timestamp, difficulty, solve_time FROM block_metrics
+                 WHERE timestamp BETWEEN ?1 AND ?2 ORDER BY height ASC",
+            )
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let metrics = stmt
+            .query_map(params![bound(range.from), bound(range.to)], |row| {
+                Ok(BlockMetric {
+                    height: row.get::<_, i64>(0)? as u64,
+                    timestamp: row.get::<_, i64>(1)? as u64,
+                    difficulty: row.get::<_, i64>(2)? as u32,
+                    solve_time_ms: row.get(3)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to load block metrics: {}", e)))?;
+        Ok(metrics)
+    }
+
+    pub fn save_utxo_stats(&self, stats: &UtxoStats) -> Result<(), ChainError> {
+        let stats_json = serde_json::to_string(stats)
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to serialize UTXO statistics: {}", e)))?;
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO utxo_stats (height, timestamp, stats) VALUES (?1, ?2, ?3)",
+            params![stats.height as i64, stats.timestamp as i64, stats_json],
+        )
+        .map_err(|e| ChainError::DatabaseError(format!("Failed to save UTXO statistics: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn load_utxo_stats(&self, range: TimeRange) -> Result<Vec<UtxoStats>, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let bound = |millis: u64| i64::try_from(millis).unwrap_or(i64::MAX);
+        let mut stmt = conn
+            .prepare("SELECT stats FROM utxo_stats WHERE timestamp BETWEEN ?1 AND ?2 ORDER BY height ASC")
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let rows = stmt
+            .query_map(params![bound(range.from), bound(range.to)], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to load UTXO statistics: {}", e)))?;
+        rows.iter()
+            .map(|json| {
+                serde_json::from_str(json)
+                    .map_err(|e| ChainError::DatabaseError(format!("Failed to deserialize UTXO statistics: {}", e)))
+            })
+            .collect()
+    }
+
+    pub fn save_peer_stats(&self, samples: &[PeerStatsSample]) -> Result<(), ChainError> {
+        let mut conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to start transaction: {}", e)))?;
+        {
+            let mut stmt = tx
+                .prepare(
+                    "INSERT OR REPLACE INTO peers_stats (peer, recorded_at, uptime_ms, bytes_in, bytes_out, blocks, bans)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+            for sample in samples {
+                stmt.execute(params![
+                    sample.peer,
+                    sample.recorded_at as i64,
+                    sample.uptime_ms as i64,
+                    sample.bytes_in as i64,
+                    sample.bytes_out as i64,
+                    sample.blocks as i64,
+                    sample.bans as i64,
+                ])
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to save peer statistics: {}", e)))?;
+            }
+        }
+        tx.commit()
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to commit peer statistics: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn load_peer_stats(&self, range: TimeRange) -> Result<Vec<PeerStatsSample>, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let bound = |millis: u64| i64::try_from(millis).unwrap_or(i64::MAX);
+        let mut stmt = conn
+            .prepare(
+                "SELECT peer, recorded_at, uptime_ms, bytes_in, bytes_out, blocks, bans FROM peers_stats
+                 WHERE recorded_at BETWEEN ?1 AND ?2 ORDER BY recorded_at ASC, peer ASC",
+            )
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to prepare statement: {}", e)))?;
+        let samples = stmt
+            .query_map(params![bound(range.from), bound(range.to)], |row| {
+                Ok(PeerStatsSample {
+                    peer: row.get(0)?,
+                    recorded_at: row.get::<_, i64>(1)? as u64,
+                    uptime_ms: row.get::<_, i64>(2)? as u64,
+                    bytes_in: row.get::<_, i64>(3)? as u64,
+                    bytes_out: row.get::<_, i64>(4)? as u64,
+                    blocks: row.get::<_, i64>(5)? as u64,
+                    bans: row.get::<_, i64>(6)? as u64,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to load peer statistics: {}", e)))?;
+        Ok(samples)
+    }
+
+    pub fn save_block_undo(&self, undo: &BlockUndo) -> Result<(), ChainError> {
+        let undo_json = serde_json::to_string(undo)
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to serialize undo record: {}", e)))?;
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        conn.execute(
+            "INSERT OR REPLACE INTO block_undo (height, undo_data) VALUES (?1, ?2)",
+            params![undo.height as i64, undo_json],
+        )
+        .map_err(|e| ChainError::DatabaseError(format!("Failed to save undo record: {}", e)))?;
+        Ok(())
+    }
+
+    pub fn load_block_undo(&self, height: u64) -> Result<Option<BlockUndo>, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let undo_json: Option<String> = conn
+            .query_row(
+                "SELECT undo_data FROM block_undo WHERE height = ?1",
+                params![height as i64],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to load undo record: {}", e)))?;
+        undo_json
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| ChainError::DatabaseError(format!("Failed to deserialize undo record: {}", e)))
+            })
+            .transpose()
+    }
+
+    pub fn block_height_by_hash(&self, hash: &Sha256Hash) -> Result<Option<u64>, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        conn.query_row(
+            "SELECT height FROM blocks WHERE hash = ?1",
+            params![hash.to_vec()],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map(|height| height.map(|height| height as u64))
+        .map_err(|e| ChainError::DatabaseError(format!("Failed to look up block hash: {}", e)))
+    }
+
+    pub fn save_spent_utxos(&self, spent: &[SpentTriangle]) -> Result<(), ChainError> {
+        let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let tx = conn_guard.unchecked_transaction().map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to start transaction: {}", e))
+        })?;
+
+        for record in spent {
+            let triangle_json = serde_json::to_string(&record.triangle).map_err(|e| {
+                ChainError::DatabaseError(format!("Failed to serialize triangle: {}", e))
+            })?;
+            tx.execute(
+                "INSERT OR REPLACE INTO spent_utxos (hash, triangle_data, created_height, spent_height, spending_tx)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    record.utxo.to_vec(),
+                    triangle_json,
+                    record.created_height as i64,
+                    record.spent_height as i64,
+                    record.spending_tx.to_vec(),
+                ],
+            )
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to save spent UTXO: {}", e)))?;
+        }
+
+        tx.commit().map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to commit transaction: {}", e))
+        })?;
+
+        Ok(())
+    }
+
     /// Atomically saves a block and the associated blockchain state
     /// This ensures database consistency by wrapping all operations in a transaction
     pub fn save_blockchain_state(
@@ -183,40 +795,11 @@ impl Database {
         })?;
 
         // Save block
-        let transactions_json = serde_json::to_string(&block.transactions).map_err(|e| {
-            ChainError::DatabaseError(format!("Failed to serialize transactions: {}", e))
-        })?;
+        Self::insert_block(&tx, block)?;
 
-        tx.execute(
-            "INSERT OR REPLACE INTO blocks (height, hash, previous_hash, timestamp, difficulty, nonce, merkle_root, transactions)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![
-                block.header.height as i64,
-                block.hash().to_vec(),
-                block.header.previous_hash.to_vec(),
-                block.header.timestamp,
-                block.header.difficulty as i64,
-                block.header.nonce as i64,
-                block.header.merkle_root.to_vec(),
-                transactions_json,
-            ],
-        ).map_err(|e| ChainError::DatabaseError(format!("Failed to save block: {}", e)))?;
-
-        // Save UTXO set
-        tx.execute("DELETE FROM utxo_set", [])
-            .map_err(|e| ChainError::DatabaseError(format!("Failed to clear utxo_set: {}", e)))?;
-
-        for (hash, triangle) in &state.utxo_set {
-            let triangle_json = serde_json::to_string(triangle).map_err(|e| {
-                ChainError::DatabaseError(format!("Failed to serialize triangle: {}", e))
-            })?;
-
-            tx.execute(
-                "INSERT INTO utxo_set (hash, triangle_data) VALUES (?1, ?2)",
-                params![hash.to_vec(), triangle_json],
-            )
-            .map_err(|e| ChainError::DatabaseError(format!("Failed to save UTXO: {}", e)))?;
-        }
+        // Save UTXO set, marked with the block it belongs to
+        Self::write_utxo_set(&tx, state)?;
+        Self::write_utxo_tip(&tx, block.header.height, &block.hash())?;
 
         // Save difficulty
         tx.execute(
@@ -233,18 +816,78 @@ impl Database {
         Ok(())
     }
 
-    pub fn load_blockchain(&self) -> Result<Blockchain, ChainError> {
-        let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
-        let mut stmt = conn_guard.prepare(
-            "SELECT height, previous_hash, timestamp, difficulty, nonce, merkle_root, transactions
+    /// Rebuild the database file without the free pages left by the
+    /// delete-and-rewrite of the UTXO set, then refresh query statistics
+    pub fn compact(&self) -> Result<CompactStats, ChainError> {
+        let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let size = |conn: &Connection| {
+            conn.query_row(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|bytes| bytes as u64)
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to read database size: {}", e)))
+        };
+        let bytes_before = size(&conn)?;
+        conn.execute_batch("VACUUM; PRAGMA optimize;")
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to compact database: {}", e)))?;
+        Ok(CompactStats {
+            bytes_before,
+            bytes_after: size(&conn)?,
+        })
+    }
+
+    /// Run SQLite's `PRAGMA integrity_check`, then replay the stored blocks
+    /// and compare the result with the stored UTXO set. The replay is
+    /// skipped when SQLite already reports corruption.
+    pub fn integrity_check(&self) -> Result<IntegrityReport, ChainError> {
+        let mut report = IntegrityReport::default();
+        let blocks = {
+            let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+            report.storage_errors = conn
+                .prepare("PRAGMA integrity_check")
+                .and_then(|mut stmt| {
+                    stmt.query_map([], |row| row.get::<_, String>(0))?
+                        .collect::<rusqlite::Result<Vec<String>>>()
+                })
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to run integrity check: {}", e)))?
+                .into_iter()
+                .filter(|line| line != "ok")
+                .collect();
+            if !report.storage_errors.is_empty() {
+                return Ok(report);
+            }
+            Self::load_blocks(&conn)?.0
+        };
+        let stored = self.load_utxo_set()?;
+        report.replay(&blocks, &stored);
+        Ok(report)
+    }
+
+    /// Transactions of a stored block in either encoding
+    fn decode_transactions(
+        data: ValueRef<'_>,
+        encoding: i64,
+    ) -> Result<Vec<Transaction>, Box<dyn std::error::Error + Send + Sync>> {
+        match encoding {
+            TX_ENCODING_JSON => Ok(serde_json::from_str(data.as_str()?)?),
+            TX_ENCODING_BINCODE => Ok(bincode::deserialize(data.as_blob()?)?),
+            other => Err(format!("Unknown transaction encoding {}", other).into()),
+        }
+    }
+
+    /// Stored blocks in height order with their stored cumulative work
+    fn load_blocks(conn: &Connection) -> Result<(Vec<Block>, Vec<Option<u128>>), ChainError> {
+        let mut stmt = conn.prepare(
+            "SELECT height, previous_hash, timestamp, difficulty, nonce, merkle_root, transactions, version, chain_work, tx_encoding
              FROM blocks ORDER BY height ASC"
         ).map_err(|e| ChainError::DatabaseError(format!("Failed to prepare query: {}", e)))?;
 
         let blocks_iter = stmt
             .query_map([], |row| {
-                let transactions_json: String = row.get(6)?;
-                let transactions: Vec<Transaction> = serde_json::from_str(&transactions_json)
-                    .map_err(|_e| rusqlite::Error::InvalidQuery)?;
+                let transactions = Self::decode_transactions(row.get_ref(6)?, row.get(9)?)
+                    .map_err(|e| rusqlite::Error::FromSqlConversionFailure(6, Type::Blob, e))?;
 
                 let height: i64 = row.get(0)?;
                 let timestamp: i64 = row.get(2)?;
@@ -252,13 +895,15 @@ impl Database {
                 let nonce: i64 = row.get(4)?;
                 let previous_hash_vec: Vec<u8> = row.get(1)?;
                 let merkle_root_vec: Vec<u8> = row.get(5)?;
+                let version: i64 = row.get(7)?;
+                let chain_work: Option<String> = row.get(8)?;
 
                 let mut previous_hash = [0u8; 32];
                 previous_hash.copy_from_slice(&previous_hash_vec);
                 let mut merkle_root = [0u8; 32];
                 merkle_root.copy_from_slice(&merkle_root_vec);
 
-                Ok(Block {
+                let block = Block {
                     header: BlockHeader {
                         height: height as u64,
                         previous_hash,
@@ -266,63 +911,167 @@ impl Database {
                         difficulty: difficulty as u32,
                         nonce: nonce as u64,
                         merkle_root,
+                        version: version as u32,
                     },
                     transactions,
-                })
+                };
+                Ok((block, chain_work.and_then(|work| work.parse::<u128>().ok())))
             })
             .map_err(|e| ChainError::DatabaseError(format!("Failed to query blocks: {}", e)))?;
 
         let mut blocks = Vec::new();
+        let mut stored_work = Vec::new();
         for block_result in blocks_iter {
-            blocks.push(
-                block_result.map_err(|e| {
-                    ChainError::DatabaseError(format!("Failed to load block: {}", e))
-                })?,
-            );
+            let (block, work) = block_result.map_err(|e| {
+                ChainError::DatabaseError(format!("Failed to load block: {}", e))
+            })?;
+            blocks.push(block);
+            stored_work.push(work);
         }
+        Ok((blocks, stored_work))
+    }
 
-        if blocks.is_empty() {
-            return Blockchain::new([0; 32], 0);
+    /// Bring the stored blocks and UTXO set back in line after an
+    /// interrupted write, before the chain is loaded.
+    ///
+    /// Blocks above the one the UTXO set was saved with are dropped. A UTXO
+    /// set saved with a block that is no longer stored is rolled back to
+    /// the stored tip with the undo records left above it, or rebuilt by
+    /// replaying the stored blocks when those are gone; a block that fails
+    /// to replay is dropped with everything above it. Undo records and
+    /// other per-block rows above the final tip are removed.
+    pub fn recover(&self) -> Result<RecoveryReport, ChainError> {
+        let (hashes, utxo_tip) = {
+            let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+            let hashes: Vec<(u64, Sha256Hash)> = conn
+                .prepare("SELECT height, hash FROM blocks ORDER BY height ASC")
+                .and_then(|mut stmt| {
+                    stmt.query_map([], |row| {
+                        let hash: Vec<u8> = row.get(1)?;
+                        let mut fixed = [0u8; 32];
+                        fixed.copy_from_slice(&hash);
+                        Ok((row.get::<_, i64>(0)? as u64, fixed))
+                    })?
+                    .collect()
+                })
+                .map_err(|e| ChainError::DatabaseError(format!("Failed to read block hashes: {}", e)))?;
+            (hashes, Self::read_utxo_tip(&conn)?)
+        };
+        let mut report = RecoveryReport {
+            stored_tip: hashes.last().map(|(height, _)| *height),
+            utxo_height: utxo_tip.map(|(height, _)| height),
+            ..RecoveryReport::default()
+        };
+        report.recovered_height = report.stored_tip;
+        let (Some(tip), Some((utxo_height, utxo_hash))) = (report.stored_tip, utxo_tip) else {
+            // Nothing stored, or written before the UTXO tip was recorded
+            return Ok(report);
+        };
+        let stored_hash = |height: u64| hashes.iter().find(|(h, _)| *h == height).map(|(_, hash)| *hash);
+
+        if stored_hash(utxo_height) == Some(utxo_hash) {
+            // Blocks were stored past the UTXO set, or it is consistent
+            report.dropped_blocks = tip - utxo_height;
+            report.recovered_height = Some(utxo_height);
+        } else if let Some(undo) = self.undo_records_between(tip, utxo_height)? {
+            let mut state = self.load_utxo_set()?;
+            state.rebuild_address_balances();
+            for record in &undo {
+                state.revert(record)?;
+            }
+            self.save_utxo_tip_state(tip, &stored_hash(tip).expect("tip is stored"), &state)?;
+            report.reverted_blocks = undo.len() as u64;
+        } else {
+            let blocks = {
+                let conn = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+                Self::load_blocks(&conn)?.0
+            };
+            let mut state = TriangleState::new();
+            let mut last = None;
+            'blocks: for block in &blocks {
+                let mut next = state.clone();
+                for tx in &block.transactions {
+                    if next.apply_transaction(tx, block.header.height).is_err() {
+                        break 'blocks;
+                    }
+                }
+                state = next;
+                last = Some(block);
+                report.replayed_blocks += 1;
+            }
+            let Some(last) = last else {
+                return Err(ChainError::DatabaseError(
+                    "Stored chain cannot be replayed from genesis; resync from scratch".to_string(),
+                ));
+            };
+            report.dropped_blocks = tip - last.header.height;
+            report.recovered_height = Some(last.header.height);
+            self.save_utxo_tip_state(last.header.height, &last.hash(), &state)?;
         }
 
-        let mut utxo_set = HashMap::new();
-        let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
-        let mut stmt = conn_guard
-            .prepare("SELECT hash, triangle_data FROM utxo_set")
-            .map_err(|e| {
-                ChainError::DatabaseError(format!("Failed to prepare UTXO query: {}", e))
-            })?;
-
-        let utxo_iter = stmt
-            .query_map([], |row| {
-                let hash_vec: Vec<u8> = row.get(0)?;
-                let mut hash = [0u8; 32];
-                hash.copy_from_slice(&hash_vec);
-                let triangle_json: String = row.get(1)?;
-                let triangle: Triangle = serde_json::from_str(&triangle_json)
-                    .map_err(|_| rusqlite::Error::InvalidQuery)?;
-                Ok((hash, triangle))
-            })
-            .map_err(|e| ChainError::DatabaseError(format!("Failed to query UTXOs: {}", e)))?;
+        // Also clears undo, spent and metric rows left above the tip
+        if let Some(height) = report.recovered_height {
+            self.remove_blocks_above(height)?;
+        }
+        Ok(report)
+    }
 
-        for utxo_result in utxo_iter {
-            let (hash, triangle) = utxo_result
-                .map_err(|e| ChainError::DatabaseError(format!("Failed to load UTXO: {}", e)))?;
-            utxo_set.insert(hash, triangle);
+    /// Undo records of every height from `to` down to just above `tip`,
+    /// newest first, or `None` if any is missing
+    fn undo_records_between(&self, tip: u64, to: u64) -> Result<Option<Vec<BlockUndo>>, ChainError> {
+        if to <= tip {
+            return Ok(None);
         }
+        let mut records = Vec::new();
+        for height in (tip + 1..=to).rev() {
+            match self.load_block_undo(height)? {
+                Some(undo) => records.push(undo),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(records))
+    }
 
-        // Load difficulty from metadata, but verify against actual blocks
+    /// Replace the UTXO set with `state`, belonging to the stored block at
+    /// `height`
+    fn save_utxo_tip_state(&self, height: u64, hash: &Sha256Hash, state: &TriangleState) -> Result<(), ChainError> {
         let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
-        let metadata_difficulty: u32 = conn_guard
-            .query_row(
-                "SELECT value FROM metadata WHERE key = 'difficulty'",
-                [],
-                |row| {
-                    let val: String = row.get(0)?;
-                    Ok(val.parse::<u32>().unwrap_or(2))
-                },
-            )
-            .unwrap_or(2);
+        let tx = conn_guard.unchecked_transaction().map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to start transaction: {}", e))
+        })?;
+        Self::write_utxo_set(&tx, state)?;
+        Self::write_utxo_tip(&tx, height, hash)?;
+        tx.commit().map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to commit transaction: {}", e))
+        })?;
+        Ok(())
+    }
+
+    pub fn load_blockchain(&self) -> Result<Blockchain, ChainError> {
+        // Each lock is released before the next query takes it again
+        let (blocks, stored_work, metadata_difficulty) = {
+            let conn_guard = self.conn.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+            let (blocks, stored_work) = Self::load_blocks(&conn_guard)?;
+            let metadata_difficulty: u32 = conn_guard
+                .query_row(
+                    "SELECT value FROM metadata WHERE key = 'difficulty'",
+                    [],
+                    |row| {
+                        let val: String = row.get(0)?;
+                        Ok(val.parse::<u32>().unwrap_or(2))
+                    },
+                )
+                .unwrap_or(2);
+            (blocks, stored_work, metadata_difficulty)
+        };
+        let chain_work = stored_work
+            .into_iter()
+            .collect::<Option<Vec<u128>>>()
+            .unwrap_or_else(|| Blockchain::cumulative_work(&blocks));
+
+        if blocks.is_empty() {
+            return Blockchain::new([0; 32], 0);
+        }
 
         // IMPORTANT: Use the difficulty from the most recent block as source of truth
         // The metadata might be stale due to crashes or non-atomic writes
@@ -332,15 +1081,12 @@ impl Database {
             .unwrap_or(2);
 
         // If there's a mismatch, warn and use the actual block difficulty
-        let difficulty = if metadata_difficulty != actual_difficulty && !blocks.is_empty() {
+        let difficulty = if metadata_difficulty != actual_difficulty {
             eprintln!("⚠️  Warning: Metadata difficulty ({}) doesn't match last block difficulty ({}). Using block data.",
                       metadata_difficulty, actual_difficulty);
             eprintln!("   Updating metadata to match...");
             // Fix the metadata - errors here are non-critical since we're using actual_difficulty anyway
-            if let Err(e) = conn_guard.execute(
-                "INSERT OR REPLACE INTO metadata (key, value) VALUES ('difficulty', ?1)",
-                params![actual_difficulty.to_string()],
-            ) {
+            if let Err(e) = self.save_difficulty(actual_difficulty as u64) {
                 eprintln!("⚠️  Warning: Failed to update difficulty metadata: {}", e);
             }
             actual_difficulty
@@ -351,12 +1097,31 @@ impl Database {
         let mut state = self.load_utxo_set()?;
         state.rebuild_address_balances();
 
+        let public_keys = Blockchain::key_directory(&blocks);
+        let names = NameRegistry::rebuild(&blocks);
+        let time_index = TimeIndex::rebuild(&blocks);
+        let address_index = AddressIndex::rebuild(&blocks);
+        let subdivisions = SubdivisionIndex::rebuild(&blocks);
+        let utxo_stats = UtxoStatsTracker::from_state(&state, DEFAULT_DUST_THRESHOLD);
         let blockchain = Blockchain {
             blocks,
+            chain_work,
+            checkpoint: None,
+            archive: None,
+            public_keys,
+            names,
+            time_index,
+            address_index,
+            subdivisions,
+            utxo_stats,
             difficulty,
             mempool: Mempool::new(),
             state,
             persistence: Box::new(InMemoryPersistence::new()),
+            consensus: ConsensusParams::default(),
+            clock: crate::blockchain::system_clock(),
+            forks: ForkTracker::default(),
+            journal: BlockJournal::default(),
         };
 
         Ok(blockchain)
@@ -388,6 +1153,54 @@ impl Persistence for Database {
     fn save_difficulty(&self, difficulty: u64) -> Result<(), ChainError> {
         Database::save_difficulty(self, difficulty)
     }
+
+    fn remove_blocks_above(&self, height: u64) -> Result<(), ChainError> {
+        Database::remove_blocks_above(self, height)
+    }
+
+    fn save_spent_utxos(&self, spent: &[SpentTriangle]) -> Result<(), ChainError> {
+        Database::save_spent_utxos(self, spent)
+    }
+
+    fn save_block_undo(&self, undo: &BlockUndo) -> Result<(), ChainError> {
+        Database::save_block_undo(self, undo)
+    }
+
+    fn load_block_undo(&self, height: u64) -> Result<Option<BlockUndo>, ChainError> {
+        Database::load_block_undo(self, height)
+    }
+
+    fn block_height_by_hash(&self, hash: &Sha256Hash) -> Result<Option<u64>, ChainError> {
+        Database::block_height_by_hash(self, hash)
+    }
+
+    fn compact(&self) -> Result<CompactStats, ChainError> {
+        Database::compact(self)
+    }
+
+    fn integrity_check(&self) -> Result<IntegrityReport, ChainError> {
+        Database::integrity_check(self)
+    }
+
+    fn save_utxo_stats(&self, stats: &UtxoStats) -> Result<(), ChainError> {
+        Database::save_utxo_stats(self, stats)
+    }
+
+    fn load_utxo_stats(&self, range: TimeRange) -> Result<Vec<UtxoStats>, ChainError> {
+        Database::load_utxo_stats(self, range)
+    }
+
+    fn save_peer_stats(&self, samples: &[PeerStatsSample]) -> Result<(), ChainError> {
+        Database::save_peer_stats(self, samples)
+    }
+
+    fn load_peer_stats(&self, range: TimeRange) -> Result<Vec<PeerStatsSample>, ChainError> {
+        Database::load_peer_stats(self, range)
+    }
+
+    fn load_block_metrics(&self, range: TimeRange) -> Result<Vec<BlockMetric>, ChainError> {
+        Database::load_block_metrics(self, range)
+    }
 }
 
 /// Simple in-memory persistence implementation useful for tests and ephemeral runs.
@@ -396,6 +1209,11 @@ pub struct InMemoryPersistence {
     pub blocks: std::sync::Arc<std::sync::Mutex<Vec<Block>>>,
     pub state: std::sync::Arc<std::sync::Mutex<TriangleState>>,
     pub difficulty: std::sync::Arc<std::sync::Mutex<u32>>,
+    pub spent_utxos: std::sync::Arc<std::sync::Mutex<Vec<SpentTriangle>>>,
+    pub block_undo: std::sync::Arc<std::sync::Mutex<HashMap<u64, BlockUndo>>>,
+    pub block_hashes: std::sync::Arc<std::sync::Mutex<HashMap<Sha256Hash, u64>>>,
+    pub utxo_stats: std::sync::Arc<std::sync::Mutex<BTreeMap<u64, UtxoStats>>>,
+    pub peer_stats: std::sync::Arc<std::sync::Mutex<Vec<PeerStatsSample>>>,
 }
 
 impl InMemoryPersistence {
@@ -404,15 +1222,30 @@ impl InMemoryPersistence {
             blocks: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
             state: std::sync::Arc::new(std::sync::Mutex::new(TriangleState::new())),
             difficulty: std::sync::Arc::new(std::sync::Mutex::new(2)),
+            spent_utxos: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+            block_undo: std::sync::Arc::new(std::sync::Mutex::new(HashMap::new())),
+            block_hashes: std::sync::Arc::new(std::sync::Mutex::new(HashMap::new())),
+            utxo_stats: std::sync::Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            peer_stats: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
         }
     }
 }
 
-impl Persistence for InMemoryPersistence {
-    fn save_blockchain_state(&self, block: &Block, state: &TriangleState, difficulty: u64) -> Result<(), ChainError> {
+impl InMemoryPersistence {
+    fn store_block(&self, block: &Block) -> Result<(), ChainError> {
         let mut blocks = self.blocks.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let mut hashes = self.block_hashes.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        hashes.retain(|_, height| *height != block.header.height);
+        hashes.insert(block.hash(), block.header.height);
         blocks.retain(|b| b.header.height != block.header.height);
         blocks.push(block.clone());
+        Ok(())
+    }
+}
+
+impl Persistence for InMemoryPersistence {
+    fn save_blockchain_state(&self, block: &Block, state: &TriangleState, difficulty: u64) -> Result<(), ChainError> {
+        self.store_block(block)?;
 
         let mut st = self.state.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
         *st = state.clone();
@@ -433,19 +1266,29 @@ impl Persistence for InMemoryPersistence {
 
         let blockchain = Blockchain {
             blocks: blocks.clone(),
+            chain_work: Blockchain::cumulative_work(&blocks),
+            checkpoint: None,
+            archive: None,
+            public_keys: Blockchain::key_directory(&blocks),
+            names: NameRegistry::rebuild(&blocks),
+            time_index: TimeIndex::rebuild(&blocks),
+            address_index: AddressIndex::rebuild(&blocks),
+            subdivisions: SubdivisionIndex::rebuild(&blocks),
+            utxo_stats: UtxoStatsTracker::from_state(&state, DEFAULT_DUST_THRESHOLD),
             difficulty: diff,
             mempool: Mempool::new(),
             state: state.clone(),
             persistence: Box::new(self.clone()),
+            consensus: ConsensusParams::default(),
+            clock: crate::blockchain::system_clock(),
+            forks: ForkTracker::default(),
+            journal: BlockJournal::default(),
         };
         Ok(blockchain)
     }
 
     fn save_block(&self, block: &Block) -> Result<(), ChainError> {
-        let mut blocks = self.blocks.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
-        blocks.retain(|b| b.header.height != block.header.height);
-        blocks.push(block.clone());
-        Ok(())
+        self.store_block(block)
     }
 
     fn save_utxo_set(&self, state: &TriangleState) -> Result<(), ChainError> {
@@ -464,6 +1307,88 @@ impl Persistence for InMemoryPersistence {
         *diff = difficulty as u32;
         Ok(())
     }
+
+    fn remove_blocks_above(&self, height: u64) -> Result<(), ChainError> {
+        let mut blocks = self.blocks.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        blocks.retain(|b| b.header.height <= height);
+        let mut hashes = self.block_hashes.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        hashes.retain(|_, block_height| *block_height <= height);
+        let mut spent = self.spent_utxos.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        spent.retain(|s| s.spent_height <= height);
+        let mut undo = self.block_undo.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        undo.retain(|undo_height, _| *undo_height <= height);
+        let mut stats = self.utxo_stats.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        stats.retain(|stats_height, _| *stats_height <= height);
+        Ok(())
+    }
+
+    fn save_spent_utxos(&self, spent: &[SpentTriangle]) -> Result<(), ChainError> {
+        let mut stored = self.spent_utxos.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        stored.retain(|s| !spent.iter().any(|new| new.utxo == s.utxo));
+        stored.extend_from_slice(spent);
+        Ok(())
+    }
+
+    fn save_block_undo(&self, undo: &BlockUndo) -> Result<(), ChainError> {
+        let mut stored = self.block_undo.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        stored.insert(undo.height, undo.clone());
+        Ok(())
+    }
+
+    fn load_block_undo(&self, height: u64) -> Result<Option<BlockUndo>, ChainError> {
+        let stored = self.block_undo.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        Ok(stored.get(&height).cloned())
+    }
+
+    fn block_height_by_hash(&self, hash: &Sha256Hash) -> Result<Option<u64>, ChainError> {
+        let hashes = self.block_hashes.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        Ok(hashes.get(hash).copied())
+    }
+
+    fn compact(&self) -> Result<CompactStats, ChainError> {
+        Ok(CompactStats::default())
+    }
+
+    fn integrity_check(&self) -> Result<IntegrityReport, ChainError> {
+        let mut blocks = self.blocks.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?.clone();
+        blocks.sort_by_key(|block| block.header.height);
+        let state = self.state.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let mut report = IntegrityReport::default();
+        report.replay(&blocks, &state);
+        Ok(report)
+    }
+
+    fn load_block_metrics(&self, range: TimeRange) -> Result<Vec<BlockMetric>, ChainError> {
+        let mut blocks = self.blocks.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?.clone();
+        blocks.sort_by_key(|block| block.header.height);
+        let mut metrics = BlockMetric::series(&blocks);
+        metrics.retain(|metric| range.contains(metric.timestamp));
+        Ok(metrics)
+    }
+
+    fn save_utxo_stats(&self, stats: &UtxoStats) -> Result<(), ChainError> {
+        let mut stored = self.utxo_stats.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        stored.insert(stats.height, stats.clone());
+        Ok(())
+    }
+
+    fn load_utxo_stats(&self, range: TimeRange) -> Result<Vec<UtxoStats>, ChainError> {
+        let stored = self.utxo_stats.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        Ok(stored.values().filter(|stats| range.contains(stats.timestamp)).cloned().collect())
+    }
+
+    fn save_peer_stats(&self, samples: &[PeerStatsSample]) -> Result<(), ChainError> {
+        let mut stored = self.peer_stats.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        stored.extend_from_slice(samples);
+        Ok(())
+    }
+
+    fn load_peer_stats(&self, range: TimeRange) -> Result<Vec<PeerStatsSample>, ChainError> {
+        let stored = self.peer_stats.lock().map_err(|_| ChainError::DatabaseError("Mutex poisoned".to_string()))?;
+        let mut samples: Vec<_> = stored.iter().filter(|sample| range.contains(sample.recorded_at)).cloned().collect();
+        samples.sort_by(|a, b| a.recorded_at.cmp(&b.recorded_at).then_with(|| a.peer.cmp(&b.peer)));
+        Ok(samples)
+    }
 }
 
 #[cfg(test)]
@@ -499,4 +1424,276 @@ mod tests {
         assert_eq!(loaded.blocks.len(), chain.blocks.len());
         assert_eq!(loaded.difficulty, chain.difficulty);
     }
+
+    #[test]
+    fn test_chain_work_is_stored_per_block() {
+        let db = Database::open(":memory:").unwrap();
+        let chain = Blockchain::new(create_test_address("miner"), 1).unwrap();
+        db.save_block(&chain.blocks[0]).unwrap();
+        let next = Block::new(1, chain.blocks[0].hash(), 2, vec![]);
+        db.save_block(&next).unwrap();
+
+        let stored: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT chain_work FROM blocks WHERE height = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, (2 + 4).to_string());
+
+        db.remove_blocks_above(0).unwrap();
+        let count: i64 = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_block_metrics_follow_blocks_and_are_backfilled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.db");
+        let chain = Blockchain::new(create_test_address("miner"), 1).unwrap();
+        let genesis = &chain.blocks[0];
+        let mut next = Block::new(1, genesis.hash(), 3, vec![]);
+        next.header.timestamp = genesis.header.timestamp + 45_000;
+        {
+            let db = Database::open(path.to_str().unwrap()).unwrap();
+            db.save_block(genesis).unwrap();
+            db.save_block(&next).unwrap();
+            let metrics = db.load_block_metrics(TimeRange::ALL).unwrap();
+            assert_eq!(metrics, BlockMetric::series(&[genesis.clone(), next.clone()]));
+            assert_eq!((metrics[1].difficulty, metrics[1].solve_time_ms), (3, 45_000));
+            let later = TimeRange { from: genesis.header.timestamp + 1, to: u64::MAX };
+            assert_eq!(db.load_block_metrics(later).unwrap(), metrics[1..]);
+
+            db.remove_blocks_above(1).unwrap();
+            db.conn.lock().unwrap().execute("DELETE FROM block_metrics", []).unwrap();
+        }
+
+        // Reopening fills the table in from the stored blocks
+        let db = Database::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(db.load_block_metrics(TimeRange::ALL).unwrap().len(), 2);
+        db.remove_blocks_above(0).unwrap();
+        assert_eq!(db.load_block_metrics(TimeRange::ALL).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_spent_utxos_follow_removed_blocks() {
+        let db = Database::open(":memory:").unwrap();
+        let chain = Blockchain::new(create_test_address("miner"), 0).unwrap();
+        let utxo = chain.blocks[0].transactions[0].hash();
+        let spent = |hash: [u8; 32], spent_height| SpentTriangle {
+            utxo: hash,
+            triangle: chain.state.utxo_set[&utxo].clone(),
+            created_height: 0,
+            spent_height,
+            spending_tx: [9u8; 32],
+        };
+        db.save_spent_utxos(&[spent(utxo, 1), spent([1u8; 32], 2)]).unwrap();
+
+        db.remove_blocks_above(1).unwrap();
+        let remaining: Vec<Vec<u8>> = db
+            .conn
+            .lock()
+            .unwrap()
+            .prepare("SELECT hash FROM spent_utxos")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(remaining, vec![utxo.to_vec()]);
+    }
+
+    #[test]
+    fn test_block_undo_follows_removed_blocks() {
+        let db = Database::open(":memory:").unwrap();
+        let chain = Blockchain::new(create_test_address("miner"), 0).unwrap();
+        let utxo = chain.blocks[0].transactions[0].hash();
+        let mut undo = BlockUndo::new(1, 0);
+        undo.spent.push((utxo, chain.state.utxo_set[&utxo].clone()));
+        undo.created.push([1u8; 32]);
+        db.save_block_undo(&undo).unwrap();
+        db.save_block_undo(&BlockUndo::new(2, 0)).unwrap();
+        assert_eq!(db.load_block_undo(1).unwrap(), Some(undo.clone()));
+
+        db.remove_blocks_above(1).unwrap();
+        assert_eq!(db.load_block_undo(1).unwrap(), Some(undo));
+        assert_eq!(db.load_block_undo(2).unwrap(), None);
+    }
+
+    #[test]
+    fn test_block_hash_index() {
+        let db = Database::open(":memory:").unwrap();
+        let memory = InMemoryPersistence::new();
+        let chain = Blockchain::new(create_test_address("miner"), 0).unwrap();
+        let genesis = &chain.blocks[0];
+        for backend in [&db as &dyn Persistence, &memory] {
+            backend.save_block(genesis).unwrap();
+            assert_eq!(backend.block_height_by_hash(&genesis.hash()).unwrap(), Some(0));
+            assert_eq!(backend.block_height_by_hash(&[9u8; 32]).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_peer_stats_survive_removed_blocks() {
+        let db = Database::open(":memory:").unwrap();
+        let memory = InMemoryPersistence::new();
+        let sample = |peer: &str, recorded_at: u64| PeerStatsSample {
+            peer: peer.to_string(),
+            recorded_at,
+            bytes_in: 10,
+            ..PeerStatsSample::default()
+        };
+        for backend in [&db as &dyn Persistence, &memory] {
+            backend.save_peer_stats(&[sample("b", 2_000), sample("a", 2_000), sample("a", 1_000)]).unwrap();
+            backend.remove_blocks_above(0).unwrap();
+            let all = backend.load_peer_stats(TimeRange::ALL).unwrap();
+            assert_eq!(all, vec![sample("a", 1_000), sample("a", 2_000), sample("b", 2_000)]);
+            let recent = backend.load_peer_stats(TimeRange { from: 1_500, to: u64::MAX }).unwrap();
+            assert_eq!(recent.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_compact_and_integrity_check() {
+        let db = Database::open(":memory:").unwrap();
+        let chain = Blockchain::new(create_test_address("miner"), 0).unwrap();
+        let genesis = &chain.blocks[0];
+        db.save_blockchain_state(genesis, &chain.state, 0).unwrap();
+        let report = db.integrity_check().unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.blocks_replayed, 1);
+
+        // A stray UTXO and a lost one are both reported
+        let coinbase = genesis.transactions[0].hash();
+        let mut tampered = chain.state.clone();
+        let triangle = tampered.utxo_set.remove(&coinbase).unwrap();
+        tampered.utxo_set.insert([7u8; 32], triangle);
+        db.save_utxo_set(&tampered).unwrap();
+        let report = db.integrity_check().unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.missing_utxos, vec![coinbase]);
+        assert_eq!(report.unexpected_utxos, vec![[7u8; 32]]);
+
+        let stats = db.compact().unwrap();
+        assert!(stats.bytes_after > 0 && stats.bytes_after <= stats.bytes_before);
+        assert_eq!(db.integrity_check().unwrap().unexpected_utxos, vec![[7u8; 32]]);
+    }
+
+    #[test]
+    fn test_recover_interrupted_writes() {
+        use crate::transaction::CoinbaseTx;
+
+        let memory = InMemoryPersistence::new();
+        let mut chain = Blockchain::new_with_persistence(create_test_address("miner"), 0, Box::new(memory.clone())).unwrap();
+        let genesis = chain.blocks[0].clone();
+        let genesis_state = chain.state.clone();
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: crate::geometry::Coord::from_num(1),
+            beneficiary_address: [3u8; 32],
+            nonce: 1,
+            height: 1,
+            fees: crate::geometry::Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let block = crate::miner::mine_block(Block::new(1, genesis.hash(), 0, vec![coinbase])).unwrap();
+        chain.apply_block(block.clone()).unwrap();
+        let undo = memory.load_block_undo(1).unwrap().unwrap();
+        let stored_blocks = |db: &Database| {
+            db.conn.lock().unwrap().query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get::<_, i64>(0)).unwrap()
+        };
+
+        // Block stored, UTXO changes not
+        let db = Database::open(":memory:").unwrap();
+        db.save_blockchain_state(&genesis, &genesis_state, 0).unwrap();
+        db.save_block(&block).unwrap();
+        db.save_block_undo(&undo).unwrap();
+        let report = db.recover().unwrap();
+        assert_eq!((report.stored_tip, report.utxo_height, report.dropped_blocks), (Some(1), Some(0), 1));
+        assert_eq!(report.recovered_height, Some(0));
+        assert_eq!(stored_blocks(&db), 1);
+        assert_eq!(db.load_block_undo(1).unwrap(), None);
+        assert!(db.recover().unwrap().is_clean());
+
+        // UTXO changes saved, block removed but its undo record left
+        let db = Database::open(":memory:").unwrap();
+        db.save_blockchain_state(&genesis, &genesis_state, 0).unwrap();
+        db.save_blockchain_state(&block, &chain.state, 0).unwrap();
+        db.save_block_undo(&undo).unwrap();
+        db.conn.lock().unwrap().execute("DELETE FROM blocks WHERE height > 0", []).unwrap();
+        let report = db.recover().unwrap();
+        assert_eq!((report.reverted_blocks, report.recovered_height), (1, Some(0)));
+        assert_eq!(db.load_utxo_set().unwrap().utxo_set, genesis_state.utxo_set);
+        assert!(db.integrity_check().unwrap().is_ok());
+
+        // The same without the undo record is rebuilt by replay
+        let db = Database::open(":memory:").unwrap();
+        db.save_blockchain_state(&genesis, &genesis_state, 0).unwrap();
+        db.save_blockchain_state(&block, &chain.state, 0).unwrap();
+        db.remove_blocks_above(0).unwrap();
+        let report = db.recover().unwrap();
+        assert_eq!((report.replayed_blocks, report.dropped_blocks), (1, 0));
+        assert!(report.summary().contains("replaying 1 block"), "{}", report.summary());
+        assert_eq!(db.load_utxo_set().unwrap().utxo_set, genesis_state.utxo_set);
+        assert!(db.recover().unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_legacy_json_blocks_are_still_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.db");
+        let chain = Blockchain::new(create_test_address("miner"), 0).unwrap();
+        let genesis = &chain.blocks[0];
+        let json = serde_json::to_string(&genesis.transactions).unwrap();
+        {
+            // Schema and row as written before transactions were bincode
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE blocks (height INTEGER PRIMARY KEY, hash BLOB NOT NULL, previous_hash BLOB NOT NULL,
+                 timestamp INTEGER NOT NULL, difficulty INTEGER NOT NULL, nonce INTEGER NOT NULL,
+                 merkle_root BLOB NOT NULL, transactions TEXT NOT NULL, version INTEGER NOT NULL DEFAULT 0,
+                 chain_work TEXT)",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO blocks VALUES (0, ?1, ?2, ?3, 0, ?4, ?5, ?6, 0, '1')",
+                params![
+                    genesis.hash().to_vec(),
+                    genesis.header.previous_hash.to_vec(),
+                    genesis.header.timestamp,
+                    genesis.header.nonce as i64,
+                    genesis.header.merkle_root.to_vec(),
+                    json,
+                ],
+            )
+            .unwrap();
+        }
+
+        let db = Database::open(path.to_str().unwrap()).unwrap();
+        db.save_utxo_set(&chain.state).unwrap();
+        let next = Block::new(1, genesis.hash(), 0, genesis.transactions.clone());
+        db.save_block(&next).unwrap();
+
+        let loaded = db.load_blockchain().unwrap();
+        assert_eq!(loaded.blocks.len(), 2);
+        assert_eq!(loaded.blocks[0].hash(), genesis.hash());
+        assert_eq!(loaded.blocks[0].transactions[0].hash(), genesis.transactions[0].hash());
+        assert_eq!(loaded.blocks[1].transactions[0].hash(), next.transactions[0].hash());
+
+        let (kind, size): (String, i64) = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT typeof(transactions), length(transactions) FROM blocks WHERE height = 1", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(kind, "blob");
+        assert!((size as usize) < json.len());
+    }
 }
@@ -0,0 +1,181 @@
+//! Staged validation of downloaded blocks
+//!
+//! During initial block download, blocks used to be applied one at a time
+//! with every check, signatures included, running on the task that holds
+//! the chain lock. The pipeline splits that work into stages joined by
+//! bounded channels, each running on its own blocking thread:
+//!
+//! 1. Header checks: proof-of-work against the block's own difficulty,
+//!    merkle root, transaction sizes and the leading coinbase.
+//! 2. Signature checks, spread over the rayon thread pool.
+//! 3. State application, left to the caller, which reads
+//!    [`CheckedBlock`]s in order and hands the valid ones to
+//!    [`Blockchain::apply_block`](crate::blockchain::Blockchain::apply_block).
+//!
+//! So while the chain applies one block, the next ones are already having
+//! their signatures checked. Signatures that pass land in the verified
+//! signature cache of [`crate::crypto`], so `apply_block` does not check them
+//! again. Nothing here depends on the chain state: a block that passes is
+//! only worth applying, not yet valid.
+
+use crate::blockchain::{Block, PowAlgorithm};
+use crate::error::ChainError;
+use crate::transaction::{SigningDomain, Transaction};
+use rayon::prelude::*;
+use tokio::sync::mpsc;
+
+/// Blocks each stage may run ahead of the next
+pub const STAGE_CAPACITY: usize = 16;
+
+/// A block that has been through the stateless stages
+#[derive(Debug)]
+pub struct CheckedBlock {
+    pub block: Block,
+    /// First stateless check the block failed, if any
+    pub result: Result<(), ChainError>,
+}
+
+/// Feed `blocks` through the header and signature stages, yielding them in
+/// the same order. `pow` is the chain's proof-of-work algorithm and
+/// `domain` its signing domain at the first block; should the batch cross
+/// the height where legacy signatures stop being valid, `apply_block`
+/// still refuses them. Dropping the receiver stops the stages once their
+/// current block is done.
+pub fn validate_blocks(
+    blocks: Vec<Block>,
+    pow: &'static dyn PowAlgorithm,
+    domain: SigningDomain,
+) -> mpsc::Receiver<CheckedBlock> {
+    let (header_tx, mut header_rx) = mpsc::channel::<CheckedBlock>(STAGE_CAPACITY);
+    let (checked_tx, checked_rx) = mpsc::channel(STAGE_CAPACITY);
+
+    tokio::task::spawn_blocking(move || {
+        for block in blocks {
+            let result = check_header(&block, pow);
+            if header_tx.blocking_send(CheckedBlock { block, result }).is_err() {
+                return;
+            }
+        }
+    });
+    tokio::task::spawn_blocking(move || {
+        while let Some(mut checked) = header_rx.blocking_recv() {
+            if checked.result.is_ok() {
+                checked.result = check_signatures(&checked.block, &domain);
+            }
+            if checked_tx.blocking_send(checked).is_err() {
+                return;
+            }
+        }
+    });
+    checked_rx
+}
+
+/// Stage 1: checks that need only the block itself
+pub fn check_header(block: &Block, pow: &dyn PowAlgorithm) -> Result<(), ChainError> {
+    if !pow.meets_target(&block.header) {
+        return Err(ChainError::InvalidBlock(
+            "Invalid Proof-of-Work: Block hash does not meet difficulty target.".to_string(),
+        ));
+    }
+    let expected_merkle_root = Block::calculate_merkle_root(block.header.height, &block.transactions);
+    if expected_merkle_root != block.header.merkle_root {
+        return Err(ChainError::InvalidBlock(format!(
+            "Merkle root mismatch. Expected {}, but got {}.",
+            hex::encode(expected_merkle_root),
+            hex::encode(block.header.merkle_root)
+        )));
+    }
+    if !matches!(block.transactions.first(), Some(Transaction::Coinbase(_))) {
+        return Err(ChainError::InvalidBlock(
+            "First transaction in a block must be a Coinbase transaction.".to_string(),
+        ));
+    }
+    block.transactions.iter().try_for_each(Transaction::validate_size)
+}
+
+/// Stage 2: signatures of every transaction, in parallel
+pub fn check_signatures(block: &Block, domain: &SigningDomain) -> Result<(), ChainError> {
+    block.transactions.par_iter().try_for_each(|tx| tx.validate_stateless(domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::crypto::KeyPair;
+    use crate::geometry::Coord;
+    use crate::transaction::{CoinbaseTx, TransferTx};
+
+    fn mine(chain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let tip = chain.blocks.last().unwrap();
+        let height = tip.header.height + 1;
+        let mut block = Block::new(height, tip.hash(), chain.difficulty, transactions);
+        while !chain.consensus.pow.meets_target(&block.header) {
+            block.header.nonce += 1;
+        }
+        block
+    }
+
+    fn coinbase(height: u64) -> Transaction {
+        Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [1u8; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_keeps_order_and_reports_failed_stage() {
+        let chain = Blockchain::new([1u8; 32], 1).unwrap();
+        let good = mine(&chain, vec![coinbase(1)]);
+
+        let keypair = KeyPair::generate().unwrap();
+        let sender: [u8; 32] = keypair.address();
+        let mut transfer = TransferTx::new([9u8; 32], [2u8; 32], sender, Coord::from_num(1), Coord::from_num(0), 1);
+        let mut signature = keypair.sign(&transfer.signable_message(&chain.chain_id())).unwrap().to_vec();
+        signature[10] ^= 1;
+        transfer.sign(signature, keypair.public_key_bytes().to_vec());
+        let forged = mine(&chain, vec![coinbase(1), Transaction::Transfer(transfer)]);
+
+        let mut tampered = good.clone();
+        tampered.transactions.push(coinbase(2));
+
+        let mut rx = validate_blocks(vec![good.clone(), forged, tampered], chain.consensus.pow, chain.signing_domain(1));
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.block.hash(), good.hash());
+        assert!(first.result.is_ok());
+        assert!(rx.recv().await.unwrap().result.unwrap_err().to_string().to_lowercase().contains("signature"));
+        let bad_header = rx.recv().await.unwrap().result.unwrap_err().to_string();
+        assert!(bad_header.contains("Merkle") || bad_header.contains("Proof-of-Work"), "{}", bad_header);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_merkle_root_commits_to_wtxids_from_activation() {
+        use crate::blockchain::{DEFAULT_POW, WITNESS_MERKLE_ACTIVATION_HEIGHT};
+        let keypair = KeyPair::generate().unwrap();
+        let mut transfer = TransferTx::new([9u8; 32], [2u8; 32], keypair.address(), Coord::from_num(1), Coord::from_num(0), 1);
+        let signature = keypair.sign(&transfer.signable_message(&[0u8; 32])).unwrap();
+        transfer.sign(signature.to_vec(), keypair.public_key_bytes().to_vec());
+        let block_with_root = |height: u64, root_height: u64| {
+            let transactions = vec![coinbase(height), Transaction::Transfer(transfer.clone())];
+            let mut block = Block::new(height, [0u8; 32], 0, transactions);
+            block.header.merkle_root = Block::calculate_merkle_root(root_height, &block.transactions);
+            while !DEFAULT_POW.meets_target(&block.header) {
+                block.header.nonce += 1;
+            }
+            block
+        };
+
+        // Existing blocks below the activation keep their txid roots
+        let activation = WITNESS_MERKLE_ACTIVATION_HEIGHT;
+        assert!(check_header(&block_with_root(activation - 1, activation - 1), DEFAULT_POW).is_ok());
+        assert!(check_header(&block_with_root(activation - 1, activation), DEFAULT_POW).is_err());
+        assert!(check_header(&block_with_root(activation, activation), DEFAULT_POW).is_ok());
+        assert!(check_header(&block_with_root(activation, activation - 1), DEFAULT_POW).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+//! Block and transaction propagation tracking
+//!
+//! Records when each relayed block or transaction was first seen and from
+//! which peer, and how far behind that first sighting every other peer
+//! delivered it. The per-peer averages show which peers relay quickly and
+//! which lag behind or only echo what others already sent.
+
+use crate::blockchain::Sha256Hash;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Number of first sightings remembered before the oldest are forgotten
+pub const MAX_TRACKED_ITEMS: usize = 10_000;
+
+/// What kind of inventory item was relayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryKind {
+    Block,
+    Transaction,
+}
+
+#[derive(Debug, Clone)]
+struct FirstSeen {
+    at: Instant,
+    /// Peer that delivered the item first; `None` if it originated locally
+    peer: Option<String>,
+}
+
+/// Relay statistics for one peer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerPropagationStats {
+    pub blocks_relayed: u64,
+    pub transactions_relayed: u64,
+    /// Items this peer delivered before anyone else
+    pub first_relays: u64,
+    /// Sum of the delays behind the first sighting, for the average
+    total_delay: Duration,
+}
+
+impl PeerPropagationStats {
+    pub fn items_relayed(&self) -> u64 {
+        self.blocks_relayed + self.transactions_relayed
+    }
+
+    /// Average delay between the first sighting of an item and this peer
+    /// delivering it (zero for items it relayed first)
+    pub fn avg_latency(&self) -> Duration {
+        match self.items_relayed() {
+            0 => Duration::ZERO,
+            n => self.total_delay / n as u32,
+        }
+    }
+
+    /// Fraction of this peer's relays that were the first sighting
+    pub fn first_relay_ratio(&self) -> f64 {
+        match self.items_relayed() {
+            0 => 0.0,
+            n => self.first_relays as f64 / n as f64,
+        }
+    }
+
+    pub fn report(&self) -> PropagationReport {
+        PropagationReport {
+            blocks_relayed: self.blocks_relayed,
+            transactions_relayed: self.transactions_relayed,
+            first_relays: self.first_relays,
+            first_relay_ratio: self.first_relay_ratio(),
+            avg_latency_ms: self.avg_latency().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// Serializable summary of [`PeerPropagationStats`]
+#[derive(Debug, Clone, Serialize)]
+pub struct PropagationReport {
+    pub blocks_relayed: u64,
+    pub transactions_relayed: u64,
+    pub first_relays: u64,
+    pub first_relay_ratio: f64,
+    pub avg_latency_ms: f64,
+}
+
+/// First sightings of relayed items and the per-peer statistics derived
+/// from them
+#[derive(Debug, Default)]
+pub struct PropagationTracker {
+    first_seen: HashMap<Sha256Hash, FirstSeen>,
+    /// Insertion order of `first_seen`, oldest first, for pruning
+    order: VecDeque<Sha256Hash>,
+    peers: HashMap<String, PeerPropagationStats>,
+}
+
+impl PropagationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remember(&mut self, hash: Sha256Hash, seen: FirstSeen) {
+        self.first_seen.insert(hash, seen);
+        self.order.push_back(hash);
+        while self.order.len() > MAX_TRACKED_ITEMS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.first_seen.remove(&oldest);
+            }
+        }
+    }
+
+    /// Items whose first sighting is remembered, at most [`MAX_TRACKED_ITEMS`]
+    pub fn tracked_items(&self) -> usize {
+        self.first_seen.len()
+    }
+
+    /// Note an item created locally (mined or submitted through the API) so
+    /// peers echoing it back are not credited with relaying it first
+    pub fn record_local(&mut self, hash: Sha256Hash, now: Instant) {
+        if !self.first_seen.contains_key(&hash) {
+            self.remember(hash, FirstSeen { at: now, peer: None });
+        }
+    }
+
+    /// Record `peer` delivering an item at `now`. Returns how long after the
+    /// first sighting it arrived (zero if this is the first sighting).
+    pub fn record_relay(
+        &mut self,
+        hash: Sha256Hash,
+        kind: InventoryKind,
+        peer: &str,
+        now: Instant,
+    ) -> Duration {
+        let delay = match self.first_seen.get(&hash) {
+            Some(first) if first.peer.as_deref() == Some(peer) => return Duration::ZERO,
+            Some(first) => now.saturating_duration_since(first.at),
+            None => {
+                self.remember(
+                    hash,
+                    FirstSeen {
+                        at: now,
+                        peer: Some(peer.to_string()),
+                    },
+                );
+                Duration::ZERO
+            }
+        };
+
+        let stats = self.peers.entry(peer.to_string()).or_default();
+        match kind {
+            InventoryKind::Block => stats.blocks_relayed += 1,
+            InventoryKind::Transaction => stats.transactions_relayed += 1,
+        }
+        if delay.is_zero() {
+            stats.first_relays += 1;
+        }
+        stats.total_delay += delay;
+        delay
+    }
+
+    /// Peer that delivered `hash` first, or `None` if it is unknown or
+    /// originated locally
+    pub fn first_relayer(&self, hash: &Sha256Hash) -> Option<&str> {
+        self.first_seen.get(hash).and_then(|seen| seen.peer.as_deref())
+    }
+
+    pub fn peer_stats(&self, peer: &str) -> Option<&PeerPropagationStats> {
+        self.peers.get(peer)
+    }
+
+    /// Forget the statistics of a disconnected peer
+    pub fn remove_peer(&mut self, peer: &str) {
+        self.peers.remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_and_first_relay_ratio() {
+        let mut tracker = PropagationTracker::new();
+        let start = Instant::now();
+        let block = [1u8; 32];
+        let tx = [2u8; 32];
+
+        assert_eq!(tracker.record_relay(block, InventoryKind::Block, "a:1", start), Duration::ZERO);
+        let late = start + Duration::from_millis(200);
+        assert_eq!(
+            tracker.record_relay(block, InventoryKind::Block, "b:1", late),
+            Duration::from_millis(200)
+        );
+        // A repeat from the first relayer is not counted again
+        tracker.record_relay(block, InventoryKind::Block, "a:1", late);
+        assert_eq!(tracker.first_relayer(&block), Some("a:1"));
+
+        tracker.record_relay(tx, InventoryKind::Transaction, "b:1", start);
+        tracker.record_relay(tx, InventoryKind::Transaction, "a:1", start + Duration::from_millis(100));
+
+        let a = tracker.peer_stats("a:1").unwrap();
+        assert_eq!((a.blocks_relayed, a.transactions_relayed, a.first_relays), (1, 1, 1));
+        assert_eq!(a.avg_latency(), Duration::from_millis(50));
+        assert_eq!(a.first_relay_ratio(), 0.5);
+        let b = tracker.peer_stats("b:1").unwrap();
+        assert_eq!(b.avg_latency(), Duration::from_millis(100));
+
+        tracker.remove_peer("b:1");
+        assert!(tracker.peer_stats("b:1").is_none());
+    }
+
+    #[test]
+    fn test_local_items_are_never_first_relays() {
+        let mut tracker = PropagationTracker::new();
+        let start = Instant::now();
+        tracker.record_local([3u8; 32], start);
+        tracker.record_relay([3u8; 32], InventoryKind::Block, "a:1", start + Duration::from_millis(10));
+        assert_eq!(tracker.first_relayer(&[3u8; 32]), None);
+        assert_eq!(tracker.peer_stats("a:1").unwrap().first_relays, 0);
+
+        for i in 0..=MAX_TRACKED_ITEMS as u32 {
+            let mut hash = [0u8; 32];
+            hash[..4].copy_from_slice(&i.to_le_bytes());
+            tracker.record_local(hash, start);
+        }
+        assert_eq!(tracker.first_seen.len(), MAX_TRACKED_ITEMS);
+    }
+}
@@ -0,0 +1,140 @@
+//! QR codes for receive addresses and payment requests
+//!
+//! A payment request is a `trinity:` URI with the receiving address and,
+//! optionally, an amount in TRC and a memo, e.g.
+//! `trinity:<64 hex digits>?amount=2.5&memo=rent`. Its QR code renders as
+//! unicode half blocks for terminals, or as an SVG or PNG image, so a phone
+//! can scan it instead of someone copying the hex address by hand.
+
+use crate::crypto::Address;
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use crate::units::{self, Unit};
+use qrcode::render::{svg, unicode};
+use qrcode::QrCode;
+use std::fmt;
+use std::io::Cursor;
+
+/// Scheme of payment request URIs
+pub const URI_SCHEME: &str = "trinity";
+
+/// Smallest width and height of rendered images, in pixels
+const MIN_IMAGE_SIZE: u32 = 256;
+
+/// What a payer needs to pay: the address, and optionally how much and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentRequest {
+    pub address: Address,
+    pub amount: Option<Coord>,
+    pub memo: Option<String>,
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address) -> Self {
+        PaymentRequest {
+            address,
+            amount: None,
+            memo: None,
+        }
+    }
+
+    pub fn with_amount(mut self, amount: Coord) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    pub fn with_memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(memo.into());
+        self
+    }
+
+    /// QR code of the request's URI
+    pub fn qr_code(&self) -> Result<QrCode, ChainError> {
+        QrCode::new(self.to_string()).map_err(|e| {
+            ChainError::InvalidTransaction(format!("Payment request does not fit in a QR code: {}", e))
+        })
+    }
+}
+
+impl fmt::Display for PaymentRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", URI_SCHEME, hex::encode(self.address))?;
+        let mut separator = '?';
+        if let Some(amount) = self.amount {
+            write!(f, "{}amount={}", separator, units::format_number(amount, Unit::Trc))?;
+            separator = '&';
+        }
+        if let Some(memo) = &self.memo {
+            write!(f, "{}memo={}", separator, percent_encode(memo))?;
+        }
+        Ok(())
+    }
+}
+
+/// Escape everything but RFC 3986 unreserved characters
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// The code as lines of unicode half blocks, light modules drawn, for
+/// terminals with a dark background
+pub fn render_terminal(code: &QrCode) -> String {
+    code.render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build()
+}
+
+pub fn render_svg(code: &QrCode) -> String {
+    code.render::<svg::Color>()
+        .min_dimensions(MIN_IMAGE_SIZE, MIN_IMAGE_SIZE)
+        .build()
+}
+
+pub fn render_png(code: &QrCode) -> Result<Vec<u8>, ChainError> {
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(MIN_IMAGE_SIZE, MIN_IMAGE_SIZE)
+        .build();
+    let mut png = Cursor::new(Vec::new());
+    image
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| ChainError::IoError(std::io::Error::other(e)))?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payment_request_uri_and_renderings() {
+        let request = PaymentRequest::new([0xab; 32]);
+        assert_eq!(request.to_string(), format!("trinity:{}", "ab".repeat(32)));
+
+        let request = request.with_amount(Coord::from_num(5) / 2).with_memo("rent & bills");
+        assert_eq!(
+            request.to_string(),
+            format!("trinity:{}?amount=2.5&memo=rent%20%26%20bills", "ab".repeat(32))
+        );
+        assert_eq!(
+            PaymentRequest::new([1; 32]).with_memo("ü").to_string(),
+            format!("trinity:{}?memo=%C3%BC", "01".repeat(32))
+        );
+
+        let code = request.qr_code().unwrap();
+        let terminal = render_terminal(&code);
+        assert!(terminal.lines().count() > 10);
+        assert!(terminal.contains('█') || terminal.contains('▀') || terminal.contains('▄'));
+        assert!(render_svg(&code).starts_with("<?xml"));
+        assert!(render_png(&code).unwrap().starts_with(b"\x89PNG"));
+
+        let huge = PaymentRequest::new([1; 32]).with_memo("x".repeat(4000));
+        assert!(huge.qr_code().is_err());
+    }
+}
@@ -0,0 +1,148 @@
+//! Persistent log of rejected blocks
+//!
+//! Every block the node refuses is recorded with the peer it came from and
+//! the validation error, so an operator can tell hours later why the node
+//! stopped following the network. The log is stored as JSON in the data
+//! directory and keeps only the most recent [`MAX_REJECTED_BLOCKS`] entries.
+
+use crate::error::ChainError;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the log inside the data directory
+pub const REJECTED_BLOCKS_FILE: &str = "rejected_blocks.json";
+
+/// Entries kept; the oldest is dropped when a new one arrives
+pub const MAX_REJECTED_BLOCKS: usize = 500;
+
+/// A block the node refused to apply
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RejectedBlock {
+    /// Block hash, hex encoded
+    pub hash: String,
+    pub height: u64,
+    /// `host:port` of the peer that sent it; `None` for blocks submitted
+    /// through the API or downloaded during sync, whose sender is not kept
+    pub peer: Option<String>,
+    pub reason: String,
+    /// Unix timestamp (seconds) of the rejection
+    pub rejected_at: u64,
+}
+
+/// Rejected-block log, optionally backed by a file that is rewritten on
+/// every entry
+#[derive(Debug, Default)]
+pub struct RejectedBlockLog {
+    path: Option<PathBuf>,
+    /// Oldest first
+    entries: VecDeque<RejectedBlock>,
+}
+
+impl RejectedBlockLog {
+    /// Create an in-memory log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the log stored at `path`. A missing file yields an empty log
+    /// that will be created on the first rejection.
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut log = RejectedBlockLog {
+            path: Some(path.to_path_buf()),
+            entries: VecDeque::new(),
+        };
+        if !path.exists() {
+            return Ok(log);
+        }
+
+        let content = fs::read_to_string(path)?;
+        let entries: Vec<RejectedBlock> = serde_json::from_str(&content).map_err(|e| {
+            ChainError::DatabaseError(format!("Invalid rejected-block log {}: {}", path.display(), e))
+        })?;
+        let skip = entries.len().saturating_sub(MAX_REJECTED_BLOCKS);
+        log.entries = entries.into_iter().skip(skip).collect();
+        Ok(log)
+    }
+
+    /// Load `rejected_blocks.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(REJECTED_BLOCKS_FILE))
+    }
+
+    /// Write the log to its backing file (no-op for in-memory logs)
+    pub fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| {
+            ChainError::DatabaseError(format!("Failed to serialize rejected-block log: {}", e))
+        })?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Append an entry, dropping the oldest beyond [`MAX_REJECTED_BLOCKS`],
+    /// and persist the log
+    pub fn record(&mut self, entry: RejectedBlock) -> Result<(), ChainError> {
+        if self.entries.len() >= MAX_REJECTED_BLOCKS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+        self.save()
+    }
+
+    /// All entries, newest first
+    pub fn list(&self) -> Vec<RejectedBlock> {
+        self.entries.iter().rev().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(height: u64) -> RejectedBlock {
+        RejectedBlock {
+            hash: format!("{:064x}", height),
+            height,
+            peer: Some("203.0.113.10:8333".to_string()),
+            reason: "Invalid proof of work".to_string(),
+            rejected_at: 1_700_000_000 + height,
+        }
+    }
+
+    #[test]
+    fn test_log_is_capped_and_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RejectedBlockLog::load_from_data_dir(dir.path()).unwrap();
+        assert!(log.is_empty());
+
+        for height in 0..MAX_REJECTED_BLOCKS as u64 + 5 {
+            log.record(entry(height)).unwrap();
+        }
+        assert_eq!(log.len(), MAX_REJECTED_BLOCKS);
+        assert_eq!(log.list()[0], entry(MAX_REJECTED_BLOCKS as u64 + 4));
+
+        let reloaded = RejectedBlockLog::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.list(), log.list());
+        assert_eq!(reloaded.list().last().unwrap().height, 5);
+    }
+}
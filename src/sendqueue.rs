@@ -0,0 +1,225 @@
+//! Bounded per-peer send queues
+//!
+//! Every connection has its own queue of outbound frames, drained by a
+//! writer task, so a broadcast never waits on a slow peer and what a peer
+//! has not taken yet cannot grow without bound. Blocks and control messages
+//! go out ahead of transactions. A frame that does not fit first evicts
+//! queued transactions; if it still does not fit, a transaction is dropped
+//! and anything else is handled by the [`OverflowPolicy`]. An empty queue
+//! takes any frame, so a block larger than the limit still goes out.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Bytes a peer may have queued by default
+pub const DEFAULT_MAX_QUEUE_BYTES: usize = 32 << 20;
+
+/// What happens when a block or control message does not fit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    /// Drop the message and keep the peer
+    Drop,
+    /// Disconnect the peer; one that cannot keep up with blocks is of
+    /// little use
+    #[default]
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendQueueLimits {
+    pub max_bytes: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for SendQueueLimits {
+    fn default() -> Self {
+        SendQueueLimits {
+            max_bytes: DEFAULT_MAX_QUEUE_BYTES,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendPriority {
+    /// Blocks, headers and control messages
+    High,
+    /// Transaction relay, sent once nothing else is waiting
+    Low,
+}
+
+/// Why a frame was not queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// It did not fit and was dropped
+    Dropped,
+    /// It did not fit and the policy is to disconnect the peer
+    Overflow,
+    /// The writer stopped after a failed write
+    Closed,
+}
+
+/// Queue depth of one peer, reported by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SendQueueStats {
+    /// Frames waiting to be written
+    pub frames: usize,
+    pub bytes: usize,
+    /// Most bytes ever queued at once
+    pub peak_bytes: usize,
+    /// Frames dropped because the queue was full
+    pub dropped: u64,
+}
+
+/// Frames shared between the queues of every peer they are broadcast to
+pub type Frame = Arc<Vec<u8>>;
+
+#[derive(Default)]
+struct Lanes {
+    high: VecDeque<Frame>,
+    low: VecDeque<Frame>,
+    stats: SendQueueStats,
+    closed: bool,
+}
+
+impl Lanes {
+    fn take(&mut self, frame: Frame) -> Frame {
+        self.stats.frames -= 1;
+        self.stats.bytes -= frame.len();
+        frame
+    }
+}
+
+pub struct SendQueue {
+    limits: SendQueueLimits,
+    lanes: Mutex<Lanes>,
+    ready: Notify,
+}
+
+impl SendQueue {
+    pub fn new(limits: SendQueueLimits) -> Self {
+        SendQueue {
+            limits,
+            lanes: Mutex::new(Lanes::default()),
+            ready: Notify::new(),
+        }
+    }
+
+    /// Queue `frame` for the writer
+    pub fn push(&self, frame: Frame, priority: SendPriority) -> Result<(), SendError> {
+        let mut lanes = self.lanes.lock().expect("send queue lock poisoned");
+        if lanes.closed {
+            return Err(SendError::Closed);
+        }
+        let fits = |lanes: &Lanes| lanes.stats.frames == 0 || lanes.stats.bytes + frame.len() <= self.limits.max_bytes;
+        if priority == SendPriority::High {
+            while !fits(&lanes) {
+                let Some(evicted) = lanes.low.pop_back() else {
+                    break;
+                };
+                lanes.take(evicted);
+                lanes.stats.dropped += 1;
+            }
+        }
+        if !fits(&lanes) {
+            lanes.stats.dropped += 1;
+            return match (priority, self.limits.overflow) {
+                (SendPriority::High, OverflowPolicy::Disconnect) => Err(SendError::Overflow),
+                _ => Err(SendError::Dropped),
+            };
+        }
+        lanes.stats.frames += 1;
+        lanes.stats.bytes += frame.len();
+        lanes.stats.peak_bytes = lanes.stats.peak_bytes.max(lanes.stats.bytes);
+        match priority {
+            SendPriority::High => lanes.high.push_back(frame),
+            SendPriority::Low => lanes.low.push_back(frame),
+        }
+        drop(lanes);
+        self.ready.notify_one();
+        Ok(())
+    }
+
+    /// Next frame to write, waiting for one; high priority first
+    pub async fn pop(&self) -> Frame {
+        loop {
+            {
+                let mut lanes = self.lanes.lock().expect("send queue lock poisoned");
+                if let Some(frame) = lanes.high.pop_front().or_else(|| lanes.low.pop_front()) {
+                    return lanes.take(frame);
+                }
+            }
+            self.ready.notified().await;
+        }
+    }
+
+    /// Refuse further frames and discard the queued ones
+    pub fn close(&self) {
+        let mut lanes = self.lanes.lock().expect("send queue lock poisoned");
+        lanes.closed = true;
+        lanes.high.clear();
+        lanes.low.clear();
+        lanes.stats.frames = 0;
+        lanes.stats.bytes = 0;
+    }
+
+    pub fn stats(&self) -> SendQueueStats {
+        self.lanes.lock().expect("send queue lock poisoned").stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(tag: u8, len: usize) -> Frame {
+        Arc::new(vec![tag; len])
+    }
+
+    fn queue(max_bytes: usize, overflow: OverflowPolicy) -> SendQueue {
+        SendQueue::new(SendQueueLimits { max_bytes, overflow })
+    }
+
+    #[tokio::test]
+    async fn test_blocks_go_first_and_evict_transactions() {
+        let queue = queue(100, OverflowPolicy::Disconnect);
+        queue.push(frame(1, 40), SendPriority::Low).unwrap();
+        queue.push(frame(2, 40), SendPriority::Low).unwrap();
+        queue.push(frame(3, 10), SendPriority::High).unwrap();
+        // A transaction that does not fit is dropped
+        assert_eq!(queue.push(frame(4, 20), SendPriority::Low), Err(SendError::Dropped));
+        // A block makes room by evicting the newest transaction
+        queue.push(frame(5, 30), SendPriority::High).unwrap();
+        let stats = queue.stats();
+        assert_eq!((stats.frames, stats.bytes, stats.peak_bytes, stats.dropped), (3, 80, 90, 2));
+
+        let order: Vec<u8> = [queue.pop().await, queue.pop().await, queue.pop().await]
+            .iter()
+            .map(|frame| frame[0])
+            .collect();
+        assert_eq!(order, vec![3, 5, 1]);
+        assert_eq!(queue.stats().bytes, 0);
+    }
+
+    #[test]
+    fn test_overflow_policy_and_oversized_frames() {
+        let strict = queue(50, OverflowPolicy::Disconnect);
+        // An empty queue takes a frame over the limit
+        strict.push(frame(1, 80), SendPriority::High).unwrap();
+        assert_eq!(strict.push(frame(2, 10), SendPriority::High), Err(SendError::Overflow));
+        assert_eq!(strict.push(frame(3, 10), SendPriority::Low), Err(SendError::Dropped));
+
+        let lenient = queue(50, OverflowPolicy::Drop);
+        lenient.push(frame(1, 40), SendPriority::High).unwrap();
+        assert_eq!(lenient.push(frame(2, 20), SendPriority::High), Err(SendError::Dropped));
+        assert_eq!(lenient.stats().dropped, 1);
+
+        lenient.close();
+        assert_eq!(lenient.push(frame(3, 1), SendPriority::High), Err(SendError::Closed));
+        assert_eq!(lenient.stats().frames, 0);
+    }
+}
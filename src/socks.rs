@@ -0,0 +1,150 @@
+//! Minimal SOCKS5 client (RFC 1928)
+//!
+//! Used to reach peers through Tor or another SOCKS5 proxy. Host names are
+//! passed to the proxy unresolved so `.onion` addresses and DNS lookups are
+//! handled on the proxy side and never leak locally.
+
+use crate::error::ChainError;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+fn proxy_error(message: String) -> ChainError {
+    ChainError::NetworkError(format!("SOCKS5 proxy: {}", message))
+}
+
+/// Open a TCP connection to `host:port` through the SOCKS5 proxy at `proxy`
+pub async fn connect(proxy: &str, host: &str, port: u16) -> Result<TcpStream, ChainError> {
+    let mut stream = TcpStream::connect(proxy)
+        .await
+        .map_err(|e| proxy_error(format!("failed to reach {}: {}", proxy, e)))?;
+
+    stream.write_all(&[SOCKS_VERSION, 1, NO_AUTHENTICATION]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice != [SOCKS_VERSION, NO_AUTHENTICATION] {
+        return Err(proxy_error("requires unsupported authentication".to_string()));
+    }
+
+    stream.write_all(&connect_request(host, port)?).await?;
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(proxy_error(format!("unexpected version {}", reply[0])));
+    }
+    if reply[1] != 0 {
+        return Err(proxy_error(format!("connect to {}:{} failed: {}", host, port, reply_message(reply[1]))));
+    }
+
+    // Skip the bound address and port
+    let address_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream.read_u8().await? as usize,
+        other => return Err(proxy_error(format!("unknown address type {}", other))),
+    };
+    let mut bound = vec![0u8; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(stream)
+}
+
+fn connect_request(host: &str, port: u16) -> Result<Vec<u8>, ChainError> {
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        request.push(ATYP_IPV4);
+        request.extend_from_slice(&ip.octets());
+    } else if let Ok(ip) = host.parse::<Ipv6Addr>() {
+        request.push(ATYP_IPV6);
+        request.extend_from_slice(&ip.octets());
+    } else {
+        let name = u8::try_from(host.len())
+            .map_err(|_| proxy_error(format!("host name too long: {}", host)))?;
+        request.push(ATYP_DOMAIN);
+        request.push(name);
+        request.extend_from_slice(host.as_bytes());
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_connect_request_encoding() {
+        assert_eq!(
+            connect_request("10.0.0.1", 8333).unwrap(),
+            vec![5, 1, 0, ATYP_IPV4, 10, 0, 0, 1, 0x20, 0x8d]
+        );
+        let v6 = connect_request("::1", 1).unwrap();
+        assert_eq!(v6[3], ATYP_IPV6);
+        assert_eq!(v6.len(), 4 + 16 + 2);
+        let name = connect_request("example.onion", 1).unwrap();
+        assert_eq!(&name[3..5], &[ATYP_DOMAIN, 13]);
+        assert_eq!(&name[5..18], b"example.onion");
+        assert!(connect_request(&"a".repeat(256), 1).is_err());
+    }
+
+    /// A proxy that accepts one CONNECT and then echoes the tunnelled bytes
+    async fn spawn_proxy(reply_code: u8) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            socket.read_exact(&mut greeting).await.unwrap();
+            socket.write_all(&[SOCKS_VERSION, NO_AUTHENTICATION]).await.unwrap();
+            let mut header = [0u8; 5];
+            socket.read_exact(&mut header).await.unwrap();
+            let mut rest = vec![0u8; header[4] as usize + 2];
+            socket.read_exact(&mut rest).await.unwrap();
+            socket
+                .write_all(&[SOCKS_VERSION, reply_code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            let mut buf = [0u8; 4];
+            if socket.read_exact(&mut buf).await.is_ok() {
+                socket.write_all(&buf).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_proxy() {
+        let onion = format!("{}.onion", "a".repeat(56));
+        let proxy = spawn_proxy(0).await;
+        let mut stream = connect(&proxy, &onion, 8333).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        let mut echo = [0u8; 4];
+        stream.read_exact(&mut echo).await.unwrap();
+        assert_eq!(&echo, b"ping");
+
+        let refusing = spawn_proxy(5).await;
+        let err = connect(&refusing, &onion, 8333).await.unwrap_err();
+        assert!(err.to_string().contains("connection refused"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Wallet spending limits
+//!
+//! A [`SpendingPolicy`] caps what a wallet may pay per transaction and per
+//! rolling 24 hours, and can demand explicit confirmation above a threshold.
+//! It is checked wherever a wallet signs a payment, so a scripted mistake or
+//! a compromised shell cannot drain it in one go. What each address spent is
+//! kept in a [`SpendingLedger`] stored next to the wallets.
+
+use crate::error::{ChainError, ResultExt};
+use crate::geometry::Coord;
+use crate::security::current_timestamp;
+use crate::transaction::Transaction;
+use crate::units::{self, Unit};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the ledger inside the data directory
+pub const SPENDING_LEDGER_FILE: &str = "spending.json";
+
+/// Length of the window `max_per_day` applies to
+pub const SPENDING_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Limits on what a wallet may spend; `None` leaves a limit off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpendingPolicy {
+    pub max_per_transaction: Option<Coord>,
+    /// Cap on the total over the last 24 hours
+    pub max_per_day: Option<Coord>,
+    /// Payments above this need explicit confirmation
+    pub confirm_above: Option<Coord>,
+}
+
+/// Outcome of checking a payment against a policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Allowed,
+    /// Within the limits, but above `confirm_above`
+    NeedsConfirmation,
+}
+
+impl SpendingPolicy {
+    /// Check a payment of `amount` by a wallet that already spent
+    /// `spent_today` in the current window
+    pub fn check(&self, amount: Coord, spent_today: Coord) -> Result<Approval, ChainError> {
+        if let Some(max) = self.max_per_transaction {
+            if amount > max {
+                return Err(ChainError::SpendingLimit(format!(
+                    "{} exceeds the per-transaction limit of {}",
+                    units::format_amount(amount, Unit::Trc),
+                    units::format_amount(max, Unit::Trc)
+                )));
+            }
+        }
+        if let Some(max) = self.max_per_day {
+            if spent_today.saturating_add(amount) > max {
+                return Err(ChainError::SpendingLimit(format!(
+                    "{} would exceed the daily limit of {} ({} already spent in the last 24 hours)",
+                    units::format_amount(amount, Unit::Trc),
+                    units::format_amount(max, Unit::Trc),
+                    units::format_amount(spent_today, Unit::Trc)
+                )));
+            }
+        }
+        if self.confirm_above.is_some_and(|threshold| amount > threshold) {
+            return Ok(Approval::NeedsConfirmation);
+        }
+        Ok(Approval::Allowed)
+    }
+}
+
+/// Value leaving the wallet through `txs`: amounts paid to other addresses
+/// plus every fee. Subdivisions and payments to oneself only cost their fee.
+pub fn amount_spent(txs: &[Transaction]) -> Coord {
+    txs.iter()
+        .map(|tx| match tx {
+            Transaction::Transfer(transfer) if transfer.new_owner != transfer.sender => {
+                transfer.amount.saturating_add(transfer.fee_area)
+            }
+            Transaction::Transfer(transfer) => transfer.fee_area,
+            Transaction::Subdivision(subdivision) => subdivision.fee_area,
+            Transaction::Data(data) => data.fee_area,
+            Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => Coord::from_num(0),
+        })
+        .fold(Coord::from_num(0), Coord::saturating_add)
+}
+
+/// A signed payment counted against the daily limit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpendRecord {
+    /// Hex address of the paying wallet
+    pub address: String,
+    pub amount: Coord,
+    /// Unix timestamp (seconds)
+    pub spent_at: u64,
+}
+
+/// Recent payments per address, optionally backed by a file that is
+/// rewritten on every change. Records older than the window are dropped.
+#[derive(Debug, Default)]
+pub struct SpendingLedger {
+    path: Option<PathBuf>,
+    records: Vec<SpendRecord>,
+}
+
+impl SpendingLedger {
+    /// Create an in-memory ledger
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the ledger stored at `path`. A missing file yields an empty
+    /// ledger that will be created on the first payment.
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut ledger = SpendingLedger {
+            path: Some(path.to_path_buf()),
+            records: Vec::new(),
+        };
+        if !path.exists() {
+            return Ok(ledger);
+        }
+
+        let content = fs::read_to_string(path).context(|| format!("Reading {}", path.display()))?;
+        ledger.records = serde_json::from_str(&content)
+            .map_err(|e| ChainError::WalletError(format!("Invalid spending ledger {}: {}", path.display(), e)))?;
+        ledger.prune(current_timestamp());
+        Ok(ledger)
+    }
+
+    /// Load `spending.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(SPENDING_LEDGER_FILE))
+    }
+
+    /// Write the ledger to its backing file (no-op for in-memory ledgers)
+    pub fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&self.records)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize spending ledger: {}", e)))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Total `address` spent in the window ending at `now`
+    pub fn spent_today(&self, address: &str, now: u64) -> Coord {
+        let since = now.saturating_sub(SPENDING_WINDOW_SECS);
+        self.records
+            .iter()
+            .filter(|record| record.address == address && record.spent_at > since)
+            .map(|record| record.amount)
+            .fold(Coord::from_num(0), Coord::saturating_add)
+    }
+
+    /// Count a payment by `address` and persist the ledger
+    pub fn record(&mut self, address: &str, amount: Coord, now: u64) -> Result<(), ChainError> {
+        self.prune(now);
+        self.records.push(SpendRecord {
+            address: address.to_string(),
+            amount,
+            spent_at: now,
+        });
+        self.save()
+    }
+
+    /// Check a payment by `address` against `policy` at the current time
+    pub fn check(&self, policy: &SpendingPolicy, address: &str, amount: Coord) -> Result<Approval, ChainError> {
+        policy.check(amount, self.spent_today(address, current_timestamp()))
+    }
+
+    fn prune(&mut self, now: u64) {
+        let since = now.saturating_sub(SPENDING_WINDOW_SECS);
+        self.records.retain(|record| record.spent_at > since);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trc(value: f64) -> Coord {
+        Coord::from_num(value)
+    }
+
+    #[test]
+    fn test_policy_limits_and_confirmation() {
+        let policy = SpendingPolicy {
+            max_per_transaction: Some(trc(100.0)),
+            max_per_day: Some(trc(150.0)),
+            confirm_above: Some(trc(50.0)),
+        };
+        assert_eq!(policy.check(trc(10.0), trc(0.0)).unwrap(), Approval::Allowed);
+        assert_eq!(policy.check(trc(60.0), trc(0.0)).unwrap(), Approval::NeedsConfirmation);
+        assert!(matches!(policy.check(trc(101.0), trc(0.0)), Err(ChainError::SpendingLimit(_))));
+        assert!(matches!(policy.check(trc(60.0), trc(100.0)), Err(ChainError::SpendingLimit(_))));
+        assert_eq!(SpendingPolicy::default().check(trc(1e6), trc(1e6)).unwrap(), Approval::Allowed);
+    }
+
+    #[test]
+    fn test_ledger_window_survives_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = current_timestamp();
+        let mut ledger = SpendingLedger::load_from_data_dir(dir.path()).unwrap();
+        ledger.record("alice", trc(30.0), now - SPENDING_WINDOW_SECS - 1).unwrap();
+        ledger.record("alice", trc(20.0), now - 60).unwrap();
+        ledger.record("bob", trc(5.0), now).unwrap();
+
+        let reloaded = SpendingLedger::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.spent_today("alice", now), trc(20.0));
+        assert_eq!(reloaded.spent_today("bob", now), trc(5.0));
+        assert_eq!(reloaded.spent_today("carol", now), trc(0.0));
+    }
+}
@@ -6,24 +6,30 @@
 //! - Peer management and selection
 //! - Sync progress tracking
 //! - Automatic peer discovery
+//! - Locator-based fork point negotiation
 
-use crate::blockchain::{Block, Blockchain};
+use crate::blockchain::{Block, BlockHeader, Blockchain, Sha256Hash};
 use crate::error::ChainError;
 use crate::network::Node;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tracing::{info, warn};
 
 /// Peer sync information
 #[derive(Debug, Clone)]
 pub struct PeerSyncInfo {
     pub node: Node,
     pub height: u64,
+    /// Cumulative work of the peer's chain; zero until it is announced
+    pub chain_work: u128,
     pub last_seen: Instant,
     pub blocks_received: u64,
     pub sync_failures: u32,
     pub is_syncing: bool,
+    /// Last measured ping round trip; `None` until the peer answers one
+    pub latency: Option<Duration>,
 }
 
 impl PeerSyncInfo {
@@ -31,10 +37,12 @@ impl PeerSyncInfo {
         Self {
             node,
             height,
+            chain_work: 0,
             last_seen: Instant::now(),
             blocks_received: 0,
             sync_failures: 0,
             is_syncing: false,
+            latency: None,
         }
     }
 
@@ -47,6 +55,11 @@ impl PeerSyncInfo {
     pub fn is_stale(&self) -> bool {
         self.last_seen.elapsed() > Duration::from_secs(300)
     }
+
+    /// Sort key ranking faster peers first; unmeasured peers come last
+    fn latency_rank(&self) -> Duration {
+        self.latency.unwrap_or(Duration::MAX)
+    }
 }
 
 /// Sync statistics
@@ -58,6 +71,8 @@ pub struct SyncStats {
     pub sync_speed: f64, // blocks per second
     pub estimated_time_remaining: Duration,
     pub last_block_time: Instant,
+    /// Downloaded blocks dropped because the sync buffer was full
+    pub blocks_dropped: u64,
 }
 
 impl Default for SyncStats {
@@ -69,12 +84,34 @@ impl Default for SyncStats {
             sync_speed: 0.0,
             estimated_time_remaining: Duration::from_secs(0),
             last_block_time: Instant::now(),
+            blocks_dropped: 0,
         }
     }
 }
 
+/// A behind node that has not received a block for this long is reported as
+/// stalled
+pub const SYNC_STALL_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Snapshot of how far a node is from the best known peer height
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncProgress {
+    pub state: SyncState,
+    pub current_height: u64,
+    pub target_height: u64,
+    /// Fraction of the target height reached, in `0.0..=1.0`
+    pub progress: f64,
+    pub blocks_per_sec: f64,
+    pub eta_secs: u64,
+    pub active_peers: usize,
+    pub blocks_in_flight: usize,
+    /// Behind the network but no block received for [`SYNC_STALL_THRESHOLD`]
+    pub stalled: bool,
+}
+
 /// Sync state tracking
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SyncState {
     Idle,
     Syncing,
@@ -82,22 +119,175 @@ pub enum SyncState {
     Failed,
 }
 
+/// Number of consecutive blocks requested from one peer at a time
+pub const BLOCK_DOWNLOAD_CHUNK: usize = 16;
+/// Maximum number of peers blocks are downloaded from concurrently
+pub const MAX_DOWNLOAD_PEERS: usize = 8;
+/// How long a peer may take to deliver a requested block before the request
+/// is handed to another peer
+pub const BLOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Default for [`SyncBufferLimits::max_blocks`]
+pub const DEFAULT_SYNC_BUFFER_BLOCKS: usize = 1_024;
+/// Default for [`SyncBufferLimits::max_bytes`]
+pub const DEFAULT_SYNC_BUFFER_BYTES: usize = 128 * 1024 * 1024;
+
+/// Caps on the downloaded blocks waiting for their predecessors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncBufferLimits {
+    pub max_blocks: usize,
+    /// Summed serialized size of the buffered blocks
+    pub max_bytes: usize,
+}
+
+impl Default for SyncBufferLimits {
+    fn default() -> Self {
+        SyncBufferLimits {
+            max_blocks: DEFAULT_SYNC_BUFFER_BLOCKS,
+            max_bytes: DEFAULT_SYNC_BUFFER_BYTES,
+        }
+    }
+}
+
+/// Sync buffer fill against its [`SyncBufferLimits`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SyncBufferUsage {
+    pub blocks: usize,
+    pub bytes: usize,
+    pub max_blocks: usize,
+    pub max_bytes: usize,
+    /// Blocks dropped because the buffer was full
+    pub dropped: u64,
+}
+
+/// Blocks pending application with their serialized sizes
+#[derive(Debug, Default)]
+struct BlockBuffer {
+    blocks: VecDeque<(Block, usize)>,
+    bytes: usize,
+}
+
+impl BlockBuffer {
+    fn pop_front(&mut self) -> Option<Block> {
+        let (block, size) = self.blocks.pop_front()?;
+        self.bytes -= size;
+        Some(block)
+    }
+
+    fn clear(&mut self) {
+        self.blocks.clear();
+        self.bytes = 0;
+    }
+}
+
+/// A block requested from a peer that has not arrived yet
+#[derive(Debug, Clone)]
+struct BlockRequest {
+    peer: Node,
+    hash: Sha256Hash,
+    requested_at: Instant,
+}
+
+/// Outcome of matching a peer's `getheaders` reply against the local chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderNegotiation {
+    /// Height of the last block shared with the peer
+    pub fork_height: u64,
+    /// Hashes of the blocks after the fork point that we still need, in order
+    pub missing: Vec<Sha256Hash>,
+    /// Cumulative work of the peer's chain up to the last header received
+    pub chain_work: u128,
+}
+
+/// Find where a run of headers received from a peer attaches to our chain and
+/// which blocks we are missing. The headers must form a contiguous chain whose
+/// first entry builds on a block we already have.
+pub fn negotiate_headers(
+    local: &Blockchain,
+    headers: &[BlockHeader],
+) -> Result<HeaderNegotiation, ChainError> {
+    let local_height = local.blocks.last().map_or(0, |b| b.header.height);
+    let Some(first) = headers.first() else {
+        return Ok(HeaderNegotiation {
+            fork_height: local_height,
+            missing: Vec::new(),
+            chain_work: local.total_work(),
+        });
+    };
+
+    let known: HashMap<Sha256Hash, u64> = local
+        .blocks
+        .iter()
+        .map(|b| (b.hash(), b.header.height))
+        .collect();
+
+    let mut fork_height = match known.get(&first.previous_hash) {
+        Some(&height) if height + 1 == first.height => height,
+        _ if first.height == 0 && known.contains_key(&first.hash()) => 0,
+        _ => {
+            return Err(ChainError::NetworkError(format!(
+                "Headers starting at height {} do not connect to the local chain",
+                first.height
+            )))
+        }
+    };
+
+    let mut missing = Vec::new();
+    let mut missing_work = 0u128;
+    let mut previous: Option<&BlockHeader> = None;
+    for header in headers {
+        if let Some(prev) = previous {
+            if header.previous_hash != prev.hash() || header.height != prev.height + 1 {
+                return Err(ChainError::NetworkError(format!(
+                    "Non-contiguous header at height {}",
+                    header.height
+                )));
+            }
+        }
+        let hash = header.hash();
+        if missing.is_empty() && known.get(&hash) == Some(&header.height) {
+            fork_height = header.height;
+        } else {
+            missing.push(hash);
+            missing_work = missing_work.saturating_add(header.work());
+        }
+        previous = Some(header);
+    }
+
+    let fork_work = local.work_at(fork_height).unwrap_or(0);
+    Ok(HeaderNegotiation {
+        fork_height,
+        missing,
+        chain_work: fork_work.saturating_add(missing_work),
+    })
+}
+
 /// Node synchronizer
 pub struct NodeSynchronizer {
     peers: Arc<RwLock<HashMap<String, PeerSyncInfo>>>,
     sync_state: Arc<RwLock<SyncState>>,
     stats: Arc<RwLock<SyncStats>>,
     /// Queue of blocks pending application
-    pending_blocks: Arc<RwLock<VecDeque<Block>>>,
+    pending_blocks: Arc<RwLock<BlockBuffer>>,
+    buffer_limits: SyncBufferLimits,
+    /// Outstanding block requests keyed by height
+    in_flight: Arc<RwLock<BTreeMap<u64, BlockRequest>>>,
 }
 
 impl NodeSynchronizer {
     pub fn new() -> Self {
+        Self::with_buffer_limits(SyncBufferLimits::default())
+    }
+
+    /// A synchronizer buffering at most what `limits` allow
+    pub fn with_buffer_limits(limits: SyncBufferLimits) -> Self {
         Self {
             peers: Arc::new(RwLock::new(HashMap::new())),
             sync_state: Arc::new(RwLock::new(SyncState::Idle)),
             stats: Arc::new(RwLock::new(SyncStats::default())),
-            pending_blocks: Arc::new(RwLock::new(VecDeque::new())),
+            pending_blocks: Arc::new(RwLock::new(BlockBuffer::default())),
+            buffer_limits: limits,
+            in_flight: Arc::new(RwLock::new(BTreeMap::new())),
         }
     }
 
@@ -129,14 +319,42 @@ impl NodeSynchronizer {
         }
     }
 
-    /// Get the best peer to sync from (highest height, no failures)
+    /// Update the cumulative chain work a peer announced
+    pub async fn update_peer_chain_work(&self, node_addr: &str, height: u64, chain_work: u128) -> Result<(), ChainError> {
+        let mut peers = self.peers.write().await;
+
+        if let Some(peer) = peers.get_mut(node_addr) {
+            peer.height = height;
+            peer.chain_work = chain_work;
+            peer.last_seen = Instant::now();
+            Ok(())
+        } else {
+            Err(ChainError::NetworkError("Peer not found".to_string()))
+        }
+    }
+
+    /// Record a ping round trip to the peer
+    pub async fn record_latency(&self, node_addr: &str, latency: Duration) -> Result<(), ChainError> {
+        let mut peers = self.peers.write().await;
+
+        if let Some(peer) = peers.get_mut(node_addr) {
+            peer.latency = Some(latency);
+            peer.last_seen = Instant::now();
+            Ok(())
+        } else {
+            Err(ChainError::NetworkError("Peer not found".to_string()))
+        }
+    }
+
+    /// Get the best peer to sync from (most chain work, then highest height,
+    /// then lowest latency, no failures)
     pub async fn get_best_peer(&self) -> Option<Node> {
         let peers = self.peers.read().await;
 
         peers
             .values()
             .filter(|p| !p.is_unreliable() && !p.is_stale() && !p.is_syncing)
-            .max_by_key(|p| p.height)
+            .max_by_key(|p| (p.chain_work, p.height, std::cmp::Reverse(p.latency_rank())))
             .map(|p| p.node.clone())
     }
 
@@ -149,8 +367,9 @@ impl NodeSynchronizer {
             .filter(|p| !p.is_unreliable() && !p.is_stale() && !p.is_syncing)
             .collect();
 
-        // Sort by height (descending) and return top N
-        valid_peers.sort_by(|a, b| b.height.cmp(&a.height));
+        // Sort by chain work, then height (descending), then latency and
+        // return top N
+        valid_peers.sort_by_key(|b| (std::cmp::Reverse((b.chain_work, b.height)), b.latency_rank()));
         valid_peers
             .into_iter()
             .take(count)
@@ -178,6 +397,51 @@ impl NodeSynchronizer {
         self.stats.read().await.clone()
     }
 
+    /// Sync progress relative to the highest peer, derived from
+    /// [`NodeSynchronizer::get_stats`] and the registered peers.
+    pub async fn progress(&self, local_height: u64) -> SyncProgress {
+        let (target_height, active_peers) = {
+            let peers = self.peers.read().await;
+            let active: Vec<_> = peers.values().filter(|p| !p.is_stale()).collect();
+            let best = active.iter().map(|p| p.height).max().unwrap_or(0);
+            (best.max(local_height), active.len())
+        };
+        let stats = self.get_stats().await;
+        let blocks_in_flight = self.blocks_in_flight().await;
+
+        let remaining = target_height - local_height;
+        let state = if active_peers == 0 {
+            SyncState::Idle
+        } else if remaining <= 1 {
+            SyncState::Synced
+        } else {
+            SyncState::Syncing
+        };
+        let eta_secs = if stats.sync_speed > 0.0 {
+            (remaining as f64 / stats.sync_speed).ceil() as u64
+        } else {
+            0
+        };
+        let progress = if target_height == 0 {
+            1.0
+        } else {
+            local_height as f64 / target_height as f64
+        };
+
+        SyncProgress {
+            state,
+            current_height: local_height,
+            target_height,
+            progress,
+            blocks_per_sec: stats.sync_speed,
+            eta_secs,
+            active_peers,
+            blocks_in_flight,
+            stalled: state == SyncState::Syncing
+                && stats.last_block_time.elapsed() > SYNC_STALL_THRESHOLD,
+        }
+    }
+
     /// Set sync state
     async fn set_sync_state(&self, state: SyncState) {
         *self.sync_state.write().await = state;
@@ -218,7 +482,7 @@ impl NodeSynchronizer {
             peer.last_seen = Instant::now();
 
             if peer.is_unreliable() {
-                println!("⚠️  Peer {} marked as unreliable", node_addr);
+                warn!("⚠️  Peer {} marked as unreliable", node_addr);
             }
 
             Ok(())
@@ -227,21 +491,77 @@ impl NodeSynchronizer {
         }
     }
 
-    /// Queue a block for application
-    pub async fn queue_block(&self, block: Block) {
+    /// Queue a block for application. A full buffer makes room by dropping
+    /// blocks above this one, since the lowest heights are applied first;
+    /// if none are higher, this block is dropped instead and `false`
+    /// returned. Dropped blocks are requested again in a later sync round.
+    /// An empty buffer takes any block, so one oversized block cannot stall
+    /// the sync.
+    pub async fn queue_block(&self, block: Block) -> bool {
+        let size = bincode::serialized_size(&block).map_or(usize::MAX, |size| size as usize);
+        let limits = self.buffer_limits;
         let mut queue = self.pending_blocks.write().await;
-        queue.push_back(block);
+        let mut dropped = 0;
+        let mut queued = true;
+        while !queue.blocks.is_empty()
+            && (queue.blocks.len() >= limits.max_blocks || queue.bytes.saturating_add(size) > limits.max_bytes)
+        {
+            let highest = queue
+                .blocks
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, (buffered, _))| buffered.header.height)
+                .map(|(index, (buffered, _))| (index, buffered.header.height));
+            match highest {
+                Some((index, height)) if height > block.header.height => {
+                    if let Some((_, size)) = queue.blocks.remove(index) {
+                        queue.bytes -= size;
+                    }
+                    dropped += 1;
+                }
+                _ => {
+                    queued = false;
+                    dropped += 1;
+                    break;
+                }
+            }
+        }
+        if queued {
+            queue.bytes = queue.bytes.saturating_add(size);
+            queue.blocks.push_back((block, size));
+        }
+        drop(queue);
+        if dropped > 0 {
+            self.stats.write().await.blocks_dropped += dropped;
+        }
+        queued
     }
 
     /// Get all queued blocks
     pub async fn get_pending_blocks(&self) -> Vec<Block> {
         let mut queue = self.pending_blocks.write().await;
-        queue.drain(..).collect()
+        queue.bytes = 0;
+        queue.blocks.drain(..).map(|(block, _)| block).collect()
+    }
+
+    /// Buffered blocks against the limits
+    pub async fn buffer_usage(&self) -> SyncBufferUsage {
+        let (blocks, bytes) = {
+            let queue = self.pending_blocks.read().await;
+            (queue.blocks.len(), queue.bytes)
+        };
+        SyncBufferUsage {
+            blocks,
+            bytes,
+            max_blocks: self.buffer_limits.max_blocks,
+            max_bytes: self.buffer_limits.max_bytes,
+            dropped: self.stats.read().await.blocks_dropped,
+        }
     }
 
     /// Check if there are pending blocks
     pub async fn has_pending_blocks(&self) -> bool {
-        !self.pending_blocks.read().await.is_empty()
+        !self.pending_blocks.read().await.blocks.is_empty()
     }
 
     /// Clear all pending blocks
@@ -249,6 +569,170 @@ impl NodeSynchronizer {
         self.pending_blocks.write().await.clear();
     }
 
+    /// Split the missing `(height, hash)` pairs into chunks and assign them
+    /// round-robin to the best peers that claim to have them. Heights already
+    /// in flight are skipped. `fallback` serves chunks no registered peer can.
+    /// Returns the hashes each peer should be asked for.
+    pub async fn schedule_downloads(
+        &self,
+        missing: Vec<(u64, Sha256Hash)>,
+        fallback: &Node,
+    ) -> Vec<(Node, Vec<Sha256Hash>)> {
+        let candidates: Vec<(Node, u64)> = {
+            let peers = self.peers.read().await;
+            let mut valid: Vec<_> = peers
+                .values()
+                .filter(|p| !p.is_unreliable() && !p.is_stale())
+                .collect();
+            valid.sort_by_key(|p| (std::cmp::Reverse(p.height), p.latency_rank()));
+            valid.truncate(MAX_DOWNLOAD_PEERS);
+            valid.into_iter().map(|p| (p.node.clone(), p.height)).collect()
+        };
+
+        let mut in_flight = self.in_flight.write().await;
+        let missing: Vec<_> = missing
+            .into_iter()
+            .filter(|(height, _)| !in_flight.contains_key(height))
+            .collect();
+
+        let mut assignments: Vec<(Node, Vec<Sha256Hash>)> = Vec::new();
+        for (index, chunk) in missing.chunks(BLOCK_DOWNLOAD_CHUNK).enumerate() {
+            let last_height = chunk.last().map_or(0, |(height, _)| *height);
+            let able: Vec<&Node> = candidates
+                .iter()
+                .filter(|(_, height)| *height >= last_height)
+                .map(|(node, _)| node)
+                .collect();
+            let peer = if able.is_empty() {
+                fallback.clone()
+            } else {
+                able[index % able.len()].clone()
+            };
+
+            for (height, hash) in chunk {
+                in_flight.insert(
+                    *height,
+                    BlockRequest {
+                        peer: peer.clone(),
+                        hash: *hash,
+                        requested_at: Instant::now(),
+                    },
+                );
+            }
+            let hashes = chunk.iter().map(|(_, hash)| *hash);
+            match assignments.iter_mut().find(|(node, _)| *node == peer) {
+                Some((_, list)) => list.extend(hashes),
+                None => assignments.push((peer, hashes.collect())),
+            }
+        }
+        assignments
+    }
+
+    /// Record the arrival of a block. Returns `true` if it answered one of our
+    /// outstanding requests, in which case it is buffered for in-order
+    /// application via [`NodeSynchronizer::take_ready_blocks`].
+    pub async fn block_arrived(&self, block: Block) -> bool {
+        let height = block.header.height;
+        let request = {
+            let mut in_flight = self.in_flight.write().await;
+            match in_flight.get(&height) {
+                Some(request) if request.hash == block.hash() => in_flight.remove(&height),
+                _ => None,
+            }
+        };
+
+        match request {
+            Some(request) => {
+                let _ = self.record_block_received(&request.peer.addr()).await;
+                self.queue_block(block).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove and return the buffered blocks that continue the chain from
+    /// `next_height`, in height order. Blocks below `next_height` are dropped.
+    pub async fn take_ready_blocks(&self, next_height: u64) -> Vec<Block> {
+        let mut queue = self.pending_blocks.write().await;
+        queue.blocks.make_contiguous().sort_by_key(|(b, _)| b.header.height);
+
+        let mut ready = Vec::new();
+        let mut expected = next_height;
+        while let Some((block, _)) = queue.blocks.front() {
+            if block.header.height < expected {
+                queue.pop_front();
+            } else if block.header.height == expected {
+                ready.extend(queue.pop_front());
+                expected += 1;
+            } else {
+                break;
+            }
+        }
+        ready
+    }
+
+    /// Height of the lowest buffered block, if any
+    pub async fn first_pending_height(&self) -> Option<u64> {
+        self.pending_blocks.read().await.blocks.iter().map(|(b, _)| b.header.height).min()
+    }
+
+    /// Number of block requests still waiting for an answer
+    pub async fn blocks_in_flight(&self) -> usize {
+        self.in_flight.read().await.len()
+    }
+
+    /// Hand requests older than `timeout` to a different peer, counting a
+    /// failure against the peer that stalled. Returns the new assignments.
+    pub async fn reassign_stalled(&self, timeout: Duration) -> Vec<(Node, Vec<Sha256Hash>)> {
+        let stalled: Vec<(u64, Node)> = {
+            let in_flight = self.in_flight.read().await;
+            in_flight
+                .iter()
+                .filter(|(_, request)| request.requested_at.elapsed() >= timeout)
+                .map(|(height, request)| (*height, request.peer.clone()))
+                .collect()
+        };
+        if stalled.is_empty() {
+            return Vec::new();
+        }
+
+        let mut stalled_peers: Vec<Node> = stalled.iter().map(|(_, peer)| peer.clone()).collect();
+        stalled_peers.dedup();
+        for peer in &stalled_peers {
+            let _ = self.record_sync_failure(&peer.addr()).await;
+        }
+
+        let candidates = self.get_best_peers(MAX_DOWNLOAD_PEERS).await;
+        let mut in_flight = self.in_flight.write().await;
+        let mut assignments: Vec<(Node, Vec<Sha256Hash>)> = Vec::new();
+        for (index, (height, old_peer)) in stalled.into_iter().enumerate() {
+            let others: Vec<&Node> = candidates.iter().filter(|n| **n != old_peer).collect();
+            let peer = if others.is_empty() {
+                old_peer
+            } else {
+                others[index / BLOCK_DOWNLOAD_CHUNK % others.len()].clone()
+            };
+            let Some(request) = in_flight.get_mut(&height) else {
+                continue;
+            };
+            request.peer = peer.clone();
+            request.requested_at = Instant::now();
+            match assignments.iter_mut().find(|(node, _)| *node == peer) {
+                Some((_, list)) => list.push(request.hash),
+                None => assignments.push((peer, vec![request.hash])),
+            }
+        }
+        assignments
+    }
+
+    /// Forget every outstanding request and buffered block, e.g. after a
+    /// downloaded block failed validation.
+    pub async fn reset_downloads(&self) {
+        self.in_flight.write().await.clear();
+        self.clear_pending_blocks().await;
+    }
+
     /// Get list of all registered peers
     pub async fn get_all_peers(&self) -> Vec<Node> {
         let peers = self.peers.read().await;
@@ -296,7 +780,7 @@ impl NodeSynchronizer {
         self.set_sync_state(SyncState::Syncing).await;
         self.set_peer_syncing(&peer_addr, true).await?;
 
-        println!(
+        info!(
             "🔄 Starting sync from peer {} (local: {}, remote: estimated)",
             peer_addr, local_height
         );
@@ -335,7 +819,7 @@ impl NodeSynchronizer {
         } else {
             let lag = max_peer_height.saturating_sub(local_height);
             if lag > 100 {
-                println!("⚠️  Node is {} blocks behind best peer", lag);
+                warn!("⚠️  Node is {} blocks behind best peer", lag);
             }
             SyncState::Syncing
         }
@@ -351,7 +835,7 @@ impl NodeSynchronizer {
             .collect();
 
         for addr in stale_peers {
-            println!("🗑️  Removing stale peer: {}", addr);
+            info!("🗑️  Removing stale peer: {}", addr);
             peers.remove(&addr);
         }
     }
@@ -398,9 +882,37 @@ mod tests {
             let best = sync.get_best_peer().await;
             assert!(best.is_some());
             assert_eq!(best.unwrap().port, 8334);
+
+            // A lower chain with more work is preferred
+            sync.update_peer_chain_work("127.0.0.1:8333", 100, 1 << 20).await.unwrap();
+            sync.update_peer_chain_work("127.0.0.2:8334", 200, 1 << 10).await.unwrap();
+            assert_eq!(sync.get_best_peer().await.unwrap().port, 8333);
+            assert_eq!(sync.get_best_peers(2).await[0].port, 8333);
         }).await.expect("test_get_best_peer timed out");
     }
 
+    #[tokio::test]
+    async fn test_latency_breaks_ties_between_peers() {
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            let sync = NodeSynchronizer::new();
+            let slow = Node::new("127.0.0.1".to_string(), 8333);
+            let fast = Node::new("127.0.0.2".to_string(), 8334);
+            sync.register_peer(slow.clone(), 100).await.unwrap();
+            sync.register_peer(fast.clone(), 100).await.unwrap();
+
+            sync.record_latency(&slow.addr(), Duration::from_millis(400)).await.unwrap();
+            assert_eq!(sync.get_best_peer().await.unwrap(), slow);
+
+            sync.record_latency(&fast.addr(), Duration::from_millis(20)).await.unwrap();
+            assert_eq!(sync.get_best_peer().await.unwrap(), fast);
+            assert_eq!(sync.get_best_peers(2).await, vec![fast.clone(), slow.clone()]);
+
+            // Height still outranks latency
+            sync.update_peer_height(&slow.addr(), 101).await.unwrap();
+            assert_eq!(sync.get_best_peer().await.unwrap().port, 8333);
+        }).await.expect("test_latency_breaks_ties_between_peers timed out");
+    }
+
     #[tokio::test]
     async fn test_sync_stats() {
         tokio::time::timeout(std::time::Duration::from_secs(5), async {
@@ -433,6 +945,164 @@ mod tests {
         }).await.expect("test_peer_failure_tracking timed out");
     }
 
+    fn extend_chain(chain: &mut Blockchain, count: u64, tag: u8) {
+        use crate::transaction::{CoinbaseTx, Transaction};
+        for _ in 0..count {
+            let tip = chain.blocks.last().unwrap();
+            let height = tip.header.height + 1;
+            let coinbase = Transaction::Coinbase(CoinbaseTx {
+                reward_area: crate::geometry::Coord::from_num(1),
+                beneficiary_address: [tag; 32],
+                nonce: height,
+                height,
+                fees: crate::geometry::Coord::from_num(0),
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            });
+            let block = Block::new(height, tip.hash(), 0, vec![coinbase]);
+            let block = crate::miner::mine_block(block).unwrap();
+            chain.apply_block(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_block_locator_is_exponentially_spaced() {
+        let mut chain = Blockchain::new([1u8; 32], 0).unwrap();
+        extend_chain(&mut chain, 40, 1);
+
+        let locator = chain.block_locator();
+        let heights: Vec<u64> = locator
+            .iter()
+            .map(|h| chain.blocks.iter().find(|b| b.hash() == *h).unwrap().header.height)
+            .collect();
+        assert_eq!(&heights[..10], &[40, 39, 38, 37, 36, 35, 34, 33, 32, 31]);
+        assert_eq!(&heights[10..], &[29, 25, 17, 1, 0]);
+    }
+
+    #[test]
+    fn test_getheaders_sends_only_missing_segment() {
+        let mut ours = Blockchain::new([1u8; 32], 0).unwrap();
+        extend_chain(&mut ours, 5, 1);
+        let mut theirs = ours.clone();
+        extend_chain(&mut theirs, 7, 2);
+        // We diverge after height 5 on our own branch
+        extend_chain(&mut ours, 2, 3);
+
+        let headers = theirs.headers_after_locator(&ours.block_locator(), None, 2000);
+        assert_eq!(headers.len(), 7);
+        assert_eq!(headers[0].height, 6);
+
+        let negotiation = negotiate_headers(&ours, &headers).unwrap();
+        assert_eq!(negotiation.fork_height, 5);
+        assert_eq!(negotiation.missing.len(), 7);
+        assert_eq!(negotiation.missing[0], theirs.blocks[6].hash());
+        assert_eq!(negotiation.chain_work, theirs.total_work());
+    }
+
+    #[test]
+    fn test_negotiate_rejects_disconnected_headers() {
+        let ours = Blockchain::new([1u8; 32], 0).unwrap();
+        let mut other = Blockchain::new([9u8; 32], 0).unwrap();
+        extend_chain(&mut other, 3, 9);
+
+        let headers: Vec<BlockHeader> =
+            other.blocks[2..].iter().map(|b| b.header.clone()).collect();
+        assert!(negotiate_headers(&ours, &headers).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_download_buffers_out_of_order_blocks() {
+        let mut source = Blockchain::new([1u8; 32], 0).unwrap();
+        extend_chain(&mut source, 40, 1);
+        let local_height = 0;
+
+        let sync = NodeSynchronizer::new();
+        let peer_a = Node::new("127.0.0.1".to_string(), 1);
+        let peer_b = Node::new("127.0.0.2".to_string(), 2);
+        sync.register_peer(peer_a.clone(), 40).await.unwrap();
+        sync.register_peer(peer_b.clone(), 40).await.unwrap();
+
+        let missing: Vec<(u64, Sha256Hash)> = source.blocks[1..]
+            .iter()
+            .map(|b| (b.header.height, b.hash()))
+            .collect();
+        let assignments = sync.schedule_downloads(missing.clone(), &peer_a).await;
+        assert_eq!(assignments.len(), 2);
+        assert_eq!(sync.blocks_in_flight().await, 40);
+        // Scheduling again does not duplicate in-flight requests
+        assert!(sync.schedule_downloads(missing, &peer_a).await.is_empty());
+
+        // Deliver blocks in reverse order; nothing is ready until height 1 arrives
+        for block in source.blocks[2..].iter().rev() {
+            assert!(sync.block_arrived(block.clone()).await);
+        }
+        assert!(sync.take_ready_blocks(local_height + 1).await.is_empty());
+        assert!(sync.block_arrived(source.blocks[1].clone()).await);
+
+        let ready = sync.take_ready_blocks(local_height + 1).await;
+        let heights: Vec<u64> = ready.iter().map(|b| b.header.height).collect();
+        assert_eq!(heights, (1..=40).collect::<Vec<_>>());
+        assert_eq!(sync.blocks_in_flight().await, 0);
+
+        // Unrequested blocks are not buffered
+        assert!(!sync.block_arrived(source.blocks[5].clone()).await);
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_keeps_lowest_heights() {
+        let mut source = Blockchain::new([1u8; 32], 0).unwrap();
+        extend_chain(&mut source, 5, 1);
+        let sync = NodeSynchronizer::with_buffer_limits(SyncBufferLimits { max_blocks: 2, ..SyncBufferLimits::default() });
+
+        assert!(sync.queue_block(source.blocks[4].clone()).await);
+        assert!(sync.queue_block(source.blocks[3].clone()).await);
+        // A lower block pushes out the highest; a higher one is refused
+        assert!(sync.queue_block(source.blocks[1].clone()).await);
+        assert!(!sync.queue_block(source.blocks[5].clone()).await);
+        assert_eq!(sync.first_pending_height().await, Some(1));
+
+        let usage = sync.buffer_usage().await;
+        assert_eq!((usage.blocks, usage.max_blocks, usage.dropped), (2, 2, 2));
+        assert_eq!(sync.take_ready_blocks(1).await.len(), 1);
+        assert!(sync.buffer_usage().await.bytes > 0);
+        sync.clear_pending_blocks().await;
+        assert_eq!(sync.buffer_usage().await.bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_requests_are_reassigned() {
+        let sync = NodeSynchronizer::new();
+        let slow = Node::new("127.0.0.1".to_string(), 1);
+        let fast = Node::new("127.0.0.2".to_string(), 2);
+        sync.register_peer(slow.clone(), 10).await.unwrap();
+
+        sync.schedule_downloads(vec![(1, [1u8; 32]), (2, [2u8; 32])], &slow).await;
+        sync.register_peer(fast.clone(), 10).await.unwrap();
+
+        assert!(sync.reassign_stalled(BLOCK_REQUEST_TIMEOUT).await.is_empty());
+        let reassigned = sync.reassign_stalled(Duration::ZERO).await;
+        assert_eq!(reassigned, vec![(fast, vec![[1u8; 32], [2u8; 32]])]);
+        assert_eq!(sync.get_peer_info(&slow.addr()).await.unwrap().sync_failures, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sync_progress_reports_target_and_state() {
+        let sync = NodeSynchronizer::new();
+        assert_eq!(sync.progress(5).await.state, SyncState::Idle);
+
+        let node = Node::new("127.0.0.1".to_string(), 8333);
+        sync.register_peer(node, 200).await.unwrap();
+
+        let progress = sync.progress(50).await;
+        assert_eq!(progress.state, SyncState::Syncing);
+        assert_eq!(progress.target_height, 200);
+        assert_eq!(progress.active_peers, 1);
+        assert!((progress.progress - 0.25).abs() < f64::EPSILON);
+        assert!(!progress.stalled);
+
+        assert_eq!(sync.progress(200).await.state, SyncState::Synced);
+    }
+
     #[tokio::test]
     async fn test_pending_blocks_queue() {
         tokio::time::timeout(std::time::Duration::from_secs(5), async {
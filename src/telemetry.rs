@@ -0,0 +1,224 @@
+//! Opt-in node telemetry
+//!
+//! When `[telemetry] enabled = true`, the node periodically POSTs a small
+//! anonymized JSON report (software version, network, chain height, peer
+//! count and OS) to the configured endpoint. The report carries no
+//! addresses, keys or peer identities; `/api/telemetry/preview` shows the
+//! exact payload whether or not reporting is enabled.
+
+use crate::config::TelemetryConfig;
+use crate::error::ChainError;
+use crate::network::{NetworkNode, PROTOCOL_VERSION};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::warn;
+
+/// Shortest accepted reporting interval
+pub const MIN_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The payload sent to the telemetry endpoint
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TelemetryReport {
+    pub version: String,
+    pub protocol_version: u32,
+    pub network_id: String,
+    pub height: u64,
+    pub peer_count: usize,
+    pub os: String,
+    pub arch: String,
+}
+
+impl TelemetryReport {
+    pub async fn collect(network_id: &str, network: &NetworkNode) -> Self {
+        let height = network
+            .blockchain
+            .read()
+            .await
+            .blocks
+            .last()
+            .map_or(0, |b| b.header.height);
+        TelemetryReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            network_id: network_id.to_string(),
+            height,
+            peer_count: network.list_peers().await.len(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+}
+
+/// Telemetry settings of a running node
+#[derive(Debug, Clone, Default)]
+pub struct Telemetry {
+    pub config: TelemetryConfig,
+    pub network_id: String,
+    /// SOCKS5 proxy used for peer connections; reports go through it too
+    pub proxy: Option<String>,
+}
+
+impl Telemetry {
+    pub fn new(config: TelemetryConfig, network_id: String, proxy: Option<String>) -> Self {
+        Telemetry {
+            config,
+            network_id,
+            proxy,
+        }
+    }
+
+    /// Whether reports will actually be sent
+    pub fn is_active(&self) -> bool {
+        self.config.enabled && self.config.endpoint.is_some()
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval_secs).max(MIN_REPORT_INTERVAL)
+    }
+
+    pub async fn report(&self, network: &NetworkNode) -> TelemetryReport {
+        TelemetryReport::collect(&self.network_id, network).await
+    }
+
+    /// Send one report to the configured endpoint
+    pub async fn send(&self, report: &TelemetryReport) -> Result<(), ChainError> {
+        let endpoint = self
+            .config
+            .endpoint
+            .as_deref()
+            .ok_or_else(|| ChainError::NetworkError("No telemetry endpoint configured".to_string()))?;
+        let body = serde_json::to_string(report)
+            .map_err(|e| ChainError::NetworkError(format!("Failed to encode telemetry: {}", e)))?;
+        post_json(endpoint, &body, self.proxy.as_deref()).await
+    }
+
+    /// Report every [`Self::interval`] until the process exits. Does nothing
+    /// unless telemetry is enabled and an endpoint is set.
+    pub fn spawn_reporter(self: Arc<Self>, network: Arc<NetworkNode>) {
+        if !self.is_active() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval());
+            loop {
+                interval.tick().await;
+                let report = self.report(&network).await;
+                if let Err(e) = self.send(&report).await {
+                    warn!("⚠️  Telemetry report failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Split `http://host[:port][/path]` into host, port and path
+pub(crate) fn parse_endpoint(endpoint: &str) -> Result<(String, u16, String), ChainError> {
+    let invalid = |reason: &str| ChainError::NetworkError(format!("Endpoint '{}': {}", endpoint, reason));
+    let rest = endpoint
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid("only http:// endpoints are supported"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let node = if authority.ends_with(']') || !authority.contains(':') {
+        crate::network::Node::new(authority.trim_matches(|c| c == '[' || c == ']').to_string(), 80)
+    } else {
+        authority.parse().map_err(|_| invalid("invalid host or port"))?
+    };
+    if node.host.is_empty() {
+        return Err(invalid("missing host"));
+    }
+    Ok((node.host, node.port, path.to_string()))
+}
+
+/// POST `body` as JSON to an `http://` endpoint; also used for webhooks
+pub(crate) async fn post_json(endpoint: &str, body: &str, proxy: Option<&str>) -> Result<(), ChainError> {
+    let (host, port, path) = parse_endpoint(endpoint)?;
+    let mut stream = match proxy {
+        Some(proxy) => crate::socks::connect(proxy, &host, port).await?,
+        None => TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(|e| ChainError::NetworkError(format!("Cannot reach {}: {}", endpoint, e)))?,
+    };
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| ChainError::NetworkError(format!("Malformed HTTP response from {}", endpoint)))?;
+    if !(200..300).contains(&status) {
+        return Err(ChainError::NetworkError(format!("{} answered {}", endpoint, status)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use tokio::net::TcpListener;
+    use tokio::sync::RwLock;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            parse_endpoint("http://telemetry.example.org/v1/report").unwrap(),
+            ("telemetry.example.org".to_string(), 80, "/v1/report".to_string())
+        );
+        assert_eq!(
+            parse_endpoint("http://[::1]:9000").unwrap(),
+            ("::1".to_string(), 9000, "/".to_string())
+        );
+        assert!(parse_endpoint("https://telemetry.example.org").is_err());
+        assert!(parse_endpoint("http://:80/").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_report_is_posted_to_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).contains("\"arch\"") {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let chain = Blockchain::new([1u8; 32], 0).unwrap();
+        let network = NetworkNode::new(Arc::new(RwLock::new(chain)));
+        let config = TelemetryConfig {
+            enabled: true,
+            endpoint: Some(format!("http://127.0.0.1:{}/report", port)),
+            interval_secs: 3600,
+        };
+        let telemetry = Telemetry::new(config, "devnet".to_string(), None);
+        let report = telemetry.report(&network).await;
+        assert_eq!((report.height, report.peer_count), (0, 0));
+        telemetry.send(&report).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /report HTTP/1.1"));
+        assert!(request.contains("\"network_id\":\"devnet\""));
+        assert!(!request.contains("0101010101"), "report must not contain addresses");
+    }
+}
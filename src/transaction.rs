@@ -1,19 +1,28 @@
 //! Transaction module split into types and validation for better modularity
 
+pub mod builder;
 pub mod types;
 pub mod validation;
 
+pub use builder::{sponsor_transfer, TransactionBuilder};
 pub use types::*;
 // validation module kept internal; only types are re-exported publicly
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::blockchain::TriangleState;
+    use crate::blockchain::{Sha256Hash, TriangleState};
     use crate::crypto::Address;
     use crate::crypto::KeyPair;
     use crate::error::ChainError;
-    use crate::geometry::{Coord, Point, Triangle};
+    use crate::geometry::{Coord, Point, Triangle, MIN_TRIANGLE_VALUE};
+    use sha2::{Digest, Sha256};
+
+    const CHAIN: Sha256Hash = [7; 32];
+
+    fn domain() -> SigningDomain {
+        SigningDomain::new(CHAIN)
+    }
 
     fn create_test_address(s: &str) -> Address {
         let mut address = [0u8; 32];
@@ -47,11 +56,11 @@ mod tests {
             Coord::from_num(0),
             1,
         );
-        let message = tx.signable_message();
+        let message = tx.signable_message(&CHAIN);
         let signature = keypair.sign(&message).unwrap();
         let public_key = keypair.public_key.serialize().to_vec();
         tx.sign(signature.to_vec(), public_key);
-        assert!(tx.validate(&state).is_ok());
+        assert!(tx.validate(&state, &domain()).is_ok());
     }
 
     #[test]
@@ -77,7 +86,7 @@ mod tests {
             Coord::from_num(0),
             1,
         );
-        assert!(tx.validate(&state).is_err());
+        assert!(tx.validate(&state, &domain()).is_err());
     }
 
     #[test]
@@ -108,7 +117,7 @@ mod tests {
         let public_key = keypair.public_key.serialize().to_vec();
         tx.sign(fake_signature, public_key);
 
-        assert!(tx.validate(&state).is_err());
+        assert!(tx.validate(&state, &domain()).is_err());
     }
 
     #[test]
@@ -137,7 +146,7 @@ mod tests {
         let address = keypair.address();
 
         let tx = SubdivisionTx::new(parent_hash, children, address, Coord::from_num(0), 1);
-        assert!(tx.validate(&state).is_err());
+        assert!(tx.validate(&state, &domain()).is_err());
     }
 
     #[test]
@@ -163,7 +172,7 @@ mod tests {
             1,
         );
 
-        assert!(tx.validate(&state).is_err());
+        assert!(tx.validate(&state, &domain()).is_err());
     }
 
     #[test]
@@ -197,12 +206,12 @@ mod tests {
             1,
         );
 
-        let message = tx.signable_message();
+        let message = tx.signable_message(&CHAIN);
         let signature = keypair.sign(&message).unwrap();
         let public_key = keypair.public_key.serialize().to_vec();
         tx.sign(signature.to_vec(), public_key);
 
-        assert!(tx.validate_with_state(&state).is_ok());
+        assert!(tx.validate_with_state(&state, &domain()).is_ok());
 
         let old_triangle = state.utxo_set.remove(&triangle_hash).unwrap();
         let new_value = old_triangle.effective_value() - fee_area;
@@ -256,19 +265,15 @@ mod tests {
             1,
         );
 
-        let message = tx.signable_message();
+        let message = tx.signable_message(&CHAIN);
         let signature = keypair.sign(&message).unwrap();
         let public_key = keypair.public_key.serialize().to_vec();
         tx.sign(signature.to_vec(), public_key);
 
-        let result = tx.validate_with_state(&state);
+        let result = tx.validate_with_state(&state, &domain());
         assert!(result.is_err());
 
-        if let Err(ChainError::InvalidTransaction(msg)) = result {
-            assert!(msg.contains("Insufficient"));
-        } else {
-            panic!("Expected InvalidTransaction error");
-        }
+        assert!(matches!(result, Err(ChainError::InsufficientValue { .. })));
     }
 
     #[test]
@@ -284,12 +289,323 @@ mod tests {
             1,
         );
 
-        let message = tx.signable_message();
+        let message = tx.signable_message(&CHAIN);
         let signature = keypair.sign(&message).unwrap();
         let public_key = keypair.public_key.serialize().to_vec();
         tx.sign(signature.to_vec(), public_key);
 
-        let result = tx.validate();
+        let result = tx.validate(&domain());
         assert!(result.is_err());
     }
-}
+
+    #[test]
+    fn test_overflowing_transfer_rejected_without_panic() {
+        let mut state = TriangleState::new();
+        let keypair = KeyPair::generate().unwrap();
+        let sender_address = keypair.address();
+
+        let triangle = Triangle::new(
+            Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
+            Point::new(Coord::from_num(4.0), Coord::from_num(0.0)),
+            Point::new(Coord::from_num(0.0), Coord::from_num(5.0)),
+            None,
+            sender_address,
+        );
+        let triangle_hash = triangle.hash();
+        state.utxo_set.insert(triangle_hash, triangle);
+
+        // amount + fee_area overflows I32F32
+        let mut tx = TransferTx::new(
+            triangle_hash,
+            create_test_address("recipient"),
+            sender_address,
+            Coord::MAX,
+            Coord::from_num(1.0),
+            1,
+        );
+        let signature = keypair.sign(&tx.signable_message(&CHAIN)).unwrap();
+        tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+
+        assert!(matches!(
+            tx.validate_with_state(&state, &domain()),
+            Err(ChainError::ArithmeticOverflow(_))
+        ));
+        let result = state.apply_transaction(&Transaction::Transfer(tx), 1);
+        assert!(matches!(result, Err(ChainError::ArithmeticOverflow(_))));
+        assert!(state.utxo_set.contains_key(&triangle_hash));
+    }
+
+    fn signed_subdivision(keypair: &KeyPair, parent: &Triangle) -> SubdivisionTx {
+        let mut tx = SubdivisionTx::new(
+            parent.hash(),
+            parent.subdivide().to_vec(),
+            keypair.address(),
+            Coord::from_num(0),
+            1,
+        );
+        let signature = keypair.sign(&tx.signable_message(&CHAIN)).unwrap();
+        tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        tx
+    }
+
+    #[test]
+    fn test_subdivision_below_minimum_value_rejected() {
+        let keypair = KeyPair::generate().unwrap();
+        let origin = Point::new(Coord::from_num(0), Coord::from_num(0));
+        // A value-only triangle, like a coinbase output
+        let parent = Triangle::new(origin, origin, origin, None, keypair.address())
+            .with_effective_value(MIN_TRIANGLE_VALUE * 2);
+        assert!(!parent.can_subdivide());
+
+        let mut state = TriangleState::new();
+        state.utxo_set.insert(parent.hash(), parent.clone());
+        let result = signed_subdivision(&keypair, &parent).validate(&state, &domain());
+        assert!(matches!(result, Err(ChainError::InvalidTransaction(msg)) if msg.contains("minimum")));
+
+        let funded = parent.with_effective_value(Coord::from_num(3));
+        assert!(funded.can_subdivide());
+        state.utxo_set.insert(funded.hash(), funded.clone());
+        assert!(signed_subdivision(&keypair, &funded).validate(&state, &domain()).is_ok());
+    }
+
+    #[test]
+    fn test_subdivision_below_geometric_tolerance_rejected() {
+        let keypair = KeyPair::generate().unwrap();
+        // Valuable, but so small that its children have no meaningful area
+        let tiny = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(0.005), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(0.005)),
+            None,
+            keypair.address(),
+        )
+        .with_effective_value(Coord::from_num(1));
+        assert!(!tiny.can_subdivide());
+
+        let mut state = TriangleState::new();
+        state.utxo_set.insert(tiny.hash(), tiny.clone());
+        let result = signed_subdivision(&keypair, &tiny).validate(&state, &domain());
+        assert!(matches!(result, Err(ChainError::InvalidTransaction(msg)) if msg.contains("tolerance")));
+    }
+
+    #[test]
+    fn test_subdivision_child_ids_must_follow_parent() {
+        let keypair = KeyPair::generate().unwrap();
+        let parent = Triangle::new(
+            Point::new(Coord::from_num(0.0), Coord::from_num(0.0)),
+            Point::new(Coord::from_num(4.0), Coord::from_num(0.0)),
+            Point::new(Coord::from_num(0.0), Coord::from_num(4.0)),
+            None,
+            keypair.address(),
+        )
+        .with_id(crate::geometry::TriangleId::root([5; 32]));
+        let mut state = TriangleState::new();
+        state.utxo_set.insert(parent.hash(), parent.clone());
+        assert!(signed_subdivision(&keypair, &parent).validate(&state, &domain()).is_ok());
+
+        // Right geometry, but the first two children claim each other's address
+        let mut children = parent.subdivide().to_vec();
+        let first_id = children[0].id.take();
+        children[0].id = children[1].id.replace(first_id.unwrap());
+        let mut tx = SubdivisionTx::new(parent.hash(), children, keypair.address(), Coord::from_num(0), 1);
+        let signature = keypair.sign(&tx.signable_message(&CHAIN)).unwrap();
+        tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        let result = tx.validate(&state, &domain());
+        assert!(matches!(result, Err(ChainError::InvalidTransaction(msg)) if msg.contains("structural id")));
+    }
+
+    #[test]
+    fn test_txid_excludes_witness_and_wtxid_commits_to_it() {
+        let keypair = KeyPair::generate().unwrap();
+        let unsigned = TransferTx::new([1; 32], [2; 32], keypair.address(), Coord::from_num(5), Coord::from_num(0), 1);
+        let sign = |tx: &TransferTx| {
+            let mut tx = tx.clone();
+            let signature = keypair.sign(&tx.signable_message(&CHAIN)).unwrap();
+            tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+            Transaction::Transfer(tx)
+        };
+        let signed = sign(&unsigned);
+        let with_memo = sign(&unsigned.clone().with_memo("rent".to_string()).unwrap());
+        let unsigned = Transaction::Transfer(unsigned);
+
+        // Signing and memos never change the txid...
+        assert_eq!(signed.txid(), unsigned.txid());
+        assert_eq!(with_memo.txid(), unsigned.txid());
+        // ...but do change the wtxid, which is the txid when there is no witness
+        assert_eq!(unsigned.wtxid(), unsigned.txid());
+        assert_ne!(signed.wtxid(), signed.txid());
+        assert_ne!(with_memo.wtxid(), signed.wtxid());
+
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [3; 32],
+            nonce: 0,
+            height: 0,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        assert_eq!(coinbase.wtxid(), coinbase.txid());
+
+        // From the activation height a swapped witness changes the block's
+        // merkle root; below it the root covers txids as it always did
+        use crate::blockchain::{Block, WITNESS_MERKLE_ACTIVATION_HEIGHT};
+        let root = |height: u64, tx: &Transaction| Block::calculate_merkle_root(height, &[coinbase.clone(), tx.clone()]);
+        let activation = WITNESS_MERKLE_ACTIVATION_HEIGHT;
+        assert_ne!(root(activation, &signed), root(activation, &with_memo));
+        assert_eq!(root(activation - 1, &signed), root(activation - 1, &with_memo));
+        let mut hasher = Sha256::new();
+        hasher.update(coinbase.txid());
+        hasher.update(signed.txid());
+        assert_eq!(root(activation - 1, &signed), <[u8; 32]>::from(hasher.finalize()));
+    }
+
+    #[test]
+    fn test_coinbase_extra_nonce_is_opaque_but_bounded() {
+        let coinbase = |extra_nonce: Vec<u8>| CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [3; 32],
+            nonce: 0,
+            height: 0,
+            fees: Coord::from_num(0),
+            extra_nonce,
+            outputs: Vec::new(),
+        };
+        let legacy = Transaction::Coinbase(coinbase(Vec::new()));
+        let rolled = Transaction::Coinbase(coinbase(vec![0xab; CoinbaseTx::MAX_EXTRA_NONCE_LEN]));
+        assert_ne!(legacy.txid(), rolled.txid());
+
+        // Coinbases from before the height, fees and extra nonce fields keep
+        // their txids, so their outputs stay spendable
+        let mut hasher = Sha256::new();
+        hasher.update("coinbase".as_bytes());
+        hasher.update(Coord::from_num(1).to_le_bytes());
+        hasher.update([3u8; 32]);
+        hasher.update(0u64.to_le_bytes());
+        assert_eq!(legacy.txid(), <[u8; 32]>::from(hasher.finalize()));
+        let committed = |height: u64, fees: Coord| {
+            Transaction::Coinbase(CoinbaseTx { height, fees, ..coinbase(Vec::new()) }).txid()
+        };
+        assert_ne!(committed(1, Coord::from_num(0)), legacy.txid());
+        assert_ne!(committed(0, Coord::from_num(1)), legacy.txid());
+        assert_ne!(committed(1, Coord::from_num(0)), committed(0, Coord::from_bits(1)));
+        assert!(coinbase(vec![0xab; CoinbaseTx::MAX_EXTRA_NONCE_LEN]).validate().is_ok());
+        assert!(coinbase(vec![0; CoinbaseTx::MAX_EXTRA_NONCE_LEN + 1]).validate().is_err());
+    }
+    #[test]
+    fn test_sponsor_pays_fee_from_its_own_triangle() {
+        let alice = KeyPair::generate().unwrap();
+        let sponsor = KeyPair::generate().unwrap();
+        let triangle = |size: i32, owner: Address| {
+            Triangle::new(
+                Point::new(Coord::from_num(0), Coord::from_num(0)),
+                Point::new(Coord::from_num(size), Coord::from_num(0)),
+                Point::new(Coord::from_num(0), Coord::from_num(size)),
+                None,
+                owner,
+            )
+        };
+        let mut state = TriangleState::new();
+        let (alice_triangle, sponsor_triangle) = (triangle(4, alice.address()), triangle(2, sponsor.address()));
+        let (alice_utxo, sponsor_utxo) = (alice_triangle.hash(), sponsor_triangle.hash());
+        state.utxo_set.insert(alice_utxo, alice_triangle);
+        state.utxo_set.insert(sponsor_utxo, sponsor_triangle);
+        state.rebuild_address_balances();
+
+        // Alice spends all but a sliver of her triangle and pays no fee
+        let build = || {
+            TransactionBuilder::new()
+                .chain(CHAIN)
+                .input(alice_utxo)
+                .to([9; 32])
+                .amount(Coord::from_num(7.5))
+                .fee(Coord::from_num(0.5))
+                .sponsor(sponsor_utxo, sponsor.address())
+                .check_against(&state)
+        };
+        let unsponsored = build().sign(&alice).unwrap();
+        assert!(matches!(unsponsored.validate(&state, &domain()), Err(ChainError::InvalidTransaction(msg)) if msg.contains("not signed")));
+        assert!(sponsor_transfer(unsponsored.clone(), &alice, &CHAIN).is_err());
+        let tx = sponsor_transfer(unsponsored.clone(), &sponsor, &CHAIN).unwrap();
+        assert_eq!(tx.txid(), unsponsored.txid());
+        assert_ne!(tx.wtxid(), unsponsored.wtxid());
+        assert_eq!(tx.inputs(), vec![alice_utxo, sponsor_utxo]);
+
+        let Transaction::Transfer(transfer) = &tx else {
+            panic!("expected a transfer");
+        };
+        transfer.validate_with_state(&state, &domain()).unwrap();
+        // The sponsor's signature is checked on its own and does not carry
+        // over to another of its triangles
+        let mut moved = transfer.clone();
+        moved.sponsor.as_mut().unwrap().input_hash = [5; 32];
+        assert!(moved.validate_sender(&domain()).is_ok());
+        assert!(moved.validate(&domain()).is_err());
+
+        let mut after = state.clone();
+        after.apply_transaction(&tx, 1).unwrap();
+        assert_eq!(after.get_balance(&alice.address()), Coord::from_num(0.5));
+        assert_eq!(after.get_balance(&sponsor.address()), Coord::from_num(1.5));
+        assert_eq!(after.get_balance(&[9; 32]), Coord::from_num(7.5));
+        assert!(!after.utxo_set.contains_key(&sponsor_utxo));
+        let inputs = crate::blockchain::spent_inputs(&tx, &state);
+        let mut created: Vec<_> = crate::blockchain::created_outputs(&tx, &inputs)
+            .unwrap()
+            .into_iter()
+            .map(|(output, _)| output)
+            .collect();
+        let mut expected: Vec<_> = after.utxo_set.keys().copied().collect();
+        created.sort();
+        expected.sort();
+        assert_eq!(created, expected);
+
+        // A sponsor too small for the fee is refused
+        assert!(matches!(
+            build().fee(Coord::from_num(3)).sign(&alice),
+            Err(ChainError::InsufficientValue { .. })
+        ));
+
+        // So is a sender paying out more than its triangle holds, whether
+        // the fee is sponsored or its own
+        let mut overspent = transfer.clone();
+        overspent.amount = Coord::from_num(8.5);
+        let own_fee = TransferTx::new(alice_utxo, [9; 32], alice.address(), Coord::from_num(7.5), Coord::from_num(1), 1);
+        for tx in [Transaction::Transfer(overspent), Transaction::Transfer(own_fee)] {
+            let mut after = state.clone();
+            assert!(matches!(after.apply_transaction(&tx, 1), Err(ChainError::InsufficientValue { .. })));
+            assert_eq!(after.utxo_set, state.utxo_set);
+        }
+    }
+
+    #[test]
+    fn test_signatures_are_bound_to_one_chain() {
+        let keypair = KeyPair::generate().unwrap();
+        let mut tx = TransferTx::new([1; 32], [2; 32], keypair.address(), Coord::from_num(5), Coord::from_num(0), 1);
+        let signed = |tx: &TransferTx, message: Vec<u8>| {
+            let mut tx = tx.clone();
+            tx.sign(keypair.sign(&message).unwrap().to_vec(), keypair.public_key.serialize().to_vec());
+            tx
+        };
+        let testnet = SigningDomain::new([8; 32]);
+
+        // Signed for one chain, the transfer cannot be replayed on another
+        let bound = signed(&tx, tx.signable_message(&CHAIN));
+        assert!(bound.validate(&domain()).is_ok());
+        assert!(bound.validate(&testnet).is_err());
+        assert!(bound.validate(&SigningDomain { allow_legacy: true, ..testnet }).is_err());
+
+        // Signatures without the chain id only pass where still allowed
+        let legacy = signed(&tx, tx.legacy_signable_message());
+        assert!(legacy.validate(&domain()).is_err());
+        assert!(legacy.validate(&SigningDomain { allow_legacy: true, ..domain() }).is_ok());
+
+        // The same holds for a fee sponsor's signature
+        tx = tx.with_sponsor([3; 32], keypair.address());
+        tx.fee_area = Coord::from_num(1);
+        let mut sponsored = signed(&tx, tx.signable_message(&CHAIN));
+        let message = sponsored.sponsor_signable_message(&[8; 32]).unwrap();
+        sponsored.sign_sponsor(keypair.sign(&message).unwrap().to_vec(), keypair.public_key.serialize().to_vec());
+        assert!(sponsored.validate_sender(&domain()).is_ok());
+        assert!(sponsored.validate(&domain()).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,266 @@
+//! Checked construction of transfers
+//!
+//! [`TransactionBuilder`] collects the fields of a [`TransferTx`], refuses to
+//! build until the required ones are set, and signs with the right message,
+//! so code embedding the crate never assembles or signs a transfer by hand:
+//!
+//! ```no_run
+//! # use trinitychain::crypto::KeyPair;
+//! # use trinitychain::geometry::Coord;
+//! # use trinitychain::transaction::TransactionBuilder;
+//! # fn example(keypair: &KeyPair, chain_id: [u8; 32], utxo: [u8; 32], recipient: [u8; 32]) -> Result<(), trinitychain::error::ChainError> {
+//! let tx = TransactionBuilder::new()
+//!     .chain(chain_id)
+//!     .input(utxo)
+//!     .to(recipient)
+//!     .amount(Coord::from_num(5))
+//!     .fee(Coord::from_num(0.01))
+//!     .memo("rent")
+//!     .sign(keypair)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::types::{SigningDomain, Transaction, TransferTx};
+use crate::blockchain::{Sha256Hash, TriangleState};
+use crate::crypto::{Address, KeyPair};
+use crate::error::ChainError;
+use crate::geometry::Coord;
+
+/// Computes the fee of an unsigned transfer, e.g. from its size; see
+/// [`TransactionBuilder::fee_with`]
+pub type FeeHook<'a> = Box<dyn Fn(&TransferTx) -> Coord + 'a>;
+
+enum Fee<'a> {
+    Fixed(Coord),
+    Estimated(FeeHook<'a>),
+}
+
+enum Memo<'a> {
+    Plain(String),
+    Encrypted { text: String, recipient_key: &'a [u8] },
+}
+
+/// Builder for a signed [`Transaction::Transfer`]. Chain, input, recipient
+/// and amount are required; the fee defaults to zero and the nonce to a
+/// random value, so two otherwise identical payments still get different
+/// txids.
+#[derive(Default)]
+pub struct TransactionBuilder<'a> {
+    chain_id: Option<Sha256Hash>,
+    input: Option<Sha256Hash>,
+    recipient: Option<Address>,
+    amount: Option<Coord>,
+    fee: Option<Fee<'a>>,
+    nonce: Option<u64>,
+    memo: Option<Memo<'a>>,
+    sponsor: Option<(Sha256Hash, Address)>,
+    state: Option<&'a TriangleState>,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The chain the transfer is signed for, identified by the hash of its
+    /// genesis block (`Blockchain::chain_id`)
+    pub fn chain(mut self, chain_id: Sha256Hash) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    /// The unspent triangle paid from
+    pub fn input(mut self, utxo: Sha256Hash) -> Self {
+        self.input = Some(utxo);
+        self
+    }
+
+    pub fn to(mut self, recipient: Address) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+
+    pub fn amount(mut self, amount: Coord) -> Self {
+        self.amount = Some(amount);
+        self
+    }
+
+    /// Pay exactly `fee_area` to the miner
+    pub fn fee(mut self, fee_area: Coord) -> Self {
+        self.fee = Some(Fee::Fixed(fee_area));
+        self
+    }
+
+    /// Compute the fee from the finished unsigned transfer, memo included
+    pub fn fee_with(mut self, estimate: impl Fn(&TransferTx) -> Coord + 'a) -> Self {
+        self.fee = Some(Fee::Estimated(Box::new(estimate)));
+        self
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Attach a plain-text memo, visible to everyone
+    pub fn memo(mut self, memo: impl Into<String>) -> Self {
+        self.memo = Some(Memo::Plain(memo.into()));
+        self
+    }
+
+    /// Attach a memo encrypted to the recipient's compressed public key,
+    /// which must match the recipient address
+    pub fn encrypted_memo(mut self, memo: impl Into<String>, recipient_key: &'a [u8]) -> Self {
+        self.memo = Some(Memo::Encrypted {
+            text: memo.into(),
+            recipient_key,
+        });
+        self
+    }
+
+    /// Have `payer` pay the fee from its triangle `utxo`. The signed
+    /// transfer is then handed to the sponsor, who adds its signature with
+    /// [`sponsor_transfer`] before it is submitted.
+    pub fn sponsor(mut self, utxo: Sha256Hash, payer: Address) -> Self {
+        self.sponsor = Some((utxo, payer));
+        self
+    }
+
+    /// Also check the input exists, belongs to the signer and covers amount
+    /// and fee in `state` when building
+    pub fn check_against(mut self, state: &'a TriangleState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Build the transfer from `sender` without signing it, for signing
+    /// elsewhere with [`TransferTx::signable_message`]
+    pub fn build_unsigned(self, sender: Address) -> Result<TransferTx, ChainError> {
+        let missing = |field: &str| ChainError::InvalidTransaction(format!("Transfer is missing its {}", field));
+        let input = self.input.ok_or_else(|| missing("input"))?;
+        let recipient = self.recipient.ok_or_else(|| missing("recipient"))?;
+        let amount = self.amount.ok_or_else(|| missing("amount"))?;
+        let nonce = self.nonce.unwrap_or_else(rand::random);
+
+        let mut tx = TransferTx::new(input, recipient, sender, amount, Coord::from_num(0), nonce);
+        tx = match self.memo {
+            Some(Memo::Plain(text)) => tx.with_memo(text)?,
+            Some(Memo::Encrypted { text, recipient_key }) => tx.with_encrypted_memo(&text, recipient_key)?,
+            None => tx,
+        };
+        if let Some((utxo, payer)) = self.sponsor {
+            tx = tx.with_sponsor(utxo, payer);
+        }
+        tx.fee_area = match &self.fee {
+            Some(Fee::Fixed(fee_area)) => *fee_area,
+            Some(Fee::Estimated(estimate)) => estimate(&tx),
+            None => Coord::from_num(0),
+        };
+        Ok(tx)
+    }
+
+    /// Build the transfer from `keypair`'s address, sign it and run the same
+    /// checks a node applies before accepting it
+    pub fn sign(self, keypair: &KeyPair) -> Result<Transaction, ChainError> {
+        let state = self.state;
+        let chain_id = self
+            .chain_id
+            .ok_or_else(|| ChainError::InvalidTransaction("Transfer is missing its chain id".to_string()))?;
+        let mut tx = self.build_unsigned(keypair.address())?;
+        let signature = keypair.sign(&tx.signable_message(&chain_id))?;
+        tx.sign(signature.to_vec(), keypair.public_key.serialize().to_vec());
+        // A fee sponsor, if any, signs afterwards
+        tx.validate_sender(&SigningDomain::new(chain_id))?;
+        if let Some(state) = state {
+            tx.validate_inputs(state)?;
+        }
+        let tx = Transaction::Transfer(tx);
+        tx.validate_size()?;
+        Ok(tx)
+    }
+}
+
+/// Sign `tx` as its fee sponsor with `keypair`, whose address must be the
+/// payer [`TransactionBuilder::sponsor`] named, for the chain `chain_id`,
+/// and check the result
+pub fn sponsor_transfer(tx: Transaction, keypair: &KeyPair, chain_id: &Sha256Hash) -> Result<Transaction, ChainError> {
+    let Transaction::Transfer(mut transfer) = tx else {
+        return Err(ChainError::InvalidTransaction("Only transfers can be sponsored".to_string()));
+    };
+    let message = transfer
+        .sponsor_signable_message(chain_id)
+        .ok_or_else(|| ChainError::InvalidTransaction("Transfer has no fee sponsor".to_string()))?;
+    let signature = keypair.sign(&message)?;
+    transfer.sign_sponsor(signature.to_vec(), keypair.public_key.serialize().to_vec());
+    transfer.validate(&SigningDomain::new(*chain_id))?;
+    Ok(Transaction::Transfer(transfer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Point, Triangle};
+
+    const CHAIN: Sha256Hash = [7; 32];
+
+    fn funded_state(owner: Address) -> (TriangleState, Sha256Hash) {
+        let mut state = TriangleState::new();
+        let triangle = Triangle::new(
+            Point::new(Coord::from_num(0), Coord::from_num(0)),
+            Point::new(Coord::from_num(10), Coord::from_num(0)),
+            Point::new(Coord::from_num(0), Coord::from_num(10)),
+            None,
+            owner,
+        );
+        let hash = triangle.hash();
+        state.utxo_set.insert(hash, triangle);
+        (state, hash)
+    }
+
+    #[test]
+    fn test_builder_signs_and_checks() {
+        let alice = KeyPair::generate().unwrap();
+        let (state, utxo) = funded_state(alice.address());
+
+        let tx = TransactionBuilder::new()
+            .chain(CHAIN)
+            .input(utxo)
+            .to([2; 32])
+            .amount(Coord::from_num(5))
+            .fee_with(|unsigned| Coord::from_num(unsigned.memo.as_ref().map_or(0, String::len)) / 100)
+            .memo("rent")
+            .nonce(7)
+            .check_against(&state)
+            .sign(&alice)
+            .unwrap();
+        let Transaction::Transfer(transfer) = &tx else {
+            panic!("expected a transfer");
+        };
+        assert_eq!(transfer.sender, alice.address());
+        assert_eq!(transfer.fee_area, Coord::from_num(4) / 100);
+        assert_eq!(transfer.nonce, 7);
+        assert!(transfer.validate_with_state(&state, &SigningDomain::new(CHAIN)).is_ok());
+        assert!(transfer.validate(&SigningDomain::new([8; 32])).is_err());
+
+        // Missing fields, overspending and a foreign input are refused
+        let missing = TransactionBuilder::new().chain(CHAIN).input(utxo).amount(Coord::from_num(1)).sign(&alice);
+        assert!(matches!(missing, Err(ChainError::InvalidTransaction(msg)) if msg.contains("recipient")));
+        let unbound = TransactionBuilder::new().input(utxo).to([2; 32]).amount(Coord::from_num(1)).sign(&alice);
+        assert!(matches!(unbound, Err(ChainError::InvalidTransaction(msg)) if msg.contains("chain id")));
+        let base = || TransactionBuilder::new().chain(CHAIN).input(utxo).to([2; 32]).check_against(&state);
+        assert!(matches!(
+            base().amount(Coord::from_num(60)).sign(&alice),
+            Err(ChainError::InsufficientValue { .. })
+        ));
+        let mallory = KeyPair::generate().unwrap();
+        assert!(base().amount(Coord::from_num(1)).sign(&mallory).is_err());
+        assert!(TransactionBuilder::new()
+            .chain(CHAIN)
+            .input(utxo)
+            .to(alice.address())
+            .amount(Coord::from_num(1))
+            .sign(&alice)
+            .is_err());
+    }
+}
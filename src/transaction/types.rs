@@ -8,12 +8,57 @@ use sha2::{Digest, Sha256};
 /// Maximum transaction size in bytes (100KB) to prevent DoS
 pub const MAX_TRANSACTION_SIZE: usize = 100_000;
 
+/// Tag in front of the chain id in every signed message
+const CHAIN_TAG: &[u8] = b"CHAIN:";
+
+/// `message` bound to the chain whose genesis block hash is `chain_id`
+fn chain_bound(chain_id: &Sha256Hash, message: &[u8]) -> Vec<u8> {
+    let mut bound = Vec::with_capacity(CHAIN_TAG.len() + chain_id.len() + message.len());
+    bound.extend_from_slice(CHAIN_TAG);
+    bound.extend_from_slice(chain_id);
+    bound.extend_from_slice(message);
+    bound
+}
+
+/// The chain transaction signatures are checked for. Signatures cover the
+/// chain id, the hash of the genesis block, so a transaction signed for one
+/// network is invalid on every other one even when the same keys hold
+/// triangles there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningDomain {
+    pub chain_id: Sha256Hash,
+    /// Also accept signatures made before they covered the chain id
+    pub allow_legacy: bool,
+}
+
+impl SigningDomain {
+    /// Domain accepting only signatures that cover `chain_id`
+    pub fn new(chain_id: Sha256Hash) -> Self {
+        SigningDomain {
+            chain_id,
+            allow_legacy: false,
+        }
+    }
+
+    /// Check `signature` over `message`, a transaction's legacy signable
+    /// message, bound to the chain
+    pub fn verify(&self, public_key: &[u8], message: &[u8], signature: &[u8]) -> Result<(), ChainError> {
+        match crate::crypto::verify_signature(public_key, &chain_bound(&self.chain_id, message), signature) {
+            Err(_) if self.allow_legacy => crate::crypto::verify_signature(public_key, message, signature),
+            result => result,
+        }
+    }
+}
+
 /// A transaction that can occur in a block
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Transaction {
     Transfer(TransferTx),
     Subdivision(SubdivisionTx),
     Coinbase(CoinbaseTx),
+    Announce(AnnounceTx),
+    Register(RegisterNameTx),
+    Data(DataTx),
 }
 
 impl Transaction {
@@ -21,16 +66,30 @@ impl Transaction {
         hex::encode(self.hash())
     }
 
+    /// Size in bytes of the canonical (bincode) encoding, as relayed between
+    /// peers and stored in blocks
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).map_or(0, |size| size as usize)
+    }
+
+    /// Fee area paid per byte of [`Self::size`], for comparing fees of
+    /// transactions of different sizes
+    pub fn fee_per_byte(&self) -> f64 {
+        match self.size() {
+            0 => 0.0,
+            size => self.fee_area().to_num::<f64>() / size as f64,
+        }
+    }
+
     /// Validate transaction size to prevent DoS attacks
     pub fn validate_size(&self) -> Result<(), ChainError> {
-        let serialized = bincode::serialize(self)
+        let size = bincode::serialized_size(self)
             .map_err(|e| ChainError::InvalidTransaction(format!("Serialization failed: {}", e)))?;
 
-        if serialized.len() > MAX_TRANSACTION_SIZE {
+        if size as usize > MAX_TRANSACTION_SIZE {
             return Err(ChainError::InvalidTransaction(format!(
                 "Transaction too large: {} bytes (max: {})",
-                serialized.len(),
-                MAX_TRANSACTION_SIZE
+                size, MAX_TRANSACTION_SIZE
             )));
         }
         Ok(())
@@ -41,7 +100,9 @@ impl Transaction {
         match self {
             Transaction::Subdivision(tx) => tx.fee_area,
             Transaction::Transfer(tx) => tx.fee_area,
+            Transaction::Data(tx) => tx.fee_area,
             Transaction::Coinbase(_) => Coord::from_num(0), // Coinbase has no fee
+            Transaction::Announce(_) | Transaction::Register(_) => Coord::from_num(0),
         }
     }
 
@@ -51,7 +112,10 @@ impl Transaction {
         self.fee_area().to_num::<u64>()
     }
 
-    /// Calculate the hash of this transaction
+    /// Transaction id. Commits to everything except the witness data (see
+    /// [`Transaction::wtxid`]), so it is fixed once the transaction is built
+    /// and re-signing or relaying cannot change it. Outputs, mempool entries
+    /// and lookups are keyed by it.
     pub fn hash(&self) -> [u8; 32] {
         let mut hasher = Sha256::new();
         match self {
@@ -69,6 +133,25 @@ impl Transaction {
                 hasher.update(tx.reward_area.to_le_bytes());
                 hasher.update(tx.beneficiary_address);
                 hasher.update(tx.nonce.to_le_bytes());
+                // Coinbases without a height, claimed fees or an extra nonce
+                // hash exactly as before those fields existed
+                if tx.height != 0 {
+                    hasher.update("height".as_bytes());
+                    hasher.update(tx.height.to_le_bytes());
+                }
+                if tx.fees != Coord::from_num(0) {
+                    hasher.update("fees".as_bytes());
+                    hasher.update(tx.fees.to_le_bytes());
+                }
+                if !tx.extra_nonce.is_empty() {
+                    hasher.update(&tx.extra_nonce);
+                }
+                // ...and without reward outputs as before those existed
+                for output in &tx.outputs {
+                    hasher.update("output".as_bytes());
+                    hasher.update(output.address);
+                    hasher.update(output.amount.to_le_bytes());
+                }
             }
             Transaction::Transfer(tx) => {
                 hasher.update("transfer".as_bytes());
@@ -78,8 +161,96 @@ impl Transaction {
                 hasher.update(tx.amount.to_le_bytes());
                 hasher.update(tx.fee_area.to_le_bytes());
                 hasher.update(tx.nonce.to_le_bytes());
+                // Unsponsored transfers hash exactly as before the field
+                // existed
+                if let Some(sponsor) = &tx.sponsor {
+                    hasher.update("sponsor".as_bytes());
+                    hasher.update(sponsor.input_hash);
+                    hasher.update(sponsor.payer);
+                }
+            }
+            Transaction::Announce(tx) => {
+                hasher.update("announce".as_bytes());
+                hasher.update(&tx.public_key);
+            }
+            Transaction::Register(tx) => {
+                hasher.update("register".as_bytes());
+                hasher.update(tx.name.as_bytes());
+                hasher.update(tx.owner);
+                hasher.update(tx.nonce.to_le_bytes());
             }
+            // The payload itself stays out of the txid so it can be pruned
+            Transaction::Data(tx) => {
+                hasher.update("data".as_bytes());
+                hasher.update(tx.input_hash);
+                hasher.update(tx.owner);
+                hasher.update(tx.payload_hash);
+                hasher.update(tx.payload_len.to_le_bytes());
+                hasher.update(tx.fee_area.to_le_bytes());
+                hasher.update(tx.nonce.to_le_bytes());
+            }
+        };
+        hasher.finalize().into()
+    }
+
+    /// Same as [`Transaction::hash`]
+    pub fn txid(&self) -> Sha256Hash {
+        self.hash()
+    }
+
+    /// UTXOs this transaction consumes: the input of a transfer or data
+    /// transaction or the parent of a subdivision, then the triangle of a
+    /// fee sponsor
+    pub fn inputs(&self) -> Vec<Sha256Hash> {
+        match self {
+            Transaction::Transfer(tx) => std::iter::once(tx.input_hash)
+                .chain(tx.sponsor.as_ref().map(|sponsor| sponsor.input_hash))
+                .collect(),
+            Transaction::Subdivision(tx) => vec![tx.parent_hash],
+            Transaction::Data(tx) => vec![tx.input_hash],
+            Transaction::Coinbase(_) | Transaction::Announce(_) | Transaction::Register(_) => Vec::new(),
+        }
+    }
+
+    /// Witness transaction id: the txid extended with the witness data, i.e.
+    /// the signature and public key, plus the unsigned memo and the fee
+    /// sponsor's signature and public key of transfers.
+    /// Equal to the txid for transactions without any. Block merkle roots
+    /// commit to it, so none of these can be swapped in a relayed block.
+    pub fn wtxid(&self) -> Sha256Hash {
+        let (signature, public_key, memo) = match self {
+            Transaction::Transfer(tx) => (&tx.signature, &tx.public_key, tx.memo.as_ref().map(|m| m.as_bytes())),
+            Transaction::Subdivision(tx) => (&tx.signature, &tx.public_key, None),
+            Transaction::Register(tx) => (&tx.signature, &tx.public_key, None),
+            Transaction::Data(tx) => (&tx.signature, &tx.public_key, None),
+            Transaction::Coinbase(_) | Transaction::Announce(_) => return self.hash(),
+        };
+        let sponsor = match self {
+            Transaction::Transfer(tx) => tx.sponsor.as_ref(),
+            _ => None,
         };
+        if signature.is_none() && public_key.is_none() && memo.is_none() && sponsor.is_none() {
+            return self.hash();
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update("witness".as_bytes());
+        hasher.update(self.hash());
+        // Length-prefixed so no two witnesses share an encoding
+        let sponsor_fields = sponsor.map(|s| [s.signature.as_deref(), s.public_key.as_deref()]);
+        for field in [signature.as_deref(), public_key.as_deref(), memo]
+            .into_iter()
+            .chain(sponsor_fields.into_iter().flatten())
+        {
+            match field {
+                Some(bytes) => {
+                    hasher.update([1u8]);
+                    hasher.update((bytes.len() as u64).to_le_bytes());
+                    hasher.update(bytes);
+                }
+                None => hasher.update([0u8]),
+            }
+        }
         hasher.finalize().into()
     }
 }
@@ -115,7 +286,13 @@ impl SubdivisionTx {
         }
     }
 
-    pub fn signable_message(&self) -> Vec<u8> {
+    /// What the owner signs for the chain `chain_id`
+    pub fn signable_message(&self, chain_id: &Sha256Hash) -> Vec<u8> {
+        chain_bound(chain_id, &self.legacy_signable_message())
+    }
+
+    /// What was signed before signatures covered the chain id
+    pub fn legacy_signable_message(&self) -> Vec<u8> {
         let mut message = Vec::new();
         message.extend_from_slice(&self.parent_hash);
         for child in &self.children {
@@ -140,12 +317,73 @@ pub struct CoinbaseTx {
     pub beneficiary_address: Address,
     #[serde(default)]
     pub nonce: u64,
+    /// Height of the block this coinbase belongs to, so coinbases paying
+    /// the same reward to the same address never share a hash
+    #[serde(default)]
+    pub height: u64,
+    /// Fees of the block claimed on top of the subsidy
+    #[serde(default)]
+    pub fees: Coord,
+    /// Arbitrary bytes miners change once the header nonce space is
+    /// exhausted, giving the block a new merkle root to search under
+    #[serde(default)]
+    pub extra_nonce: Vec<u8>,
+    /// Shares of the reward paid to other addresses, as the network's
+    /// reward split requires (see `ConsensusParams::reward_shares`). The
+    /// beneficiary receives what is left.
+    #[serde(default)]
+    pub outputs: Vec<CoinbaseOutput>,
+}
+
+/// Part of a coinbase reward paid to an address other than the beneficiary
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CoinbaseOutput {
+    pub address: Address,
+    pub amount: Coord,
 }
 
 impl CoinbaseTx {
     /// Maximum reward area that can be claimed in a coinbase transaction
     pub const MAX_REWARD_AREA: Coord = Coord::from_bits(1000i64 << 32);
 
+    /// Longest extra nonce accepted; its content is never interpreted
+    pub const MAX_EXTRA_NONCE_LEN: usize = 32;
+
+    /// Most reward outputs a coinbase may carry
+    pub const MAX_OUTPUTS: usize = 16;
+
+    /// What the beneficiary receives: the reward less the outputs
+    pub fn beneficiary_amount(&self) -> Result<Coord, ChainError> {
+        let outputs = crate::geometry::coord_checked_sum(self.outputs.iter().map(|output| output.amount))?;
+        crate::geometry::coord_checked_sub(self.reward_area, outputs)
+    }
+
+    /// Every (address, amount) the coinbase pays, the beneficiary first
+    pub fn payouts(&self) -> Result<Vec<(Address, Coord)>, ChainError> {
+        let mut payouts = vec![(self.beneficiary_address, self.beneficiary_amount()?)];
+        payouts.extend(self.outputs.iter().map(|output| (output.address, output.amount)));
+        Ok(payouts)
+    }
+
+    /// Total the coinbase pays to `address`
+    pub fn paid_to(&self, address: &Address) -> Coord {
+        self.payouts()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(payee, _)| payee == address)
+            .fold(Coord::from_num(0), |sum, (_, amount)| sum.saturating_add(amount))
+    }
+
+    /// UTXO hash of the output at `index`; the beneficiary's triangle is
+    /// keyed by the txid itself
+    pub fn output_hash(txid: &Sha256Hash, index: usize) -> Sha256Hash {
+        let mut hasher = Sha256::new();
+        hasher.update("coinbase output".as_bytes());
+        hasher.update(txid);
+        hasher.update((index as u64).to_le_bytes());
+        hasher.finalize().into()
+    }
+
     pub fn validate(&self) -> Result<(), ChainError> {
         // Validate reward area is within acceptable bounds
         if self.reward_area <= Coord::from_num(0) {
@@ -169,6 +407,34 @@ impl CoinbaseTx {
             ));
         }
 
+        if self.extra_nonce.len() > Self::MAX_EXTRA_NONCE_LEN {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Coinbase extra nonce is {} bytes, at most {} are allowed",
+                self.extra_nonce.len(),
+                Self::MAX_EXTRA_NONCE_LEN
+            )));
+        }
+
+        if self.outputs.len() > Self::MAX_OUTPUTS {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Coinbase has {} reward outputs, at most {} are allowed",
+                self.outputs.len(),
+                Self::MAX_OUTPUTS
+            )));
+        }
+        for output in &self.outputs {
+            if output.address == [0; 32] || output.amount <= Coord::from_num(0) {
+                return Err(ChainError::InvalidTransaction(
+                    "Coinbase reward outputs must pay a positive amount to a non-empty address".to_string(),
+                ));
+            }
+        }
+        if self.beneficiary_amount()? <= Coord::from_num(0) {
+            return Err(ChainError::InvalidTransaction(
+                "Coinbase reward outputs leave nothing for the beneficiary".to_string(),
+            ));
+        }
+
         Ok(())
     }
 }
@@ -189,6 +455,23 @@ pub struct TransferTx {
     pub public_key: Option<Vec<u8>>,
     #[serde(default)]
     pub memo: Option<String>,
+    /// Third party paying `fee_area` from its own triangle, so the sender's
+    /// input only has to cover `amount`
+    #[serde(default)]
+    pub sponsor: Option<FeeSponsor>,
+}
+
+/// Fee payer of a sponsored transfer. Its signature covers only the fee:
+/// the sponsor's triangle, the amount paid from it and the transfer input it
+/// pays for, which can be spent only once. The sender signs the transfer
+/// without it, so either side can sign first.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FeeSponsor {
+    /// Triangle the fee is deducted from; the rest returns to `payer`
+    pub input_hash: Sha256Hash,
+    pub payer: Address,
+    pub signature: Option<Vec<u8>>,
+    pub public_key: Option<Vec<u8>>,
 }
 
 impl TransferTx {
@@ -212,9 +495,22 @@ impl TransferTx {
             signature: None,
             public_key: None,
             memo: None,
+            sponsor: None,
         }
     }
 
+    /// Have `payer` pay the fee from its triangle `input_hash`. The sponsor
+    /// still has to sign [`Self::sponsor_signable_message`].
+    pub fn with_sponsor(mut self, input_hash: Sha256Hash, payer: Address) -> Self {
+        self.sponsor = Some(FeeSponsor {
+            input_hash,
+            payer,
+            signature: None,
+            public_key: None,
+        });
+        self
+    }
+
     pub fn with_memo(mut self, memo: String) -> Result<Self, ChainError> {
         if memo.len() > Self::MAX_MEMO_LENGTH {
             return Err(ChainError::InvalidTransaction(format!(
@@ -226,7 +522,21 @@ impl TransferTx {
         Ok(self)
     }
 
-    pub fn signable_message(&self) -> Vec<u8> {
+    /// Attach `memo` encrypted to the recipient, whose compressed public key
+    /// must match `new_owner`
+    pub fn with_encrypted_memo(self, memo: &str, recipient_public_key: &[u8]) -> Result<Self, ChainError> {
+        crate::memo::check_recipient_key(recipient_public_key, &self.new_owner)?;
+        let encrypted = crate::memo::encrypt(memo, recipient_public_key)?;
+        self.with_memo(encrypted)
+    }
+
+    /// What the sender signs for the chain `chain_id`
+    pub fn signable_message(&self, chain_id: &Sha256Hash) -> Vec<u8> {
+        chain_bound(chain_id, &self.legacy_signable_message())
+    }
+
+    /// What was signed before signatures covered the chain id
+    pub fn legacy_signable_message(&self) -> Vec<u8> {
         let mut message = Vec::new();
         message.extend_from_slice("TRANSFER:".as_bytes());
         message.extend_from_slice(&self.input_hash);
@@ -243,4 +553,247 @@ impl TransferTx {
         self.signature = Some(signature);
         self.public_key = Some(public_key);
     }
+
+    /// What the fee sponsor signs for the chain `chain_id`, `None` for
+    /// unsponsored transfers
+    pub fn sponsor_signable_message(&self, chain_id: &Sha256Hash) -> Option<Vec<u8>> {
+        self.legacy_sponsor_signable_message().map(|message| chain_bound(chain_id, &message))
+    }
+
+    /// What the fee sponsor signed before signatures covered the chain id
+    pub fn legacy_sponsor_signable_message(&self) -> Option<Vec<u8>> {
+        let sponsor = self.sponsor.as_ref()?;
+        let mut message = Vec::new();
+        message.extend_from_slice("SPONSOR:".as_bytes());
+        message.extend_from_slice(&self.input_hash);
+        message.extend_from_slice(&sponsor.input_hash);
+        message.extend_from_slice(&sponsor.payer);
+        message.extend_from_slice(&self.fee_area.to_le_bytes());
+        Some(message)
+    }
+
+    /// Attach the sponsor's signature; does nothing without a sponsor
+    pub fn sign_sponsor(&mut self, signature: Vec<u8>, public_key: Vec<u8>) {
+        if let Some(sponsor) = &mut self.sponsor {
+            sponsor.signature = Some(signature);
+            sponsor.public_key = Some(public_key);
+        }
+    }
+
+    /// Part of the fee taken from the sender's input: all of it, or none
+    /// when a sponsor pays
+    pub fn sender_fee(&self) -> crate::geometry::Coord {
+        match self.sponsor {
+            Some(_) => crate::geometry::Coord::from_num(0),
+            None => self.fee_area,
+        }
+    }
+
+    /// The implicit change transaction returning `remaining_value` to the
+    /// sender. Its hash keys the change UTXO created when this transfer is applied.
+    pub fn change_transaction(&self, remaining_value: crate::geometry::Coord) -> Transaction {
+        Transaction::Transfer(TransferTx {
+            input_hash: Transaction::Transfer(self.clone()).hash(),
+            new_owner: self.sender,
+            sender: self.sender,
+            amount: remaining_value,
+            fee_area: crate::geometry::Coord::from_num(0),
+            nonce: self.nonce + 1,
+            signature: None,
+            public_key: None,
+            memo: Some("Change".to_string()),
+            sponsor: None,
+        })
+    }
+
+    /// The implicit change transaction returning what is left of the
+    /// sponsor's triangle after the fee, `None` for unsponsored transfers
+    pub fn sponsor_change_transaction(&self, remaining_value: crate::geometry::Coord) -> Option<Transaction> {
+        let payer = self.sponsor.as_ref()?.payer;
+        Some(Transaction::Transfer(TransferTx {
+            input_hash: Transaction::Transfer(self.clone()).hash(),
+            new_owner: payer,
+            sender: payer,
+            amount: remaining_value,
+            fee_area: crate::geometry::Coord::from_num(0),
+            nonce: self.nonce + 2,
+            signature: None,
+            public_key: None,
+            memo: Some("Sponsor change".to_string()),
+            sponsor: None,
+        }))
+    }
+}
+
+/// Announcement transaction: publishes the public key behind an address, so
+/// others can encrypt memos to it before it has signed anything on chain.
+/// It moves no value and carries no fee; the key itself is the proof, since
+/// an address is the hash of its key.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AnnounceTx {
+    /// Compressed secp256k1 public key
+    pub public_key: Vec<u8>,
+}
+
+impl AnnounceTx {
+    pub fn new(public_key: Vec<u8>) -> Self {
+        AnnounceTx { public_key }
+    }
+
+    /// The address whose key is announced
+    pub fn address(&self) -> Address {
+        Sha256::digest(&self.public_key).into()
+    }
+}
+
+/// Shortest and longest registrable name
+pub const MIN_NAME_LENGTH: usize = 3;
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// Name registration: maps a human-readable name to the owner's address, so
+/// payments can be sent to `@name`. Names are first come, first served and
+/// lapse unless the owner registers them again before they expire. Like an
+/// announcement it moves no value and carries no fee.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegisterNameTx {
+    pub name: String,
+    pub owner: Address,
+    pub nonce: u64,
+    pub signature: Option<Vec<u8>>,
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl RegisterNameTx {
+    pub fn new(name: String, owner: Address, nonce: u64) -> Self {
+        RegisterNameTx {
+            name,
+            owner,
+            nonce,
+            signature: None,
+            public_key: None,
+        }
+    }
+
+    /// What the owner signs for the chain `chain_id`
+    pub fn signable_message(&self, chain_id: &Sha256Hash) -> Vec<u8> {
+        chain_bound(chain_id, &self.legacy_signable_message())
+    }
+
+    /// What was signed before signatures covered the chain id
+    pub fn legacy_signable_message(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(b"NAME:");
+        message.extend_from_slice(self.name.as_bytes());
+        message.extend_from_slice(&self.owner);
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message
+    }
+
+    pub fn sign(&mut self, signature: Vec<u8>, public_key: Vec<u8>) {
+        self.signature = Some(signature);
+        self.public_key = Some(public_key);
+    }
+}
+
+/// Check that `name` is registrable: 3 to 32 lowercase letters, digits and
+/// inner hyphens
+pub fn validate_name(name: &str) -> Result<(), ChainError> {
+    if !(MIN_NAME_LENGTH..=MAX_NAME_LENGTH).contains(&name.len()) {
+        return Err(ChainError::InvalidTransaction(format!(
+            "Name must be {} to {} characters long",
+            MIN_NAME_LENGTH, MAX_NAME_LENGTH
+        )));
+    }
+    if !name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-') {
+        return Err(ChainError::InvalidTransaction(
+            "Name may only contain lowercase letters, digits and hyphens".to_string(),
+        ));
+    }
+    if name.starts_with('-') || name.ends_with('-') {
+        return Err(ChainError::InvalidTransaction(
+            "Name cannot start or end with a hyphen".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Most payload bytes a data transaction may carry
+pub const MAX_DATA_PAYLOAD: usize = 1024;
+
+/// Fee per payload byte of a data transaction, 1/1024 TRC, so a full
+/// payload costs 1 TRC
+pub const DATA_FEE_PER_BYTE: Coord = Coord::from_bits(1 << 22);
+
+/// Data-carrier transaction: anchors application bytes (a document hash,
+/// metadata) on chain. The fee is paid from the owner's triangle and scales
+/// with the payload size; the rest of the triangle returns to the owner.
+/// The txid commits to the payload's hash and length rather than the bytes,
+/// so nodes outside archive mode can prune old payloads without breaking
+/// the block.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DataTx {
+    /// Triangle paying the fee; the change is keyed by the txid
+    pub input_hash: Sha256Hash,
+    pub owner: Address,
+    /// The carried bytes, `None` once pruned
+    pub payload: Option<Vec<u8>>,
+    pub payload_hash: Sha256Hash,
+    pub payload_len: u32,
+    pub fee_area: Coord,
+    pub nonce: u64,
+    pub signature: Option<Vec<u8>>,
+    pub public_key: Option<Vec<u8>>,
+}
+
+impl DataTx {
+    pub fn new(input_hash: Sha256Hash, owner: Address, payload: Vec<u8>, fee_area: Coord, nonce: u64) -> Self {
+        DataTx {
+            input_hash,
+            owner,
+            payload_hash: Sha256::digest(&payload).into(),
+            payload_len: payload.len() as u32,
+            payload: Some(payload),
+            fee_area,
+            nonce,
+            signature: None,
+            public_key: None,
+        }
+    }
+
+    /// Smallest fee a payload of `len` bytes may pay
+    pub fn min_fee(len: usize) -> Coord {
+        DATA_FEE_PER_BYTE.saturating_mul(Coord::saturating_from_num(len))
+    }
+
+    /// What the owner signs for the chain `chain_id`
+    pub fn signable_message(&self, chain_id: &Sha256Hash) -> Vec<u8> {
+        chain_bound(chain_id, &self.legacy_signable_message())
+    }
+
+    /// What was signed before signatures covered the chain id
+    pub fn legacy_signable_message(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(b"DATA:");
+        message.extend_from_slice(&self.input_hash);
+        message.extend_from_slice(&self.owner);
+        message.extend_from_slice(&self.payload_hash);
+        message.extend_from_slice(&self.payload_len.to_le_bytes());
+        message.extend_from_slice(&self.fee_area.to_le_bytes());
+        message.extend_from_slice(&self.nonce.to_le_bytes());
+        message
+    }
+
+    pub fn sign(&mut self, signature: Vec<u8>, public_key: Vec<u8>) {
+        self.signature = Some(signature);
+        self.public_key = Some(public_key);
+    }
+
+    /// Drop the payload, keeping what the txid commits to
+    pub fn prune(&mut self) {
+        self.payload = None;
+    }
+
+    pub fn is_pruned(&self) -> bool {
+        self.payload.is_none()
+    }
 }
@@ -1,25 +1,179 @@
 /// Validation logic for transactions separated from type definitions
 use crate::error::ChainError;
-use crate::geometry::GEOMETRIC_TOLERANCE;
-use crate::transaction::types::{SubdivisionTx, Transaction, TransferTx};
+use crate::geometry::{
+    coord_checked_add, coord_checked_sub, GEOMETRIC_TOLERANCE, MIN_TRIANGLE_VALUE,
+};
+use crate::transaction::types::{
+    validate_name, AnnounceTx, DataTx, RegisterNameTx, SigningDomain, SubdivisionTx, Transaction, TransferTx,
+    MAX_DATA_PAYLOAD,
+};
 
 use crate::blockchain::TriangleState;
+use sha2::{Digest, Sha256};
 
 impl Transaction {
-    /// Validate this transaction against the current UTXO state
-    pub fn validate(&self, state: &TriangleState) -> Result<(), ChainError> {
+    /// Validate this transaction against the current UTXO state, with
+    /// signatures checked for `domain`
+    pub fn validate(&self, state: &TriangleState, domain: &SigningDomain) -> Result<(), ChainError> {
         match self {
-            Transaction::Subdivision(tx) => tx.validate(state),
+            Transaction::Subdivision(tx) => tx.validate(state, domain),
             Transaction::Coinbase(tx) => tx.validate(),
-            Transaction::Transfer(tx) => tx.validate(),
+            Transaction::Transfer(tx) => tx.validate(domain),
+            Transaction::Announce(tx) => tx.validate(state),
+            Transaction::Register(tx) => tx.validate(state, domain),
+            Transaction::Data(tx) => tx.validate(state, domain),
+        }
+    }
+
+    /// The checks of [`Self::validate`] that need no UTXO state, chiefly
+    /// signatures, so they can run ahead of the state they will be applied
+    /// to. Passing them does not make the transaction valid.
+    pub fn validate_stateless(&self, domain: &SigningDomain) -> Result<(), ChainError> {
+        match self {
+            Transaction::Subdivision(tx) => tx.validate_signature(domain),
+            Transaction::Coinbase(tx) => tx.validate(),
+            Transaction::Transfer(tx) => tx.validate(domain),
+            Transaction::Announce(_) => Ok(()),
+            Transaction::Register(tx) => tx.validate_signature(domain),
+            Transaction::Data(tx) => tx.validate_signature(domain),
+        }
+    }
+}
+
+impl AnnounceTx {
+    /// The key must be a valid compressed public key, and its address must
+    /// hold at least one triangle so announcements cannot be spammed for free.
+    pub fn validate(&self, state: &TriangleState) -> Result<(), ChainError> {
+        if self.public_key.len() != secp256k1::constants::PUBLIC_KEY_SIZE {
+            return Err(ChainError::InvalidTransaction(
+                "Announced public key must be compressed".to_string(),
+            ));
+        }
+        secp256k1::PublicKey::from_slice(&self.public_key).map_err(|e| {
+            ChainError::InvalidTransaction(format!("Invalid announced public key: {}", e))
+        })?;
+
+        let address = self.address();
+        if !state.utxo_set.values().any(|triangle| triangle.owner == address) {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Address {} owns no triangles and cannot announce its key",
+                hex::encode(address)
+            )));
         }
+        Ok(())
+    }
+}
+
+impl RegisterNameTx {
+    /// The name must be well formed and signed by the key behind the owner
+    /// address, which must hold at least one triangle, as for announcements.
+    /// Whether the name is still free is up to the chain's name registry.
+    pub fn validate(&self, state: &TriangleState, domain: &SigningDomain) -> Result<(), ChainError> {
+        self.validate_signature(domain)?;
+
+        if !state.utxo_set.values().any(|triangle| triangle.owner == self.owner) {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Address {} owns no triangles and cannot register a name",
+                hex::encode(self.owner)
+            )));
+        }
+        Ok(())
+    }
+
+    /// The name and signature checks of [`Self::validate`], without state
+    pub fn validate_signature(&self, domain: &SigningDomain) -> Result<(), ChainError> {
+        validate_name(&self.name)?;
+        let (signature, public_key) = match (&self.signature, &self.public_key) {
+            (Some(sig), Some(pk)) => (sig, pk),
+            _ => {
+                return Err(ChainError::InvalidTransaction(
+                    "Name registration not signed".to_string(),
+                ))
+            }
+        };
+        let key_address: [u8; 32] = Sha256::digest(public_key).into();
+        if key_address != self.owner {
+            return Err(ChainError::InvalidTransaction(
+                "Public key does not match the name owner".to_string(),
+            ));
+        }
+        domain.verify(public_key, &self.legacy_signable_message(), signature)
+    }
+}
+
+impl DataTx {
+    /// The stateless checks, then the fee triangle must belong to the owner
+    /// and cover the fee
+    pub fn validate(&self, state: &TriangleState, domain: &SigningDomain) -> Result<(), ChainError> {
+        self.validate_signature(domain)?;
+
+        let triangle = state.utxo_set.get(&self.input_hash).ok_or(ChainError::UnknownUtxo(self.input_hash))?;
+        if triangle.owner != self.owner {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Data transaction owner {} does not own its fee triangle (owned by {})",
+                hex::encode(self.owner),
+                hex::encode(triangle.owner)
+            )));
+        }
+        let value = triangle.checked_effective_value()?;
+        if value < self.fee_area {
+            return Err(ChainError::InsufficientValue {
+                available: value,
+                required: self.fee_area,
+            });
+        }
+        Ok(())
+    }
+
+    /// Payload bounds, the size-priced fee and the owner's signature. A
+    /// pruned payload is checked through the length and hash the txid
+    /// commits to.
+    pub fn validate_signature(&self, domain: &SigningDomain) -> Result<(), ChainError> {
+        let len = self.payload_len as usize;
+        if len == 0 || len > MAX_DATA_PAYLOAD {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Data payload must be 1 to {} bytes",
+                MAX_DATA_PAYLOAD
+            )));
+        }
+        if let Some(payload) = &self.payload {
+            let hash: [u8; 32] = Sha256::digest(payload).into();
+            if payload.len() != len || hash != self.payload_hash {
+                return Err(ChainError::InvalidTransaction(
+                    "Data payload does not match its committed length and hash".to_string(),
+                ));
+            }
+        }
+        let min_fee = DataTx::min_fee(len);
+        if self.fee_area < min_fee {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Data transaction fee {} is below {} for {} bytes",
+                self.fee_area, min_fee, len
+            )));
+        }
+
+        let (signature, public_key) = match (&self.signature, &self.public_key) {
+            (Some(sig), Some(pk)) => (sig, pk),
+            _ => {
+                return Err(ChainError::InvalidTransaction(
+                    "Data transaction not signed".to_string(),
+                ))
+            }
+        };
+        let key_address: [u8; 32] = Sha256::digest(public_key).into();
+        if key_address != self.owner {
+            return Err(ChainError::InvalidTransaction(
+                "Public key does not match the data transaction owner".to_string(),
+            ));
+        }
+        domain.verify(public_key, &self.legacy_signable_message(), signature)
     }
 }
 
 impl SubdivisionTx {
     /// Validates just the signature of the transaction, without access to blockchain state.
     /// This is useful for early validation in the mempool.
-    pub fn validate_signature(&self) -> Result<(), ChainError> {
+    pub fn validate_signature(&self, domain: &SigningDomain) -> Result<(), ChainError> {
         let (signature, public_key) = match (&self.signature, &self.public_key) {
             (Some(sig), Some(pk)) => (sig, pk),
             _ => {
@@ -29,25 +183,21 @@ impl SubdivisionTx {
             }
         };
 
-        let message = self.signable_message();
-        crate::crypto::verify_signature(public_key, &message, signature)?;
+        domain.verify(public_key, &self.legacy_signable_message(), signature)?;
 
         Ok(())
     }
 
     /// Performs a full validation of the transaction against the current blockchain state.
-    pub fn validate(&self, state: &TriangleState) -> Result<(), ChainError> {
+    pub fn validate(&self, state: &TriangleState, domain: &SigningDomain) -> Result<(), ChainError> {
         // First, perform a stateless signature check.
-        self.validate_signature()?;
+        self.validate_signature(domain)?;
 
         // Then, validate against the current state (UTXO set).
         let parent = match state.utxo_set.get(&self.parent_hash) {
             Some(triangle) => triangle,
             None => {
-                return Err(ChainError::TriangleNotFound(format!(
-                    "Parent triangle {} not found in UTXO set",
-                    hex::encode(self.parent_hash)
-                )))
+                return Err(ChainError::UnknownUtxo(self.parent_hash))
             }
         };
 
@@ -62,6 +212,24 @@ impl SubdivisionTx {
 
         let expected_children = parent.subdivide();
 
+        let parent_has_area = parent
+            .checked_area()
+            .is_ok_and(|area| area > GEOMETRIC_TOLERANCE);
+        for child in &expected_children {
+            let value = child.checked_effective_value()?;
+            if value < MIN_TRIANGLE_VALUE {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Subdivision child value {} is below the minimum {}",
+                    value, MIN_TRIANGLE_VALUE
+                )));
+            }
+            if parent_has_area && !child.is_valid() {
+                return Err(ChainError::InvalidTransaction(
+                    "Subdivision child area falls below the geometric tolerance".to_string(),
+                ));
+            }
+        }
+
         if self.children.len() != 3 {
             return Err(ChainError::InvalidTransaction(
                 "Subdivision must produce exactly 3 children".to_string(),
@@ -79,6 +247,12 @@ impl SubdivisionTx {
                     i
                 )));
             }
+            if child.id != expected.id {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Child {} structural id does not match its position under the parent",
+                    i
+                )));
+            }
         }
 
         Ok(())
@@ -86,9 +260,16 @@ impl SubdivisionTx {
 }
 
 impl TransferTx {
-    /// Stateless validation: checks signature, addresses, memo, and fee bounds.
+    /// Stateless validation: checks signatures, addresses, memo, and fee bounds.
     /// Does NOT validate against UTXO state - use validate_with_state() for that.
-    pub fn validate(&self) -> Result<(), ChainError> {
+    pub fn validate(&self, domain: &SigningDomain) -> Result<(), ChainError> {
+        self.validate_sender(domain)?;
+        self.validate_sponsor(domain)
+    }
+
+    /// [`Self::validate`] without the fee sponsor's signature, for a
+    /// transfer the sponsor has yet to sign
+    pub fn validate_sender(&self, domain: &SigningDomain) -> Result<(), ChainError> {
         if self.signature.is_none() || self.public_key.is_none() {
             return Err(ChainError::InvalidTransaction(
                 "Transfer not signed".to_string(),
@@ -151,36 +332,68 @@ impl TransferTx {
             }
         };
 
-        let message = self.signable_message();
-        crate::crypto::verify_signature(public_key, &message, signature)?;
+        domain.verify(public_key, &self.legacy_signable_message(), signature)?;
 
         Ok(())
     }
 
+    /// The sponsor's signature, checked independently of the sender's
+    fn validate_sponsor(&self, domain: &SigningDomain) -> Result<(), ChainError> {
+        let Some(sponsor) = &self.sponsor else {
+            return Ok(());
+        };
+        let (Some(signature), Some(public_key)) = (&sponsor.signature, &sponsor.public_key) else {
+            return Err(ChainError::InvalidTransaction(
+                "Fee sponsor has not signed".to_string(),
+            ));
+        };
+        if self.fee_area <= crate::geometry::Coord::from_num(0) {
+            return Err(ChainError::InvalidTransaction(
+                "Sponsored transfer must pay a fee".to_string(),
+            ));
+        }
+        if sponsor.input_hash == self.input_hash {
+            return Err(ChainError::InvalidTransaction(
+                "Fee sponsor must pay from a different triangle than the sender".to_string(),
+            ));
+        }
+        if Sha256::digest(public_key).as_slice() != sponsor.payer {
+            return Err(ChainError::InvalidTransaction(format!(
+                "Sponsor public key does not belong to payer {}",
+                hex::encode(sponsor.payer)
+            )));
+        }
+        let message = self.legacy_sponsor_signable_message().unwrap_or_default();
+        domain.verify(public_key, &message, signature)
+    }
+
     /// Full validation including UTXO state check.
     /// Ensures: input triangle exists AND input.effective_value() > fee_area + TOLERANCE
-    pub fn validate_with_state(&self, state: &TriangleState) -> Result<(), ChainError> {
+    pub fn validate_with_state(&self, state: &TriangleState, domain: &SigningDomain) -> Result<(), ChainError> {
         // First perform stateless validation
-        self.validate()?;
+        self.validate(domain)?;
+        self.validate_inputs(state)
+    }
 
+    /// The state half of [`Self::validate_with_state`]: the inputs exist,
+    /// belong to the sender and sponsor and cover what is paid from them
+    pub(super) fn validate_inputs(&self, state: &TriangleState) -> Result<(), ChainError> {
         // Check input triangle exists in UTXO set
-        let input_triangle = state.utxo_set.get(&self.input_hash).ok_or_else(|| {
-            ChainError::TriangleNotFound(format!(
-                "Transfer input {} not found in UTXO set",
-                hex::encode(self.input_hash)
-            ))
-        })?;
+        let input_triangle = state
+            .utxo_set
+            .get(&self.input_hash)
+            .ok_or(ChainError::UnknownUtxo(self.input_hash))?;
 
         // Area balance check: input value must be strictly greater than fee
-        let input_value = input_triangle.effective_value();
-        let total_spent = self.amount + self.fee_area;
-        let remaining_value = input_value - total_spent;
+        let input_value = input_triangle.checked_effective_value()?;
+        let total_spent = coord_checked_add(self.amount, self.sender_fee())?;
+        let remaining_value = coord_checked_sub(input_value, total_spent)?;
 
         if remaining_value < GEOMETRIC_TOLERANCE {
-            return Err(ChainError::InvalidTransaction(format!(
-                "Insufficient triangle value: input has {} but amount + fee_area is {}, leaving {} (minimum: {})",
-                input_value, total_spent, remaining_value, GEOMETRIC_TOLERANCE
-            )));
+            return Err(ChainError::InsufficientValue {
+                available: input_value,
+                required: coord_checked_add(total_spent, GEOMETRIC_TOLERANCE)?,
+            });
         }
 
         // Verify sender owns the triangle
@@ -192,6 +405,27 @@ impl TransferTx {
             )));
         }
 
+        if let Some(sponsor) = &self.sponsor {
+            let triangle = state
+                .utxo_set
+                .get(&sponsor.input_hash)
+                .ok_or(ChainError::UnknownUtxo(sponsor.input_hash))?;
+            if triangle.owner != sponsor.payer {
+                return Err(ChainError::InvalidTransaction(format!(
+                    "Fee sponsor {} does not own its triangle (owned by {})",
+                    hex::encode(sponsor.payer),
+                    hex::encode(triangle.owner)
+                )));
+            }
+            let value = triangle.checked_effective_value()?;
+            if coord_checked_sub(value, self.fee_area)? < GEOMETRIC_TOLERANCE {
+                return Err(ChainError::InsufficientValue {
+                    available: value,
+                    required: coord_checked_add(self.fee_area, GEOMETRIC_TOLERANCE)?,
+                });
+            }
+        }
+
         Ok(())
     }
 }
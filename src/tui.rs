@@ -0,0 +1,271 @@
+//! Terminal dashboard widgets shared by the node and miner TUIs
+
+use crate::sync::{SyncProgress, SyncState};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::Alignment,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block as TuiBlock, Borders, Gauge, Paragraph},
+    Terminal,
+};
+use std::io::{self, Stdout};
+use std::time::Duration;
+
+pub type Tui = Terminal<CrosstermBackend<Stdout>>;
+
+/// Switch the terminal to raw mode on the alternate screen
+pub fn enter_terminal() -> io::Result<Tui> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+/// Undo [`enter_terminal`]
+pub fn restore_terminal(terminal: &mut Tui) -> io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
+
+/// Whether 'q' was pressed, waiting at most `timeout` for a key
+pub fn quit_requested(timeout: Duration) -> bool {
+    if event::poll(timeout).unwrap_or(false) {
+        if let Ok(Event::Key(key)) = event::read() {
+            return key.code == KeyCode::Char('q');
+        }
+    }
+    false
+}
+
+pub fn format_number(num: u64) -> String {
+    let num_str = num.to_string();
+    let mut result = String::new();
+    let chars: Vec<char> = num_str.chars().collect();
+
+    for (i, &ch) in chars.iter().enumerate() {
+        if i > 0 && (chars.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+        result.push(ch);
+    }
+    result
+}
+
+pub fn format_hash(hash: &str) -> String {
+    if hash.len() > 20 {
+        format!("{}...{}", &hash[..10], &hash[hash.len() - 10..])
+    } else {
+        hash.to_string()
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Centered, bold title framed by `icon`
+pub fn title_bar(icon: &str, title: &str) -> Paragraph<'static> {
+    Paragraph::new(vec![Line::from(vec![
+        Span::styled(format!("{}   ", icon), Style::default().fg(Color::Yellow)),
+        Span::styled(
+            title.to_string(),
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(format!("   {}", icon), Style::default().fg(Color::Yellow)),
+    ])])
+    .block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan)),
+    )
+    .style(Style::default().fg(Color::White))
+    .alignment(Alignment::Center)
+}
+
+/// Sync progress towards the best peer's height
+pub fn sync_gauge(sync: Option<&SyncProgress>) -> Gauge<'static> {
+    let (sync_ratio, sync_label, sync_color) = match sync {
+        Some(sync) if sync.stalled => (
+            sync.progress,
+            format!(
+                "STALLED at {} / {} ({} peers)",
+                sync.current_height, sync.target_height, sync.active_peers
+            ),
+            Color::Red,
+        ),
+        Some(sync) if sync.state == SyncState::Syncing => (
+            sync.progress,
+            format!(
+                "{} / {} · {:.1} blk/s · ETA {}s · {} peers",
+                sync.current_height,
+                sync.target_height,
+                sync.blocks_per_sec,
+                sync.eta_secs,
+                sync.active_peers
+            ),
+            Color::Yellow,
+        ),
+        Some(sync) if sync.state == SyncState::Synced => (
+            1.0,
+            format!("Synced at {} ({} peers)", sync.current_height, sync.active_peers),
+            Color::Green,
+        ),
+        _ => (0.0, "Waiting for peers...".to_string(), Color::DarkGray),
+    };
+    Gauge::default()
+        .block(
+            TuiBlock::default()
+                .borders(Borders::ALL)
+                .title("🔄 Sync Progress")
+                .border_style(Style::default().fg(sync_color)),
+        )
+        .gauge_style(Style::default().fg(sync_color).bg(Color::Black))
+        .ratio(sync_ratio.clamp(0.0, 1.0))
+        .label(sync_label)
+}
+
+/// The last five of `recent_blocks` (height, hash, parent hash; oldest
+/// first) drawn as a chain from the tip down
+pub fn block_tree(recent_blocks: &[(u64, String, String)]) -> Paragraph<'static> {
+    let mut tree_lines = vec![Line::from("")];
+
+    if recent_blocks.is_empty() {
+        tree_lines.push(Line::from(vec![Span::styled(
+            "   Waiting for blocks...",
+            Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC),
+        )]));
+    } else {
+        let blocks_to_show = recent_blocks.iter().rev().take(5).collect::<Vec<_>>();
+
+        for (i, (height, hash, parent_hash)) in blocks_to_show.iter().enumerate() {
+            let color = match i {
+                0 => Color::Green,
+                1 => Color::Cyan,
+                _ => Color::Gray,
+            };
+
+            // Block node
+            tree_lines.push(Line::from(vec![
+                Span::styled("      ", Style::default()),
+                Span::styled("▲", Style::default().fg(color).add_modifier(Modifier::BOLD)),
+                Span::styled(
+                    format!(" #{}", height),
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+            ]));
+
+            // Hash
+            tree_lines.push(Line::from(vec![
+                Span::styled("     ", Style::default()),
+                Span::styled("╱ ╲", Style::default().fg(color)),
+                Span::styled(format!("  {}", format_hash(hash)), Style::default().fg(color)),
+            ]));
+
+            if i < blocks_to_show.len() - 1 {
+                // Connection to parent
+                tree_lines.push(Line::from(vec![Span::styled(
+                    "      │",
+                    Style::default().fg(Color::DarkGray),
+                )]));
+                tree_lines.push(Line::from(vec![
+                    Span::styled("      │", Style::default().fg(Color::DarkGray)),
+                    Span::styled(
+                        format!("  parent: {}", format_hash(parent_hash)),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]));
+            }
+        }
+    }
+
+    Paragraph::new(tree_lines).block(
+        TuiBlock::default()
+            .borders(Borders::ALL)
+            .title("🌳 Blockchain Tree (Parent → Child)")
+            .border_style(Style::default().fg(Color::Magenta)),
+    )
+}
+
+pub fn quit_hint() -> Paragraph<'static> {
+    Paragraph::new(vec![Line::from(vec![
+        Span::styled("Press ", Style::default().fg(Color::DarkGray)),
+        Span::styled(
+            "'q'",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(" to quit", Style::default().fg(Color::DarkGray)),
+    ])])
+}
+
+/// Memory and thread usage of this process. Read from `/proc`, so only
+/// available on Linux.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub resident_bytes: Option<u64>,
+    pub threads: Option<u64>,
+}
+
+impl ResourceUsage {
+    pub fn sample() -> Self {
+        std::fs::read_to_string("/proc/self/status")
+            .map(|status| Self::parse_status(&status))
+            .unwrap_or_default()
+    }
+
+    fn parse_status(status: &str) -> Self {
+        let field = |name: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .and_then(|value| value.split_whitespace().next())
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        ResourceUsage {
+            resident_bytes: field("VmRSS:").map(|kib| kib * 1024),
+            threads: field("Threads:"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(format_number(1234567), "1,234,567");
+        assert_eq!(format_hash("0123456789abcdef0123456789"), "0123456789...0123456789");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024 / 2), "1.5 MiB");
+    }
+
+    #[test]
+    fn test_parse_proc_status() {
+        let usage = ResourceUsage::parse_status("Name:\ttrinity-node\nVmRSS:\t   2048 kB\nThreads:\t7\n");
+        assert_eq!(usage.resident_bytes, Some(2 * 1024 * 1024));
+        assert_eq!(usage.threads, Some(7));
+        assert_eq!(ResourceUsage::parse_status(""), ResourceUsage::default());
+    }
+}
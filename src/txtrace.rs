@@ -0,0 +1,187 @@
+//! Transaction propagation tracing (debug mode)
+//!
+//! To find where transactions stall on the way through the network, a node
+//! with `[trace] enabled = true` can submit a transaction with a
+//! [`TraceTag`]. The tag travels next to the transaction in
+//! `NetworkMessage::TracedTransaction`, never inside it: the mempool, blocks
+//! and transaction hashes only ever see the plain transaction, and nodes
+//! without tracing relay and validate it as if it were untagged.
+//!
+//! Every tracing node that receives a tagged transaction stores a
+//! [`HopReport`] with the delay since the previous hop sent it and since the
+//! origin, POSTs it to its `trace.aggregator` if one is set, and forwards
+//! the transaction with the tag of the next hop to its other tracing peers,
+//! up to [`MAX_TRACE_HOPS`]. Delays compare the wall clocks of different
+//! nodes, so they are only as accurate as those clocks are synchronized.
+
+use crate::blockchain::Sha256Hash;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Hops after which a traced transaction is no longer forwarded
+pub const MAX_TRACE_HOPS: u8 = 8;
+
+/// Traces kept; the oldest is dropped for a new one
+pub const MAX_TRACES: usize = 1_000;
+
+/// Hop reports kept per trace
+pub const MAX_HOPS_PER_TRACE: usize = 256;
+
+/// Trace flag relayed next to a transaction
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceTag {
+    pub id: u64,
+    /// When the origin first sent the transaction, in milliseconds since
+    /// the epoch
+    pub origin_ms: u64,
+    /// When the previous hop sent it
+    pub sent_ms: u64,
+    /// Relays so far; the origin sends hop 1
+    pub hop: u8,
+}
+
+impl TraceTag {
+    /// Tag of a transaction first sent at `now_ms`
+    pub fn new(id: u64, now_ms: u64) -> Self {
+        TraceTag {
+            id,
+            origin_ms: now_ms,
+            sent_ms: now_ms,
+            hop: 1,
+        }
+    }
+
+    /// Tag to forward this one with at `now_ms`, `None` once
+    /// [`MAX_TRACE_HOPS`] is reached
+    pub fn next_hop(&self, now_ms: u64) -> Option<Self> {
+        (self.hop < MAX_TRACE_HOPS).then(|| TraceTag {
+            sent_ms: now_ms,
+            hop: self.hop + 1,
+            ..*self
+        })
+    }
+}
+
+/// Hex form of a trace id, as used by the API
+pub fn trace_id_hex(id: u64) -> String {
+    format!("{:016x}", id)
+}
+
+/// One node receiving a traced transaction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HopReport {
+    pub trace_id: String,
+    /// Hex hash of the transaction
+    pub tx: String,
+    /// `trace.name` of the reporting node
+    pub node: String,
+    /// Peer the transaction came from
+    pub from: String,
+    pub hop: u8,
+    pub origin_ms: u64,
+    pub sent_ms: u64,
+    pub received_ms: u64,
+    /// Delay since the previous hop sent it
+    pub hop_ms: u64,
+    /// Delay since the origin sent it
+    pub total_ms: u64,
+    /// Already pending here, so not forwarded again
+    pub duplicate: bool,
+}
+
+impl HopReport {
+    pub fn new(tag: &TraceTag, tx: Sha256Hash, node: &str, from: &str, received_ms: u64, duplicate: bool) -> Self {
+        HopReport {
+            trace_id: trace_id_hex(tag.id),
+            tx: hex::encode(tx),
+            node: node.to_string(),
+            from: from.to_string(),
+            hop: tag.hop,
+            origin_ms: tag.origin_ms,
+            sent_ms: tag.sent_ms,
+            received_ms,
+            hop_ms: received_ms.saturating_sub(tag.sent_ms),
+            total_ms: received_ms.saturating_sub(tag.origin_ms),
+            duplicate,
+        }
+    }
+}
+
+/// Hop reports by trace id, of the [`MAX_TRACES`] most recent traces
+#[derive(Debug, Default)]
+pub struct TraceLog {
+    traces: HashMap<String, Vec<HopReport>>,
+    /// Trace ids, oldest first
+    order: VecDeque<String>,
+}
+
+impl TraceLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a report. Returns `false` if its trace already holds
+    /// [`MAX_HOPS_PER_TRACE`] reports.
+    pub fn record(&mut self, report: HopReport) -> bool {
+        if !self.traces.contains_key(&report.trace_id) {
+            while self.order.len() >= MAX_TRACES {
+                let Some(oldest) = self.order.pop_front() else { break };
+                self.traces.remove(&oldest);
+            }
+            self.order.push_back(report.trace_id.clone());
+        }
+        let hops = self.traces.entry(report.trace_id.clone()).or_default();
+        if hops.len() >= MAX_HOPS_PER_TRACE {
+            return false;
+        }
+        hops.push(report);
+        true
+    }
+
+    /// Reports of a trace by hop, then by arrival
+    pub fn hops(&self, trace_id: &str) -> Option<Vec<HopReport>> {
+        let mut hops = self.traces.get(trace_id)?.clone();
+        hops.sort_by_key(|report| (report.hop, report.received_ms));
+        Some(hops)
+    }
+
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_count_hops_and_log_keeps_recent_traces() {
+        let tag = TraceTag::new(0xabc, 1_000);
+        let second = tag.next_hop(1_040).unwrap();
+        assert_eq!((second.hop, second.origin_ms, second.sent_ms), (2, 1_000, 1_040));
+        let mut last = second;
+        while let Some(next) = last.next_hop(2_000) {
+            last = next;
+        }
+        assert_eq!(last.hop, MAX_TRACE_HOPS);
+
+        let report = HopReport::new(&second, [1u8; 32], "b", "10.0.0.1:8333", 1_100, false);
+        assert_eq!((report.trace_id.as_str(), report.hop_ms, report.total_ms), ("0000000000000abc", 60, 100));
+
+        let mut log = TraceLog::new();
+        log.record(report.clone());
+        log.record(HopReport::new(&tag, [1u8; 32], "a", "local", 1_020, false));
+        let hops = log.hops("0000000000000abc").unwrap();
+        assert_eq!((hops[0].node.as_str(), hops[1].node.as_str()), ("a", "b"));
+
+        for id in 0..MAX_TRACES as u64 {
+            log.record(HopReport::new(&TraceTag::new(id + 0x1000, 0), [2u8; 32], "a", "local", 0, false));
+        }
+        assert_eq!(log.len(), MAX_TRACES);
+        assert!(log.hops("0000000000000abc").is_none());
+    }
+}
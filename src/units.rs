@@ -0,0 +1,277 @@
+//! Coin units and amount formatting
+//!
+//! Value on TrinityChain is triangle area, held as a [`Coord`]. One TRC is
+//! one unit of area and a milliTRC is a thousandth of that. Formatting and
+//! parsing work on the exact decimal form of the fixed-point value, never on
+//! `f64`, so amounts round-trip exactly. Output does not depend on the
+//! system locale: the decimal separator is always `.` and digits are never
+//! grouped.
+
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Unit {
+    #[default]
+    Trc,
+    MilliTrc,
+    /// Geometric area, numerically the same as TRC; used for triangles
+    Area,
+}
+
+impl Unit {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Trc => "TRC",
+            Unit::MilliTrc => "mTRC",
+            Unit::Area => "area",
+        }
+    }
+
+    /// Decimal places between a TRC amount and the same amount in this unit
+    fn shift(self) -> usize {
+        match self {
+            Unit::Trc | Unit::Area => 0,
+            Unit::MilliTrc => 3,
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = ChainError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trc" => Ok(Unit::Trc),
+            "mtrc" | "millitrc" => Ok(Unit::MilliTrc),
+            "area" => Ok(Unit::Area),
+            _ => Err(ChainError::InvalidTransaction(format!(
+                "Unknown unit '{}' (expected TRC, mTRC or area)",
+                s
+            ))),
+        }
+    }
+}
+
+impl TryFrom<String> for Unit {
+    type Error = ChainError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Unit> for String {
+    fn from(unit: Unit) -> Self {
+        unit.symbol().to_string()
+    }
+}
+
+/// The exact value in `unit`, without a symbol, e.g. "1500" for 1.5 TRC in mTRC
+pub fn format_number(value: Coord, unit: Unit) -> String {
+    shift_right(&value.to_string(), unit.shift())
+}
+
+/// The value in `unit` rounded to `decimals` places, without a symbol
+pub fn format_rounded(value: Coord, unit: Unit, decimals: usize) -> String {
+    shift_right(
+        &format!("{:.*}", decimals + unit.shift(), value),
+        unit.shift(),
+    )
+}
+
+/// The exact value with its symbol, e.g. "1.5 TRC"
+pub fn format_amount(value: Coord, unit: Unit) -> String {
+    format!("{} {}", format_number(value, unit), unit.symbol())
+}
+
+/// Parse an amount such as "1.5", "1.5 TRC" or "1500mTRC". A bare number is
+/// taken to be in `default_unit`.
+pub fn parse_amount(s: &str, default_unit: Unit) -> Result<Coord, ChainError> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    let (number, symbol) = s.split_at(split);
+    let number = number.trim();
+    let unit = if symbol.is_empty() {
+        default_unit
+    } else {
+        symbol.parse()?
+    };
+
+    let invalid = || ChainError::InvalidTransaction(format!("Invalid amount '{}'", s));
+    let digits = number.strip_prefix('-').unwrap_or(number);
+    let mut parts = digits.splitn(2, '.');
+    let whole = parts.next().unwrap_or_default();
+    let frac = parts.next().unwrap_or_default();
+    if (whole.is_empty() && frac.is_empty())
+        || !whole.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let sign = if digits.len() < number.len() { "-" } else { "" };
+    let whole = if whole.is_empty() { "0" } else { whole };
+    let canonical = if frac.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, frac)
+    };
+    shift_left(&canonical, unit.shift())
+        .parse::<Coord>()
+        .map_err(|_| invalid())
+}
+
+/// An amount as written in a config file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawAmount {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+}
+
+impl RawAmount {
+    fn parse<E: serde::de::Error>(self) -> Result<Coord, E> {
+        let text = match self {
+            RawAmount::Text(text) => text,
+            RawAmount::Integer(value) => value.to_string(),
+            RawAmount::Float(value) => value.to_string(),
+        };
+        parse_amount(&text, Unit::Trc).map_err(E::custom)
+    }
+}
+
+/// Deserialize an optional amount written as a number (`5`, `0.5`) or a
+/// string with a unit (`"500mTRC"`); bare numbers are TRC. For config files.
+pub fn deserialize_optional_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Coord>, D::Error> {
+    Option::<RawAmount>::deserialize(deserializer)?
+        .map(RawAmount::parse)
+        .transpose()
+}
+
+/// Deserialize an amount written as for [`deserialize_optional_amount`]
+pub fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Coord, D::Error> {
+    RawAmount::deserialize(deserializer)?.parse()
+}
+
+/// Deserialize a table of amounts, written as for
+/// [`deserialize_optional_amount`]
+pub fn deserialize_amount_map<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Coord>, D::Error> {
+    HashMap::<String, RawAmount>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, raw)| Ok((key, raw.parse()?)))
+        .collect()
+}
+
+/// Serialize an optional amount as an exact TRC string, the inverse of
+/// [`deserialize_optional_amount`]
+pub fn serialize_optional_amount<S: Serializer>(value: &Option<Coord>, serializer: S) -> Result<S::Ok, S::Error> {
+    value.map(|value| format_number(value, Unit::Trc)).serialize(serializer)
+}
+
+/// Serialize an amount as an exact TRC string
+pub fn serialize_amount<S: Serializer>(value: &Coord, serializer: S) -> Result<S::Ok, S::Error> {
+    format_number(*value, Unit::Trc).serialize(serializer)
+}
+
+/// Serialize a table of amounts as exact TRC strings
+pub fn serialize_amount_map<S: Serializer>(map: &HashMap<String, Coord>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().map(|(key, value)| (key, format_number(*value, Unit::Trc))))
+}
+
+/// Move the decimal point of a plain decimal string `places` to the right
+fn shift_right(decimal: &str, places: usize) -> String {
+    if places == 0 {
+        return decimal.to_string();
+    }
+    let (sign, digits) = split_sign(decimal);
+    let (whole, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let frac = format!("{:0<width$}", frac, width = places);
+    let (moved, rest) = frac.split_at(places);
+    let whole = format!("{}{}", whole, moved);
+    let whole = match whole.trim_start_matches('0') {
+        "" => "0",
+        trimmed => trimmed,
+    };
+    if rest.is_empty() {
+        format!("{}{}", sign, whole)
+    } else {
+        format!("{}{}.{}", sign, whole, rest)
+    }
+}
+
+/// Move the decimal point of a plain decimal string `places` to the left
+fn shift_left(decimal: &str, places: usize) -> String {
+    if places == 0 {
+        return decimal.to_string();
+    }
+    let (sign, digits) = split_sign(decimal);
+    let (whole, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let whole = format!("{:0>width$}", whole, width = places + 1);
+    let (whole, moved) = whole.split_at(whole.len() - places);
+    format!("{}{}.{}{}", sign, whole, moved, frac)
+}
+
+fn split_sign(decimal: &str) -> (&str, &str) {
+    match decimal.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", decimal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse_round_trip() {
+        let values = [
+            Coord::from_num(0),
+            Coord::from_num(1000),
+            Coord::from_num(1.5),
+            Coord::from_num(-0.0625),
+            Coord::from_bits(1),
+            crate::geometry::MIN_TRIANGLE_VALUE,
+        ];
+        for value in values {
+            for unit in [Unit::Trc, Unit::MilliTrc, Unit::Area] {
+                let text = format_amount(value, unit);
+                assert_eq!(parse_amount(&text, Unit::Trc).unwrap(), value, "{}", text);
+            }
+        }
+
+        assert_eq!(format_amount(Coord::from_num(1000), Unit::Trc), "1000 TRC");
+        assert_eq!(format_amount(Coord::from_num(1.5), Unit::MilliTrc), "1500 mTRC");
+        assert_eq!(format_number(Coord::from_num(0.0625), Unit::MilliTrc), "62.5");
+        assert_eq!(format_rounded(Coord::from_num(2) / 3, Unit::Trc, 2), "0.67");
+        assert_eq!(format_rounded(Coord::from_num(1.5), Unit::MilliTrc, 1), "1500.0");
+    }
+
+    #[test]
+    fn test_parse_amount_units_and_rejections() {
+        assert_eq!(parse_amount("2", Unit::MilliTrc).unwrap(), Coord::from_num(0.002));
+        assert_eq!(parse_amount("250mTRC", Unit::Trc).unwrap(), Coord::from_num(0.25));
+        assert_eq!(parse_amount(" 3 trc ", Unit::MilliTrc).unwrap(), Coord::from_num(3));
+        assert_eq!(parse_amount(".5", Unit::Trc).unwrap(), Coord::from_num(0.5));
+
+        for bad in ["", "TRC", "1,5", "1.000,5", "1_000", "1e3", "5 BTC", "--1"] {
+            assert!(parse_amount(bad, Unit::Trc).is_err(), "{}", bad);
+        }
+        assert_eq!("mtrc".parse::<Unit>().unwrap(), Unit::MilliTrc);
+    }
+}
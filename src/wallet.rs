@@ -1,22 +1,25 @@
 //! Wallet management for TrinityChain
 //!
 //! Provides functionality for creating, loading, and managing wallets
-//! that store keypairs and track triangle ownership.
+//! that store keypairs and track triangle ownership, and the versioned
+//! backup container ([`WalletBackup`]) used to move them between machines.
 
 // Suppress deprecation warnings from aes-gcm's generic-array dependency
 #![allow(deprecated)]
 
+use crate::addressbook::AddressEntry;
 use crate::crypto::KeyPair;
 use crate::error::ChainError;
 use rpassword::prompt_password;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::NamedTempFile;
 
 /// Wallet data structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Wallet {
     /// Optional wallet name
     pub name: Option<String>,
@@ -80,11 +83,10 @@ impl Wallet {
     }
 }
 
-/// Get the default wallet directory
+/// Get the default wallet directory, which is the data directory
 pub fn get_wallet_dir() -> Result<PathBuf, ChainError> {
-    let home = std::env::var("HOME")
-        .map_err(|e| ChainError::WalletError(format!("Failed to get HOME env var: {}", e)))?;
-    Ok(PathBuf::from(home).join(".trinitychain"))
+    crate::config::prepare_data_dir()
+        .map_err(|e| ChainError::WalletError(format!("Failed to prepare data directory: {}", e)))
 }
 
 /// Get the default wallet file path
@@ -203,7 +205,7 @@ pub fn list_wallets() -> Result<Vec<String>, ChainError> {
 // ============================================================================
 
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Nonce,
 };
 use argon2::{
@@ -211,6 +213,20 @@ use argon2::{
     Argon2, Params, Version,
 };
 
+/// Argon2id memory cost of keystore entries, in KiB
+const KEYSTORE_ARGON2_M_COST: u32 = 19456;
+/// Argon2id iterations of keystore entries
+const KEYSTORE_ARGON2_T_COST: u32 = 2;
+/// Argon2id lanes of keystore entries
+const KEYSTORE_ARGON2_P_COST: u32 = 1;
+
+/// The Argon2id instance every keystore entry is hashed with
+fn keystore_argon2() -> Result<Argon2<'static>, ChainError> {
+    let params = Params::new(KEYSTORE_ARGON2_M_COST, KEYSTORE_ARGON2_T_COST, KEYSTORE_ARGON2_P_COST, None)
+        .map_err(|e| ChainError::CryptoError(format!("Argon2 params error: {}", e)))?;
+    Ok(Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params))
+}
+
 /// Encrypted wallet structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EncryptedWallet {
@@ -229,9 +245,7 @@ impl EncryptedWallet {
         let salt = SaltString::generate(&mut OsRng);
 
         // Configure Argon2 with recommended parameters
-        let params = Params::new(19456, 2, 1, None)
-            .map_err(|e| ChainError::CryptoError(format!("Argon2 params error: {}", e)))?;
-        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params);
+        let argon2 = keystore_argon2()?;
 
         // Hash the password
         let password_hash = argon2
@@ -272,14 +286,30 @@ impl EncryptedWallet {
         })
     }
 
-    /// Decrypt the wallet using a password.
+    /// Decrypt the wallet using a password. Entries may come from untrusted
+    /// callers, so only the parameters [`EncryptedWallet::from_wallet`]
+    /// writes are accepted: the hash must not choose its own cost.
     pub fn decrypt(&self, password: &str) -> Result<Wallet, ChainError> {
         use argon2::password_hash::{PasswordHash, PasswordVerifier};
 
-        // Verify password against the stored hash
         let parsed_hash = PasswordHash::new(&self.password_hash)
             .map_err(|e| ChainError::CryptoError(format!("Invalid password hash: {}", e)))?;
-        Argon2::default()
+        let params = Params::try_from(&parsed_hash)
+            .map_err(|e| ChainError::CryptoError(format!("Invalid password hash: {}", e)))?;
+        if parsed_hash.algorithm != argon2::Algorithm::Argon2id.ident()
+            || parsed_hash.version != Some(Version::V0x13.into())
+            || (params.m_cost(), params.t_cost(), params.p_cost())
+                != (KEYSTORE_ARGON2_M_COST, KEYSTORE_ARGON2_T_COST, KEYSTORE_ARGON2_P_COST)
+        {
+            return Err(ChainError::CryptoError(format!(
+                "Unsupported password hash parameters: keystore entries use argon2id with m={}, t={}, p={}",
+                KEYSTORE_ARGON2_M_COST, KEYSTORE_ARGON2_T_COST, KEYSTORE_ARGON2_P_COST
+            )));
+        }
+
+        // Verify password against the stored hash
+        let argon2 = keystore_argon2()?;
+        argon2
             .verify_password(password.as_bytes(), &parsed_hash)
             .map_err(|_| {
                 ChainError::CryptoError("Decryption failed - wrong password?".to_string())
@@ -289,12 +319,6 @@ impl EncryptedWallet {
         let salt = parsed_hash.salt.ok_or(ChainError::CryptoError(
             "Salt not found in password hash".to_string(),
         ))?;
-        let argon2 = Argon2::new(
-            argon2::Algorithm::Argon2id,
-            Version::V0x13,
-            Params::new(19456, 2, 1, None)
-                .map_err(|e| ChainError::CryptoError(format!("Argon2 params error: {}", e)))?,
-        );
         let hash_bytes = argon2
             .hash_password(password.as_bytes(), salt)
             .map_err(|e| ChainError::CryptoError(format!("Password hashing failed: {}", e)))?
@@ -336,41 +360,11 @@ impl EncryptedWallet {
     }
 
     /// Save encrypted wallet to file
-    pub fn save(&self, path: &PathBuf) -> Result<(), ChainError> {
+    pub fn save(&self, path: &Path) -> Result<(), ChainError> {
         let json = serde_json::to_string_pretty(self)
             .map_err(|e| ChainError::WalletError(format!("Failed to serialize wallet: {}", e)))?;
 
-        // Create the file in a temporary location
-        let mut temp_file = NamedTempFile::new()
-            .map_err(|e| ChainError::WalletError(format!("Failed to create temp file: {}", e)))?;
-
-        // Write the wallet data
-        write!(temp_file, "{}", json)
-            .map_err(|e| ChainError::WalletError(format!("Failed to write to temp file: {}", e)))?;
-
-        // Set file permissions before persisting
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = temp_file
-                .as_file()
-                .metadata()
-                .map_err(|e| {
-                    ChainError::WalletError(format!("Failed to get file metadata: {}", e))
-                })?
-                .permissions();
-            perms.set_mode(0o600); // rw-------
-            fs::set_permissions(temp_file.path(), perms).map_err(|e| {
-                ChainError::WalletError(format!("Failed to set file permissions: {}", e))
-            })?;
-        }
-
-        // Atomically move the file to the final destination
-        temp_file.persist(path).map_err(|e| {
-            ChainError::WalletError(format!("Failed to persist wallet file: {}", e))
-        })?;
-
-        Ok(())
+        write_private_file(path, &json)
     }
 
     /// Load encrypted wallet from file
@@ -386,6 +380,229 @@ impl EncryptedWallet {
     }
 }
 
+/// Atomically write `contents` to `path`, readable only by the owner
+fn write_private_file(path: &Path, contents: &str) -> Result<(), ChainError> {
+    // Create the file in a temporary location
+    let mut temp_file = NamedTempFile::new()
+        .map_err(|e| ChainError::WalletError(format!("Failed to create temp file: {}", e)))?;
+
+    // Write the wallet data
+    write!(temp_file, "{}", contents)
+        .map_err(|e| ChainError::WalletError(format!("Failed to write to temp file: {}", e)))?;
+
+    // Set file permissions before persisting
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = temp_file
+            .as_file()
+            .metadata()
+            .map_err(|e| {
+                ChainError::WalletError(format!("Failed to get file metadata: {}", e))
+            })?
+            .permissions();
+        perms.set_mode(0o600); // rw-------
+        fs::set_permissions(temp_file.path(), perms).map_err(|e| {
+            ChainError::WalletError(format!("Failed to set file permissions: {}", e))
+        })?;
+    }
+
+    // Atomically move the file to the final destination
+    temp_file.persist(path).map_err(|e| {
+        ChainError::WalletError(format!("Failed to persist wallet file: {}", e))
+    })?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Wallet Backups
+// ============================================================================
+
+/// Identifies a file as a TrinityChain wallet backup
+pub const BACKUP_FORMAT: &str = "trinitychain-wallet-backup";
+
+/// Newest backup version this build writes and reads
+pub const BACKUP_VERSION: u32 = 1;
+
+/// HD wallet data needed to re-derive keys, e.g. the miner's rotating
+/// reward addresses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HdMetadata {
+    pub mnemonic: String,
+    pub derivation_path: String,
+}
+
+/// Everything a backup restores
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackupContents {
+    pub wallets: Vec<Wallet>,
+    /// Address book entries
+    #[serde(default)]
+    pub labels: Vec<AddressEntry>,
+    #[serde(default)]
+    pub hd: Option<HdMetadata>,
+}
+
+/// Key derivation and cipher parameters of an encrypted backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupEncryption {
+    /// Always "argon2id-aes256gcm" in version 1
+    pub scheme: String,
+    pub salt: String,  // Base64
+    pub nonce: String, // Base64
+}
+
+const BACKUP_ENCRYPTION_SCHEME: &str = "argon2id-aes256gcm";
+
+/// Versioned wallet backup container, stored as JSON.
+///
+/// `payload` is the JSON of [`BackupContents`], or its AES-256-GCM
+/// ciphertext (base64) under a password-derived key when `encryption` is
+/// set. `checksum` is the hex SHA-256 of `payload`, so corruption is
+/// detected before anything is decrypted or restored. Fields added by later
+/// versions are ignored; a `version` newer than [`BACKUP_VERSION`] is
+/// refused with a clear error rather than misread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WalletBackup {
+    pub format: String,
+    pub version: u32,
+    pub created: String,
+    pub checksum: String,
+    #[serde(default)]
+    pub encryption: Option<BackupEncryption>,
+    pub payload: String,
+}
+
+impl WalletBackup {
+    /// Pack `contents`, encrypting them when a password is given
+    pub fn seal(contents: &BackupContents, password: Option<&str>) -> Result<Self, ChainError> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let json = serde_json::to_string(contents)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize backup: {}", e)))?;
+        let (payload, encryption) = match password {
+            None => (json, None),
+            Some(password) => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                let ciphertext = backup_cipher(password, &salt)?
+                    .encrypt(&nonce, json.as_bytes())
+                    .map_err(|e| ChainError::CryptoError(format!("Encryption failed: {}", e)))?;
+                let encryption = BackupEncryption {
+                    scheme: BACKUP_ENCRYPTION_SCHEME.to_string(),
+                    salt: general_purpose::STANDARD.encode(salt),
+                    nonce: general_purpose::STANDARD.encode(nonce),
+                };
+                (general_purpose::STANDARD.encode(ciphertext), Some(encryption))
+            }
+        };
+
+        Ok(WalletBackup {
+            format: BACKUP_FORMAT.to_string(),
+            version: BACKUP_VERSION,
+            created: chrono::Utc::now().to_rfc3339(),
+            checksum: hex::encode(Sha256::digest(payload.as_bytes())),
+            encryption,
+            payload,
+        })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// Verify the checksum and unpack the contents. `password` is required
+    /// for encrypted backups.
+    pub fn open(&self, password: Option<&str>) -> Result<BackupContents, ChainError> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        if hex::encode(Sha256::digest(self.payload.as_bytes())) != self.checksum {
+            return Err(ChainError::WalletError(
+                "Backup checksum mismatch - the file is corrupted".to_string(),
+            ));
+        }
+        let json = match &self.encryption {
+            None => self.payload.clone(),
+            Some(encryption) => {
+                if encryption.scheme != BACKUP_ENCRYPTION_SCHEME {
+                    return Err(ChainError::WalletError(format!(
+                        "Unsupported backup encryption '{}'",
+                        encryption.scheme
+                    )));
+                }
+                let password = password.ok_or_else(|| {
+                    ChainError::WalletError("Backup is encrypted; a password is required".to_string())
+                })?;
+                let decode = |field: &str, value: &str| {
+                    general_purpose::STANDARD
+                        .decode(value)
+                        .map_err(|e| ChainError::CryptoError(format!("Invalid backup {}: {}", field, e)))
+                };
+                let salt = decode("salt", &encryption.salt)?;
+                let nonce = decode("nonce", &encryption.nonce)?;
+                let ciphertext = decode("payload", &self.payload)?;
+                if nonce.len() != 12 {
+                    return Err(ChainError::CryptoError("Invalid backup nonce length".to_string()));
+                }
+                let plaintext = backup_cipher(password, &salt)?
+                    .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+                    .map_err(|_| ChainError::CryptoError("Decryption failed - wrong password?".to_string()))?;
+                String::from_utf8(plaintext)
+                    .map_err(|e| ChainError::CryptoError(format!("Invalid UTF-8: {}", e)))?
+            }
+        };
+        serde_json::from_str(&json)
+            .map_err(|e| ChainError::WalletError(format!("Failed to parse backup contents: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<String, ChainError> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| ChainError::WalletError(format!("Failed to serialize backup: {}", e)))
+    }
+
+    /// Parse a backup, refusing other files and versions newer than this
+    /// build understands
+    pub fn from_json(json: &str) -> Result<Self, ChainError> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| ChainError::WalletError(format!("Failed to parse backup: {}", e)))?;
+        if value.get("format").and_then(|f| f.as_str()) != Some(BACKUP_FORMAT) {
+            return Err(ChainError::WalletError("Not a TrinityChain wallet backup".to_string()));
+        }
+        let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0);
+        if version == 0 || version > BACKUP_VERSION as u64 {
+            return Err(ChainError::WalletError(format!(
+                "Backup format version {} is not supported (this build reads up to version {}); upgrade TrinityChain to restore it",
+                version, BACKUP_VERSION
+            )));
+        }
+        serde_json::from_value(value)
+            .map_err(|e| ChainError::WalletError(format!("Failed to parse backup: {}", e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ChainError> {
+        write_private_file(path, &self.to_json()?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| ChainError::WalletError(format!("Failed to read backup: {}", e)))?;
+        Self::from_json(&contents)
+    }
+}
+
+fn backup_cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm, ChainError> {
+    let params = Params::new(19456, 2, 1, Some(32))
+        .map_err(|e| ChainError::CryptoError(format!("Argon2 params error: {}", e)))?;
+    let mut key = [0u8; 32];
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| ChainError::CryptoError(format!("Key derivation failed: {}", e)))?;
+    Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| ChainError::CryptoError(format!("Failed to create cipher: {}", e)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -456,4 +673,78 @@ mod tests {
         let decrypt_result = loaded_wallet.decrypt("wrong_password");
         assert!(decrypt_result.is_err());
     }
+
+    #[test]
+    fn test_decrypt_refuses_attacker_chosen_hash_parameters() {
+        let wallet = Wallet::new(None).unwrap();
+        let encrypted = EncryptedWallet::from_wallet(&wallet, "correct_password").unwrap();
+        assert!(encrypted.password_hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+        assert_eq!(encrypted.decrypt("correct_password").unwrap().address, wallet.address);
+
+        // 4 GiB, many passes or another variant are refused before hashing:
+        // the error names the parameters, not the password
+        for (from, to) in [("m=19456", "m=4194304"), ("t=2", "t=100000"), ("p=1", "p=64"), ("$argon2id$", "$argon2i$")] {
+            let tampered = EncryptedWallet {
+                password_hash: encrypted.password_hash.replacen(from, to, 1),
+                ..encrypted.clone()
+            };
+            let started = std::time::Instant::now();
+            let err = tampered.decrypt("correct_password").unwrap_err().to_string();
+            assert!(err.contains("Unsupported password hash parameters"), "{}: {}", to, err);
+            assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        }
+    }
+
+    fn sample_backup_contents() -> BackupContents {
+        let book = crate::addressbook::AddressBook::new();
+        book.add("alice".to_string(), "a".repeat(64), Some("friend".to_string())).unwrap();
+        BackupContents {
+            wallets: vec![Wallet::new(None).unwrap(), Wallet::new(Some("savings".to_string())).unwrap()],
+            labels: book.list(),
+            hd: Some(HdMetadata {
+                mnemonic: crate::hdwallet::HDWallet::generate_mnemonic(12).unwrap(),
+                derivation_path: crate::hdwallet::TRINITY_DERIVATION_PATH.to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("backup.json");
+        let contents = sample_backup_contents();
+
+        let plain = WalletBackup::seal(&contents, None).unwrap();
+        plain.save(&path).unwrap();
+        assert_eq!(WalletBackup::load(&path).unwrap().open(None).unwrap(), contents);
+
+        let encrypted = WalletBackup::seal(&contents, Some("backup password")).unwrap();
+        assert!(!encrypted.payload.contains(&contents.wallets[0].secret_key_hex));
+        encrypted.save(&path).unwrap();
+        let loaded = WalletBackup::load(&path).unwrap();
+        assert!(loaded.is_encrypted());
+        assert!(loaded.open(None).is_err());
+        assert!(loaded.open(Some("wrong password")).is_err());
+        assert_eq!(loaded.open(Some("backup password")).unwrap(), contents);
+    }
+
+    #[test]
+    fn test_backup_rejects_corruption_and_future_versions() {
+        let backup = WalletBackup::seal(&sample_backup_contents(), None).unwrap();
+        let mut corrupted = backup.clone();
+        corrupted.payload = corrupted.payload.replacen("savings", "sav1ngs", 1);
+        let err = corrupted.open(None).unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+
+        let mut value: serde_json::Value = serde_json::from_str(&backup.to_json().unwrap()).unwrap();
+        value["version"] = serde_json::json!(BACKUP_VERSION + 1);
+        value["new_field"] = serde_json::json!("from the future");
+        let err = WalletBackup::from_json(&value.to_string()).unwrap_err();
+        assert!(err.to_string().contains("upgrade"));
+
+        // Unknown fields alone do not break a supported version
+        value["version"] = serde_json::json!(BACKUP_VERSION);
+        assert!(WalletBackup::from_json(&value.to_string()).is_ok());
+        assert!(WalletBackup::from_json(r#"{"address": "abc"}"#).is_err());
+    }
 }
@@ -0,0 +1,600 @@
+//! Address watchlists and notifications
+//!
+//! Subscribers (Telegram chats, webhook URLs, server-sent-event clients)
+//! watch addresses. A [`Notifier`] scans each new block once and emits a
+//! [`Notification`] for every transfer into or out of a watched address.
+//! It emits another when that transfer reaches each confirmation depth the
+//! subscriber asked for. Notifications are published on a broadcast
+//! channel. Each consumer (the Telegram bot, webhook delivery, the SSE
+//! endpoint) picks out its own subscribers, so chain scanning lives only
+//! here.
+//!
+//! Webhook and Telegram watches are stored in the data directory. SSE
+//! watches last as long as the connection.
+
+use crate::blockchain::{Blockchain, Sha256Hash};
+use crate::crypto::{address_from_hex, address_to_hex, Address};
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use crate::transaction::Transaction;
+use crate::units::{self, Unit};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+
+/// File name of the watchlist inside the data directory
+pub const WATCHLIST_FILE: &str = "watchlist.json";
+
+/// Watchlist of the Telegram bot, which runs as its own process
+pub const BOT_WATCHLIST_FILE: &str = "bot_watchlist.json";
+
+/// Depths reported when a watch does not name any
+pub const DEFAULT_CONFIRMATION_DEPTHS: [u64; 1] = [6];
+
+/// Deepest reorganization the scanner follows back
+pub const MAX_REORG_DEPTH: usize = 100;
+
+/// How often [`Notifier::spawn`] looks for new blocks
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Notifications buffered for slow consumers before they miss some
+const EVENT_BUFFER: usize = 1024;
+
+/// Who gets told about a watched address
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Subscriber {
+    Telegram { chat_id: i64 },
+    /// `http://` URL the notification is POSTed to as JSON
+    Webhook { url: String },
+    /// A connected server-sent-events client
+    Sse { client_id: u64 },
+}
+
+impl Subscriber {
+    /// A webhook subscriber, once `url` is one notifications can be POSTed to
+    pub fn webhook(url: &str) -> Result<Self, ChainError> {
+        crate::telemetry::parse_endpoint(url)?;
+        Ok(Subscriber::Webhook { url: url.to_string() })
+    }
+
+    /// SSE clients go away with their connection and are never stored
+    pub fn is_persistent(&self) -> bool {
+        !matches!(self, Subscriber::Sse { .. })
+    }
+}
+
+/// One subscriber watching one address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Watch {
+    pub subscriber: Subscriber,
+    /// Hex address
+    pub address: String,
+    /// Confirmation counts to report beyond the first, e.g. `[6]`
+    #[serde(default = "default_depths")]
+    pub depths: Vec<u64>,
+}
+
+impl Watch {
+    /// Watch `address`, reporting the given depths (the defaults when empty)
+    pub fn new(subscriber: Subscriber, address: &str, depths: Vec<u64>) -> Result<Self, ChainError> {
+        address_from_hex(address)?;
+        let mut depths: Vec<u64> = depths.into_iter().filter(|&d| d > 1).collect();
+        if depths.is_empty() {
+            depths = default_depths();
+        }
+        depths.sort_unstable();
+        depths.dedup();
+        Ok(Watch {
+            subscriber,
+            address: address.to_ascii_lowercase(),
+            depths,
+        })
+    }
+}
+
+fn default_depths() -> Vec<u64> {
+    DEFAULT_CONFIRMATION_DEPTHS.to_vec()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// What happened to a watched address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    /// A transfer or mining reward was included in a block (1 confirmation)
+    Transfer {
+        direction: Direction,
+        txid: String,
+        /// Exact amount in TRC as a decimal string
+        amount: String,
+        /// The other side; `None` for mining rewards
+        counterparty: Option<String>,
+        height: u64,
+    },
+    /// A transfer reported earlier reached `confirmations`
+    Confirmed {
+        txid: String,
+        height: u64,
+        confirmations: u64,
+    },
+}
+
+impl WatchEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchEvent::Transfer { .. } => "transfer",
+            WatchEvent::Confirmed { .. } => "confirmed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notification {
+    pub subscriber: Subscriber,
+    pub address: String,
+    #[serde(flatten)]
+    pub event: WatchEvent,
+}
+
+/// A reported transfer still waiting for deeper confirmations
+#[derive(Debug, Clone)]
+struct Tracked {
+    subscriber: Subscriber,
+    address: String,
+    txid: String,
+    height: u64,
+    block_hash: Sha256Hash,
+    /// Ascending
+    depths: Vec<u64>,
+}
+
+/// Watches plus the scanner state that turns blocks into notifications
+#[derive(Debug, Default)]
+pub struct Watchlist {
+    path: Option<PathBuf>,
+    watches: Vec<Watch>,
+    /// Recently scanned blocks, oldest first, to detect reorganizations
+    scanned: VecDeque<(u64, Sha256Hash)>,
+    tracked: Vec<Tracked>,
+}
+
+impl Watchlist {
+    /// Create an in-memory watchlist
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the watches stored at `path`. A missing file yields an empty
+    /// watchlist that will be created on the first subscription.
+    pub fn load(path: &Path) -> Result<Self, ChainError> {
+        let mut watchlist = Watchlist {
+            path: Some(path.to_path_buf()),
+            ..Default::default()
+        };
+        if !path.exists() {
+            return Ok(watchlist);
+        }
+
+        let content = fs::read_to_string(path)?;
+        watchlist.watches = serde_json::from_str(&content)
+            .map_err(|e| ChainError::DatabaseError(format!("Invalid watchlist {}: {}", path.display(), e)))?;
+        Ok(watchlist)
+    }
+
+    /// Load `watchlist.json` from a node data directory
+    pub fn load_from_data_dir(data_dir: &Path) -> Result<Self, ChainError> {
+        Self::load(&data_dir.join(WATCHLIST_FILE))
+    }
+
+    /// Write the persistent watches to the backing file (no-op in memory)
+    pub fn save(&self) -> Result<(), ChainError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let persistent: Vec<&Watch> = self.watches.iter().filter(|w| w.subscriber.is_persistent()).collect();
+        let json = serde_json::to_string_pretty(&persistent)
+            .map_err(|e| ChainError::DatabaseError(format!("Failed to serialize watchlist: {}", e)))?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, json)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Add a watch, replacing the subscriber's existing watch on the same
+    /// address
+    pub fn subscribe(&mut self, watch: Watch) -> Result<(), ChainError> {
+        self.watches
+            .retain(|w| !(w.subscriber == watch.subscriber && w.address == watch.address));
+        let persistent = watch.subscriber.is_persistent();
+        self.watches.push(watch);
+        if persistent {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Stop watching `address` for `subscriber`; false if it was not watched
+    pub fn unsubscribe(&mut self, subscriber: &Subscriber, address: &str) -> Result<bool, ChainError> {
+        let address = address.to_ascii_lowercase();
+        let before = self.watches.len();
+        self.watches.retain(|w| !(&w.subscriber == subscriber && w.address == address));
+        self.tracked.retain(|t| !(&t.subscriber == subscriber && t.address == address));
+        let removed = self.watches.len() < before;
+        if removed && subscriber.is_persistent() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Drop every watch of `subscriber`, e.g. when an SSE client disconnects
+    pub fn remove_subscriber(&mut self, subscriber: &Subscriber) -> Result<(), ChainError> {
+        let before = self.watches.len();
+        self.watches.retain(|w| &w.subscriber != subscriber);
+        self.tracked.retain(|t| &t.subscriber != subscriber);
+        if self.watches.len() < before && subscriber.is_persistent() {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn watches_of(&self, subscriber: &Subscriber) -> Vec<Watch> {
+        self.watches
+            .iter()
+            .filter(|w| &w.subscriber == subscriber)
+            .cloned()
+            .collect()
+    }
+
+    /// Notifications for blocks added to `chain` since the last scan. The
+    /// first scan only records the tip, so history is never replayed.
+    pub fn scan(&mut self, chain: &Blockchain) -> Vec<Notification> {
+        let Some(tip) = chain.blocks.last().map(|b| b.header.height) else {
+            return Vec::new();
+        };
+        let hash_at = |height: u64| chain.blocks.get(height as usize).map(|b| b.hash());
+
+        // Step back over blocks that are no longer on the main chain
+        while let Some(&(height, hash)) = self.scanned.back() {
+            if hash_at(height) == Some(hash) {
+                break;
+            }
+            self.scanned.pop_back();
+        }
+        self.tracked.retain(|t| hash_at(t.height) == Some(t.block_hash));
+
+        let mut notifications = Vec::new();
+        match self.scanned.back() {
+            Some(&(last, _)) => {
+                for height in last + 1..=tip {
+                    let block = &chain.blocks[height as usize];
+                    let block_hash = block.hash();
+                    for tx in &block.transactions {
+                        self.scan_transaction(tx, height, block_hash, &mut notifications);
+                    }
+                    self.scanned.push_back((height, block_hash));
+                }
+            }
+            // First scan, or reorganized past everything seen: start at the tip
+            None => {
+                if let Some(hash) = hash_at(tip) {
+                    self.scanned.push_back((tip, hash));
+                }
+            }
+        }
+        while self.scanned.len() > MAX_REORG_DEPTH {
+            self.scanned.pop_front();
+        }
+
+        // Transfers that reached a requested depth
+        for tracked in &mut self.tracked {
+            let confirmations = tip + 1 - tracked.height;
+            while tracked.depths.first().is_some_and(|&d| d <= confirmations) {
+                let depth = tracked.depths.remove(0);
+                notifications.push(Notification {
+                    subscriber: tracked.subscriber.clone(),
+                    address: tracked.address.clone(),
+                    event: WatchEvent::Confirmed {
+                        txid: tracked.txid.clone(),
+                        height: tracked.height,
+                        confirmations: depth,
+                    },
+                });
+            }
+        }
+        self.tracked.retain(|t| !t.depths.is_empty());
+        notifications
+    }
+
+    fn scan_transaction(
+        &mut self,
+        tx: &Transaction,
+        height: u64,
+        block_hash: Sha256Hash,
+        notifications: &mut Vec<Notification>,
+    ) {
+        // (address, direction, amount, counterparty)
+        let mut moves: Vec<(Address, Direction, Coord, Option<Address>)> = Vec::new();
+        match tx {
+            Transaction::Transfer(transfer) if transfer.sender != transfer.new_owner => {
+                moves.push((transfer.new_owner, Direction::Incoming, transfer.amount, Some(transfer.sender)));
+                moves.push((transfer.sender, Direction::Outgoing, transfer.amount, Some(transfer.new_owner)));
+            }
+            Transaction::Coinbase(coinbase) => {
+                let paid_out = coinbase.outputs.iter().fold(Coord::from_num(0), |sum, o| sum.saturating_add(o.amount));
+                let reward = coinbase.reward_area.saturating_add(coinbase.fees).saturating_sub(paid_out);
+                moves.push((coinbase.beneficiary_address, Direction::Incoming, reward, None));
+                for output in &coinbase.outputs {
+                    moves.push((output.address, Direction::Incoming, output.amount, None));
+                }
+            }
+            _ => {}
+        }
+
+        let txid = tx.hash_str();
+        for (address, direction, amount, counterparty) in moves {
+            let address = address_to_hex(&address);
+            for watch in self.watches.iter().filter(|w| w.address == address) {
+                notifications.push(Notification {
+                    subscriber: watch.subscriber.clone(),
+                    address: address.clone(),
+                    event: WatchEvent::Transfer {
+                        direction,
+                        txid: txid.clone(),
+                        amount: units::format_number(amount, Unit::Trc),
+                        counterparty: counterparty.as_ref().map(address_to_hex),
+                        height,
+                    },
+                });
+                self.tracked.push(Tracked {
+                    subscriber: watch.subscriber.clone(),
+                    address: address.clone(),
+                    txid: txid.clone(),
+                    height,
+                    block_hash,
+                    depths: watch.depths.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Shared notification engine: one watchlist scanned against the chain,
+/// with every notification published to all consumers
+pub struct Notifier {
+    watchlist: Mutex<Watchlist>,
+    events: broadcast::Sender<Notification>,
+    next_client_id: AtomicU64,
+}
+
+impl Notifier {
+    pub fn new(watchlist: Watchlist) -> Self {
+        Notifier {
+            watchlist: Mutex::new(watchlist),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            next_client_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Receive every notification published from now on
+    pub fn events(&self) -> broadcast::Receiver<Notification> {
+        self.events.subscribe()
+    }
+
+    /// A fresh identity for an SSE client
+    pub fn next_sse_client(&self) -> Subscriber {
+        Subscriber::Sse {
+            client_id: self.next_client_id.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn subscribe(&self, watch: Watch) -> Result<(), ChainError> {
+        self.watchlist.lock().subscribe(watch)
+    }
+
+    pub fn unsubscribe(&self, subscriber: &Subscriber, address: &str) -> Result<bool, ChainError> {
+        self.watchlist.lock().unsubscribe(subscriber, address)
+    }
+
+    pub fn remove_subscriber(&self, subscriber: &Subscriber) -> Result<(), ChainError> {
+        self.watchlist.lock().remove_subscriber(subscriber)
+    }
+
+    pub fn watches(&self) -> Vec<Watch> {
+        self.watchlist.lock().watches().to_vec()
+    }
+
+    pub fn watches_of(&self, subscriber: &Subscriber) -> Vec<Watch> {
+        self.watchlist.lock().watches_of(subscriber)
+    }
+
+    /// Scan `chain` for new blocks and publish the resulting notifications
+    pub fn poll(&self, chain: &Blockchain) -> Vec<Notification> {
+        let notifications = self.watchlist.lock().scan(chain);
+        for notification in &notifications {
+            // No receivers is fine; nobody is listening yet
+            let _ = self.events.send(notification.clone());
+        }
+        notifications
+    }
+
+    /// Poll `chain` every `interval` until the process exits
+    pub fn spawn(self: Arc<Self>, chain: Arc<RwLock<Blockchain>>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let chain = chain.read().await;
+                self.poll(&chain);
+            }
+        });
+    }
+
+    /// POST notifications for webhook subscribers to their URL, through
+    /// `proxy` when set. Failed deliveries are logged and dropped.
+    pub fn spawn_webhooks(self: &Arc<Self>, proxy: Option<String>) {
+        let mut events = self.events();
+        tokio::spawn(async move {
+            loop {
+                let notification = match events.recv().await {
+                    Ok(notification) => notification,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "webhook delivery fell behind");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let Subscriber::Webhook { url } = &notification.subscriber else {
+                    continue;
+                };
+                let body = match serde_json::to_string(&notification) {
+                    Ok(body) => body,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to encode webhook notification");
+                        continue;
+                    }
+                };
+                if let Err(e) = crate::telemetry::post_json(url, &body, proxy.as_deref()).await {
+                    tracing::warn!(url = %url, error = %e, "webhook delivery failed");
+                }
+            }
+        });
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new(Watchlist::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Block;
+    use crate::crypto::KeyPair;
+    use crate::persistence::InMemoryPersistence;
+    use crate::transaction::{CoinbaseTx, TransferTx};
+
+    fn coinbase(beneficiary_address: Address, height: u64) -> Transaction {
+        Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address,
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        })
+    }
+
+    fn mine(chain: &mut Blockchain, miner: Address, txs: Vec<Transaction>) {
+        let height = chain.blocks.len() as u64;
+        let mut transactions = vec![coinbase(miner, height)];
+        transactions.extend(txs);
+        let block = Block::new(height, chain.blocks.last().unwrap().hash(), 0, transactions);
+        chain.apply_block(crate::miner::mine_block(block).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_transfers_and_confirmation_depths() {
+        let alice = KeyPair::generate().unwrap();
+        let miner = [3u8; 32];
+        let bob = [5u8; 32];
+        let mut chain =
+            Blockchain::new_with_persistence(alice.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+
+        let notifier = Notifier::default();
+        let webhook = Subscriber::Webhook {
+            url: "http://127.0.0.1:9/hook".to_string(),
+        };
+        let chat = Subscriber::Telegram { chat_id: 42 };
+        notifier
+            .subscribe(Watch::new(webhook.clone(), &hex::encode(bob), vec![3]).unwrap())
+            .unwrap();
+        notifier
+            .subscribe(Watch::new(chat.clone(), &hex::encode(alice.address()), vec![]).unwrap())
+            .unwrap();
+        assert!(Watch::new(chat.clone(), "not-an-address", vec![]).is_err());
+
+        // The first poll only marks the tip; genesis is history
+        assert!(notifier.poll(&chain).is_empty());
+
+        let mut transfer =
+            TransferTx::new(genesis_utxo, bob, alice.address(), Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+        mine(&mut chain, miner, vec![Transaction::Transfer(transfer)]);
+        let mut events = notifier.events();
+        let notifications = notifier.poll(&chain);
+        assert_eq!(notifications.len(), 2);
+        let incoming = notifications.iter().find(|n| n.subscriber == webhook).unwrap();
+        assert!(matches!(
+            incoming.event,
+            WatchEvent::Transfer { direction: Direction::Incoming, height: 1, .. }
+        ));
+        let outgoing = notifications.iter().find(|n| n.subscriber == chat).unwrap();
+        assert!(matches!(outgoing.event, WatchEvent::Transfer { direction: Direction::Outgoing, .. }));
+        assert_eq!(events.try_recv().unwrap(), notifications[0]);
+
+        mine(&mut chain, miner, vec![]);
+        assert!(notifier.poll(&chain).is_empty());
+        mine(&mut chain, miner, vec![]);
+        let notifications = notifier.poll(&chain);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].subscriber, webhook);
+        assert!(matches!(notifications[0].event, WatchEvent::Confirmed { confirmations: 3, height: 1, .. }));
+
+        // The chat asked for the default depth of 6
+        for _ in 0..3 {
+            mine(&mut chain, miner, vec![]);
+        }
+        let notifications = notifier.poll(&chain);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].subscriber, chat);
+        assert!(matches!(notifications[0].event, WatchEvent::Confirmed { confirmations: 6, .. }));
+    }
+
+    #[test]
+    fn test_only_persistent_watches_are_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let address = hex::encode([5u8; 32]);
+        let mut watchlist = Watchlist::load_from_data_dir(dir.path()).unwrap();
+        let chat = Subscriber::Telegram { chat_id: 7 };
+        watchlist.subscribe(Watch::new(chat.clone(), &address, vec![2, 2, 10]).unwrap()).unwrap();
+        watchlist
+            .subscribe(Watch::new(Subscriber::Sse { client_id: 1 }, &address, vec![]).unwrap())
+            .unwrap();
+
+        let reloaded = Watchlist::load_from_data_dir(dir.path()).unwrap();
+        assert_eq!(reloaded.watches().len(), 1);
+        assert_eq!(reloaded.watches()[0].depths, vec![2, 10]);
+
+        assert!(watchlist.unsubscribe(&chat, &address.to_uppercase()).unwrap());
+        assert!(!watchlist.unsubscribe(&chat, &address).unwrap());
+        assert!(Watchlist::load_from_data_dir(dir.path()).unwrap().watches().is_empty());
+    }
+}
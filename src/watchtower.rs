@@ -0,0 +1,403 @@
+//! Watchtower: alerts on spends from cold-storage addresses
+//!
+//! Addresses listed in `[watchtower] addresses` are expected never to spend,
+//! so any transaction their key signs is a sign it may be compromised. The
+//! watchtower raises an [`Alert`] as soon as such a transaction shows up in
+//! the mempool, again when it is mined, and once more at the configured
+//! depth. A spend whose block leaves the main chain is reported as
+//! reorganized and followed until it is mined again or leaves the mempool,
+//! so a spend confirmed on a branch that lost is never taken as final.
+//!
+//! Alerts are always logged, and delivered to the configured webhook and
+//! Telegram chat. Unlike [`crate::watchlist`], the addresses come from the
+//! config file only, so nothing reachable over the API can silence them.
+
+use crate::blockchain::{Blockchain, Sha256Hash};
+use crate::config::WatchtowerConfig;
+use crate::crypto::{address_from_hex, address_to_hex, Address};
+use crate::error::ChainError;
+use crate::geometry::Coord;
+use crate::transaction::Transaction;
+use crate::units::{self, Unit};
+use crate::watchlist::{MAX_REORG_DEPTH, POLL_INTERVAL};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Environment variable holding the Telegram bot token, as for
+/// `trinity-telegram-bot`
+pub const TELEGRAM_TOKEN_ENV: &str = "TELOXIDE_TOKEN";
+
+/// What happened to a spend from a watched address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AlertEvent {
+    /// Entered the mempool
+    Pending,
+    /// Included in a main chain block
+    Mined { height: u64 },
+    /// Reached the configured depth
+    Confirmed { height: u64, confirmations: u64 },
+    /// Its block at `height` left the main chain
+    Reorged { height: u64 },
+    /// Left the mempool without being mined
+    Dropped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    /// Hex address that spent
+    pub address: String,
+    pub txid: String,
+    /// Exact amount taken from the address in TRC, fees included
+    pub amount: String,
+    #[serde(flatten)]
+    pub event: AlertEvent,
+}
+
+impl Alert {
+    /// One-line description for logs and chat messages
+    pub fn message(&self) -> String {
+        let what = match &self.event {
+            AlertEvent::Pending => "is pending in the mempool".to_string(),
+            AlertEvent::Mined { height } => format!("was mined at height {}", height),
+            AlertEvent::Confirmed { height, confirmations } => {
+                format!("has {} confirmations at height {}", confirmations, height)
+            }
+            AlertEvent::Reorged { height } => format!("was reorganized out of height {}", height),
+            AlertEvent::Dropped => "left the mempool unmined".to_string(),
+        };
+        format!(
+            "Watchtower: spend of {} TRC from {} in {} {}",
+            self.amount, self.address, self.txid, what
+        )
+    }
+}
+
+/// A spend being followed to its final depth
+#[derive(Debug, Clone)]
+struct Spend {
+    amount: Coord,
+    /// Main chain block holding it, `None` while pending
+    block: Option<(u64, Sha256Hash)>,
+    final_reported: bool,
+}
+
+/// Scanner turning the chain and mempool into alerts
+#[derive(Debug)]
+pub struct Watchtower {
+    addresses: HashSet<Address>,
+    confirmations: u64,
+    /// Recently scanned blocks, oldest first, to detect reorganizations
+    scanned: VecDeque<(u64, Sha256Hash)>,
+    /// Spends by (txid, spending address)
+    spends: HashMap<(Sha256Hash, Address), Spend>,
+}
+
+impl Watchtower {
+    /// Watch `addresses`, reporting spends as final at `confirmations`
+    pub fn new(addresses: &[String], confirmations: u64) -> Result<Self, ChainError> {
+        Ok(Watchtower {
+            addresses: addresses.iter().map(|a| address_from_hex(a.trim())).collect::<Result<_, _>>()?,
+            confirmations: confirmations.max(1),
+            scanned: VecDeque::new(),
+            spends: HashMap::new(),
+        })
+    }
+
+    pub fn from_config(config: &WatchtowerConfig) -> Result<Self, ChainError> {
+        Self::new(&config.addresses, config.confirmations)
+    }
+
+    /// Alerts for changes since the last scan. The first scan reports what
+    /// is already in the mempool, but no block mined before it.
+    pub fn scan(&mut self, chain: &Blockchain) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let Some(tip) = chain.blocks.last().map(|b| b.header.height) else {
+            return alerts;
+        };
+        let hash_at = |height: u64| chain.blocks.get(height as usize).map(|b| b.hash());
+
+        // Step back over blocks that are no longer on the main chain
+        while let Some(&(height, hash)) = self.scanned.back() {
+            if hash_at(height) == Some(hash) {
+                break;
+            }
+            self.scanned.pop_back();
+        }
+        for (&(txid, address), spend) in &mut self.spends {
+            if let Some((height, hash)) = spend.block {
+                if hash_at(height) != Some(hash) {
+                    spend.block = None;
+                    spend.final_reported = false;
+                    alerts.push(alert(txid, address, spend.amount, AlertEvent::Reorged { height }));
+                }
+            }
+        }
+
+        match self.scanned.back() {
+            Some(&(last, _)) => {
+                for height in last + 1..=tip {
+                    let block = &chain.blocks[height as usize];
+                    let block_hash = block.hash();
+                    for tx in &block.transactions {
+                        for (address, amount) in self.spenders(tx) {
+                            let spend = self.spends.entry((tx.hash(), address)).or_insert(Spend {
+                                amount,
+                                block: None,
+                                final_reported: false,
+                            });
+                            if spend.block.is_none() {
+                                spend.block = Some((height, block_hash));
+                                alerts.push(alert(tx.hash(), address, amount, AlertEvent::Mined { height }));
+                            }
+                        }
+                    }
+                    self.scanned.push_back((height, block_hash));
+                }
+            }
+            // First scan, or reorganized past everything seen: start at the tip
+            None => {
+                if let Some(hash) = hash_at(tip) {
+                    self.scanned.push_back((tip, hash));
+                }
+            }
+        }
+        while self.scanned.len() > MAX_REORG_DEPTH {
+            self.scanned.pop_front();
+        }
+
+        let mempool = chain.mempool.get_all_transactions();
+        let in_mempool: HashSet<Sha256Hash> = mempool.iter().map(Transaction::hash).collect();
+        for tx in &mempool {
+            for (address, amount) in self.spenders(tx) {
+                if let Entry::Vacant(entry) = self.spends.entry((tx.hash(), address)) {
+                    entry.insert(Spend { amount, block: None, final_reported: false });
+                    alerts.push(alert(tx.hash(), address, amount, AlertEvent::Pending));
+                }
+            }
+        }
+
+        let confirmations = self.confirmations;
+        self.spends.retain(|&(txid, address), spend| match spend.block {
+            None if in_mempool.contains(&txid) => true,
+            None => {
+                alerts.push(alert(txid, address, spend.amount, AlertEvent::Dropped));
+                false
+            }
+            Some((height, _)) => {
+                let depth = tip + 1 - height;
+                if !spend.final_reported && depth >= confirmations {
+                    spend.final_reported = true;
+                    alerts.push(alert(txid, address, spend.amount, AlertEvent::Confirmed { height, confirmations }));
+                }
+                // Past the deepest reorganization followed, nothing changes
+                depth <= MAX_REORG_DEPTH as u64
+            }
+        });
+        alerts
+    }
+
+    /// Watched addresses `tx` takes value from, or that signed it, with the
+    /// amount each one pays
+    fn spenders(&self, tx: &Transaction) -> Vec<(Address, Coord)> {
+        let zero = Coord::from_num(0);
+        let spenders = match tx {
+            Transaction::Transfer(transfer) => match &transfer.sponsor {
+                Some(sponsor) => vec![(transfer.sender, transfer.amount), (sponsor.payer, transfer.fee_area)],
+                None => vec![(transfer.sender, transfer.amount.saturating_add(transfer.fee_area))],
+            },
+            Transaction::Subdivision(subdivision) => vec![(subdivision.owner_address, subdivision.fee_area)],
+            Transaction::Register(register) => vec![(register.owner, zero)],
+            Transaction::Data(data) => vec![(data.owner, data.fee_area)],
+            Transaction::Coinbase(_) | Transaction::Announce(_) => Vec::new(),
+        };
+        spenders
+            .into_iter()
+            .filter(|(address, _)| self.addresses.contains(address))
+            .collect()
+    }
+}
+
+fn alert(txid: Sha256Hash, address: Address, amount: Coord, event: AlertEvent) -> Alert {
+    Alert {
+        address: address_to_hex(&address),
+        txid: hex::encode(txid),
+        amount: units::format_number(amount, Unit::Trc),
+        event,
+    }
+}
+
+/// Send `alert` to the webhook and Telegram chat of `config`, logging
+/// failures
+async fn deliver(alert: &Alert, config: &WatchtowerConfig, proxy: Option<&str>) {
+    tracing::error!(address = %alert.address, txid = %alert.txid, "{}", alert.message());
+    if let Some(url) = &config.webhook {
+        match serde_json::to_string(alert) {
+            Ok(body) => {
+                if let Err(e) = crate::telemetry::post_json(url, &body, proxy).await {
+                    tracing::warn!(url = %url, error = %e, "watchtower webhook delivery failed");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to encode watchtower alert"),
+        }
+    }
+    if let Some(chat_id) = config.telegram_chat_id {
+        let Ok(token) = std::env::var(TELEGRAM_TOKEN_ENV) else {
+            tracing::warn!("watchtower.telegram_chat_id is set but {} is not", TELEGRAM_TOKEN_ENV);
+            return;
+        };
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+        let form = [("chat_id", chat_id.to_string()), ("text", alert.message())];
+        let sent = reqwest::Client::new().post(url).form(&form).send().await;
+        if let Err(e) = sent.and_then(|response| response.error_for_status()) {
+            tracing::warn!(error = %e.without_url(), "watchtower Telegram delivery failed");
+        }
+    }
+}
+
+/// Scan `chain` for spends from the configured addresses until the process
+/// exits, delivering webhooks through `proxy` when set. Does nothing when no
+/// address is configured.
+pub fn spawn(config: &WatchtowerConfig, chain: Arc<RwLock<Blockchain>>, proxy: Option<String>) {
+    if config.addresses.is_empty() {
+        return;
+    }
+    let mut watchtower = match Watchtower::from_config(config) {
+        Ok(watchtower) => watchtower,
+        Err(e) => {
+            tracing::error!(error = %e, "invalid [watchtower] addresses; watchtower disabled");
+            return;
+        }
+    };
+    tracing::info!(addresses = config.addresses.len(), "watchtower started");
+    let config = config.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let alerts = watchtower.scan(&*chain.read().await);
+            for alert in &alerts {
+                deliver(alert, &config, proxy.as_deref()).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Block;
+    use crate::crypto::KeyPair;
+    use crate::persistence::InMemoryPersistence;
+    use crate::transaction::{CoinbaseTx, TransferTx};
+
+    fn mine(chain: &mut Blockchain, txs: Vec<Transaction>) {
+        let height = chain.blocks.len() as u64;
+        let mut transactions = vec![Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: [3; 32],
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        })];
+        transactions.extend(txs);
+        let block = Block::new(height, chain.blocks.last().unwrap().hash(), 0, transactions);
+        chain.apply_block(crate::miner::mine_block(block).unwrap()).unwrap();
+    }
+
+    fn events(alerts: Vec<Alert>) -> Vec<AlertEvent> {
+        alerts.into_iter().map(|alert| alert.event).collect()
+    }
+
+    #[test]
+    fn test_spend_is_followed_through_a_reorg() {
+        let cold = KeyPair::generate().unwrap();
+        let mut chain =
+            Blockchain::new_with_persistence(cold.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let mut watchtower = Watchtower::new(&[hex::encode(cold.address())], 2).unwrap();
+        assert!(Watchtower::new(&["cold".to_string()], 2).is_err());
+        assert!(watchtower.scan(&chain).is_empty());
+
+        let mut transfer =
+            TransferTx::new(genesis_utxo, [5u8; 32], cold.address(), Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = cold.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), cold.public_key_bytes().to_vec());
+        let transfer = Transaction::Transfer(transfer);
+        let state = chain.state.clone();
+        let domain = chain.signing_domain(chain.next_height());
+        chain.mempool.accept_transaction(transfer.clone(), &state, &domain).unwrap();
+        let alerts = watchtower.scan(&chain);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].txid, transfer.hash_str());
+        assert_eq!(alerts[0].amount, "10");
+        assert_eq!(alerts[0].event, AlertEvent::Pending);
+        assert!(alerts[0].message().contains("pending"));
+        assert!(watchtower.scan(&chain).is_empty());
+
+        mine(&mut chain, vec![transfer.clone()]);
+        assert_eq!(events(watchtower.scan(&chain)), vec![AlertEvent::Mined { height: 1 }]);
+
+        // The block is reverted before reaching the depth, and the spend
+        // returns to the mempool
+        chain.revert_block().unwrap();
+        let state = chain.state.clone();
+        let domain = chain.signing_domain(chain.next_height());
+        chain.mempool.accept_transaction(transfer.clone(), &state, &domain).unwrap();
+        assert_eq!(events(watchtower.scan(&chain)), vec![AlertEvent::Reorged { height: 1 }]);
+
+        mine(&mut chain, vec![]);
+        mine(&mut chain, vec![transfer.clone()]);
+        assert_eq!(events(watchtower.scan(&chain)), vec![AlertEvent::Mined { height: 2 }]);
+        mine(&mut chain, vec![]);
+        assert_eq!(
+            events(watchtower.scan(&chain)),
+            vec![AlertEvent::Confirmed { height: 2, confirmations: 2 }]
+        );
+        mine(&mut chain, vec![]);
+        assert!(watchtower.scan(&chain).is_empty());
+    }
+
+    #[test]
+    fn test_dropped_spends_and_other_addresses() {
+        let cold = KeyPair::generate().unwrap();
+        let hot = KeyPair::generate().unwrap();
+        let mut chain =
+            Blockchain::new_with_persistence(hot.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+        let genesis_utxo = chain.blocks[0].transactions[0].hash();
+        let mut watchtower = Watchtower::new(&[hex::encode(cold.address())], 6).unwrap();
+        watchtower.scan(&chain);
+
+        // Spends from unwatched addresses and payments to the cold address
+        // are not alerts
+        let mut transfer =
+            TransferTx::new(genesis_utxo, cold.address(), hot.address(), Coord::from_num(10), Coord::from_num(0), 1);
+        let signature = hot.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+        transfer.sign(signature.to_vec(), hot.public_key_bytes().to_vec());
+        let transfer = Transaction::Transfer(transfer);
+        let state = chain.state.clone();
+        let domain = chain.signing_domain(chain.next_height());
+        chain.mempool.accept_transaction(transfer.clone(), &state, &domain).unwrap();
+        mine(&mut chain, vec![transfer]);
+        assert!(watchtower.scan(&chain).is_empty());
+
+        let cold_utxo = chain.blocks[1].transactions[1].hash();
+        let mut spend =
+            TransferTx::new(cold_utxo, hot.address(), cold.address(), Coord::from_num(4), Coord::from_num(0), 1);
+        let signature = cold.sign(&spend.signable_message(&chain.chain_id())).unwrap();
+        spend.sign(signature.to_vec(), cold.public_key_bytes().to_vec());
+        let spend = Transaction::Transfer(spend);
+        let state = chain.state.clone();
+        let domain = chain.signing_domain(chain.next_height());
+        chain.mempool.accept_transaction(spend.clone(), &state, &domain).unwrap();
+        assert_eq!(events(watchtower.scan(&chain)), vec![AlertEvent::Pending]);
+        chain.mempool.remove_transaction(&spend.hash());
+        assert_eq!(events(watchtower.scan(&chain)), vec![AlertEvent::Dropped]);
+        assert!(watchtower.scan(&chain).is_empty());
+    }
+}
@@ -9,7 +9,9 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use trinitychain::api::{build_api_router, Node};
 use trinitychain::blockchain::Blockchain;
+use trinitychain::geometry::Coord;
 use trinitychain::network::NetworkNode;
+use trinitychain::transaction::{SigningDomain, Transaction, TransferTx};
 
 #[tokio::test]
 async fn test_dashboard_endpoints() {
@@ -66,6 +68,15 @@ async fn test_dashboard_endpoints() {
     assert!(json["count"].is_number());
     assert!(json["peers"].is_array());
 
+    // Test /api/sync/status
+    let response = server.get("/api/sync/status").await;
+    assert_eq!(response.status_code(), 200);
+    let json: Value = response.json();
+    assert_eq!(json["state"], "idle");
+    assert!(json["target_height"].is_number());
+    assert!(json["eta_secs"].is_number());
+    assert_eq!(json["active_peers"], 0);
+
     // Test /api/mempool
     let response = server.get("/api/mempool").await;
     assert_eq!(response.status_code(), 200);
@@ -110,12 +121,719 @@ async fn test_dashboard_endpoints() {
     assert!(json["error"].is_string());
 
     // Test /api/wallet/create
-    let response = server.post("/api/wallet/create").await;
+    let response = server
+        .post("/api/wallet/create")
+        .json(&serde_json::json!({ "passphrase": "correct horse battery" }))
+        .await;
     assert_eq!(response.status_code(), 200);
     let json: Value = response.json();
     assert!(json["address"].is_string());
-    assert!(json["public_key"].is_string());
-    assert!(json["private_key"].is_string());
+    assert!(json["encrypted_backup"].is_object());
+    assert!(json.get("private_key").is_none());
 
     println!("✅ All dashboard endpoints responded correctly with expected JSON");
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_wallet_keystore_session_flow() {
+    let blockchain = Blockchain::new([0; 32], 1).expect("Failed to create blockchain");
+    let domain = SigningDomain::new(blockchain.chain_id());
+    let blockchain = Arc::new(RwLock::new(blockchain));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let api_node = Arc::new(Node::new_shared(blockchain, network, None));
+    let server = TestServer::new(build_api_router(api_node)).expect("Failed to create test server");
+
+    // Passphrases that are too short are rejected
+    let response = server
+        .post("/api/wallet/create")
+        .json(&serde_json::json!({ "passphrase": "short" }))
+        .await;
+    assert_eq!(response.status_code(), 400);
+
+    let response = server
+        .post("/api/wallet/create")
+        .json(&serde_json::json!({ "passphrase": "correct horse battery" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let created: Value = response.json();
+    let address = created["address"].as_str().unwrap().to_string();
+
+    // Wrong passphrase cannot unlock the keystore
+    let response = server
+        .post("/api/wallet/unlock")
+        .json(&serde_json::json!({ "address": address, "passphrase": "wrong passphrase" }))
+        .await;
+    assert_eq!(response.status_code(), 401);
+
+    let response = server
+        .post("/api/wallet/unlock")
+        .json(&serde_json::json!({ "address": address, "passphrase": "correct horse battery" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let unlocked: Value = response.json();
+    let token = unlocked["session_token"].as_str().unwrap().to_string();
+
+    // Sign a transfer from the unlocked wallet without submitting it
+    let sender = trinitychain::crypto::address_from_hex(&address).unwrap();
+    let tx = Transaction::Transfer(TransferTx::new(
+        [1u8; 32],
+        [2u8; 32],
+        sender,
+        Coord::from_num(1),
+        Coord::from_num(0),
+        0,
+    ));
+    let response = server
+        .post("/api/wallet/sign")
+        .json(&serde_json::json!({ "session_token": token, "transaction": tx }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let signed: Value = response.json();
+    let signed_tx: Transaction = serde_json::from_value(signed["transaction"].clone()).unwrap();
+    match signed_tx {
+        Transaction::Transfer(transfer) => assert!(transfer.validate(&domain).is_ok()),
+        _ => panic!("Expected a transfer transaction"),
+    }
+
+    // After locking, the session can no longer sign
+    let response = server
+        .post("/api/wallet/lock")
+        .json(&serde_json::json!({ "session_token": token }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+
+    let response = server
+        .post("/api/wallet/sign")
+        .json(&serde_json::json!({ "session_token": token, "transaction": tx }))
+        .await;
+    assert_eq!(response.status_code(), 401);
+}
+
+#[tokio::test]
+async fn test_wallet_spending_limits() {
+    use trinitychain::spending::{SpendingLedger, SpendingPolicy};
+
+    let blockchain = Arc::new(RwLock::new(Blockchain::new([0; 32], 1).unwrap()));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let policy = SpendingPolicy {
+        max_per_transaction: Some(Coord::from_num(10)),
+        max_per_day: Some(Coord::from_num(12)),
+        confirm_above: Some(Coord::from_num(5)),
+    };
+    let api_node = Node::new_shared(blockchain, network, None).with_spending_limits(policy, SpendingLedger::new());
+    let server = TestServer::new(build_api_router(Arc::new(api_node))).unwrap();
+
+    let passphrase = "correct horse battery";
+    let created: Value = server.post("/api/wallet/create").json(&serde_json::json!({ "passphrase": passphrase })).await.json();
+    let address = created["address"].as_str().unwrap().to_string();
+    let unlocked: Value = server
+        .post("/api/wallet/unlock")
+        .json(&serde_json::json!({ "address": address, "passphrase": passphrase }))
+        .await
+        .json();
+    let token = unlocked["session_token"].as_str().unwrap().to_string();
+    let sender = trinitychain::crypto::address_from_hex(&address).unwrap();
+    let sign = |amount: i32, confirm: bool| {
+        let tx = Transaction::Transfer(TransferTx::new([1u8; 32], [2u8; 32], sender, Coord::from_num(amount), Coord::from_num(0), 0));
+        server
+            .post("/api/wallet/sign")
+            .json(&serde_json::json!({ "session_token": token, "transaction": tx, "confirm": confirm }))
+    };
+
+    assert_eq!(sign(3, false).await.status_code(), 200);
+    // Above confirm_above only with confirmation
+    assert_eq!(sign(6, false).expect_failure().await.status_code(), 403);
+    assert_eq!(sign(6, true).await.status_code(), 200);
+    // Over the per-transaction limit, then over the daily total of 3 + 6
+    assert_eq!(sign(11, true).expect_failure().await.status_code(), 403);
+    assert_eq!(sign(4, true).expect_failure().await.status_code(), 403);
+    assert_eq!(sign(3, false).await.status_code(), 200);
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_require_token() {
+    let blockchain = Arc::new(RwLock::new(Blockchain::new([0; 32], 1).unwrap()));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let bearer = |token: &str| {
+        (
+            axum::http::header::AUTHORIZATION,
+            axum::http::HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        )
+    };
+
+    // Without a configured token the operator API is disabled
+    let disabled = Node::new_shared(blockchain.clone(), network.clone(), None);
+    let server = TestServer::new(build_api_router(Arc::new(disabled))).unwrap();
+    assert_eq!(server.get("/api/network/bans").await.status_code(), 403);
+
+    let api_node = Node::new_shared(blockchain, network, None).with_admin_token(Some("s3cret".into()));
+    let server = TestServer::new(build_api_router(Arc::new(api_node))).unwrap();
+
+    assert_eq!(server.get("/api/network/bans").await.status_code(), 401);
+    let (name, value) = bearer("wrong");
+    assert_eq!(server.get("/api/network/bans").add_header(name, value).await.status_code(), 401);
+
+    // Peer ban management
+    let (name, value) = bearer("s3cret");
+    let response = server
+        .post("/api/network/ban")
+        .add_header(name.clone(), value.clone())
+        .json(&serde_json::json!({ "address": "203.0.113.7:8333", "reason": "spam" }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let json: Value = server
+        .get("/api/network/bans")
+        .add_header(name.clone(), value.clone())
+        .await
+        .json();
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["bans"][0]["host"], "203.0.113.7");
+    let unban = || {
+        server
+            .post("/api/network/unban")
+            .add_header(name.clone(), value.clone())
+            .json(&serde_json::json!({ "address": "203.0.113.7" }))
+    };
+    assert_eq!(unban().await.status_code(), 200);
+    assert_eq!(unban().await.status_code(), 404);
+
+    // Runtime peer management
+    let peer_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let peer_port = peer_listener.local_addr().unwrap().port();
+    let peer = Arc::new(NetworkNode::new(Arc::new(RwLock::new(
+        Blockchain::new([0; 32], 1).unwrap(),
+    ))));
+    tokio::spawn(peer.serve(peer_listener));
+
+    let address = format!("127.0.0.1:{}", peer_port);
+    let response = server
+        .post("/api/network/peers")
+        .add_header(name.clone(), value.clone())
+        .json(&serde_json::json!({ "address": address }))
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let json: Value = server
+        .get("/api/network/addnodes")
+        .add_header(name.clone(), value.clone())
+        .await
+        .json();
+    assert_eq!(json["count"], 1);
+
+    let response = server
+        .delete(&format!("/api/network/peers/{}", address))
+        .add_header(name.clone(), value.clone())
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let response = server
+        .delete(&format!("/api/network/peers/{}", address))
+        .add_header(name, value)
+        .await;
+    assert_eq!(response.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_mempool_operator_endpoints() {
+    let blockchain = Arc::new(RwLock::new(Blockchain::new([0; 32], 1).unwrap()));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let cheap = Transaction::Transfer(TransferTx::new(
+        [1; 32],
+        [2; 32],
+        [1; 32],
+        Coord::from_num(10),
+        Coord::from_num(0),
+        1,
+    ));
+    let rich = Transaction::Transfer(TransferTx::new(
+        [3; 32],
+        [2; 32],
+        [3; 32],
+        Coord::from_num(10),
+        Coord::from_num(5),
+        1,
+    ));
+    {
+        let mut chain = blockchain.write().await;
+        chain.mempool.add_transaction(cheap.clone()).unwrap();
+        chain.mempool.add_transaction(rich.clone()).unwrap();
+    }
+
+    let api_node = Node::new_shared(blockchain, network, None).with_admin_token(Some("s3cret".into()));
+    let server = TestServer::new(build_api_router(Arc::new(api_node))).unwrap();
+    let name = axum::http::header::AUTHORIZATION;
+    let value = axum::http::HeaderValue::from_static("Bearer s3cret");
+
+    let json: Value = server.get("/api/mempool/by-fee").await.json();
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["entries"][0]["hash"], rich.hash_str());
+
+    let prioritize_path = format!("/api/mempool/{}/prioritize", cheap.hash_str());
+    assert_eq!(server.post(&prioritize_path).await.status_code(), 401);
+    let response = server
+        .post(&prioritize_path)
+        .add_header(name.clone(), value.clone())
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let json: Value = server.get("/api/mempool/by-fee").await.json();
+    assert_eq!(json["entries"][0]["hash"], cheap.hash_str());
+    assert_eq!(json["entries"][0]["prioritized"], true);
+
+    let response = server
+        .delete(&format!("/api/mempool/{}", cheap.hash_str()))
+        .add_header(name.clone(), value.clone())
+        .await;
+    assert_eq!(response.status_code(), 200);
+    let response = server
+        .delete(&prioritize_path)
+        .add_header(name, value)
+        .await;
+    assert_eq!(response.status_code(), 404);
+    let json: Value = server.get("/api/mempool/by-fee").await.json();
+    assert_eq!(json["count"], 1);
+}
+
+#[tokio::test]
+async fn test_triangle_lookup_by_structural_id() {
+    let blockchain = Blockchain::new([1; 32], 1).unwrap();
+    let genesis_coinbase = blockchain.blocks[0].transactions[0].hash();
+    let blockchain = Arc::new(RwLock::new(blockchain));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let server = TestServer::new(build_api_router(Arc::new(Node::new_shared(blockchain, network, None)))).unwrap();
+
+    let root = hex::encode(genesis_coinbase);
+    let response = server.get(&format!("/api/triangle/{}", root)).await;
+    assert_eq!(response.status_code(), 200);
+    let json: Value = response.json();
+    assert_eq!(json["id"], root);
+    assert_eq!(json["depth"], 0);
+    assert_eq!(json["outputs"][0]["triangle"]["id"], root);
+
+    assert_eq!(server.get(&format!("/api/triangle/{}/0", root)).await.status_code(), 404);
+    assert_eq!(server.get("/api/triangle/not-an-id").await.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_address_utxos_endpoint() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::transaction::CoinbaseTx;
+
+    let owner = [4u8; 32];
+    let mut chain = Blockchain::new(owner, 0).unwrap();
+    for height in 1..=2u64 {
+        let tip = chain.blocks.last().unwrap();
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: owner,
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let block = Block::new(height, tip.hash(), 0, vec![coinbase]);
+        chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    }
+    let blockchain = Arc::new(RwLock::new(chain));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let server = TestServer::new(build_api_router(Arc::new(Node::new_shared(blockchain, network, None)))).unwrap();
+    let path = format!("/api/address/{}/utxos", hex::encode(owner));
+
+    let json: Value = server.get(&path).await.json();
+    assert_eq!(json["total"], 3);
+    // Newest first: the latest coinbase has one confirmation, genesis three
+    assert_eq!(json["utxos"][0]["height"], 2);
+    assert_eq!(json["utxos"][0]["confirmations"], 1);
+    assert_eq!(json["utxos"][2]["confirmations"], 3);
+    assert_eq!(json["utxos"][0]["vertices"].as_array().unwrap().len(), 3);
+    assert!(json["utxos"][0]["value"].is_string());
+
+    let json: Value = server
+        .get(&path)
+        .add_query_param("page", 1)
+        .add_query_param("limit", 2)
+        .await
+        .json();
+    assert_eq!(json["utxos"].as_array().unwrap().len(), 1);
+    assert_eq!(json["utxos"][0]["height"], 0);
+
+    assert_eq!(server.get("/api/address/zz/utxos").await.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_telemetry_preview() {
+    let blockchain = Blockchain::new([1u8; 32], 1).unwrap();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(blockchain)))).unwrap();
+
+    let json: Value = server.get("/api/telemetry/preview").await.json();
+    // Telemetry is off unless configured; the preview still shows the payload
+    assert_eq!(json["enabled"], false);
+    assert_eq!(json["payload"]["height"], 0);
+    assert_eq!(json["payload"]["peer_count"], 0);
+    assert_eq!(json["payload"]["os"], std::env::consts::OS);
+    assert!(json["payload"]["version"].is_string());
+}
+
+#[tokio::test]
+async fn test_checkpoint_submission() {
+    use trinitychain::checkpoint::{Checkpoint, CheckpointAuthority, SignedCheckpoint};
+    use trinitychain::crypto::KeyPair;
+
+    let blockchain = Blockchain::new([1u8; 32], 1).unwrap();
+    let genesis = blockchain.blocks[0].hash();
+
+    // Disabled unless an authority is configured
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(blockchain.clone())))).unwrap();
+    let json: Value = server.get("/api/blockchain/stats").await.json();
+    assert_eq!(json["checkpoints"]["enabled"], false);
+    let signed = SignedCheckpoint::new(Checkpoint { height: 0, hash: genesis });
+    let response = server.post("/api/blockchain/checkpoint").json(&signed).await;
+    assert_eq!(response.status_code(), 403);
+
+    let key = KeyPair::generate().unwrap();
+    let authority = CheckpointAuthority::new(vec![key.public_key_bytes().to_vec()], 1).unwrap();
+    let blockchain = Arc::new(RwLock::new(blockchain));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()).with_checkpoint_authority(authority));
+    let server = TestServer::new(build_api_router(Arc::new(Node::new_shared(blockchain, network, None)))).unwrap();
+
+    let response = server.post("/api/blockchain/checkpoint").json(&signed).await;
+    assert_eq!(response.status_code(), 400);
+
+    let mut signed = signed;
+    signed.sign(&key).unwrap();
+    let response = server.post("/api/blockchain/checkpoint").json(&signed).await;
+    assert_eq!(response.status_code(), 200);
+
+    let json: Value = server.get("/api/blockchain/stats").await.json();
+    assert_eq!(json["checkpoints"]["enabled"], true);
+    assert_eq!(json["checkpoints"]["threshold"], 1);
+    assert_eq!(json["checkpoints"]["latest"]["height"], 0);
+    assert_eq!(json["checkpoints"]["latest"]["hash"], hex::encode(genesis));
+}
+
+#[tokio::test]
+async fn test_archive_endpoints() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::crypto::KeyPair;
+    use trinitychain::transaction::CoinbaseTx;
+
+    let alice = KeyPair::generate().unwrap();
+    let bob = [5u8; 32];
+    let mut chain = Blockchain::new(alice.address(), 0).unwrap();
+    let genesis_utxo = chain.blocks[0].transactions[0].hash();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain.clone())))).unwrap();
+    let response = server.get(&format!("/api/archive/utxo/{}", hex::encode(genesis_utxo))).await;
+    assert_eq!(response.status_code(), 403);
+
+    chain.enable_archive().unwrap();
+    let mut transfer = TransferTx::new(genesis_utxo, bob, alice.address(), Coord::from_num(10), Coord::from_num(0), 1);
+    let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+    transfer.sign(signature.to_vec(), alice.public_key.serialize().to_vec());
+    let transfer = Transaction::Transfer(transfer);
+    let coinbase = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address: alice.address(),
+        nonce: 1,
+        height: 1,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let block = Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase, transfer.clone()]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    let json: Value = server.get(&format!("/api/archive/utxo/{}", hex::encode(genesis_utxo))).await.json();
+    assert_eq!(json["created_height"], 0);
+    assert_eq!(json["spent_height"], 1);
+    assert_eq!(json["spending_tx"], hex::encode(transfer.hash()));
+
+    let json: Value = server
+        .get(&format!("/api/archive/utxo/{}/provenance", hex::encode(transfer.hash())))
+        .await
+        .json();
+    assert_eq!(json["output"]["spent_height"], Value::Null);
+    assert_eq!(json["ancestors"][0]["utxo"], hex::encode(genesis_utxo));
+    assert!(json["descendants"].as_array().unwrap().is_empty());
+
+    // Only Alice held the genesis triangle before the transfer
+    let path = format!("/api/archive/triangle/{}", hex::encode(genesis_utxo));
+    let json: Value = server.get(&path).add_query_param("height", 0).await.json();
+    assert_eq!(json["outputs"].as_array().unwrap().len(), 1);
+    assert_eq!(json["outputs"][0]["owner"], hex::encode(alice.address()));
+    let json: Value = server.get(&path).await.json();
+    assert_eq!(json["height"], 1);
+    assert_eq!(json["outputs"].as_array().unwrap().len(), 2);
+    assert_eq!(server.get(&path).add_query_param("height", 5).await.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_address_pubkey_endpoint() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::crypto::KeyPair;
+    use trinitychain::transaction::{AnnounceTx, CoinbaseTx};
+
+    let alice = KeyPair::generate().unwrap();
+    let mut chain = Blockchain::new(alice.address(), 0).unwrap();
+    let path = format!("/api/address/{}/pubkey", hex::encode(alice.address()));
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain.clone())))).unwrap();
+    assert_eq!(server.get(&path).await.status_code(), 404);
+    assert_eq!(server.get("/api/address/xyz/pubkey").await.status_code(), 400);
+
+    // Alice has only mined, so she announces her key
+    let coinbase = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address: alice.address(),
+        nonce: 1,
+        height: 1,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let announce = Transaction::Announce(AnnounceTx::new(alice.public_key_bytes().to_vec()));
+    let block = Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase, announce]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    let json: Value = server.get(&path).await.json();
+    assert_eq!(json["public_key"], hex::encode(alice.public_key_bytes()));
+}
+
+#[tokio::test]
+async fn test_name_endpoint() {
+    use trinitychain::blockchain::{Block, NAME_REGISTRATION_PERIOD};
+    use trinitychain::crypto::KeyPair;
+    use trinitychain::transaction::{CoinbaseTx, RegisterNameTx};
+
+    let alice = KeyPair::generate().unwrap();
+    let mut chain = Blockchain::new(alice.address(), 0).unwrap();
+    let coinbase = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address: alice.address(),
+        nonce: 1,
+        height: 1,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let mut register = RegisterNameTx::new("alice".to_string(), alice.address(), 0);
+    let signature = alice.sign(&register.signable_message(&chain.chain_id())).unwrap();
+    register.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+    let block = Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase, Transaction::Register(register)]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    for path in ["/api/name/alice", "/api/name/@alice"] {
+        let json: Value = server.get(path).await.json();
+        assert_eq!(json["name"], "alice");
+        assert_eq!(json["owner"], hex::encode(alice.address()));
+        assert_eq!(json["registered_height"], 1);
+        assert_eq!(json["expires_height"], 1 + NAME_REGISTRATION_PERIOD);
+        assert_eq!(json["expired"], false);
+    }
+    assert_eq!(server.get("/api/name/bob").await.status_code(), 404);
+}
+
+#[tokio::test]
+async fn test_request_limits() {
+    use std::time::Duration;
+
+    let node = Node::new(Blockchain::new([0; 32], 1).unwrap()).with_request_limits(1024, Duration::from_millis(200));
+    let blockchain = node.blockchain.clone();
+    let server = TestServer::new(build_api_router(Arc::new(node))).unwrap();
+
+    // An oversized body is refused before it is parsed
+    let response = server
+        .post("/api/transaction")
+        .json(&serde_json::json!({ "padding": "x".repeat(4096) }))
+        .expect_failure()
+        .await;
+    assert_eq!(response.status_code(), 413);
+    let json: Value = response.json();
+    assert_eq!(json["error"], "Request body exceeds the limit of 1024 bytes");
+
+    // A request stuck behind the chain lock times out
+    let guard = blockchain.write().await;
+    let response = server.get("/api/blockchain/height").expect_failure().await;
+    assert_eq!(response.status_code(), 408);
+    let json: Value = response.json();
+    assert_eq!(json["error"], "Request timed out");
+    drop(guard);
+    assert_eq!(server.get("/api/blockchain/height").await.status_code(), 200);
+}
+
+#[tokio::test]
+async fn test_raw_block_and_transaction_endpoints() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::transaction::CoinbaseTx;
+
+    let chain = Blockchain::new([7; 32], 0).unwrap();
+    let genesis = chain.blocks[0].clone();
+    let node = Arc::new(Node::new(chain));
+    let server = TestServer::new(build_api_router(node.clone())).unwrap();
+
+    let json: Value = server.get(&format!("/api/block/{}/raw", hex::encode(genesis.hash()))).await.json();
+    let raw = hex::decode(json["hex"].as_str().unwrap()).unwrap();
+    assert_eq!(bincode::deserialize::<Block>(&raw).unwrap().hash(), genesis.hash());
+    let coinbase = &genesis.transactions[0];
+    let json: Value = server.get(&format!("/api/transaction/{}/raw", coinbase.hash_str())).await.json();
+    assert_eq!(json["hex"], hex::encode(bincode::serialize(coinbase).unwrap()));
+    let unknown = server.get(&format!("/api/block/{}/raw", "ab".repeat(32))).expect_failure().await;
+    assert_eq!(unknown.status_code(), 404);
+
+    // Submit an externally mined block
+    let coinbase = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address: [7; 32],
+        nonce: 1,
+        height: 1,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let block = trinitychain::miner::mine_block(Block::new(1, genesis.hash(), 0, vec![coinbase])).unwrap();
+    let body = serde_json::json!({ "hex": hex::encode(bincode::serialize(&block).unwrap()) });
+    let json: Value = server.post("/api/block/raw").json(&body).await.json();
+    assert_eq!(json["height"], 1);
+    assert_eq!(json["hash"], hex::encode(block.hash()));
+    assert_eq!(node.blockchain.read().await.blocks.len(), 2);
+
+    // It no longer extends the tip, and garbage is refused
+    assert_eq!(server.post("/api/block/raw").json(&body).expect_failure().await.status_code(), 400);
+    let garbage = serde_json::json!({ "hex": "00ff" });
+    assert_eq!(server.post("/api/block/raw").json(&garbage).expect_failure().await.status_code(), 400);
+
+    // A block that fails validation is kept in the rejected-block log
+    let greedy = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1_000_000),
+        beneficiary_address: [7; 32],
+        nonce: 2,
+        height: 2,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let invalid = trinitychain::miner::mine_block(Block::new(2, block.hash(), 0, vec![greedy])).unwrap();
+    let body = serde_json::json!({ "hex": hex::encode(bincode::serialize(&invalid).unwrap()) });
+    assert!(server.post("/api/block/raw").json(&body).expect_failure().await.status_code().is_client_error());
+    let rejected = node.network.rejected_blocks.read().await.list();
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].hash, hex::encode(invalid.hash()));
+    assert_eq!(rejected[0].height, 2);
+    assert_eq!(rejected[0].peer, None);
+}
+
+#[tokio::test]
+async fn test_block_by_hash_and_header_endpoints() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::transaction::CoinbaseTx;
+
+    let mut chain = Blockchain::new([7; 32], 0).unwrap();
+    let coinbase = Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address: [7; 32],
+        nonce: 1,
+        height: 1,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    });
+    let block = trinitychain::miner::mine_block(Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase])).unwrap();
+    chain.apply_block(block.clone()).unwrap();
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    let hash = hex::encode(block.hash());
+    let json: Value = server.get(&format!("/api/blockchain/block/hash/{}", hash)).await.json();
+    assert_eq!(json["header"]["height"], 1);
+    let unknown = server.get(&format!("/api/blockchain/block/hash/{}", "ab".repeat(32))).expect_failure().await;
+    assert_eq!(unknown.status_code(), 404);
+
+    // Headers by height and by hash agree and carry no transactions
+    let by_height: Value = server.get("/api/blockchain/header/1").await.json();
+    let by_hash: Value = server.get(&format!("/api/blockchain/header/{}", hash)).await.json();
+    assert_eq!(by_height, by_hash);
+    assert_eq!(by_height["hash"], hash);
+    assert_eq!(by_height["transaction_count"], 1);
+    assert!(by_height.get("transactions").is_none());
+
+    // Following previous_hash leads back to genesis
+    let previous = hex::encode(
+        by_height["previous_hash"].as_array().unwrap().iter().map(|b| b.as_u64().unwrap() as u8).collect::<Vec<_>>(),
+    );
+    let genesis: Value = server.get(&format!("/api/blockchain/header/{}", previous)).await.json();
+    assert_eq!(genesis["height"], 0);
+
+    assert_eq!(server.get("/api/blockchain/header/9").expect_failure().await.status_code(), 404);
+    assert_eq!(server.get("/api/blockchain/header/tip").expect_failure().await.status_code(), 400);
+}
+
+#[tokio::test]
+async fn test_chain_tips_endpoint() {
+    let chain = Blockchain::new([7; 32], 0).unwrap();
+    let genesis = hex::encode(chain.blocks[0].hash());
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    let json: Value = server.get("/api/blockchain/tips").await.json();
+    assert_eq!(
+        json,
+        serde_json::json!([{ "height": 0, "hash": genesis, "branch_len": 0, "status": "active" }])
+    );
+}
+
+#[tokio::test]
+async fn test_time_range_queries() {
+    use trinitychain::blockchain::Block;
+    use trinitychain::transaction::CoinbaseTx;
+
+    let alice = [4u8; 32];
+    let mut chain = Blockchain::new(alice, 0).unwrap();
+    // 2026-03-05 and 2026-04-02, UTC
+    for (height, timestamp) in [(1u64, 1_772_668_800_000u64), (2, 1_775_088_000_000)] {
+        let coinbase = Transaction::Coinbase(CoinbaseTx {
+            reward_area: Coord::from_num(1),
+            beneficiary_address: alice,
+            nonce: height,
+            height,
+            fees: Coord::from_num(0),
+            extra_nonce: Vec::new(),
+            outputs: Vec::new(),
+        });
+        let mut block = Block::new(height, chain.blocks.last().unwrap().hash(), 0, vec![coinbase]);
+        block.header.timestamp = timestamp;
+        chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    }
+    let server = TestServer::new(build_api_router(Arc::new(Node::new(chain)))).unwrap();
+
+    let march = |request: axum_test::TestRequest| {
+        request.add_query_param("from", "2026-03-01").add_query_param("to", "2026-03-31")
+    };
+    let json: Value = march(server.get("/api/blockchain/blocks")).await.json();
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["blocks"][0]["index"], 1);
+    let json: Value = server.get("/api/blockchain/blocks").add_query_param("from", "2026-03-01").await.json();
+    assert_eq!(json["total"], 2);
+    assert_eq!(json["blocks"][0]["index"], 2);
+
+    let history = format!("/api/address/{}/transactions", hex::encode(alice));
+    let json: Value = server.get(&history).await.json();
+    assert_eq!(json["count"], 3);
+    let json: Value = march(server.get(&history)).await.json();
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["transactions"][0]["block_height"], 1);
+
+    // Difficulty history comes from the stored per-block metrics
+    let json: Value = server.get("/api/analytics/difficulty").await.json();
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["points"][0]["solve_time_ms"], 0);
+    let json: Value = server.get("/api/analytics/difficulty").add_query_param("from", "2026-04-01").await.json();
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["points"][0]["height"], 2);
+    assert_eq!(json["points"][0]["difficulty"], 0);
+    // 2026-03-05 to 2026-04-02
+    assert_eq!(json["average_solve_time_ms"], 28u64 * 24 * 3600 * 1000);
+
+    let response = server
+        .get("/api/blockchain/blocks")
+        .add_query_param("from", "March")
+        .expect_failure()
+        .await;
+    assert_eq!(response.status_code(), 400);
+}
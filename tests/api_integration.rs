@@ -0,0 +1,1163 @@
+//! End-to-end tests of the API router
+//!
+//! Requests go straight through `build_api_router` with
+//! `tower::ServiceExt::oneshot`, so every route is exercised, success and
+//! error paths alike, without binding a socket. Each test builds its own
+//! node on a chain kept in `InMemoryPersistence`.
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Method, Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tower::ServiceExt;
+use trinitychain::api::{build_api_router, build_api_router_for, ApiProfile, Node, TransactionView};
+use trinitychain::blockchain::{BalanceProof, Block, Blockchain, UtxoTree};
+use trinitychain::checkpoint::{Checkpoint, SignedCheckpoint};
+use trinitychain::crypto::{Address, KeyPair};
+use trinitychain::geometry::{Coord, Point, Triangle};
+use trinitychain::network::NetworkNode;
+use trinitychain::node::NodeState;
+use trinitychain::peerstats::PeerStatsSample;
+use trinitychain::persistence::InMemoryPersistence;
+use trinitychain::transaction::{CoinbaseTx, DataTx, SubdivisionTx, Transaction, TransferTx};
+use trinitychain::watchlist::Notifier;
+
+const ADMIN_TOKEN: &str = "s3cret";
+
+/// A two-block chain: Alice mines genesis, then sends its triangle to Bob
+struct Fixture {
+    alice: KeyPair,
+    bob: Address,
+    genesis_utxo: [u8; 32],
+    transfer: Transaction,
+    chain: Blockchain,
+}
+
+fn fixture() -> Fixture {
+    let alice = KeyPair::generate().unwrap();
+    let bob = [5u8; 32];
+    let mut chain =
+        Blockchain::new_with_persistence(alice.address(), 0, Box::new(InMemoryPersistence::new())).unwrap();
+    chain.enable_archive().unwrap();
+    let genesis_utxo = chain.blocks[0].transactions[0].hash();
+
+    let mut transfer = TransferTx::new(genesis_utxo, bob, alice.address(), Coord::from_num(10), Coord::from_num(0), 1);
+    let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+    transfer.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+    let transfer = Transaction::Transfer(transfer);
+    let block = Block::new(1, chain.blocks[0].hash(), 0, vec![coinbase(alice.address(), 1), transfer.clone()]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+
+    Fixture {
+        alice,
+        bob,
+        genesis_utxo,
+        transfer,
+        chain,
+    }
+}
+
+fn coinbase(beneficiary_address: Address, height: u64) -> Transaction {
+    Transaction::Coinbase(CoinbaseTx {
+        reward_area: Coord::from_num(1),
+        beneficiary_address,
+        nonce: height,
+        height,
+        fees: Coord::from_num(0),
+        extra_nonce: Vec::new(),
+        outputs: Vec::new(),
+    })
+}
+
+fn router(node: Node) -> Router {
+    build_api_router(Arc::new(node.with_admin_token(Some(ADMIN_TOKEN.to_string()))))
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+fn send(method: Method, uri: &str, body: Value) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+fn post(uri: &str, body: Value) -> Request<Body> {
+    send(Method::POST, uri, body)
+}
+
+fn admin(mut request: Request<Body>) -> Request<Body> {
+    let value = format!("Bearer {}", ADMIN_TOKEN).parse().unwrap();
+    request.headers_mut().insert(header::AUTHORIZATION, value);
+    request
+}
+
+/// Status and JSON body of the answer; bodies that are not JSON, like
+/// axum's own rejections, come back as `null`
+async fn call(app: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+/// Status of a request expected to fail with the usual JSON error body
+async fn error(app: &Router, request: Request<Body>) -> StatusCode {
+    let (status, json) = call(app, request).await;
+    assert!(json["error"].is_string(), "{} without an error message: {}", status, json);
+    assert!(json["code"].is_string(), "{} without an error code: {}", status, json);
+    status
+}
+
+#[tokio::test]
+async fn test_blockchain_routes() {
+    let Fixture { alice, chain, .. } = fixture();
+    let tip = chain.blocks[1].clone();
+    let tip_hash = hex::encode(tip.hash());
+    let app = router(Node::new(chain));
+
+    let (status, json) = call(&app, get("/api/blockchain/height")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json, 2);
+
+    let (_, json) = call(&app, get("/api/blockchain/blocks?page=0&limit=1")).await;
+    assert_eq!(json["total"], 2);
+    assert_eq!(json["blocks"][0]["hash"], tip_hash);
+    let (_, json) = call(&app, get("/api/blockchain/blocks?page=5&limit=10")).await;
+    assert!(json["blocks"].as_array().unwrap().is_empty());
+
+    let (status, json) = call(&app, get("/api/blockchain/block/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["header"]["height"], 1);
+    assert_eq!(error(&app, get("/api/blockchain/block/7")).await, StatusCode::NOT_FOUND);
+    assert_eq!(call(&app, get("/api/blockchain/block/latest")).await.0, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get(&format!("/api/blockchain/block/hash/{}", tip_hash))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["header"]["height"], 1);
+    let unknown = format!("/api/blockchain/block/hash/{}", "ab".repeat(32));
+    assert_eq!(error(&app, get(&unknown)).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/blockchain/block/hash/zz")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get("/api/blockchain/header/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["hash"], tip_hash);
+    assert_eq!(json["transaction_count"], 2);
+    assert_eq!(error(&app, get("/api/blockchain/header/2")).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/blockchain/header/tip")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get("/api/blockchain/stats")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total_blocks"], 2);
+
+    let (_, json) = call(&app, get("/api/blockchain/tips")).await;
+    assert_eq!(json[0]["hash"], tip_hash);
+    assert_eq!(json[0]["status"], "active");
+
+    let (status, json) = call(&app, get("/api/blockchain/deployments")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["height"], 2);
+    assert!(json["deployments"].is_array());
+
+    // Raw blocks round-trip through the wire encoding
+    let (status, json) = call(&app, get(&format!("/api/block/{}/raw", tip_hash))).await;
+    assert_eq!(status, StatusCode::OK);
+    let raw = hex::decode(json["hex"].as_str().unwrap()).unwrap();
+    assert_eq!(bincode::deserialize::<Block>(&raw).unwrap().hash(), tip.hash());
+    let unknown = format!("/api/block/{}/raw", "ab".repeat(32));
+    assert_eq!(error(&app, get(&unknown)).await, StatusCode::NOT_FOUND);
+
+    let next = Block::new(2, tip.hash(), 0, vec![coinbase(alice.address(), 2)]);
+    let next = trinitychain::miner::mine_block(next).unwrap();
+    let body = json!({ "hex": hex::encode(bincode::serialize(&next).unwrap()) });
+    let (status, json) = call(&app, post("/api/block/raw", body.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["height"], 2);
+    assert_eq!(error(&app, post("/api/block/raw", body)).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, post("/api/block/raw", json!({ "hex": "00ff" }))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(call(&app, post("/api/block/raw", json!({}))).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Checkpoints need a configured authority
+    let checkpoint = SignedCheckpoint::new(Checkpoint { height: 1, hash: tip.hash() });
+    let checkpoint = serde_json::to_value(checkpoint).unwrap();
+    assert_eq!(error(&app, post("/api/blockchain/checkpoint", checkpoint)).await, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_transaction_routes() {
+    let Fixture { bob, transfer, chain, .. } = fixture();
+    let app = router(Node::new(chain));
+
+    let (status, json) = call(&app, get(&format!("/api/transaction/{}", transfer.hash_str()))).await;
+    assert_eq!(status, StatusCode::OK);
+    let view = serde_json::from_value::<TransactionView>(json).unwrap();
+    assert_eq!(view.transaction.hash(), transfer.hash());
+    assert_eq!(view.size, bincode::serialize(&transfer).unwrap().len());
+    assert_eq!(view.fee_per_byte, transfer.fee_per_byte());
+    let (status, json) = call(&app, get(&format!("/api/transaction/{}/raw", transfer.hash_str()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["hex"], hex::encode(bincode::serialize(&transfer).unwrap()));
+    let unknown = format!("/api/transaction/{}", "cd".repeat(32));
+    assert_eq!(error(&app, get(&unknown)).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get(&format!("{}/raw", unknown))).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/transaction/nothex")).await, StatusCode::BAD_REQUEST);
+
+    // Submitted transactions wait in the mempool and can be looked up there
+    let pending = Transaction::Transfer(TransferTx::new(
+        transfer.hash(),
+        [6u8; 32],
+        bob,
+        Coord::from_num(4),
+        Coord::from_num(1),
+        1,
+    ));
+    let (status, json) = call(&app, post("/api/transaction", serde_json::to_value(&pending).unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["hash"], pending.hash_str());
+    assert_eq!(json["relayed_to"], 0);
+    assert_eq!(json["queued_for_relay"], true);
+    let (status, _) = call(&app, get(&format!("/api/transaction/{}", pending.hash_str()))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Balances show what the pending transfer moves; Bob's triangle is
+    // spent until it is mined
+    let (_, json) = call(&app, get(&format!("/api/address/{}/balance", hex::encode(bob)))).await;
+    assert_eq!(json["balance"], "10");
+    assert_eq!(json["pending_out"], "5");
+    assert_eq!(json["pending_in"], "0");
+    assert_eq!(json["available"], "0");
+    let (_, json) = call(&app, get(&format!("/api/address/{}/balance", hex::encode([6u8; 32])))).await;
+    assert_eq!(json["pending_in"], "4");
+    let (_, json) = call(&app, get("/api/mempool")).await;
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["transactions"][0]["size"], pending.size());
+    let (_, json) = call(&app, get("/api/mempool/by-fee")).await;
+    assert_eq!(json["entries"][0]["hash"], pending.hash_str());
+    assert_eq!(json["entries"][0]["fee_per_byte"], pending.fee_per_byte());
+
+    let resubmitted = post("/api/transaction", serde_json::to_value(&pending).unwrap());
+    let (status, json) = call(&app, resubmitted).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(json["code"], "duplicate_transaction");
+    assert_eq!(call(&app, post("/api/transaction", json!({ "Transfer": {} }))).await.0, StatusCode::UNPROCESSABLE_ENTITY);
+    let (status, _) = call(&app, post("/api/stats", json!({}))).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+
+    let (_, json) = call(&app, get("/api/stats")).await;
+    assert_eq!(json["transactions_submitted"], 1);
+}
+
+#[tokio::test]
+async fn test_data_routes() {
+    let Fixture { alice, transfer, chain, .. } = fixture();
+    let reward = chain.blocks[1].transactions[0].hash();
+    let chain_id = chain.chain_id();
+    let app = router(Node::new(chain));
+
+    let payload = b"Hello".to_vec();
+    let mut data = DataTx::new(reward, alice.address(), payload.clone(), DataTx::min_fee(payload.len()), 1);
+    let signature = alice.sign(&data.signable_message(&chain_id)).unwrap();
+    data.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+    let data = Transaction::Data(data);
+    let (status, _) = call(&app, post("/api/transaction", serde_json::to_value(&data).unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, json) = call(&app, get(&format!("/api/data/{}", data.hash_str()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["payload"], hex::encode(&payload));
+    assert_eq!(json["payload_len"], 5);
+    assert_eq!(json["pruned"], false);
+    assert!(json["height"].is_null());
+    let not_data = format!("/api/data/{}", transfer.hash_str());
+    assert_eq!(error(&app, get(&not_data)).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, get(&format!("/api/data/{}", "cd".repeat(32)))).await, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_utxo_analytics_route() {
+    let Fixture { chain, .. } = fixture();
+    let utxos = chain.state.utxo_set.len() as u64;
+    let app = router(Node::new(chain));
+
+    let (status, json) = call(&app, get("/api/analytics/utxo")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["height"], 1);
+    assert_eq!(json["count"], utxos);
+    assert_eq!(json["dust_count"], 0);
+    let distribution = json["distribution"].as_array().unwrap();
+    assert!(distribution.last().unwrap()["below"].is_null());
+    let bucketed: u64 = distribution.iter().map(|bucket| bucket["count"].as_u64().unwrap()).sum();
+    assert_eq!(bucketed, utxos);
+    // A point per block, genesis included
+    assert_eq!(json["points"].as_array().unwrap().len(), 2);
+    assert_eq!(json["points"][1]["count"], utxos);
+
+    let (_, json) = call(&app, get("/api/analytics/utxo?to=2000-01-01")).await;
+    assert_eq!(json["points"].as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_network_stats_history_route() {
+    let Fixture { chain, .. } = fixture();
+    let sample = |peer: &str, recorded_at: u64, uptime_ms: u64, bytes_in: u64| PeerStatsSample {
+        peer: peer.to_string(),
+        recorded_at,
+        uptime_ms,
+        bytes_in,
+        ..PeerStatsSample::default()
+    };
+    chain
+        .persistence
+        .save_peer_stats(&[
+            sample("10.0.0.1", 1_000, 300_000, 50),
+            sample("10.0.0.2", 1_000, 100_000, 20),
+            sample("10.0.0.1", 2_000, 300_000, 30),
+        ])
+        .unwrap();
+    let app = router(Node::new(chain));
+
+    let (status, json) = call(&app, get("/api/network/stats/history")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["interval_secs"], 300);
+    let points = json["points"].as_array().unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!((points[0]["peers"].as_u64(), points[0]["bytes_in"].as_u64()), (Some(2), Some(70)));
+    assert_eq!(json["peers"][0]["peer"], "10.0.0.1");
+    assert_eq!(json["peers"][0]["uptime_ms"], 600_000);
+
+    let (_, json) = call(&app, get("/api/network/stats/history?from=1500&peer=10.0.0.1")).await;
+    assert_eq!(json["points"].as_array().unwrap().len(), 1);
+    assert_eq!(json["peers"].as_array().unwrap().len(), 1);
+    assert_eq!(error(&app, get("/api/network/stats/history?from=yesterday")).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_transaction_audit_routes() {
+    let Fixture { alice, bob, transfer, chain, .. } = fixture();
+    let app = router(Node::new(chain));
+    // Bob's triangle and Alice's block reward, so the two do not conflict
+    let pending = |nonce| match nonce {
+        1 => Transaction::Transfer(TransferTx::new(transfer.hash(), [6u8; 32], bob, Coord::from_num(4), Coord::from_num(1), 1)),
+        _ => {
+            let reward = coinbase(alice.address(), 1).hash();
+            Transaction::Transfer(TransferTx::new(reward, [6u8; 32], alice.address(), Coord::from_num(1), Coord::from_num(0), nonce))
+        }
+    };
+
+    let (status, _) = call(&app, post("/api/transaction", serde_json::to_value(pending(1)).unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+    let by_admin = admin(post("/api/transaction", serde_json::to_value(pending(2)).unwrap()));
+    assert_eq!(call(&app, by_admin).await.0, StatusCode::OK);
+    // Refused submissions never reach the log
+    let duplicate = post("/api/transaction", serde_json::to_value(pending(1)).unwrap());
+    assert_eq!(call(&app, duplicate).await.0, StatusCode::CONFLICT);
+
+    assert_eq!(error(&app, get("/api/audit/transactions")).await, StatusCode::UNAUTHORIZED);
+    let (status, json) = call(&app, admin(get("/api/audit/transactions"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 2);
+    // Newest first; oneshot requests carry no client address
+    assert_eq!(json["entries"][0]["source"], "api:admin");
+    assert_eq!(json["entries"][1]["source"], "api:unknown");
+    assert_eq!(json["entries"][1]["txid"], pending(1).hash_str());
+    assert_eq!(json["entries"][1]["raw"], hex::encode(bincode::serialize(&pending(1)).unwrap()));
+
+    let (_, json) = call(&app, admin(get("/api/audit/transactions?source=api:admin"))).await;
+    assert_eq!(json["total"], 1);
+    let (_, json) = call(&app, admin(get(&format!("/api/audit/transactions?txid={}", pending(1).hash_str())))).await;
+    assert_eq!(json["total"], 1);
+    let (_, json) = call(&app, admin(get("/api/audit/transactions?to=2000-01-01"))).await;
+    assert_eq!(json["total"], 0);
+
+    let response = app.clone().oneshot(admin(get("/api/audit/transactions/export"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/csv");
+    assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains("tx_audit.csv"));
+    let csv = String::from_utf8(to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+    assert_eq!(csv.lines().count(), 3);
+    assert!(csv.contains(&pending(2).hash_str()));
+    let response = app.clone().oneshot(admin(get("/api/audit/transactions/export?format=jsonl"))).await.unwrap();
+    let jsonl = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&jsonl).lines().count(), 2);
+    let bad_format = admin(get("/api/audit/transactions/export?format=xml"));
+    assert_eq!(error(&app, bad_format).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_mempool_snapshot_routes() {
+    let Fixture { alice, bob, transfer, chain, .. } = fixture();
+    let reward = coinbase(alice.address(), 1).hash();
+    let mut signed = TransferTx::new(reward, [6u8; 32], alice.address(), Coord::from_num(0.5), Coord::from_num(0), 1);
+    let signature = alice.sign(&signed.signable_message(&chain.chain_id())).unwrap();
+    signed.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+    let signed = Transaction::Transfer(signed);
+    let unsigned = Transaction::Transfer(TransferTx::new(transfer.hash(), [6u8; 32], bob, Coord::from_num(4), Coord::from_num(1), 1));
+
+    let source = router(Node::new(chain.clone()));
+    for tx in [&signed, &unsigned] {
+        assert_eq!(call(&source, post("/api/transaction", serde_json::to_value(tx).unwrap())).await.0, StatusCode::OK);
+    }
+    assert_eq!(error(&source, get("/api/mempool/snapshot")).await, StatusCode::UNAUTHORIZED);
+    let (status, snapshot) = call(&source, admin(get("/api/mempool/snapshot"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(snapshot["tip_height"], 1);
+    assert_eq!(snapshot["entries"].as_array().unwrap().len(), 2);
+
+    // Every entry is validated again on the node loading the snapshot
+    let target = router(Node::new(chain));
+    let (status, report) = call(&target, admin(post("/api/mempool/snapshot", snapshot.clone()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["restored"], json!([signed.hash_str()]));
+    assert_eq!(report["rejected"][0]["txid"], unsigned.hash_str());
+    assert_eq!(call(&target, get("/api/mempool")).await.1["count"], 1);
+
+    let mut unsupported = snapshot;
+    unsupported["version"] = json!(99);
+    let request = admin(post("/api/mempool/snapshot", unsupported));
+    assert_eq!(error(&target, request).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_address_and_triangle_routes() {
+    let Fixture {
+        alice,
+        bob,
+        genesis_utxo,
+        transfer,
+        chain,
+    } = fixture();
+    let commitment = UtxoTree::build(&chain.state).commitment();
+    let app = router(Node::new(chain));
+    let bob_hex = hex::encode(bob);
+
+    let (status, json) = call(&app, get(&format!("/api/address/{}/balance", bob_hex))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["address"], bob_hex);
+    assert_eq!(error(&app, get("/api/address/zz/balance")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get(&format!("/api/address/{}/transactions", bob_hex))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["transactions"][0]["block_height"], 1);
+    assert_eq!(error(&app, get("/api/address/zz/transactions")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get(&format!("/api/address/{}/summary", bob_hex))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total_received"], "10");
+    assert_eq!(json["total_sent"], "0");
+    assert_eq!(json["tx_count"], 1);
+    assert_eq!((json["first_seen_height"].clone(), json["last_active_height"].clone()), (1.into(), 1.into()));
+    let (_, json) = call(&app, get(&format!("/api/address/{}/summary", hex::encode([6u8; 32])))).await;
+    assert_eq!(json["tx_count"], 0);
+    assert!(json["first_seen_height"].is_null());
+    assert_eq!(error(&app, get("/api/address/zz/summary")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get(&format!("/api/address/{}/utxos", bob_hex))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["total"], 1);
+    assert_eq!(json["utxos"][0]["hash"], transfer.hash_str());
+    assert_eq!(error(&app, get("/api/address/zz/utxos")).await, StatusCode::BAD_REQUEST);
+
+    // A light client checks the proof against a commitment it trusts
+    let (status, json) = call(&app, get(&format!("/api/address/{}/proof", bob_hex))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((json["balance"].clone(), json["outputs"].clone()), ("10".into(), 1.into()));
+    let proof: BalanceProof = serde_json::from_value(json["proof"].clone()).unwrap();
+    assert_eq!(json["commitment"], hex::encode(commitment));
+    assert_eq!(proof.verify(&commitment).unwrap(), Coord::from_num(10));
+    assert_eq!(error(&app, get("/api/address/zz/proof")).await, StatusCode::BAD_REQUEST);
+
+    // Signing the transfer revealed Alice's key; Bob has never signed
+    let (status, json) = call(&app, get(&format!("/api/address/{}/pubkey", hex::encode(alice.address())))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["public_key"], hex::encode(alice.public_key_bytes()));
+    assert_eq!(error(&app, get(&format!("/api/address/{}/pubkey", bob_hex))).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/address/zz/pubkey")).await, StatusCode::BAD_REQUEST);
+
+    let qr = app
+        .clone()
+        .oneshot(get(&format!("/api/address/{}/qr.svg?amount=2.5&memo=rent", bob_hex)))
+        .await
+        .unwrap();
+    assert_eq!(qr.status(), StatusCode::OK);
+    assert_eq!(qr.headers()[header::CONTENT_TYPE], "image/svg+xml");
+    let svg = to_bytes(qr.into_body(), usize::MAX).await.unwrap();
+    assert!(svg.starts_with(b"<?xml"));
+    assert_eq!(error(&app, get("/api/address/zz/qr.svg")).await, StatusCode::BAD_REQUEST);
+    let bad_amount = format!("/api/address/{}/qr.svg?amount=lots", bob_hex);
+    assert_eq!(error(&app, get(&bad_amount)).await, StatusCode::BAD_REQUEST);
+
+    let root = hex::encode(genesis_utxo);
+    let (status, json) = call(&app, get(&format!("/api/triangle/{}", root))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["depth"], 0);
+    assert_eq!(error(&app, get(&format!("/api/triangle/{}/2", root))).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/triangle/not-an-id")).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_triangle_tree_route() {
+    let Fixture { alice, mut chain, .. } = fixture();
+    let chain_id = chain.chain_id();
+    let subdivide = |parent: [u8; 32], chain: &Blockchain| {
+        let children = chain.state.utxo_set[&parent].subdivide().to_vec();
+        let mut tx = SubdivisionTx::new(parent, children, alice.address(), Coord::from_num(0), 1);
+        let signature = alice.sign(&tx.signable_message(&chain_id)).unwrap();
+        tx.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+        Transaction::Subdivision(tx)
+    };
+    // Alice splits her block 1 reward, then the middle child of it. Rewards
+    // are minted as points, so give this one an area to split.
+    let reward = chain.blocks[1].transactions[0].hash();
+    let minted = chain.state.utxo_set[&reward].clone();
+    let shaped = Triangle::new(Point::new(0.into(), 0.into()), Point::new(8.into(), 0.into()), Point::new(0.into(), 8.into()), None, alice.address());
+    let shaped = Triangle { value: minted.value, id: minted.id, ..shaped };
+    chain.state.utxo_set.insert(reward, shaped);
+    let first = subdivide(reward, &chain);
+    let block = Block::new(2, chain.blocks[1].hash(), 0, vec![coinbase(alice.address(), 2), first]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    let middle = chain.subdivisions.children(&reward)[1].0;
+    let second = subdivide(middle, &chain);
+    let block = Block::new(3, chain.blocks[2].hash(), 0, vec![coinbase(alice.address(), 3), second]);
+    chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    let app = router(Node::new(chain));
+
+    let (status, json) = call(&app, get(&format!("/api/triangle/{}/tree", hex::encode(reward)))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["depth"], 3);
+    let root = &json["root"];
+    assert_eq!((root["unspent"].clone(), root["subdivided"].clone()), (false.into(), true.into()));
+    assert_eq!(root["owner"], hex::encode(alice.address()));
+    let children = root["children"].as_array().unwrap();
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0]["unspent"], true);
+    assert_eq!(children[1]["hash"], hex::encode(middle));
+    assert_eq!(children[1]["children"].as_array().unwrap().len(), 3);
+
+    // A depth limit cuts the tree; a subdivided child still says so
+    let (_, json) = call(&app, get(&format!("/api/triangle/{}/tree?depth=1", hex::encode(reward)))).await;
+    let middle_node = &json["root"]["children"][1];
+    assert_eq!(middle_node["subdivided"], true);
+    assert!(middle_node["children"].as_array().unwrap().is_empty());
+
+    let (_, json) = call(&app, get(&format!("/api/triangle/{}/tree", hex::encode(middle)))).await;
+    assert_eq!(json["parent"], hex::encode(reward));
+    assert_eq!(json["root"]["value"], children[1]["value"]);
+
+    let too_deep = format!("/api/triangle/{}/tree?depth=7", hex::encode(reward));
+    assert_eq!(error(&app, get(&too_deep)).await, StatusCode::BAD_REQUEST);
+    let unknown = format!("/api/triangle/{}/tree", "ef".repeat(32));
+    assert_eq!(error(&app, get(&unknown)).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/triangle/zz/tree")).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_archive_routes() {
+    let Fixture {
+        genesis_utxo,
+        transfer,
+        chain,
+        ..
+    } = fixture();
+    let mut pruned = chain.clone();
+    pruned.archive = None;
+    let app = router(Node::new(chain));
+    let genesis = hex::encode(genesis_utxo);
+
+    let (status, json) = call(&app, get(&format!("/api/archive/utxo/{}", genesis))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["spent_height"], 1);
+    assert_eq!(json["spending_tx"], transfer.hash_str());
+    let unknown = format!("/api/archive/utxo/{}", "ef".repeat(32));
+    assert_eq!(error(&app, get(&unknown)).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/archive/utxo/xyz")).await, StatusCode::BAD_REQUEST);
+
+    let (status, json) = call(&app, get(&format!("/api/archive/utxo/{}/provenance", transfer.hash_str()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["ancestors"][0]["utxo"], genesis);
+    assert_eq!(error(&app, get(&format!("{}/provenance", unknown))).await, StatusCode::NOT_FOUND);
+
+    let (status, json) = call(&app, get(&format!("/api/archive/triangle/{}?height=0", genesis))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["outputs"].as_array().unwrap().len(), 1);
+    let above_tip = format!("/api/archive/triangle/{}?height=9", genesis);
+    assert_eq!(error(&app, get(&above_tip)).await, StatusCode::BAD_REQUEST);
+    let ownerless = format!("/api/archive/triangle/{}", "ef".repeat(32));
+    assert_eq!(error(&app, get(&ownerless)).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, get("/api/archive/triangle/not-an-id")).await, StatusCode::BAD_REQUEST);
+
+    // Without archive mode every archive route is refused
+    let app = router(Node::new(pruned));
+    for path in [
+        format!("/api/archive/utxo/{}", genesis),
+        format!("/api/archive/utxo/{}/provenance", genesis),
+        format!("/api/archive/triangle/{}", genesis),
+    ] {
+        assert_eq!(error(&app, get(&path)).await, StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn test_mining_routes() {
+    let Fixture { chain, .. } = fixture();
+    let node = Node::new(chain);
+    let blockchain = node.blockchain.clone();
+    let app = router(node);
+
+    let (status, json) = call(&app, get("/api/mining/status")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["is_mining"], false);
+    assert_eq!(json["blocks_mined"], 0);
+    assert_eq!(error(&app, post("/api/mining/stop", json!({}))).await, StatusCode::CONFLICT);
+    assert_eq!(error(&app, post("/api/mining/start", json!({ "miner_address": "" }))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, post("/api/mining/start", json!({ "miner_address": "zz" }))).await, StatusCode::BAD_REQUEST);
+
+    // Throttle changes are partial, and a null schedule clears it
+    let throttle = |body: Value| post("/api/mining/throttle", body);
+    let (status, json) = call(&app, throttle(json!({ "cpu_percent": 40, "pause_hours": "22-07" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json, json!({ "cpu_percent": 40, "pause_hours": "22-07", "pause_on_battery": false }));
+    let (_, json) = call(&app, throttle(json!({ "pause_on_battery": true }))).await;
+    assert_eq!(json["pause_hours"], "22-07");
+    let (_, json) = call(&app, get("/api/mining/status")).await;
+    assert_eq!(json["throttle"]["cpu_percent"], 40);
+    assert_eq!(error(&app, throttle(json!({ "cpu_percent": 0 }))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, throttle(json!({ "pause_hours": "8 to 5" }))).await, StatusCode::BAD_REQUEST);
+    let (_, json) = call(&app, throttle(json!({ "cpu_percent": 100, "pause_hours": null, "pause_on_battery": false }))).await;
+    assert_eq!(json, json!({ "cpu_percent": 100, "pause_hours": null, "pause_on_battery": false }));
+    let (_, json) = call(&app, get("/api/mining/status")).await;
+    assert_eq!(json["paused"], Value::Null);
+
+    let start = || post("/api/mining/start", json!({ "miner_address": hex::encode([8u8; 32]) }));
+    assert_eq!(call(&app, start()).await.0, StatusCode::OK);
+    assert_eq!(error(&app, start()).await, StatusCode::CONFLICT);
+    let (_, json) = call(&app, get("/api/mining/status")).await;
+    assert_eq!(json["is_mining"], true);
+
+    for _ in 0..100 {
+        if blockchain.read().await.blocks.len() > 2 {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(call(&app, post("/api/mining/stop", json!({}))).await.0, StatusCode::OK);
+    let (_, json) = call(&app, get("/api/mining/status")).await;
+    assert_eq!(json["is_mining"], false);
+    assert!(json["blocks_mined"].as_u64().unwrap() >= 1);
+    assert!(blockchain.read().await.blocks.len() > 2);
+}
+
+#[tokio::test]
+async fn test_mining_template_preview_route() {
+    let Fixture { alice, chain, .. } = fixture();
+    let reward = coinbase(alice.address(), 1).hash();
+    let mut transfer = TransferTx::new(reward, [6u8; 32], alice.address(), Coord::from_num(0.5), Coord::from_num(0.25), 1);
+    let signature = alice.sign(&transfer.signable_message(&chain.chain_id())).unwrap();
+    transfer.sign(signature.to_vec(), alice.public_key_bytes().to_vec());
+    let transfer = Transaction::Transfer(transfer);
+    let node = Node::new(chain);
+    let blockchain = node.blockchain.clone();
+    let app = router(node);
+    assert_eq!(call(&app, post("/api/transaction", serde_json::to_value(&transfer).unwrap())).await.0, StatusCode::OK);
+
+    let miner = hex::encode([8u8; 32]);
+    let (status, json) = call(&app, get(&format!("/api/mining/template/preview?address={}", miner))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["height"], 2);
+    assert_eq!(json["previous_hash"], hex::encode(blockchain.read().await.blocks[1].hash()));
+    assert_eq!(json["transactions"][0]["hash"], transfer.hash_str());
+    assert_eq!(json["transactions"][0]["local"], false);
+    assert_eq!(json["excluded"], 0);
+    assert_eq!(json["total_fees"], "0.25");
+    assert!(json["size"].as_u64().unwrap() > 0);
+
+    // Nothing was mined or changed
+    assert_eq!(blockchain.read().await.blocks.len(), 2);
+    assert_eq!(blockchain.read().await.mempool.len(), 1);
+    let (_, without_address) = call(&app, get("/api/mining/template/preview")).await;
+    assert_eq!(without_address["reward"], json["reward"]);
+    assert_eq!(error(&app, get("/api/mining/template/preview?address=zz")).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_network_and_system_routes() {
+    let Fixture { chain, .. } = fixture();
+    let app = router(Node::new(chain.clone()));
+
+    let (status, json) = call(&app, get("/api/health")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["status"], "healthy");
+    let (status, json) = call(&app, get("/api/version")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["protocol_version"], trinitychain::network::PROTOCOL_VERSION);
+    assert!(json["features"].as_array().unwrap().contains(&json!("api")));
+
+    let (status, json) = call(&app, get("/api/network/peers")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    let (status, json) = call(&app, get("/api/network/info")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["chain_id"], hex::encode(chain.blocks[0].hash()));
+    let (status, json) = call(&app, get("/api/sync/status")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["state"], "idle");
+    let (status, json) = call(&app, get("/api/telemetry/preview")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["enabled"], false);
+    assert_eq!(call(&app, get("/api/no/such/route")).await.0, StatusCode::NOT_FOUND);
+
+    let (status, json) = call(&app, get("/api/stats")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["total_requests"].as_u64().unwrap() >= 5);
+
+    let (status, json) = call(&app, get("/api/ready")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["ready"], true);
+
+    // A node that is still syncing is alive but not ready, as is one
+    // shutting down
+    let blockchain = Arc::new(RwLock::new(chain));
+    let network = Arc::new(NetworkNode::new(blockchain.clone()));
+    let state = Arc::new(RwLock::new(NodeState::Syncing));
+    let app = router(Node::new_shared(blockchain, network, Some(state.clone())));
+    let (status, json) = call(&app, get("/api/health")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((json["status"].as_str(), json["node_state"].as_str()), (Some("healthy"), Some("Syncing")));
+    let (status, json) = call(&app, get("/api/ready")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["ready"], false);
+
+    *state.write().await = NodeState::Ready;
+    assert_eq!(call(&app, get("/api/ready")).await.0, StatusCode::OK);
+    *state.write().await = NodeState::Stopping;
+    assert_eq!(call(&app, get("/api/ready")).await.0, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_debug_state_routes() {
+    let Fixture { alice, bob, mut chain, .. } = fixture();
+    let tip = chain.revert_block().unwrap();
+    chain.apply_block(tip.clone()).unwrap();
+    // Drift the node can only get into through a bug
+    chain.state.address_balances.insert(bob, Coord::from_num(99));
+    let orphan = TransferTx::new([7u8; 32], bob, alice.address(), Coord::from_num(1), Coord::from_num(0), 1);
+    chain.mempool.add_transaction(Transaction::Transfer(orphan.clone())).unwrap();
+    let utxo_count = chain.state.utxo_set.len();
+    let app = router(Node::new(chain));
+
+    assert_eq!(error(&app, get("/api/debug/state")).await, StatusCode::UNAUTHORIZED);
+    let (status, json) = call(&app, admin(get("/api/debug/state"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["tip_height"], 1);
+    assert_eq!(json["utxo_count"], utxo_count);
+    assert_eq!(json["balance_mismatches"], 1);
+    assert_eq!(json["mempool_conflicts"], 1);
+    assert_eq!(json["last_block_event"]["event"], "applied");
+
+    let (_, json) = call(&app, admin(get("/api/debug/state/utxos?limit=1"))).await;
+    assert_eq!(json["total"], utxo_count);
+    assert_eq!(json["utxos"].as_array().unwrap().len(), 1);
+
+    let (_, json) = call(&app, admin(get("/api/debug/state/balances"))).await;
+    assert_eq!(json["consistent"], false);
+    assert_eq!(json["mismatches"][0]["address"], hex::encode(bob));
+    assert_eq!(json["mismatches"][0]["stored"], "99");
+    assert_eq!(json["mismatches"][0]["computed"], "10");
+
+    let (_, json) = call(&app, admin(get("/api/debug/state/mempool"))).await;
+    assert_eq!(json["conflicts"][0]["hash"], Transaction::Transfer(orphan).hash_str());
+    assert_eq!(json["conflicts"][0]["kind"], "missing_input");
+
+    let (_, json) = call(&app, admin(get("/api/debug/state/blocks"))).await;
+    assert_eq!(json["total"], 4);
+    let events: Vec<_> = json["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["event"].as_str().unwrap().to_string(), e["height"].as_u64().unwrap()))
+        .collect();
+    let expected = [("applied", 1), ("reverted", 1), ("applied", 1), ("applied", 0)];
+    assert_eq!(events, expected.map(|(event, height)| (event.to_string(), height)));
+    assert_eq!(json["events"][1]["hash"], hex::encode(tip.hash()));
+
+    let (_, json) = call(&app, admin(get("/api/debug/memory"))).await;
+    assert_eq!(json["mempool"]["transactions"], 1);
+    assert_eq!(json["mempool"]["max_transactions"], 10_000);
+    assert!(json["mempool"]["bytes"].as_u64().unwrap() > 0);
+    assert_eq!(json["sync_buffer"]["blocks"], 0);
+    assert_eq!(json["known_invalid"]["max"], 5_000);
+}
+
+#[tokio::test]
+async fn test_admin_routes() {
+    let Fixture { chain, .. } = fixture();
+    let disabled = build_api_router(Arc::new(Node::new(chain.clone())));
+    assert_eq!(error(&disabled, get("/api/network/bans")).await, StatusCode::FORBIDDEN);
+
+    let app = router(Node::new(chain));
+    assert_eq!(error(&app, get("/api/network/bans")).await, StatusCode::UNAUTHORIZED);
+
+    let (status, json) = call(&app, admin(get("/api/network/bans"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    let (status, json) = call(&app, admin(get("/api/network/addnodes"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    let (status, json) = call(&app, admin(get("/api/debug/rejected-blocks"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    assert!(json["blocks"].as_array().unwrap().is_empty());
+
+    let ban = admin(post("/api/network/ban", json!({ "address": "203.0.113.9", "duration_secs": 60 })));
+    assert_eq!(call(&app, ban).await.0, StatusCode::OK);
+    let unban = || admin(post("/api/network/unban", json!({ "address": "203.0.113.9" })));
+    assert_eq!(call(&app, unban()).await.0, StatusCode::OK);
+    assert_eq!(error(&app, unban()).await, StatusCode::NOT_FOUND);
+
+    let bad_peer = admin(post("/api/network/peers", json!({ "address": "no-port" })));
+    assert_eq!(error(&app, bad_peer).await, StatusCode::BAD_REQUEST);
+    let unknown_peer = admin(send(Method::DELETE, "/api/network/peers/203.0.113.9:8333", Value::Null));
+    assert_eq!(error(&app, unknown_peer).await, StatusCode::NOT_FOUND);
+
+    let missing = "ab".repeat(32);
+    for (method, path) in [
+        (Method::POST, format!("/api/mempool/{}/prioritize", missing)),
+        (Method::DELETE, format!("/api/mempool/{}/prioritize", missing)),
+        (Method::DELETE, format!("/api/mempool/{}", missing)),
+    ] {
+        assert_eq!(error(&app, admin(send(method, &path, Value::Null))).await, StatusCode::NOT_FOUND);
+    }
+    let bad_hash = admin(send(Method::DELETE, "/api/mempool/nothex", Value::Null));
+    assert_eq!(error(&app, bad_hash).await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_public_profile_is_read_only() {
+    let Fixture { chain, transfer, .. } = fixture();
+    let node = Arc::new(Node::new(chain).with_admin_token(Some(ADMIN_TOKEN.to_string())));
+    let app = build_api_router_for(node, ApiProfile::Public);
+
+    for path in ["/api/blockchain/height", "/api/mempool", "/api/network/peers", "/api/health"] {
+        assert_eq!(call(&app, get(path)).await.0, StatusCode::OK, "{}", path);
+    }
+    // Submissions stay open
+    assert_eq!(call(&app, post("/api/transaction", json!(transfer))).await.0, StatusCode::OK);
+
+    // Unrouted POSTs fall through to the static dashboard, which only serves GET
+    for path in ["/api/mining/start", "/api/mining/stop", "/api/mining/throttle", "/api/wallet/create", "/api/wallet/sign"] {
+        assert_eq!(call(&app, post(path, json!({}))).await.0, StatusCode::METHOD_NOT_ALLOWED, "{}", path);
+    }
+    for path in ["/api/network/bans", "/api/debug/rejected-blocks", "/api/debug/state", "/api/telemetry/preview"] {
+        assert_eq!(call(&app, admin(get(path))).await.0, StatusCode::NOT_FOUND, "{}", path);
+    }
+    let add_peer = admin(post("/api/network/peers", json!({ "address": "203.0.113.9:8333" })));
+    assert_eq!(call(&app, add_peer).await.0, StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn test_watchlist_routes() {
+    use futures_util::StreamExt;
+
+    let Fixture { alice, bob, chain, .. } = fixture();
+    let notifier = Arc::new(Notifier::default());
+    let node = Node::new(chain).with_notifier(notifier.clone());
+    let blockchain = node.blockchain.clone();
+    let app = router(node);
+
+    let watch = json!({ "address": hex::encode(bob), "webhook": "http://127.0.0.1:9/hook", "depths": [3] });
+    assert_eq!(error(&app, post("/api/watchlist/watch", watch.clone())).await, StatusCode::UNAUTHORIZED);
+    let (status, json) = call(&app, admin(post("/api/watchlist/watch", watch.clone()))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["depths"], json!([3]));
+    let https = json!({ "address": hex::encode(bob), "webhook": "https://example.org/hook" });
+    assert_eq!(error(&app, admin(post("/api/watchlist/watch", https))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(call(&app, admin(get("/api/watchlist"))).await.1["count"], 1);
+    assert_eq!(call(&app, admin(post("/api/watchlist/unwatch", watch.clone()))).await.0, StatusCode::OK);
+    assert_eq!(error(&app, admin(post("/api/watchlist/unwatch", watch))).await, StatusCode::NOT_FOUND);
+
+    assert_eq!(error(&app, get("/api/watchlist/events?addresses=nothex")).await, StatusCode::BAD_REQUEST);
+    let uri = format!("/api/watchlist/events?addresses={}&depths=2", hex::encode(alice.address()));
+    let response = app.clone().oneshot(get(&uri)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+
+    // Mine a reward to Alice after the first poll has marked the tip
+    notifier.poll(&*blockchain.read().await);
+    {
+        let mut chain = blockchain.write().await;
+        let block = Block::new(2, chain.blocks[1].hash(), 0, vec![coinbase(alice.address(), 2)]);
+        chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+    }
+    notifier.poll(&*blockchain.read().await);
+
+    let mut body = response.into_body().into_data_stream();
+    let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), body.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let event = String::from_utf8(chunk.to_vec()).unwrap();
+    assert!(event.starts_with("event: transfer"), "{}", event);
+    assert!(event.contains("\"direction\":\"incoming\""), "{}", event);
+
+    // Closing the stream forgets the client's watches
+    drop(body);
+    assert!(notifier.watches().is_empty());
+}
+
+#[tokio::test]
+async fn test_address_proof_follows_the_tip_and_is_rate_limited() {
+    let Fixture { alice, bob, chain, .. } = fixture();
+    let node = Node::new(chain);
+    let blockchain = node.blockchain.clone();
+    let app = router(node);
+    let proof_of = |query: &str| get(&format!("/api/address/{}/proof{}", hex::encode(bob), query));
+
+    let (status, json) = call(&app, proof_of("?height=1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["height"], 1);
+    assert_eq!(error(&app, proof_of("?height=0")).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, proof_of("?height=2")).await, StatusCode::NOT_FOUND);
+
+    // A new tip gets a new tree
+    let commitment = {
+        let mut chain = blockchain.write().await;
+        let block = Block::new(2, chain.blocks[1].hash(), 0, vec![coinbase(alice.address(), 2)]);
+        chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+        UtxoTree::build(&chain.state).commitment()
+    };
+    let (_, json) = call(&app, proof_of("")).await;
+    assert_eq!((json["height"].clone(), json["commitment"].clone()), (2.into(), hex::encode(commitment).into()));
+
+    // Four requests so far; the rest of the client's allowance runs out
+    for _ in 4..100 {
+        assert_eq!(call(&app, proof_of("")).await.0, StatusCode::OK);
+    }
+    assert_eq!(error(&app, proof_of("")).await, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_wallet_routes() {
+    let Fixture { chain, .. } = fixture();
+    let app = router(Node::new(chain));
+    let passphrase = "correct horse battery";
+
+    assert_eq!(error(&app, post("/api/wallet/create", json!({ "passphrase": "short" }))).await, StatusCode::BAD_REQUEST);
+    let (status, json) = call(&app, post("/api/wallet/create", json!({ "passphrase": passphrase }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let address = json["address"].as_str().unwrap().to_string();
+
+    let unlock = |body: Value| post("/api/wallet/unlock", body);
+    assert_eq!(error(&app, unlock(json!({ "passphrase": passphrase }))).await, StatusCode::BAD_REQUEST);
+    let stranger = json!({ "address": hex::encode([9u8; 32]), "passphrase": passphrase });
+    assert_eq!(error(&app, unlock(stranger)).await, StatusCode::NOT_FOUND);
+    let wrong = json!({ "address": address, "passphrase": "wrong passphrase" });
+    assert_eq!(error(&app, unlock(wrong)).await, StatusCode::UNAUTHORIZED);
+    let (status, json) = call(&app, unlock(json!({ "address": address, "passphrase": passphrase }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = json["session_token"].as_str().unwrap().to_string();
+
+    let sender = trinitychain::crypto::address_from_hex(&address).unwrap();
+    let transfer = Transaction::Transfer(TransferTx::new(
+        [1u8; 32],
+        [2u8; 32],
+        sender,
+        Coord::from_num(1),
+        Coord::from_num(0),
+        0,
+    ));
+    let sign = |token: &str, transaction: &Transaction| {
+        post("/api/wallet/sign", json!({ "session_token": token, "transaction": transaction }))
+    };
+    let (status, json) = call(&app, sign(&token, &transfer)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["submitted"], false);
+    assert_eq!(error(&app, sign(&token, &coinbase(sender, 3))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, sign("no-such-session", &transfer)).await, StatusCode::UNAUTHORIZED);
+
+    let message = "link exchange account 7";
+    let (status, json) = call(
+        &app,
+        post("/api/wallet/sign-message", json!({ "session_token": token, "message": message })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["address"], address.as_str());
+    let signature = json["signature"].as_str().unwrap().to_string();
+    let verify = |address: &str, message: &str| {
+        post(
+            "/api/wallet/verify-message",
+            json!({ "address": address, "message": message, "signature": signature }),
+        )
+    };
+    assert_eq!(call(&app, verify(&address, message)).await.1["valid"], true);
+    assert_eq!(call(&app, verify(&address, "something else")).await.1["valid"], false);
+    assert_eq!(call(&app, verify(&hex::encode([9u8; 32]), message)).await.1["valid"], false);
+    assert_eq!(error(&app, verify("nothex", message)).await, StatusCode::BAD_REQUEST);
+
+    let lock = || post("/api/wallet/lock", json!({ "session_token": token }));
+    assert_eq!(call(&app, lock()).await.0, StatusCode::OK);
+    assert_eq!(error(&app, lock()).await, StatusCode::NOT_FOUND);
+    assert_eq!(error(&app, sign(&token, &transfer)).await, StatusCode::UNAUTHORIZED);
+
+    // The operator sees sessions without their tokens and can revoke them
+    let (_, json) = call(&app, unlock(json!({ "address": address, "passphrase": passphrase, "ttl_secs": 86400 }))).await;
+    assert_eq!(json["expires_in_secs"], 3600);
+    let token = json["session_token"].as_str().unwrap().to_string();
+    assert_eq!(error(&app, get("/api/wallet/sessions")).await, StatusCode::UNAUTHORIZED);
+    let (status, json) = call(&app, admin(get("/api/wallet/sessions"))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json[0]["address"], address.as_str());
+    assert!(json[0].get("session_token").is_none());
+    let revoke = |query: &str| admin(send(Method::DELETE, &format!("/api/wallet/sessions{}", query), Value::Null));
+    let stranger = format!("?address={}", hex::encode([9u8; 32]));
+    assert_eq!(call(&app, revoke(&stranger)).await.1["revoked"], 0);
+    assert_eq!(call(&app, revoke(&format!("?address={}", address))).await.1["revoked"], 1);
+    assert_eq!(error(&app, sign(&token, &transfer)).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(call(&app, revoke("")).await.1["revoked"], 0);
+}
+
+#[tokio::test]
+async fn test_wallet_unlock_stays_inside_the_keystore() {
+    let dir = tempfile::tempdir().unwrap();
+    let keystore = dir.path().join("keystore");
+    let passphrase = "correct horse battery";
+    let Fixture { chain, .. } = fixture();
+    let app = router(Node::new(chain).with_keystore_dir(keystore.clone()));
+    let (_, json) = call(&app, post("/api/wallet/create", json!({ "passphrase": passphrase }))).await;
+    let address = json["address"].as_str().unwrap().to_string();
+
+    // An unlockable entry outside the keystore directory is out of reach
+    std::fs::copy(keystore.join(format!("{}.json", address)), dir.path().join("outside.json")).unwrap();
+    let Fixture { chain, .. } = fixture();
+    let app = router(Node::new(chain).with_keystore_dir(keystore));
+    let unlock = |address: &str| post("/api/wallet/unlock", json!({ "address": address, "passphrase": passphrase }));
+    for traversal in ["../outside", "../../outside", "/etc/passwd", &format!("{}/../../outside", address)] {
+        assert_eq!(error(&app, unlock(traversal)).await, StatusCode::BAD_REQUEST, "{}", traversal);
+    }
+
+    // A backup blob cannot pick its own Argon2 cost
+    let backup: Value = serde_json::from_str(&std::fs::read_to_string(dir.path().join("outside.json")).unwrap()).unwrap();
+    let mut inflated = backup.clone();
+    inflated["password_hash"] = json!(backup["password_hash"].as_str().unwrap().replacen("m=19456", "m=4194304", 1));
+    let started = std::time::Instant::now();
+    let request = post("/api/wallet/unlock", json!({ "encrypted_backup": inflated, "passphrase": passphrase }));
+    assert_eq!(error(&app, request).await, StatusCode::UNAUTHORIZED);
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+    // Entries inside it still load from disk, whatever the hex case
+    let (status, json) = call(&app, unlock(&address.to_uppercase())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["address"], address.as_str());
+}
+
+#[tokio::test]
+async fn test_wallet_session_lifetime_is_configurable() {
+    let Fixture { chain, .. } = fixture();
+    let node = Node::new(chain).with_session_ttl(std::time::Duration::from_secs(60), std::time::Duration::from_secs(120));
+    let app = router(node);
+    let passphrase = "correct horse battery";
+    let (_, json) = call(&app, post("/api/wallet/create", json!({ "passphrase": passphrase }))).await;
+    let address = json["address"].as_str().unwrap().to_string();
+
+    let unlock = |ttl: Value| post("/api/wallet/unlock", json!({ "address": address, "passphrase": passphrase, "ttl_secs": ttl }));
+    assert_eq!(call(&app, unlock(Value::Null)).await.1["expires_in_secs"], 60);
+    assert_eq!(call(&app, unlock(json!(3600))).await.1["expires_in_secs"], 120);
+}
+
+#[tokio::test]
+async fn test_wallet_bump_fee_route() {
+    let Fixture { chain, .. } = fixture();
+    let node = Node::new(chain);
+    let blockchain = node.blockchain.clone();
+    let app = router(node);
+    let passphrase = "correct horse battery";
+    let (_, json) = call(&app, post("/api/wallet/create", json!({ "passphrase": passphrase }))).await;
+    let address = json["address"].as_str().unwrap().to_string();
+    let (_, json) = call(&app, post("/api/wallet/unlock", json!({ "address": address, "passphrase": passphrase }))).await;
+    let token = json["session_token"].as_str().unwrap().to_string();
+
+    // The wallet mines a block, then pays from it without a fee
+    let sender = trinitychain::crypto::address_from_hex(&address).unwrap();
+    let reward = {
+        let mut chain = blockchain.write().await;
+        let block = Block::new(2, chain.blocks[1].hash(), 0, vec![coinbase(sender, 2)]);
+        chain.apply_block(trinitychain::miner::mine_block(block).unwrap()).unwrap();
+        chain.blocks[2].transactions[0].hash()
+    };
+    let transfer = TransferTx::new(reward, [2u8; 32], sender, Coord::from_num(0.5), Coord::from_num(0), 0);
+    let body = json!({ "session_token": token, "transaction": Transaction::Transfer(transfer), "submit": true });
+    let (status, json) = call(&app, post("/api/wallet/sign", body)).await;
+    assert_eq!(status, StatusCode::OK);
+    let stuck = json["hash"].as_str().unwrap().to_string();
+
+    let bump = |txid: &str, fee: Value| post("/api/wallet/bump", json!({ "session_token": token, "txid": txid, "fee": fee }));
+    assert_eq!(error(&app, bump(&stuck, json!("0"))).await, StatusCode::BAD_REQUEST);
+    assert_eq!(error(&app, bump(&"ef".repeat(32), Value::Null)).await, StatusCode::NOT_FOUND);
+    let (status, json) = call(&app, bump(&stuck, Value::Null)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["replaced"], stuck.as_str());
+    assert_eq!(json["evicted"], json!([stuck]));
+    let bumped = json["hash"].as_str().unwrap().to_string();
+    assert_ne!(bumped, stuck);
+    {
+        let chain = blockchain.read().await;
+        assert_eq!(chain.mempool.len(), 1);
+        let hash: [u8; 32] = hex::decode(&bumped).unwrap().try_into().unwrap();
+        let pending = chain.mempool.get_transaction(&hash).unwrap();
+        assert!(pending.fee_area() > Coord::from_num(0));
+    }
+
+    // A fee of choice on top, and only while the session is open
+    let (_, json) = call(&app, bump(&bumped, json!("0.25"))).await;
+    assert_eq!(json["fee"], "0.25");
+    let locked = post("/api/wallet/bump", json!({ "session_token": "no-such-session", "txid": json["hash"] }));
+    assert_eq!(error(&app, locked).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_trace_routes() {
+    let Fixture { bob, transfer, chain, .. } = fixture();
+    let pending = Transaction::Transfer(TransferTx::new(
+        transfer.hash(),
+        [6u8; 32],
+        bob,
+        Coord::from_num(4),
+        Coord::from_num(1),
+        1,
+    ));
+
+    // Tracing is off unless configured
+    let app = router(Node::new(chain.clone()));
+    let request = post("/api/transaction?trace=true", serde_json::to_value(&pending).unwrap());
+    assert_eq!(error(&app, request).await, StatusCode::BAD_REQUEST);
+    let report = json!({
+        "trace_id": "00000000000000ab", "tx": pending.hash_str(), "node": "b", "from": "10.0.0.1:8333",
+        "hop": 1, "origin_ms": 1000, "sent_ms": 1000, "received_ms": 1050, "hop_ms": 50, "total_ms": 50,
+        "duplicate": false
+    });
+    assert_eq!(error(&app, post("/api/trace/report", report.clone())).await, StatusCode::NOT_FOUND);
+
+    let blockchain = Arc::new(RwLock::new(chain));
+    let network = NetworkNode::new(blockchain.clone()).with_tx_trace("a".to_string(), None);
+    let app = router(Node::new_shared(blockchain, Arc::new(network), None));
+    let (status, json) = call(&app, post("/api/transaction?trace=true", serde_json::to_value(&pending).unwrap())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["trace_id"].as_str().unwrap().len(), 16);
+    assert_eq!(json["queued_for_relay"], true);
+
+    // Reports of other nodes are collected by trace id
+    assert_eq!(call(&app, post("/api/trace/report", report)).await.0, StatusCode::OK);
+    let (status, json) = call(&app, get("/api/trace/00000000000000AB")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["hops"][0]["node"], "b");
+    assert_eq!(json["hops"][0]["hop_ms"], 50);
+    assert_eq!(error(&app, get("/api/trace/0000000000000001")).await, StatusCode::NOT_FOUND);
+}
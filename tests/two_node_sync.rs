@@ -0,0 +1,150 @@
+//! Two in-process nodes talking over the real P2P protocol
+//!
+//! Both nodes start from the same genesis with their chain in
+//! `InMemoryPersistence` and connect over loopback. One of them mines, and
+//! the other has to end up on the same tip with the same UTXO set, with the
+//! blocks it received persisted, exactly as it would between two machines.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use trinitychain::blockchain::{Block, Blockchain, Sha256Hash};
+use trinitychain::crypto::KeyPair;
+use trinitychain::geometry::Coord;
+use trinitychain::miner::mine_block;
+use trinitychain::network::NetworkNode;
+use trinitychain::persistence::{InMemoryPersistence, Persistence};
+use trinitychain::transaction::{CoinbaseTx, Transaction, TransactionBuilder};
+
+/// Every wait gives up after this long instead of hanging the test run
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+struct TestNode {
+    network: Arc<NetworkNode>,
+    persistence: InMemoryPersistence,
+    port: u16,
+}
+
+impl TestNode {
+    /// A listening node whose genesis pays `miner`
+    async fn spawn(miner: &KeyPair) -> Self {
+        let persistence = InMemoryPersistence::new();
+        let chain = Blockchain::new_with_persistence(miner.address(), 0, Box::new(persistence.clone())).unwrap();
+        let network = Arc::new(NetworkNode::new(Arc::new(RwLock::new(chain))));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(network.clone().serve(listener));
+        TestNode {
+            network,
+            persistence,
+            port,
+        }
+    }
+
+    async fn tip(&self) -> Sha256Hash {
+        self.network.blockchain.read().await.blocks.last().unwrap().hash()
+    }
+
+    /// Mine a block with the mempool on top of the local tip, apply it and
+    /// announce it to peers, like the embedded miner does
+    async fn mine(&self, beneficiary: &KeyPair) -> Block {
+        let block = {
+            let chain = self.network.blockchain.read().await;
+            let tip = chain.blocks.last().unwrap();
+            let height = tip.header.height + 1;
+            let txs = chain.mempool.get_transactions_by_fee(50);
+            let fees = chain.claimable_fees(&txs).unwrap();
+            let mut transactions = vec![Transaction::Coinbase(CoinbaseTx {
+                reward_area: Coord::from_num(1),
+                beneficiary_address: beneficiary.address(),
+                nonce: height,
+                height,
+                fees,
+                extra_nonce: Vec::new(),
+                outputs: Vec::new(),
+            })];
+            transactions.extend(txs);
+            Block::new(height, tip.hash(), 0, transactions).with_version(chain.next_block_version())
+        };
+        let block = mine_block(block).unwrap();
+        self.network.blockchain.write().await.apply_block(block.clone()).unwrap();
+        self.network.broadcast_block(&block).await;
+        block
+    }
+}
+
+/// Poll `condition` until it holds, failing the test after [`TIMEOUT`]
+async fn wait_until<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    tokio::time::timeout(TIMEOUT, async {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("timed out waiting until {}", what));
+}
+
+#[tokio::test]
+async fn test_blocks_mined_on_one_node_reach_the_other() {
+    let alice = KeyPair::generate().unwrap();
+    let miner = KeyPair::generate().unwrap();
+    let a = TestNode::spawn(&alice).await;
+    let b = TestNode::spawn(&alice).await;
+    assert_eq!(a.tip().await, b.tip().await, "both nodes must share a genesis");
+
+    // Blocks mined before the nodes meet are caught up through headers
+    for _ in 0..3 {
+        a.mine(&miner).await;
+    }
+    b.network.clone().connect_peer("127.0.0.1".to_string(), a.port).await.unwrap();
+    wait_until("b caught up with a", || async { a.tip().await == b.tip().await }).await;
+    wait_until("a sees b as a peer", || async { !a.network.list_peers().await.is_empty() }).await;
+
+    // Blocks mined while connected are relayed as they are found
+    for _ in 0..3 {
+        let block = a.mine(&miner).await;
+        wait_until("b accepted the new block", || async { b.tip().await == block.hash() }).await;
+    }
+
+    // A payment submitted to b reaches a's mempool, gets mined there and
+    // comes back to b in a block
+    let (genesis_coinbase, chain_id) = {
+        let chain = b.network.blockchain.read().await;
+        (chain.blocks[0].transactions[0].hash(), chain.chain_id())
+    };
+    let payment = TransactionBuilder::new()
+        .chain(chain_id)
+        .input(genesis_coinbase)
+        .to(miner.address())
+        .amount(Coord::from_num(25))
+        .sign(&alice)
+        .unwrap();
+    b.network.blockchain.write().await.mempool.add_transaction(payment.clone()).unwrap();
+    b.network.broadcast_transaction(&payment).await;
+    wait_until("a received the payment", || async {
+        a.network.blockchain.read().await.mempool.get_transaction(&payment.hash()).is_some()
+    })
+    .await;
+    let block = a.mine(&miner).await;
+    assert!(block.transactions.iter().any(|tx| tx.hash() == payment.hash()));
+    wait_until("b accepted the block with the payment", || async { b.tip().await == block.hash() }).await;
+
+    let chain_a = a.network.blockchain.read().await;
+    let chain_b = b.network.blockchain.read().await;
+    assert_eq!(chain_b.blocks.len(), 8);
+    assert_eq!(chain_a.total_work(), chain_b.total_work());
+    assert_eq!(chain_a.state.utxo_set, chain_b.state.utxo_set);
+    assert!(!chain_b.state.utxo_set.contains_key(&genesis_coinbase));
+    assert!(chain_b.mempool.get_transaction(&payment.hash()).is_none());
+
+    // What b received is what b stored
+    let stored = b.persistence.load_blockchain().unwrap();
+    assert_eq!(stored.blocks.len(), chain_b.blocks.len());
+    assert_eq!(stored.state.utxo_set, chain_b.state.utxo_set);
+    assert!(b.persistence.integrity_check().unwrap().is_ok());
+}
@@ -99,7 +99,7 @@ fn test_blockchain_initialization() -> Result<(), Box<dyn std::error::Error>> {
     let blockchain = Blockchain::new(alice_addr, 4)?;
 
     // Verify blockchain has genesis block
-    assert!(blockchain.blocks.len() > 0);
+    assert!(!blockchain.blocks.is_empty());
     assert_eq!(blockchain.blocks[0].header.height, 0);
 
     Ok(())
@@ -127,6 +127,7 @@ fn test_transfer_transaction_creation() -> Result<(), Box<dyn std::error::Error>
         nonce: 1,
         public_key: None,
         signature: None,
+        sponsor: None,
     };
 
     let tx = Transaction::Transfer(transfer);
@@ -211,6 +212,7 @@ fn test_transaction_fee_calculation() -> Result<(), Box<dyn std::error::Error>>
         nonce: 1,
         public_key: None,
         signature: None,
+        sponsor: None,
     };
 
     let tx = Transaction::Transfer(transfer);
@@ -245,6 +247,7 @@ fn test_alice_to_bob_transaction() -> Result<(), Box<dyn std::error::Error>> {
         nonce: 1,
         public_key: None,
         signature: None,
+        sponsor: None,
     };
 
     let tx = Transaction::Transfer(transfer);